@@ -0,0 +1,167 @@
+// benches/diff.rs
+// collecting the changes of repositories of several sizes, and the exclude patterns on their
+// own; nothing here talks to a provider. Run with `cargo bench --bench diff`.
+//
+// The repositories are built on the first run into $TMPDIR/git-changes-bench-<FIXTURES> and
+// reused after that; changing how they are built means bumping FIXTURES. Each has one commit of
+// N files and an unstaged change to every one of them: "small" changes one line of each file,
+// "large" 100 of its 200 lines. The collect_changes runs exclude the first 5 patterns.
+//
+// Baseline, median of `cargo bench` (release profile, Linux, one core, fixtures already built):
+//
+//   collect_changes/100/small      20.0 ms
+//   collect_changes/100/large      116 ms
+//   collect_changes/1000/small     203 ms
+//   collect_changes/1000/large     1.19 s
+//   collect_changes/10000/small    2.22 s
+//   collect_changes/10000/large    13.1 s
+//   is_excluded_path/0             227 µs
+//   is_excluded_path/5             15.6 ms
+//   is_excluded_path/50            130 ms
+//
+// (is_excluded_path is timed over the 10000 paths of the largest repository.)
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use git2::{IndexAddOption, Repository, Signature};
+use git_changes_rs::diff::{collect_changes, CollectOptions, DiffMode};
+use git_changes_rs::filter::is_excluded_path;
+use std::fs;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+
+// bump when the fixtures below change, so stale ones aren't reused
+const FIXTURES: &str = "1";
+
+const SIZES: [usize; 3] = [100, 1_000, 10_000];
+const LINES: usize = 200;
+
+#[derive(Clone, Copy)]
+enum Patch {
+    Small,
+    Large,
+}
+
+impl Patch {
+    fn name(self) -> &'static str {
+        match self {
+            Patch::Small => "small",
+            Patch::Large => "large",
+        }
+    }
+
+    // the file's text after the change
+    fn apply(self, original: &[String]) -> Vec<String> {
+        original
+            .iter()
+            .enumerate()
+            .map(|(n, line)| match self {
+                Patch::Small if n == LINES / 2 => format!("{} // changed", line),
+                Patch::Large if n % 2 == 0 => format!("{} // rewritten", line),
+                _ => line.clone(),
+            })
+            .collect()
+    }
+}
+
+// spread over directories the way a source tree is, a few dozen files to a directory
+fn file_path(n: usize) -> String {
+    format!("src/module{:03}/file{:05}.rs", n / 40, n)
+}
+
+fn lines(n: usize) -> Vec<String> {
+    (0..LINES)
+        .map(|line| format!("    let value_{}_{} = compute({}, {});", n, line, n, line))
+        .collect()
+}
+
+fn write(root: &Path, path: &str, lines: &[String]) {
+    let path = root.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, lines.join("\n") + "\n").unwrap();
+}
+
+// the repository for `files` and `patch`, built the first time it is asked for
+fn fixture(files: usize, patch: Patch) -> PathBuf {
+    let root = std::env::temp_dir()
+        .join(format!("git-changes-bench-{}", FIXTURES))
+        .join(format!("{}-{}", files, patch.name()));
+    let done = root.join(".git").join("bench-fixture-complete");
+    if done.exists() {
+        return root;
+    }
+
+    let _ = fs::remove_dir_all(&root);
+    let repo = Repository::init(&root).unwrap();
+    for n in 0..files {
+        write(&root, &file_path(n), &lines(n));
+    }
+    write(&root, "Cargo.lock", &["version = 3".to_string()]);
+    let mut index = repo.index().unwrap();
+    index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+    index.write().unwrap();
+    let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+    let signature = Signature::now("Bench", "bench@example.com").unwrap();
+    repo.commit(Some("HEAD"), &signature, &signature, "fixture", &tree, &[])
+        .unwrap();
+
+    for n in 0..files {
+        write(&root, &file_path(n), &patch.apply(&lines(n)));
+    }
+    write(&root, "Cargo.lock", &["version = 4".to_string()]);
+    fs::write(done, "").unwrap();
+    root
+}
+
+// the first `count` of a plausible set of exclude patterns, none matching src/**/*.rs
+fn patterns(count: usize) -> Vec<String> {
+    let common = [
+        "Cargo.lock",
+        "*.log",
+        "target/**",
+        "docs/generated/**",
+        "*.min.js",
+    ];
+    common
+        .iter()
+        .map(|p| p.to_string())
+        .chain((0..).map(|n| format!("vendor/lib{}/**", n)))
+        .take(count)
+        .collect()
+}
+
+fn collect(c: &mut Criterion) {
+    let mut group = c.benchmark_group("collect_changes");
+    group.sample_size(10);
+    for files in SIZES {
+        for patch in [Patch::Small, Patch::Large] {
+            let repo = Repository::open(fixture(files, patch)).unwrap();
+            let options = CollectOptions::new(DiffMode::Auto).exclude(patterns(5));
+            group.throughput(Throughput::Elements(files as u64));
+            group.bench_function(BenchmarkId::new(files.to_string(), patch.name()), |b| {
+                b.iter(|| collect_changes(&repo, black_box(&options)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+fn exclude(c: &mut Criterion) {
+    let paths: Vec<String> = (0..SIZES[2]).map(file_path).collect();
+    let mut group = c.benchmark_group("is_excluded_path");
+    group.throughput(Throughput::Elements(paths.len() as u64));
+    for count in [0, 5, 50] {
+        let patterns = patterns(count);
+        group.bench_function(BenchmarkId::from_parameter(count), |b| {
+            b.iter(|| {
+                paths
+                    .iter()
+                    .filter(|path| is_excluded_path(black_box(path), &patterns))
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, collect, exclude);
+criterion_main!(benches);
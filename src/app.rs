@@ -0,0 +1,108 @@
+// src/app.rs
+// a whole run of the binary once its arguments are parsed: the process-wide settings, then the
+// command they name
+
+use crate::{commands, config, exit, interactivity, interrupt, invocation, logging, provider};
+use crate::{style, timings, GitChangesError, QUIET};
+use anyhow::Result;
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use dotenvy::dotenv;
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::Ordering;
+
+// the run's exit status; an error is printed here, unless it says it was already reported
+pub async fn run(matches: &ArgMatches) -> ExitCode {
+    let generate = matches.subcommand_matches("generate").unwrap_or(matches);
+    let chdir = matches.get_one::<String>("chdir").map(String::as_str);
+    if let Err(e) = invocation::enter(chdir, generate.contains_id("hook")) {
+        eprintln!("Error: {:?}", e);
+        return exit::exit_code(exit::USAGE);
+    }
+    // read from where the run happens, so after -C
+    dotenv().ok();
+    if let Some(model) = matches.get_one::<String>("model") {
+        provider::MODEL.set(model.clone()).ok();
+    }
+    // left at its default, generation.provider from the config decides
+    if matches.value_source("provider") == Some(ValueSource::CommandLine) {
+        if let Some(name) = matches.get_one::<String>("provider") {
+            provider::PROVIDER.set(name.clone()).ok();
+        }
+    }
+    let config_file = matches.get_one::<String>("config").map(PathBuf::from);
+    if let Some(path) = config_file.or_else(|| env::var_os(config::CONFIG_ENV).map(PathBuf::from)) {
+        config::use_file(path);
+    }
+    QUIET.store(matches.get_flag("quiet"), Ordering::Relaxed);
+    interactivity::assume_yes(matches.get_flag("yes"));
+    if matches.get_flag("confirm") {
+        interactivity::confirm_sends();
+    }
+    style::init(matches.get_flag("no_color"), matches.get_flag("ascii"));
+    logging::init(matches.get_count("verbose"), matches.get_flag("timings"));
+    interrupt::install();
+
+    // the command is polled first, so one waiting on the provider reports the interruption
+    // itself (as a --json error, say); anything else it waits on is dropped here. A press it
+    // didn't see, e.g. one during a write, still ends the run with 130
+    let result = tokio::select! {
+        biased;
+        result = command(matches) => match result {
+            Ok(()) if interrupt::pressed() => Err(GitChangesError::Interrupted.into()),
+            result => result,
+        },
+        _ = interrupt::cancelled() => Err(GitChangesError::Interrupted.into()),
+    };
+    timings::print();
+    // printed like returning the error would, with the status from exit.rs instead of always 1
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if e.is::<exit::Status>() => exit::exit_code(exit::code(&e)),
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            exit::exit_code(exit::code(&e))
+        }
+    }
+}
+
+// the status to end with when Ctrl-C can't be waited out: pressed twice, or while a question
+// waits on the terminal, which is put back as it was first
+pub async fn forced() -> i32 {
+    interrupt::forced().await;
+    interrupt::restore_terminal();
+    eprintln!("Error: {}", GitChangesError::Interrupted);
+    exit::INTERRUPTED
+}
+
+async fn command(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        Some(("generate", sub)) => commands::generate::run(sub).await,
+        Some(("models", _)) => commands::models::run().await,
+        Some(("lint", sub)) => commands::lint::run(sub).await,
+        Some(("reword", sub)) => commands::reword::run(sub).await,
+        Some(("split", sub)) => commands::split::run(sub).await,
+        Some(("branch-name", sub)) => commands::branch_name::run(sub).await,
+        Some(("pr", sub)) => commands::pr::run(sub).await,
+        Some(("changelog", sub)) => commands::changelog::run(sub).await,
+        Some(("release-notes", sub)) => commands::release_notes::run(sub).await,
+        Some(("tag", sub)) => commands::tag::run(sub).await,
+        Some(("bump", sub)) => commands::bump::run(sub),
+        Some(("notes", sub)) => commands::notes::run(sub),
+        Some(("stash", sub)) => commands::stash::run(sub).await,
+        Some(("explain", sub)) => commands::explain::run(sub).await,
+        Some(("review", sub)) => commands::review::run(sub).await,
+        Some(("standup", sub)) => commands::standup::run(sub).await,
+        Some(("install-hook", sub)) => commands::hooks::install(sub),
+        Some(("uninstall-hook", sub)) => commands::hooks::uninstall(sub),
+        Some(("completions", sub)) => commands::completions::run(sub),
+        Some(("config", sub)) => commands::config::run(sub),
+        Some(("doctor", sub)) => commands::doctor::run(sub, matches).await,
+        Some(("history", sub)) => commands::journal::run(sub),
+        Some(("generate-man", sub)) => commands::man::run(sub),
+        // no subcommand: the generate flags are on the root, as they were before `generate`
+        _ => commands::generate::run(matches).await,
+    }
+}
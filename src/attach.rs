@@ -0,0 +1,106 @@
+// src/attach.rs
+// auxiliary text files (test output, logs) included in the prompt as supporting material
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::Path;
+
+// per-file cap; larger files keep their beginning and end
+pub const DEFAULT_ATTACHMENT_CAP: usize = 8 * 1024;
+
+/// A text file included in the prompt as supporting material.
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    /// What the prompt calls it, usually its path.
+    pub label: String,
+    /// The text, or its beginning and end when it was too long.
+    pub content: String,
+    /// The size of the file before truncation, in bytes.
+    pub original_len: usize,
+}
+
+impl Attachment {
+    /// Whether `content` is only part of the file.
+    pub fn truncated(&self) -> bool {
+        self.content.len() < self.original_len
+    }
+
+    /// `text` under the name `label`, keeping its beginning and end when it is longer than
+    /// `cap` bytes.
+    pub fn from_text(label: impl Into<String>, text: &str, cap: usize) -> Self {
+        Attachment {
+            label: label.into(),
+            original_len: text.len(),
+            content: truncate_middle(text, cap),
+        }
+    }
+}
+
+pub fn load(path: &Path, cap: usize) -> Result<Attachment> {
+    let bytes =
+        fs::read(path).with_context(|| format!("failed to read attachment {}", path.display()))?;
+
+    let sniff = &bytes[..bytes.len().min(8000)];
+    if sniff.contains(&0) {
+        return Err(anyhow!(
+            "attachment {} looks binary; only text files can be attached",
+            path.display()
+        ));
+    }
+    let text = String::from_utf8(bytes).map_err(|_| {
+        anyhow!(
+            "attachment {} is not valid utf-8; only text files can be attached",
+            path.display()
+        )
+    })?;
+
+    Ok(Attachment::from_text(path.display().to_string(), &text, cap))
+}
+
+// keeps the head and tail of the text, where compiler errors and test summaries usually are
+pub fn truncate_middle(text: &str, cap: usize) -> String {
+    if text.len() <= cap {
+        return text.to_string();
+    }
+
+    let half = cap / 2;
+    let head_end = floor_char_boundary(text, half);
+    let tail_start = ceil_char_boundary(text, text.len() - half);
+    format!(
+        "{}\n[... {} bytes omitted ...]\n{}",
+        &text[..head_end],
+        tail_start - head_end,
+        &text[tail_start..]
+    )
+}
+
+fn floor_char_boundary(text: &str, mut idx: usize) -> usize {
+    while !text.is_char_boundary(idx) {
+        idx -= 1;
+    }
+    idx
+}
+
+fn ceil_char_boundary(text: &str, mut idx: usize) -> usize {
+    while !text.is_char_boundary(idx) {
+        idx += 1;
+    }
+    idx
+}
+
+// the labeled section appended to the user prompt
+pub fn render(attachments: &[Attachment]) -> String {
+    let mut out = String::from("Supporting material (provided for context, not part of the diff):");
+    for attachment in attachments {
+        out.push_str(&format!("\n\n--- {} ---\n", attachment.label));
+        if attachment.truncated() {
+            out.push_str(&format!(
+                "(truncated to {} of {} bytes)\n",
+                attachment.content.len(),
+                attachment.original_len
+            ));
+        }
+        out.push_str(&format!("```\n{}\n```", attachment.content.trim_end()));
+    }
+    out
+}
@@ -0,0 +1,89 @@
+// src/branch.rs
+// branch names for the current changes: prompting, cleanup and creation
+
+use crate::attach;
+use anyhow::{anyhow, Context, Result};
+use git2::{Reference, Repository};
+
+// git allows far longer names, but these get typed and shown in prompts
+pub const MAX_BRANCH_NAME: usize = 40;
+
+// a branch name only needs the gist of the change
+const MAX_DIFF_BYTES: usize = 16 * 1024;
+
+pub const BRANCH_SYSTEM_PROMPT: &str = "You name git branches. Reply with a single branch name and nothing else: a conventional-commit type, a slash, and two to five lowercase kebab-case words describing the change (e.g., `feat/config-file-support`, `fix/login-timeout`).";
+
+pub fn branch_prompt(diff: &str, types: &[String]) -> String {
+    format!(
+        "Allowed prefixes: {}.\n\nSuggest a branch name for this diff:\n\n```diff\n{}\n```",
+        types.join(", "),
+        attach::truncate_middle(diff, MAX_DIFF_BYTES)
+    )
+}
+
+// lowercase words of [a-z0-9] joined by single dashes
+fn kebab(text: &str) -> String {
+    text.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+// cuts at the last dash that keeps the name within `max`
+fn cap_length(name: &str, max: usize) -> &str {
+    if name.len() <= max {
+        return name;
+    }
+    match name[..=max].rfind('-') {
+        Some(cut) => &name[..cut],
+        None => &name[..max],
+    }
+}
+
+pub fn is_valid(name: &str) -> bool {
+    Reference::is_valid_name(&format!("refs/heads/{}", name))
+}
+
+// the model's reply as `type/words`: an unknown or missing type becomes the first allowed
+// one; none when nothing usable is left
+pub fn clean_name(response: &str, types: &[String]) -> Option<String> {
+    let line = response
+        .lines()
+        .map(|l| l.trim().trim_matches(['`', '"', '\'']))
+        .find(|l| !l.is_empty())?;
+    let (prefix, rest) = match line.split_once('/') {
+        Some((prefix, rest)) => (prefix.trim().to_lowercase(), rest),
+        None => (String::new(), line),
+    };
+    let prefix = if types.contains(&prefix) {
+        prefix
+    } else {
+        types.first()?.clone()
+    };
+
+    let slug = kebab(rest);
+    if slug.is_empty() {
+        return None;
+    }
+    let name = format!("{}/{}", prefix, slug);
+    let name = cap_length(&name, MAX_BRANCH_NAME).trim_end_matches('-');
+    is_valid(name).then(|| name.to_string())
+}
+
+// creates the branch at HEAD and switches to it; the working tree is already right
+pub fn create(repo: &Repository, name: &str) -> Result<()> {
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|_| anyhow!("--create needs an existing HEAD commit"))?;
+    let branch = repo
+        .branch(name, &head, false)
+        .with_context(|| format!("failed to create branch '{}'", name))?;
+    let reference = branch
+        .get()
+        .name()
+        .context("the new branch has no valid name")?;
+    repo.set_head(reference)
+        .with_context(|| format!("failed to switch to '{}'", name))
+}
@@ -0,0 +1,92 @@
+// src/bullets.rs
+// checks that per-file body bullets only refer to paths that actually changed
+
+pub struct FileBulletReport {
+    // the message with bullets for unchanged paths removed
+    pub message: String,
+    // path prefixes of removed bullets
+    pub dropped: Vec<String>,
+    // bullets that don't start with a path at all (kept as-is)
+    pub unprefixed: Vec<String>,
+    // changed files that no bullet mentions
+    pub uncovered: Vec<String>,
+}
+
+// true when `prefix` names a changed file or a directory containing one
+fn prefix_matches(prefix: &str, file: &str) -> bool {
+    file == prefix
+        || file
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+fn normalize_prefix(prefix: &str) -> &str {
+    let prefix = prefix.trim().trim_matches('`');
+    let prefix = prefix.strip_prefix("./").unwrap_or(prefix);
+    prefix.trim_end_matches('/')
+}
+
+// the path before the first ": " of a top-level bullet
+fn bullet_prefix(text: &str) -> Option<&str> {
+    let (prefix, _) = text.split_once(": ")?;
+    let prefix = normalize_prefix(prefix);
+    (!prefix.is_empty() && !prefix.contains(' ')).then_some(prefix)
+}
+
+pub fn check_file_bullets(message: &str, files: &[String]) -> FileBulletReport {
+    let mut kept: Vec<&str> = Vec::new();
+    let mut dropped = Vec::new();
+    let mut unprefixed = Vec::new();
+    let mut covered = vec![false; files.len()];
+    // continuation lines follow the fate of the bullet they belong to
+    let mut skipping = false;
+
+    for line in message.lines() {
+        let top_level = line.strip_prefix("- ").or_else(|| line.strip_prefix("* "));
+        let Some(text) = top_level else {
+            let continuation = line.starts_with(' ') && !line.trim().is_empty();
+            if !(skipping && continuation) {
+                skipping = false;
+                kept.push(line);
+            }
+            continue;
+        };
+
+        skipping = false;
+        match bullet_prefix(text) {
+            Some(prefix) => {
+                let mut matched = false;
+                for (idx, file) in files.iter().enumerate() {
+                    if prefix_matches(prefix, file) {
+                        covered[idx] = true;
+                        matched = true;
+                    }
+                }
+                if matched {
+                    kept.push(line);
+                } else {
+                    dropped.push(prefix.to_string());
+                    skipping = true;
+                }
+            }
+            None => {
+                unprefixed.push(text.to_string());
+                kept.push(line);
+            }
+        }
+    }
+
+    let uncovered = files
+        .iter()
+        .zip(&covered)
+        .filter(|(_, covered)| !**covered)
+        .map(|(file, _)| file.clone())
+        .collect();
+
+    FileBulletReport {
+        message: kept.join("\n").trim_end().to_string(),
+        dropped,
+        unprefixed,
+        uncovered,
+    }
+}
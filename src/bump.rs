@@ -0,0 +1,414 @@
+// src/bump.rs
+// the next semver version from the commits since the last release, decided locally
+
+use crate::changelog::{Entry, Section};
+use crate::history;
+use crate::rust_api;
+use anyhow::{anyhow, Context, Result};
+use git2::{Delta, Oid, Repository};
+use serde_json::json;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Patch => "patch",
+            Level::Minor => "minor",
+            Level::Major => "major",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    // `rc.1` in 2.0.0-rc.1; build metadata is dropped
+    pub pre: Option<String>,
+}
+
+impl Version {
+    // `1.2.3`, `v1.2.3` or `1.2.3-rc.1+build`
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let text = text.strip_prefix('v').unwrap_or(text);
+        let text = text.split('+').next()?;
+        let (numbers, pre) = match text.split_once('-') {
+            Some((numbers, pre)) if !pre.is_empty() => (numbers, Some(pre.to_string())),
+            Some(_) => return None,
+            None => (text, None),
+        };
+        let mut parts = numbers.split('.').map(|n| n.parse::<u64>().ok());
+        let version = Version {
+            major: parts.next()??,
+            minor: parts.next()??,
+            patch: parts.next()??,
+            pre,
+        };
+        parts.next().is_none().then_some(version)
+    }
+
+    // a pre-release becomes the release it leads up to; before 1.0.0 everything moves one
+    // place down, as cargo reads 0.x versions (breaking: 0.3.0 -> 0.4.0, feature: -> 0.3.1)
+    pub fn bump(&self, level: Level) -> Self {
+        let (major, minor, patch) = (self.major, self.minor, self.patch);
+        let (major, minor, patch) = match (self.pre.is_some(), major, level) {
+            (true, _, _) => (major, minor, patch),
+            (false, 0, Level::Major) => (0, minor + 1, 0),
+            (false, 0, _) => (0, minor, patch + 1),
+            (false, _, Level::Major) => (major + 1, 0, 0),
+            (false, _, Level::Minor) => (major, minor + 1, 0),
+            (false, _, Level::Patch) => (major, minor, patch + 1),
+        };
+        Version {
+            major,
+            minor,
+            patch,
+            pre: None,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+// why the level is what it is: one commit or one API change
+#[derive(Debug, Clone)]
+pub struct Reason {
+    pub level: Level,
+    // none for API changes, which come from the whole range's diff
+    pub short_id: Option<String>,
+    pub description: String,
+    pub why: String,
+}
+
+// the level a commit calls for and why: the marker, or the commit's type
+pub fn commit_level(entry: &Entry) -> (Level, String) {
+    match (entry.section, entry.kind.as_deref()) {
+        (Section::Breaking, _) => (Level::Major, "breaking change".to_string()),
+        (_, Some(kind)) if kind == "feat" => (Level::Minor, kind.to_string()),
+        (_, Some(kind)) => (Level::Patch, kind.to_string()),
+        (_, None) => (Level::Patch, "not a conventional commit".to_string()),
+    }
+}
+
+// the highest level any commit or API change calls for, with the reasons at that level
+pub fn classify(entries: &[Entry], api_breaks: &[String]) -> (Level, Vec<Reason>) {
+    let mut reasons: Vec<Reason> = entries
+        .iter()
+        .map(|entry| {
+            let (level, why) = commit_level(entry);
+            Reason {
+                level,
+                short_id: Some(entry.short_id.clone()),
+                description: entry.subject.clone(),
+                why,
+            }
+        })
+        .collect();
+    reasons.extend(api_breaks.iter().map(|summary| Reason {
+        level: Level::Major,
+        short_id: None,
+        description: summary.clone(),
+        why: "public API removed or changed".to_string(),
+    }));
+
+    let level = reasons
+        .iter()
+        .map(|r| r.level)
+        .max()
+        .unwrap_or(Level::Patch);
+    reasons.retain(|r| r.level == level);
+    (level, reasons)
+}
+
+// removed or changed `pub` items between two commits, one summary line per file; only for
+// crates with a library target, since a binary's `pub` items are nobody else's API
+pub fn api_breaks(repo: &Repository, from: Oid, to: Oid) -> Result<Vec<String>> {
+    let to_tree = repo.find_commit(to)?.tree()?;
+    if to_tree.get_path(Path::new("src/lib.rs")).is_err() {
+        return Ok(Vec::new());
+    }
+
+    let source = |id: Oid| -> Option<String> {
+        let blob = repo.find_blob(id).ok()?;
+        String::from_utf8(blob.content().to_vec()).ok()
+    };
+    let diff = history::diff_between(repo, from, to)?;
+    let mut breaks = Vec::new();
+    for delta in diff.deltas() {
+        let path = delta.new_file().path().or(delta.old_file().path());
+        let Some(path) = path.filter(|p| p.extension().is_some_and(|ext| ext == "rs")) else {
+            continue;
+        };
+        let old = match delta.status() {
+            Delta::Added => Some(String::new()),
+            _ => source(delta.old_file().id()),
+        };
+        let new = match delta.status() {
+            Delta::Deleted => Some(String::new()),
+            _ => source(delta.new_file().id()),
+        };
+        let (Some(old), Some(new)) = (
+            old.as_deref().and_then(rust_api::public_items),
+            new.as_deref().and_then(rust_api::public_items),
+        ) else {
+            continue;
+        };
+        let diff = rust_api::diff_items(&old, &new);
+        if diff.is_breaking() {
+            breaks.push(rust_api::summarize(&path.to_string_lossy(), &diff));
+        }
+    }
+    Ok(breaks)
+}
+
+fn package_version(manifest: &str) -> Option<String> {
+    let table: toml::Table = toml::from_str(manifest).ok()?;
+    table
+        .get("package")?
+        .get("version")?
+        .as_str()
+        .map(str::to_string)
+}
+
+// the current version and where it came from: Cargo.toml's package version, else the tag
+pub fn current_version(workdir: Option<&Path>, tag: Option<&str>) -> Result<(Version, String)> {
+    if let Some(manifest) = workdir.map(|dir| dir.join("Cargo.toml")) {
+        if let Some(version) = fs::read_to_string(&manifest)
+            .ok()
+            .and_then(|text| package_version(&text))
+        {
+            let parsed = Version::parse(&version)
+                .with_context(|| format!("Cargo.toml has a non-semver version '{}'", version))?;
+            return Ok((parsed, "Cargo.toml".to_string()));
+        }
+    }
+    let tag = tag.context("no Cargo.toml version and no earlier tag to read the version from")?;
+    let parsed = Version::parse(tag).with_context(|| {
+        format!(
+            "tag '{}' is not a semver version; pass --from <release tag>",
+            tag
+        )
+    })?;
+    Ok((parsed, format!("tag {}", tag)))
+}
+
+// the manifest with `version` under [package] replaced, everything else as it was
+pub fn set_package_version(manifest: &str, version: &str) -> Result<String> {
+    let mut in_package = false;
+    let mut out = String::with_capacity(manifest.len());
+    let mut replaced = false;
+    for line in manifest.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('[') {
+            in_package = trimmed.starts_with("[package]");
+        }
+        let is_version = trimmed
+            .strip_prefix("version")
+            .is_some_and(|rest| rest.trim_start().starts_with('='));
+        if in_package && is_version && !replaced {
+            let (Some(open), Some(close)) = (line.find('"'), line.rfind('"')) else {
+                return Err(anyhow!(
+                    "can't rewrite the [package] version line: {}",
+                    line.trim()
+                ));
+            };
+            if open == close {
+                return Err(anyhow!(
+                    "can't rewrite the [package] version line: {}",
+                    line.trim()
+                ));
+            }
+            out.push_str(&line[..=open]);
+            out.push_str(version);
+            out.push_str(&line[close..]);
+            replaced = true;
+            continue;
+        }
+        out.push_str(line);
+    }
+    if !replaced {
+        return Err(anyhow!(
+            "Cargo.toml has no version = \"...\" under [package]"
+        ));
+    }
+    Ok(out)
+}
+
+pub fn json_report(
+    current: &Version,
+    source: &str,
+    from: Option<&str>,
+    level: Level,
+    next: &Version,
+    reasons: &[Reason],
+) -> serde_json::Value {
+    json!({
+        "current": current.to_string(),
+        "source": source,
+        "from": from,
+        "level": level.name(),
+        "next": next.to_string(),
+        "reasons": reasons
+            .iter()
+            .map(|r| json!({
+                "commit": r.short_id,
+                "description": r.description,
+                "why": r.why,
+            }))
+            .collect::<Vec<_>>(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(text: &str) -> Version {
+        Version::parse(text).unwrap()
+    }
+
+    #[test]
+    fn versions() {
+        let cases = [
+            ("1.2.3", Some((1, 2, 3, None))),
+            ("v1.2.3", Some((1, 2, 3, None))),
+            (" 0.14.0\n", Some((0, 14, 0, None))),
+            ("2.0.0-rc.1", Some((2, 0, 0, Some("rc.1")))),
+            ("2.0.0-rc.1+build.5", Some((2, 0, 0, Some("rc.1")))),
+            ("1.0.0+build", Some((1, 0, 0, None))),
+            ("1.2", None),
+            ("1.2.3.4", None),
+            ("1.2.x", None),
+            ("1.2.3-", None),
+            ("release-1", None),
+            ("", None),
+        ];
+        for (text, expected) in cases {
+            let parsed = Version::parse(text);
+            let parts = parsed
+                .as_ref()
+                .map(|v| (v.major, v.minor, v.patch, v.pre.as_deref()));
+            assert_eq!(parts, expected, "{:?}", text);
+        }
+        assert_eq!(version("v2.0.0-rc.1+build").to_string(), "2.0.0-rc.1");
+    }
+
+    #[test]
+    fn bumps() {
+        let cases = [
+            ("1.2.3", Level::Patch, "1.2.4"),
+            ("1.2.3", Level::Minor, "1.3.0"),
+            ("1.2.3", Level::Major, "2.0.0"),
+            // before 1.0.0 every level moves one place down, as cargo reads 0.x versions
+            ("0.3.2", Level::Patch, "0.3.3"),
+            ("0.3.2", Level::Minor, "0.3.3"),
+            ("0.3.2", Level::Major, "0.4.0"),
+            ("0.0.1", Level::Major, "0.1.0"),
+            // a pre-release becomes the release it leads up to, whatever the level
+            ("2.0.0-rc.1", Level::Patch, "2.0.0"),
+            ("2.0.0-rc.1", Level::Major, "2.0.0"),
+            ("0.4.0-beta", Level::Minor, "0.4.0"),
+        ];
+        for (from, level, to) in cases {
+            assert_eq!(
+                version(from).bump(level).to_string(),
+                to,
+                "{} {:?}",
+                from,
+                level
+            );
+        }
+    }
+
+    fn entry(message: &str) -> Entry {
+        crate::changelog::entry("abc1234".to_string(), message)
+    }
+
+    #[test]
+    fn classification() {
+        let cases: &[(&[&str], &[&str], Level, usize)] = &[
+            (&[], &[], Level::Patch, 0),
+            (&["fix: a", "chore: b"], &[], Level::Patch, 2),
+            (&["not conventional"], &[], Level::Patch, 1),
+            (&["fix: a", "feat: b", "docs: c"], &[], Level::Minor, 1),
+            (&["feat(cli): a", "Feat: b"], &[], Level::Minor, 2),
+            (&["feat: a", "fix!: b"], &[], Level::Major, 1),
+            (&["feat: a\n\nBREAKING CHANGE: gone"], &[], Level::Major, 1),
+            (
+                &["fix: a"],
+                &["src/lib.rs: removed `fn old`"],
+                Level::Major,
+                1,
+            ),
+            (
+                &["fix!: a"],
+                &["src/lib.rs: removed `fn old`"],
+                Level::Major,
+                2,
+            ),
+        ];
+        for &(messages, api, level, reasons) in cases {
+            let entries: Vec<Entry> = messages.iter().map(|m| entry(m)).collect();
+            let api: Vec<String> = api.iter().map(|s| s.to_string()).collect();
+            let (got, why) = classify(&entries, &api);
+            assert_eq!(
+                (got, why.len()),
+                (level, reasons),
+                "{:?} {:?}",
+                messages,
+                api
+            );
+            assert!(why.iter().all(|r| r.level == level));
+        }
+    }
+
+    #[test]
+    fn the_reasons_name_the_commit_or_the_api_change() {
+        let (_, reasons) = classify(
+            &[entry("feat!: drop v1")],
+            &["src/lib.rs: removed `fn old`".to_string()],
+        );
+        assert_eq!(reasons[0].short_id.as_deref(), Some("abc1234"));
+        assert_eq!(reasons[0].why, "breaking change");
+        assert_eq!(reasons[1].short_id, None);
+        assert_eq!(reasons[1].why, "public API removed or changed");
+    }
+
+    #[test]
+    fn a_feat_on_0_x_is_a_patch_bump() {
+        let (level, _) = classify(&[entry("feat: add it")], &[]);
+        assert_eq!(level, Level::Minor);
+        assert_eq!(version("0.14.0").bump(level).to_string(), "0.14.1");
+        assert_eq!(version("1.14.0").bump(level).to_string(), "1.15.0");
+    }
+
+    #[test]
+    fn the_package_version_is_replaced_in_place() {
+        let manifest = "[package]\nname = \"x\"\nversion   = \"0.1.0\" # keep\n\n[dependencies]\nversion = \"9\"\n";
+        assert_eq!(
+            set_package_version(manifest, "0.2.0").unwrap(),
+            manifest.replace("\"0.1.0\"", "\"0.2.0\"")
+        );
+        assert_eq!(package_version(manifest).as_deref(), Some("0.1.0"));
+        assert!(set_package_version("[dependencies]\nversion = \"9\"\n", "1.0.0").is_err());
+    }
+}
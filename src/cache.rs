@@ -0,0 +1,136 @@
+// src/cache.rs
+// the last suggested message for a request, kept inside .git so a run asking the same can reuse
+// it and --feedback can revise it, and the model list in the user's cache for completing --model
+
+use crate::interrupt;
+use anyhow::{anyhow, Context, Result};
+use git2::Repository;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const CACHE_DIR: &str = "git-changes";
+const LAST_MESSAGE_FILE: &str = "last-message";
+const USER_CACHE_DIR: &str = "git-changes-rs";
+const MODELS_FILE: &str = "models";
+
+// fnv-1a, which unlike std's hasher is stable across rust releases
+pub fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in data {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+pub fn diff_hash(diff: &str) -> String {
+    format!("{:016x}", fnv1a(diff.as_bytes()))
+}
+
+// what a cached message is keyed by: every input that shapes the request for it, each with its
+// length in front so that moving text from one part to the next changes the key
+pub fn message_key(parts: &[&str]) -> String {
+    let mut data = Vec::new();
+    for part in parts {
+        data.extend_from_slice(&(part.len() as u64).to_le_bytes());
+        data.extend_from_slice(part.as_bytes());
+    }
+    format!("{:016x}", fnv1a(&data))
+}
+
+// where the tool keeps its files for this repository
+pub fn data_dir(repo: &Repository) -> PathBuf {
+    repo.path().join(CACHE_DIR)
+}
+
+fn last_message_path(repo: &Repository) -> PathBuf {
+    data_dir(repo).join(LAST_MESSAGE_FILE)
+}
+
+// the cached message, if it was generated for exactly this key
+pub fn load(repo: &Repository, key: &str) -> Option<String> {
+    let contents = fs::read_to_string(last_message_path(repo)).ok()?;
+    let (stored, message) = contents.split_once('\n')?;
+    (stored == key).then(|| message.to_string())
+}
+
+// replaces the cached entry; the first line is the key, the rest the message
+pub fn store(repo: &Repository, key: &str, message: &str) -> Result<()> {
+    let _writing = interrupt::writing()?;
+    let path = last_message_path(repo);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create cache directory {}", dir.display()))?;
+    }
+    fs::write(&path, format!("{}\n{}", key, message))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+// for files not tied to a repository: $XDG_CACHE_HOME, ~/.cache or %LOCALAPPDATA%
+fn user_cache_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| match cfg!(windows) {
+            true => env::var_os("LOCALAPPDATA").map(PathBuf::from),
+            false => env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")),
+        })?;
+    Some(base.join(USER_CACHE_DIR))
+}
+
+// the ids `models` listed last, one per line
+pub fn store_models(models: &[String]) -> Result<()> {
+    let dir = user_cache_dir().ok_or_else(|| anyhow!("no cache directory (HOME isn't set)"))?;
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("failed to create cache directory {}", dir.display()))?;
+    let path = dir.join(MODELS_FILE);
+    fs::write(&path, format!("{}\n", models.join("\n")))
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+// empty until `models` has run once
+pub fn load_models() -> Vec<String> {
+    user_cache_dir()
+        .and_then(|dir| fs::read_to_string(dir.join(MODELS_FILE)).ok())
+        .map(|text| text.lines().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_part_of_the_key_counts() {
+        let base = ["gemini-2.0-flash", "detailed", "full", "", "system", "diff"];
+        let key = message_key(&base);
+        assert_eq!(key, message_key(&base));
+        for index in 0..base.len() {
+            let mut changed = base;
+            changed[index] = "other";
+            assert_ne!(message_key(&changed), key, "part {} is ignored", index);
+        }
+    }
+
+    #[test]
+    fn text_moved_between_parts_changes_the_key() {
+        assert_ne!(message_key(&["ab", "c"]), message_key(&["a", "bc"]));
+        assert_ne!(message_key(&["a", ""]), message_key(&["", "a"]));
+    }
+
+    #[test]
+    fn a_message_is_loaded_only_for_its_own_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let key = message_key(&["diff"]);
+        assert_eq!(load(&repo, &key), None);
+
+        store(&repo, &key, "feat: add notes\n\n- start them").unwrap();
+        assert_eq!(
+            load(&repo, &key).as_deref(),
+            Some("feat: add notes\n\n- start them")
+        );
+        assert_eq!(load(&repo, &message_key(&["other diff"])), None);
+    }
+}
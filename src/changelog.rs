@@ -0,0 +1,143 @@
+// src/changelog.rs
+// changelogs for a range of commits, grouped locally by conventional type
+
+use crate::conventional::ConventionalMessage;
+use crate::history;
+use anyhow::{anyhow, Result};
+use git2::{Oid, Repository};
+use std::collections::BTreeMap;
+
+pub const CHANGELOG_MAX_OUTPUT_TOKENS: u32 = 2048;
+
+pub const CHANGELOG_SYSTEM_PROMPT: &str = "You write release changelogs in markdown for the people using the software. You are given the commits of a release, already grouped into sections. Write one `###` heading per non-empty section, in the order given, with one bullet per user-visible change in plain language. Merge commits that describe the same change into one bullet. End every bullet with the short SHAs it covers in parentheses, e.g. `(a1b2c3d)`. Do not invent changes, and do not add a title or any text outside the sections.";
+
+// declaration order is the order sections appear in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Section {
+    Breaking,
+    Features,
+    Fixes,
+    Performance,
+    Other,
+}
+
+impl Section {
+    pub fn title(self) -> &'static str {
+        match self {
+            Section::Breaking => "Breaking Changes",
+            Section::Features => "Features",
+            Section::Fixes => "Fixes",
+            Section::Performance => "Performance",
+            Section::Other => "Other",
+        }
+    }
+
+    fn for_kind(kind: &str) -> Self {
+        match kind {
+            "feat" => Section::Features,
+            "fix" => Section::Fixes,
+            "perf" => Section::Performance,
+            _ => Section::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub short_id: String,
+    pub subject: String,
+    pub section: Section,
+    // the conventional type, lowercased; none for other subjects
+    pub kind: Option<String>,
+    // files the commit touched
+    pub paths: Vec<String>,
+}
+
+// breaking changes are marked with `!` or a BREAKING CHANGE footer; subjects that aren't
+// conventional go to Other as they are
+pub fn entry(short_id: String, message: &str) -> Entry {
+    let parsed = ConventionalMessage::parse(message);
+    let (subject, section, kind) = match &parsed.prefix {
+        Some(prefix) => {
+            let kind = prefix.kind.to_ascii_lowercase();
+            let subject = match &prefix.scope {
+                Some(scope) => format!("{}: {}", scope, parsed.subject),
+                None => parsed.subject.clone(),
+            };
+            let section = if parsed.is_breaking() {
+                Section::Breaking
+            } else {
+                Section::for_kind(&kind)
+            };
+            (subject, section, Some(kind))
+        }
+        None => (parsed.subject.clone(), Section::Other, None),
+    };
+    Entry {
+        short_id,
+        subject,
+        section,
+        kind,
+        paths: Vec::new(),
+    }
+}
+
+// the range's commits with the files each touched, oldest first, skipping merges
+pub fn entries(repo: &Repository, oids: &[Oid]) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for oid in oids.iter().rev() {
+        let commit = repo.find_commit(*oid)?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let mut entry = entry(
+            history::short_id(*oid),
+            commit.message().unwrap_or_default(),
+        );
+        entry.paths = history::changed_paths(&history::diff_to_parent(repo, *oid)?);
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// the entries for `from..to`, or for all of `to`'s history without `from`; either end can
+// be a branch, a commit or a tag of either kind
+pub fn range_entries(repo: &Repository, from: Option<&str>, to: &str) -> Result<Vec<Entry>> {
+    // resolving both ends first names the bad one
+    if let Some(from) = from {
+        history::resolve(repo, from)?;
+    }
+    history::resolve(repo, to)?;
+    let range = match from {
+        Some(from) => format!("{}..{}", from, to),
+        None => to.to_string(),
+    };
+    let oids = history::commits_in_range(repo, Some(&range))?;
+    let entries = entries(repo, &oids)?;
+    if entries.is_empty() {
+        return Err(anyhow!("no commits in {}; nothing to write", range));
+    }
+    Ok(entries)
+}
+
+pub fn group(entries: &[Entry]) -> BTreeMap<Section, Vec<&Entry>> {
+    let mut sections: BTreeMap<Section, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        sections.entry(entry.section).or_default().push(entry);
+    }
+    sections
+}
+
+pub fn changelog_prompt(from: &str, to: &str, sections: &BTreeMap<Section, Vec<&Entry>>) -> String {
+    let mut prompt = format!(
+        "Write the changelog for the commits from {} to {}:\n",
+        from, to
+    );
+    for (section, entries) in sections {
+        prompt.push_str(&format!("\n{}:\n", section.title()));
+        for entry in entries {
+            prompt.push_str(&format!("- {} ({})\n", entry.subject, entry.short_id));
+        }
+    }
+    prompt
+}
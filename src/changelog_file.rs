@@ -0,0 +1,440 @@
+// src/changelog_file.rs
+// reading and editing a Keep a Changelog CHANGELOG.md without touching the rest of it
+
+use anyhow::{anyhow, Context, Result};
+use git2::Patch;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+const UNRELEASED: &str = "Unreleased";
+
+// what a new CHANGELOG.md starts with
+const NEW_FILE: &str = "# Changelog\n\nAll notable changes to this project will be documented in this file.\n\nThe format is based on [Keep a Changelog](https://keepachangelog.com/en/1.1.0/).\n\n";
+
+// one `## ` heading and everything up to the next one
+#[derive(Debug, Clone)]
+pub struct Release {
+    // the heading line as written, line ending included
+    pub heading: String,
+    pub body: String,
+}
+
+impl Release {
+    // `Unreleased` or the version: `## [1.4.0] - 2024-05-01` and `## 1.4.0` both give 1.4.0
+    pub fn name(&self) -> &str {
+        let title = self.heading.trim_start_matches('#').trim();
+        match title.strip_prefix('[') {
+            Some(rest) => rest.split(']').next().unwrap_or_default().trim(),
+            None => title.split_whitespace().next().unwrap_or_default(),
+        }
+    }
+
+    fn is_unreleased(&self) -> bool {
+        self.name().eq_ignore_ascii_case(UNRELEASED)
+    }
+}
+
+// the file split into parts that concatenate back to the exact original bytes
+#[derive(Debug, Clone)]
+pub struct ChangelogFile {
+    // everything above the first release heading
+    pub preamble: String,
+    pub releases: Vec<Release>,
+    // the link reference definitions at the bottom, one entry per line (blank lines included)
+    pub links: Vec<String>,
+    newline: &'static str,
+}
+
+// `[label]: url`, the form of the compare links at the bottom
+fn link_parts(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_end().strip_prefix('[')?;
+    let (label, url) = rest.split_once("]:")?;
+    let url = url.trim();
+    (!label.is_empty() && !url.is_empty() && !url.contains(' ')).then_some((label, url))
+}
+
+fn is_blank(line: &str) -> bool {
+    line.trim().is_empty()
+}
+
+impl ChangelogFile {
+    pub fn parse(text: &str) -> Self {
+        let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        let lines: Vec<&str> = text.split_inclusive('\n').collect();
+
+        // the trailing run of link definitions and blank lines, starting at a definition
+        let mut start = lines.len();
+        while start > 0 && (is_blank(lines[start - 1]) || link_parts(lines[start - 1]).is_some()) {
+            start -= 1;
+        }
+        while start < lines.len() && is_blank(lines[start]) {
+            start += 1;
+        }
+        let links = lines[start..].iter().map(|l| l.to_string()).collect();
+
+        let mut preamble = String::new();
+        let mut releases: Vec<Release> = Vec::new();
+        let mut fenced = false;
+        for line in &lines[..start] {
+            if line.trim_start().starts_with("```") {
+                fenced = !fenced;
+            }
+            if !fenced && line.starts_with("## ") {
+                releases.push(Release {
+                    heading: line.to_string(),
+                    body: String::new(),
+                });
+                continue;
+            }
+            match releases.last_mut() {
+                Some(release) => release.body.push_str(line),
+                None => preamble.push_str(line),
+            }
+        }
+
+        ChangelogFile {
+            preamble,
+            releases,
+            links,
+            newline,
+        }
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut text = self.preamble.clone();
+        for release in &self.releases {
+            text.push_str(&release.heading);
+            text.push_str(&release.body);
+        }
+        for line in &self.links {
+            text.push_str(line);
+        }
+        text
+    }
+
+    fn lines(&self, text: &str) -> String {
+        text.replace('\n', self.newline)
+    }
+
+    pub fn has_release(&self, version: &str) -> bool {
+        self.releases.iter().any(|r| r.name() == version)
+    }
+
+    // the text before release `index` ends with a line break, so a heading can follow it
+    fn end_line_before(&mut self, index: usize) {
+        let newline = self.newline;
+        let previous = match index.checked_sub(1) {
+            Some(i) if self.releases[i].body.is_empty() => &mut self.releases[i].heading,
+            Some(i) => &mut self.releases[i].body,
+            None => &mut self.preamble,
+        };
+        if !previous.is_empty() && !previous.ends_with('\n') {
+            previous.push_str(newline);
+        }
+    }
+
+    // adds `## [version] - date` with `notes` right below `## [Unreleased]`, which is
+    // created first if the file has none; nothing else changes
+    pub fn add_release(&mut self, version: &str, date: &str, notes: &str) -> Result<()> {
+        if self.has_release(version) {
+            return Err(anyhow!("{} is already in the changelog", version));
+        }
+
+        let unreleased = match self.releases.iter().position(Release::is_unreleased) {
+            Some(index) => index,
+            None => {
+                self.end_line_before(0);
+                let release = Release {
+                    heading: self.lines(&format!("## [{}]\n", UNRELEASED)),
+                    body: self.newline.to_string(),
+                };
+                self.releases.insert(0, release);
+                0
+            }
+        };
+        if self.releases[unreleased].body.lines().any(|l| !is_blank(l)) {
+            warn!(
+                "[Unreleased] has entries of its own; they stay where they are, move them into {} by hand if they belong to it",
+                version
+            );
+        }
+
+        let index = unreleased + 1;
+        self.end_line_before(index);
+        let release = Release {
+            heading: self.lines(&format!("## [{}] - {}\n", version, date)),
+            // a blank line before the next heading or the links, but none at the end of the file
+            body: match index == self.releases.len() && self.links.is_empty() {
+                true => self.lines(&format!("\n{}\n", notes.trim())),
+                false => self.lines(&format!("\n{}\n\n", notes.trim())),
+            },
+        };
+        self.releases.insert(index, release);
+        Ok(())
+    }
+
+    // points the Unreleased compare link at the new release and adds one for the release
+    // itself; false when there is no `[unreleased]: .../<tag>...HEAD` link to work from
+    pub fn update_links(&mut self, version: &str) -> bool {
+        let Some(line_index) = self.links.iter().position(|line| {
+            link_parts(line).is_some_and(|(label, _)| label.eq_ignore_ascii_case(UNRELEASED))
+        }) else {
+            return false;
+        };
+        let line = self.links[line_index].clone();
+        let Some((_, url)) = link_parts(&line) else {
+            return false;
+        };
+        let Some(base_and_previous) = url.strip_suffix("...HEAD") else {
+            return false;
+        };
+        let cut = base_and_previous.rfind('/').map_or(0, |i| i + 1);
+        let (base, previous) = base_and_previous.split_at(cut);
+        if previous.is_empty() {
+            return false;
+        }
+
+        // the new tag is spelled like the previous one: v1.4.0 with heading 1.4.0 means v1.5.0
+        let previous_version = self
+            .releases
+            .iter()
+            .filter(|r| !r.is_unreleased() && r.name() != version)
+            .map(|r| r.name().to_string())
+            .next();
+        let prefix = previous_version
+            .as_deref()
+            .and_then(|v| previous.strip_suffix(v))
+            .unwrap_or_default();
+        let tag = match version.starts_with(prefix) {
+            true => version.to_string(),
+            false => format!("{}{}", prefix, version),
+        };
+
+        let old_range = format!("{}...HEAD", previous);
+        let Some(at) = line.rfind(&old_range) else {
+            return false;
+        };
+        let mut updated = line.clone();
+        updated.replace_range(at..at + old_range.len(), &format!("{}...HEAD", tag));
+        if !updated.ends_with('\n') {
+            updated.push_str(self.newline);
+        }
+        self.links[line_index] = updated;
+
+        let already_linked = self
+            .links
+            .iter()
+            .any(|l| link_parts(l).is_some_and(|(label, _)| label == version));
+        if !already_linked {
+            let link = format!("[{}]: {}{}...{}\n", version, base, previous, tag);
+            self.links.insert(line_index + 1, self.lines(&link));
+        }
+        true
+    }
+}
+
+// the file's text, or the Keep a Changelog header when it doesn't exist yet
+pub fn read(path: &Path) -> Result<String> {
+    match fs::read_to_string(path) {
+        Ok(text) => Ok(text),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(NEW_FILE.to_string()),
+        Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+    }
+}
+
+// the change as a unified diff, for --dry-run
+pub fn diff(path: &Path, old: &str, new: &str) -> Result<String> {
+    let name = path.to_string_lossy();
+    let mut patch = Patch::from_buffers(
+        old.as_bytes(),
+        Some(Path::new(name.as_ref())),
+        new.as_bytes(),
+        Some(Path::new(name.as_ref())),
+        None,
+    )
+    .context("failed to diff the changelog")?;
+    let text = patch
+        .to_buf()
+        .context("failed to render the changelog diff")?;
+    Ok(String::from_utf8_lossy(&text).into_owned())
+}
+
+// today in UTC as YYYY-MM-DD
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (year, month, day) = civil_date((seconds / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// days since 1970-01-01 to a proleptic Gregorian date (Howard Hinnant's algorithm)
+pub fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEEP_A_CHANGELOG: &str = "# Changelog\n\
+\n\
+All notable changes to this project will be documented in this file.\n\
+\n\
+## [Unreleased]\n\
+\n\
+## [1.1.0] - 2024-03-02\n\
+\n\
+### Added\n\
+\n\
+- A `## heading` inside a code span\n\
+\n\
+```\n\
+## not a release, it's fenced\n\
+```\n\
+\n\
+## [1.0.0] - 2024-01-05\n\
+\n\
+- First release.\n\
+\n\
+[unreleased]: https://github.com/o/r/compare/v1.1.0...HEAD\n\
+[1.1.0]: https://github.com/o/r/compare/v1.0.0...v1.1.0\n\
+[1.0.0]: https://github.com/o/r/releases/tag/v1.0.0\n";
+
+    fn round_trip(text: &str) {
+        assert_eq!(ChangelogFile::parse(text).serialize(), text);
+    }
+
+    #[test]
+    fn parse_and_serialize_give_back_the_same_bytes() {
+        for text in [
+            KEEP_A_CHANGELOG.to_string(),
+            KEEP_A_CHANGELOG.replace('\n', "\r\n"),
+            KEEP_A_CHANGELOG.trim_end().to_string(),
+            KEEP_A_CHANGELOG
+                .replace('\n', "\r\n")
+                .trim_end()
+                .to_string(),
+            NEW_FILE.to_string(),
+            String::new(),
+            "no headings at all".to_string(),
+            "## [Unreleased]".to_string(),
+            "[unreleased]: https://example.com/v1...HEAD".to_string(),
+            "# Changelog\n\n## 1.0.0\n- released\n\n\n".to_string(),
+        ] {
+            round_trip(&text);
+        }
+    }
+
+    #[test]
+    fn the_parts_are_split_at_the_release_headings_and_links() {
+        let file = ChangelogFile::parse(KEEP_A_CHANGELOG);
+        let names: Vec<&str> = file.releases.iter().map(Release::name).collect();
+        assert_eq!(names, ["Unreleased", "1.1.0", "1.0.0"]);
+        assert!(file.releases[1].body.contains("## not a release"));
+        assert_eq!(file.links.len(), 3);
+        assert!(file.links[0].starts_with("[unreleased]: "));
+    }
+
+    #[test]
+    fn add_release_only_adds_the_section_and_its_link() {
+        let mut file = ChangelogFile::parse(KEEP_A_CHANGELOG);
+        file.add_release("1.2.0", "2024-05-01", "### Fixed\n\n- A crash.\n")
+            .unwrap();
+        assert!(file.update_links("1.2.0"));
+
+        let expected = KEEP_A_CHANGELOG
+            .replace(
+                "## [Unreleased]\n\n",
+                "## [Unreleased]\n\n## [1.2.0] - 2024-05-01\n\n### Fixed\n\n- A crash.\n\n",
+            )
+            .replace(
+                "[unreleased]: https://github.com/o/r/compare/v1.1.0...HEAD\n",
+                "[unreleased]: https://github.com/o/r/compare/v1.2.0...HEAD\n\
+                 [1.2.0]: https://github.com/o/r/compare/v1.1.0...v1.2.0\n",
+            );
+        assert_eq!(file.serialize(), expected);
+    }
+
+    #[test]
+    fn add_release_keeps_crlf_line_endings() {
+        let text = KEEP_A_CHANGELOG.replace('\n', "\r\n");
+        let mut file = ChangelogFile::parse(&text);
+        file.add_release("1.2.0", "2024-05-01", "- A crash.")
+            .unwrap();
+        assert!(file.update_links("1.2.0"));
+
+        let new = file.serialize();
+        assert!(new.contains("## [1.2.0] - 2024-05-01\r\n\r\n- A crash.\r\n\r\n## [1.1.0]"));
+        assert!(new.contains("[1.2.0]: https://github.com/o/r/compare/v1.1.0...v1.2.0\r\n"));
+        assert!(!new.replace("\r\n", "").contains('\n'));
+    }
+
+    #[test]
+    fn add_release_after_a_missing_final_newline() {
+        let mut file = ChangelogFile::parse("# Changelog\n\n## [1.0.0] - 2024-01-05\n- First.");
+        file.add_release("1.1.0", "2024-02-01", "- Second.")
+            .unwrap();
+        assert_eq!(
+            file.serialize(),
+            "# Changelog\n\n## [Unreleased]\n\n## [1.1.0] - 2024-02-01\n\n- Second.\n\n\
+             ## [1.0.0] - 2024-01-05\n- First."
+        );
+    }
+
+    #[test]
+    fn add_release_to_a_new_file() {
+        let mut file = ChangelogFile::parse(NEW_FILE);
+        file.add_release("0.1.0", "2024-01-01", "- First.").unwrap();
+        assert!(!file.update_links("0.1.0"));
+        assert_eq!(
+            file.serialize(),
+            format!(
+                "{}## [Unreleased]\n\n## [0.1.0] - 2024-01-01\n\n- First.\n",
+                NEW_FILE
+            )
+        );
+    }
+
+    #[test]
+    fn a_release_already_in_the_file_is_refused() {
+        let mut file = ChangelogFile::parse(KEEP_A_CHANGELOG);
+        assert!(file.add_release("1.1.0", "2024-05-01", "- Again.").is_err());
+        assert_eq!(file.serialize(), KEEP_A_CHANGELOG);
+    }
+
+    #[test]
+    fn the_tag_prefix_follows_the_previous_link() {
+        let text = "## [1.0.0]\n\n[unreleased]: https://e.com/compare/1.0.0...HEAD\n";
+        let mut file = ChangelogFile::parse(text);
+        file.add_release("1.1.0", "2024-02-01", "- Next.").unwrap();
+        assert!(file.update_links("1.1.0"));
+        assert_eq!(
+            file.links,
+            [
+                "[unreleased]: https://e.com/compare/1.1.0...HEAD\n",
+                "[1.1.0]: https://e.com/compare/1.0.0...1.1.0\n",
+            ]
+        );
+    }
+
+    #[test]
+    fn civil_dates() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(19_782), (2024, 2, 29));
+        assert_eq!(civil_date(-1), (1969, 12, 31));
+    }
+}
@@ -0,0 +1,71 @@
+// src/cherry_pick.rs
+// cherry-picks and backports: the original commit, from CHERRY_PICK_HEAD or --cherry-pick-of
+
+use crate::trailers;
+use anyhow::{anyhow, Context, Result};
+use git2::{Oid, Repository};
+use std::fs;
+
+// what `git cherry-pick` leaves in the git dir while it waits for a conflict to be resolved
+const CHERRY_PICK_HEAD: &str = "CHERRY_PICK_HEAD";
+
+const ORIGIN_PREFIX: &str = "(cherry picked from commit ";
+
+#[derive(Debug, Clone)]
+pub struct Original {
+    pub oid: Oid,
+    pub message: String,
+    // git is in the middle of the cherry-pick; committing concludes it, as the original author
+    pub in_progress: bool,
+}
+
+pub fn in_progress(repo: &Repository) -> bool {
+    repo.path().join(CHERRY_PICK_HEAD).is_file()
+}
+
+// the commit being cherry-picked, if git is in the middle of it
+pub fn pending(repo: &Repository) -> Result<Option<Original>> {
+    let Ok(text) = fs::read_to_string(repo.path().join(CHERRY_PICK_HEAD)) else {
+        return Ok(None);
+    };
+    let oid = Oid::from_str(text.trim())
+        .map_err(|_| anyhow!("{} doesn't name a commit", CHERRY_PICK_HEAD))?;
+    let mut original = lookup(repo, oid)?;
+    original.in_progress = true;
+    Ok(Some(original))
+}
+
+// --cherry-pick-of: changes already staged by hand (`git cherry-pick -n`, or a manual backport)
+pub fn original(repo: &Repository, rev: &str) -> Result<Original> {
+    let oid = repo
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_commit())
+        .map(|commit| commit.id())
+        .with_context(|| format!("failed to resolve {} to a commit", rev))?;
+    lookup(repo, oid)
+}
+
+fn lookup(repo: &Repository, oid: Oid) -> Result<Original> {
+    let commit = repo
+        .find_commit(oid)
+        .with_context(|| format!("failed to look up commit {}", oid))?;
+    Ok(Original {
+        oid,
+        message: commit.message().unwrap_or_default().to_string(),
+        in_progress: false,
+    })
+}
+
+// the message with the `git cherry-pick -x` line closing the body, above any trailers; a
+// copy of it the model made is dropped first
+pub fn with_origin(message: &str, original: &Original) -> String {
+    let kept: Vec<&str> = message
+        .lines()
+        .filter(|line| !line.trim_start().starts_with(ORIGIN_PREFIX))
+        .collect();
+    let mut cleaned = kept.join("\n");
+    while cleaned.contains("\n\n\n") {
+        cleaned = cleaned.replace("\n\n\n", "\n\n");
+    }
+    trailers::insert_paragraph(&cleaned, &format!("{}{})", ORIGIN_PREFIX, original.oid))
+}
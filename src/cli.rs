@@ -0,0 +1,1233 @@
+// src/cli.rs
+// the command line: the flags every command takes, the generate flags (also taken without a
+// subcommand, the way the tool was always run) and the other subcommands
+
+use crate::template::Template;
+use crate::{code_review, exit, hook, invocation, provider, release_notes, trailers, validate};
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgGroup, ArgMatches, Command};
+use clap_complete::Shell;
+use std::process::ExitCode;
+
+// the ids of global_args, which go before or after a subcommand
+const GLOBAL_ARGS: [&str; 11] = [
+    "provider", "model", "config", "verbose", "quiet", "yes", "confirm", "no_color", "ascii",
+    "timings", "chdir",
+];
+
+// the parsed command line; generate flags given to the root are an error with a subcommand,
+// where they would otherwise do nothing. Usage lines say `git changes` when run as that
+pub fn parse() -> Result<ArgMatches, ExitCode> {
+    let mut command = build().bin_name(invocation::program_name());
+    let matches = command
+        .try_get_matches_from_mut(std::env::args_os())
+        .map_err(usage_error)?;
+    if let Some((name, _)) = matches.subcommand() {
+        let stray = matches.ids().map(|id| id.as_str()).find(|id| {
+            !GLOBAL_ARGS.contains(id) && matches.value_source(id) == Some(ValueSource::CommandLine)
+        });
+        if let Some(id) = stray {
+            let flag = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .and_then(|arg| arg.get_long())
+                .map_or_else(|| id.to_string(), |long| format!("--{}", long));
+            return Err(usage_error(command.error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "{} belongs to generate and can't be used with '{}'",
+                    flag, name
+                ),
+            )));
+        }
+    }
+    Ok(matches)
+}
+
+// --help and --version end with 0 as usual; real usage errors with exit::USAGE, not clap's 2,
+// which means no changes here
+fn usage_error(error: clap::Error) -> ExitCode {
+    let _ = error.print();
+    match error.use_stderr() {
+        true => exit::exit_code(exit::USAGE),
+        false => ExitCode::SUCCESS,
+    }
+}
+
+// every command and flag; completions and the man pages are made from it too
+pub fn build() -> Command {
+    let root = Command::new("git-changes-rs")
+        .version(env!("CARGO_PKG_VERSION"))
+        .about("Generate a commit message based on diffs using Gemini API")
+        .subcommand_negates_reqs(true)
+        .after_help(exit::help())
+        .args(global_args());
+    generate_args(root)
+        .subcommand(generate_args(
+            Command::new("generate")
+                .about("Generate a commit message for the changes (also what runs without a subcommand)")
+                .after_help(exit::help()),
+        ))
+        .subcommand(
+            Command::new("models")
+                .about("List the models the provider offers for generating text, marking the one in use"),
+        )
+        .subcommand(
+            Command::new("lint")
+                .about("Check existing commit messages against conventional-commit rules")
+                .arg(
+                    Arg::new("range")
+                        .help("Commits to check, e.g. 'origin/main..HEAD' (default: current branch's commits not on its upstream)")
+                        .index(1),
+                )
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("fix_suggestions")
+                        .long("fix-suggestions")
+                        .help("Ask the model for a corrected message for each failing commit")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("reword")
+                .about("Generate proper messages for low-quality commits on the current branch")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("onto")
+                        .long("onto")
+                        .help("Reword commits after this base instead of those not on the upstream")
+                        .value_name("BASE"),
+                )
+                .arg(
+                    Arg::new("apply")
+                        .long("apply")
+                        .help("Rewrite the commits in place instead of printing a rebase todo (unpushed branches only)")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("split")
+                .about("Propose how to split the working tree's changes into several commits")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("apply")
+                        .long("apply")
+                        .help("Stage and commit each proposed group in turn instead of printing the commands")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("branch-name")
+                .about("Suggest a branch name like feat/config-file-support for the changes")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("create")
+                        .long("create")
+                        .help("Create the branch at HEAD and switch to it")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("pr")
+                .about("Write a pull request title and description for the current branch")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("base")
+                        .long("base")
+                        .help("Compare with this ref (default: the upstream, then origin/HEAD)")
+                        .value_name("REF"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Write the markdown to FILE instead of stdout")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("github")
+                        .long("github")
+                        .help("Create the pull request on GitHub, or update the open one for this branch (needs GITHUB_TOKEN)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("gitlab")
+                        .long("gitlab")
+                        .help("Create the merge request on GitLab, or update the open one for this branch (needs GITLAB_TOKEN)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("gitlab_url")
+                        .long("gitlab-url")
+                        .help("GitLab instance to use (default: CI_SERVER_URL, then the origin's host)")
+                        .requires("gitlab")
+                        .value_name("URL"),
+                )
+                .arg(
+                    Arg::new("draft")
+                        .long("draft")
+                        .help("With --gitlab, mark the merge request as a draft ('Draft: ' title prefix)")
+                        .requires("gitlab")
+                        .action(ArgAction::SetTrue),
+                )
+                .group(ArgGroup::new("forge").args(["github", "gitlab"]))
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .help("With --github or --gitlab, show the request instead of sending it")
+                        .requires("forge")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("changelog")
+                .about("Write a markdown changelog for the commits since a tag or other ref")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .help("Start after this tag or ref (e.g. v1.3.0)")
+                        .required(true)
+                        .value_name("REF"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .help("End at this ref")
+                        .default_value("HEAD")
+                        .value_name("REF"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Write the markdown to FILE instead of stdout")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("update")
+                        .long("update")
+                        .help("Add the changelog to FILE (Keep a Changelog format) as a new release")
+                        .requires("release")
+                        .conflicts_with("output")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("release")
+                        .long("release")
+                        .help("With --update, the version of the new release (e.g. 1.5.0)")
+                        .requires("update")
+                        .value_name("VERSION"),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .help("With --update, print the diff of the file instead of writing it")
+                        .requires("update")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("release-notes")
+                .about("Write user-facing release notes for the commits since a tag or other ref")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .help("Start after this tag or ref (e.g. v1.3.0)")
+                        .required(true)
+                        .value_name("REF"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .help("End at this ref")
+                        .default_value("HEAD")
+                        .value_name("REF"),
+                )
+                .arg(
+                    Arg::new("audience")
+                        .long("audience")
+                        .help("Who the notes are for: users (what changed for them) or developers (APIs, options, migrations)")
+                        .value_parser(release_notes::Audience::NAMES)
+                        .default_value("users"),
+                )
+                .arg(
+                    Arg::new("heading")
+                        .long("heading")
+                        .help("Put a heading above the notes; {version} and {date} are filled in (e.g. '## {version} ({date})')")
+                        .value_name("TEMPLATE"),
+                )
+                .arg(
+                    Arg::new("release")
+                        .long("release")
+                        .help("Version for {version} in the heading (default: the --to ref)")
+                        .requires("heading")
+                        .value_name("VERSION"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Write the markdown to FILE instead of stdout")
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new("tag")
+                .about("Create an annotated tag on HEAD with a generated release summary")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("name")
+                        .help("Name of the tag (e.g. v2.0.0)")
+                        .required(true)
+                        .value_name("NAME"),
+                )
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .help("Summarize the commits after this ref (default: the previous tag reachable from HEAD)")
+                        .value_name("REF"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Replace an existing tag of the same name")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("sign")
+                        .short('s')
+                        .long("sign")
+                        .help("Sign the tag through 'git tag -s' (also when tag.gpgSign is set)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .help("Print the message without creating the tag")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("bump")
+                .about("Suggest the next semver version from the commits since the last tag")
+                .after_help(
+                    "A breaking change (`!` or a BREAKING CHANGE footer) means major, a feat means minor, \
+                     anything else patch. Before 1.0.0 the levels move one place down, as cargo reads 0.x \
+                     versions: a breaking change takes 0.3.0 to 0.4.0, a feat to 0.3.1.",
+                )
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("from")
+                        .long("from")
+                        .help("Classify the commits after this ref (default: the previous tag reachable from HEAD)")
+                        .value_name("REF"),
+                )
+                .arg(
+                    Arg::new("json")
+                        .long("json")
+                        .help("Print the result as JSON for release scripts")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("apply")
+                        .long("apply")
+                        .help("Write the suggested version to the [package] version in Cargo.toml")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("notes")
+                .about("Inspect the notes written by --note")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("show")
+                        .about("Show how a commit's message was generated")
+                        .arg(repo_arg())
+                        .arg(
+                            Arg::new("rev")
+                                .help("Commit to show (default: HEAD)")
+                                .default_value("HEAD")
+                                .value_name("REV"),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("stash")
+                .about("Stash the working tree changes under a generated one-line description")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("include_untracked")
+                        .short('u')
+                        .long("include-untracked")
+                        .help("Stash untracked files too, like 'git stash -u'")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("dry_run")
+                        .long("dry-run")
+                        .help("Print the description without stashing")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Explain in plain prose what a diff does, for reviewing it")
+                .arg(repo_arg())
+                .args(diff_source_args())
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Write the markdown to FILE instead of stdout")
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new("review")
+                .about("Ask for a quick review of a diff; exits non-zero when something looks wrong")
+                .arg(repo_arg())
+                .args(diff_source_args())
+                .arg(
+                    Arg::new("severity")
+                        .long("severity")
+                        .help("The least severe findings to show: error, or warn (errors and warnings)")
+                        .value_parser(code_review::Severity::NAMES)
+                        .default_value("warn"),
+                ),
+        )
+        .subcommand(
+            Command::new("standup")
+                .about("Summarize your recent commits by day, for a standup update")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("since")
+                        .long("since")
+                        .help("Start of the window: today, yesterday, '3 days ago', '2 weeks ago' or a date like 2024-05-01")
+                        .default_value("yesterday")
+                        .value_name("WHEN"),
+                )
+                .arg(
+                    Arg::new("author")
+                        .long("author")
+                        .help("Whose commits to include (defaults to each repository's user.email)")
+                        .value_name("EMAIL"),
+                )
+                .arg(
+                    Arg::new("recursive")
+                        .long("recursive")
+                        .help("Include every repository under --repo (up to three levels down)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .help("Write the summary to FILE instead of stdout")
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new("install-hook")
+                .about("Install a git hook that pre-fills the commit message in 'git commit'")
+                .arg(repo_arg())
+                .arg(hook_type_arg())
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Replace an existing hook, keeping it as <hook>.pre-git-changes and calling it first")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("merge_messages")
+                        .long("merge-messages")
+                        .help("Have the hook write merge commit messages too (runs it with --merge-messages)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("cherry_pick_messages")
+                        .long("cherry-pick-messages")
+                        .help("Have the hook rewrite cherry-pick messages too (runs it with --cherry-pick-messages)")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("uninstall-hook")
+                .about("Remove the hook installed by install-hook, restoring any hook it replaced")
+                .arg(repo_arg())
+                .arg(hook_type_arg()),
+        )
+        .subcommand(
+            Command::new("generate-man")
+                .about("Write the man pages, for packaging")
+                .hide(true)
+                .arg(
+                    Arg::new("out_dir")
+                        .long("out-dir")
+                        .help("Write git-changes-rs.1 and a page per subcommand here instead of printing the main page")
+                        .value_name("DIR"),
+                ),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a completion script for your shell (e.g., `git-changes-rs completions bash > ~/.local/share/bash-completion/completions/git-changes-rs`)")
+                .arg(
+                    Arg::new("shell")
+                        .help("The shell to complete for")
+                        .required(true)
+                        .value_parser(clap::value_parser!(Shell))
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            Command::new("config")
+                .about("Manage the configuration files")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("init")
+                        .about("Write a commented config.toml with every setting at its default")
+                        .arg(repo_arg())
+                        .arg(
+                            Arg::new("local")
+                                .long("local")
+                                .help("Write the repository's .git-changes.toml instead of the user's config.toml")
+                                .action(ArgAction::SetTrue),
+                        )
+                        .arg(
+                            Arg::new("force")
+                                .long("force")
+                                .help("Replace the file if it already exists")
+                                .action(ArgAction::SetTrue),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("history")
+                .about("List the messages generated lately, newest first, including the ones turned down")
+                .arg(repo_arg())
+                .arg(
+                    Arg::new("all")
+                        .long("all")
+                        .help("List the messages of every repository, not just this one")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("max_count")
+                        .short('n')
+                        .long("max-count")
+                        .help("How many messages to list")
+                        .value_name("N")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("10"),
+                )
+                .subcommand(
+                    Command::new("show")
+                        .about("Print a message from the history in full, e.g. for `git commit -F -`")
+                        .arg(
+                            Arg::new("id")
+                                .help("The id `history` lists, or enough of it to be unique")
+                                .required(true)
+                                .index(1),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the setup: config files, API key, network, repository and hook, with a hint for each problem")
+                .arg(repo_arg()),
+        )
+}
+
+// the command line as `completions` describes it: --model offers the models `models` listed
+// last, --types and --type the configured types. Only the script sees these lists; parsing
+// never does
+pub fn completion_command(models: &[String], types: &[String]) -> Command {
+    let hint = |values: &[String]| {
+        let values: Vec<PossibleValue> = values.iter().map(PossibleValue::new).collect();
+        move |arg: Arg| arg.value_parser(PossibleValuesParser::new(values.clone()))
+    };
+    let mut command = build()
+        .mut_arg("types", hint(types))
+        .mut_arg("type", hint(types));
+    if !models.is_empty() {
+        command = command.mut_arg("model", hint(models));
+    }
+    command.mut_subcommand("generate", |generate| {
+        generate
+            .mut_arg("types", hint(types))
+            .mut_arg("type", hint(types))
+    })
+}
+
+// defined once on the root and accepted before or after any subcommand
+fn global_args() -> [Arg; 11] {
+    [
+        Arg::new("provider")
+            .long("provider")
+            .help("Model provider to use (only gemini for now)")
+            .value_parser(PossibleValuesParser::new(provider::PROVIDERS.iter().copied()))
+            .default_value(provider::PROVIDERS[0])
+            .global(true),
+        Arg::new("model")
+            .long("model")
+            .help("Model id to use instead of the default (see the models subcommand)")
+            .value_name("MODEL")
+            .global(true),
+        Arg::new("config")
+            .long("config")
+            .help("Read settings from this file instead of the user's config.toml and the repository's .git-changes.toml")
+            .value_name("PATH")
+            .global(true),
+        Arg::new("verbose")
+            .short('v')
+            .long("verbose")
+            .help("Show what a run decides and each request; -vv adds details and prompts, -vvv timings (RUST_LOG overrides)")
+            .conflicts_with("quiet")
+            .action(ArgAction::Count)
+            .global(true),
+        Arg::new("quiet")
+            .short('q')
+            .long("quiet")
+            .help("Print only results, warnings and errors; for generate, just the message on stdout and no prompts")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("yes")
+            .short('y')
+            .long("yes")
+            .visible_alias("non-interactive")
+            .help("Never prompt: take the first candidate, skip the editor and the review prompt (implied without a terminal), and answer yes to --confirm")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("confirm")
+            .long("confirm")
+            .help("Before the first request, show the provider, model, size and files being sent and ask to go ahead; fails without a terminal unless --yes is given")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("no_color")
+            .long("no-color")
+            .help("Don't color the output (also with NO_COLOR set, or when it isn't a terminal)")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("ascii")
+            .long("ascii")
+            .help("Draw only ASCII: plain 'waiting...' lines instead of the spinner (also with TERM=dumb or a locale that isn't UTF-8)")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("timings")
+            .long("timings")
+            .help("At the end, print how long each phase took, with the bytes and tokens it handled, to stderr (also with -vv)")
+            .action(ArgAction::SetTrue)
+            .global(true),
+        Arg::new("chdir")
+            .short('C')
+            .help("Run as if started in PATH, like 'git -C'; the directory argument and --repo can then be left out")
+            .value_name("PATH")
+            .global(true),
+    ]
+}
+
+// the message generation flags; the root command takes them too, so `git-changes-rs .` works
+// as it did before there was a generate subcommand
+fn generate_args(command: Command) -> Command {
+    let directory = Arg::new("directory")
+        .help("Path to the git repository directory (default with -C, or as 'git changes': the current one)")
+        .index(1);
+    // `git changes` works on the current directory, like other git commands
+    let directory = match invocation::as_git_subcommand() {
+        true => directory,
+        false => directory.required_unless_present_any(["hook", "chdir"]),
+    };
+    command
+        .arg(directory)
+        .arg(
+            Arg::new("exclude")
+                .short('e')
+                .long("exclude")
+                .help("Glob patterns to exclude (e.g., '*.log', 'target/**'). Use commas or multiple args.")
+                .action(ArgAction::Append)
+                .value_delimiter(',')
+                .value_name("PATTERNS"),
+        )
+        .arg(
+            Arg::new("ignore_lines")
+                .long("ignore-lines")
+                .help("Regular expression for churn lines (e.g., '^// generated at: '): a removed and an added line it matches are left out of the prompt, and so are hunks with nothing else. Repeat for more; adds to diff.ignore_lines")
+                .action(ArgAction::Append)
+                .value_name("REGEX"),
+        )
+        .arg(
+            Arg::new("lang")
+                .long("lang")
+                .help("Language for the commit subject and body (e.g., 'pt-BR', 'Portuguese'). Type/scope tokens stay in English.")
+                .value_name("LANG"),
+        )
+        .arg(
+            Arg::new("style")
+                .long("style")
+                .help("Message style preset: concise, detailed (default), corporate, casual, or one defined in the config file")
+                .value_name("STYLE"),
+        )
+        .arg(
+            Arg::new("max_title_length")
+                .long("max-title-length")
+                .help("Hard cap for the subject line; longer subjects are cut and the rest moved into the body (default 72)")
+                .value_parser(clap::value_parser!(usize))
+                .value_name("CHARS"),
+        )
+        .arg(
+            Arg::new("no_validate")
+                .long("no-validate")
+                .help("Accept the generated message even if it doesn't follow conventional-commit rules")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("title_only")
+                .long("title-only")
+                .help("Generate only the subject line, without a body")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("per_file_bullets")
+                .long("per-file-bullets")
+                .help("Write one body bullet per changed file or area, each starting with its path")
+                .conflicts_with("title_only")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("types")
+                .long("types")
+                .help("Allowed conventional-commit types (e.g., 'feat,fix,perf'); overrides commit.types in config")
+                .value_delimiter(',')
+                .action(ArgAction::Append)
+                .value_name("TYPES"),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .help("Conventional-commit type the message has to use; one of the allowed types (see --types)")
+                .value_name("TYPE"),
+        )
+        .arg(
+            Arg::new("scope")
+                .long("scope")
+                .help("Scope the message has to use, as in 'fix(cli): ...'")
+                .value_parser(validate::parse_scope)
+                .value_name("SCOPE"),
+        )
+        .arg(
+            Arg::new("candidates")
+                .long("candidates")
+                .help("Generate several alternatives and pick one interactively (first one when not on a terminal)")
+                .value_parser(clap::value_parser!(u32).range(1..=8))
+                .value_name("N"),
+        )
+        .arg(
+            Arg::new("commit")
+                .long("commit")
+                .help("Create the commit from the staged changes with the generated message")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("add_all")
+                .short('a')
+                .long("add-all")
+                .help("Stage all modified and deleted tracked files before committing, like 'git commit -a'. Exclude patterns only filter the prompt; excluded files are still staged and committed.")
+                .requires("commit")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("per_file")
+                .long("per-file")
+                .help("One message per changed file, in path order; with --commit, one commit per file (without it, only print the plan)")
+                .conflicts_with_all([
+                    "amend",
+                    "candidates",
+                    "feedback",
+                    "fixup",
+                    "fixup_detect",
+                    "hook",
+                ])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("include_untracked")
+                .long("include-untracked")
+                .help("Describe new files that aren't ignored by .gitignore, .git/info/exclude or core.excludesFile along with the unstaged changes; with --add-all, stage them too")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_gitignore")
+                .long("no-gitignore")
+                .help("With --include-untracked, stage and describe ignored new files too, like 'git add -A --force'")
+                .requires("include_untracked")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("amend")
+                .long("amend")
+                .help("Replace HEAD with a newly described commit, folding in any staged changes")
+                .conflicts_with_all(["commit", "fixup", "fixup_detect", "feedback"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .help("With --amend, rewrite HEAD even if it is a merge or already pushed")
+                .requires("amend")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("allow_conflicts")
+                .long("allow-conflicts")
+                .help("Describe the changes even while a merge, rebase, cherry-pick or revert has unresolved conflicts (they still can't be committed)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("use_git_binary")
+                .long("use-git-binary")
+                .help("Commit through 'git commit -F -' and push through 'git push', so hooks, signing and credential settings apply (with --commit or --push)")
+                .requires("runs_git")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("sign")
+                .short('S')
+                .long("sign")
+                .help("Sign the commit even if commit.gpgsign is off (gpg.format, gpg.<format>.program and user.signingKey are honored)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("squash_of")
+                .long("squash-of")
+                .help("Describe the staged squash merge of BRANCH as one change (found automatically while .git/SQUASH_MSG exists)")
+                .conflicts_with_all(["amend", "per_file"])
+                .value_name("BRANCH"),
+        )
+        .arg(
+            Arg::new("cherry_pick_of")
+                .long("cherry-pick-of")
+                .help("Describe the staged changes as a cherry-pick of REV, keeping its substance and trailers and adding the 'cherry picked from' line (found automatically while .git/CHERRY_PICK_HEAD exists)")
+                .conflicts_with_all(["amend", "per_file", "squash_of"])
+                .value_name("REV"),
+        )
+        .arg(
+            Arg::new("note")
+                .long("note")
+                .help("Record the model, prompt version, token counts and whether you edited the message in a note under refs/notes/git-changes")
+                .requires("creates_commit")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("push")
+                .long("push")
+                .help("Push the branch after committing, to its upstream or else to origin with --set-upstream")
+                .requires("creates_commit")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force_with_lease")
+                .long("force-with-lease")
+                .help("With --push, force the push unless the remote branch moved since it was fetched (needed for --amend --push)")
+                .requires("push")
+                .action(ArgAction::SetTrue),
+        )
+        .group(
+            ArgGroup::new("creates_commit")
+                .args(["commit", "amend"])
+                .multiple(true),
+        )
+        // what --use-git-binary hands to git: the commit, the push, or both
+        .group(
+            ArgGroup::new("runs_git")
+                .args(["commit", "push"])
+                .multiple(true),
+        )
+        .arg(
+            Arg::new("edit")
+                .long("edit")
+                .help("Open the generated message in your editor before it is used ('#' lines are dropped; an empty message aborts)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("hook")
+                .long("hook")
+                .help("Run as a prepare-commit-msg hook: fill MSGFILE from the staged diff unless git already has a message (never fails the commit)")
+                .num_args(1..=3)
+                .value_names(["MSGFILE", "SOURCE", "SHA"])
+                .conflicts_with_all(["commit", "amend", "add_all", "edit"]),
+        )
+        .arg(
+            Arg::new("merge_messages")
+                .long("merge-messages")
+                .help("With --hook, write a message for merge commits too instead of keeping git's 'Merge branch' line")
+                .requires("hook")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("cherry_pick_messages")
+                .long("cherry-pick-messages")
+                .help("With --hook, rewrite the message of a cherry-pick being committed instead of keeping the original's")
+                .requires("hook")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Also write the final message to PATH (atomically, owner-only permissions); '-' prints only the message on stdout, like -q but keeping the progress lines")
+                .conflicts_with("hook")
+                .value_name("PATH"),
+        )
+        .arg(
+            Arg::new("no_trailing_newline")
+                .long("no-trailing-newline")
+                .help("End the bare message (-q, '--output', a pipe) without the one newline it otherwise ends with")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("null")
+                .short('z')
+                .long("null")
+                .help("End each bare message with a NUL instead of a newline, for 'xargs -0' and other readers of NUL-separated input")
+                .conflicts_with("no_trailing_newline")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("allow_comment_lines")
+                .long("allow-comment-lines")
+                .help("Keep lines starting with '#' as they are; otherwise they get a leading '\\' so git doesn't strip them as comments")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Print one JSON object with the message, its conventional parts, the files, model, token usage and timing, and nothing else on stdout (errors become a JSON object too)")
+                .conflicts_with_all(["hook", "per_file", "format"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("How to print the result: plain (default), markdown (a heading, the body, a collapsed table of the changed files and the model as a footnote), json (same as --json) or yaml (the --json document as YAML)")
+                .value_parser(["plain", "markdown", "json", "yaml"])
+                .conflicts_with_all(["hook", "per_file"])
+                .value_name("FORMAT"),
+        )
+        .arg(
+            Arg::new("format_string")
+                .long("format-string")
+                .help("Print the message through a template such as '{type}: {subject}\\n\\n{body}'; placeholders: {type}, {scope}, {subject}, {title}, {body}, {trailers}, {message}, {breaking}, {files}, {model}, {tokens_in}, {tokens_out} ('{{' and '}}' for literal braces)")
+                .value_parser(Template::parse)
+                .conflicts_with_all(["hook", "per_file", "json", "format"])
+                .value_name("TEMPLATE"),
+        )
+        .arg(
+            Arg::new("select")
+                .long("select")
+                .help("Choose from a checklist which of the changed files the message is about (needs a terminal)")
+                .conflicts_with("hook")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("select_hunks")
+                .long("select-hunks")
+                .help("Go through the changed hunks like 'git add -p' and describe only the ones you keep (needs a terminal)")
+                .conflicts_with("hook")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("remember")
+                .long("remember")
+                .help("With --select, reuse the files chosen last time on this branch instead of asking, and remember the first choice")
+                .requires("select")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("force_large")
+                .long("force-large")
+                .help("Send the diff even when it's over diff.max_files or diff.max_kb, without asking")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("show_prompt")
+                .long("show-prompt")
+                .help("Print the system prompt, the prompt and the generation config that would be sent, and stop before the request")
+                .conflicts_with_all(["hook", "per_file", "json", "format"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("print_config")
+                .long("print-config")
+                .help("Print the settings this run would use as TOML, each with where it came from (flag, environment, repository or user config, or default), and stop")
+                .conflicts_with_all(["hook", "per_file", "json", "show_prompt"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("show_diff")
+                .long("show-diff")
+                .help("Show the diff the model would see (through core.pager or $PAGER at a terminal), with the excluded files listed at the end, and stop; with --confirm, show it on the terminal and go on to ask whether to send it")
+                .conflicts_with_all(["hook", "per_file", "json", "format", "show_prompt", "print_config"])
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("copy")
+                .long("copy")
+                .help("Copy the final message to the system clipboard (still printed; only a warning if no clipboard is available)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("feedback")
+                .long("feedback")
+                .help("Revise the last suggestion for the same diff instead of starting over (e.g., 'mention the config migration')")
+                .conflicts_with("candidates")
+                .value_name("TEXT"),
+        )
+        .arg(
+            Arg::new("no_cache")
+                .long("no-cache")
+                .help("Ask the model again even when the last message was generated for the same diff, style, language, model and attachments")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("deterministic")
+                .long("deterministic")
+                .help("Use greedy sampling with a fixed seed so repeated runs give the same message (best-effort)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("signoff")
+                .long("signoff")
+                .help("Append a Signed-off-by trailer using user.name and user.email from git config")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("co_author")
+                .long("co-author")
+                .help("Append a Co-authored-by trailer ('Name <email>'); repeatable")
+                .action(ArgAction::Append)
+                .value_parser(trailers::parse_identity)
+                .value_name("IDENTITY"),
+        )
+        .arg(
+            Arg::new("attribution")
+                .long("attribution")
+                .help("Append a trailer disclosing that the message was AI-generated (wording set in config)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("rust_api")
+                .long("rust-api")
+                .help("Summarize public Rust API changes for the model (default: on when Cargo.toml is present)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("no_rust_api")
+                .long("no-rust-api")
+                .help("Skip the public Rust API analysis")
+                .conflicts_with("rust_api")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fixup_detect")
+                .long("fixup-detect")
+                .help("Suggest 'fixup! <subject>' when the changes mostly touch files of one of the last 10 commits")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("fixup")
+                .long("fixup")
+                .help("Write a 'fixup! <subject>' message for the given commit instead of generating one")
+                .conflicts_with("fixup_detect")
+                .value_name("REV"),
+        )
+        .arg(
+            Arg::new("no_revert_detect")
+                .long("no-revert-detect")
+                .help("Don't check whether the changes undo a recent commit")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("smart_context")
+                .long("smart-context")
+                .help("Annotate each hunk with the function or item it is in (Rust, JavaScript/TypeScript, Python)")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("tree_context")
+                .long("tree-context")
+                .help("Include a compact tree of the tracked files (top two levels, capped at 2 KB) in the prompt")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("attach")
+                .long("attach")
+                .help("Include a text file (test output, logs) as supporting context; repeatable. Large files are truncated.")
+                .action(ArgAction::Append)
+                .value_name("PATH"),
+        )
+}
+
+// --repo option shared by the subcommands, which otherwise work on the current directory
+fn repo_arg() -> Arg {
+    Arg::new("repo")
+        .long("repo")
+        .help("Path to the git repository directory")
+        .default_value(".")
+        .value_name("PATH")
+}
+
+// which changes a reading subcommand (explain, review) looks at
+fn diff_source_args() -> [Arg; 4] {
+    [
+        Arg::new("staged")
+            .long("staged")
+            .help("Read the staged changes only (default: unstaged changes, else staged ones)")
+            .conflicts_with("from")
+            .action(ArgAction::SetTrue),
+        Arg::new("from")
+            .long("from")
+            .help("Read the changes since this ref's merge base with --to, like 'git diff REF...'")
+            .value_name("REF"),
+        Arg::new("to")
+            .long("to")
+            .help("With --from, the end of the range")
+            .requires("from")
+            .default_value("HEAD")
+            .value_name("REF"),
+        Arg::new("exclude")
+            .short('e')
+            .long("exclude")
+            .help("Glob patterns to exclude (e.g., '*.log', 'target/**'), on top of Cargo.lock and diff.exclude from the config")
+            .action(ArgAction::Append)
+            .value_delimiter(',')
+            .value_name("PATTERNS"),
+    ]
+}
+
+// only prepare-commit-msg for now; other hooks take different arguments
+fn hook_type_arg() -> Arg {
+    Arg::new("type")
+        .long("type")
+        .help("Which hook to manage")
+        .value_parser([hook::DEFAULT_HOOK])
+        .default_value(hook::DEFAULT_HOOK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parses(args: &[&str]) -> ArgMatches {
+        let mut all = vec!["git-changes-rs"];
+        all.extend(args);
+        build()
+            .try_get_matches_from(all)
+            .unwrap_or_else(|e| panic!("{:?} didn't parse: {}", args, e))
+    }
+
+    fn fails(args: &[&str]) -> ErrorKind {
+        let mut all = vec!["git-changes-rs"];
+        all.extend(args);
+        build().try_get_matches_from(all).unwrap_err().kind()
+    }
+
+    #[test]
+    fn the_command_line_is_consistent() {
+        build().debug_assert();
+        completion_command(&["gemini-2.0-flash".to_string()], &["feat".to_string()]).debug_assert();
+    }
+
+    #[test]
+    fn the_root_still_generates_without_a_subcommand() {
+        let matches = parses(&[".", "--commit", "--types", "feat,fix"]);
+        assert!(matches.subcommand().is_none());
+        assert_eq!(matches.get_one::<String>("directory").unwrap(), ".");
+        assert!(matches.get_flag("commit"));
+        assert_eq!(fails(&[]), ErrorKind::MissingRequiredArgument);
+        assert!(parses(&["-C", "elsewhere"])
+            .get_one::<String>("directory")
+            .is_none());
+    }
+
+    #[test]
+    fn generate_takes_the_same_flags_as_the_root() {
+        let root = build();
+        let generate = root.find_subcommand("generate").unwrap();
+        let ids = |command: &Command| -> Vec<String> {
+            let mut ids: Vec<String> = command
+                .get_arguments()
+                .filter(|arg| !arg.is_global_set())
+                .map(|arg| arg.get_id().to_string())
+                .filter(|id| id != "help" && id != "version")
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids(&root), ids(generate));
+
+        let matches = parses(&["generate", ".", "--commit"]);
+        let (name, sub) = matches.subcommand().unwrap();
+        assert_eq!(name, "generate");
+        assert!(sub.get_flag("commit"));
+    }
+
+    #[test]
+    fn global_flags_go_before_or_after_a_subcommand() {
+        for args in [
+            ["--model", "m", "-vv", "lint"],
+            ["lint", "--model", "m", "-vv"],
+        ] {
+            let matches = parses(&args);
+            let (name, sub) = matches.subcommand().unwrap();
+            assert_eq!(name, "lint");
+            assert_eq!(sub.get_one::<String>("model").unwrap(), "m");
+            assert_eq!(sub.get_count("verbose"), 2);
+        }
+        let matches = parses(&["models", "--config", "c.toml", "--yes", "-C", "dir"]);
+        let (_, sub) = matches.subcommand().unwrap();
+        assert_eq!(sub.get_one::<String>("config").unwrap(), "c.toml");
+        assert!(sub.get_flag("yes"));
+        assert_eq!(sub.get_one::<String>("chdir").unwrap(), "dir");
+        assert_eq!(
+            sub.get_one::<String>("provider").unwrap(),
+            provider::PROVIDERS[0]
+        );
+    }
+
+    #[test]
+    fn every_global_arg_is_listed_as_one() {
+        let globals: Vec<String> = build()
+            .get_arguments()
+            .filter(|arg| arg.is_global_set())
+            .map(|arg| arg.get_id().to_string())
+            .collect();
+        assert_eq!(globals, GLOBAL_ARGS);
+    }
+
+    #[test]
+    fn subcommands_check_their_own_arguments() {
+        assert_eq!(
+            fails(&["--provider", "nope", "models"]),
+            ErrorKind::InvalidValue
+        );
+        assert_eq!(fails(&["-v", "-q", "lint"]), ErrorKind::ArgumentConflict);
+        assert_eq!(fails(&["lint", "--commit"]), ErrorKind::UnknownArgument);
+        // a word that isn't a subcommand is the directory, as it always was
+        let matches = parses(&["no-such-command"]);
+        assert!(matches.subcommand().is_none());
+        assert_eq!(
+            matches.get_one::<String>("directory").unwrap(),
+            "no-such-command"
+        );
+        let matches = parses(&["lint", "origin/main..HEAD", "--repo", "r"]);
+        let (_, sub) = matches.subcommand().unwrap();
+        assert_eq!(sub.get_one::<String>("range").unwrap(), "origin/main..HEAD");
+        assert_eq!(sub.get_one::<String>("repo").unwrap(), "r");
+        let matches = parses(&["split"]);
+        let (_, sub) = matches.subcommand().unwrap();
+        assert_eq!(sub.get_one::<String>("repo").unwrap(), ".");
+    }
+}
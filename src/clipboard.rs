@@ -0,0 +1,23 @@
+// src/clipboard.rs
+// --copy: putting the final message on the system clipboard
+
+use anyhow::Result;
+
+// on X11 and Wayland the text stays available after we exit only if a clipboard manager
+// takes it over, which arboard asks for when the handle is dropped
+#[cfg(feature = "clipboard")]
+pub fn copy(message: &str) -> Result<()> {
+    use anyhow::Context;
+
+    let mut clipboard = arboard::Clipboard::new().context("no clipboard is available")?;
+    clipboard
+        .set_text(message.trim_end())
+        .context("failed to copy the message to the clipboard")
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub fn copy(_message: &str) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "this build has no clipboard support (rebuild with the 'clipboard' feature)"
+    ))
+}
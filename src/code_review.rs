@@ -0,0 +1,167 @@
+// src/code_review.rs
+// review: asking a reviewer persona for concrete problems in the diff and reporting them
+// per file; nothing here is shared with commit message generation
+
+use crate::attach;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+const MAX_REVIEW_DIFF_BYTES: usize = 64 * 1024;
+
+pub const REVIEW_MAX_OUTPUT_TOKENS: u32 = 2048;
+
+pub const REVIEW_SYSTEM_PROMPT: &str = "You are a careful code reviewer doing a quick pass before a human review. Look only for concrete problems in the changed lines: likely bugs, missing or swallowed error handling, leftover debug prints or commented-out code, TODO or FIXME comments being introduced, and tests that should have been updated but weren't. Do not comment on style or naming, and do not praise. Every line of the diff you are given starts with its line number in the new file (removed lines have none). Reply with a JSON array only, no prose: one object per problem with `file` (the path as shown in the diff), `line` (the number from the start of the line you mean), `severity` (`error` for something that is almost certainly wrong, `warn` for anything worth a second look) and `message` (one or two sentences). Reply with `[]` when there is nothing to report.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warn,
+    Error,
+}
+
+impl Severity {
+    pub const NAMES: [&'static str; 2] = ["error", "warn"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "error" | "err" | "bug" => Some(Severity::Error),
+            "warn" | "warning" | "info" | "note" => Some(Severity::Warn),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub file: String,
+    pub line: Option<u64>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+struct RawFinding {
+    file: Option<String>,
+    line: Option<serde_json::Value>,
+    severity: Option<String>,
+    message: Option<String>,
+}
+
+// the diff with each new-side line prefixed by its line number, so findings can quote them
+pub fn number_lines(diff: &str) -> String {
+    let mut numbered = String::with_capacity(diff.len() + diff.len() / 4);
+    let mut next_line: Option<u64> = None;
+    for line in diff.lines() {
+        if let Some(header) = line.strip_prefix("@@ ") {
+            // @@ -a,b +c,d @@
+            next_line = header
+                .split_whitespace()
+                .find_map(|part| part.strip_prefix('+'))
+                .and_then(|new| new.split(',').next())
+                .and_then(|start| start.parse().ok());
+            let _ = writeln!(numbered, "{}", line);
+            continue;
+        }
+        match (next_line, line.chars().next()) {
+            (Some(n), Some(' ') | Some('+')) if !line.starts_with("+++") => {
+                let _ = writeln!(numbered, "{:>6} {}", n, line);
+                next_line = Some(n + 1);
+            }
+            (Some(_), Some('-')) if !line.starts_with("---") => {
+                let _ = writeln!(numbered, "{:>6} {}", "", line);
+            }
+            _ => {
+                let _ = writeln!(numbered, "{}", line);
+            }
+        }
+    }
+    numbered
+}
+
+pub fn review_prompt(diff: &str, files: &[String]) -> String {
+    let listed: Vec<String> = files.iter().map(|f| format!("- {}", f)).collect();
+    format!(
+        "Review these changes.\n\nChanged files:\n{}\n\n```diff\n{}\n```",
+        listed.join("\n"),
+        attach::truncate_middle(&number_lines(diff), MAX_REVIEW_DIFF_BYTES)
+    )
+}
+
+// the model's JSON, ignoring code fences; findings about files that aren't in the diff are
+// dropped, since there is nowhere to jump to
+pub fn parse_findings(response: &str, files: &[String]) -> Result<Vec<Finding>> {
+    let text = response.trim();
+    let start = text.find('[');
+    let end = text.rfind(']');
+    let json = match (start, end) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => return Err(anyhow!("the review isn't a JSON array: {}", text)),
+    };
+    let raw: Vec<RawFinding> = serde_json::from_str(json)
+        .map_err(|e| anyhow!("failed to parse the review ({}): {}", e, text))?;
+
+    Ok(raw
+        .into_iter()
+        .filter_map(|finding| {
+            let file = finding.file?.trim().trim_start_matches("b/").to_string();
+            let message = finding.message?.trim().to_string();
+            if message.is_empty() || !files.contains(&file) {
+                return None;
+            }
+            let line = match finding.line? {
+                serde_json::Value::Number(n) => n.as_u64(),
+                serde_json::Value::String(s) => s.trim().trim_start_matches('L').parse().ok(),
+                _ => None,
+            };
+            let severity = finding
+                .severity
+                .as_deref()
+                .and_then(Severity::from_name)
+                .unwrap_or(Severity::Warn);
+            Some(Finding {
+                file,
+                line,
+                severity,
+                message,
+            })
+        })
+        .collect())
+}
+
+// findings at or above `min`, grouped by file in diff order and by line within a file
+pub fn render(findings: &[Finding], files: &[String], min: Severity) -> String {
+    let mut by_file: BTreeMap<usize, Vec<&Finding>> = BTreeMap::new();
+    for finding in findings.iter().filter(|f| f.severity >= min) {
+        let position = files.iter().position(|f| *f == finding.file).unwrap_or(0);
+        by_file.entry(position).or_default().push(finding);
+    }
+
+    let mut out = String::new();
+    for (position, mut findings) in by_file {
+        findings.sort_by_key(|f| f.line);
+        let _ = writeln!(out, "{}", files[position]);
+        for finding in findings {
+            let line = finding.line.map(|n| format!("L{}", n)).unwrap_or_default();
+            let _ = writeln!(
+                out,
+                "  {:>6}  {:<5}  {}",
+                line,
+                finding.severity.label(),
+                finding.message
+            );
+        }
+        out.push('\n');
+    }
+    if !findings.iter().any(|f| f.severity == Severity::Error) {
+        out.push_str("no blocking issues found\n");
+    }
+    out
+}
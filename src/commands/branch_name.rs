@@ -0,0 +1,53 @@
+// src/commands/branch_name.rs
+// branch-name: a branch name for the changes
+
+use super::excludes;
+use crate::{
+    branch, config, diff::fetch_diffs, diff::DiffMode, diff::Untracked, invocation, prompt,
+    provider, provider::send_to_gemini, validate,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let types =
+        validate::resolve_types(config.commit.types.clone()).context("invalid commit types")?;
+
+    let excludes = excludes(&config, []);
+    let changes = fetch_diffs(&repo, &excludes, DiffMode::Auto, Untracked::None)
+        .context("failed to fetch diffs")?;
+    if changes.diff.trim().is_empty() {
+        return Err(anyhow!("no changes to name a branch after"));
+    }
+
+    let client = provider::client(&config.generation)?;
+    let response = send_to_gemini(
+        &client,
+        branch::BRANCH_SYSTEM_PROMPT,
+        &branch::branch_prompt(&changes.diff, &types),
+        prompt::TITLE_ONLY_MAX_OUTPUT_TOKENS,
+        1,
+    )
+    .await
+    .context("failed to fetch response from gemini api")?
+    .remove(0);
+    let name = branch::clean_name(&response, &types).with_context(|| {
+        format!(
+            "the model didn't suggest a usable branch name: {}",
+            response.trim()
+        )
+    })?;
+
+    if sub.get_flag("create") {
+        branch::create(&repo, &name)?;
+        status!("switched to a new branch '{}'", name);
+    } else {
+        outln!("{}", name)?;
+    }
+    Ok(())
+}
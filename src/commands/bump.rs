@@ -0,0 +1,65 @@
+// src/commands/bump.rs
+// bump: the next semver version from the commits since the last tag
+
+use crate::{bump, changelog, history, invocation, output, tag};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use tracing::info;
+
+pub fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+
+    let from = match sub.get_one::<String>("from") {
+        Some(from) => Some(from.clone()),
+        None => tag::previous_tag(&repo, None)?,
+    };
+    let entries = changelog::range_entries(&repo, from.as_deref(), "HEAD")?;
+    let api_breaks = match &from {
+        Some(from) => bump::api_breaks(
+            &repo,
+            history::resolve(&repo, from)?,
+            history::resolve(&repo, "HEAD")?,
+        )?,
+        None => Vec::new(),
+    };
+    let (level, reasons) = bump::classify(&entries, &api_breaks);
+
+    let (current, source) = bump::current_version(repo.workdir(), from.as_deref())?;
+    let next = current.bump(level);
+
+    if sub.get_flag("json") {
+        let report = bump::json_report(&current, &source, from.as_deref(), level, &next, &reasons);
+        outln!("{:#}", report)?;
+    } else {
+        outln!("current version: {} ({})", current, source)?;
+        let since = from
+            .as_deref()
+            .map(|f| format!(", since {}", f))
+            .unwrap_or_default();
+        outln!("next version:    {} ({}{})", next, level.name(), since)?;
+        outln!("because:")?;
+        for reason in &reasons {
+            match &reason.short_id {
+                Some(id) => outln!("  {} {} ({})", id, reason.description, reason.why)?,
+                None => outln!("  {} ({})", reason.description, reason.why)?,
+            }
+        }
+    }
+
+    if sub.get_flag("apply") {
+        let manifest = repo
+            .workdir()
+            .map(|dir| dir.join("Cargo.toml"))
+            .filter(|path| path.is_file())
+            .context("--apply needs a Cargo.toml at the top of the working tree")?;
+        let text = std::fs::read_to_string(&manifest)
+            .with_context(|| format!("failed to read {}", manifest.display()))?;
+        let updated = bump::set_package_version(&text, &next.to_string())?;
+        output::replace_file(&manifest, &updated)?;
+        info!("set the version in {} to {}", manifest.display(), next);
+    }
+    Ok(())
+}
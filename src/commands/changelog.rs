@@ -0,0 +1,94 @@
+// src/commands/changelog.rs
+// changelog: a markdown changelog for a range of commits
+
+use crate::{
+    changelog, changelog_file, config, invocation, output, provider, provider::send_to_gemini,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+use std::path::Path;
+use tracing::{info, warn};
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let from = sub.get_one::<String>("from").context("from is required")?;
+    let to = sub.get_one::<String>("to").context("to has a default")?;
+
+    // the file is read before anything is generated, so a release that is already in it
+    // costs no API call
+    let update = match sub.get_one::<String>("update") {
+        Some(path) => {
+            let release = sub
+                .get_one::<String>("release")
+                .context("--update needs --release")?;
+            let text = changelog_file::read(Path::new(path))?;
+            let file = changelog_file::ChangelogFile::parse(&text);
+            if file.has_release(release) {
+                return Err(anyhow!("{} is already in {}", release, path));
+            }
+            Some((Path::new(path), release, text, file))
+        }
+        None => None,
+    };
+
+    let entries = changelog::range_entries(&repo, Some(from), to)?;
+    let sections = changelog::group(&entries);
+    let summary: Vec<String> = sections
+        .iter()
+        .map(|(section, entries)| format!("{} {}", entries.len(), section.title()))
+        .collect();
+    info!(
+        "{} commits from {} to {} ({})",
+        entries.len(),
+        from,
+        to,
+        summary.join(", ")
+    );
+
+    let client = provider::client(&config.generation)?;
+    let response = send_to_gemini(
+        &client,
+        changelog::CHANGELOG_SYSTEM_PROMPT,
+        &changelog::changelog_prompt(from, to, &sections),
+        changelog::CHANGELOG_MAX_OUTPUT_TOKENS,
+        1,
+    )
+    .await
+    .context("failed to fetch response from gemini api")?
+    .remove(0);
+    let markdown = response.trim();
+    if markdown.is_empty() {
+        return Err(anyhow!("the model returned an empty changelog"));
+    }
+
+    if let Some((path, release, old, mut file)) = update {
+        file.add_release(release, &changelog_file::today(), markdown)?;
+        if !file.update_links(release) && !file.links.is_empty() {
+            warn!(
+                "no `[unreleased]: .../<tag>...HEAD` link in {}; the links were left as they are",
+                path.display()
+            );
+        }
+        let new = file.serialize();
+        if sub.get_flag("dry_run") {
+            out!("{}", changelog_file::diff(path, &old, &new)?)?;
+        } else {
+            output::replace_file(path, &new)?;
+            info!("added {} to {}", release, path.display());
+        }
+        return Ok(());
+    }
+
+    match sub.get_one::<String>("output") {
+        Some(path) => {
+            output::write_message(Path::new(path), markdown)?;
+            info!("wrote the changelog to {}", path);
+        }
+        None => outln!("{}", markdown)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,27 @@
+// src/commands/completions.rs
+// completions: a completion script for a shell, on stdout; needs neither a repository nor a key
+
+use crate::{cache, cli, config, validate};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use clap_complete::Shell;
+use git2::Repository;
+use std::io;
+
+pub fn run(sub: &ArgMatches) -> Result<()> {
+    let shell = *sub.get_one::<Shell>("shell").context("shell is required")?;
+    // the types configured for the repository it's run in, or the defaults
+    let types = Repository::discover(".")
+        .ok()
+        .and_then(|repo| config::load(&repo).ok())
+        .and_then(|config| config.commit.types)
+        .unwrap_or_else(|| {
+            validate::DEFAULT_TYPES
+                .iter()
+                .map(|t| t.to_string())
+                .collect()
+        });
+    let mut command = cli::completion_command(&cache::load_models(), &types);
+    clap_complete::generate(shell, &mut command, "git-changes-rs", &mut io::stdout());
+    Ok(())
+}
@@ -0,0 +1,183 @@
+// src/commands/config.rs
+// config init, a commented template to start from, and --print-config, the settings a run
+// would use with where each one came from
+
+use crate::config::{self, Kind, Layer, Setting, SETTINGS};
+use crate::invocation;
+use anyhow::{anyhow, Context, Result};
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use git2::Repository;
+use std::env;
+use std::fs;
+use toml::Value;
+
+// read from the environment only, so --print-config just says whether they're set
+const CREDENTIALS: [&str; 3] = ["GEMINI_API_KEY", "GITHUB_TOKEN", "GITLAB_TOKEN"];
+
+const STYLES_EXAMPLE: &str = "
+# extra style presets; one with a built-in name replaces it
+# [styles.release]
+# prompt = \"The title should summarize the release-relevant change.\"
+# prompt_file = \"prompts/release.txt\"
+# max_output_tokens = 384
+";
+
+pub fn run(sub: &ArgMatches) -> Result<()> {
+    let Some(("init", sub)) = sub.subcommand() else {
+        return Err(anyhow!("config needs a subcommand (init)"));
+    };
+    let path = if sub.get_flag("local") {
+        let directory = sub
+            .get_one::<String>("repo")
+            .context("repo has a default")?;
+        let repo = invocation::open(directory).context("failed to open git repository")?;
+        let workdir = repo
+            .workdir()
+            .context("a bare repository has no working tree to hold .git-changes.toml")?;
+        workdir.join(config::REPO_CONFIG_FILE)
+    } else {
+        config::user_path().context("no config directory (HOME isn't set)")?
+    };
+    if path.exists() && !sub.get_flag("force") {
+        return Err(anyhow!(
+            "{} already exists (use --force to replace it)",
+            path.display()
+        ));
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create directory {}", dir.display()))?;
+    }
+    fs::write(&path, template()).with_context(|| format!("failed to write {}", path.display()))?;
+    status!("wrote {}", path.display());
+    Ok(())
+}
+
+// every setting commented out at its default, so the file changes nothing until edited
+fn template() -> String {
+    let mut out = String::from("# git-changes-rs configuration; uncomment a key to change it\n");
+    let mut current = "";
+    for setting in &SETTINGS {
+        let (section, field) = split_key(setting);
+        if section != current {
+            out.push_str(&format!("\n[{}]\n", section));
+            current = section;
+        }
+        let value = (setting.default)()
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| setting.example.to_string());
+        out.push_str(&format!("# {} (${})\n", setting.help, setting.env));
+        out.push_str(&format!("# {} = {}\n", field, value));
+    }
+    out.push_str(STYLES_EXAMPLE);
+    out
+}
+
+// --print-config: the settings that apply in `repo`, as TOML with each one's origin
+pub fn print(repo: &Repository, matches: &ArgMatches) -> Result<()> {
+    let layers = config::layers(repo)?;
+    let resolved = config::resolve(&layers)?;
+    let place = repo.workdir().unwrap_or(repo.path());
+    let mut out = format!("# settings for {}\n", place.display());
+    let mut current = "";
+    for setting in &SETTINGS {
+        let (section, field) = split_key(setting);
+        if section != current {
+            out.push_str(&format!("\n[{}]\n", section));
+            current = section;
+        }
+        let (value, origin) = effective(setting, &layers, matches)?;
+        match value {
+            Some(value) => out.push_str(&format!("{} = {}  # {}\n", field, value, origin)),
+            None => out.push_str(&format!("# {} is unset  # {}\n", field, origin)),
+        }
+    }
+    for (name, style) in &resolved.styles {
+        let key = format!("styles.{}", name);
+        let origin = layers
+            .iter()
+            .rev()
+            .find(|layer| config::lookup(&layer.table, &key).is_some())
+            .map_or_else(|| "default".to_string(), layer_origin);
+        out.push_str(&format!("\n[{}]  # {}\n", key, origin));
+        out.push_str(&format!(
+            "prompt = {}\n",
+            Value::String(style.prompt.clone())
+        ));
+        if let Some(max_output_tokens) = style.max_output_tokens {
+            out.push_str(&format!("max_output_tokens = {}\n", max_output_tokens));
+        }
+    }
+    out.push_str("\n# credentials come from the environment and are never shown\n");
+    for name in CREDENTIALS {
+        let set = env::var_os(name).is_some_and(|value| !value.is_empty());
+        out.push_str(&format!(
+            "# {}: {}\n",
+            name,
+            if set { "set" } else { "not set" }
+        ));
+    }
+    out!("{}", out)?;
+    Ok(())
+}
+
+fn split_key(setting: &Setting) -> (&'static str, &'static str) {
+    setting.key.split_once('.').unwrap_or(("", setting.key))
+}
+
+fn layer_origin(layer: &Layer) -> String {
+    format!("{} {}", layer.origin, layer.path.display())
+}
+
+// the value that applies and where it came from, in the order the loader applies them: the
+// flag, the environment, the repository's file, the user's file, the default
+fn effective(
+    setting: &Setting,
+    layers: &[Layer],
+    matches: &ArgMatches,
+) -> Result<(Option<Value>, String)> {
+    let flag = flag_value(setting, matches);
+    if let (Some(value), false) = (&flag, setting.flag_appends) {
+        return Ok((Some(value.clone()), flag_name(setting)));
+    }
+    let (value, origin) = match config::env_override(setting)? {
+        Some(value) => (Some(value), setting.env.to_string()),
+        None => layers
+            .iter()
+            .rev()
+            .find_map(|layer| {
+                config::lookup(&layer.table, setting.key)
+                    .map(|value| (Some(value.clone()), layer_origin(layer)))
+            })
+            .unwrap_or_else(|| ((setting.default)(), "default".to_string())),
+    };
+    match (flag, value) {
+        (Some(Value::Array(added)), Some(Value::Array(mut values))) => {
+            values.extend(added);
+            let origin = format!("{} and {}", origin, flag_name(setting));
+            Ok((Some(Value::Array(values)), origin))
+        }
+        (_, value) => Ok((value, origin)),
+    }
+}
+
+fn flag_name(setting: &Setting) -> String {
+    format!("--{}", setting.flag.unwrap_or_default().replace('_', "-"))
+}
+
+// what the setting's flag was given as, read the way the environment layer reads a variable
+fn flag_value(setting: &Setting, matches: &ArgMatches) -> Option<Value> {
+    let id = setting.flag?;
+    if matches.value_source(id) != Some(ValueSource::CommandLine) {
+        return None;
+    }
+    if let Kind::Bool = setting.kind {
+        return Some(Value::Boolean(matches.get_flag(id)));
+    }
+    let raw: Vec<String> = matches
+        .get_raw(id)?
+        .map(|value| value.to_string_lossy().into_owned())
+        .collect();
+    config::parse_value(&raw.join(","), setting.kind)
+}
@@ -0,0 +1,342 @@
+// src/commands/doctor.rs
+// doctor: the checks for "it doesn't work", one line each with what to do about a failure. Each
+// check gets what it looks at passed in, so none of them reads the environment on its own
+
+use crate::{config, hook, invocation, provider, style};
+#[cfg(feature = "gemini")]
+use crate::{exit, provider::create_http_client, provider::list_models, provider::models_url};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+use git2::{ErrorCode, Repository, RepositoryState};
+#[cfg(feature = "gemini")]
+use reqwest::Client;
+use std::env;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Pass,
+    // not a failure, but worth knowing about
+    Note,
+    // generating a message won't work until this is fixed
+    Fail,
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            outcome: Outcome::Pass,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    fn note(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            outcome: Outcome::Note,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, hint: impl Into<String>) -> Self {
+        Check {
+            name,
+            outcome: Outcome::Fail,
+            detail: detail.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+pub async fn run(sub: &ArgMatches, root: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory);
+    let (config_check, configured_provider) = config_files(repo.as_ref().ok());
+    let mut providers = vec![root
+        .get_one::<String>("provider")
+        .context("provider has a default")?
+        .clone()];
+    providers.extend(configured_provider.filter(|p| !providers.contains(p)));
+
+    let mut checks = vec![version(env::current_exe().ok().as_deref()), config_check];
+    for provider in &providers {
+        checks.push(provider_build(provider));
+        let variable = key_variable(provider);
+        checks.push(api_key(variable, env::var(variable).ok().as_deref()));
+    }
+    #[cfg(feature = "gemini")]
+    {
+        let key = env::var(key_variable("gemini")).ok();
+        checks.push(network(&create_http_client(), key.as_deref()).await);
+    }
+    #[cfg(not(feature = "gemini"))]
+    checks.push(Check::note(
+        "network",
+        "not checked: this build has no gemini support",
+        "cargo install git-changes-rs --features gemini (it is on by default)",
+    ));
+    checks.push(repository(directory, &repo));
+    if let Ok(repo) = &repo {
+        checks.push(hook_status(repo));
+    }
+
+    for check in &checks {
+        print_check(check)?;
+    }
+    let failed = checks
+        .iter()
+        .filter(|check| check.outcome == Outcome::Fail)
+        .count();
+    match failed {
+        0 => Ok(()),
+        n => Err(anyhow!("{} of {} checks failed", n, checks.len())),
+    }
+}
+
+fn print_check(check: &Check) -> Result<()> {
+    let glyphs = style::glyphs();
+    let mark = match check.outcome {
+        Outcome::Pass => glyphs.pass,
+        Outcome::Note => glyphs.note,
+        Outcome::Fail => glyphs.fail,
+    };
+    outln!("{} {}: {}", mark, check.name, check.detail)?;
+    if let Some(hint) = &check.hint {
+        outln!("    {}", hint)?;
+    }
+    Ok(())
+}
+
+// the environment variable a provider's key is read from; gemini is the only provider so far
+fn key_variable(_provider: &str) -> &'static str {
+    "GEMINI_API_KEY"
+}
+
+pub fn version(binary: Option<&Path>) -> Check {
+    let version = format!("git-changes-rs {}", env!("CARGO_PKG_VERSION"));
+    match binary {
+        Some(binary) => Check::pass("version", format!("{} ({})", version, binary.display())),
+        None => Check::pass("version", version),
+    }
+}
+
+// the files that apply and whether they parse, with the provider they configure
+pub fn config_files(repo: Option<&Repository>) -> (Check, Option<String>) {
+    let hint = "fix the file, or see `git-changes-rs config init` for every key";
+    let layers = match config::layers_for(repo) {
+        Ok(layers) => layers,
+        Err(e) => return (Check::fail("config", format!("{:#}", e), hint), None),
+    };
+    let config = match config::resolve(&layers) {
+        Ok(config) => config,
+        Err(e) => return (Check::fail("config", format!("{:#}", e), hint), None),
+    };
+    let files: Vec<String> = layers
+        .iter()
+        .map(|layer| format!("{} ({})", layer.path.display(), layer.origin))
+        .collect();
+    let detail = match files.is_empty() {
+        true => "no config files, using the defaults".to_string(),
+        false => files.join(", "),
+    };
+    (Check::pass("config", detail), config.generation.provider)
+}
+
+// whether the key is there; only its length is shown
+// whether the provider was compiled in
+pub fn provider_build(provider: &str) -> Check {
+    match provider::check_provider(provider) {
+        Ok(()) => Check::pass("provider", format!("{} is in this build", provider)),
+        Err(e) => Check::fail(
+            "provider",
+            e.to_string(),
+            format!(
+                "cargo install git-changes-rs --features {} (it is on by default)",
+                provider
+            ),
+        ),
+    }
+}
+
+pub fn api_key(variable: &str, value: Option<&str>) -> Check {
+    match value.map(str::trim) {
+        Some(value) if !value.is_empty() => Check::pass(
+            "api key",
+            format!("{} is set ({} characters)", variable, value.len()),
+        ),
+        Some(_) => Check::fail(
+            "api key",
+            format!("{} is empty", variable),
+            format!("set {} to your key, in the environment or a .env file", variable),
+        ),
+        None => Check::fail(
+            "api key",
+            format!("{} is not set", variable),
+            format!(
+                "get a key at https://aistudio.google.com/apikey and export {}=..., or put it in a .env file",
+                variable
+            ),
+        ),
+    }
+}
+
+// a models call with the key, which also tells whether the key works; without one, any answer
+// from the endpoint at all. Errors show their root cause only, as the url holds the key
+#[cfg(feature = "gemini")]
+pub async fn network(http: &Client, key: Option<&str>) -> Check {
+    let proxy_hint = "check the connection, and HTTPS_PROXY if you're behind a proxy";
+    if key.is_none() {
+        return match http.head(models_url()).send().await {
+            Ok(_) => Check::pass("network", "the gemini api answers (the key wasn't tried)"),
+            Err(e) => Check::fail(
+                "network",
+                format!("no answer from the gemini api: {}", anyhow!(e).root_cause()),
+                proxy_hint,
+            ),
+        };
+    }
+    match list_models().await {
+        Ok(models) => Check::pass(
+            "network",
+            format!(
+                "the gemini api accepted the key and lists {} models",
+                models.len()
+            ),
+        ),
+        Err(e) => {
+            let error = exit::classify(&e);
+            match error.kind {
+                exit::Kind::Network => Check::fail(
+                    "network",
+                    format!("no answer from the gemini api: {}", e.root_cause()),
+                    proxy_hint,
+                ),
+                exit::Kind::RateLimited => Check::note(
+                    "network",
+                    "the gemini api answers, but is rate limiting the key",
+                    "wait a moment, or check the quota of the key",
+                ),
+                _ => Check::fail(
+                    "network",
+                    format!("the gemini api refused the request: {}", e),
+                    "check that the key is valid and the Generative Language API is enabled for it",
+                ),
+            }
+        }
+    }
+}
+
+// the repository's state: a bare one has nothing to describe, an unborn HEAD or an operation in
+// progress changes what a run looks at
+pub fn repository(directory: &str, repo: &Result<Repository, git2::Error>) -> Check {
+    let repo = match repo {
+        Ok(repo) => repo,
+        Err(e) => {
+            return Check::fail(
+                "repository",
+                format!("no git repository at {}: {}", directory, e.message()),
+                "run it inside a repository, or pass --repo <path>",
+            )
+        }
+    };
+    if repo.is_bare() {
+        return Check::fail(
+            "repository",
+            format!("{} is bare", repo.path().display()),
+            "messages describe a working tree; run it in a clone with one",
+        );
+    }
+    let workdir = repo
+        .workdir()
+        .map_or_else(|| PathBuf::from(directory), Path::to_path_buf);
+    let head = match repo.head() {
+        Ok(head) if head.is_branch() => format!("on {}", head.shorthand().unwrap_or("HEAD")),
+        Ok(_) => {
+            return Check::note(
+                "repository",
+                format!("{}, HEAD is detached", workdir.display()),
+                "commits made here belong to no branch",
+            )
+        }
+        Err(e) if e.code() == ErrorCode::UnbornBranch => {
+            return Check::note(
+                "repository",
+                format!("{}, no commits yet", workdir.display()),
+                "the first message describes everything staged",
+            )
+        }
+        Err(e) => {
+            return Check::fail(
+                "repository",
+                format!(
+                    "{}: failed to read HEAD: {}",
+                    workdir.display(),
+                    e.message()
+                ),
+                "check the repository with `git status`",
+            )
+        }
+    };
+    match repo.state() {
+        RepositoryState::Clean => {
+            Check::pass("repository", format!("{}, {}", workdir.display(), head))
+        }
+        state => Check::note(
+            "repository",
+            format!("{}, {}, {:?} in progress", workdir.display(), head, state),
+            "finish or abort it first unless the message is for it",
+        ),
+    }
+}
+
+// the hook is optional; a foreign one, or ours pointing at a binary that moved, is worth a note
+pub fn hook_status(repo: &Repository) -> Check {
+    let hook = match hook::hooks_dir(repo) {
+        Ok(dir) => dir.join(hook::DEFAULT_HOOK),
+        Err(e) => {
+            return Check::note(
+                "hook",
+                format!("{:#}", e),
+                "check core.hooksPath in the git config",
+            )
+        }
+    };
+    if !hook.exists() {
+        return Check::pass(
+            "hook",
+            format!("{} not installed (optional)", hook::DEFAULT_HOOK),
+        );
+    }
+    if !hook::is_ours(&hook) {
+        return Check::note(
+            "hook",
+            format!("{} is another tool's hook", hook.display()),
+            "`git-changes-rs install-hook --force` keeps it and calls it first",
+        );
+    }
+    match hook::installed_binary(&hook) {
+        Some(binary) if !binary.exists() => Check::note(
+            "hook",
+            format!(
+                "{} runs {}, which doesn't exist",
+                hook.display(),
+                binary.display()
+            ),
+            "run `git-changes-rs install-hook` again to point it at this binary",
+        ),
+        _ => Check::pass("hook", format!("{} installed", hook.display())),
+    }
+}
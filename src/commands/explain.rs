@@ -0,0 +1,49 @@
+// src/commands/explain.rs
+// explain: a plain-prose explanation of a diff
+
+use super::selected_diff;
+use crate::{
+    config, error::GitChangesError, explain, invocation, output, provider, provider::send_to_gemini,
+};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use std::path::Path;
+use tracing::info;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+
+    let (diff, files, source) = selected_diff(&repo, &config, sub)?;
+    if diff.trim().is_empty() {
+        return Err(
+            GitChangesError::no_changes(format!("no changes to explain ({})", source)).into(),
+        );
+    }
+    info!("explaining {} ({} files)", source, files.len());
+
+    let client = provider::client(&config.generation)?;
+    let response = send_to_gemini(
+        &client,
+        explain::EXPLAIN_SYSTEM_PROMPT,
+        &explain::explain_prompt(&diff, &files, &source),
+        explain::EXPLAIN_MAX_OUTPUT_TOKENS,
+        1,
+    )
+    .await
+    .context("failed to fetch response from gemini api")?
+    .remove(0);
+    let markdown = response.trim();
+
+    match sub.get_one::<String>("output") {
+        Some(path) => {
+            output::write_message(Path::new(path), markdown)?;
+            info!("wrote the explanation to {}", path);
+        }
+        None => outln!("{}", markdown)?,
+    }
+    Ok(())
+}
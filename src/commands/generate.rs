@@ -0,0 +1,1550 @@
+// src/commands/generate.rs
+// generate: the commit message for the changes, also what runs without a subcommand
+
+use super::excludes;
+use crate::conventional::ConventionalMessage;
+use crate::{
+    attach, attach::Attachment, bullets, cache, cherry_pick, clipboard, commit, config, context,
+    diff::fetch_diffs, diff::hunk_annotations, diff::per_file_units, diff::repository_tree,
+    diff::rust_api_summary, diff::ChangeSet, diff::DiffMode, diff::Untracked, editor,
+    error::GitChangesError, exit, filter::is_excluded, filter::line_rules, fixup, format,
+    format::FormatOptions, history, hook, hook::HookArgs, interactivity,
+    interactivity::Interactivity, interactivity::Terminal, invocation, journal, large, merge,
+    message::generate_candidates, message::generate_message, notes, operation, output, pager,
+    per_file, picker, picker::PickerAction, prompt, prompt::PromptOptions, provider,
+    provider::generation_config, provider::model_id, provider::Gemini, provider::Sampling,
+    provider::OUTPUT_TOKENS, provider::PROMPT_TOKENS, push, report, revert, review,
+    review::ReviewAction, select, sign::Signer, spinner, spinner::Spinner, squash, style, template,
+    template::Template, trailers, trailers::Trailer, validate, validate::Rules, QUIET,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+use git2::{Commit, Oid, Repository};
+use regex::Regex;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use tracing::{debug, field, info, info_span, warn};
+
+// the default command: with `--hook`, as git's prepare-commit-msg hook
+pub async fn run(matches: &ArgMatches) -> Result<()> {
+    if let Some(values) = matches.get_many::<String>("hook") {
+        let values: Vec<String> = values.cloned().collect();
+        let args = HookArgs::from_values(&values).context("--hook needs the message file")?;
+        return run_hook(matches, &args).await;
+    }
+    let result = run_generate(matches, None).await;
+    match (result, encoding(matches)) {
+        (Err(e), Some(encoding)) if !e.is::<exit::Status>() => {
+            let class = exit::classify(&e);
+            report::print(
+                &report::ErrorReport::new(&class, format!("{:#}", e)),
+                encoding,
+            )?;
+            Err(exit::Status(class.code()).into())
+        }
+        (result, _) => result,
+    }
+}
+
+// --json or --format json, and --format yaml
+fn encoding(matches: &ArgMatches) -> Option<report::Encoding> {
+    if matches.get_flag("json") {
+        return Some(report::Encoding::Json);
+    }
+    match matches.get_one::<String>("format").map(String::as_str) {
+        Some("json") => Some(report::Encoding::Json),
+        Some("yaml") => Some(report::Encoding::Yaml),
+        _ => None,
+    }
+}
+
+// past the size limits: a yes at the terminal, or the end of the run
+fn confirm_large(may_ask: bool, assume_yes: bool) -> Result<()> {
+    let refused = || {
+        GitChangesError::too_large(
+            "not sent: the diff is over the size limits (use --force-large to send it)",
+        )
+    };
+    if !may_ask || !Interactivity::detect(assume_yes).is_interactive() {
+        return Err(refused().into());
+    }
+    let mut terminal = Terminal::open().context("the terminal went away")?;
+    let send = interactivity::confirm("send it anyway?", &mut terminal.input, &mut terminal.output)
+        .context("failed to read the answer")?;
+    match send {
+        true => Ok(()),
+        false => Err(refused().into()),
+    }
+}
+
+// --select: narrows `changes` to the checked files. The files in `excluded` are listed
+// unchecked, so one can be taken back in. A remembered selection is used as it is with
+// --remember and pre-checks the list otherwise; any answer replaces it once it exists. False
+// when the list was quit, and nothing is to be sent
+fn select_files(
+    repo: &Repository,
+    changes: &mut ChangeSet,
+    excluded: &[String],
+    matches: &ArgMatches,
+) -> Result<bool> {
+    let branch = select::branch_key(repo);
+    let remembered = select::load(repo, &branch);
+    let stats = changes.stats();
+    let marked = |paths: &[String]| -> Vec<bool> {
+        stats
+            .iter()
+            .map(|stat| paths.contains(&stat.path))
+            .collect()
+    };
+    let reuse = remembered
+        .as_deref()
+        .filter(|_| matches.get_flag("remember"))
+        .map(marked)
+        .filter(|keep| keep.contains(&true));
+
+    let keep = match reuse {
+        Some(keep) => {
+            status!(
+                "using the files selected before on {} ({} of {})",
+                branch,
+                keep.iter().filter(|&&k| k).count(),
+                keep.len()
+            );
+            keep
+        }
+        None => {
+            if !Interactivity::detect(matches.get_flag("yes")).is_interactive() {
+                return Err(GitChangesError::usage("--select needs a terminal to ask on").into());
+            }
+            let checked = match remembered.as_deref() {
+                Some(paths) => marked(paths),
+                None => stats
+                    .iter()
+                    .map(|stat| !excluded.contains(&stat.path))
+                    .collect(),
+            };
+            let mut terminal = Terminal::open().context("the terminal went away")?;
+            let Some(keep) =
+                select::choose(&stats, checked, &mut terminal.input, &mut terminal.output)
+                    .context("failed to read the selection")?
+            else {
+                status!("quit; nothing was sent");
+                return Ok(false);
+            };
+            if !keep.contains(&true) {
+                return Err(anyhow!("no files selected; nothing was sent"));
+            }
+            if remembered.is_some() || matches.get_flag("remember") {
+                let paths: Vec<&str> = stats
+                    .iter()
+                    .zip(&keep)
+                    .filter(|(_, &k)| k)
+                    .map(|(stat, _)| stat.path.as_str())
+                    .collect();
+                select::store(repo, &branch, &paths)?;
+            }
+            keep
+        }
+    };
+    for (stat, _) in stats.iter().zip(&keep).filter(|(_, &k)| !k) {
+        info!("not selected: {}", stat.path);
+    }
+    changes.retain(&keep);
+    Ok(true)
+}
+
+// --select-hunks: narrows `changes` to the hunks kept at the prompt
+fn select_hunks(repo: &Repository, changes: &mut ChangeSet, assume_yes: bool) -> Result<()> {
+    if !Interactivity::detect(assume_yes).is_interactive() {
+        return Err(GitChangesError::usage("--select-hunks needs a terminal to ask on").into());
+    }
+    if changes.hunks.is_empty() {
+        return Ok(());
+    }
+    let hunks: Vec<select::HunkView> = changes
+        .hunks
+        .iter()
+        .map(|mark| select::HunkView {
+            path: mark.path.to_string_lossy().into_owned(),
+            text: mark.display(&changes.diff),
+        })
+        .collect();
+    let mut terminal = Terminal::open().context("the terminal went away")?;
+    let mut pager_output = terminal
+        .output
+        .try_clone()
+        .context("failed to open the terminal")?;
+    let view =
+        |text: &str| pager::page_to(Some(repo), text, &mut pager_output).map_err(io::Error::other);
+    let keep = select::choose_hunks(&hunks, &mut terminal.input, &mut terminal.output, view)
+        .context("failed to read the answers")?;
+    if !keep.contains(&true) {
+        return Err(anyhow!("no hunks selected; nothing was sent"));
+    }
+    info!(
+        "kept {} of {} hunks",
+        keep.iter().filter(|&&k| k).count(),
+        keep.len()
+    );
+    changes.retain_hunks(&keep);
+    Ok(())
+}
+
+// prepare-commit-msg: never blocks the commit, so every failure becomes a warning and the
+// message file is left as git wrote it
+async fn run_hook(matches: &ArgMatches, args: &HookArgs) -> Result<()> {
+    let existing = match args.read() {
+        Ok(existing) => existing,
+        Err(e) => {
+            warn!("git-changes hook: {:#}", e);
+            return Ok(());
+        }
+    };
+    let replaced = hook::Replaced {
+        merges: matches.get_flag("merge_messages"),
+        cherry_picks: matches.get_flag("cherry_pick_messages"),
+        cherry_picking: Repository::open_from_env().is_ok_and(|r| cherry_pick::in_progress(&r)),
+    };
+    if let Some(reason) = hook::skip_reason(args, &existing, replaced) {
+        info!("hook: keeping git's message ({})", reason);
+        return Ok(());
+    }
+    let existing = match replaced.covers(args) {
+        true => hook::comments_only(&existing),
+        false => existing,
+    };
+    if let Err(e) = run_generate(matches, Some((args, &existing))).await {
+        warn!("git-changes hook: no message generated: {:#}", e);
+    }
+    Ok(())
+}
+
+// the default mode; with `hook`, the message goes into the hook's file instead of stdout. Each
+// step may end the run early, with Ok when there is nothing left to do
+async fn run_generate(matches: &ArgMatches, hook: Option<(&HookArgs, &str)>) -> Result<()> {
+    let repo = open_repository(matches, hook.is_some())?;
+    if matches.get_flag("print_config") {
+        return super::config::print(&repo, matches);
+    }
+    let mut run = Run::new(&repo, matches, hook)?;
+    let Some(collected) = collect(&run)? else {
+        return Ok(());
+    };
+    let Some(request) = build_request(&mut run, &collected)? else {
+        return Ok(());
+    };
+    let mut job = Job {
+        run,
+        collected,
+        request,
+    };
+    if matches.get_flag("per_file") {
+        return describe_per_file(&job).await;
+    }
+    let suggestion = suggest(&mut job).await?;
+    let Some(applied) = apply(&job, &suggestion)? else {
+        return Ok(());
+    };
+    report(job, suggestion, applied)
+}
+
+// git runs hooks from the top of the working tree, with GIT_DIR and GIT_INDEX_FILE pointing at
+// the (possibly temporary) index being committed
+fn open_repository(matches: &ArgMatches, in_hook: bool) -> Result<Repository> {
+    let _discover = info_span!("discover").entered();
+    // with -C or as `git changes` and no directory, the current one
+    let directory = matches
+        .get_one::<String>("directory")
+        .map(String::as_str)
+        .or((matches.contains_id("chdir") || invocation::as_git_subcommand()).then_some("."));
+    let repo = match directory {
+        Some(directory) => {
+            invocation::open(directory).map_err(|e| GitChangesError::open(directory, e))?
+        }
+        None if in_hook => Repository::open_from_env().context("failed to open git repository")?,
+        None => return Err(GitChangesError::usage("directory argument is required").into()),
+    };
+    Ok(repo)
+}
+
+// what the command line and the config settle before any change is looked at
+struct Run<'a> {
+    repo: &'a Repository,
+    matches: &'a ArgMatches,
+    hook: Option<(&'a HookArgs, &'a str)>,
+    config: config::Config,
+    output: Option<&'a str>,
+    // --json and --format yaml: stdout gets the report alone, which holds the message
+    encoding: Option<report::Encoding>,
+    // --format-string: the message through a template, checked when the flag was parsed
+    template: Option<&'a Template>,
+    markdown: bool,
+    started: Instant,
+    // -q and `--output -`: stdout gets the message alone, for `$(git-changes-rs -q .)`
+    bare: bool,
+    framing: output::Framing,
+    excludes: Vec<String>,
+    line_rules: Vec<Regex>,
+    types: Vec<String>,
+    prompt_options: PromptOptions,
+    format_options: FormatOptions,
+    create_commit: bool,
+    untracked: Untracked,
+}
+
+impl<'a> Run<'a> {
+    fn new(
+        repo: &'a Repository,
+        matches: &'a ArgMatches,
+        hook: Option<(&'a HookArgs, &'a str)>,
+    ) -> Result<Self> {
+        let config = config::load(repo)?;
+        let output = matches.get_one::<String>("output").map(String::as_str);
+        let encoding = encoding(matches);
+        let structured = encoding.is_some();
+        let template = matches.get_one::<Template>("format_string");
+        // output.format only applies where --format would be accepted
+        let format = matches
+            .get_one::<String>("format")
+            .map(String::as_str)
+            .or(config.output.format.as_deref())
+            .filter(|_| {
+                !structured
+                    && template.is_none()
+                    && hook.is_none()
+                    && !matches.get_flag("per_file")
+                    && !matches.get_flag("show_prompt")
+            });
+        let markdown = format == Some("markdown");
+        if structured || hook.is_some() {
+            spinner::disable();
+        }
+        let started = Instant::now();
+        let bare = QUIET.load(Ordering::Relaxed) || output == Some(output::STDOUT) || structured;
+        let framing = output::Framing {
+            terminator: match (
+                matches.get_flag("null"),
+                matches.get_flag("no_trailing_newline"),
+            ) {
+                (true, _) => output::Terminator::Nul,
+                (false, true) => output::Terminator::None,
+                (false, false) => output::Terminator::Newline,
+            },
+            allow_comment_lines: matches.get_flag("allow_comment_lines"),
+        };
+
+        let excludes = excludes(
+            &config,
+            matches.get_many::<String>("exclude").into_iter().flatten(),
+        );
+        info!("excluding patterns: {:?}", excludes);
+        let line_rules = line_rules(
+            config.diff.ignore_lines.iter().chain(
+                matches
+                    .get_many::<String>("ignore_lines")
+                    .into_iter()
+                    .flatten(),
+            ),
+        )?;
+
+        let types = validate::resolve_types(
+            matches
+                .get_many::<String>("types")
+                .map(|t| t.cloned().collect())
+                .or(config.commit.types.clone()),
+        )
+        .context("invalid commit types")?;
+        // --type is checked here, once the allowed types are known
+        let commit_type = match matches.get_one::<String>("type") {
+            Some(kind) if !types.contains(kind) => {
+                return Err(GitChangesError::usage(format!(
+                    "invalid value '{}' for '--type': the allowed types are {}",
+                    kind,
+                    types.join(", ")
+                ))
+                .into())
+            }
+            kind => kind.cloned(),
+        };
+
+        let style_name = matches
+            .get_one::<String>("style")
+            .cloned()
+            .or_else(|| config.message.style.clone())
+            .unwrap_or_else(|| prompt::DEFAULT_STYLE.to_string());
+        let style = config.style(&style_name)?;
+
+        let prompt_options = PromptOptions {
+            language: matches
+                .get_one::<String>("lang")
+                .cloned()
+                .or_else(|| config.message.language.clone()),
+            style,
+            title_only: matches.get_flag("title_only"),
+            per_file_bullets: matches.get_flag("per_file_bullets"),
+            attachments: load_attachments(matches)?,
+            types: if validate::is_default_types(&types) {
+                Vec::new()
+            } else {
+                types.clone()
+            },
+            commit_type,
+            scope: matches.get_one::<String>("scope").cloned(),
+            ..PromptOptions::default()
+        };
+        if let Some(language) = &prompt_options.language {
+            info!("message language: {}", language);
+        }
+        info!(
+            "message style: {}{} (maxOutputTokens={})",
+            prompt_options.style.name,
+            if prompt_options.title_only {
+                ", title only"
+            } else {
+                ""
+            },
+            prompt_options.max_output_tokens()
+        );
+
+        let format_options = FormatOptions::for_language(
+            prompt_options.language.as_deref(),
+            matches
+                .get_one::<usize>("max_title_length")
+                .copied()
+                .or(config.message.max_title_length),
+            config.message.warn_title_length,
+        );
+
+        let untracked = match (
+            matches.get_flag("include_untracked"),
+            matches.get_flag("no_gitignore"),
+        ) {
+            (false, _) => Untracked::None,
+            (true, false) => Untracked::NotIgnored,
+            (true, true) => Untracked::All,
+        };
+        Ok(Run {
+            repo,
+            matches,
+            hook,
+            config,
+            output,
+            encoding,
+            template,
+            markdown,
+            started,
+            bare,
+            framing,
+            excludes,
+            line_rules,
+            types,
+            prompt_options,
+            format_options,
+            create_commit: matches.get_flag("commit"),
+            untracked,
+        })
+    }
+}
+
+// the changes to describe, and what the repository's state adds to them
+struct Collected<'a> {
+    changes: ChangeSet,
+    stats: Vec<report::FileStat>,
+    amend_target: Option<Commit<'a>>,
+    signer: Option<Signer>,
+    push_target: Option<push::Target>,
+    squash_subjects: Option<Vec<String>>,
+    pending_merge: Option<merge::PendingMerge>,
+    cherry_picked: Option<cherry_pick::Original>,
+}
+
+// the first step: a commit that couldn't be made stops the run before anything is sent, an
+// amended HEAD, a merge, squash or cherry-pick in progress are picked up, and the diff is
+// narrowed by --select and --select-hunks. None when the run is over without a message, as
+// when the hook leaves git's own alone
+fn collect<'a>(run: &Run<'a>) -> Result<Option<Collected<'a>>> {
+    let (repo, matches) = (run.repo, run.matches);
+    let create_commit = run.create_commit;
+    if matches.get_flag("add_all") {
+        // staged before diffing so the message describes exactly what is committed
+        commit::stage_all(repo, run.untracked)?;
+    }
+    operation::check_conflicts(repo, matches.get_flag("allow_conflicts"))?;
+    if create_commit && !commit::has_staged_changes(repo)? {
+        return Err(GitChangesError::usage(
+            "nothing is staged to commit; stage changes with 'git add' first",
+        )
+        .into());
+    }
+    let amend_target = if matches.get_flag("amend") {
+        Some(commit::amend_target(repo, matches.get_flag("force"))?)
+    } else {
+        None
+    };
+    let mut signer = None;
+    if create_commit || amend_target.is_some() {
+        // fail before spending a request when the commit couldn't be created anyway
+        commit::signature(repo)?;
+        // git signs --use-git-binary commits itself
+        if !matches.get_flag("use_git_binary") {
+            signer = Signer::from_config(repo, matches.get_flag("sign"))?;
+        }
+        if let Some(signer) = &signer {
+            info!("signing the commit ({:?})", signer.format);
+        }
+    }
+
+    let push_target = if matches.get_flag("push") {
+        if amend_target.is_some() && !matches.get_flag("force_with_lease") {
+            return Err(GitChangesError::usage(
+                "--amend --push rewrites the pushed branch; add --force-with-lease to allow it",
+            )
+            .into());
+        }
+        Some(push::Target::of_head(repo)?)
+    } else {
+        None
+    };
+
+    // `git merge --squash` stages the branch's changes and leaves SQUASH_MSG behind
+    let squash_subjects = match matches.get_one::<String>("squash_of") {
+        Some(branch) => Some(squash::branch_subjects(repo, branch)?),
+        None if commit::has_staged_changes(repo)? => squash::pending_subjects(repo)?,
+        None => None,
+    };
+
+    // a conflicted or --no-commit merge leaves MERGE_HEAD behind until it is committed
+    let pending_merge = match amend_target.is_some() || squash_subjects.is_some() {
+        true => None,
+        false => merge::pending(repo)?,
+    };
+    if let Some(merge) = &pending_merge {
+        info!(
+            "merge in progress: {} ({} commits, {} conflicted files)",
+            merge.title,
+            merge.subjects.len(),
+            merge.conflicts.len()
+        );
+        if create_commit && !merge.unresolved.is_empty() {
+            return Err(anyhow!(
+                "{} still conflicted; resolve and 'git add' them before committing",
+                merge.unresolved.join(", ")
+            ));
+        }
+        if create_commit && matches.get_flag("per_file") {
+            return Err(GitChangesError::usage(
+                "--per-file can't commit a merge; commit it in one go",
+            )
+            .into());
+        }
+    }
+
+    // `git cherry-pick` stops at a conflict with CHERRY_PICK_HEAD left behind
+    let cherry_picked = match matches.get_one::<String>("cherry_pick_of") {
+        Some(rev) => Some(cherry_pick::original(repo, rev)?),
+        None if amend_target.is_none() && pending_merge.is_none() => cherry_pick::pending(repo)?,
+        None => None,
+    };
+    if let Some(original) = &cherry_picked {
+        info!(
+            "cherry-pick of {} {}",
+            history::short_id(original.oid),
+            original.message.lines().next().unwrap_or_default()
+        );
+    }
+    let merge_source = run
+        .hook
+        .is_some_and(|(args, _)| args.source.as_deref() == Some(hook::MERGE_SOURCE));
+    if merge_source && pending_merge.is_none() && cherry_picked.is_none() {
+        info!("hook: keeping git's message (no merge or cherry-pick in progress)");
+        return Ok(None);
+    }
+
+    status!("fetching diffs (filtering excluded files)...");
+    // a commit records the index, so only the staged changes may describe it
+    let diff_mode = if amend_target.is_some() {
+        DiffMode::Amend
+    } else if create_commit
+        || run.hook.is_some()
+        || squash_subjects.is_some()
+        || pending_merge.is_some()
+        || cherry_picked.is_some()
+    {
+        DiffMode::Staged
+    } else {
+        DiffMode::Auto
+    };
+    let mut changes = {
+        let _spinner = Spinner::start("collecting diff");
+        let mut changes = fetch_diffs(repo, &run.excludes, diff_mode, run.untracked)
+            .context("failed to fetch diffs")?;
+        let churn = changes.drop_churn(&run.line_rules);
+        if churn > 0 {
+            info!("ignored {} churn lines", churn);
+        }
+        changes
+    };
+
+    if changes.diff.trim().is_empty() {
+        let (kind, reason) = match changes.excluded.len() {
+            0 => (
+                exit::Kind::NoChanges,
+                "no relevant changes to describe".to_string(),
+            ),
+            1 => (
+                exit::Kind::AllExcluded,
+                "the only changed file is excluded".to_string(),
+            ),
+            n => (
+                exit::Kind::AllExcluded,
+                format!("all {} changed files are excluded", n),
+            ),
+        };
+        status!("{}", reason);
+        if let Some(encoding) = run.encoding {
+            report::print(
+                &report::ErrorReport::new(&exit::Class::new(kind), reason),
+                encoding,
+            )?;
+        }
+        // the hook leaves git's own message alone and lets the commit go ahead
+        return match run.hook {
+            Some(_) => Ok(None),
+            None => Err(exit::Status(kind.code()).into()),
+        };
+    }
+
+    // what's left of the diff after --select and --select-hunks
+    let filter = info_span!("filter", bytes = field::Empty).entered();
+    if matches.get_flag("select") {
+        // the checklist has the excluded files too, so they are collected again without the
+        // excludes, from the changes the first pass settled on
+        let excluded = changes.excluded.clone();
+        if !excluded.is_empty() {
+            let mode = match (diff_mode, changes.staged) {
+                (DiffMode::Auto, true) => DiffMode::Staged,
+                (mode, _) => mode,
+            };
+            changes =
+                fetch_diffs(repo, &[], mode, run.untracked).context("failed to fetch diffs")?;
+            changes.drop_churn(&run.line_rules);
+        }
+        if !select_files(repo, &mut changes, &excluded, matches)? {
+            return Ok(None);
+        }
+    }
+    if matches.get_flag("select_hunks") {
+        select_hunks(repo, &mut changes, matches.get_flag("yes"))?;
+    }
+    filter.record("bytes", changes.diff.len());
+    drop(filter);
+
+    info!(
+        "final filtered diffs found (len={}, files={})",
+        changes.diff.len(),
+        changes.files.len()
+    );
+    let stats = changes.stats();
+    status!(
+        "{} {} changed ({}, {})",
+        stats.len(),
+        if stats.len() == 1 { "file" } else { "files" },
+        style::added(stats.iter().map(|stat| stat.added).sum()),
+        style::removed(stats.iter().map(|stat| stat.removed).sum())
+    );
+    Ok(Some(Collected {
+        changes,
+        stats,
+        amend_target,
+        signer,
+        push_target,
+        squash_subjects,
+        pending_merge,
+        cherry_picked,
+    }))
+}
+
+// what goes to the model, or what stands in for its answer
+struct Request {
+    client: Gemini,
+    system_prompt: String,
+    user_prompt: String,
+    cache_key: String,
+    trailers: Vec<Trailer>,
+    revert_match: Option<revert::RevertMatch>,
+    fixup_target: Option<fixup::FixupTarget>,
+    candidate_count: u32,
+    // a fixup or a full revert is written without the model
+    sends: bool,
+    rules: Rules,
+    // merge subjects aren't conventional commits
+    validate: bool,
+}
+
+// the second step: everything the prompt is built from besides the diff, and the prompt
+// itself. None when --show-diff or --show-prompt already said all there is to say
+fn build_request(run: &mut Run<'_>, collected: &Collected<'_>) -> Result<Option<Request>> {
+    let (repo, matches) = (run.repo, run.matches);
+    let changes = &collected.changes;
+    let prompt_span = info_span!("prompt", bytes = field::Empty).entered();
+    // when amending, the diff includes HEAD's own changes, which would match HEAD itself
+    let revert_match = if matches.get_flag("no_revert_detect")
+        || collected.amend_target.is_some()
+        || collected.pending_merge.is_some()
+        || collected.cherry_picked.is_some()
+    {
+        None
+    } else {
+        revert::find_revert(
+            repo,
+            &changes.line_hashes,
+            revert::DEFAULT_REVERT_DEPTH,
+            |delta| !is_excluded(delta, &run.excludes),
+        )?
+    };
+    if let Some(related) = &revert_match {
+        info!(
+            "changes undo {:.0}% of {} {}",
+            related.similarity * 100.0,
+            history::short_id(related.oid),
+            related.subject
+        );
+        if !related.is_full() {
+            run.prompt_options.related_commit = Some(related.prompt_note());
+        }
+    }
+
+    let fixup_target = match matches.get_one::<String>("fixup") {
+        Some(rev) => Some(fixup::target_for_rev(repo, rev)?),
+        // a full revert says more than "fixup!" would
+        None if revert_match.as_ref().is_some_and(|r| r.is_full()) => None,
+        None if collected.pending_merge.is_some() || collected.cherry_picked.is_some() => None,
+        None if matches.get_flag("fixup_detect") => {
+            let history = fixup::recent_history(repo, fixup::DEFAULT_FIXUP_DEPTH)?;
+            fixup::best_target(&changes.files, &history)
+        }
+        None => None,
+    };
+    if let Some(target) = &fixup_target {
+        info!(
+            "fixup target: {} {} (score {:.2})",
+            target.oid, target.subject, target.score
+        );
+    } else if matches.get_flag("fixup_detect") {
+        info!("no recent commit looks like a fixup target; generating a fresh message");
+    }
+
+    let has_manifest = repo
+        .workdir()
+        .is_some_and(|dir| dir.join("Cargo.toml").is_file());
+    let rust_api =
+        matches.get_flag("rust_api") || (has_manifest && !matches.get_flag("no_rust_api"));
+    if rust_api {
+        let (api_changes, api_breaking) = rust_api_summary(repo, changes);
+        for line in &api_changes {
+            debug!("public API: {}", line);
+        }
+        run.prompt_options.api_changes = api_changes;
+        run.prompt_options.api_breaking = api_breaking;
+    }
+
+    if matches.get_flag("tree_context") {
+        let tree = repository_tree(repo)?;
+        debug!("repository layout: {} bytes", tree.len());
+        run.prompt_options.tree = Some(tree);
+    }
+    let prompt_options = &run.prompt_options;
+
+    let candidate_count = matches.get_one::<u32>("candidates").copied().unwrap_or(1);
+    let mut client = provider::client(&run.config.generation)?;
+    if matches.get_flag("deterministic") {
+        client.options.sampling = Sampling::deterministic();
+    }
+    let request_options = client
+        .options
+        .for_request(prompt_options.max_output_tokens(), candidate_count);
+    debug!(
+        "generation config: model={} {}",
+        model_id(),
+        generation_config(&request_options, model_id())
+    );
+
+    // annotations only go into the prompt copy of the diff
+    let prompt_diff = if matches.get_flag("smart_context") {
+        let annotations = hunk_annotations(repo, changes);
+        debug!(
+            "smart context: annotated {} of {} hunks",
+            annotations.len(),
+            changes.hunks.len()
+        );
+        context::annotate(&changes.diff, &annotations)
+    } else {
+        changes.diff.clone()
+    };
+    // --show-diff: what the model would see, after the excludes and any annotations
+    if matches.get_flag("show_diff") {
+        let mut text = prompt_diff.clone();
+        if !changes.excluded.is_empty() {
+            text.push_str(&format!(
+                "\n--- excluded, not sent to the model\n{}\n",
+                changes.excluded.join("\n")
+            ));
+        }
+        if !interactivity::confirms_sends() {
+            pager::page(Some(repo), &text)?;
+            return Ok(None);
+        }
+        // with --confirm, the question whether to send it comes next, and stdout is still
+        // kept for the message
+        match Terminal::open() {
+            Some(mut terminal) => pager::page_to(Some(repo), &text, &mut terminal.output)?,
+            None => eprint!("{}", text),
+        }
+    }
+
+    let (system_prompt, mut user_prompt) = match &collected.pending_merge {
+        Some(merge) => (
+            merge::MERGE_SYSTEM_PROMPT.to_string(),
+            merge::merge_prompt(merge, &prompt_diff, &changes.files),
+        ),
+        None => (
+            prompt::build_system_prompt(prompt_options),
+            prompt::build_user_prompt(&prompt_diff, &changes.files, prompt_options),
+        ),
+    };
+    if let Some(subjects) = collected.squash_subjects.as_ref().filter(|s| !s.is_empty()) {
+        info!("squash merge of {} commits", subjects.len());
+        user_prompt = format!("{}\n\n{}", user_prompt, prompt::squash_section(subjects));
+    }
+    if let Some(original) = &collected.cherry_picked {
+        let branch = repo
+            .head()
+            .ok()
+            .filter(|head| head.is_branch())
+            .and_then(|head| head.shorthand().map(str::to_string));
+        user_prompt = format!(
+            "{}\n\n{}",
+            user_prompt,
+            prompt::cherry_pick_section(&original.message, branch.as_deref())
+        );
+    }
+    let mut message_trailers = Vec::new();
+    if matches.get_flag("signoff") || run.config.commit.signoff {
+        message_trailers.push(trailers::signoff(repo)?);
+    }
+    if let Some(co_authors) = matches.get_many::<String>("co_author") {
+        message_trailers.extend(co_authors.map(|c| Trailer::new("Co-authored-by", c)));
+    }
+    if matches.get_flag("attribution") || run.config.attribution.enabled {
+        message_trailers.push(trailers::attribution(
+            &run.config.attribution.key,
+            &run.config.attribution.template,
+            model_id(),
+        ));
+    }
+
+    // everything the request is made of, so another style, language, model, attachment or
+    // template asks again instead of reusing a message shaped for something else, and the
+    // trailers a reused message already carries
+    let trailer_lines: Vec<String> = message_trailers.iter().map(Trailer::render).collect();
+    let cache_key = cache::message_key(&[
+        model_id(),
+        &prompt_options.style.name,
+        if prompt_options.title_only {
+            "title-only"
+        } else {
+            "full"
+        },
+        prompt_options.language.as_deref().unwrap_or_default(),
+        &system_prompt,
+        &user_prompt,
+        collected
+            .amend_target
+            .as_ref()
+            .and_then(|head| head.message())
+            .unwrap_or_default(),
+        &trailer_lines.join("\n"),
+    ]);
+    if let Some(feedback) = matches.get_one::<String>("feedback") {
+        let previous = cache::load(repo, &cache_key).context(
+            "no previous suggestion for the current diff; run once without --feedback first",
+        )?;
+        info!("revising the previous suggestion with feedback");
+        user_prompt = format!(
+            "{}\n\n{}",
+            user_prompt,
+            prompt::feedback_section(&previous, feedback)
+        );
+    }
+    prompt_span.record("bytes", system_prompt.len() + user_prompt.len());
+    drop(prompt_span);
+    // --show-prompt: the request as it would go out, without spending tokens on it
+    if matches.get_flag("show_prompt") {
+        let config = generation_config(&request_options, model_id());
+        outln!(
+            "--- system prompt\n{}\n--- prompt\n{}\n--- generation config ({})\n{:#}",
+            system_prompt,
+            user_prompt,
+            model_id(),
+            config
+        )?;
+        return Ok(None);
+    }
+    let sends = fixup_target.is_none() && !revert_match.as_ref().is_some_and(|r| r.is_full());
+    if sends && !matches.get_flag("force_large") {
+        let limits = large::Limits {
+            files: run
+                .config
+                .diff
+                .max_files
+                .unwrap_or(large::DEFAULT_MAX_FILES),
+            kb: run.config.diff.max_kb.unwrap_or(large::DEFAULT_MAX_KB),
+        };
+        let sizes: Vec<(&str, usize)> = (0..changes.files.len())
+            .map(|index| {
+                (
+                    changes.files[index].as_str(),
+                    changes.file_patch(index).len(),
+                )
+            })
+            .collect();
+        let prompt_bytes = system_prompt.len() + user_prompt.len();
+        if let Some(report) = large::check(limits, &sizes, prompt_bytes) {
+            warn!("{}", report);
+            confirm_large(run.hook.is_none() && !run.bare, matches.get_flag("yes"))?;
+        }
+    }
+    Ok(Some(Request {
+        client,
+        system_prompt,
+        user_prompt,
+        cache_key,
+        trailers: message_trailers,
+        revert_match,
+        fixup_target,
+        candidate_count,
+        sends,
+        rules: Rules {
+            types: run.types.clone(),
+            max_title_length: run.format_options.max_title_length,
+        },
+        validate: !matches.get_flag("no_validate") && collected.pending_merge.is_none(),
+    }))
+}
+
+// a run with its changes and its request, for the steps that make the message of them
+struct Job<'a> {
+    run: Run<'a>,
+    collected: Collected<'a>,
+    request: Request,
+}
+
+impl Job<'_> {
+    fn rules(&self) -> Option<&Rules> {
+        self.request.validate.then_some(&self.request.rules)
+    }
+
+    // each generated message goes to the history log, with what became of it
+    fn remember(&self, message: &str, outcome: journal::Outcome, commit: Option<Oid>) {
+        if !self.request.sends || !self.run.config.privacy.history {
+            return;
+        }
+        let entry = journal::entry(
+            self.run.repo,
+            &self.collected.changes.diff,
+            model_id(),
+            message,
+            outcome,
+            commit,
+        );
+        if let Err(e) = journal::append(&entry) {
+            warn!("failed to add the message to the history: {:#}", e);
+        }
+    }
+
+    // what the model said, before the review, the editor or --edit change it: what the same
+    // request reuses, and what --feedback revises
+    fn store(&self, message: &str) {
+        if let Err(e) = cache::store(self.run.repo, &self.request.cache_key, message) {
+            warn!("failed to remember the suggestion: {:#}", e);
+        }
+    }
+
+    // --type and --scope hold whatever the model wrote in front of the subject
+    fn prefixed(&self, message: String) -> String {
+        let options = &self.run.prompt_options;
+        if options.commit_type.is_none() && options.scope.is_none() {
+            return message;
+        }
+        let mut parsed = ConventionalMessage::parse(&message);
+        parsed.force_prefix(options.commit_type.as_deref(), options.scope.as_deref());
+        parsed.to_string()
+    }
+
+    // post-processing that applies to every candidate after formatting and validation
+    fn finalize(&self, message: String) -> String {
+        let collected = &self.collected;
+        let message = self.prefixed(message);
+        let message = if self.run.prompt_options.per_file_bullets {
+            let report = bullets::check_file_bullets(&message, &collected.changes.files);
+            for path in &report.dropped {
+                warn!("dropped bullet for '{}', which is not a changed path", path);
+            }
+            for text in &report.unprefixed {
+                warn!("bullet doesn't start with a changed path: {}", text);
+            }
+            if !report.uncovered.is_empty() {
+                info!("files without a bullet: {:?}", report.uncovered);
+            }
+            report.message
+        } else {
+            message
+        };
+        let message = match &collected.amend_target {
+            Some(head) => trailers::preserve(head.message().unwrap_or_default(), &message),
+            None => message,
+        };
+        let message = match &collected.pending_merge {
+            Some(merge) => merge::with_title(merge, &message),
+            None => message,
+        };
+        let message = match &collected.cherry_picked {
+            Some(original) => trailers::preserve(
+                &original.message,
+                &cherry_pick::with_origin(&message, original),
+            ),
+            None => message,
+        };
+        trailers::append(&message, &self.request.trailers)
+    }
+}
+
+// --per-file: a message and, with --commit, a commit for each file or group of files that
+// belong together, in place of the steps after the request
+async fn describe_per_file(job: &Job<'_>) -> Result<()> {
+    let (run, collected, request) = (&job.run, &job.collected, &job.request);
+    let (repo, changes) = (run.repo, &collected.changes);
+    let units = per_file_units(changes);
+    // excluded files are in the snapshot too, and stay staged afterwards
+    let mut snapshot = if run.create_commit {
+        Some(per_file::StagedSnapshot::take(repo)?)
+    } else {
+        None
+    };
+
+    let mut committed = Vec::new();
+    for (step, unit) in units.iter().enumerate() {
+        let files: Vec<String> = unit.iter().map(|&i| changes.files[i].clone()).collect();
+        let label = files.join(" -> ");
+        status!("describing {} ({}/{})...", label, step + 1, units.len());
+        let outcome = async {
+            let patch: String = unit.iter().map(|&i| changes.file_patch(i)).collect();
+            let message = generate_message(
+                &request.client,
+                &request.system_prompt,
+                &prompt::build_user_prompt(&patch, &files, &run.prompt_options),
+                &run.prompt_options,
+                &run.format_options,
+                job.rules(),
+            )
+            .await?;
+            let message = trailers::append(&job.prefixed(message), &request.trailers);
+            let Some(snapshot) = snapshot.as_mut() else {
+                output::print_message(&label, &message, run.bare, &run.framing)?;
+                job.remember(&message, journal::Outcome::Suggested, None);
+                return Ok(());
+            };
+            let paths: Vec<PathBuf> = unit
+                .iter()
+                .flat_map(|&i| changes.changes[i].paths())
+                .collect();
+            snapshot.stage(repo, &paths)?;
+            let oid = commit::create(repo, &message, collected.signer.as_ref())?;
+            job.remember(&message, journal::Outcome::Committed, Some(oid));
+            status!(
+                "committed {} {}",
+                history::short_id(oid),
+                message.lines().next().unwrap_or_default()
+            );
+            if run.matches.get_flag("note") {
+                add_generation_note(repo, oid, false);
+            }
+            Ok::<(), anyhow::Error>(())
+        }
+        .await;
+
+        if let Err(e) = outcome {
+            let remaining: Vec<&str> = units[step..]
+                .iter()
+                .flatten()
+                .map(|&i| changes.files[i].as_str())
+                .collect();
+            if let Some(snapshot) = snapshot {
+                snapshot.restore(repo)?;
+            }
+            return Err(e.context(format!(
+                "stopped at {}; committed: [{}]; not committed: [{}]",
+                label,
+                committed.join(", "),
+                remaining.join(", ")
+            )));
+        }
+        committed.push(label);
+    }
+    if let Some(snapshot) = snapshot {
+        snapshot.restore(repo)?;
+    }
+    if let Some(target) = &collected.push_target {
+        push_committed(repo, run.matches, target)?;
+    }
+    Ok(())
+}
+
+// the message, and what became of it at the prompts
+struct Suggestion {
+    message: String,
+    edited: bool,
+    // accepted at the review prompt, where it is on screen already
+    reviewed: bool,
+    // taken at the review or candidate prompt
+    accepted: bool,
+    // reused from the last run instead of generated
+    cached: bool,
+}
+
+impl Suggestion {
+    fn new(message: String) -> Self {
+        Suggestion {
+            message,
+            edited: false,
+            reviewed: false,
+            accepted: false,
+            cached: false,
+        }
+    }
+}
+
+// the third and fourth steps: the message from the model, reviewed, or the fixup or revert
+// message standing in for it; then --edit
+async fn suggest(job: &mut Job<'_>) -> Result<Suggestion> {
+    if let Some(head) = &job.collected.amend_target {
+        let previous = head.message().unwrap_or_default();
+        job.request.user_prompt = format!(
+            "{}\n\n{}",
+            job.request.user_prompt,
+            prompt::previous_message_section(previous)
+        );
+    }
+    let matches = job.run.matches;
+    // git opens the editor itself after the hook, and prompts would end up in `--output -`
+    let interactive = job.run.hook.is_none()
+        && !job.run.bare
+        && Interactivity::detect(matches.get_flag("yes")).is_interactive();
+    let mut suggestion = if let Some(target) = &job.request.fixup_target {
+        Suggestion::new(trailers::append(&target.message(), &job.request.trailers))
+    } else if let Some(revert) = job.request.revert_match.as_ref().filter(|r| r.is_full()) {
+        status!("writing a revert message without the api...");
+        Suggestion::new(trailers::append(&revert.message(), &job.request.trailers))
+    } else if job.request.candidate_count > 1 {
+        choose_candidate(job, interactive).await?
+    } else {
+        review_suggestion(job, interactive).await?
+    };
+
+    if matches.get_flag("edit") && !suggestion.edited {
+        if interactive {
+            let before = suggestion.message.clone();
+            suggestion.message = editor::edit_message(
+                job.run.repo,
+                &suggestion.message,
+                &job.collected.changes.files,
+            )?;
+            suggestion.edited = suggestion.message != before;
+        } else {
+            warn!("not opening the editor in non-interactive mode");
+        }
+    }
+    Ok(suggestion)
+}
+
+// one message for `request_prompt`, ready to show
+async fn generate_one(job: &Job<'_>, request_prompt: &str) -> Result<String> {
+    status!("generating commit message via gemini...");
+    let message = job.finalize(
+        generate_message(
+            &job.request.client,
+            &job.request.system_prompt,
+            request_prompt,
+            &job.run.prompt_options,
+            &job.run.format_options,
+            job.rules(),
+        )
+        .await?,
+    );
+    job.store(&message);
+    Ok(message)
+}
+
+// --candidates: as many messages for `request_prompt` as the model gives, ready to show
+async fn generate_several(job: &Job<'_>, request_prompt: &str) -> Result<Vec<String>> {
+    let candidates = generate_candidates(
+        &job.request.client,
+        &job.request.system_prompt,
+        request_prompt,
+        &job.run.prompt_options,
+        &job.run.format_options,
+        job.rules(),
+        job.request.candidate_count,
+    )
+    .await?;
+    Ok(candidates.into_iter().map(|c| job.finalize(c)).collect())
+}
+
+// --candidates: the picker, or the first candidate without a terminal
+async fn choose_candidate(job: &Job<'_>, interactive: bool) -> Result<Suggestion> {
+    status!("generating commit message via gemini...");
+    let user_prompt = &job.request.user_prompt;
+    let mut request_prompt = user_prompt.clone();
+    loop {
+        let candidates = generate_several(job, &request_prompt).await?;
+        if !interactive {
+            job.store(&candidates[0]);
+            return Ok(Suggestion::new(candidates[0].clone()));
+        }
+
+        let mut terminal = Terminal::open().context("the terminal went away")?;
+        let (input, output) = (&mut terminal.input, &mut terminal.output);
+        picker::render(&candidates, true, output)?;
+        let action = picker::prompt(candidates.len(), input, output)?;
+        let chosen = match action {
+            PickerAction::Select(idx) | PickerAction::Edit(idx) => Some(idx),
+            _ => None,
+        };
+        if let Some(idx) = chosen {
+            job.store(&candidates[idx]);
+        }
+        for (idx, candidate) in candidates.iter().enumerate() {
+            if chosen != Some(idx) {
+                job.remember(candidate, journal::Outcome::Rejected, None);
+            }
+        }
+        match action {
+            PickerAction::Select(idx) => {
+                let mut suggestion = Suggestion::new(candidates[idx].clone());
+                suggestion.accepted = true;
+                return Ok(suggestion);
+            }
+            PickerAction::Edit(idx) => {
+                let message = editor::edit_message(
+                    job.run.repo,
+                    &candidates[idx],
+                    &job.collected.changes.files,
+                )?;
+                let mut suggestion = Suggestion::new(message);
+                suggestion.edited = true;
+                return Ok(suggestion);
+            }
+            PickerAction::Feedback(idx) => match picker::ask_feedback(input, output)? {
+                Some(feedback) => {
+                    status!("revising candidate {}...", idx + 1);
+                    request_prompt = format!(
+                        "{}\n\n{}",
+                        user_prompt,
+                        prompt::feedback_section(&candidates[idx], &feedback)
+                    );
+                }
+                None => status!("no feedback given; regenerating candidates..."),
+            },
+            PickerAction::Regenerate => status!("regenerating candidates..."),
+            PickerAction::Quit => return Err(anyhow!("aborted: no candidate was selected")),
+        }
+    }
+}
+
+// one message and the review prompt, or the message alone without a terminal. The same
+// request as last time gets the same message, trailers and all, unless asked again;
+// regenerating from the review prompt always goes to the model
+async fn review_suggestion(job: &mut Job<'_>, interactive: bool) -> Result<Suggestion> {
+    let matches = job.run.matches;
+    let mut reused = match matches.get_flag("no_cache") || matches.contains_id("feedback") {
+        true => None,
+        false => cache::load(job.run.repo, &job.request.cache_key),
+    };
+    let mut request_prompt = job.request.user_prompt.clone();
+    loop {
+        let cached = reused.is_some();
+        let message = match reused.take() {
+            Some(message) => {
+                status!("reusing the message generated for this request (--no-cache asks again)");
+                message
+            }
+            None => generate_one(job, &request_prompt).await?,
+        };
+        let mut suggestion = Suggestion::new(message);
+        suggestion.cached = cached;
+        if !interactive {
+            return Ok(suggestion);
+        }
+
+        let mut terminal = Terminal::open().context("the terminal went away")?;
+        let (input, output) = (&mut terminal.input, &mut terminal.output);
+        review::render(&suggestion.message, output)?;
+        let action = review::prompt(input, output)?;
+        if !matches!(action, ReviewAction::Accept | ReviewAction::Edit) {
+            job.remember(&suggestion.message, journal::Outcome::Rejected, None);
+        }
+        match action {
+            ReviewAction::Accept => {
+                suggestion.reviewed = true;
+                suggestion.accepted = true;
+                return Ok(suggestion);
+            }
+            ReviewAction::Edit => {
+                suggestion.message = editor::edit_message(
+                    job.run.repo,
+                    &suggestion.message,
+                    &job.collected.changes.files,
+                )?;
+                suggestion.edited = true;
+                return Ok(suggestion);
+            }
+            ReviewAction::Regenerate => {
+                job.request.client.options.sampling = job.request.client.options.sampling.varied();
+                status!("regenerating...");
+            }
+            ReviewAction::Feedback => match picker::ask_feedback(input, output)? {
+                Some(feedback) => {
+                    status!("revising...");
+                    request_prompt = format!(
+                        "{}\n\n{}",
+                        job.request.user_prompt,
+                        prompt::feedback_section(&suggestion.message, &feedback)
+                    );
+                }
+                None => status!("no feedback given; regenerating..."),
+            },
+            ReviewAction::Quit => return Err(anyhow!("aborted: the suggestion was not accepted")),
+        }
+    }
+}
+
+// what the last step reports on
+struct Applied {
+    committed: Option<Oid>,
+    usage: report::Usage,
+}
+
+// the fifth step: the message where it was asked for (the hook's file, --output, the
+// clipboard, stdout), the commit or amend, and the push. None when the hook took the message
+fn apply(job: &Job<'_>, suggestion: &Suggestion) -> Result<Option<Applied>> {
+    let (run, collected) = (&job.run, &job.collected);
+    let (repo, matches) = (run.repo, run.matches);
+    let message = &suggestion.message;
+    let subject_len = format::char_len(message.lines().next().unwrap_or_default());
+    if subject_len > run.format_options.warn_title_length {
+        warn!(
+            "subject is {} characters (recommended at most {})",
+            subject_len, run.format_options.warn_title_length
+        );
+    }
+
+    if let Some((args, existing)) = run.hook {
+        // git strips the comment lines of this file too, so only the terminator is left out
+        hook::write_message(args, existing, &run.framing.body(message))?;
+        info!("hook: wrote the message to {}", args.path.display());
+        job.remember(message, journal::Outcome::Suggested, None);
+        return Ok(None);
+    }
+
+    if let Some(path) = run.output.filter(|&path| path != output::STDOUT) {
+        output::write_commit_message(Path::new(path), message, &run.framing)?;
+        info!("wrote the message to {}", path);
+    }
+    if matches.get_flag("copy") {
+        match clipboard::copy(message) {
+            Ok(()) => info!("copied the message to the clipboard"),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+    // an accepted suggestion is already on screen, unless stdout goes somewhere else
+    let on_screen = suggestion.reviewed && io::stdout().is_terminal();
+    if !on_screen && run.encoding.is_none() && !run.markdown && run.template.is_none() {
+        output::print_message("suggested commit message", message, run.bare, &run.framing)?;
+    }
+    // read before --note takes them
+    let usage = report::Usage {
+        prompt_tokens: PROMPT_TOKENS.load(Ordering::Relaxed),
+        output_tokens: OUTPUT_TOKENS.load(Ordering::Relaxed),
+    };
+    let mut committed = None;
+
+    if let Some(head) = &collected.amend_target {
+        let oid = commit::amend(repo, head, message, collected.signer.as_ref())?;
+        committed = Some(oid);
+        status!(
+            "amended {} -> {}\n    old: {}\n    new: {}",
+            history::short_id(head.id()),
+            history::short_id(oid),
+            head.summary().unwrap_or_default(),
+            message.lines().next().unwrap_or_default()
+        );
+        if matches.get_flag("note") {
+            add_generation_note(repo, oid, suggestion.edited);
+        }
+    }
+
+    if run.create_commit {
+        let signer = collected.signer.as_ref();
+        let oid = if matches.get_flag("use_git_binary") {
+            commit::create_with_git(repo, message, matches.get_flag("sign"))?
+        } else if let Some(merge) = &collected.pending_merge {
+            commit::create_merge(repo, message, signer, &merge.heads)?
+        } else if let Some(original) = collected.cherry_picked.as_ref().filter(|o| o.in_progress) {
+            commit::create_cherry_pick(repo, message, signer, original.oid)?
+        } else {
+            commit::create(repo, message, signer)?
+        };
+        committed = Some(oid);
+        status!(
+            "committed {} {}",
+            history::short_id(oid),
+            message.lines().next().unwrap_or_default()
+        );
+        if !matches.get_flag("use_git_binary") {
+            status!("note: commit hooks were not run (use --use-git-binary to run them)");
+        }
+        if matches.get_flag("note") {
+            add_generation_note(repo, oid, suggestion.edited);
+        }
+    }
+
+    let outcome = match (committed, suggestion.accepted || suggestion.edited) {
+        (Some(_), _) => journal::Outcome::Committed,
+        (None, true) => journal::Outcome::Accepted,
+        (None, false) => journal::Outcome::Suggested,
+    };
+    job.remember(message, outcome, committed);
+
+    if let Some(target) = &collected.push_target {
+        push_committed(repo, matches, target)?;
+    }
+    Ok(Some(Applied { committed, usage }))
+}
+
+// the last step: --format markdown, --format-string, and the --json or yaml report
+fn report(job: Job<'_>, suggestion: Suggestion, applied: Applied) -> Result<()> {
+    let Job { run, collected, .. } = job;
+    let Applied { committed, usage } = applied;
+    let message = suggestion.message;
+    if run.markdown {
+        let parsed = ConventionalMessage::parse(&message);
+        out!(
+            "{}",
+            report::markdown(&parsed, &collected.stats, model_id(), &usage)
+        )?;
+    }
+    if let Some(template) = run.template {
+        outln!(
+            "{}",
+            template.render(&template::Values {
+                message: &ConventionalMessage::parse(&message),
+                files: &collected.changes.files,
+                model: model_id(),
+                usage: &usage,
+            })
+        )?;
+    }
+    if let Some(encoding) = run.encoding {
+        report::print(
+            &report::Report {
+                schema_version: report::SCHEMA_VERSION,
+                conventional: report::conventional(&ConventionalMessage::parse(&message)),
+                message,
+                files: report::Files {
+                    included: collected.changes.files,
+                    excluded: collected.changes.excluded,
+                },
+                model: model_id().to_string(),
+                usage,
+                elapsed_ms: run.started.elapsed().as_millis(),
+                cached: suggestion.cached,
+                commit: committed.map(|oid| oid.to_string()),
+            },
+            encoding,
+        )?;
+    }
+    Ok(())
+}
+
+// --push once the commits are made; a failure says so, so nobody commits again
+fn push_committed(repo: &Repository, matches: &ArgMatches, target: &push::Target) -> Result<()> {
+    info!(
+        "pushing {} to {}/{}{}",
+        target.branch,
+        target.remote,
+        target.remote_branch,
+        if target.set_upstream {
+            " (setting it as the upstream)"
+        } else {
+            ""
+        }
+    );
+    let lease = matches.get_flag("force_with_lease");
+    let pushed = if matches.get_flag("use_git_binary") {
+        push::push_with_git(repo, target, lease)
+    } else {
+        push::push(repo, target, lease)
+    };
+    pushed.context("the commit succeeded; only the push failed")?;
+    status!(
+        "pushed {} to {}/{}",
+        target.branch,
+        target.remote,
+        target.remote_branch
+    );
+    Ok(())
+}
+
+// --note for a commit just made, with the tokens spent since the last one; the commit is
+// already there, so a failure is only a warning
+fn add_generation_note(repo: &Repository, oid: Oid, human_edited: bool) {
+    let note = notes::GenerationNote {
+        tool: "git-changes-rs".to_string(),
+        tool_version: env!("CARGO_PKG_VERSION").to_string(),
+        provider: "gemini".to_string(),
+        model: model_id().to_string(),
+        prompt_version: prompt::PROMPT_VERSION,
+        prompt_tokens: PROMPT_TOKENS.swap(0, Ordering::Relaxed),
+        output_tokens: OUTPUT_TOKENS.swap(0, Ordering::Relaxed),
+        human_edited,
+    };
+    match notes::add(repo, oid, &note) {
+        Ok(()) => info!("noted how {} was generated", history::short_id(oid)),
+        Err(e) => warn!("the commit is made, but the note failed: {:#}", e),
+    }
+}
+
+fn load_attachments(matches: &ArgMatches) -> Result<Vec<Attachment>> {
+    let Some(paths) = matches.get_many::<String>("attach") else {
+        return Ok(Vec::new());
+    };
+
+    let mut attachments = Vec::new();
+    for path in paths {
+        let attachment = attach::load(Path::new(path), attach::DEFAULT_ATTACHMENT_CAP)?;
+        debug!(
+            "attaching {} ({} bytes{})",
+            attachment.label,
+            attachment.original_len,
+            if attachment.truncated() {
+                ", truncated"
+            } else {
+                ""
+            }
+        );
+        attachments.push(attachment);
+    }
+    Ok(attachments)
+}
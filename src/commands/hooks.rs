@@ -0,0 +1,46 @@
+// src/commands/hooks.rs
+// install-hook and uninstall-hook
+
+use crate::{hook, invocation};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+
+pub fn install(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let hook_type = sub
+        .get_one::<String>("type")
+        .context("type has a default")?;
+
+    let mut options = Vec::new();
+    if sub.get_flag("merge_messages") {
+        options.push("--merge-messages");
+    }
+    if sub.get_flag("cherry_pick_messages") {
+        options.push("--cherry-pick-messages");
+    }
+    let path = hook::install(&repo, hook_type, sub.get_flag("force"), &options)?;
+    status!("installed {}", path.display());
+    Ok(())
+}
+
+pub fn uninstall(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let hook_type = sub
+        .get_one::<String>("type")
+        .context("type has a default")?;
+
+    match hook::uninstall(&repo, hook_type)? {
+        Some(restored) => status!(
+            "removed the git-changes hook; restored {}",
+            restored.display()
+        ),
+        None => status!("removed the git-changes {} hook", hook_type),
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+// src/commands/journal.rs
+// history: the messages journal.rs logged, newest first, and `history show` for one of them
+
+use crate::invocation;
+use crate::journal::{self, Entry};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use std::path::Path;
+
+pub fn run(sub: &ArgMatches) -> Result<()> {
+    let entries = journal::load();
+    if let Some(("show", show)) = sub.subcommand() {
+        let id = show.get_one::<String>("id").context("id is required")?;
+        let entry = journal::find(&entries, id)?;
+        outln!("{}", entry.message)?;
+        return Ok(());
+    }
+
+    let all = sub.get_flag("all");
+    let repo = match all {
+        true => None,
+        false => {
+            let directory = sub
+                .get_one::<String>("repo")
+                .context("repo has a default")?;
+            let repo = invocation::open(directory)
+                .context("failed to open git repository (use --all outside one)")?;
+            Some(journal::repo_key(&repo))
+        }
+    };
+    let count = *sub
+        .get_one::<usize>("max_count")
+        .context("max-count has a default")?;
+    let listed: Vec<&Entry> = entries
+        .iter()
+        .rev()
+        .filter(|entry| repo.as_ref().is_none_or(|repo| &entry.repo == repo))
+        .take(count)
+        .collect();
+    if listed.is_empty() {
+        status!(
+            "no messages in the history{}",
+            match repo {
+                Some(_) => " for this repository",
+                None => "",
+            }
+        );
+        return Ok(());
+    }
+
+    let place = |entry: &Entry| match all {
+        true => {
+            let name = Path::new(&entry.repo).file_name().map_or_else(
+                || entry.repo.clone(),
+                |name| name.to_string_lossy().into_owned(),
+            );
+            format!("{}:{}", name, entry.branch)
+        }
+        false => entry.branch.clone(),
+    };
+    let width = listed
+        .iter()
+        .map(|entry| place(entry).len())
+        .max()
+        .unwrap_or(0);
+    for entry in listed {
+        outln!(
+            "{}  {}  {:<width$}  {:<9}  {}",
+            entry.short_id(),
+            entry.date(),
+            place(entry),
+            entry.outcome.label(),
+            entry.message.lines().next().unwrap_or_default(),
+            width = width
+        )?;
+    }
+    Ok(())
+}
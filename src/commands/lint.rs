@@ -0,0 +1,56 @@
+// src/commands/lint.rs
+// lint: checking existing commit messages against the conventional-commit rules
+
+use super::{config_prompt_options, config_rules};
+use crate::{
+    config, exit, history, invocation, lint, message::generate_message, prompt, provider, trailers,
+};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use tracing::warn;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let (format_options, rules) = config_rules(&config)?;
+
+    let range = sub.get_one::<String>("range").map(String::as_str);
+    let report = lint::lint_range(&repo, range, &rules)?;
+    lint::print_report(&report)?;
+
+    if sub.get_flag("fix_suggestions") && !report.failures.is_empty() {
+        let prompt_options = config_prompt_options(&config, &rules)?;
+        let system_prompt = prompt::build_system_prompt(&prompt_options);
+        let client = provider::client(&config.generation)?;
+
+        for failure in &report.failures {
+            let short_id = history::short_id(failure.oid);
+            let user_prompt = lint::fix_prompt(&repo, failure)?;
+            match generate_message(
+                &client,
+                &system_prompt,
+                &user_prompt,
+                &prompt_options,
+                &format_options,
+                Some(&rules),
+            )
+            .await
+            {
+                Ok(message) => outln!(
+                    "\nsuggested message for {}:\n---\n{}\n---",
+                    short_id,
+                    trailers::preserve(&failure.message, &message)
+                )?,
+                Err(e) => warn!("no suggestion for {}: {:#}", short_id, e),
+            }
+        }
+    }
+
+    if !report.failures.is_empty() {
+        return Err(exit::Status(exit::ERROR).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,226 @@
+// src/commands/man.rs
+// generate-man (hidden): roff man pages made from the clap definitions, so they can't drift
+
+use crate::{cli, config, exit};
+use anyhow::{Context, Result};
+use clap::{ArgMatches, Command};
+use clap_mangen::Man;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+// what the tool reads from the environment, for the main page
+const ENVIRONMENT: &[(&str, &str)] = &[
+    (
+        "GEMINI_API_KEY",
+        "The Gemini API key. It can also be set in a .env file.",
+    ),
+    (
+        "GIT_CHANGES_CONFIG",
+        "The config file to read instead of the user's and the repository's, when --config isn't given.",
+    ),
+    ("GITHUB_TOKEN", "Token for pr --github."),
+    (
+        "GITHUB_API_URL",
+        "GitHub API to use instead of api.github.com, as set by GitHub Actions on Enterprise.",
+    ),
+    ("GITLAB_TOKEN", "Token for pr --gitlab."),
+    (
+        "CI_SERVER_URL",
+        "GitLab instance for pr --gitlab when --gitlab-url isn't given.",
+    ),
+    (
+        "GIT_EDITOR, VISUAL, EDITOR",
+        "The editor for --edit, after git's core.editor, in git's order.",
+    ),
+    (
+        "GIT_PAGER, PAGER",
+        "The pager for --show-diff, after git's core.pager; LESS defaults to FRX.",
+    ),
+    ("NO_COLOR", "A non-empty value turns color off, like --no-color."),
+    (
+        "TERM, LC_ALL, LC_CTYPE, LANG",
+        "TERM=dumb, or a locale that isn't UTF-8, implies --ascii.",
+    ),
+    (
+        "RUST_LOG",
+        "Filter for the diagnostics, replacing the one -v sets (e.g. git_changes_rs=debug).",
+    ),
+    (
+        "XDG_CACHE_HOME",
+        "Where the model list for completing --model is kept (default ~/.cache).",
+    ),
+    (
+        "XDG_CONFIG_HOME",
+        "Where git-changes/config.toml, the user's configuration, is read from (default ~/.config).",
+    ),
+];
+
+pub fn run(sub: &ArgMatches) -> Result<()> {
+    let mut command = cli::build().disable_help_subcommand(true);
+    command.build();
+
+    let Some(dir) = sub.get_one::<String>("out_dir") else {
+        return render(&command, true, &mut io::stdout());
+    };
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    write_page(&command, true, dir)?;
+    for subcommand in command.get_subcommands().filter(|s| !s.is_hide_set()) {
+        write_page(subcommand, false, dir)?;
+    }
+    Ok(())
+}
+
+fn write_page(command: &Command, main: bool, dir: &Path) -> Result<()> {
+    let path = dir.join(Man::new(command.clone()).get_filename());
+    let mut page = Vec::new();
+    render(command, main, &mut page)?;
+    fs::write(&path, page).with_context(|| format!("failed to write {}", path.display()))?;
+    status!("wrote {}", path.display());
+    Ok(())
+}
+
+// clap_mangen's sections, with the environment and exit status as sections of their own
+// instead of the --help epilogue
+fn render(command: &Command, main: bool, out: &mut dyn Write) -> Result<()> {
+    let man = Man::new(command.clone());
+    man.render_title(out)?;
+    man.render_name_section(out)?;
+    man.render_synopsis_section(out)?;
+    man.render_description_section(out)?;
+    if command.get_arguments().any(|arg| !arg.is_hide_set()) {
+        man.render_options_section(out)?;
+    }
+    if command.get_subcommands().any(|sub| !sub.is_hide_set()) {
+        man.render_subcommands_section(out)?;
+    }
+    if main {
+        writeln!(out, ".SH ENVIRONMENT")?;
+        for (name, meaning) in ENVIRONMENT {
+            writeln!(out, ".TP\n\\fB{}\\fR\n{}", name, escape(meaning))?;
+        }
+        for setting in &config::SETTINGS {
+            let meaning = format!("Overrides {} from the config files.", setting.key);
+            writeln!(out, ".TP\n\\fB{}\\fR\n{}", setting.env, escape(&meaning))?;
+        }
+    }
+    if main || command.get_after_help().is_some() {
+        writeln!(out, ".SH \"EXIT STATUS\"")?;
+        for (code, meaning) in exit::STATUSES {
+            writeln!(out, ".TP\n\\fB{}\\fR\n{}", code, escape(meaning))?;
+        }
+    }
+    if command.get_version().is_some() {
+        man.render_version_section(out)?;
+    }
+    Ok(())
+}
+
+// a leading `.` or `'` would start a roff request, and `\` an escape
+fn escape(text: &str) -> String {
+    let text = text.replace('\\', "\\e").replace('-', "\\-");
+    match text.starts_with(['.', '\'']) {
+        true => format!("\\&{}", text),
+        false => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(command: &Command, main: bool) -> String {
+        let mut out = Vec::new();
+        render(command, main, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn built() -> Command {
+        let mut command = cli::build().disable_help_subcommand(true);
+        command.build();
+        command
+    }
+
+    // the page as roff writes a flag, e.g. `\-\-no\-color`
+    fn flag(long: &str) -> String {
+        format!("\\-\\-{}", long.replace('-', "\\-"))
+    }
+
+    #[test]
+    fn the_main_page_documents_every_flag_variable_and_status() {
+        let command = built();
+        let page = page(&command, true);
+        assert!(
+            page.starts_with(".ie \\n(.g .ds Aq \\(aq"),
+            "{}",
+            &page[..80]
+        );
+        assert!(page.contains(".TH git-changes-rs 1"));
+        for arg in command.get_arguments().filter(|arg| !arg.is_hide_set()) {
+            if let Some(long) = arg.get_long() {
+                assert!(page.contains(&flag(long)), "--{} is missing", long);
+            }
+        }
+        for (name, _) in ENVIRONMENT {
+            assert!(page.contains(&format!("\\fB{}\\fR", name)), "{}", name);
+        }
+        for setting in &config::SETTINGS {
+            let name = setting.env.replace('-', "\\-");
+            assert!(page.contains(&format!("\\fB{}\\fR", name)), "{}", name);
+        }
+        assert!(page.contains(".SH \"EXIT STATUS\""));
+        for (code, _) in exit::STATUSES {
+            assert!(
+                page.contains(&format!(".TP\n\\fB{}\\fR\n", code)),
+                "{}",
+                code
+            );
+        }
+        assert!(!page.contains("generate\\-man"));
+    }
+
+    #[test]
+    fn a_subcommand_page_has_its_own_flags_and_no_environment() {
+        let command = built();
+        let lint = command.find_subcommand("lint").unwrap();
+        let page = page(lint, false);
+        assert!(page.contains(&flag("fix-suggestions")));
+        assert!(page.contains(&flag("model")));
+        assert!(!page.contains(".SH ENVIRONMENT"));
+        assert!(!page.contains(&flag("commit")));
+    }
+
+    #[test]
+    fn text_that_roff_would_read_as_a_request_is_escaped() {
+        assert_eq!(escape("a --flag"), "a \\-\\-flag");
+        assert_eq!(escape(".hidden"), "\\&.hidden");
+        assert_eq!(escape("'quoted'"), "\\&'quoted'");
+        assert_eq!(escape("C:\\path"), "C:\\epath");
+    }
+
+    #[test]
+    fn out_dir_gets_a_page_per_visible_subcommand() {
+        let dir = tempfile::tempdir().unwrap();
+        let matches = cli::build()
+            .try_get_matches_from([
+                "git-changes-rs",
+                "generate-man",
+                "--out-dir",
+                dir.path().to_str().unwrap(),
+            ])
+            .unwrap();
+        run(matches.subcommand_matches("generate-man").unwrap()).unwrap();
+
+        let pages: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(pages.contains(&"git-changes-rs.1".to_string()));
+        assert!(pages.contains(&"git-changes-rs-lint.1".to_string()));
+        assert!(pages.contains(&"git-changes-rs-standup.1".to_string()));
+        assert!(!pages.iter().any(|page| page.contains("generate-man")));
+        let main = fs::read_to_string(dir.path().join("git-changes-rs.1")).unwrap();
+        assert!(main.contains(".SH ENVIRONMENT"));
+    }
+}
@@ -0,0 +1,152 @@
+// src/commands/mod.rs
+// what each subcommand does, and the helpers several of them share
+
+pub mod branch_name;
+pub mod bump;
+pub mod changelog;
+pub mod completions;
+pub mod config;
+pub mod doctor;
+pub mod explain;
+pub mod generate;
+pub mod hooks;
+pub mod journal;
+pub mod lint;
+pub mod man;
+pub mod models;
+pub mod notes;
+pub mod pr;
+pub mod release_notes;
+pub mod review;
+pub mod reword;
+pub mod split;
+pub mod standup;
+pub mod stash;
+pub mod tag;
+
+use crate::{
+    config::Config, diff::fetch_diffs, diff::DiffMode, diff::Untracked, filter::is_excluded,
+    format::FormatOptions, history, prompt, prompt::PromptOptions, validate, validate::Rules,
+};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use git2::Repository;
+
+// the patterns no command sends to the model: Cargo.lock, diff.exclude from the config (set
+// by GIT_CHANGES_EXCLUDE too) and then the command's own --exclude
+fn excludes<'a>(config: &Config, given: impl IntoIterator<Item = &'a String>) -> Vec<String> {
+    let mut excludes: Vec<String> = vec!["Cargo.lock".to_string()];
+    excludes.extend(config.diff.exclude.iter().cloned());
+    excludes.extend(given.into_iter().cloned());
+    excludes
+}
+
+// the patch text, its files and a short description of where they came from, for the
+// arguments of diff_source_args
+fn selected_diff(
+    repo: &Repository,
+    config: &Config,
+    sub: &ArgMatches,
+) -> Result<(String, Vec<String>, String)> {
+    let excludes = excludes(
+        config,
+        sub.get_many::<String>("exclude").into_iter().flatten(),
+    );
+
+    if let Some(from) = sub.get_one::<String>("from") {
+        let to = sub.get_one::<String>("to").context("to has a default")?;
+        let to_oid = history::resolve(repo, to)?;
+        let base = repo
+            .merge_base(history::resolve(repo, from)?, to_oid)
+            .with_context(|| format!("{} and {} have no common history", from, to))?;
+        let diff = history::diff_between(repo, base, to_oid)?;
+        let files = diff
+            .deltas()
+            .filter(|delta| !is_excluded(delta, &excludes))
+            .filter_map(|d| d.new_file().path().or(d.old_file().path()))
+            .map(|p| p.to_string_lossy().into_owned())
+            .collect();
+        let text = history::patch_text_where(&diff, |delta| !is_excluded(delta, &excludes))?;
+        return Ok((text, files, format!("{}...{}", from, to)));
+    }
+
+    let mode = match sub.get_flag("staged") {
+        true => DiffMode::Staged,
+        false => DiffMode::Auto,
+    };
+    let changes =
+        fetch_diffs(repo, &excludes, mode, Untracked::None).context("failed to fetch diffs")?;
+    let source = match changes.staged {
+        true => "staged changes",
+        false => "unstaged changes",
+    };
+    Ok((changes.diff, changes.files, source.to_string()))
+}
+
+// formatting and validation settings from the config file, for the subcommands
+fn config_rules(config: &Config) -> Result<(FormatOptions, Rules)> {
+    let format_options = FormatOptions::for_language(
+        config.message.language.as_deref(),
+        config.message.max_title_length,
+        config.message.warn_title_length,
+    );
+    let types = validate::resolve_types(config.commit.types.clone())
+        .context("invalid commit.types in config")?;
+    let rules = Rules {
+        types,
+        max_title_length: format_options.max_title_length,
+    };
+    Ok((format_options, rules))
+}
+
+// prompt settings from the config file alone, for subcommands that rewrite existing messages
+fn config_prompt_options(config: &Config, rules: &Rules) -> Result<PromptOptions> {
+    let style_name = config
+        .message
+        .style
+        .clone()
+        .unwrap_or_else(|| prompt::DEFAULT_STYLE.to_string());
+    Ok(PromptOptions {
+        language: config.message.language.clone(),
+        style: config.style(&style_name)?,
+        types: if validate::is_default_types(&rules.types) {
+            Vec::new()
+        } else {
+            rules.types.clone()
+        },
+        ..PromptOptions::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::IndexAddOption;
+    use std::fs;
+
+    #[test]
+    fn the_config_excludes_apply_to_the_subcommands_too() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        for file in ["notes.txt", "secrets.env", "build.log", "Cargo.lock"] {
+            fs::write(dir.path().join(file), "contents\n").unwrap();
+        }
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+
+        let config: Config = toml::from_str("[diff]\nexclude = [\"*.env\"]\n").unwrap();
+        assert_eq!(
+            excludes(&config, &["*.log".to_string()]),
+            ["Cargo.lock", "*.env", "*.log"]
+        );
+        let matches = crate::cli::build()
+            .try_get_matches_from(["git-changes-rs", "explain", "--staged", "-e", "*.log"])
+            .unwrap();
+        let (_, sub) = matches.subcommand().unwrap();
+        let (diff, files, source) = selected_diff(&repo, &config, sub).unwrap();
+        assert_eq!(files, ["notes.txt"]);
+        assert!(!diff.contains("secrets.env"), "{}", diff);
+        assert_eq!(source, "staged changes");
+    }
+}
@@ -0,0 +1,24 @@
+// src/commands/models.rs
+// models: what --model can be set to
+
+use crate::{cache, provider::list_models, provider::model_id};
+use anyhow::{Context, Result};
+use tracing::warn;
+
+pub async fn run() -> Result<()> {
+    let models = list_models().await.context("failed to list the models")?;
+    let current = model_id();
+    for model in &models {
+        match model == current {
+            true => outln!("* {}", model)?,
+            false => outln!("  {}", model)?,
+        }
+    }
+    if !models.iter().any(|model| model == current) {
+        warn!("the configured model {} isn't in the list", current);
+    }
+    if let Err(e) = cache::store_models(&models) {
+        warn!("failed to keep the list for completing --model: {:#}", e);
+    }
+    Ok(())
+}
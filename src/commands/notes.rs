@@ -0,0 +1,28 @@
+// src/commands/notes.rs
+// notes: reading back what --note recorded
+
+use crate::{history, invocation, notes};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+
+pub fn run(sub: &ArgMatches) -> Result<()> {
+    let Some(("show", sub)) = sub.subcommand() else {
+        return Err(anyhow!("notes needs a subcommand (show)"));
+    };
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let rev = sub.get_one::<String>("rev").context("rev has a default")?;
+
+    let oid = history::resolve(&repo, rev)?;
+    let text = notes::read(&repo, oid)?.with_context(|| {
+        format!(
+            "{} has no note in {}",
+            history::short_id(oid),
+            notes::NOTES_REF
+        )
+    })?;
+    outln!("{}", notes::render(&text))?;
+    Ok(())
+}
@@ -0,0 +1,80 @@
+// src/commands/pr.rs
+// pr: a pull request title and description for the current branch
+
+use super::excludes;
+use crate::{
+    config, filter::is_excluded, github, gitlab, history, invocation, output, pr, provider,
+    provider::send_to_gemini,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+use std::path::Path;
+use tracing::info;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+
+    let range = pr::branch_range(&repo, sub.get_one::<String>("base").map(String::as_str))?;
+    if range.commits.is_empty() {
+        return Err(anyhow!(
+            "no commits between {} and HEAD; nothing to describe",
+            range.base_name
+        ));
+    }
+    info!(
+        "describing {} commits since {} ({})",
+        range.commits.len(),
+        history::short_id(range.merge_base),
+        range.base_name
+    );
+
+    let mut subjects = Vec::new();
+    for oid in range.commits.iter().rev() {
+        let commit = repo.find_commit(*oid)?;
+        subjects.push(commit.summary().unwrap_or_default().to_string());
+    }
+    let excludes = excludes(&config, []);
+    let diff = history::diff_between(&repo, range.merge_base, range.head)?;
+    let diff = history::patch_text_where(&diff, |delta| !is_excluded(delta, &excludes))?;
+
+    let client = provider::client(&config.generation)?;
+    let response = send_to_gemini(
+        &client,
+        pr::PR_SYSTEM_PROMPT,
+        &pr::pr_prompt(&subjects, &diff),
+        pr::PR_MAX_OUTPUT_TOKENS,
+        1,
+    )
+    .await
+    .context("failed to fetch response from gemini api")?
+    .remove(0);
+    let text = pr::PrText::parse(&response).context("the model returned an empty description")?;
+
+    let publish = sub.get_flag("github") || sub.get_flag("gitlab");
+    match sub.get_one::<String>("output") {
+        Some(path) => {
+            output::write_message(Path::new(path), &text.markdown())?;
+            info!("wrote the description to {}", path);
+        }
+        None if !publish => outln!("{}", text.markdown())?,
+        None => {}
+    }
+    let base = sub.get_one::<String>("base").map(String::as_str);
+    if sub.get_flag("github") {
+        github::publish(&repo, &text, base, sub.get_flag("dry_run")).await?;
+    }
+    if sub.get_flag("gitlab") {
+        let options = gitlab::GitLabOptions {
+            base,
+            url: sub.get_one::<String>("gitlab_url").map(String::as_str),
+            draft: sub.get_flag("draft"),
+            dry_run: sub.get_flag("dry_run"),
+        };
+        gitlab::publish(&repo, &text, &options).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,96 @@
+// src/commands/release_notes.rs
+// release-notes: user-facing notes for a range of commits
+
+use crate::{
+    changelog, changelog_file, config, invocation, output, provider, provider::send_to_gemini,
+    release_notes,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+use std::path::Path;
+use tracing::info;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let from = sub.get_one::<String>("from").context("from is required")?;
+    let to = sub.get_one::<String>("to").context("to has a default")?;
+    let audience = sub
+        .get_one::<String>("audience")
+        .and_then(|name| release_notes::Audience::from_name(name))
+        .context("audience has a default")?;
+
+    let heading = match sub.get_one::<String>("heading") {
+        Some(template) => {
+            let version = match sub.get_one::<String>("release") {
+                Some(version) => version.as_str(),
+                None if template.contains("{version}") && to == "HEAD" => {
+                    return Err(anyhow!(
+                        "--heading uses {{version}} but --to is HEAD; pass --release <VERSION>"
+                    ))
+                }
+                None => to.as_str(),
+            };
+            Some(release_notes::heading(
+                template,
+                version,
+                &changelog_file::today(),
+            ))
+        }
+        None => None,
+    };
+
+    let entries = changelog::range_entries(&repo, Some(from), to)?;
+    let (internal, shown): (Vec<&changelog::Entry>, Vec<&changelog::Entry>) = entries
+        .iter()
+        .partition(|entry| release_notes::is_internal(entry));
+    info!(
+        "{} commits from {} to {}; leaving out {} internal (refactors, tests, CI)",
+        entries.len(),
+        from,
+        to,
+        internal.len()
+    );
+    if shown.is_empty() {
+        return Err(anyhow!(
+            "all {} commits between {} and {} are internal; nothing to tell {}",
+            entries.len(),
+            from,
+            to,
+            sub.get_one::<String>("audience")
+                .context("audience has a default")?
+        ));
+    }
+
+    let client = provider::client(&config.generation)?;
+    let response = send_to_gemini(
+        &client,
+        audience.system_prompt(),
+        &release_notes::release_notes_prompt(from, to, &shown),
+        release_notes::RELEASE_NOTES_MAX_OUTPUT_TOKENS,
+        1,
+    )
+    .await
+    .context("failed to fetch response from gemini api")?
+    .remove(0);
+    let notes = response.trim();
+    if notes.is_empty() {
+        return Err(anyhow!("the model returned empty release notes"));
+    }
+    let markdown = match heading {
+        Some(heading) => format!("{}\n\n{}", heading, notes),
+        None => notes.to_string(),
+    };
+
+    match sub.get_one::<String>("output") {
+        Some(path) => {
+            output::write_message(Path::new(path), &markdown)?;
+            info!("wrote the release notes to {}", path);
+        }
+        None => outln!("{}", markdown)?,
+    }
+    Ok(())
+}
@@ -0,0 +1,54 @@
+// src/commands/review.rs
+// review: line-referenced findings for a diff
+
+use super::selected_diff;
+use crate::{
+    code_review, config, error::GitChangesError, exit, invocation, provider,
+    provider::send_to_gemini,
+};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use tracing::info;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let severity = sub
+        .get_one::<String>("severity")
+        .and_then(|name| code_review::Severity::from_name(name))
+        .context("severity has a default")?;
+
+    let (diff, files, source) = selected_diff(&repo, &config, sub)?;
+    if diff.trim().is_empty() {
+        return Err(
+            GitChangesError::no_changes(format!("no changes to review ({})", source)).into(),
+        );
+    }
+    info!("reviewing {} ({} files)", source, files.len());
+
+    let client = provider::client(&config.generation)?;
+    let response = send_to_gemini(
+        &client,
+        code_review::REVIEW_SYSTEM_PROMPT,
+        &code_review::review_prompt(&diff, &files),
+        code_review::REVIEW_MAX_OUTPUT_TOKENS,
+        1,
+    )
+    .await
+    .context("failed to fetch response from gemini api")?
+    .remove(0);
+    let findings = code_review::parse_findings(&response, &files)?;
+
+    out!("{}", code_review::render(&findings, &files, severity))?;
+    // errors fail the run whatever --severity hides, so a hook can gate on it
+    if findings
+        .iter()
+        .any(|f| f.severity == code_review::Severity::Error)
+    {
+        return Err(exit::Status(exit::ERROR).into());
+    }
+    Ok(())
+}
@@ -0,0 +1,98 @@
+// src/commands/reword.rs
+// reword: better messages for the low-quality commits on the current branch
+
+use super::{config_prompt_options, config_rules};
+use crate::{
+    config, history, invocation, message::generate_message, prompt, provider, reword, trailers,
+    validate,
+};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use tracing::warn;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let (format_options, rules) = config_rules(&config)?;
+    let prompt_options = config_prompt_options(&config, &rules)?;
+    let system_prompt = prompt::build_system_prompt(&prompt_options);
+    let client = provider::client(&config.generation)?;
+
+    let onto = sub.get_one::<String>("onto").map(String::as_str);
+    let mut entries = Vec::new();
+    for oid in reword::branch_commits(&repo, onto)? {
+        let commit = repo.find_commit(oid)?;
+        let original = commit.message().unwrap_or_default().to_string();
+        let mut entry = reword::RewordEntry {
+            oid,
+            original,
+            message: None,
+        };
+        if commit.parent_count() > 1 || validate::validate(&entry.original, &rules).is_empty() {
+            entries.push(entry);
+            continue;
+        }
+
+        let short_id = history::short_id(oid);
+        status!("rewording {} {}...", short_id, entry.subject());
+        let diff = history::diff_to_parent(&repo, oid)?;
+        let user_prompt = format!(
+            "{}\n\n{}",
+            prompt::build_user_prompt(
+                &history::patch_text(&diff)?,
+                &history::changed_paths(&diff),
+                &prompt_options
+            ),
+            prompt::previous_message_section(&entry.original)
+        );
+        match generate_message(
+            &client,
+            &system_prompt,
+            &user_prompt,
+            &prompt_options,
+            &format_options,
+            Some(&rules),
+        )
+        .await
+        {
+            Ok(message) => entry.message = Some(trailers::preserve(&entry.original, &message)),
+            Err(e) => warn!("keeping the message of {}: {:#}", short_id, e),
+        }
+        entries.push(entry);
+    }
+
+    let reworded: Vec<&reword::RewordEntry> =
+        entries.iter().filter(|e| e.message.is_some()).collect();
+    if reworded.is_empty() {
+        status!("no commits need rewording ({} checked)", entries.len());
+        return Ok(());
+    }
+    for entry in &reworded {
+        outln!(
+            "{} {}\n    -> {}",
+            history::short_id(entry.oid),
+            entry.subject(),
+            entry
+                .message
+                .as_deref()
+                .and_then(|m| m.lines().next())
+                .unwrap_or_default()
+        )?;
+    }
+
+    if sub.get_flag("apply") {
+        reword::check_apply(&repo, &entries)?;
+        let tip = reword::apply(&repo, &entries)?;
+        status!(
+            "\nreworded {} commits; branch now at {}",
+            reworded.len(),
+            history::short_id(tip)
+        );
+    } else {
+        outln!("\n{}", reword::todo_list(&repo, &entries)?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,135 @@
+// src/commands/split.rs
+// split: proposing, and optionally making, several commits out of the working tree
+
+use super::{config_prompt_options, config_rules};
+use crate::{
+    commit, config, diff::fetch_diffs, diff::DiffMode, diff::Untracked, error::GitChangesError,
+    history, invocation, message::generate_message, prompt, provider, provider::send_to_gemini,
+    sign::Signer, split,
+};
+use anyhow::{Context, Result};
+use clap::ArgMatches;
+use std::path::PathBuf;
+use tracing::warn;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let (format_options, rules) = config_rules(&config)?;
+    let prompt_options = config_prompt_options(&config, &rules)?;
+    let apply = sub.get_flag("apply");
+
+    // nothing is excluded: every changed file has to end up in some commit
+    let changes = fetch_diffs(&repo, &[], DiffMode::Auto, Untracked::None)
+        .context("failed to fetch diffs")?;
+    if changes.files.is_empty() {
+        return Err(GitChangesError::no_changes("no changes to split").into());
+    }
+    let signer = if apply {
+        split::check_apply(&repo)?;
+        commit::signature(&repo)?;
+        Signer::from_config(&repo, false)?
+    } else {
+        None
+    };
+    let client = provider::client(&config.generation)?;
+
+    let groups = if changes.files.len() == 1 {
+        vec![changes.files.clone()]
+    } else {
+        status!("grouping {} changed files...", changes.files.len());
+        let excerpts: Vec<(&str, &str)> = changes
+            .files
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path.as_str(), changes.file_patch(i)))
+            .collect();
+        let response = send_to_gemini(
+            &client,
+            split::GROUPING_SYSTEM_PROMPT,
+            &split::grouping_prompt(&excerpts),
+            prompt::DEFAULT_MAX_OUTPUT_TOKENS,
+            1,
+        )
+        .await
+        .context("failed to fetch the grouping from gemini api")?
+        .remove(0);
+        let (groups, unknown) = split::parse_groups(&response, &changes.files);
+        for path in &unknown {
+            warn!(
+                "ignoring '{}' from the grouping; it is not a changed path",
+                path
+            );
+        }
+        groups
+    };
+
+    let system_prompt = prompt::build_system_prompt(&prompt_options);
+    let mut planned = Vec::new();
+    for files in groups {
+        let indices: Vec<usize> = files
+            .iter()
+            .filter_map(|f| changes.files.iter().position(|c| c == f))
+            .collect();
+        let patch: String = indices.iter().map(|&i| changes.file_patch(i)).collect();
+        let mut paths: Vec<PathBuf> = indices
+            .iter()
+            .flat_map(|&i| changes.changes[i].paths())
+            .collect();
+        paths.dedup();
+
+        status!("describing {}...", files.join(", "));
+        let message = generate_message(
+            &client,
+            &system_prompt,
+            &prompt::build_user_prompt(&patch, &files, &prompt_options),
+            &prompt_options,
+            &format_options,
+            Some(&rules),
+        )
+        .await?;
+        planned.push((split::SplitGroup { files, message }, paths));
+    }
+
+    for (number, (group, _)) in planned.iter().enumerate() {
+        outln!(
+            "\ncommit {}/{}: {}\n    files: {}\n---\n{}\n---",
+            number + 1,
+            planned.len(),
+            group.subject(),
+            group.files.join(", "),
+            group.message
+        )?;
+    }
+
+    if !apply {
+        outln!(
+            "\nto make these commits:\n{}",
+            split::commands(&repo, &planned)?
+        )?;
+        return Ok(());
+    }
+    for (number, (group, paths)) in planned.iter().enumerate() {
+        let committed = split::stage_paths(&repo, paths)
+            .and_then(|()| commit::create(&repo, &group.message, signer.as_ref()));
+        match committed {
+            Ok(oid) => status!("committed {} {}", history::short_id(oid), group.subject()),
+            Err(e) => {
+                let remaining: Vec<&str> = planned[number..]
+                    .iter()
+                    .flat_map(|(g, _)| g.files.iter().map(String::as_str))
+                    .collect();
+                return Err(e.context(format!(
+                    "stopped after {} of {} commits; not committed: {}",
+                    number,
+                    planned.len(),
+                    remaining.join(", ")
+                )));
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,93 @@
+// src/commands/standup.rs
+// standup: recent commits by day, summarized
+
+use crate::{
+    config::GenerationConfig, invocation, output, provider, provider::send_to_gemini, standup,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+use git2::Repository;
+use std::path::Path;
+use tracing::{info, warn};
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let since_text = sub
+        .get_one::<String>("since")
+        .context("since has a default")?;
+    // git2 works out the local time zone for new signatures
+    let now = git2::Signature::now("git-changes-rs", "git-changes-rs")
+        .context("failed to read the current time")?
+        .when();
+    let since = standup::parse_since(since_text, now.seconds(), now.offset_minutes())?;
+
+    let repos = if sub.get_flag("recursive") {
+        let paths = standup::discover_repos(Path::new(directory), 3);
+        let mut repos = Vec::new();
+        for path in paths {
+            match Repository::open(&path) {
+                Ok(repo) => repos.push(repo),
+                Err(e) => warn!("skipping {}: {}", path.display(), e.message()),
+            }
+        }
+        if repos.is_empty() {
+            return Err(anyhow!("no git repositories under {}", directory));
+        }
+        repos
+    } else {
+        vec![invocation::open(directory).context("failed to open git repository")?]
+    };
+
+    let mut commits = Vec::new();
+    for repo in &repos {
+        let author = match sub.get_one::<String>("author") {
+            Some(author) => author.clone(),
+            None => repo
+                .config()
+                .and_then(|config| config.get_string("user.email"))
+                .map_err(|_| anyhow!("user.email isn't set; pass --author <email>"))?,
+        };
+        let label = repo
+            .workdir()
+            .unwrap_or_else(|| repo.path())
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        commits.extend(standup::collect(repo, &label, &author, since)?);
+    }
+    if commits.is_empty() {
+        status!("no commits of yours since {}", since_text);
+        return Ok(());
+    }
+    commits.sort_by_key(|commit| commit.time);
+    info!(
+        "summarizing {} commits from {} repositories",
+        commits.len(),
+        repos.len()
+    );
+
+    // the default sampling: standup reads no config
+    let client = provider::client(&GenerationConfig::default())?;
+    let response = send_to_gemini(
+        &client,
+        standup::STANDUP_SYSTEM_PROMPT,
+        &standup::standup_prompt(&commits, repos.len() > 1),
+        standup::STANDUP_MAX_OUTPUT_TOKENS,
+        1,
+    )
+    .await
+    .context("failed to fetch response from gemini api")?
+    .remove(0);
+    let summary = response.trim();
+
+    match sub.get_one::<String>("output") {
+        Some(path) => {
+            output::write_message(Path::new(path), summary)?;
+            info!("wrote the summary to {}", path);
+        }
+        None => outln!("{}", summary)?,
+    }
+    Ok(())
+}
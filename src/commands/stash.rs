@@ -0,0 +1,65 @@
+// src/commands/stash.rs
+// stash: stashing the changes under a generated description
+
+use super::{config_prompt_options, config_rules, excludes};
+use crate::{
+    config, filter::is_excluded, history, invocation, message::generate_message, prompt,
+    prompt::PromptOptions, provider, stash,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let mut repo = invocation::open(directory).context("failed to open git repository")?;
+    let include_untracked = sub.get_flag("include_untracked");
+
+    let (tracked, untracked) = stash::dirty_state(&repo)?;
+    let stashable = tracked || (untracked && include_untracked);
+    if !stashable && untracked {
+        return Err(anyhow!(
+            "nothing to stash; untracked files are only stashed with --include-untracked"
+        ));
+    }
+    if !stashable {
+        return Err(anyhow!("nothing to stash; the working tree is clean"));
+    }
+
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let (format_options, rules) = config_rules(&config)?;
+    let prompt_options = PromptOptions {
+        title_only: true,
+        ..config_prompt_options(&config, &rules)?
+    };
+    let excludes = excludes(&config, []);
+    let (diff, files) = {
+        let diff = stash::dirty_diff(&repo, include_untracked)?;
+        let files = history::changed_paths(&diff);
+        (
+            history::patch_text_where(&diff, |delta| !is_excluded(delta, &excludes))?,
+            files,
+        )
+    };
+
+    let client = provider::client(&config.generation)?;
+    let message = generate_message(
+        &client,
+        &prompt::build_system_prompt(&prompt_options),
+        &prompt::build_user_prompt(&diff, &files, &prompt_options),
+        &prompt_options,
+        &format_options,
+        None,
+    )
+    .await?;
+    let message = message.lines().next().unwrap_or_default().to_string();
+
+    if sub.get_flag("dry_run") {
+        outln!("{}", message)?;
+        return Ok(());
+    }
+    stash::save(&mut repo, &message, include_untracked)?;
+    status!("stashed stash@{{0}}: {}", message);
+    Ok(())
+}
@@ -0,0 +1,82 @@
+// src/commands/tag.rs
+// tag: an annotated tag on HEAD with a generated summary
+
+use crate::{
+    changelog, config, history, interactivity, interactivity::Interactivity,
+    interactivity::Terminal, invocation, provider, provider::send_to_gemini, tag,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::ArgMatches;
+use tracing::info;
+
+pub async fn run(sub: &ArgMatches) -> Result<()> {
+    let directory = sub
+        .get_one::<String>("repo")
+        .context("repo has a default")?;
+    let repo = invocation::open(directory).context("failed to open git repository")?;
+    let config = config::load(&repo).context("failed to load configuration")?;
+    let name = sub.get_one::<String>("name").context("name is required")?;
+    let force = sub.get_flag("force");
+    let dry_run = sub.get_flag("dry_run");
+
+    if !tag::is_valid_name(name) {
+        return Err(anyhow!("'{}' is not a valid tag name", name));
+    }
+    if tag::exists(&repo, name) && !force && !dry_run {
+        return Err(anyhow!(
+            "tag '{}' already exists; pass --force to replace it",
+            name
+        ));
+    }
+
+    let from = match sub.get_one::<String>("from") {
+        Some(from) => Some(from.clone()),
+        None => tag::previous_tag(&repo, Some(name))?,
+    };
+    match &from {
+        Some(from) => info!("summarizing the commits since {}", from),
+        None => info!("no earlier tag; summarizing all of HEAD's history"),
+    }
+    let entries = changelog::range_entries(&repo, from.as_deref(), "HEAD")?;
+
+    let client = provider::client(&config.generation)?;
+    let response = send_to_gemini(
+        &client,
+        tag::TAG_SYSTEM_PROMPT,
+        &tag::tag_prompt(name, from.as_deref(), &entries),
+        tag::TAG_MAX_OUTPUT_TOKENS,
+        1,
+    )
+    .await
+    .context("failed to fetch response from gemini api")?
+    .remove(0);
+    let message = tag::clean_message(&response);
+    if message.is_empty() {
+        return Err(anyhow!("the model returned an empty tag message"));
+    }
+    if dry_run {
+        outln!("{}", message)?;
+        return Ok(());
+    }
+
+    status!("\ntag message for {}:\n---\n{}\n---", name, message);
+    if Interactivity::detect(sub.get_flag("yes")).is_interactive() {
+        let mut terminal = Terminal::open().context("the terminal went away")?;
+        let question = format!("create tag {} on HEAD?", name);
+        let confirmed =
+            interactivity::confirm(&question, &mut terminal.input, &mut terminal.output)
+                .context("failed to read the answer")?;
+        if !confirmed {
+            return Err(anyhow!("tag not created"));
+        }
+    }
+
+    if tag::wants_signature(&repo, sub.get_flag("sign")) {
+        tag::create_signed_with_git(&repo, name, &message, force)?;
+        status!("created signed tag {}", name);
+    } else {
+        let oid = tag::create(&repo, name, &message, force)?;
+        status!("created tag {} ({})", name, history::short_id(oid));
+    }
+    Ok(())
+}
@@ -0,0 +1,411 @@
+// src/commit.rs
+// creating the commit from the index once the message is final
+
+use crate::diff::Untracked;
+use crate::error::GitChangesError;
+use crate::interrupt;
+use crate::sign::Signer;
+use anyhow::{anyhow, Context, Result};
+use git2::{Commit, ErrorCode, IndexAddOption, Oid, Repository, Signature, Tree};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// HEAD's tree, or none on an unborn branch
+pub fn head_tree(repo: &Repository) -> Result<Option<Tree<'_>>> {
+    match repo.head() {
+        Ok(head) => {
+            let tree = head
+                .peel_to_tree()
+                .map_err(|e| GitChangesError::git("peel head ref to tree", e))?;
+            Ok(Some(tree))
+        }
+        Err(e) if e.code() == ErrorCode::UnbornBranch || e.code() == ErrorCode::NotFound => {
+            Ok(None)
+        }
+        Err(e) => Err(GitChangesError::git("get head reference", e).into()),
+    }
+}
+
+// whether the index differs from HEAD, including files the prompt excludes
+pub fn has_staged_changes(repo: &Repository) -> Result<bool> {
+    let head_tree = head_tree(repo)?;
+    let diff = repo
+        .diff_tree_to_index(head_tree.as_ref(), None, None)
+        .context("failed to get diff between head tree and index")?;
+    Ok(diff.deltas().len() > 0)
+}
+
+// `git add -u`, or `git add -A` with the untracked files `untracked` selects
+pub fn stage_all(repo: &Repository, untracked: Untracked) -> Result<()> {
+    let mut index = repo.index().context("failed to read the index")?;
+    index
+        .update_all(["*"], None)
+        .context("failed to stage tracked changes")?;
+    let option = match untracked {
+        Untracked::None => None,
+        Untracked::NotIgnored => Some(IndexAddOption::DEFAULT),
+        Untracked::All => Some(IndexAddOption::FORCE),
+    };
+    if let Some(option) = option {
+        index
+            .add_all(["*"], option, None)
+            .context("failed to stage untracked files")?;
+    }
+    index.write().context("failed to write the index")
+}
+
+pub fn signature(repo: &Repository) -> Result<Signature<'static>> {
+    repo.signature()
+        .map_err(|_| anyhow!("committing needs user.name and user.email to be set in git config"))
+}
+
+// git stores messages with a trailing newline
+fn commit_text(message: &str) -> String {
+    format!("{}\n", message.trim_end())
+}
+
+// moves the branch HEAD is on (or a detached HEAD) to `oid`, as creating a commit would
+fn update_head(repo: &Repository, oid: Oid, log_message: &str) -> Result<()> {
+    let head = repo.find_reference("HEAD").context("failed to read HEAD")?;
+    match head.symbolic_target() {
+        Some(branch) => {
+            repo.reference(branch, oid, true, log_message)
+                .with_context(|| format!("failed to update {}", branch))?;
+        }
+        None => repo
+            .set_head_detached(oid)
+            .context("failed to update the detached HEAD")?,
+    }
+    Ok(())
+}
+
+// writes the commit and points HEAD at it; with a signer, the commit carries its signature
+// in the gpgsig header like `git commit -S`
+fn write_commit(
+    repo: &Repository,
+    author: &Signature<'_>,
+    committer: &Signature<'_>,
+    message: &str,
+    tree: &Tree<'_>,
+    parents: &[&Commit<'_>],
+    signer: Option<&Signer>,
+) -> Result<Oid> {
+    let _writing = interrupt::writing()?;
+    let Some(signer) = signer else {
+        return repo
+            .commit(Some("HEAD"), author, committer, message, tree, parents)
+            .context("failed to create commit");
+    };
+
+    let buffer = repo
+        .commit_create_buffer(author, committer, message, tree, parents)
+        .context("failed to build the commit")?;
+    let buffer = std::str::from_utf8(&buffer).context("the commit is not valid UTF-8")?;
+    let signature = signer.sign(buffer)?;
+    let oid = repo
+        .commit_signed(buffer, &signature, None)
+        .context("failed to create the signed commit")?;
+    update_head(
+        repo,
+        oid,
+        &format!("commit: {}", message.lines().next().unwrap_or_default()),
+    )?;
+    Ok(oid)
+}
+
+// commits the index with HEAD (if any) as the parent; hooks are not run
+pub fn create(repo: &Repository, message: &str, signer: Option<&Signer>) -> Result<Oid> {
+    create_with_parents(repo, message, signer, &[], None)
+}
+
+// concludes an in-progress merge: HEAD and the merged heads are the parents, and MERGE_HEAD
+// and MERGE_MSG are removed like `git commit` does
+pub fn create_merge(
+    repo: &Repository,
+    message: &str,
+    signer: Option<&Signer>,
+    heads: &[Oid],
+) -> Result<Oid> {
+    let _writing = interrupt::writing()?;
+    let oid = create_with_parents(repo, message, signer, heads, None)?;
+    repo.cleanup_state()
+        .context("committed, but failed to clear the merge state")?;
+    // newer gits also leave AUTO_MERGE, which libgit2 doesn't know about
+    let _ = std::fs::remove_file(repo.path().join("AUTO_MERGE"));
+    Ok(oid)
+}
+
+// concludes an in-progress cherry-pick of `original`: like `git commit`, the original author
+// is kept, and CHERRY_PICK_HEAD is removed
+pub fn create_cherry_pick(
+    repo: &Repository,
+    message: &str,
+    signer: Option<&Signer>,
+    original: Oid,
+) -> Result<Oid> {
+    let original = repo
+        .find_commit(original)
+        .with_context(|| format!("failed to look up the cherry-picked commit {}", original))?;
+    let oid = create_with_parents(repo, message, signer, &[], Some(original.author()))?;
+    repo.cleanup_state()
+        .context("committed, but failed to clear the cherry-pick state")?;
+    Ok(oid)
+}
+
+fn create_with_parents(
+    repo: &Repository,
+    message: &str,
+    signer: Option<&Signer>,
+    merged: &[Oid],
+    author: Option<Signature<'_>>,
+) -> Result<Oid> {
+    let signature = signature(repo)?;
+    let mut index = repo.index().context("failed to read the index")?;
+    let tree_id = index
+        .write_tree()
+        .context("failed to write the index tree")?;
+    let tree = repo
+        .find_tree(tree_id)
+        .context("failed to look up index tree")?;
+
+    // only a branch with no commits yet has no parent; any other failure to read HEAD would
+    // otherwise make an unrelated root commit
+    let parent = match repo.head() {
+        Ok(head) => Some(
+            head.peel_to_commit()
+                .context("failed to peel HEAD to a commit")?,
+        ),
+        Err(e) if e.code() == ErrorCode::UnbornBranch => None,
+        Err(e) => return Err(GitChangesError::git("read HEAD", e).into()),
+    };
+    let merged = merged
+        .iter()
+        .map(|oid| {
+            repo.find_commit(*oid)
+                .with_context(|| format!("failed to look up the merged commit {}", oid))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let parents: Vec<&git2::Commit> = parent.iter().chain(merged.iter()).collect();
+
+    write_commit(
+        repo,
+        author.as_ref().unwrap_or(&signature),
+        &signature,
+        &commit_text(message),
+        &tree,
+        &parents,
+        signer,
+    )
+}
+
+// `git commit -F -`, so hooks and commit signing configuration apply; `sign` adds -S
+pub fn create_with_git(repo: &Repository, message: &str, sign: bool) -> Result<Oid> {
+    let workdir = repo
+        .workdir()
+        .context("--use-git-binary needs a repository with a working tree")?;
+    let mut command = Command::new("git");
+    command.arg("commit").arg("--quiet");
+    if sign {
+        command.arg("-S");
+    }
+    let mut child = command
+        .arg("-F")
+        .arg("-")
+        .current_dir(workdir)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("failed to run git")?;
+    child
+        .stdin
+        .take()
+        .context("failed to open git's stdin")?
+        .write_all(commit_text(message).as_bytes())
+        .context("failed to pass the message to git")?;
+    let status = child.wait().context("failed to wait for git")?;
+    if !status.success() {
+        return Err(anyhow!("git commit exited with {}", status));
+    }
+
+    repo.head()
+        .ok()
+        .and_then(|head| head.target())
+        .context("git commit succeeded but HEAD has no target")
+}
+
+// HEAD, if it is safe to amend: not a merge, and not reachable from any remote-tracking ref
+pub fn amend_target(repo: &Repository, force: bool) -> Result<Commit<'_>> {
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_commit())
+        .map_err(|_| anyhow!("--amend needs an existing HEAD commit"))?;
+    if force {
+        return Ok(head);
+    }
+
+    if head.parent_count() > 1 {
+        return Err(anyhow!(
+            "HEAD is a merge commit; refusing to amend it (use --force to override)"
+        ));
+    }
+    let remotes = repo
+        .references_glob("refs/remotes/*")
+        .context("failed to list remote-tracking refs")?;
+    for reference in remotes.flatten() {
+        let Some(target) = reference.target() else {
+            continue;
+        };
+        let published = target == head.id()
+            || repo
+                .graph_descendant_of(target, head.id())
+                .context("failed to check whether HEAD was pushed")?;
+        if published {
+            return Err(anyhow!(
+                "HEAD is already on {}; refusing to amend a pushed commit (use --force to override)",
+                reference.shorthand().unwrap_or("a remote")
+            ));
+        }
+    }
+    Ok(head)
+}
+
+// replaces HEAD with a commit of the current index and `message`, keeping its author and
+// parents like `git commit --amend`
+pub fn amend(
+    repo: &Repository,
+    head: &Commit<'_>,
+    message: &str,
+    signer: Option<&Signer>,
+) -> Result<Oid> {
+    let _writing = interrupt::writing()?;
+    let signature = signature(repo)?;
+    let mut index = repo.index().context("failed to read the index")?;
+    let tree_id = index
+        .write_tree()
+        .context("failed to write the index tree")?;
+    let tree = repo
+        .find_tree(tree_id)
+        .context("failed to look up index tree")?;
+
+    if signer.is_none() {
+        return head
+            .amend(
+                Some("HEAD"),
+                None,
+                Some(&signature),
+                None,
+                Some(&commit_text(message)),
+                Some(&tree),
+            )
+            .context("failed to amend HEAD");
+    }
+    // `Commit::amend` can't sign, so build the replacement from scratch
+    let parents: Vec<Commit> = head.parents().collect();
+    let parents: Vec<&Commit> = parents.iter().collect();
+    write_commit(
+        repo,
+        &head.author(),
+        &signature,
+        &commit_text(message),
+        &tree,
+        &parents,
+        signer,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::Path;
+
+    fn repo() -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        fs::write(dir.path().join("notes.txt"), "notes\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("notes.txt")).unwrap();
+        index.write().unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn the_first_commit_on_an_unborn_branch_has_no_parent() {
+        let (_dir, repo) = repo();
+        let oid = create(&repo, "feat: start the notes", None).unwrap();
+        let commit = repo.find_commit(oid).unwrap();
+        assert_eq!(commit.parent_count(), 0);
+        assert_eq!(commit.message(), Some("feat: start the notes\n"));
+        assert_eq!(repo.head().unwrap().target(), Some(oid));
+    }
+
+    #[test]
+    fn a_later_commit_has_head_as_its_parent() {
+        let (_dir, repo) = repo();
+        let first = create(&repo, "feat: start the notes", None).unwrap();
+        let second = create(&repo, "docs: nothing new", None).unwrap();
+        let commit = repo.find_commit(second).unwrap();
+        assert_eq!(commit.parent_ids().collect::<Vec<_>>(), [first]);
+    }
+
+    #[test]
+    fn a_head_that_cant_be_read_is_an_error_not_a_root_commit() {
+        let (dir, repo) = repo();
+        create(&repo, "feat: start the notes", None).unwrap();
+        let branch = repo.head().unwrap().name().unwrap().to_string();
+        fs::write(dir.path().join(".git").join(&branch), "not an object id\n").unwrap();
+
+        let error = create(&repo, "feat: more notes", None).unwrap_err();
+        assert!(
+            format!("{:#}", error).contains("failed to read HEAD"),
+            "{:#}",
+            error
+        );
+        let contents = fs::read_to_string(dir.path().join(".git").join(&branch)).unwrap();
+        assert_eq!(contents, "not an object id\n");
+    }
+
+    // the paths in the index after `stage_all` in a repository with a modified, a deleted,
+    // an untracked and an ignored file
+    fn staged_with(untracked: Untracked) -> Vec<String> {
+        let (dir, repo) = repo();
+        fs::write(dir.path().join("gone.txt"), "gone\n").unwrap();
+        fs::write(dir.path().join(".gitignore"), "*.log\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_path(Path::new("gone.txt")).unwrap();
+        index.add_path(Path::new(".gitignore")).unwrap();
+        index.write().unwrap();
+        create(&repo, "feat: start the notes", None).unwrap();
+
+        fs::write(dir.path().join("notes.txt"), "more notes\n").unwrap();
+        fs::remove_file(dir.path().join("gone.txt")).unwrap();
+        fs::write(dir.path().join("new.txt"), "new\n").unwrap();
+        fs::write(dir.path().join("build.log"), "noise\n").unwrap();
+        stage_all(&repo, untracked).unwrap();
+
+        let index = repo.index().unwrap();
+        let head = repo.head().unwrap().peel_to_tree().unwrap();
+        let notes = head.get_path(Path::new("notes.txt")).unwrap().id();
+        let entry = index.get_path(Path::new("notes.txt"), 0).unwrap();
+        assert_ne!(entry.id, notes, "the modification isn't staged");
+        index
+            .iter()
+            .map(|entry| String::from_utf8(entry.path).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn stage_all_takes_tracked_changes_and_the_untracked_files_it_is_told_to() {
+        assert_eq!(staged_with(Untracked::None), [".gitignore", "notes.txt"]);
+        assert_eq!(
+            staged_with(Untracked::NotIgnored),
+            [".gitignore", "new.txt", "notes.txt"]
+        );
+        assert_eq!(
+            staged_with(Untracked::All),
+            [".gitignore", "build.log", "new.txt", "notes.txt"]
+        );
+    }
+}
@@ -0,0 +1,974 @@
+// src/config.rs
+//! Optional settings from the user's `config.toml`, the repository's `.git-changes.toml` and
+//! `GIT_CHANGES_*` variables. The command line beats the environment, which beats the
+//! repository, which beats the user's file, which beats the defaults.
+
+use crate::error::GitChangesError;
+use crate::{format, interactivity, large, prompt, validate};
+use anyhow::{anyhow, Context, Result};
+use git2::Repository;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use toml::{Table, Value};
+use tracing::{debug, warn};
+
+/// The repository's config file, at the root of its working tree.
+pub const REPO_CONFIG_FILE: &str = ".git-changes.toml";
+const USER_CONFIG_DIR: &str = "git-changes";
+const USER_CONFIG_FILE: &str = "config.toml";
+const OUTPUT_FORMATS: [&str; 2] = ["plain", "markdown"];
+/// The variable naming a config file to read instead of the others, like `--config`.
+pub const CONFIG_ENV: &str = "GIT_CHANGES_CONFIG";
+
+// how the text of a variable or flag becomes a config value
+#[derive(Clone, Copy)]
+pub(crate) enum Kind {
+    Text,
+    Integer,
+    Float,
+    Bool,
+    // comma-separated
+    List,
+}
+
+// one line per setting outside [styles]; the environment layer, `config init`'s template,
+// --print-config and the man page all read this table, so a new key can't miss any of them
+pub(crate) struct Setting {
+    pub key: &'static str,
+    pub env: &'static str,
+    pub kind: Kind,
+    // the generate flag that beats every layer, or adds to them
+    pub flag: Option<&'static str>,
+    pub flag_appends: bool,
+    pub help: &'static str,
+    // what applies when nothing sets it; None when the setting is simply off
+    pub default: fn() -> Option<Value>,
+    // shown in the template for settings without a default
+    pub example: &'static str,
+}
+
+const fn setting(
+    key: &'static str,
+    env: &'static str,
+    kind: Kind,
+    flag: Option<&'static str>,
+    help: &'static str,
+    default: fn() -> Option<Value>,
+) -> Setting {
+    Setting {
+        key,
+        env,
+        kind,
+        flag,
+        flag_appends: false,
+        help,
+        default,
+        example: "",
+    }
+}
+
+fn text(value: &str) -> Option<Value> {
+    Some(Value::String(value.to_string()))
+}
+
+fn texts(values: &[&str]) -> Option<Value> {
+    Some(Value::Array(
+        values
+            .iter()
+            .map(|v| Value::String(v.to_string()))
+            .collect(),
+    ))
+}
+
+pub(crate) const SETTINGS: [Setting; 23] = [
+    Setting {
+        example: "\"pt-BR\"",
+        ..setting(
+            "message.language",
+            "GIT_CHANGES_LANGUAGE",
+            Kind::Text,
+            Some("lang"),
+            "natural language for the subject and body; english when unset",
+            || None,
+        )
+    },
+    setting(
+        "message.style",
+        "GIT_CHANGES_STYLE",
+        Kind::Text,
+        Some("style"),
+        "style preset, built-in or from [styles]",
+        || text(prompt::DEFAULT_STYLE),
+    ),
+    setting(
+        "message.max_title_length",
+        "GIT_CHANGES_MAX_TITLE_LENGTH",
+        Kind::Integer,
+        Some("max_title_length"),
+        "hard cap for the subject line; overflow moves into the body",
+        || Some(Value::Integer(format::DEFAULT_MAX_TITLE_LENGTH as i64)),
+    ),
+    setting(
+        "message.warn_title_length",
+        "GIT_CHANGES_WARN_TITLE_LENGTH",
+        Kind::Integer,
+        None,
+        "subjects longer than this produce a warning",
+        || Some(Value::Integer(format::DEFAULT_WARN_TITLE_LENGTH as i64)),
+    ),
+    setting(
+        "attribution.enabled",
+        "GIT_CHANGES_ATTRIBUTION",
+        Kind::Bool,
+        Some("attribution"),
+        "append the disclosure trailer even without --attribution",
+        || Some(Value::Boolean(AttributionConfig::default().enabled)),
+    ),
+    setting(
+        "attribution.key",
+        "GIT_CHANGES_ATTRIBUTION_KEY",
+        Kind::Text,
+        None,
+        "the trailer's key",
+        || text(&AttributionConfig::default().key),
+    ),
+    setting(
+        "attribution.template",
+        "GIT_CHANGES_ATTRIBUTION_TEMPLATE",
+        Kind::Text,
+        None,
+        "the trailer's value; {model} and {version} are filled in",
+        || text(&AttributionConfig::default().template),
+    ),
+    setting(
+        "commit.types",
+        "GIT_CHANGES_TYPES",
+        Kind::List,
+        Some("types"),
+        "allowed conventional-commit types",
+        || texts(validate::DEFAULT_TYPES),
+    ),
+    setting(
+        "commit.signoff",
+        "GIT_CHANGES_SIGNOFF",
+        Kind::Bool,
+        Some("signoff"),
+        "add a Signed-off-by trailer even without --signoff",
+        || Some(Value::Boolean(false)),
+    ),
+    setting(
+        "generation.provider",
+        "GIT_CHANGES_PROVIDER",
+        Kind::Text,
+        Some("provider"),
+        "model provider (only gemini for now)",
+        || text(crate::provider::PROVIDERS[0]),
+    ),
+    setting(
+        "generation.model",
+        "GIT_CHANGES_MODEL",
+        Kind::Text,
+        Some("model"),
+        "model id (see the models subcommand)",
+        || text(crate::provider::GEMINI_MODEL),
+    ),
+    setting(
+        "generation.temperature",
+        "GIT_CHANGES_TEMPERATURE",
+        Kind::Float,
+        None,
+        "sampling temperature",
+        || {
+            Some(Value::Float(
+                crate::provider::Sampling::default().temperature,
+            ))
+        },
+    ),
+    setting(
+        "generation.top_p",
+        "GIT_CHANGES_TOP_P",
+        Kind::Float,
+        None,
+        "nucleus sampling cutoff",
+        || Some(Value::Float(crate::provider::Sampling::default().top_p)),
+    ),
+    Setting {
+        example: "40",
+        ..setting(
+            "generation.top_k",
+            "GIT_CHANGES_TOP_K",
+            Kind::Integer,
+            None,
+            "sample only from the k likeliest tokens; the model's own default when unset",
+            || None,
+        )
+    },
+    Setting {
+        example: "512",
+        ..setting(
+            "generation.max_output_tokens",
+            "GIT_CHANGES_MAX_OUTPUT_TOKENS",
+            Kind::Integer,
+            None,
+            "cap on the response length; the style's own cap when unset",
+            || None,
+        )
+    },
+    Setting {
+        example: "1024",
+        ..setting(
+            "generation.thinking_budget",
+            "GIT_CHANGES_THINKING_BUDGET",
+            Kind::Integer,
+            None,
+            "tokens a thinking model may spend first; 0 turns thinking off, other models ignore it",
+            || None,
+        )
+    },
+    Setting {
+        flag_appends: true,
+        ..setting(
+            "diff.exclude",
+            "GIT_CHANGES_EXCLUDE",
+            Kind::List,
+            Some("exclude"),
+            "glob patterns left out of the diff, besides Cargo.lock; --exclude adds to them",
+            || texts(&[]),
+        )
+    },
+    Setting {
+        flag_appends: true,
+        ..setting(
+            "diff.ignore_lines",
+            "GIT_CHANGES_IGNORE_LINES",
+            Kind::List,
+            Some("ignore_lines"),
+            "regexes for churn lines left out of the diff in removed/added pairs; --ignore-lines adds to them",
+            || texts(&[]),
+        )
+    },
+    setting(
+        "diff.max_files",
+        "GIT_CHANGES_MAX_FILES",
+        Kind::Integer,
+        None,
+        "more changed files than this needs a confirmation or --force-large; 0 is no limit",
+        || Some(Value::Integer(large::DEFAULT_MAX_FILES as i64)),
+    ),
+    setting(
+        "diff.max_kb",
+        "GIT_CHANGES_MAX_KB",
+        Kind::Integer,
+        None,
+        "a bigger prompt, in KB, needs a confirmation or --force-large; 0 is no limit",
+        || Some(Value::Integer(large::DEFAULT_MAX_KB as i64)),
+    ),
+    setting(
+        "output.format",
+        "GIT_CHANGES_FORMAT",
+        Kind::Text,
+        Some("format"),
+        "plain or markdown when --format isn't given",
+        || text(OUTPUT_FORMATS[0]),
+    ),
+    setting(
+        "privacy.confirm_send",
+        "GIT_CHANGES_CONFIRM_SEND",
+        Kind::Bool,
+        Some("confirm"),
+        "show what is about to be sent and ask before the first request",
+        || Some(Value::Boolean(false)),
+    ),
+    setting(
+        "privacy.history",
+        "GIT_CHANGES_HISTORY",
+        Kind::Bool,
+        None,
+        "keep generated messages, never the diff, in the log `history` lists",
+        || Some(Value::Boolean(true)),
+    ),
+];
+
+// --config: one file for every repository the command touches
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+// generation.model of the first config loaded; --model still wins
+static MODEL: OnceLock<String> = OnceLock::new();
+
+/// The settings that apply, every layer merged.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct Config {
+    /// `[message]`: the language, style and length of messages.
+    pub message: MessageConfig,
+    /// `[styles.<name>]`: style presets, which replace built-in ones with the same name.
+    pub styles: BTreeMap<String, StyleConfig>,
+    /// `[attribution]`: the trailer naming the tool and model.
+    pub attribution: AttributionConfig,
+    /// `[commit]`: the allowed types and sign-off.
+    pub commit: CommitConfig,
+    /// `[generation]`: the provider, model and sampling.
+    pub generation: GenerationConfig,
+    /// `[diff]`: what is left out and how much may be sent.
+    pub diff: DiffConfig,
+    /// `[output]`: how the result is printed.
+    pub output: OutputConfig,
+    /// `[privacy]`: asking before sending, and the history log.
+    pub privacy: PrivacyConfig,
+}
+
+/// `[commit]`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct CommitConfig {
+    /// The allowed conventional-commit types; the standard set when unset.
+    pub types: Option<Vec<String>>,
+    /// Add a `Signed-off-by` trailer even without `--signoff`.
+    pub signoff: bool,
+}
+
+/// `[generation]`: the defaults for `--provider` and `--model`, and the sampling parameters.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct GenerationConfig {
+    /// The provider; only `gemini` so far.
+    pub provider: Option<String>,
+    /// The model id.
+    pub model: Option<String>,
+    /// The sampling temperature.
+    pub temperature: Option<f64>,
+    /// The nucleus sampling share.
+    pub top_p: Option<f64>,
+    /// Draw only from this many of the most likely tokens.
+    pub top_k: Option<u32>,
+    /// Replaces the style's own cap on the response length, in tokens.
+    pub max_output_tokens: Option<u32>,
+    /// How many tokens a model that thinks may spend on it; left to the model when unset.
+    pub thinking_budget: Option<u32>,
+}
+
+/// `[diff]`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct DiffConfig {
+    /// Glob patterns left out of the diff, in addition to `Cargo.lock` and any `--exclude`.
+    pub exclude: Vec<String>,
+    /// Regular expressions for churn lines left out of the diff, with any `--ignore-lines`.
+    pub ignore_lines: Vec<String>,
+    /// Above this many files, a run asks before sending, or stops without a terminal.
+    pub max_files: Option<usize>,
+    /// The same for the size of the prompt, in kilobytes.
+    pub max_kb: Option<usize>,
+}
+
+/// `[privacy]`
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct PrivacyConfig {
+    /// Ask before the first request, as `--confirm` does.
+    pub confirm_send: bool,
+    /// Keep generated messages (never the diff) in the log `history` lists; on by default.
+    pub history: bool,
+}
+
+impl Default for PrivacyConfig {
+    fn default() -> Self {
+        PrivacyConfig {
+            confirm_send: false,
+            history: true,
+        }
+    }
+}
+
+/// `[output]`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct OutputConfig {
+    /// `plain` or `markdown` when `--format` isn't given; json stays a command-line choice.
+    pub format: Option<String>,
+}
+
+/// `[message]`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct MessageConfig {
+    /// Natural language for the subject and body, such as `pt-BR` or `Portuguese`.
+    pub language: Option<String>,
+    /// The style preset when `--style` isn't given.
+    pub style: Option<String>,
+    /// The longest subject line; what's cut moves into the body.
+    pub max_title_length: Option<usize>,
+    /// Subjects longer than this get a warning.
+    pub warn_title_length: Option<usize>,
+}
+
+/// `[styles.<name>]`: a style preset.
+#[derive(Deserialize, Debug)]
+#[non_exhaustive]
+pub struct StyleConfig {
+    /// The prompt fragment describing the shape and tone of the message. `prompt_file` reads it
+    /// from a file instead, relative to the config file that names it; in a repository's
+    /// `.git-changes.toml`, the file has to be inside the repository.
+    #[serde(default)]
+    pub prompt: String,
+    // only checked here; the layer has already swapped it for the file's contents
+    #[serde(default, rename = "prompt_file")]
+    _prompt_file: Option<PathBuf>,
+    /// The most a message in this style may take, in tokens.
+    pub max_output_tokens: Option<u32>,
+}
+
+/// `[attribution]`
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+#[non_exhaustive]
+pub struct AttributionConfig {
+    /// Add the trailer even without `--attribution`.
+    pub enabled: bool,
+    /// The trailer's key.
+    pub key: String,
+    /// The trailer's value; `{model}` and `{version}` are filled in when it is written.
+    pub template: String,
+}
+
+impl Default for AttributionConfig {
+    fn default() -> Self {
+        AttributionConfig {
+            enabled: false,
+            key: "Commit-message-generated-by".to_string(),
+            template: "git-changes-rs ({model})".to_string(),
+        }
+    }
+}
+
+pub(crate) fn use_file(path: PathBuf) {
+    CONFIG_FILE.set(path).ok();
+}
+
+// generation.model from the config, for when --model isn't given
+pub(crate) fn model() -> Option<&'static str> {
+    MODEL.get().map(String::as_str)
+}
+
+// $XDG_CONFIG_HOME/git-changes/config.toml, ~/.config/... or %APPDATA%\git-changes\config.toml
+pub(crate) fn user_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| match cfg!(windows) {
+            true => env::var_os("APPDATA").map(PathBuf::from),
+            false => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")),
+        })?;
+    Some(base.join(USER_CONFIG_DIR).join(USER_CONFIG_FILE))
+}
+
+// one config file as read, with where it came from
+pub(crate) struct Layer {
+    // "global", "repo" or "--config"
+    pub origin: &'static str,
+    pub path: PathBuf,
+    pub table: Table,
+}
+
+// the files that apply to `repo`, lowest precedence first: the user's config and the one at the
+// workdir root, or only the file given with --config, which has to exist
+fn paths(repo: Option<&Repository>) -> Vec<(&'static str, PathBuf)> {
+    if let Some(path) = CONFIG_FILE.get() {
+        return vec![("--config", path.clone())];
+    }
+    let repo_path = repo
+        .and_then(Repository::workdir)
+        .map(|workdir| workdir.join(REPO_CONFIG_FILE));
+    [("global", user_path()), ("repo", repo_path)]
+        .into_iter()
+        .filter_map(|(origin, path)| Some((origin, path?)))
+        .filter(|(_, path)| path.is_file())
+        .collect()
+}
+
+// every file is read and checked on its own, so errors and unknown keys name their file
+pub(crate) fn layers(repo: &Repository) -> Result<Vec<Layer>> {
+    layers_for(Some(repo))
+}
+
+// the same outside a repository, where only the user's config or --config applies
+pub(crate) fn layers_for(repo: Option<&Repository>) -> Result<Vec<Layer>> {
+    let mut layers = Vec::new();
+    for (origin, path) in paths(repo) {
+        debug!("reading config {}", path.display());
+        let table = read_layer(&path, origin)?;
+        layers.push(Layer {
+            origin,
+            path,
+            table,
+        });
+    }
+    Ok(layers)
+}
+
+/// The settings that apply in `repo`: its `.git-changes.toml` over the user's config, with the
+/// environment over both. An unknown key is logged as a warning naming its file and otherwise
+/// ignored; a value of the wrong type is an error naming its file, inside a
+/// [`GitChangesError::Config`].
+///
+/// ```no_run
+/// let repo = git2::Repository::discover(".")?;
+/// let config = git_changes_rs::config::load(&repo)?;
+/// println!("excluded: {:?}", config.diff.exclude);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn load(repo: &Repository) -> Result<Config, GitChangesError> {
+    layers(repo)
+        .and_then(|layers| resolve(&layers))
+        .map_err(|e| GitChangesError::Config {
+            message: "failed to load configuration".to_string(),
+            source: Some(e.into()),
+        })
+}
+
+// the files and then the environment, merged key by key and read once more as the config that
+// applies
+pub(crate) fn resolve(layers: &[Layer]) -> Result<Config> {
+    let mut merged = Table::new();
+    for layer in layers {
+        merge(&mut merged, layer.table.clone());
+    }
+    merge(&mut merged, env_layer()?);
+    let config: Config = Value::Table(merged)
+        .try_into()
+        .context("failed to combine config files")?;
+    config.check()?;
+    if let Some(model) = &config.generation.model {
+        MODEL.set(model.clone()).ok();
+    }
+    if config.privacy.confirm_send {
+        interactivity::confirm_sends();
+    }
+    Ok(config)
+}
+
+fn read_layer(path: &Path, origin: &str) -> Result<Table> {
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file {}", path.display()))?;
+    let parse_error = || format!("failed to parse config file {}", path.display());
+    let mut unknown = Vec::new();
+    let deserializer = toml::Deserializer::new(&text);
+    serde_ignored::deserialize(deserializer, |key| unknown.push(key.to_string()))
+        .map(|_: Config| ())
+        .with_context(parse_error)?;
+    for key in unknown {
+        warn!("unknown key '{}' in {}", key, path.display());
+    }
+    let mut table: Table = text.parse().with_context(parse_error)?;
+    read_prompt_files(&mut table, path, origin == "repo")?;
+    Ok(table)
+}
+
+// styles.<name>.prompt_file becomes that style's prompt, before a later layer can override it.
+// A repository's own file may only name files inside the repository, since a cloned one could
+// otherwise put any file the user can read into the prompt that is sent off
+fn read_prompt_files(table: &mut Table, path: &Path, in_repository: bool) -> Result<()> {
+    let Some(Value::Table(styles)) = table.get_mut("styles") else {
+        return Ok(());
+    };
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for (name, style) in styles.iter_mut() {
+        let Value::Table(style) = style else { continue };
+        let Some(file) = style.remove("prompt_file") else {
+            continue;
+        };
+        let file = file.as_str().map(|file| dir.join(file)).ok_or_else(|| {
+            anyhow!(
+                "styles.{}.prompt_file in {} must be a path",
+                name,
+                path.display()
+            )
+        })?;
+        let read_error = || {
+            format!(
+                "failed to read prompt file {} for style '{}'",
+                file.display(),
+                name
+            )
+        };
+        if in_repository {
+            let root = dir.canonicalize().with_context(read_error)?;
+            let real = file.canonicalize().with_context(read_error)?;
+            if !real.starts_with(&root) {
+                return Err(anyhow!(
+                    "styles.{}.prompt_file in {} is outside the repository: {}",
+                    name,
+                    path.display(),
+                    file.display()
+                ));
+            }
+        }
+        let prompt = fs::read_to_string(&file).with_context(read_error)?;
+        style.insert("prompt".to_string(), Value::String(prompt));
+    }
+    Ok(())
+}
+
+// the GIT_CHANGES_* variables that are set and not empty, as one more layer
+fn env_layer() -> Result<Table> {
+    let mut layer = Table::new();
+    for setting in &SETTINGS {
+        let Some(value) = env_override(setting)? else {
+            continue;
+        };
+        debug!("{} from {}", setting.key, setting.env);
+        let (section, field) = setting.key.split_once('.').unwrap_or(("", setting.key));
+        let section = layer
+            .entry(section)
+            .or_insert_with(|| Value::Table(Table::new()));
+        if let Value::Table(section) = section {
+            section.insert(field.to_string(), value);
+        }
+    }
+    Ok(layer)
+}
+
+// the setting's variable, when it's set and not empty
+pub(crate) fn env_override(setting: &Setting) -> Result<Option<Value>> {
+    let Some(text) = env::var(setting.env)
+        .ok()
+        .filter(|text| !text.trim().is_empty())
+    else {
+        return Ok(None);
+    };
+    parse_value(&text, setting.kind).map(Some).ok_or_else(|| {
+        anyhow!(
+            "{}={} is not a valid value for {}",
+            setting.env,
+            text,
+            setting.key
+        )
+    })
+}
+
+pub(crate) fn parse_value(text: &str, kind: Kind) -> Option<Value> {
+    let text = text.trim();
+    Some(match kind {
+        Kind::Text => Value::String(text.to_string()),
+        Kind::Integer => Value::Integer(text.parse().ok()?),
+        Kind::Float => Value::Float(text.parse().ok()?),
+        Kind::Bool => Value::Boolean(match text.to_ascii_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => true,
+            "0" | "false" | "no" | "off" => false,
+            _ => return None,
+        }),
+        Kind::List => Value::Array(
+            text.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(|item| Value::String(item.to_string()))
+                .collect(),
+        ),
+    })
+}
+
+// a dotted key such as "message.style" in a table
+pub(crate) fn lookup<'a>(table: &'a Table, key: &str) -> Option<&'a Value> {
+    let (section, field) = key.split_once('.')?;
+    table.get(section)?.as_table()?.get(field)
+}
+
+// tables merge recursively; anything else, arrays included, is replaced by the later layer
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+impl Config {
+    /// The style named `name` (see [`crate::prompt::resolve_style`]), with
+    /// `generation.max_output_tokens` over the style's own cap.
+    pub fn style(&self, name: &str) -> Result<prompt::StylePreset, GitChangesError> {
+        let mut style = prompt::resolve_style(name, &self.styles)?;
+        if let Some(max_output_tokens) = self.generation.max_output_tokens {
+            style.max_output_tokens = max_output_tokens;
+        }
+        Ok(style)
+    }
+
+    // values serde can't check on its own
+    fn check(&self) -> Result<()> {
+        if let Some(provider) = &self.generation.provider {
+            if !crate::provider::PROVIDERS.contains(&provider.as_str()) {
+                return Err(anyhow!(
+                    "unknown generation.provider '{}' in config (available: {})",
+                    provider,
+                    crate::provider::PROVIDERS.join(", ")
+                ));
+            }
+        }
+        if let Some(format) = &self.output.format {
+            if !OUTPUT_FORMATS.contains(&format.as_str()) {
+                return Err(anyhow!(
+                    "unknown output.format '{}' in config (available: {})",
+                    format,
+                    OUTPUT_FORMATS.join(", ")
+                ));
+            }
+        }
+        for (name, style) in &self.styles {
+            if style.prompt.trim().is_empty() {
+                return Err(anyhow!(
+                    "style '{}' in config needs a prompt or prompt_file",
+                    name
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(origin: &'static str, text: &str) -> Layer {
+        Layer {
+            origin,
+            path: PathBuf::from(format!("{}.toml", origin)),
+            table: text.parse().unwrap(),
+        }
+    }
+
+    fn write(dir: &Path, name: &str, text: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn later_layers_win_key_by_key() {
+        let global = layer(
+            "global",
+            "[message]\nstyle = \"detailed\"\nmax_title_length = 50\n\n[diff]\nexclude = [\"*.log\", \"*.tmp\"]\n",
+        );
+        let repo = layer(
+            "repo",
+            "[message]\nmax_title_length = 60\n\n[diff]\nexclude = [\"dist/**\"]\n",
+        );
+        let config = resolve(&[global, repo]).unwrap();
+        assert_eq!(config.message.style.as_deref(), Some("detailed"));
+        assert_eq!(config.message.max_title_length, Some(60));
+        // a list is a value like any other: the repository's replaces the user's
+        assert_eq!(config.diff.exclude, ["dist/**"]);
+        assert!(config.privacy.history);
+        assert_eq!(config.attribution.key, "Commit-message-generated-by");
+    }
+
+    #[test]
+    fn tables_merge_and_everything_else_is_replaced() {
+        let mut base: Table = "a = 1\n[t]\nx = 1\ny = [1]\n".parse().unwrap();
+        merge(&mut base, "b = 2\n[t]\ny = [2]\nz = 3\n".parse().unwrap());
+        let expected: Table = "a = 1\nb = 2\n[t]\nx = 1\ny = [2]\nz = 3\n"
+            .parse()
+            .unwrap();
+        assert_eq!(base, expected);
+    }
+
+    #[test]
+    fn a_parse_error_names_the_file_line_and_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(dir.path(), "bad.toml", "[message]\nstyle = \n");
+        let error = format!("{:#}", read_layer(&path, "global").unwrap_err());
+        assert!(error.contains(&path.display().to_string()), "{}", error);
+        assert!(error.contains("line 2, column"), "{}", error);
+
+        let path = write(
+            dir.path(),
+            "typed.toml",
+            "[message]\nmax_title_length = \"long\"\n",
+        );
+        let error = format!("{:#}", read_layer(&path, "global").unwrap_err());
+        assert!(error.contains("typed.toml"), "{}", error);
+        assert!(error.contains("line 2"), "{}", error);
+    }
+
+    #[test]
+    fn unknown_keys_are_read_past() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(
+            dir.path(),
+            "extra.toml",
+            "[message]\nstyle = \"casual\"\nbogus = 1\n\n[nonsense]\nx = 1\n",
+        );
+        let table = read_layer(&path, "global").unwrap();
+        let config = resolve(&[Layer {
+            origin: "repo",
+            path,
+            table,
+        }])
+        .unwrap();
+        assert_eq!(config.message.style.as_deref(), Some("casual"));
+    }
+
+    #[test]
+    fn a_prompt_file_is_read_next_to_its_config() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("prompts")).unwrap();
+        write(dir.path(), "prompts/terse.txt", "One line, no body.");
+        let path = write(
+            dir.path(),
+            "config.toml",
+            "[styles.terse]\nprompt_file = \"prompts/terse.txt\"\n",
+        );
+        let table = read_layer(&path, "global").unwrap();
+        assert_eq!(
+            lookup(&table, "styles.terse"),
+            Some(
+                &"prompt = \"One line, no body.\""
+                    .parse::<Table>()
+                    .unwrap()
+                    .into()
+            )
+        );
+
+        let path = write(
+            dir.path(),
+            "missing.toml",
+            "[styles.terse]\nprompt_file = \"nowhere.txt\"\n",
+        );
+        let error = format!("{:#}", read_layer(&path, "global").unwrap_err());
+        assert!(error.contains("for style 'terse'"), "{}", error);
+    }
+
+    #[test]
+    fn a_repository_prompt_file_stays_inside_the_repository() {
+        let outside = tempfile::tempdir().unwrap();
+        let secret = write(outside.path(), "secret.txt", "not for the model");
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("repo")).unwrap();
+        let repo = dir.path().join("repo");
+        write(dir.path(), "above.txt", "not for the model either");
+        write(&repo, "terse.txt", "One line, no body.");
+        for file in [secret.display().to_string(), "../above.txt".to_string()] {
+            let path = write(
+                &repo,
+                ".git-changes.toml",
+                &format!("[styles.terse]\nprompt_file = {:?}\n", file),
+            );
+            let error = format!("{:#}", read_layer(&path, "repo").unwrap_err());
+            assert!(error.contains("is outside the repository"), "{}", error);
+            // the user's own config and --config may point anywhere
+            assert!(read_layer(&path, "global").is_ok());
+            assert!(read_layer(&path, "--config").is_ok());
+        }
+
+        let path = write(
+            &repo,
+            ".git-changes.toml",
+            "[styles.terse]\nprompt_file = \"./terse.txt\"\n",
+        );
+        assert!(read_layer(&path, "repo").is_ok());
+    }
+
+    #[test]
+    fn values_serde_cant_check_are_checked_after_merging() {
+        let error = resolve(&[layer("repo", "[output]\nformat = \"html\"\n")]).unwrap_err();
+        assert!(error.to_string().contains("unknown output.format 'html'"));
+        let error = resolve(&[layer("repo", "[styles.empty]\nprompt = \" \"\n")]).unwrap_err();
+        assert!(error.to_string().contains("style 'empty'"));
+    }
+
+    #[test]
+    fn only_the_files_that_exist_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        assert!(paths(Some(&repo))
+            .iter()
+            .all(|(origin, _)| *origin != "repo"));
+        write(
+            dir.path(),
+            REPO_CONFIG_FILE,
+            "[message]\nstyle = \"casual\"\n",
+        );
+        let found = paths(Some(&repo));
+        let (origin, path) = found.last().unwrap();
+        assert_eq!(*origin, "repo");
+        assert_eq!(path.file_name().unwrap(), REPO_CONFIG_FILE);
+    }
+
+    #[test]
+    fn variables_are_read_as_their_settings_kind() {
+        assert_eq!(parse_value(" pt-BR ", Kind::Text), text("pt-BR"));
+        assert_eq!(parse_value("72", Kind::Integer), Some(Value::Integer(72)));
+        assert_eq!(parse_value("seventy", Kind::Integer), None);
+        assert_eq!(parse_value("0.2", Kind::Float), Some(Value::Float(0.2)));
+        for (text, value) in [
+            ("1", true),
+            ("Yes", true),
+            ("on", true),
+            ("false", false),
+            ("off", false),
+        ] {
+            assert_eq!(parse_value(text, Kind::Bool), Some(Value::Boolean(value)));
+        }
+        assert_eq!(parse_value("maybe", Kind::Bool), None);
+        assert_eq!(
+            parse_value("*.log, dist/** ,,", Kind::List),
+            texts(&["*.log", "dist/**"])
+        );
+    }
+
+    #[test]
+    fn every_setting_has_a_variable_of_its_own_and_a_real_key() {
+        let mut seen = Vec::new();
+        let mut table = Table::new();
+        for setting in &SETTINGS {
+            assert!(setting.env.starts_with("GIT_CHANGES_"), "{}", setting.env);
+            assert!(!seen.contains(&setting.env), "{} twice", setting.env);
+            seen.push(setting.env);
+            let (section, field) = setting.key.split_once('.').unwrap();
+            let value = (setting.default)()
+                .or_else(|| setting.example.parse::<Value>().ok())
+                .or_else(|| parse_value("1", setting.kind))
+                .unwrap();
+            table
+                .entry(section)
+                .or_insert_with(|| Value::Table(Table::new()))
+                .as_table_mut()
+                .unwrap()
+                .insert(field.to_string(), value);
+        }
+        // each key lands in a field of Config, so none of them is unknown
+        let mut unknown = Vec::new();
+        let text = toml::to_string(&table).unwrap();
+        serde_ignored::deserialize(toml::Deserializer::new(&text), |key| {
+            unknown.push(key.to_string())
+        })
+        .map(|_: Config| ())
+        .unwrap();
+        assert!(unknown.is_empty(), "{:?}", unknown);
+    }
+
+    #[test]
+    fn every_settings_flag_is_a_generate_flag() {
+        let command = crate::cli::build();
+        for flag in SETTINGS.iter().filter_map(|setting| setting.flag) {
+            assert!(
+                command.get_arguments().any(|arg| arg.get_id() == flag),
+                "{} isn't a flag",
+                flag
+            );
+        }
+    }
+}
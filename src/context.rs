@@ -0,0 +1,322 @@
+// src/context.rs
+// names the function or item each hunk falls in, so the model knows where a change lives
+
+// languages with a known definition syntax; everything else gets no annotation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    JavaScript,
+    Python,
+}
+
+impl Language {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let ext = path.rsplit_once('.')?.1.to_ascii_lowercase();
+        match ext.as_str() {
+            "rs" => Some(Language::Rust),
+            "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => Some(Language::JavaScript),
+            "py" => Some(Language::Python),
+            _ => None,
+        }
+    }
+}
+
+fn indent(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+// leading identifier characters of `text`
+fn ident(text: &str) -> Option<&str> {
+    let end = text
+        .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '$'))
+        .unwrap_or(text.len());
+    (end > 0).then(|| &text[..end])
+}
+
+fn strip_any<'a>(mut text: &'a str, prefixes: &[&str]) -> &'a str {
+    loop {
+        let before = text;
+        for prefix in prefixes {
+            if let Some(rest) = text.strip_prefix(prefix) {
+                text = rest.trim_start();
+            }
+        }
+        if text == before {
+            return text;
+        }
+    }
+}
+
+fn rust_item(line: &str) -> Option<String> {
+    let line = strip_any(
+        line.trim(),
+        &[
+            "pub(crate) ",
+            "pub(super) ",
+            "pub ",
+            "async ",
+            "const ",
+            "unsafe ",
+            "extern \"C\" ",
+            "default ",
+        ],
+    );
+    if let Some(rest) = line.strip_prefix("impl") {
+        if rest.starts_with([' ', '<']) {
+            let header = rest.split('{').next().unwrap_or(rest).trim();
+            return Some(format!(
+                "impl{}{}",
+                if rest.starts_with('<') { "" } else { " " },
+                header
+            ));
+        }
+    }
+    if let Some(rest) = line.strip_prefix("macro_rules!") {
+        return ident(rest.trim_start()).map(|name| format!("macro {}", name));
+    }
+    for keyword in ["fn", "struct", "enum", "trait", "mod", "union"] {
+        if let Some(rest) = line.strip_prefix(keyword).and_then(|r| r.strip_prefix(' ')) {
+            return ident(rest.trim_start()).map(|name| format!("{} {}", keyword, name));
+        }
+    }
+    None
+}
+
+fn python_item(line: &str) -> Option<String> {
+    let line = strip_any(line.trim(), &["async "]);
+    for keyword in ["def", "class"] {
+        if let Some(rest) = line.strip_prefix(keyword).and_then(|r| r.strip_prefix(' ')) {
+            return ident(rest.trim_start()).map(|name| format!("{} {}", keyword, name));
+        }
+    }
+    None
+}
+
+fn javascript_item(line: &str) -> Option<String> {
+    let trimmed = strip_any(
+        line.trim(),
+        &[
+            "export ", "default ", "async ", "static ", "public ", "private ",
+        ],
+    );
+    if let Some(rest) = trimmed.strip_prefix("function") {
+        let rest = rest.trim_start_matches('*').trim_start();
+        return ident(rest).map(|name| format!("function {}", name));
+    }
+    if let Some(rest) = trimmed.strip_prefix("class ") {
+        return ident(rest.trim_start()).map(|name| format!("class {}", name));
+    }
+    // `const handler = (req) => {` and `const handler = function (req) {`
+    for keyword in ["const ", "let ", "var "] {
+        if let Some(rest) = trimmed.strip_prefix(keyword) {
+            let name = ident(rest.trim_start())?;
+            let value = rest.split_once('=')?.1.trim_start();
+            let value = value.strip_prefix("async").unwrap_or(value).trim_start();
+            if value.starts_with("function") || value.contains("=>") {
+                return Some(format!("function {}", name));
+            }
+            return None;
+        }
+    }
+    // class methods: `name(args) {`, only when indented so calls at top level don't match
+    if indent(line) > 0 && trimmed.ends_with('{') {
+        let name = ident(trimmed)?;
+        let after = trimmed[name.len()..].trim_start();
+        let is_statement = matches!(
+            name,
+            "if" | "for" | "while" | "switch" | "catch" | "with" | "return" | "function"
+        );
+        if after.starts_with('(') && !is_statement {
+            return Some(format!("method {}", name));
+        }
+    }
+    None
+}
+
+fn item_name(language: Language, line: &str) -> Option<String> {
+    match language {
+        Language::Rust => rust_item(line),
+        Language::JavaScript => javascript_item(line),
+        Language::Python => python_item(line),
+    }
+}
+
+// the innermost definition enclosing 1-based `line`: the nearest definition above it that is
+// indented less than it (or the line itself, when the change is on a signature)
+pub fn enclosing_item(language: Language, source: &str, line: usize) -> Option<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    let target = line.checked_sub(1).filter(|&idx| idx < lines.len())?;
+
+    if let Some(name) = item_name(language, lines[target]) {
+        return Some(name);
+    }
+
+    // blank target lines take the indentation of the next non-blank line
+    let mut limit = lines[target..]
+        .iter()
+        .find(|l| !l.trim().is_empty())
+        .map(|l| indent(l))
+        .unwrap_or(0);
+
+    for candidate in lines[..target].iter().rev() {
+        if candidate.trim().is_empty() || indent(candidate) >= limit {
+            continue;
+        }
+        // the tail of a multi-line signature sits at the definition's indentation, and so
+        // does the brace after a `where` clause
+        if candidate.trim_start().starts_with([')', ']'])
+            || matches!(candidate.trim(), "where" | "{")
+        {
+            continue;
+        }
+        if let Some(name) = item_name(language, candidate) {
+            return Some(name);
+        }
+        limit = indent(candidate);
+        if limit == 0 {
+            break;
+        }
+    }
+    None
+}
+
+// the marker appended to a hunk header; bracketed and labeled so it can't pass for diff content
+pub fn annotation(item: &str) -> String {
+    format!(" // [git-changes context: in {}]", item)
+}
+
+// inserts annotations at byte offsets of the diff text; offsets refer to the unannotated text
+pub fn annotate(diff: &str, annotations: &[(usize, String)]) -> String {
+    let mut sorted: Vec<&(usize, String)> = annotations.iter().collect();
+    sorted.sort_by_key(|(offset, _)| *offset);
+
+    let mut out = String::with_capacity(diff.len() + annotations.len() * 40);
+    let mut last = 0;
+    for (offset, text) in sorted {
+        if *offset < last || *offset > diff.len() || !diff.is_char_boundary(*offset) {
+            continue;
+        }
+        out.push_str(&diff[last..*offset]);
+        out.push_str(text);
+        last = *offset;
+    }
+    out.push_str(&diff[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn languages_come_from_the_extension() {
+        assert_eq!(Language::from_path("src/lib.rs"), Some(Language::Rust));
+        assert_eq!(
+            Language::from_path("web/App.TSX"),
+            Some(Language::JavaScript)
+        );
+        assert_eq!(Language::from_path("tool.py"), Some(Language::Python));
+        assert_eq!(Language::from_path("README.md"), None);
+        assert_eq!(Language::from_path("Makefile"), None);
+    }
+
+    #[test]
+    fn a_line_is_in_the_nearest_less_indented_definition() {
+        let source = "\
+pub struct Repo;
+
+impl Repo {
+    pub async fn open(path: &str) -> Self {
+        let x = 1;
+
+        Repo
+    }
+}
+
+fn helper<T>(
+    value: T,
+) -> T
+where
+    T: Clone,
+{
+    value
+}
+";
+        assert_eq!(
+            enclosing_item(Language::Rust, source, 5).as_deref(),
+            Some("fn open")
+        );
+        // a blank line takes the indentation of the line after it
+        assert_eq!(
+            enclosing_item(Language::Rust, source, 6).as_deref(),
+            Some("fn open")
+        );
+        assert_eq!(
+            enclosing_item(Language::Rust, source, 4).as_deref(),
+            Some("fn open")
+        );
+        assert_eq!(
+            enclosing_item(Language::Rust, source, 8).as_deref(),
+            Some("impl Repo")
+        );
+        // past the signature's `) -> T` and `where`
+        assert_eq!(
+            enclosing_item(Language::Rust, source, 17).as_deref(),
+            Some("fn helper")
+        );
+        assert_eq!(
+            enclosing_item(Language::Rust, source, 1).as_deref(),
+            Some("struct Repo")
+        );
+        assert_eq!(enclosing_item(Language::Rust, source, 2), None);
+        assert_eq!(enclosing_item(Language::Rust, source, 100), None);
+    }
+
+    #[test]
+    fn python_and_javascript_definitions_are_named() {
+        let python = "class Repo:\n    async def open(self):\n        return 1\n";
+        assert_eq!(
+            enclosing_item(Language::Python, python, 3).as_deref(),
+            Some("def open")
+        );
+        let javascript = "\
+export default class Repo {
+  open(path) {
+    if (path) {
+      return 1;
+    }
+  }
+}
+const handler = async (req) => {
+  return req;
+};
+";
+        assert_eq!(
+            enclosing_item(Language::JavaScript, javascript, 4).as_deref(),
+            Some("method open")
+        );
+        assert_eq!(
+            enclosing_item(Language::JavaScript, javascript, 9).as_deref(),
+            Some("function handler")
+        );
+    }
+
+    #[test]
+    fn annotations_go_in_at_their_offsets_of_the_original_text() {
+        let diff = "@@ -1 +1 @@\n-a\n+b\n@@ -9 +9 @@\n-c\n+d\n";
+        let second = diff.find("\n-c").unwrap();
+        let first = diff.find('\n').unwrap();
+        let annotated = annotate(
+            diff,
+            &[
+                (second, annotation("fn b")),
+                (first, annotation("fn a")),
+                (diff.len() + 1, annotation("out of range")),
+            ],
+        );
+        assert_eq!(
+            annotated,
+            "@@ -1 +1 @@ // [git-changes context: in fn a]\n-a\n+b\n@@ -9 +9 @@ // [git-changes context: in fn b]\n-c\n+d\n"
+        );
+    }
+}
@@ -0,0 +1,372 @@
+// src/conventional.rs
+// a commit message as its parts: the `type(scope)!` prefix, subject, body paragraphs and
+// trailers, so post-processing edits the parts instead of the text
+
+use crate::trailers::Trailer;
+use crate::validate;
+use std::fmt;
+
+// the `type(scope)!` in front of the subject
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prefix {
+    pub kind: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(scope) = &self.scope {
+            write!(f, "({})", scope)?;
+        }
+        if self.breaking {
+            write!(f, "!")?;
+        }
+        Ok(())
+    }
+}
+
+// parsing never fails: a subject that isn't `type(scope): subject` leaves `prefix` empty and
+// the message unstructured. Rendering gives back the parsed text with line ends trimmed and
+// blank lines collapsed to one, so parse and render round-trip
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConventionalMessage {
+    pub prefix: Option<Prefix>,
+    // the description after the prefix, or the whole first line
+    pub subject: String,
+    // separated by blank lines, each as written; a bullet list is one paragraph, and blank
+    // lines inside a code fence don't end one
+    pub body: Vec<String>,
+    // the closing paragraph of `Key: value` (or `Key #value`) footers, BREAKING CHANGE included
+    pub trailers: Vec<Trailer>,
+}
+
+impl ConventionalMessage {
+    pub fn parse(message: &str) -> Self {
+        let lines: Vec<&str> = message
+            .lines()
+            .map(str::trim_end)
+            .skip_while(|line| line.is_empty())
+            .collect();
+        let Some((title, rest)) = lines.split_first() else {
+            return ConventionalMessage::default();
+        };
+
+        let mut parsed = ConventionalMessage::default();
+        parsed.set_title(title);
+        parsed.body = paragraphs(rest);
+        if let Some(trailers) = parsed.body.last().and_then(|last| parse_trailers(last)) {
+            parsed.body.pop();
+            parsed.trailers = trailers;
+        }
+        parsed
+    }
+
+    pub fn title(&self) -> String {
+        match &self.prefix {
+            Some(prefix) => format!("{}: {}", prefix, self.subject),
+            None => self.subject.clone(),
+        }
+    }
+
+    // a prefix is only taken apart when it renders back as written, so odd spacing such as
+    // `feat:  x` stays unstructured instead of being changed
+    pub fn set_title(&mut self, title: &str) {
+        let title = title.trim();
+        let prefix = validate::parse_header(title)
+            .filter(|header| !header.subject.is_empty())
+            .map(|header| {
+                let prefix = Prefix {
+                    kind: header.kind.to_string(),
+                    scope: header.scope.map(str::to_string),
+                    breaking: header.breaking,
+                };
+                (prefix, header.subject.to_string())
+            })
+            .filter(|(prefix, subject)| format!("{}: {}", prefix, subject) == title);
+        match prefix {
+            Some((prefix, subject)) => {
+                self.prefix = Some(prefix);
+                self.subject = subject;
+            }
+            None => {
+                self.prefix = None;
+                self.subject = title.to_string();
+            }
+        }
+    }
+
+    // --type and --scope: the given parts replace the model's, and a title without a prefix
+    // gets one when there is a type to put in it
+    pub fn force_prefix(&mut self, kind: Option<&str>, scope: Option<&str>) {
+        match &mut self.prefix {
+            Some(prefix) => {
+                if let Some(kind) = kind {
+                    prefix.kind = kind.to_string();
+                }
+                if let Some(scope) = scope {
+                    prefix.scope = Some(scope.to_string());
+                }
+            }
+            None => {
+                if let Some(kind) = kind {
+                    self.prefix = Some(Prefix {
+                        kind: kind.to_string(),
+                        scope: scope.map(str::to_string),
+                        breaking: false,
+                    });
+                }
+            }
+        }
+    }
+
+    // `!` in the prefix, or a BREAKING CHANGE footer, even one that isn't in the last paragraph
+    pub fn is_breaking(&self) -> bool {
+        self.prefix.as_ref().is_some_and(|prefix| prefix.breaking)
+            || self.trailers.iter().any(|t| is_breaking_key(&t.key))
+            || self
+                .body
+                .iter()
+                .flat_map(|paragraph| paragraph.lines())
+                .any(|line| {
+                    line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+                })
+    }
+
+    pub fn body_text(&self) -> String {
+        self.body.join("\n\n")
+    }
+
+    // a paragraph at the end of the body, above the trailers
+    pub fn push_paragraph(&mut self, paragraph: &str) {
+        let paragraph = paragraph.trim();
+        if !paragraph.is_empty() {
+            self.body.push(paragraph.to_string());
+        }
+    }
+
+    // adds trailers after the existing ones, skipping any that are already there
+    pub fn add_trailers(&mut self, trailers: &[Trailer]) {
+        for trailer in trailers {
+            let key = normalize(&trailer.render());
+            if !self.trailers.iter().any(|t| normalize(&t.render()) == key) {
+                self.trailers.push(trailer.clone());
+            }
+        }
+    }
+}
+
+impl fmt::Display for ConventionalMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.title())?;
+        for paragraph in &self.body {
+            write!(f, "\n\n{}", paragraph)?;
+        }
+        let trailers: Vec<String> = self.trailers.iter().map(Trailer::render).collect();
+        if !trailers.is_empty() {
+            write!(f, "\n\n{}", trailers.join("\n"))?;
+        }
+        Ok(())
+    }
+}
+
+fn is_breaking_key(key: &str) -> bool {
+    key == "BREAKING CHANGE" || key == "BREAKING-CHANGE"
+}
+
+fn paragraphs(lines: &[&str]) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut in_fence = false;
+    for &line in lines {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        }
+        if line.is_empty() && !in_fence {
+            if !current.is_empty() {
+                paragraphs.push(current.join("\n"));
+                current.clear();
+            }
+            continue;
+        }
+        if line.is_empty() && current.last().is_some_and(|last| last.is_empty()) {
+            continue;
+        }
+        current.push(line);
+    }
+    // an unclosed fence can have kept blank lines at the very end
+    while current.last().is_some_and(|last| last.is_empty()) {
+        current.pop();
+    }
+    if !current.is_empty() {
+        paragraphs.push(current.join("\n"));
+    }
+    paragraphs
+}
+
+// a paragraph made only of footers; indented lines continue the footer above them
+fn parse_trailers(paragraph: &str) -> Option<Vec<Trailer>> {
+    let mut trailers: Vec<Trailer> = Vec::new();
+    for line in paragraph.lines() {
+        match trailers.last_mut() {
+            Some(last) if line.starts_with([' ', '\t']) => {
+                last.value.push('\n');
+                last.value.push_str(line);
+            }
+            _ => trailers.push(Trailer::parse(line).filter(|_| !line.starts_with([' ', '\t']))?),
+        }
+    }
+    (!trailers.is_empty()).then_some(trailers)
+}
+
+// trailer keys compare case-insensitively, values by their trimmed text
+fn normalize(line: &str) -> String {
+    match line.split_once(':') {
+        Some((key, value)) => format!("{}:{}", key.trim().to_ascii_lowercase(), value.trim()),
+        None => line.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // messages that render back exactly as written
+    const ROUND_TRIPS: &[&str] = &[
+        "feat: add it",
+        "fix(cli)!: read the flag",
+        "not conventional at all",
+        "feat:  odd spacing stays as written",
+        "feat!: drop v1\n\nBREAKING CHANGE: v1 is gone",
+        "feat(api)!: drop v1\n\nThe v1 routes are removed.\n\nBREAKING CHANGE: v1 is gone\n  and so is its config\nRefs: #12",
+        "fix: close the file\n\nBREAKING-CHANGE: the handle is no longer shared",
+        "docs: explain\n\nFirst paragraph,\nover two lines.\n\n- a bullet\n- another\n  continued\n\nLast paragraph.",
+        "fix: keep the fence\n\n```\nfirst\n\nsecond\n```\n\nAfter it.",
+        "chore: tidy\n\nNote: this paragraph reads like a footer but more follows.\n\nThe end.",
+        "chore: tidy\n\nWarning: a footer-looking line\nfollowed by prose is body text",
+        "fix: thing\n\nCloses #40\nSigned-off-by: A <a@example.com>\nCo-authored-by: B <b@example.com>",
+    ];
+
+    #[test]
+    fn parse_and_render_round_trip() {
+        for &message in ROUND_TRIPS {
+            let parsed = ConventionalMessage::parse(message);
+            assert_eq!(parsed.to_string(), message);
+            assert_eq!(ConventionalMessage::parse(&parsed.to_string()), parsed);
+        }
+    }
+
+    #[test]
+    fn line_ends_and_extra_blank_lines_are_normalized() {
+        let parsed =
+            ConventionalMessage::parse("\n\nfeat: add it  \r\n\r\n\r\nA body.\n\n\n\nRefs: #1\n\n");
+        assert_eq!(parsed.to_string(), "feat: add it\n\nA body.\n\nRefs: #1");
+    }
+
+    #[test]
+    fn the_parts_of_a_breaking_change() {
+        let parsed = ConventionalMessage::parse(ROUND_TRIPS[5]);
+        assert_eq!(
+            parsed.prefix,
+            Some(Prefix {
+                kind: "feat".to_string(),
+                scope: Some("api".to_string()),
+                breaking: true,
+            })
+        );
+        assert_eq!(parsed.subject, "drop v1");
+        assert_eq!(parsed.body, ["The v1 routes are removed."]);
+        let keys: Vec<&str> = parsed.trailers.iter().map(|t| t.key.as_str()).collect();
+        assert_eq!(keys, ["BREAKING CHANGE", "Refs"]);
+        assert_eq!(
+            parsed.trailers[0].value,
+            "v1 is gone\n  and so is its config"
+        );
+        assert_eq!(parsed.trailers[1].separator, ": ");
+        assert!(parsed.is_breaking());
+    }
+
+    #[test]
+    fn what_makes_a_message_breaking() {
+        let cases = [
+            ("feat!: drop v1", true),
+            ("feat: drop v1\n\nBREAKING CHANGE: v1 is gone", true),
+            ("feat: drop v1\n\nBREAKING-CHANGE: v1 is gone", true),
+            // a footer left above the last paragraph still counts
+            (
+                "feat: drop v1\n\nBREAKING CHANGE: v1 is gone\n\nMore prose.",
+                true,
+            ),
+            ("feat: talk about a BREAKING CHANGE: in prose", false),
+            ("feat: add it\n\nRefs: #1", false),
+        ];
+        for (message, breaking) in cases {
+            assert_eq!(
+                ConventionalMessage::parse(message).is_breaking(),
+                breaking,
+                "{:?}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn footer_lookalikes_in_the_body_stay_body() {
+        let parsed = ConventionalMessage::parse(ROUND_TRIPS[9]);
+        assert!(parsed.trailers.is_empty());
+        assert_eq!(parsed.body.len(), 2);
+
+        let parsed = ConventionalMessage::parse(ROUND_TRIPS[10]);
+        assert!(parsed.trailers.is_empty());
+        assert_eq!(parsed.body.len(), 1);
+
+        // the first line is always the title, never a trailer
+        let parsed = ConventionalMessage::parse("Refs: #1");
+        assert!(parsed.trailers.is_empty());
+        assert_eq!(parsed.title(), "Refs: #1");
+    }
+
+    #[test]
+    fn edits_keep_the_trailers_last() {
+        let mut parsed = ConventionalMessage::parse("fix: thing\n\nRefs: #1");
+        parsed.push_paragraph("  A new paragraph.  ");
+        parsed.push_paragraph(" ");
+        parsed.add_trailers(&[
+            Trailer::new("refs", "#1"),
+            Trailer::new("Signed-off-by", "A <a@example.com>"),
+        ]);
+        parsed.set_title("fix(io): thing");
+        assert_eq!(
+            parsed.to_string(),
+            "fix(io): thing\n\nA new paragraph.\n\nRefs: #1\nSigned-off-by: A <a@example.com>"
+        );
+    }
+
+    #[test]
+    fn a_forced_prefix_replaces_only_the_parts_given() {
+        let forced = |message: &str, kind: Option<&str>, scope: Option<&str>| {
+            let mut parsed = ConventionalMessage::parse(message);
+            parsed.force_prefix(kind, scope);
+            parsed.to_string()
+        };
+        assert_eq!(
+            forced("feat: add it\n\nA body.", Some("fix"), Some("cli")),
+            "fix(cli): add it\n\nA body."
+        );
+        assert_eq!(
+            forced("feat(api)!: drop v1", Some("fix"), None),
+            "fix(api)!: drop v1"
+        );
+        assert_eq!(
+            forced("feat(api): add it", None, Some("cli")),
+            "feat(cli): add it"
+        );
+        assert_eq!(
+            forced("add it", Some("fix"), Some("cli")),
+            "fix(cli): add it"
+        );
+        // a scope alone has no type to go with
+        assert_eq!(forced("add it", None, Some("cli")), "add it");
+    }
+}
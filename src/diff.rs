@@ -0,0 +1,1046 @@
+// src/diff.rs
+//! Reading the changes of a repository into a [`ChangeSet`].
+//!
+//! The diff ignores whitespace changes, and files matching the exclude patterns are left out
+//! while it is walked, so their contents never reach the patch text. Churn lines, a removed and
+//! an added line that the same `--ignore-lines` rule matches, are taken out afterwards.
+
+use crate::error::GitChangesError;
+use crate::filter::{is_excluded, line_rules};
+use crate::{commit, context, paths, report, revert, rust_api, tree};
+use anyhow::{Context, Result};
+use git2::{Delta, DiffDelta, DiffFormat, DiffHunk, DiffLine, DiffOptions, Oid, Repository};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use tracing::{field, instrument, Span};
+
+/// The changes a message is written for: the patch text of the files that made it through the
+/// exclude patterns, with what is known about each of them.
+///
+/// Made by [`collect_changes`]; [`crate::prompt::build_prompt`] turns it into a prompt.
+#[derive(Debug, Clone)]
+pub struct ChangeSet {
+    pub(crate) diff: String,
+    pub(crate) files: Vec<String>,
+    // paths the exclude patterns or --select kept out of `diff`
+    pub(crate) excluded: Vec<String>,
+    pub(crate) changes: Vec<FileChange>,
+    pub(crate) hunks: Vec<HunkMark>,
+    // hashes of the added and removed lines, for revert detection
+    pub(crate) line_hashes: Vec<u64>,
+    // true when the diff is index-vs-HEAD rather than workdir-vs-index
+    pub(crate) staged: bool,
+}
+
+impl ChangeSet {
+    /// The patch text of every file, in the order of [`ChangeSet::files`].
+    pub fn diff(&self) -> &str {
+        &self.diff
+    }
+
+    /// The paths of the changed files, `/`-separated and relative to the repository root.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// The paths of the changed files the exclude patterns left out.
+    pub fn excluded(&self) -> &[String] {
+        &self.excluded
+    }
+
+    /// Whether the changes are the staged ones (index against `HEAD`) rather than unstaged.
+    pub fn is_staged(&self) -> bool {
+        self.staged
+    }
+
+    /// Whether no file is left to describe.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    // the part of `diff` belonging to files[index]
+    pub(crate) fn file_patch(&self, index: usize) -> &str {
+        let end = self
+            .changes
+            .get(index + 1)
+            .map_or(self.diff.len(), |next| next.offset);
+        &self.diff[self.changes[index].offset..end]
+    }
+
+    pub(crate) fn stats(&self) -> Vec<report::FileStat> {
+        self.changes
+            .iter()
+            .zip(&self.files)
+            .map(|(change, path)| change.stat(path))
+            .collect()
+    }
+
+    // keeps only the files marked in `keep`, moving the rest to `excluded`
+    pub(crate) fn retain(&mut self, keep: &[bool]) {
+        let hunks: Vec<bool> = self
+            .hunks
+            .iter()
+            .map(|mark| keep[self.hunk_file(mark)])
+            .collect();
+        self.narrow(keep, &hunks);
+    }
+
+    // keeps only the hunks marked in `keep`; a file left with none of its hunks moves to
+    // `excluded`, and one that never had any (binary, mode-only) stays
+    pub(crate) fn retain_hunks(&mut self, keep: &[bool]) {
+        self.narrow(&vec![true; self.files.len()], keep);
+    }
+
+    // the index of the file a hunk belongs to
+    pub(crate) fn hunk_file(&self, mark: &HunkMark) -> usize {
+        self.changes
+            .partition_point(|change| change.offset <= mark.span.start)
+            .saturating_sub(1)
+    }
+
+    // whole files and hunks, marked in `keep_files` and `keep_hunks`
+    fn narrow(&mut self, keep_files: &[bool], keep_hunks: &[bool]) {
+        let keep_lines: Vec<Vec<bool>> = self
+            .hunks
+            .iter()
+            .zip(keep_hunks)
+            .map(|(mark, &keep)| vec![keep; mark.lines.len()])
+            .collect();
+        self.rebuild(keep_files, &keep_lines, false);
+    }
+
+    // takes the churn `rules` match out of every hunk, shrinking its header to match, and drops
+    // the hunks left without changes; a file that lost lines ends with a note saying how many,
+    // and stays even when none of its hunks is left. Returns the number of lines taken out
+    pub(crate) fn drop_churn(&mut self, rules: &[Regex]) -> usize {
+        if rules.is_empty() {
+            return 0;
+        }
+        let keep_lines: Vec<Vec<bool>> = self
+            .hunks
+            .iter()
+            .map(|mark| {
+                churn_lines(&self.diff, mark, rules)
+                    .into_iter()
+                    .map(|churn| !churn)
+                    .collect()
+            })
+            .collect();
+        self.rebuild(&vec![true; self.files.len()], &keep_lines, true)
+    }
+
+    // the diff text, hunks and line hashes cut from what was collected, not diffed again:
+    // the files marked in `keep_files`, and of each hunk the lines marked in `keep_lines`. A
+    // hunk that keeps every line is copied as it is, one left without changes goes, and any
+    // other gets a header counting what is left. A file left without any of its hunks moves to
+    // `excluded`, unless `note_dropped`, when it stays and every file that lost changed lines
+    // ends with a note saying how many. Returns the number of changed lines taken out
+    fn rebuild(
+        &mut self,
+        keep_files: &[bool],
+        keep_lines: &[Vec<bool>],
+        note_dropped: bool,
+    ) -> usize {
+        let ends: Vec<(usize, usize)> = (0..self.changes.len())
+            .map(|index| match self.changes.get(index + 1) {
+                Some(next) => (next.offset, next.hash_offset),
+                None => (self.diff.len(), self.line_hashes.len()),
+            })
+            .collect();
+        let mut by_file: Vec<Vec<(&HunkMark, &[bool])>> = vec![Vec::new(); self.files.len()];
+        for (mark, keep) in self.hunks.iter().zip(keep_lines) {
+            by_file[self.hunk_file(mark)].push((mark, keep));
+        }
+        // the added and removed lines of a hunk that are taken out
+        let dropped_counts = |mark: &HunkMark, keep: &[bool]| {
+            mark.lines
+                .iter()
+                .zip(keep)
+                .filter(|(_, keep)| !**keep)
+                .fold((0, 0), |(p, m), ((_, origin), _)| match origin {
+                    '+' => (p + 1, m),
+                    '-' => (p, m + 1),
+                    _ => (p, m),
+                })
+        };
+        let survives = |mark: &HunkMark, keep: &[bool]| {
+            let (plus, minus) = mark.counts();
+            keep.iter().all(|keep| *keep) || dropped_counts(mark, keep) != (plus, minus)
+        };
+
+        let mut diff = String::new();
+        let mut files = Vec::new();
+        let mut changes = Vec::new();
+        let mut hunks = Vec::new();
+        let mut line_hashes = Vec::new();
+        let mut excluded = Vec::new();
+        let mut total = 0;
+        for (index, change) in self.changes.iter().enumerate() {
+            let marks = &by_file[index];
+            let kept_any =
+                marks.is_empty() || marks.iter().any(|(mark, keep)| survives(mark, keep));
+            if !keep_files[index] || (!kept_any && !note_dropped) {
+                excluded.push(self.files[index].clone());
+                continue;
+            }
+            let (end, hashes_end) = ends[index];
+            let header_end = marks.first().map_or(end, |(mark, _)| mark.span.start);
+            let offset = diff.len();
+            let hash_offset = line_hashes.len();
+            diff.push_str(&self.diff[change.offset..header_end]);
+            let (mut added, mut removed) = match marks.is_empty() {
+                true => (change.added, change.removed),
+                false => (0, 0),
+            };
+            let mut hash = change.hash_offset;
+            let mut dropped = 0;
+            for (mark, keep) in marks {
+                let (plus, minus) = mark.counts();
+                let (dropped_plus, dropped_minus) = dropped_counts(mark, keep);
+                dropped += dropped_plus + dropped_minus;
+                if keep.iter().all(|keep| *keep) {
+                    hunks.push(mark.moved(mark.span.start, diff.len()));
+                    diff.push_str(&self.diff[mark.span.clone()]);
+                    line_hashes.extend_from_slice(&self.line_hashes[hash..hash + plus + minus]);
+                    added += plus;
+                    removed += minus;
+                    hash += plus + minus;
+                    continue;
+                }
+                if !survives(mark, keep) {
+                    hash += plus + minus;
+                    continue;
+                }
+
+                let start = diff.len();
+                diff.push_str(&shrink_header(
+                    &self.diff[mark.span.start..mark.offset],
+                    dropped_minus,
+                    dropped_plus,
+                ));
+                let header_offset = diff.len();
+                let first_line = mark.lines.first().map_or(mark.span.end, |(at, _)| *at);
+                diff.push_str(&self.diff[mark.offset..first_line]);
+                let mut lines = Vec::new();
+                for (i, &(at, origin)) in mark.lines.iter().enumerate() {
+                    let line_end = mark
+                        .lines
+                        .get(i + 1)
+                        .map_or(mark.span.end, |(next, _)| *next);
+                    let changed = matches!(origin, '+' | '-');
+                    if changed && keep[i] {
+                        line_hashes.extend(self.line_hashes.get(hash).copied());
+                    }
+                    hash += changed as usize;
+                    if !keep[i] {
+                        continue;
+                    }
+                    match origin {
+                        '+' => added += 1,
+                        '-' => removed += 1,
+                        _ => {}
+                    }
+                    lines.push((diff.len(), origin));
+                    diff.push_str(&self.diff[at..line_end]);
+                }
+                hunks.push(HunkMark {
+                    offset: header_offset,
+                    span: start..diff.len(),
+                    lines,
+                    path: mark.path.clone(),
+                    ..**mark
+                });
+            }
+            // anything after the last hunk, and the hashes of a file without hunks
+            let tail = marks.last().map_or(end, |(mark, _)| mark.span.end);
+            diff.push_str(&self.diff[tail..end]);
+            line_hashes.extend_from_slice(&self.line_hashes[hash.min(hashes_end)..hashes_end]);
+            if note_dropped && dropped > 0 {
+                diff.push_str(&format!("# {} churn lines ignored\n", dropped));
+            }
+            total += dropped;
+            files.push(self.files[index].clone());
+            changes.push(FileChange {
+                old_path: change.old_path.clone(),
+                new_path: change.new_path.clone(),
+                offset,
+                hash_offset,
+                added,
+                removed,
+                ..*change
+            });
+        }
+        self.diff = diff;
+        self.files = files;
+        self.changes = changes;
+        self.hunks = hunks;
+        self.line_hashes = line_hashes;
+        self.excluded.extend(excluded);
+        total
+    }
+}
+
+// which lines of a hunk are churn: a removed line and an added one the same rule matches, paired
+// in order, with a no-newline marker going along with the line before it. A match without a
+// partner on the other side is a real change, such as the version line of a new file
+fn churn_lines(diff: &str, mark: &HunkMark, rules: &[Regex]) -> Vec<bool> {
+    let content = |i: usize| {
+        let (at, _) = mark.lines[i];
+        let end = mark
+            .lines
+            .get(i + 1)
+            .map_or(mark.span.end, |(next, _)| *next);
+        diff[at + 1..end].trim_end_matches('\n')
+    };
+    let origins: Vec<char> = mark.lines.iter().map(|(_, origin)| *origin).collect();
+    let mut churn = vec![false; origins.len()];
+    for removed in (0..origins.len()).filter(|&i| origins[i] == '-') {
+        for rule in rules.iter().filter(|rule| rule.is_match(content(removed))) {
+            let partner = (0..origins.len())
+                .find(|&i| origins[i] == '+' && !churn[i] && rule.is_match(content(i)));
+            if let Some(added) = partner {
+                churn[removed] = true;
+                churn[added] = true;
+                break;
+            }
+        }
+    }
+    for i in 1..origins.len() {
+        if !matches!(origins[i], ' ' | '+' | '-') && churn[i - 1] {
+            churn[i] = true;
+        }
+    }
+    churn
+}
+
+// "@@ -12,7 +12,7 @@ fn name" with `removed` fewer old and `added` fewer new lines; a header
+// that doesn't read that way stays as it is
+fn shrink_header(header: &str, removed: usize, added: usize) -> String {
+    let shrink = |range: &str, sign: char, by: usize| -> Option<String> {
+        let range = range.strip_prefix(sign)?;
+        let (start, count) = match range.split_once(',') {
+            Some((start, count)) => (start, count.parse::<usize>().ok()?),
+            None => (range, 1),
+        };
+        Some(format!("{}{},{}", sign, start, count.checked_sub(by)?))
+    };
+    let Some(rest) = header.strip_prefix("@@ ") else {
+        return header.to_string();
+    };
+    let mut parts = rest.splitn(3, ' ');
+    let (Some(old), Some(new), Some(rest)) = (parts.next(), parts.next(), parts.next()) else {
+        return header.to_string();
+    };
+    match (shrink(old, '-', removed), shrink(new, '+', added)) {
+        (Some(old), Some(new)) => format!("@@ {} {} {}", old, new, rest),
+        _ => header.to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct FileChange {
+    pub(crate) old_path: Option<PathBuf>,
+    pub(crate) new_path: Option<PathBuf>,
+    pub(crate) old_id: Oid,
+    pub(crate) new_id: Oid,
+    pub(crate) status: Delta,
+    // where the file's patch starts in the diff text, and its first entry in line_hashes
+    pub(crate) offset: usize,
+    pub(crate) hash_offset: usize,
+    // lines, as in `git diff --numstat`
+    pub(crate) added: usize,
+    pub(crate) removed: usize,
+}
+
+impl FileChange {
+    // both sides of a rename, which have to be staged together
+    pub(crate) fn paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = [&self.old_path, &self.new_path]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+        paths.dedup();
+        paths
+    }
+
+    pub(crate) fn stat(&self, path: &str) -> report::FileStat {
+        let status = match self.status {
+            Delta::Added | Delta::Untracked => "added",
+            Delta::Deleted => "deleted",
+            Delta::Renamed => "renamed",
+            Delta::Copied => "copied",
+            Delta::Typechange => "type changed",
+            _ => "modified",
+        };
+        report::FileStat {
+            path: path.to_string(),
+            status,
+            added: self.added,
+            removed: self.removed,
+        }
+    }
+}
+
+// a hunk in the diff text and the new-file line of its first change
+#[derive(Debug, Clone)]
+pub(crate) struct HunkMark {
+    // byte offset of the end of the header line, before its newline
+    pub(crate) offset: usize,
+    // from the start of the header to the end of the last line
+    pub(crate) span: Range<usize>,
+    // where each line after the header starts, and its origin (' ', '+', '-' or a
+    // no-newline marker)
+    pub(crate) lines: Vec<(usize, char)>,
+    pub(crate) path: PathBuf,
+    pub(crate) start: usize,
+    pub(crate) first_change: Option<usize>,
+}
+
+impl HunkMark {
+    pub(crate) fn line(&self) -> usize {
+        self.first_change.unwrap_or(self.start)
+    }
+
+    // added and removed lines, as record_file counts them
+    pub(crate) fn counts(&self) -> (usize, usize) {
+        let count = |origin| self.lines.iter().filter(|(_, o)| *o == origin).count();
+        (count('+'), count('-'))
+    }
+
+    // the same hunk with its text moved from `from` to `to` in the diff
+    pub(crate) fn moved(&self, from: usize, to: usize) -> HunkMark {
+        let shift = |offset: usize| offset - from + to;
+        HunkMark {
+            offset: shift(self.offset),
+            span: shift(self.span.start)..shift(self.span.end),
+            lines: self.lines.iter().map(|&(at, o)| (shift(at), o)).collect(),
+            path: self.path.clone(),
+            ..*self
+        }
+    }
+
+    // the hunk as `git diff` shows it
+    pub(crate) fn display(&self, diff: &str) -> String {
+        diff[self.span.clone()].to_string()
+    }
+}
+
+// called after each line is appended to the diff text, which it started at `line_start`
+fn record_hunk(
+    hunks: &mut Vec<HunkMark>,
+    diff_text: &str,
+    line_start: usize,
+    delta: &DiffDelta,
+    hunk: Option<DiffHunk>,
+    line: &DiffLine,
+) {
+    if line.origin() != 'H' && hunk.is_some() {
+        if let Some(mark) = hunks.last_mut() {
+            mark.lines.push((line_start, line.origin()));
+            mark.span.end = diff_text.len();
+        }
+    }
+    match (line.origin(), hunk) {
+        ('H', Some(hunk)) => {
+            if let Some(path) = delta.new_file().path() {
+                hunks.push(HunkMark {
+                    offset: diff_text.strip_suffix('\n').unwrap_or(diff_text).len(),
+                    span: line_start..diff_text.len(),
+                    lines: Vec::new(),
+                    path: path.to_path_buf(),
+                    start: hunk.new_start() as usize,
+                    first_change: None,
+                });
+            }
+        }
+        ('+' | '-', Some(hunk)) => {
+            let Some(mark) = hunks.last_mut().filter(|m| m.first_change.is_none()) else {
+                return;
+            };
+            // a removal has no new-side line; map it through the hunk's start offsets
+            mark.first_change = match (line.new_lineno(), line.old_lineno()) {
+                (Some(new), _) => Some(new as usize),
+                (None, Some(old)) => {
+                    Some((hunk.new_start() + old.saturating_sub(hunk.old_start())) as usize)
+                }
+                _ => None,
+            };
+        }
+        _ => {}
+    }
+}
+
+// the path a delta is known by: the new side, or the old one for deletions
+fn delta_path(delta: &DiffDelta) -> Option<String> {
+    delta
+        .new_file()
+        .path()
+        .or_else(|| delta.old_file().path())
+        .map(paths::display)
+}
+
+// a line of patch text as `git diff` shows it, with the file header's paths as readable as
+// the line content
+fn push_line(diff_text: &mut String, line: &DiffLine) {
+    if matches!(line.origin(), ' ' | '+' | '-') {
+        diff_text.push(line.origin());
+    }
+    match std::str::from_utf8(line.content()) {
+        Ok(header) if line.origin() == 'F' => diff_text.push_str(&paths::unquote_header(header)),
+        Ok(content) => diff_text.push_str(content),
+        Err(_) => diff_text.push_str("(error: non-utf8 diff content)\n"),
+    };
+}
+
+fn record_file(
+    files: &mut Vec<String>,
+    changes: &mut Vec<FileChange>,
+    delta: &DiffDelta,
+    line: &DiffLine,
+    (offset, hash_offset): (usize, usize),
+) {
+    if let Some(path) = delta_path(delta) {
+        if files.last() != Some(&path) {
+            files.push(path);
+            changes.push(FileChange {
+                old_path: delta.old_file().path().map(Path::to_path_buf),
+                new_path: delta.new_file().path().map(Path::to_path_buf),
+                old_id: delta.old_file().id(),
+                new_id: delta.new_file().id(),
+                status: delta.status(),
+                offset,
+                hash_offset,
+                added: 0,
+                removed: 0,
+            });
+        }
+    }
+    if let Some(change) = changes.last_mut() {
+        match line.origin() {
+            '+' => change.added += 1,
+            '-' => change.removed += 1,
+            _ => {}
+        }
+    }
+}
+
+fn record_excluded(excluded: &mut Vec<String>, delta: &DiffDelta) {
+    if let Some(path) = delta_path(delta) {
+        if excluded.last() != Some(&path) {
+            excluded.push(path);
+        }
+    }
+}
+
+// the files of a --per-file run in path order, with a deletion and an addition of the same
+// content (a rename, since diffs here don't detect them) kept in one unit
+pub(crate) fn per_file_units(changes: &ChangeSet) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..changes.files.len()).collect();
+    order.sort_by(|&a, &b| changes.files[a].cmp(&changes.files[b]));
+
+    let mut paired = vec![false; changes.files.len()];
+    let mut units = Vec::new();
+    for &i in &order {
+        if paired[i] {
+            continue;
+        }
+        paired[i] = true;
+        let change = &changes.changes[i];
+        let partner = order.iter().copied().find(|&j| {
+            let other = &changes.changes[j];
+            let renamed = if change.new_id.is_zero() {
+                other.old_id.is_zero() && other.new_id == change.old_id
+            } else if change.old_id.is_zero() {
+                other.new_id.is_zero() && other.old_id == change.new_id
+            } else {
+                false
+            };
+            !paired[j] && renamed
+        });
+        match partner {
+            Some(j) => {
+                paired[j] = true;
+                // deleted side first, so the unit reads "old -> new"
+                units.push(if change.new_id.is_zero() {
+                    vec![i, j]
+                } else {
+                    vec![j, i]
+                });
+            }
+            None => units.push(vec![i]),
+        }
+    }
+    units
+}
+
+/// Which changes [`collect_changes`] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum DiffMode {
+    /// The unstaged changes, or the staged ones when nothing is unstaged.
+    #[default]
+    Auto,
+    /// The index against `HEAD`: what a commit would contain.
+    Staged,
+    /// The index against the parent of `HEAD`: what amending `HEAD` would leave.
+    Amend,
+}
+
+/// Which new files [`collect_changes`] reads along with the unstaged changes of
+/// [`DiffMode::Auto`]. The index has none, so the other modes don't see them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Untracked {
+    /// None: only files git already tracks.
+    #[default]
+    None,
+    /// Those no `.gitignore`, `.git/info/exclude` or `core.excludesFile` rule matches.
+    NotIgnored,
+    /// Ignored ones too, like `git add -A --force` would add.
+    All,
+}
+
+/// What [`collect_changes`] reads and leaves out.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct CollectOptions {
+    /// Glob patterns of files to leave out, as [`crate::filter::is_excluded_path`] matches them.
+    pub excludes: Vec<String>,
+    /// Regular expressions for churn lines: a removed line and an added one in the same hunk
+    /// that one of them matches are both left out, as are hunks with nothing else in them.
+    pub ignore_lines: Vec<String>,
+    /// Which changes to read.
+    pub mode: DiffMode,
+    /// Which new files to read with the unstaged changes.
+    pub untracked: Untracked,
+}
+
+impl CollectOptions {
+    /// Options reading `mode` with nothing excluded.
+    pub fn new(mode: DiffMode) -> Self {
+        CollectOptions {
+            excludes: Vec::new(),
+            ignore_lines: Vec::new(),
+            mode,
+            untracked: Untracked::None,
+        }
+    }
+
+    /// The same options reading the new files `untracked` selects as well.
+    pub fn untracked(mut self, untracked: Untracked) -> Self {
+        self.untracked = untracked;
+        self
+    }
+
+    /// The same options with `patterns` left out as well.
+    pub fn exclude<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.excludes.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// The same options with the churn lines `patterns` match left out as well.
+    pub fn ignore_lines<I, S>(mut self, patterns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.ignore_lines
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+}
+
+/// Reads the changes of `repo` that `options` asks for.
+///
+/// A set with no files ([`ChangeSet::is_empty`]) means there was nothing to describe, or that
+/// everything was excluded, which [`ChangeSet::excluded`] tells apart. A git operation that
+/// fails is a [`GitChangesError::Git`], and an `ignore_lines` pattern that isn't a regular
+/// expression a [`GitChangesError::Config`].
+///
+/// ```no_run
+/// use git2::Repository;
+/// use git_changes_rs::diff::{collect_changes, CollectOptions, DiffMode};
+///
+/// let repo = Repository::discover(".")?;
+/// let options = CollectOptions::new(DiffMode::Staged).exclude(["Cargo.lock"]);
+/// let changes = collect_changes(&repo, &options)?;
+/// for file in changes.files() {
+///     println!("{}", file);
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn collect_changes(
+    repo: &Repository,
+    options: &CollectOptions,
+) -> Result<ChangeSet, GitChangesError> {
+    let rules = line_rules(&options.ignore_lines)?;
+    let mut changes = fetch_diffs(repo, &options.excludes, options.mode, options.untracked)
+        .map_err(GitChangesError::from)?;
+    changes.drop_churn(&rules);
+    Ok(changes)
+}
+
+// excluded files are filtered out while the diff is walked, so both are one span
+#[instrument(name = "collect_diff", skip_all, fields(bytes = field::Empty))]
+pub(crate) fn fetch_diffs(
+    repo: &Repository,
+    excludes: &[String],
+    mode: DiffMode,
+    untracked: Untracked,
+) -> Result<ChangeSet> {
+    let mut diff_options = DiffOptions::new();
+    diff_options.ignore_whitespace(true);
+    // only the workdir diff has untracked files; they show as added, with their contents
+    if untracked != Untracked::None {
+        diff_options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true);
+    }
+    // ignored files have no contents in a diff, so --no-gitignore reads the workdir through a
+    // handle of its own on which nothing is ignored; git's own directory still is
+    let unignored = match untracked {
+        Untracked::All => {
+            let handle = Repository::open(repo.path())
+                .map_err(|e| GitChangesError::git("open git repository", e))?;
+            handle
+                .add_ignore_rule("!*\n.git\n")
+                .map_err(|e| GitChangesError::git("read the ignore rules", e))?;
+            Some(handle)
+        }
+        _ => None,
+    };
+
+    let mut diff_text = String::new();
+    let mut files = Vec::new();
+    let mut excluded = Vec::new();
+    let mut changes = Vec::new();
+    let mut hunks = Vec::new();
+    let mut line_hashes = Vec::new();
+    let mut staged = false;
+
+    if mode == DiffMode::Auto {
+        let diff = unignored
+            .as_ref()
+            .unwrap_or(repo)
+            .diff_index_to_workdir(None, Some(&mut diff_options))
+            .map_err(|e| GitChangesError::git("generate diff between index and workdir", e))?;
+
+        let print_result = diff.print(DiffFormat::Patch, |delta, hunk, line| {
+            if !is_excluded(&delta, excludes) {
+                let line_start = diff_text.len();
+                record_file(
+                    &mut files,
+                    &mut changes,
+                    &delta,
+                    &line,
+                    (line_start, line_hashes.len()),
+                );
+                push_line(&mut diff_text, &line);
+                record_hunk(&mut hunks, &diff_text, line_start, &delta, hunk, &line);
+                line_hashes.extend(revert::line_hash(&delta, &line, false));
+            } else {
+                record_excluded(&mut excluded, &delta);
+            }
+            true
+        });
+        print_result
+            .map_err(|e| GitChangesError::git("process unstaged diff output with filtering", e))?;
+    }
+
+    if diff_text.trim().is_empty() {
+        let head_tree = match mode {
+            DiffMode::Amend => {
+                let head = repo
+                    .head()
+                    .and_then(|head| head.peel_to_commit())
+                    .map_err(|e| GitChangesError::git("resolve HEAD", e))?;
+                match head.parents().next() {
+                    Some(parent) => Some(
+                        parent
+                            .tree()
+                            .map_err(|e| GitChangesError::git("read parent tree", e))?,
+                    ),
+                    None => None,
+                }
+            }
+            _ => commit::head_tree(repo)?,
+        };
+
+        let staged_diff = repo
+            .diff_tree_to_index(head_tree.as_ref(), None, Some(&mut diff_options))
+            .map_err(|e| GitChangesError::git("get diff between head tree and index", e))?;
+
+        if staged_diff.deltas().len() > 0 {
+            let mut staged_diff_text_local = String::new();
+            let mut staged_files = Vec::new();
+            let mut staged_excluded = Vec::new();
+            let mut staged_changes = Vec::new();
+            let mut staged_hunks = Vec::new();
+            let mut staged_line_hashes = Vec::new();
+            let staged_print_result = staged_diff.print(DiffFormat::Patch, |delta, hunk, line| {
+                if !is_excluded(&delta, excludes) {
+                    let line_start = staged_diff_text_local.len();
+                    record_file(
+                        &mut staged_files,
+                        &mut staged_changes,
+                        &delta,
+                        &line,
+                        (line_start, staged_line_hashes.len()),
+                    );
+                    push_line(&mut staged_diff_text_local, &line);
+                    record_hunk(
+                        &mut staged_hunks,
+                        &staged_diff_text_local,
+                        line_start,
+                        &delta,
+                        hunk,
+                        &line,
+                    );
+                    staged_line_hashes.extend(revert::line_hash(&delta, &line, false));
+                } else {
+                    record_excluded(&mut staged_excluded, &delta);
+                }
+                true
+            });
+            staged_print_result.map_err(|e| {
+                GitChangesError::git("process staged diff output with filtering", e)
+            })?;
+
+            if !staged_diff_text_local.trim().is_empty() {
+                diff_text = staged_diff_text_local;
+                files = staged_files;
+                excluded = staged_excluded;
+                changes = staged_changes;
+                hunks = staged_hunks;
+                line_hashes = staged_line_hashes;
+                staged = true;
+            } else {
+                // everything staged was excluded; exit.rs tells that apart from no changes
+                excluded.extend(staged_excluded);
+            }
+        }
+    }
+
+    if diff_text.trim().is_empty() {
+        return Ok(ChangeSet {
+            diff: String::new(),
+            files: Vec::new(),
+            excluded,
+            changes: Vec::new(),
+            hunks: Vec::new(),
+            line_hashes: Vec::new(),
+            staged,
+        });
+    }
+
+    Span::current().record("bytes", diff_text.len());
+    Ok(ChangeSet {
+        diff: diff_text,
+        files,
+        excluded,
+        changes,
+        hunks,
+        line_hashes,
+        staged,
+    })
+}
+
+fn read_blob(repo: &Repository, id: Oid) -> Option<String> {
+    if id.is_zero() {
+        return None;
+    }
+    let blob = repo.find_blob(id).ok()?;
+    String::from_utf8(blob.content().to_vec()).ok()
+}
+
+// old and new contents of a changed file; the new side of an unstaged change lives in the workdir
+fn file_versions(
+    repo: &Repository,
+    change: &FileChange,
+    staged: bool,
+) -> (Option<String>, Option<String>) {
+    let old = read_blob(repo, change.old_id);
+    let new = read_blob(repo, change.new_id).or_else(|| {
+        if staged {
+            return None;
+        }
+        let path = repo.workdir()?.join(change.new_path.as_ref()?);
+        std::fs::read_to_string(path).ok()
+    });
+    (old, new)
+}
+
+// "in fn name" annotations for hunks of supported languages; files that can't be read get none
+pub(crate) fn hunk_annotations(repo: &Repository, changes: &ChangeSet) -> Vec<(usize, String)> {
+    let mut sources: BTreeMap<&Path, Option<String>> = BTreeMap::new();
+    let mut annotations = Vec::new();
+
+    for mark in &changes.hunks {
+        let Some(language) = context::Language::from_path(&mark.path.to_string_lossy()) else {
+            continue;
+        };
+        let source = sources.entry(&mark.path).or_insert_with(|| {
+            changes
+                .changes
+                .iter()
+                .find(|c| c.new_path.as_ref() == Some(&mark.path))
+                .and_then(|change| file_versions(repo, change, changes.staged).1)
+        });
+        if let Some(item) = source
+            .as_deref()
+            .and_then(|source| context::enclosing_item(language, source, mark.line()))
+        {
+            annotations.push((mark.offset, context::annotation(&item)));
+        }
+    }
+    annotations
+}
+
+// the tracked files as a compact tree; the index already leaves out ignored files
+pub(crate) fn repository_tree(repo: &Repository) -> Result<String> {
+    let index = repo.index().context("failed to read the index")?;
+    let paths: Vec<String> = index
+        .iter()
+        .map(|entry| String::from_utf8_lossy(&entry.path).into_owned())
+        .collect();
+    Ok(tree::render_tree(
+        &paths,
+        tree::MAX_TREE_ENTRIES,
+        tree::MAX_TREE_BYTES,
+    ))
+}
+
+// one summary line per changed .rs file whose public items differ
+pub(crate) fn rust_api_summary(repo: &Repository, changes: &ChangeSet) -> (Vec<String>, bool) {
+    let mut lines = Vec::new();
+    let mut breaking = false;
+
+    for change in &changes.changes {
+        let path = change.new_path.as_ref().or(change.old_path.as_ref());
+        let Some(path) = path.filter(|p| p.extension().is_some_and(|ext| ext == "rs")) else {
+            continue;
+        };
+
+        let (old, new) = file_versions(repo, change, changes.staged);
+        // a missing side (added/deleted file) has no items; a parse failure skips the file
+        let old_items = match old {
+            Some(source) => rust_api::public_items(&source),
+            None => Some(Default::default()),
+        };
+        let new_items = match new {
+            Some(source) => rust_api::public_items(&source),
+            None => Some(Default::default()),
+        };
+        let (Some(old_items), Some(new_items)) = (old_items, new_items) else {
+            continue;
+        };
+
+        let diff = rust_api::diff_items(&old_items, &new_items);
+        if !diff.is_empty() {
+            breaking |= diff.is_breaking();
+            lines.push(rust_api::summarize(&path.to_string_lossy(), &diff));
+        }
+    }
+
+    (lines, breaking)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::IndexAddOption;
+    use std::fs;
+
+    const LONG: &str =
+        "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\neleven\ntwelve\n";
+
+    // a repository with `long.txt` changed near both ends, so two hunks, and `short.txt` changed
+    fn changed() -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        fs::write(dir.path().join("long.txt"), LONG).unwrap();
+        fs::write(dir.path().join("short.txt"), "first\n").unwrap();
+        let mut index = repo.index().unwrap();
+        index.add_all(["*"], IndexAddOption::DEFAULT, None).unwrap();
+        index.write().unwrap();
+        {
+            let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+            let signature = git2::Signature::now("Test", "test@example.com").unwrap();
+            repo.commit(Some("HEAD"), &signature, &signature, "initial", &tree, &[])
+                .unwrap();
+        }
+        let long = LONG
+            .replace("two\n", "TWO\n")
+            .replace("ten\n", "ten!\n")
+            .replace("eleven\n", "ELEVEN\n");
+        fs::write(dir.path().join("long.txt"), long).unwrap();
+        fs::write(dir.path().join("short.txt"), "first\nsecond\n").unwrap();
+        (dir, repo)
+    }
+
+    fn collect(repo: &Repository) -> ChangeSet {
+        collect_changes(repo, &CollectOptions::new(DiffMode::Auto)).unwrap()
+    }
+
+    // every hunk mark still points at its own header, and the counts add up
+    fn consistent(changes: &ChangeSet) {
+        for mark in &changes.hunks {
+            assert!(changes.diff[mark.span.clone()].starts_with("@@ "));
+            assert_eq!(changes.diff.as_bytes()[mark.offset], b'\n');
+        }
+        let stats = changes.stats();
+        let hashes: usize = stats.iter().map(|stat| stat.added + stat.removed).sum();
+        assert_eq!(changes.line_hashes.len(), hashes);
+    }
+
+    #[test]
+    fn retaining_hunks_keeps_the_marked_ones_and_excludes_a_file_left_without_any() {
+        let (_dir, repo) = changed();
+        let mut changes = collect(&repo);
+        assert_eq!(changes.hunks.len(), 3);
+        changes.retain_hunks(&[false, true, false]);
+        assert_eq!(changes.files(), ["long.txt"]);
+        assert_eq!(changes.excluded(), ["short.txt"]);
+        assert!(!changes.diff().contains("TWO"), "{}", changes.diff());
+        assert!(changes.diff().contains("+ELEVEN\n"), "{}", changes.diff());
+        assert_eq!(changes.hunks.len(), 1);
+        consistent(&changes);
+    }
+
+    #[test]
+    fn retaining_files_keeps_every_hunk_of_the_kept_ones() {
+        let (_dir, repo) = changed();
+        let mut changes = collect(&repo);
+        let before = changes.file_patch(0).to_string();
+        changes.retain(&[true, false]);
+        assert_eq!(changes.files(), ["long.txt"]);
+        assert_eq!(changes.diff(), before);
+        assert_eq!(changes.hunks.len(), 2);
+        consistent(&changes);
+    }
+
+    #[test]
+    fn churn_and_hunk_selection_cut_through_the_same_bookkeeping() {
+        let (_dir, repo) = changed();
+        let mut changes = collect(&repo);
+        assert_eq!(changes.drop_churn(&[Regex::new("^ten").unwrap()]), 2);
+        assert!(!changes.diff().contains("ten!"), "{}", changes.diff());
+        assert!(changes.diff().contains("# 2 churn lines ignored\n"));
+        assert_eq!(changes.stats()[0].added, 2);
+        consistent(&changes);
+
+        changes.retain_hunks(&[false, true, true]);
+        assert_eq!(changes.files(), ["long.txt", "short.txt"]);
+        assert!(!changes.diff().contains("TWO"));
+        assert!(changes.diff().contains("+ELEVEN\n"));
+        assert_eq!(changes.stats()[0].added, 1);
+        consistent(&changes);
+    }
+}
@@ -0,0 +1,114 @@
+// src/editor.rs
+// opening text in the user's editor
+
+use crate::interactivity::Terminal;
+use crate::interrupt;
+use anyhow::{anyhow, Context, Result};
+use git2::Repository;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const EDIT_MESSAGE_FILE: &str = "COMMIT_EDITMSG";
+
+// git's order: $GIT_EDITOR, core.editor, $VISUAL, $EDITOR, then vi
+pub fn editor_command(repo: Option<&Repository>) -> String {
+    let configured = || {
+        repo?
+            .config()
+            .ok()?
+            .get_string("core.editor")
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+    };
+    let from_env = |var: &str| env::var(var).ok().filter(|value| !value.trim().is_empty());
+
+    from_env("GIT_EDITOR")
+        .or_else(configured)
+        .or_else(|| from_env("VISUAL"))
+        .or_else(|| from_env("EDITOR"))
+        .unwrap_or_else(|| "vi".to_string())
+}
+
+// writes `initial` to `path`, opens it in `editor` and returns what was saved
+pub fn edit_file(editor: &str, path: &Path, initial: &str) -> Result<String> {
+    fs::write(path, initial).with_context(|| format!("failed to write {}", path.display()))?;
+
+    // run through the shell so editors configured with arguments ("code --wait") work
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command
+            .arg("/C")
+            .arg(format!("{} \"{}\"", editor, path.display()));
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(path);
+        command
+    };
+    // on the terminal even when stdout is piped, where a terminal editor would draw into the pipe
+    if let Some((input, output)) = Terminal::open().and_then(|terminal| terminal.handles().ok()) {
+        command.stdin(input).stdout(output);
+    }
+    let _editing = interrupt::editing();
+    let status = command
+        .status()
+        .with_context(|| format!("failed to launch editor '{}'", editor))?;
+
+    if !status.success() {
+        // 127 is the shell's "command not found"
+        if status.code() == Some(127) {
+            return Err(anyhow!(
+                "editor '{}' was not found; set GIT_EDITOR, core.editor, VISUAL or EDITOR",
+                editor
+            ));
+        }
+        return Err(anyhow!("editor '{}' exited with {}", editor, status));
+    }
+    fs::read_to_string(path).with_context(|| format!("failed to read back {}", path.display()))
+}
+
+// the message followed by commented guidance and the files involved
+pub fn message_template(message: &str, files: &[String]) -> String {
+    let mut template = format!(
+        "{}\n\n# Edit the commit message above. Lines starting with '#' are ignored,\n# and an empty message aborts.\n",
+        message.trim_end()
+    );
+    if !files.is_empty() {
+        template.push_str("#\n# Files in this change:\n");
+        for file in files {
+            template.push_str(&format!("#\t{}\n", file));
+        }
+    }
+    template
+}
+
+// drops '#' comment lines and surrounding blank lines
+pub fn strip_comments(text: &str) -> String {
+    let kept: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .map(str::trim_end)
+        .collect();
+    kept.join("\n").trim().to_string()
+}
+
+// lets the user revise a message in .git/COMMIT_EDITMSG; an empty result aborts, like git
+pub fn edit_message(repo: &Repository, message: &str, files: &[String]) -> Result<String> {
+    let path = repo.path().join(EDIT_MESSAGE_FILE);
+    let edited = edit_file(
+        &editor_command(Some(repo)),
+        &path,
+        &message_template(message, files),
+    )?;
+    let edited = strip_comments(&edited);
+    if edited.is_empty() {
+        return Err(anyhow!("aborting: the edited message is empty"));
+    }
+    Ok(edited)
+}
@@ -0,0 +1,346 @@
+// src/error.rs
+//! [`GitChangesError`]: why a call into the library failed, for callers that handle a missing
+//! key differently from a rate limit or a directory outside any repository.
+//!
+//! The binary carries these inside `anyhow` errors with context on top, and its `--json` error
+//! object and exit status are read off the one it finds there.
+
+use std::error::Error;
+use std::path::PathBuf;
+
+/// How a provider failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ProviderFailure {
+    /// The request never got an answer: DNS, connection, TLS or timeout.
+    Network,
+    /// The provider asked for fewer requests (status 429).
+    RateLimited,
+    /// Any other error status.
+    Api,
+    /// An answer without a usable message in it.
+    BadResponse,
+    /// An answer with no text at all: no candidates, or candidates without any parts.
+    Empty,
+}
+
+/// The errors the library returns. Each variant keeps its meaning across releases; new ones
+/// may be added.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum GitChangesError {
+    /// A config file or value that can't be used: unreadable, not TOML, an unknown key, a value
+    /// of the wrong type, or a style that doesn't exist.
+    #[error("{message}")]
+    Config {
+        /// What was wrong, naming the file or key when there is one.
+        message: String,
+        /// The failure underneath, such as the TOML parser's.
+        #[source]
+        source: Option<Box<dyn Error + Send + Sync>>,
+    },
+
+    /// The provider's API key is not set.
+    #[error("{variable} not found in environment")]
+    MissingKey {
+        /// The provider, e.g. `gemini`.
+        provider: &'static str,
+        /// The environment variable the key is read from.
+        variable: &'static str,
+    },
+
+    /// The directory is not inside a git repository.
+    #[error("{} is not in a git repository", path.display())]
+    NotARepository {
+        /// The directory that was looked up.
+        path: PathBuf,
+        /// git's own report.
+        #[source]
+        source: git2::Error,
+    },
+
+    /// A git operation on the repository failed.
+    #[error("failed to {operation}")]
+    Git {
+        /// What was being done, e.g. `read the index`.
+        operation: String,
+        /// git's own report.
+        #[source]
+        source: git2::Error,
+    },
+
+    /// The model provider failed.
+    #[error("{message}")]
+    Provider {
+        /// The provider, e.g. `gemini`.
+        provider: &'static str,
+        /// How it failed.
+        failure: ProviderFailure,
+        /// The HTTP status of an error answer.
+        status: Option<u16>,
+        /// How long a rate-limited provider asked to wait, when it said.
+        retry_after_secs: Option<u64>,
+        /// The provider's own description, or what went wrong with its answer.
+        message: String,
+        /// The failure underneath, such as the HTTP client's.
+        #[source]
+        source: Option<Box<dyn Error + Send + Sync>>,
+    },
+
+    /// The command line asks for something that can't be done: a missing argument, flags
+    /// that don't go together, or a question without a terminal to ask it on.
+    #[error("{message}")]
+    Usage {
+        /// What was asked for, and what is missing.
+        message: String,
+    },
+
+    /// The prompt is over the size limits and sending it wasn't confirmed.
+    #[error("{message}")]
+    TooLarge {
+        /// What is over which limit.
+        message: String,
+    },
+
+    /// There are no changes to describe.
+    #[error("{message}")]
+    NoChanges {
+        /// Which changes were looked at.
+        message: String,
+    },
+
+    /// There were changes, but every one of them is excluded.
+    #[error("{message}")]
+    AllExcluded {
+        /// How many files were left out.
+        message: String,
+    },
+
+    /// The model's messages kept breaking the [`Rules`](crate::message::Rules).
+    #[error(
+        "generated message failed validation after {attempts} attempts:\n{}\n\ninvalid output:\n---\n{message}\n---",
+        problems.join("\n")
+    )]
+    Validation {
+        /// How many messages were asked for.
+        attempts: usize,
+        /// The rules the last one broke, one line each.
+        problems: Vec<String>,
+        /// The last message.
+        message: String,
+    },
+
+    /// Ctrl-C was pressed; what was waiting, such as a request, was dropped.
+    #[error("interrupted")]
+    Interrupted,
+
+    /// Anything else, with its cause chain.
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl GitChangesError {
+    pub(crate) fn config(message: impl Into<String>) -> Self {
+        GitChangesError::Config {
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    pub(crate) fn git(operation: impl Into<String>, source: git2::Error) -> Self {
+        GitChangesError::Git {
+            operation: operation.into(),
+            source,
+        }
+    }
+
+    // NotARepository when git found no repository there, Git for anything else
+    pub(crate) fn open(path: impl Into<PathBuf>, source: git2::Error) -> Self {
+        match source.code() {
+            git2::ErrorCode::NotFound => GitChangesError::NotARepository {
+                path: path.into(),
+                source,
+            },
+            _ => GitChangesError::git("open git repository", source),
+        }
+    }
+
+    pub(crate) fn no_changes(message: impl Into<String>) -> Self {
+        GitChangesError::NoChanges {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn usage(message: impl Into<String>) -> Self {
+        GitChangesError::Usage {
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn too_large(message: impl Into<String>) -> Self {
+        GitChangesError::TooLarge {
+            message: message.into(),
+        }
+    }
+
+    /// Whether the same request may succeed later: a network failure, a rate limit or a server
+    /// error (status 5xx).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            GitChangesError::Provider {
+                failure, status, ..
+            } => match failure {
+                ProviderFailure::Network | ProviderFailure::RateLimited => true,
+                ProviderFailure::Api => status.is_some_and(|status| status >= 500),
+                ProviderFailure::BadResponse | ProviderFailure::Empty => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// The provider a missing key or a provider failure belongs to.
+    pub fn provider(&self) -> Option<&'static str> {
+        match self {
+            GitChangesError::MissingKey { provider, .. }
+            | GitChangesError::Provider { provider, .. } => Some(provider),
+            _ => None,
+        }
+    }
+
+    /// How long a rate-limited provider asked to wait, in seconds.
+    pub fn retry_after_secs(&self) -> Option<u64> {
+        match self {
+            GitChangesError::Provider {
+                retry_after_secs, ..
+            } => *retry_after_secs,
+            _ => None,
+        }
+    }
+
+    // the commands' errors are anyhow ones; one that is only an anyhow error stays as it was
+    pub(crate) fn into_anyhow(self) -> anyhow::Error {
+        match self {
+            GitChangesError::Other(error) => error,
+            error => error.into(),
+        }
+    }
+}
+
+// the typed error an anyhow one carries, as itself or as context on another; the rest of the
+// chain is dropped with it
+impl From<anyhow::Error> for GitChangesError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<GitChangesError>() {
+            Ok(error) => error,
+            Err(error) => GitChangesError::Other(error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn provider(failure: ProviderFailure, status: Option<u16>) -> GitChangesError {
+        GitChangesError::Provider {
+            provider: "gemini",
+            failure,
+            status,
+            retry_after_secs: None,
+            message: "it failed".to_string(),
+            source: None,
+        }
+    }
+
+    #[test]
+    fn only_failures_that_may_pass_later_are_retryable() {
+        assert!(provider(ProviderFailure::Network, None).is_retryable());
+        assert!(provider(ProviderFailure::RateLimited, Some(429)).is_retryable());
+        assert!(provider(ProviderFailure::Api, Some(503)).is_retryable());
+        assert!(!provider(ProviderFailure::Api, Some(400)).is_retryable());
+        assert!(!provider(ProviderFailure::Api, None).is_retryable());
+        assert!(!provider(ProviderFailure::BadResponse, Some(200)).is_retryable());
+        assert!(!provider(ProviderFailure::Empty, Some(200)).is_retryable());
+        let missing = GitChangesError::MissingKey {
+            provider: "gemini",
+            variable: "GEMINI_API_KEY",
+        };
+        assert!(!missing.is_retryable());
+        assert!(!GitChangesError::Interrupted.is_retryable());
+    }
+
+    #[test]
+    fn the_provider_and_the_wait_are_read_off_the_variant() {
+        let missing = GitChangesError::MissingKey {
+            provider: "gemini",
+            variable: "GEMINI_API_KEY",
+        };
+        assert_eq!(missing.provider(), Some("gemini"));
+        assert_eq!(
+            missing.to_string(),
+            "GEMINI_API_KEY not found in environment"
+        );
+        let limited = GitChangesError::Provider {
+            provider: "gemini",
+            failure: ProviderFailure::RateLimited,
+            status: Some(429),
+            retry_after_secs: Some(21),
+            message: "slow down".to_string(),
+            source: None,
+        };
+        assert_eq!(limited.provider(), Some("gemini"));
+        assert_eq!(limited.retry_after_secs(), Some(21));
+        assert_eq!(limited.to_string(), "slow down");
+        let config = GitChangesError::config("bad");
+        assert_eq!(config.provider(), None);
+        assert_eq!(config.retry_after_secs(), None);
+    }
+
+    #[test]
+    fn opening_tells_a_missing_repository_from_other_git_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = git2::Repository::open(dir.path()).err().unwrap();
+        let error = GitChangesError::open(dir.path(), source);
+        assert!(matches!(error, GitChangesError::NotARepository { .. }));
+        assert_eq!(
+            error.to_string(),
+            format!("{} is not in a git repository", dir.path().display())
+        );
+        assert!(error.source().is_some());
+
+        let source = git2::Error::from_str("the index is locked");
+        let error = GitChangesError::open(dir.path(), source);
+        assert!(matches!(error, GitChangesError::Git { .. }));
+        assert_eq!(error.to_string(), "failed to open git repository");
+        assert_eq!(error.source().unwrap().to_string(), "the index is locked");
+    }
+
+    #[test]
+    fn the_typed_error_is_found_under_anyhow_context() {
+        let wrapped = Err::<(), _>(GitChangesError::no_changes("nothing staged"))
+            .context("failed to generate")
+            .unwrap_err();
+        let error = GitChangesError::from(wrapped);
+        assert!(matches!(error, GitChangesError::NoChanges { .. }));
+        assert_eq!(error.to_string(), "nothing staged");
+
+        let plain = GitChangesError::from(anyhow::anyhow!("something else"));
+        assert!(matches!(plain, GitChangesError::Other(_)));
+        // and back again without a layer of its own
+        assert_eq!(format!("{:#}", plain.into_anyhow()), "something else");
+    }
+
+    #[test]
+    fn validation_lists_every_problem_and_the_message() {
+        let error = GitChangesError::Validation {
+            attempts: 2,
+            problems: vec!["subject too long".to_string(), "unknown type".to_string()],
+            message: "stuff: did things".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "generated message failed validation after 2 attempts:\nsubject too long\nunknown type\n\ninvalid output:\n---\nstuff: did things\n---"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+// src/exit.rs
+// the exit statuses scripts can tell apart, and how an error maps to one
+
+use crate::error::{GitChangesError, ProviderFailure};
+use serde::Serialize;
+use std::fmt;
+use std::process::ExitCode;
+
+pub const ERROR: i32 = 1;
+pub const NO_CHANGES: i32 = 2;
+pub const ALL_EXCLUDED: i32 = 3;
+pub const PROVIDER: i32 = 4;
+pub const USAGE: i32 = 5;
+pub const TOO_LARGE: i32 = 6;
+pub const EMPTY_RESPONSE: i32 = 7;
+// 128 + SIGINT, what a shell reports for a command killed by Ctrl-C
+pub const INTERRUPTED: i32 = 130;
+
+// listed at the end of --help and in the man page
+pub const STATUSES: [(i32, &str); 9] = [
+    (
+        0,
+        "success (a message was generated, or the subcommand did its job)",
+    ),
+    (
+        ERROR,
+        "any other error, or lint and review finding what they check for",
+    ),
+    (NO_CHANGES, "no changes to describe"),
+    (
+        ALL_EXCLUDED,
+        "there were changes, but all of them are excluded",
+    ),
+    (
+        PROVIDER,
+        "the model provider isn't set up or failed (no API key, network, API error or an unusable response)",
+    ),
+    (USAGE, "invalid usage"),
+    (
+        TOO_LARGE,
+        "the diff is over the size limits and nobody confirmed sending it",
+    ),
+    (
+        EMPTY_RESPONSE,
+        "the model returned no message, even when asked again",
+    ),
+    (INTERRUPTED, "interrupted with Ctrl-C"),
+];
+
+// an error that ends the run with its status and nothing printed: what went wrong was already
+// reported (a --json error object, lint's list), or there's nothing to say (stdout was closed).
+// Returned instead of exiting on the spot, so what the run holds is dropped on the way out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Status(pub i32);
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exit status {}", self.0)
+    }
+}
+
+impl std::error::Error for Status {}
+
+// the process's exit status; main is the only place the process ends
+pub fn exit_code(code: i32) -> ExitCode {
+    ExitCode::from(u8::try_from(code).unwrap_or(ERROR as u8))
+}
+
+pub fn help() -> String {
+    let mut help = String::from("Exit status:");
+    for (code, meaning) in STATUSES {
+        help.push_str(&format!("\n  {}  {}", code, meaning));
+    }
+    help
+}
+
+// what went wrong, as the `kind` of a --json error object; each maps to one exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    NoChanges,
+    AllExcluded,
+    MissingKey,
+    InvalidConfig,
+    NotARepository,
+    // any other failure of git itself
+    GitError,
+    // the request never got an answer: DNS, connection, TLS or timeout
+    Network,
+    RateLimited,
+    // any other error status from the provider
+    ApiError,
+    // an answer without a usable message in it
+    BadResponse,
+    // an answer with no text in it, twice
+    EmptyResponse,
+    // a missing argument, flags that don't go together, or a question without a terminal
+    Usage,
+    TooLarge,
+    Interrupted,
+    Error,
+}
+
+impl Kind {
+    pub fn code(self) -> i32 {
+        match self {
+            Kind::NoChanges => NO_CHANGES,
+            Kind::AllExcluded => ALL_EXCLUDED,
+            // a missing key is the provider not being set up, which a retry won't fix either
+            Kind::MissingKey
+            | Kind::Network
+            | Kind::RateLimited
+            | Kind::ApiError
+            | Kind::BadResponse => PROVIDER,
+            Kind::Usage => USAGE,
+            Kind::TooLarge => TOO_LARGE,
+            Kind::EmptyResponse => EMPTY_RESPONSE,
+            Kind::Interrupted => INTERRUPTED,
+            Kind::InvalidConfig | Kind::NotARepository | Kind::GitError | Kind::Error => ERROR,
+        }
+    }
+}
+
+// what the --json error object and the exit status are read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Class {
+    pub kind: Kind,
+    // set for provider failures and a missing key
+    pub provider: Option<&'static str>,
+    // how long a rate-limited provider asked to wait, when it said
+    pub retry_after_secs: Option<u64>,
+}
+
+impl Class {
+    pub fn new(kind: Kind) -> Self {
+        Class {
+            kind,
+            provider: None,
+            retry_after_secs: None,
+        }
+    }
+
+    pub fn code(&self) -> i32 {
+        self.kind.code()
+    }
+}
+
+pub fn class(error: &GitChangesError) -> Class {
+    let kind = match error {
+        GitChangesError::Config { .. } => Kind::InvalidConfig,
+        GitChangesError::MissingKey { .. } => Kind::MissingKey,
+        GitChangesError::NotARepository { .. } => Kind::NotARepository,
+        GitChangesError::Git { .. } => Kind::GitError,
+        GitChangesError::Provider { failure, .. } => match failure {
+            ProviderFailure::Network => Kind::Network,
+            ProviderFailure::RateLimited => Kind::RateLimited,
+            ProviderFailure::Api => Kind::ApiError,
+            ProviderFailure::BadResponse => Kind::BadResponse,
+            ProviderFailure::Empty => Kind::EmptyResponse,
+        },
+        GitChangesError::Usage { .. } => Kind::Usage,
+        GitChangesError::TooLarge { .. } => Kind::TooLarge,
+        GitChangesError::NoChanges { .. } => Kind::NoChanges,
+        GitChangesError::AllExcluded { .. } => Kind::AllExcluded,
+        GitChangesError::Validation { .. } => Kind::Error,
+        GitChangesError::Interrupted => Kind::Interrupted,
+        GitChangesError::Other(error) => return classify(error),
+    };
+    Class {
+        kind,
+        provider: error.provider(),
+        retry_after_secs: error.retry_after_secs(),
+    }
+}
+
+// the GitChangesError an error carries, looking through the context added on the way up; a
+// request that failed before any answer counts as a network failure
+pub fn classify(error: &anyhow::Error) -> Class {
+    if let Some(error) = error.downcast_ref::<GitChangesError>() {
+        return class(error);
+    }
+    #[cfg(any(feature = "gemini", feature = "github", feature = "gitlab"))]
+    if error.downcast_ref::<reqwest::Error>().is_some() {
+        return Class::new(Kind::Network);
+    }
+    Class::new(Kind::Error)
+}
+
+// the status for an error
+pub fn code(error: &anyhow::Error) -> i32 {
+    match error.downcast_ref::<Status>() {
+        Some(Status(code)) => *code,
+        None => classify(error).code(),
+    }
+}
+
+// the `code` of a --json error object
+pub fn label(code: i32) -> &'static str {
+    match code {
+        NO_CHANGES => "no_changes",
+        ALL_EXCLUDED => "all_excluded",
+        PROVIDER => "provider_error",
+        USAGE => "usage",
+        TOO_LARGE => "too_large",
+        EMPTY_RESPONSE => "empty_response",
+        INTERRUPTED => "interrupted",
+        _ => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    fn provider(failure: ProviderFailure) -> GitChangesError {
+        GitChangesError::Provider {
+            provider: "gemini",
+            failure,
+            status: None,
+            retry_after_secs: Some(30),
+            message: "it failed".to_string(),
+            source: None,
+        }
+    }
+
+    #[test]
+    fn each_variant_has_its_kind_and_status() {
+        let message = || "it happened".to_string();
+        let cases = [
+            (GitChangesError::config("bad"), Kind::InvalidConfig, ERROR),
+            (
+                GitChangesError::MissingKey {
+                    provider: "gemini",
+                    variable: "GEMINI_API_KEY",
+                },
+                Kind::MissingKey,
+                PROVIDER,
+            ),
+            (
+                GitChangesError::Git {
+                    operation: "read the index".to_string(),
+                    source: git2::Error::from_str("broken"),
+                },
+                Kind::GitError,
+                ERROR,
+            ),
+            (provider(ProviderFailure::Network), Kind::Network, PROVIDER),
+            (
+                provider(ProviderFailure::RateLimited),
+                Kind::RateLimited,
+                PROVIDER,
+            ),
+            (provider(ProviderFailure::Api), Kind::ApiError, PROVIDER),
+            (
+                provider(ProviderFailure::BadResponse),
+                Kind::BadResponse,
+                PROVIDER,
+            ),
+            (
+                provider(ProviderFailure::Empty),
+                Kind::EmptyResponse,
+                EMPTY_RESPONSE,
+            ),
+            (
+                GitChangesError::usage("needs a terminal"),
+                Kind::Usage,
+                USAGE,
+            ),
+            (
+                GitChangesError::TooLarge { message: message() },
+                Kind::TooLarge,
+                TOO_LARGE,
+            ),
+            (
+                GitChangesError::NoChanges { message: message() },
+                Kind::NoChanges,
+                NO_CHANGES,
+            ),
+            (
+                GitChangesError::AllExcluded { message: message() },
+                Kind::AllExcluded,
+                ALL_EXCLUDED,
+            ),
+            (
+                GitChangesError::Validation {
+                    attempts: 3,
+                    problems: vec!["subject too long".to_string()],
+                    message: message(),
+                },
+                Kind::Error,
+                ERROR,
+            ),
+            (GitChangesError::Interrupted, Kind::Interrupted, INTERRUPTED),
+        ];
+        for (error, kind, code) in cases {
+            let class = class(&error);
+            assert_eq!(class.kind, kind, "{:?}", error);
+            assert_eq!(class.code(), code, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn a_provider_failure_keeps_the_provider_and_the_wait() {
+        let class = class(&provider(ProviderFailure::RateLimited));
+        assert_eq!(class.provider, Some("gemini"));
+        assert_eq!(class.retry_after_secs, Some(30));
+        assert_eq!(label(class.code()), "provider_error");
+    }
+
+    #[test]
+    fn the_class_is_found_under_context_and_inside_other() {
+        let error = Err::<(), _>(GitChangesError::NoChanges {
+            message: "no staged changes".to_string(),
+        })
+        .context("failed to describe the changes")
+        .unwrap_err();
+        assert_eq!(classify(&error).kind, Kind::NoChanges);
+        assert_eq!(code(&error), NO_CHANGES);
+
+        let wrapped = GitChangesError::Other(anyhow::Error::new(GitChangesError::Interrupted));
+        assert_eq!(class(&wrapped).kind, Kind::Interrupted);
+        assert_eq!(classify(&anyhow::anyhow!("plain")).kind, Kind::Error);
+    }
+
+    #[test]
+    fn a_status_is_its_own_code() {
+        let error = anyhow::Error::new(Status(USAGE)).context("already reported");
+        assert_eq!(code(&error), USAGE);
+        assert_eq!(label(code(&error)), "usage");
+        assert_eq!(exit_code(INTERRUPTED), ExitCode::from(130));
+        assert_eq!(exit_code(-1), ExitCode::from(ERROR as u8));
+    }
+
+    #[test]
+    fn every_status_is_listed_in_the_help() {
+        let help = help();
+        for (code, meaning) in STATUSES {
+            assert!(help.contains(&format!("  {}  {}", code, meaning)));
+        }
+    }
+}
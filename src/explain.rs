@@ -0,0 +1,21 @@
+// src/explain.rs
+// explain: a plain-language account of a diff for someone reviewing it
+
+use crate::attach;
+
+// a review of a whole branch can be long; the middle goes first, like attachments
+const MAX_EXPLAIN_DIFF_BYTES: usize = 64 * 1024;
+
+pub const EXPLAIN_MAX_OUTPUT_TOKENS: u32 = 1536;
+
+pub const EXPLAIN_SYSTEM_PROMPT: &str = "You explain code changes to a developer who is about to review them. Write a few short paragraphs of plain prose in markdown, not a commit message: first what the change is for and what it does overall, then the notable implementation details and design choices, then the risks (behavior changes, edge cases, compatibility, performance), and finally anything that looks suspicious or unfinished. Refer to files and functions by name where it helps. Say so plainly when a part of the diff is unclear, and do not invent context the diff doesn't show.";
+
+pub fn explain_prompt(diff: &str, files: &[String], source: &str) -> String {
+    let listed: Vec<String> = files.iter().map(|f| format!("- {}", f)).collect();
+    format!(
+        "Explain these changes ({}).\n\nChanged files:\n{}\n\n```diff\n{}\n```",
+        source,
+        listed.join("\n"),
+        attach::truncate_middle(diff, MAX_EXPLAIN_DIFF_BYTES)
+    )
+}
@@ -0,0 +1,132 @@
+// src/filter.rs
+//! Exclude patterns: which changed files are left out of the diff. (Which changed lines are
+//! left out, the `--ignore-lines` rules, is decided in [`crate::diff`].)
+//!
+//! Patterns are globs matched against repository paths with `/` separators. A `\` in a path or
+//! a pattern counts as a `/`, so patterns typed on Windows work everywhere, a `./` segment is
+//! dropped (`./target/**` is `target/**`), and matching is case-sensitive like git's own
+//! pathspecs.
+
+use crate::error::GitChangesError;
+use git2::DiffDelta;
+use glob::Pattern;
+use regex::Regex;
+use std::borrow::Cow;
+use std::path::Path;
+use tracing::warn;
+
+/// Whether `path` matches any of `patterns`; an invalid pattern matches nothing and is logged.
+///
+/// ```
+/// use git_changes_rs::filter::is_excluded_path;
+///
+/// let patterns = vec!["*.log".to_string(), "target/**".to_string()];
+/// assert!(is_excluded_path("build.log", &patterns));
+/// assert!(is_excluded_path("target\\debug\\app", &patterns));
+/// assert!(!is_excluded_path("src/main.rs", &patterns));
+/// ```
+pub fn is_excluded_path(path: &str, patterns: &[String]) -> bool {
+    let path = normalize(path);
+    patterns
+        .iter()
+        .any(|pattern_str| match Pattern::new(&normalize(pattern_str)) {
+            Ok(pattern) => pattern.matches(&path),
+            Err(e) => {
+                warn!("invalid exclude pattern '{}': {}", pattern_str, e);
+                false
+            }
+        })
+}
+
+// `/` separators and no `.` segments, in paths and patterns alike
+fn normalize(text: &str) -> String {
+    let text = text.replace('\\', "/");
+    if !text.split('/').any(|segment| segment == ".") {
+        return text;
+    }
+    text.split('/')
+        .filter(|segment| *segment != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether the file at `path` matches any of `patterns`, like [`is_excluded_path`] but for a
+/// name that needn't be UTF-8. Its bytes are matched as they are: `*` and `?` match a byte
+/// that isn't part of a UTF-8 character, and nothing else does, so two names that differ only
+/// in such bytes are told apart.
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use git_changes_rs::filter::is_excluded_file;
+/// use std::ffi::OsStr;
+/// use std::os::unix::ffi::OsStrExt;
+/// use std::path::Path;
+///
+/// let name = Path::new(OsStr::from_bytes(b"caf\xe9.log"));
+/// assert!(is_excluded_file(name, &["*.log".to_string()]));
+/// assert!(!is_excluded_file(name, &["caf\u{fffd}.log".to_string()]));
+/// # }
+/// ```
+pub fn is_excluded_file(path: &Path, patterns: &[String]) -> bool {
+    is_excluded_path(&path_text(path), patterns)
+}
+
+// the path as text without losing anything: to_string_lossy would turn every stray byte into
+// U+FFFD, so different names would look the same and a pattern with U+FFFD in it would match
+// them. Each such byte becomes a private-use character of its own instead, which only `*`,
+// `?` and a range spanning it can match
+#[cfg(unix)]
+fn path_text(path: &Path) -> Cow<'_, str> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let bytes = path.as_os_str().as_bytes();
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Cow::Borrowed(text);
+    }
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        text.extend(chunk.invalid().iter().map(|&byte| stray_byte(byte)));
+    }
+    Cow::Owned(text)
+}
+
+// no stray bytes outside unix: git stores paths as UTF-8 there
+#[cfg(not(unix))]
+fn path_text(path: &Path) -> Cow<'_, str> {
+    path.to_string_lossy()
+}
+
+#[cfg(unix)]
+fn stray_byte(byte: u8) -> char {
+    char::from_u32(STRAY_BYTES + u32::from(byte)).expect("a private-use character")
+}
+
+// the start of 256 characters in plane 16's private use area
+#[cfg(unix)]
+const STRAY_BYTES: u32 = 0x10_FE00;
+
+// a delta is excluded when either side of it is, so a rename out of an excluded directory is too
+pub(crate) fn is_excluded(delta: &DiffDelta, excludes: &[String]) -> bool {
+    let check_path =
+        |path: Option<&Path>| path.is_some_and(|path| is_excluded_file(path, excludes));
+    check_path(delta.old_file().path()) || check_path(delta.new_file().path())
+}
+
+// the --ignore-lines rules, compiled before anything else runs so a bad one stops it at once
+pub(crate) fn line_rules<I, S>(patterns: I) -> Result<Vec<Regex>, GitChangesError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    patterns
+        .into_iter()
+        .map(|pattern| {
+            let pattern = pattern.as_ref();
+            Regex::new(pattern).map_err(|e| GitChangesError::Config {
+                message: format!("invalid --ignore-lines pattern '{}'", pattern),
+                source: Some(Box::new(e)),
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,212 @@
+// src/fixup.rs
+// deciding whether pending changes look like a fixup of a recent commit
+
+use crate::history;
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+
+// how many commits back --fixup-detect looks
+pub const DEFAULT_FIXUP_DEPTH: usize = 10;
+// each step back in history multiplies a commit's score by this
+const RECENCY_DECAY: f64 = 0.8;
+// minimum score to suggest a fixup; full overlap qualifies up to three commits back
+const FIXUP_THRESHOLD: f64 = 0.5;
+
+pub struct CommitFiles {
+    pub oid: Oid,
+    pub subject: String,
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixupTarget {
+    pub oid: Oid,
+    pub subject: String,
+    pub score: f64,
+}
+
+impl FixupTarget {
+    pub fn message(&self) -> String {
+        format!("fixup! {}", self.subject)
+    }
+}
+
+fn subject_of(repo: &Repository, oid: Oid) -> Result<String> {
+    let commit = repo
+        .find_commit(oid)
+        .with_context(|| format!("failed to look up commit {}", oid))?;
+    Ok(commit
+        .message()
+        .unwrap_or_default()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string())
+}
+
+// the last `depth` non-merge commits on the first-parent line, with the files each touched
+pub fn recent_history(repo: &Repository, depth: usize) -> Result<Vec<CommitFiles>> {
+    let mut history = Vec::new();
+    for oid in history::recent_commits(repo, depth)? {
+        let commit = repo
+            .find_commit(oid)
+            .with_context(|| format!("failed to look up commit {}", oid))?;
+        // merges don't make sense as fixup targets, but keep their slot in the recency order
+        let files = if commit.parent_count() > 1 {
+            Vec::new()
+        } else {
+            history::changed_paths(&history::diff_to_parent(repo, oid)?)
+        };
+        history.push(CommitFiles {
+            oid,
+            subject: subject_of(repo, oid)?,
+            files,
+        });
+    }
+    Ok(history)
+}
+
+// share of the changed files that `commit` also touched, discounted by how far back it is.
+// `history` is newest first.
+pub fn score(changed: &[String], history: &[CommitFiles]) -> Vec<FixupTarget> {
+    if changed.is_empty() {
+        return Vec::new();
+    }
+    history
+        .iter()
+        .enumerate()
+        .filter_map(|(age, commit)| {
+            let overlap = changed.iter().filter(|f| commit.files.contains(f)).count();
+            (overlap > 0).then(|| FixupTarget {
+                oid: commit.oid,
+                subject: commit.subject.clone(),
+                score: overlap as f64 / changed.len() as f64 * RECENCY_DECAY.powi(age as i32),
+            })
+        })
+        .collect()
+}
+
+// the best-scoring commit, if it clears the threshold; ties go to the newer commit
+pub fn best_target(changed: &[String], history: &[CommitFiles]) -> Option<FixupTarget> {
+    score(changed, history)
+        .into_iter()
+        .filter(|target| target.score >= FIXUP_THRESHOLD)
+        .fold(None, |best: Option<FixupTarget>, target| match best {
+            Some(best) if best.score >= target.score => Some(best),
+            _ => Some(target),
+        })
+}
+
+// an explicitly requested target, from --fixup <rev>
+pub fn target_for_rev(repo: &Repository, rev: &str) -> Result<FixupTarget> {
+    let oid = history::resolve(repo, rev)?;
+    Ok(FixupTarget {
+        oid,
+        subject: subject_of(repo, oid)?,
+        score: 1.0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn files(files: &[&str]) -> Vec<String> {
+        files.iter().map(|file| file.to_string()).collect()
+    }
+
+    // a newest-first history of commits touching `touched`, with made-up ids
+    fn history(touched: &[&[&str]]) -> Vec<CommitFiles> {
+        touched
+            .iter()
+            .enumerate()
+            .map(|(age, paths)| CommitFiles {
+                oid: Oid::from_bytes(&[age as u8 + 1; 20]).unwrap(),
+                subject: format!("commit {}", age),
+                files: files(paths),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_score_is_the_overlap_discounted_by_age() {
+        let history = history(&[&["a.rs"], &[], &["a.rs", "b.rs"]]);
+        let scores = score(&files(&["a.rs", "b.rs"]), &history);
+        let scores: Vec<(&str, f64)> = scores
+            .iter()
+            .map(|target| (target.subject.as_str(), target.score))
+            .collect();
+        assert_eq!(scores, [("commit 0", 0.5), ("commit 2", 0.8 * 0.8)]);
+        assert!(score(&[], &history).is_empty());
+    }
+
+    #[test]
+    fn full_overlap_is_a_target_up_to_three_commits_back() {
+        let changed = files(&["a.rs"]);
+        for age in 0..4 {
+            let mut touched: Vec<&[&str]> = vec![&["other.rs"]; age];
+            touched.push(&["a.rs"]);
+            let target = best_target(&changed, &history(&touched)).unwrap();
+            assert_eq!(target.message(), format!("fixup! commit {}", age));
+        }
+        let five_back: Vec<&[&str]> = vec![&[], &[], &[], &[], &["a.rs"]];
+        assert_eq!(best_target(&changed, &history(&five_back)), None);
+    }
+
+    #[test]
+    fn a_full_overlap_further_back_beats_a_newer_partial_one() {
+        let changed = files(&["a.rs", "b.rs"]);
+        let target = best_target(&changed, &history(&[&["a.rs"], &["a.rs", "b.rs"]]));
+        assert_eq!(target.unwrap().subject, "commit 1");
+    }
+
+    #[test]
+    fn a_partial_overlap_below_the_threshold_isnt_a_target() {
+        let changed = files(&["a.rs", "b.rs", "c.rs"]);
+        assert_eq!(best_target(&changed, &history(&[&["a.rs"]])), None);
+    }
+
+    proptest! {
+        #[test]
+        fn scores_stay_between_zero_and_one_and_fall_with_age(
+            changed in prop::collection::btree_set("[a-e]\\.rs", 1..5),
+            touched in prop::collection::vec(prop::collection::vec("[a-e]\\.rs", 0..5), 0..12),
+        ) {
+            let changed: Vec<String> = changed.into_iter().collect();
+            let history: Vec<CommitFiles> = touched
+                .iter()
+                .enumerate()
+                .map(|(age, files)| CommitFiles {
+                    oid: Oid::from_bytes(&[age as u8 + 1; 20]).unwrap(),
+                    subject: format!("commit {}", age),
+                    files: files.clone(),
+                })
+                .collect();
+            let scores = score(&changed, &history);
+            for target in &scores {
+                prop_assert!(target.score > 0.0 && target.score <= 1.0);
+            }
+            // moving the whole history one commit further back never raises a score
+            let mut older = vec![CommitFiles {
+                oid: Oid::from_bytes(&[0xff; 20]).unwrap(),
+                subject: "unrelated".to_string(),
+                files: Vec::new(),
+            }];
+            older.extend(history.iter().map(|commit| CommitFiles {
+                oid: commit.oid,
+                subject: commit.subject.clone(),
+                files: commit.files.clone(),
+            }));
+            let older_scores = score(&changed, &older);
+            prop_assert_eq!(scores.len(), older_scores.len());
+            for (now, later) in scores.iter().zip(&older_scores) {
+                prop_assert!(later.score < now.score);
+            }
+            if let Some(best) = best_target(&changed, &history) {
+                prop_assert!(best.score >= FIXUP_THRESHOLD);
+                prop_assert!(scores.iter().all(|target| target.score <= best.score));
+            }
+        }
+    }
+}
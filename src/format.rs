@@ -0,0 +1,442 @@
+// src/format.rs
+// local post-processing of generated messages: subject cap, body wrapping, whitespace cleanup
+
+use crate::conventional::ConventionalMessage;
+
+pub const DEFAULT_MAX_TITLE_LENGTH: usize = 72;
+pub const DEFAULT_WARN_TITLE_LENGTH: usize = 50;
+pub const DEFAULT_BODY_WIDTH: usize = 72;
+
+// non-english subjects tend to run longer, so their defaults get some slack
+const LANGUAGE_TITLE_SLACK: usize = 10;
+
+/// How a generated message is reformatted.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct FormatOptions {
+    /// The longest subject line, in characters; what's cut moves into the body.
+    pub max_title_length: usize,
+    /// Subjects longer than this get a warning.
+    pub warn_title_length: usize,
+    /// The width body paragraphs are wrapped to.
+    pub body_width: usize,
+}
+
+impl FormatOptions {
+    /// The defaults for messages in `language` (longer subjects outside English), with the
+    /// limits that are given taking precedence.
+    pub fn for_language(
+        language: Option<&str>,
+        max_title_length: Option<usize>,
+        warn_title_length: Option<usize>,
+    ) -> Self {
+        let slack = match language {
+            Some(lang) if !crate::prompt::is_default_language(lang) => LANGUAGE_TITLE_SLACK,
+            _ => 0,
+        };
+        FormatOptions {
+            max_title_length: max_title_length.unwrap_or(DEFAULT_MAX_TITLE_LENGTH + slack),
+            warn_title_length: warn_title_length.unwrap_or(DEFAULT_WARN_TITLE_LENGTH + slack),
+            body_width: DEFAULT_BODY_WIDTH,
+        }
+    }
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions::for_language(None, None, None)
+    }
+}
+
+// length in characters, which is what people count when they read `git log --oneline`
+pub fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+// caps the subject, moving what's cut into the body, and wraps each body paragraph; the
+// trailers are left as they are
+pub fn format_message(message: &str, options: &FormatOptions) -> String {
+    let mut parsed = ConventionalMessage::parse(message);
+    if parsed.subject.is_empty() && parsed.prefix.is_none() {
+        return String::new();
+    }
+    let (title, overflow) = split_subject(&parsed.title(), options.max_title_length);
+    parsed.set_title(&title);
+    if let Some(rest) = overflow {
+        parsed.body.insert(0, rest);
+    }
+
+    parsed.body = parsed
+        .body
+        .iter()
+        .map(|paragraph| {
+            let lines: Vec<String> = paragraph.lines().map(str::to_string).collect();
+            wrap_body(&lines, options.body_width)
+        })
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect();
+    parsed.to_string()
+}
+
+// reduces a response to its first non-empty line; the flag reports whether anything was dropped
+pub fn first_line(message: &str) -> (String, bool) {
+    let mut lines = message.lines().map(str::trim).filter(|l| !l.is_empty());
+    let first = lines.next().unwrap_or_default().to_string();
+    (first, lines.next().is_some())
+}
+
+// cuts the subject at a word boundary within the limit, returning the remainder for the body
+fn split_subject(subject: &str, max_len: usize) -> (String, Option<String>) {
+    if max_len == 0 || char_len(subject) <= max_len {
+        return (subject.to_string(), None);
+    }
+
+    let cut = byte_index_of_char(subject, max_len);
+    let head = &subject[..cut];
+    let split_at = match head.rfind(char::is_whitespace) {
+        Some(idx) if idx > 0 => idx,
+        _ => cut,
+    };
+
+    let title = subject[..split_at].trim_end().to_string();
+    let rest = subject[split_at..].trim().to_string();
+    if rest.is_empty() {
+        (title, None)
+    } else {
+        (title, Some(rest))
+    }
+}
+
+fn byte_index_of_char(text: &str, char_pos: usize) -> usize {
+    text.char_indices()
+        .nth(char_pos)
+        .map(|(idx, _)| idx)
+        .unwrap_or(text.len())
+}
+
+#[derive(Debug)]
+enum Block {
+    Blank,
+    // kept exactly as written: code fences, indented code, trailers
+    Verbatim(String),
+    Paragraph(String),
+    Bullet {
+        indent: String,
+        marker: String,
+        text: String,
+    },
+}
+
+fn wrap_body(lines: &[String], width: usize) -> String {
+    let blocks = parse_blocks(lines);
+
+    let mut out: Vec<String> = Vec::new();
+    for block in blocks {
+        match block {
+            Block::Blank => {
+                if out.last().is_some_and(|l| !l.is_empty()) {
+                    out.push(String::new());
+                }
+            }
+            Block::Verbatim(line) => out.push(line),
+            Block::Paragraph(text) => out.extend(wrap_words(&text, "", "", width)),
+            Block::Bullet {
+                indent,
+                marker,
+                text,
+            } => {
+                let first = format!("{}{} ", indent, marker);
+                let rest = " ".repeat(char_len(&first));
+                out.extend(wrap_words(&text, &first, &rest, width));
+            }
+        }
+    }
+
+    while out.last().is_some_and(|l| l.is_empty()) {
+        out.pop();
+    }
+    while out.first().is_some_and(|l| l.is_empty()) {
+        out.remove(0);
+    }
+    out.join("\n")
+}
+
+fn parse_blocks(lines: &[String]) -> Vec<Block> {
+    let trailer_start = trailer_block_start(lines);
+
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut in_fence = false;
+
+    for (idx, line) in lines.iter().enumerate() {
+        let line = line.trim_end();
+
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            blocks.push(Block::Verbatim(line.to_string()));
+            continue;
+        }
+        if in_fence || trailer_start.is_some_and(|start| idx >= start) {
+            if line.is_empty() {
+                blocks.push(Block::Blank);
+            } else {
+                blocks.push(Block::Verbatim(line.to_string()));
+            }
+            continue;
+        }
+        if line.is_empty() {
+            blocks.push(Block::Blank);
+            continue;
+        }
+
+        if let Some((indent, marker, text)) = split_bullet(line) {
+            blocks.push(Block::Bullet {
+                indent: indent.to_string(),
+                marker: marker.to_string(),
+                text: text.to_string(),
+            });
+            continue;
+        }
+
+        let leading = line.len() - line.trim_start().len();
+        match blocks.last_mut() {
+            // an indented line directly under a bullet continues that bullet
+            Some(Block::Bullet { text, .. }) if leading > 0 => {
+                text.push(' ');
+                text.push_str(line.trim());
+            }
+            Some(Block::Paragraph(text)) => {
+                text.push(' ');
+                text.push_str(line.trim());
+            }
+            _ if leading >= 4 => blocks.push(Block::Verbatim(line.to_string())),
+            _ => blocks.push(Block::Paragraph(line.trim().to_string())),
+        }
+    }
+
+    blocks
+}
+
+// recognises "- item", "* item", "+ item" and "1. item" / "1) item" with any indentation
+fn split_bullet(line: &str) -> Option<(&str, &str, &str)> {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    let marker_len = if trimmed.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 || !trimmed[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+
+    let after = &trimmed[marker_len..];
+    if !after.starts_with(' ') {
+        return None;
+    }
+    let text = after.trim();
+    if text.is_empty() {
+        return None;
+    }
+    Some((indent, &trimmed[..marker_len], text))
+}
+
+// a trailing paragraph made only of "Key: value" lines (Signed-off-by, Refs, BREAKING CHANGE)
+fn trailer_block_start(lines: &[String]) -> Option<usize> {
+    let end = lines.iter().rposition(|l| !l.trim().is_empty())?;
+    let start = lines[..=end]
+        .iter()
+        .rposition(|l| l.trim().is_empty())
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+
+    // a lone paragraph is the body itself, never a trailer block
+    if start == 0 {
+        return None;
+    }
+    lines[start..=end]
+        .iter()
+        .all(|l| is_trailer_line(l))
+        .then_some(start)
+}
+
+pub fn is_trailer_line(line: &str) -> bool {
+    let line = line.trim();
+    if line.starts_with("BREAKING CHANGE: ") || line.starts_with("BREAKING-CHANGE: ") {
+        return true;
+    }
+    match line.split_once(": ") {
+        Some((key, value)) => {
+            !key.is_empty()
+                && !value.trim().is_empty()
+                && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                && key.chars().next().is_some_and(|c| c.is_ascii_alphabetic())
+        }
+        None => false,
+    }
+}
+
+// greedy word wrap; tokens longer than the line (urls, paths) are left intact on their own line
+fn wrap_words(text: &str, first_prefix: &str, rest_prefix: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut current = first_prefix.to_string();
+    let mut current_len = char_len(first_prefix);
+    let mut has_word = false;
+
+    for word in text.split_whitespace() {
+        let word_len = char_len(word);
+        if has_word && current_len + 1 + word_len > width {
+            lines.push(current);
+            current = rest_prefix.to_string();
+            current_len = char_len(rest_prefix);
+            has_word = false;
+        }
+        if has_word {
+            current.push(' ');
+            current_len += 1;
+        }
+        current.push_str(word);
+        current_len += word_len;
+        has_word = true;
+    }
+
+    if has_word {
+        lines.push(current);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(text: &str, width: usize) -> String {
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        wrap_body(&lines, width)
+    }
+
+    #[test]
+    fn subjects_are_cut_at_a_word_boundary_counting_characters() {
+        let cases: &[(&str, usize, &str, Option<&str>)] = &[
+            ("fix: short", 72, "fix: short", None),
+            ("fix: handle the case", 12, "fix: handle", Some("the case")),
+            // 12 characters, but 16 bytes: nothing to cut
+            ("fix: café ünï", 13, "fix: café ünï", None),
+            (
+                "feat: résumé des étapes",
+                18,
+                "feat: résumé des",
+                Some("étapes"),
+            ),
+            ("fix: naïve  spacing", 11, "fix: naïve", Some("spacing")),
+            // no whitespace in reach: the cut falls on a character, never inside one
+            (
+                "修复：处理空的配置文件时的崩溃问题",
+                8,
+                "修复：处理空的配",
+                Some("置文件时的崩溃问题"),
+            ),
+            (
+                "feat: 添加 配置文件 支持",
+                12,
+                "feat: 添加",
+                Some("配置文件 支持"),
+            ),
+            ("anything", 0, "anything", None),
+        ];
+        for &(subject, max, title, rest) in cases {
+            let (got_title, got_rest) = split_subject(subject, max);
+            assert_eq!(
+                (got_title.as_str(), got_rest.as_deref()),
+                (title, rest),
+                "{:?} at {}",
+                subject,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn other_languages_get_longer_subjects() {
+        let cases: &[(Option<&str>, usize, usize)] = &[
+            (None, 72, 50),
+            (Some("en"), 72, 50),
+            (Some("English"), 72, 50),
+            (Some("en-GB"), 72, 50),
+            (Some(" "), 72, 50),
+            (Some("pt-BR"), 82, 60),
+            (Some("ja"), 82, 60),
+            (Some("german"), 82, 60),
+        ];
+        for &(language, max, warn) in cases {
+            let options = FormatOptions::for_language(language, None, None);
+            assert_eq!(
+                (options.max_title_length, options.warn_title_length),
+                (max, warn),
+                "{:?}",
+                language
+            );
+        }
+
+        let given = FormatOptions::for_language(Some("ja"), Some(60), Some(40));
+        assert_eq!((given.max_title_length, given.warn_title_length), (60, 40));
+    }
+
+    #[test]
+    fn a_url_longer_than_the_line_stays_whole() {
+        let url = "https://example.com/a/very/long/path/that/goes/on/and/on/past/the/width?query=1";
+        let wrapped = body(&format!("See {} for the details.", url), 40);
+        assert_eq!(wrapped, format!("See\n{}\nfor the details.", url));
+
+        let wrapped = body(&format!("- {} explains it", url), 40);
+        assert_eq!(wrapped, format!("- {}\n  explains it", url));
+    }
+
+    #[test]
+    fn nested_bullets_keep_their_indentation() {
+        let text = "- the outer item, which is long enough that it has to wrap onto a second line\n  \
+                    - an inner item that also has to wrap because it is far too long for the width\n    \
+                    1. a numbered item, three levels deep, wrapped under its own text\n\
+                    - a second outer item";
+        assert_eq!(
+            body(text, 40),
+            "- the outer item, which is long enough\n  \
+             that it has to wrap onto a second line\n  \
+             - an inner item that also has to wrap\n    \
+             because it is far too long for the\n    \
+             width\n    \
+             1. a numbered item, three levels\n       \
+             deep, wrapped under its own text\n\
+             - a second outer item"
+        );
+    }
+
+    #[test]
+    fn code_and_trailers_are_left_as_written() {
+        let text = "A paragraph that wraps at the given width of twenty.\n\n\
+                    ```\nlet x = a_really_long_call(with, many, arguments, in_it);\n```\n\n    \
+                    indented code that is long but must stay on one line\n\n\
+                    Signed-off-by: Someone With A Long Name <someone@example.com>";
+        assert_eq!(
+            body(text, 20),
+            "A paragraph that\nwraps at the given\nwidth of twenty.\n\n\
+             ```\nlet x = a_really_long_call(with, many, arguments, in_it);\n```\n\n    \
+             indented code that is long but must stay on one line\n\n\
+             Signed-off-by: Someone With A Long Name <someone@example.com>"
+        );
+    }
+
+    #[test]
+    fn a_cut_subject_moves_into_the_body() {
+        let options = FormatOptions {
+            max_title_length: 20,
+            warn_title_length: 20,
+            body_width: 72,
+        };
+        assert_eq!(
+            format_message("fix(cli): read the flag from the config", &options),
+            "fix(cli): read the\n\nflag from the config"
+        );
+    }
+}
@@ -0,0 +1,559 @@
+// src/gemini.rs
+// the Gemini API on the wire: generateContent requests, reading their answers and errors, and
+// the model list; compiled in with the gemini feature
+
+use crate::error::{GitChangesError, ProviderFailure};
+use crate::interactivity;
+use crate::provider::{
+    create_http_client, generation_config, model_id, models_url, safety_settings, Gemini,
+    OUTPUT_TOKENS, PROMPT_TOKENS,
+};
+use crate::spinner::Spinner;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeSet;
+use std::env;
+use std::sync::atomic::Ordering;
+use tracing::{debug, field, info, instrument, warn, Span};
+
+// the answer to a generateContent request. Forward-compatible: everything is optional or
+// defaulted, a finish reason this client doesn't know is kept by name, and fields it doesn't
+// read land in `unknown`, which only the debug log looks at
+#[derive(Deserialize, Debug)]
+struct GeminiResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    #[serde(rename = "promptFeedback")]
+    prompt_feedback: Option<PromptFeedback>,
+    #[serde(rename = "usageMetadata")]
+    usage_metadata: Option<UsageMetadata>,
+    #[serde(flatten)]
+    unknown: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct UsageMetadata {
+    #[serde(rename = "promptTokenCount", default)]
+    prompt_token_count: u64,
+    #[serde(rename = "candidatesTokenCount", default)]
+    candidates_token_count: u64,
+    #[serde(flatten)]
+    unknown: Map<String, Value>,
+}
+
+// why the prompt itself was refused, when it was
+#[derive(Deserialize, Debug)]
+struct PromptFeedback {
+    #[serde(rename = "blockReason")]
+    block_reason: Option<String>,
+    #[serde(rename = "safetyRatings", default)]
+    safety_ratings: Vec<SafetyRating>,
+    #[serde(flatten)]
+    unknown: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Candidate {
+    content: Option<Content>,
+    #[serde(rename = "finishReason")]
+    finish_reason: Option<FinishReason>,
+    #[serde(rename = "safetyRatings", default)]
+    safety_ratings: Vec<SafetyRating>,
+    #[serde(flatten)]
+    unknown: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Content {
+    #[serde(default)]
+    parts: Vec<Part>,
+    #[serde(flatten)]
+    unknown: Map<String, Value>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Part {
+    text: Option<String>,
+    #[serde(flatten)]
+    unknown: Map<String, Value>,
+}
+
+// why the model stopped writing a candidate
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(from = "String")]
+pub(crate) enum FinishReason {
+    Stop,
+    MaxTokens,
+    Safety,
+    Recitation,
+    Blocklist,
+    ProhibitedContent,
+    Spii,
+    // one this client doesn't know yet, by the api's name for it
+    Other(String),
+}
+
+impl From<String> for FinishReason {
+    fn from(reason: String) -> Self {
+        match reason.as_str() {
+            "STOP" => FinishReason::Stop,
+            "MAX_TOKENS" => FinishReason::MaxTokens,
+            "SAFETY" => FinishReason::Safety,
+            "RECITATION" => FinishReason::Recitation,
+            "BLOCKLIST" => FinishReason::Blocklist,
+            "PROHIBITED_CONTENT" => FinishReason::ProhibitedContent,
+            "SPII" => FinishReason::Spii,
+            _ => FinishReason::Other(reason),
+        }
+    }
+}
+
+impl FinishReason {
+    // the api's name for it
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            FinishReason::Stop => "STOP",
+            FinishReason::MaxTokens => "MAX_TOKENS",
+            FinishReason::Safety => "SAFETY",
+            FinishReason::Recitation => "RECITATION",
+            FinishReason::Blocklist => "BLOCKLIST",
+            FinishReason::ProhibitedContent => "PROHIBITED_CONTENT",
+            FinishReason::Spii => "SPII",
+            FinishReason::Other(reason) => reason,
+        }
+    }
+
+    // whether the content filters stopped it
+    pub(crate) fn is_blocked(&self) -> bool {
+        matches!(
+            self,
+            FinishReason::Safety
+                | FinishReason::Recitation
+                | FinishReason::Blocklist
+                | FinishReason::ProhibitedContent
+                | FinishReason::Spii
+        )
+    }
+}
+
+// how likely a filter found an answer or prompt to be harmful in one category
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SafetyRating {
+    #[serde(default)]
+    pub(crate) category: String,
+    #[serde(default)]
+    pub(crate) probability: String,
+    #[serde(default)]
+    pub(crate) blocked: bool,
+    #[serde(flatten)]
+    unknown: Map<String, Value>,
+}
+
+// one candidate as the rest of the client reads it: its text (that of the first part, trimmed;
+// empty when there is none), why it ended, and how the filters rated it
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Generation {
+    pub(crate) text: String,
+    pub(crate) finish_reason: Option<FinishReason>,
+    pub(crate) safety_ratings: Vec<SafetyRating>,
+}
+
+impl From<Candidate> for Generation {
+    fn from(candidate: Candidate) -> Self {
+        let text = candidate
+            .content
+            .and_then(|content| content.parts.into_iter().next())
+            .and_then(|part| part.text)
+            .map(|text| text.trim().to_string())
+            .unwrap_or_default();
+        Generation {
+            text,
+            finish_reason: candidate.finish_reason,
+            safety_ratings: candidate.safety_ratings,
+        }
+    }
+}
+
+#[instrument(name = "request", skip_all, fields(tokens = field::Empty))]
+pub(crate) async fn send_to_gemini(
+    client: &Gemini,
+    system_prompt: &str,
+    user_prompt: &str,
+    max_output_tokens: u32,
+    candidate_count: u32,
+) -> Result<Vec<String>> {
+    let gemini_api_key = gemini_api_key()?;
+    let model_id = model_id();
+    let api_url = format!(
+        "{}/models/{}:generateContent?key={}",
+        client.base_url, model_id, gemini_api_key
+    );
+
+    let options = client
+        .options
+        .for_request(max_output_tokens, candidate_count);
+    let generation_config = generation_config(&options, model_id);
+    info!(
+        "request: model={} system={} bytes prompt={} bytes {}",
+        model_id,
+        system_prompt.len(),
+        user_prompt.len(),
+        generation_config
+    );
+    debug!(
+        "--- system prompt\n{}\n--- prompt\n{}\n---",
+        system_prompt, user_prompt
+    );
+    interactivity::approve_send("gemini", model_id, system_prompt, user_prompt)?;
+    let payload = json!({
+        "contents": [
+            {
+                "role": "user",
+                "parts": [
+                    {
+                        "text": user_prompt
+                    }
+                ]
+            }
+        ],
+        "systemInstruction": {
+            "parts": [
+                { "text": system_prompt }
+            ]
+        },
+        "generationConfig": generation_config,
+        "safetySettings": safety_settings(&options)
+    });
+    let spinner = Spinner::start("waiting for gemini");
+    let response = client
+        .http
+        .post(&api_url)
+        .header("content-type", "application/json")
+        .json(&payload)
+        .send()
+        .await
+        .map_err(|e| network_error("failed to send request to gemini api", e))?;
+
+    let status = response.status();
+    let retry_after = retry_after(&response);
+    let response_body_text = response
+        .text()
+        .await
+        .map_err(|e| network_error("failed to read response body", e))?;
+    drop(spinner);
+
+    if !status.is_success() {
+        return Err(gemini_error(status, retry_after, &response_body_text).into());
+    }
+
+    let gemini_response: GeminiResponse =
+        serde_json::from_str(&response_body_text).map_err(|e| {
+            gemini_failure(
+                ProviderFailure::BadResponse,
+                format!(
+                    "failed to parse json response: {}: {}",
+                    response_body_text, e
+                ),
+            )
+        })?;
+
+    if let Some(usage) = &gemini_response.usage_metadata {
+        info!(
+            "response: {} prompt tokens, {} output tokens",
+            usage.prompt_token_count, usage.candidates_token_count
+        );
+        Span::current().record(
+            "tokens",
+            usage.prompt_token_count + usage.candidates_token_count,
+        );
+        PROMPT_TOKENS.fetch_add(usage.prompt_token_count, Ordering::Relaxed);
+        OUTPUT_TOKENS.fetch_add(usage.candidates_token_count, Ordering::Relaxed);
+    }
+
+    let unknown = unknown_fields(&gemini_response);
+    if !unknown.is_empty() {
+        debug!(
+            "response: {} fields not read: {}",
+            unknown.len(),
+            unknown.join(", ")
+        );
+    }
+
+    let prompt_feedback = gemini_response.prompt_feedback;
+    let generations: Vec<Generation> = gemini_response
+        .candidates
+        .into_iter()
+        .map(Generation::from)
+        .collect();
+    for generation in &generations {
+        if generation.finish_reason == Some(FinishReason::MaxTokens) && !generation.text.is_empty()
+        {
+            warn!("gemini stopped at the output token limit; the message may be cut short");
+        }
+    }
+    let messages: Vec<String> = generations
+        .iter()
+        .map(|generation| generation.text.clone())
+        .filter(|text| !text.is_empty())
+        .collect();
+
+    if messages.is_empty() {
+        return Err(no_text(prompt_feedback.as_ref(), &generations).into());
+    }
+    Ok(messages)
+}
+
+// an answer without text: blocked by the filters, for the prompt or every candidate, is a bad
+// response; anything else is empty, and asked for again
+fn no_text(
+    prompt_feedback: Option<&PromptFeedback>,
+    generations: &[Generation],
+) -> GitChangesError {
+    let prompt_blocked = prompt_feedback.and_then(|feedback| {
+        let reason = feedback.block_reason.as_deref()?;
+        Some((reason, &feedback.safety_ratings))
+    });
+    let blocked = prompt_blocked.or_else(|| {
+        generations.iter().find_map(|generation| {
+            let reason = generation.finish_reason.as_ref()?;
+            reason
+                .is_blocked()
+                .then_some((reason.as_str(), &generation.safety_ratings))
+        })
+    });
+    if let Some((reason, ratings)) = blocked {
+        let categories: Vec<&str> = ratings
+            .iter()
+            .filter(|rating| rating.blocked)
+            .map(|rating| rating.category.as_str())
+            .collect();
+        let message = if categories.is_empty() {
+            format!("gemini blocked the answer ({})", reason)
+        } else {
+            format!(
+                "gemini blocked the answer ({}: {})",
+                reason,
+                categories.join(", ")
+            )
+        };
+        return gemini_failure(ProviderFailure::BadResponse, message);
+    }
+    let cut_short = generations
+        .iter()
+        .any(|generation| generation.finish_reason == Some(FinishReason::MaxTokens));
+    let message = if cut_short {
+        "gemini returned no text: it stopped at the output token limit before writing any"
+    } else {
+        "gemini returned no text: no candidates, or candidates without any parts"
+    };
+    gemini_failure(ProviderFailure::Empty, message)
+}
+
+// the fields of a response the types above don't read, as dotted paths, each once
+fn unknown_fields(response: &GeminiResponse) -> Vec<String> {
+    fn add(fields: &mut BTreeSet<String>, prefix: &str, unknown: &Map<String, Value>) {
+        fields.extend(unknown.keys().map(|key| format!("{}{}", prefix, key)));
+    }
+
+    let mut fields = BTreeSet::new();
+    add(&mut fields, "", &response.unknown);
+    if let Some(usage) = &response.usage_metadata {
+        add(&mut fields, "usageMetadata.", &usage.unknown);
+    }
+    if let Some(feedback) = &response.prompt_feedback {
+        add(&mut fields, "promptFeedback.", &feedback.unknown);
+        for rating in &feedback.safety_ratings {
+            add(
+                &mut fields,
+                "promptFeedback.safetyRatings.",
+                &rating.unknown,
+            );
+        }
+    }
+    for candidate in &response.candidates {
+        add(&mut fields, "candidates.", &candidate.unknown);
+        for rating in &candidate.safety_ratings {
+            add(&mut fields, "candidates.safetyRatings.", &rating.unknown);
+        }
+        if let Some(content) = &candidate.content {
+            add(&mut fields, "candidates.content.", &content.unknown);
+            for part in &content.parts {
+                add(&mut fields, "candidates.content.parts.", &part.unknown);
+            }
+        }
+    }
+    fields.into_iter().collect()
+}
+
+fn gemini_api_key() -> Result<String> {
+    env::var("GEMINI_API_KEY").context(GitChangesError::MissingKey {
+        provider: "gemini",
+        variable: "GEMINI_API_KEY",
+    })
+}
+
+fn gemini_failure(failure: ProviderFailure, message: impl Into<String>) -> GitChangesError {
+    GitChangesError::Provider {
+        provider: "gemini",
+        failure,
+        status: None,
+        retry_after_secs: None,
+        message: message.into(),
+        source: None,
+    }
+}
+
+// a request that got no (complete) answer
+fn network_error(message: &str, error: reqwest::Error) -> GitChangesError {
+    GitChangesError::Provider {
+        provider: "gemini",
+        failure: ProviderFailure::Network,
+        status: None,
+        retry_after_secs: None,
+        message: message.to_string(),
+        // the url carries the key as a query parameter
+        source: Some(Box::new(error.without_url())),
+    }
+}
+
+// the error envelope of a failed request: {"error": {"code", "message", "status", "details"}}
+#[derive(Deserialize, Debug)]
+struct GeminiErrorBody {
+    error: GeminiError,
+}
+
+#[derive(Deserialize, Debug)]
+struct GeminiError {
+    #[serde(default)]
+    message: String,
+    status: Option<String>,
+    #[serde(default)]
+    details: Vec<serde_json::Value>,
+}
+
+// Retry-After in seconds; the http-date form isn't used by the API
+fn retry_after(response: &reqwest::Response) -> Option<u64> {
+    response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+// an error status as a provider failure: 429 is rate limiting, with the wait from Retry-After or from the
+// envelope's RetryInfo ("retryDelay": "21s"); the envelope's message replaces the raw body
+fn gemini_error(
+    status: reqwest::StatusCode,
+    retry_after: Option<u64>,
+    body: &str,
+) -> GitChangesError {
+    let envelope = serde_json::from_str::<GeminiErrorBody>(body).ok();
+    let message = match &envelope {
+        Some(GeminiErrorBody { error }) => format!(
+            "gemini api returned status {} {}: {}",
+            status.as_u16(),
+            error.status.as_deref().unwrap_or_default(),
+            error.message
+        ),
+        None => format!("gemini api returned status {}: {}", status, body),
+    };
+    let retry_delay = envelope
+        .iter()
+        .flat_map(|e| &e.error.details)
+        .find_map(|detail| {
+            let delay = detail.get("retryDelay")?.as_str()?;
+            let seconds: f64 = delay.strip_suffix('s')?.parse().ok()?;
+            Some(seconds.ceil() as u64)
+        });
+    let failure = match status.as_u16() {
+        429 => ProviderFailure::RateLimited,
+        _ => ProviderFailure::Api,
+    };
+    let wait = retry_after
+        .or(retry_delay)
+        .filter(|_| failure == ProviderFailure::RateLimited);
+    GitChangesError::Provider {
+        provider: "gemini",
+        failure,
+        status: Some(status.as_u16()),
+        retry_after_secs: wait,
+        message,
+        source: None,
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelList {
+    models: Option<Vec<ModelInfo>>,
+    #[serde(rename = "nextPageToken")]
+    next_page_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ModelInfo {
+    name: String,
+    #[serde(rename = "supportedGenerationMethods", default)]
+    supported_generation_methods: Vec<String>,
+}
+
+// the ids of the models that can generate text, i.e. that --model accepts, in the api's order
+pub(crate) async fn list_models() -> Result<Vec<String>> {
+    let gemini_api_key = gemini_api_key()?;
+    let http = create_http_client();
+    let mut ids = Vec::new();
+    let mut page_token: Option<String> = None;
+    let url = models_url();
+    loop {
+        let mut request = http
+            .get(&url)
+            .query(&[("key", gemini_api_key.as_str()), ("pageSize", "1000")]);
+        if let Some(token) = &page_token {
+            request = request.query(&[("pageToken", token.as_str())]);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| network_error("failed to send request to gemini api", e))?;
+        let status = response.status();
+        let retry_after = retry_after(&response);
+        let body = response
+            .text()
+            .await
+            .map_err(|e| network_error("failed to read response body", e))?;
+        if !status.is_success() {
+            return Err(gemini_error(status, retry_after, &body).into());
+        }
+        let list: ModelList = serde_json::from_str(&body).map_err(|e| {
+            gemini_failure(
+                ProviderFailure::BadResponse,
+                format!("failed to parse json response: {}: {}", body, e),
+            )
+        })?;
+        ids.extend(
+            list.models
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|model| {
+                    model
+                        .supported_generation_methods
+                        .iter()
+                        .any(|method| method == "generateContent")
+                })
+                .map(|model| {
+                    model
+                        .name
+                        .strip_prefix("models/")
+                        .unwrap_or(&model.name)
+                        .to_string()
+                }),
+        );
+        match list.next_page_token.filter(|token| !token.is_empty()) {
+            Some(token) => page_token = Some(token),
+            None => return Ok(ids),
+        }
+    }
+}
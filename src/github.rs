@@ -0,0 +1,207 @@
+// src/github.rs
+// pr --github: creating or updating the pull request for the current branch
+
+use crate::pr::PrText;
+use anyhow::Result;
+use git2::Repository;
+#[cfg(feature = "github")]
+use tracing::warn;
+
+#[cfg(not(feature = "github"))]
+pub async fn publish(
+    _repo: &Repository,
+    _text: &PrText,
+    _base: Option<&str>,
+    _dry_run: bool,
+) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "this build has no GitHub support (rebuild with the 'github' feature)"
+    ))
+}
+
+#[cfg(feature = "github")]
+pub use client::publish;
+
+#[cfg(feature = "github")]
+mod client {
+    use super::*;
+    use crate::pr;
+    use anyhow::{anyhow, Context};
+    use reqwest::{Client, RequestBuilder, StatusCode};
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+    use std::env;
+
+    // GITHUB_API_URL is what Actions sets for GitHub Enterprise, and lets tests point at a mock
+    const DEFAULT_API_URL: &str = "https://api.github.com";
+
+    // owner and repository name from the ssh or https forms of a github.com remote
+    pub fn parse_remote(url: &str) -> Option<(String, String)> {
+        let url = url.trim().trim_end_matches('/');
+        let path = url
+            .strip_prefix("git@github.com:")
+            .or_else(|| url.strip_prefix("ssh://git@github.com/"))
+            .or_else(|| url.strip_prefix("https://github.com/"))
+            .or_else(|| url.strip_prefix("http://github.com/"))
+            .or_else(|| {
+                // https://user@github.com/owner/repo
+                let rest = url.strip_prefix("https://")?;
+                let (_, rest) = rest.split_once('@')?;
+                rest.strip_prefix("github.com/")
+            })?;
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        let (owner, name) = path.split_once('/')?;
+        if owner.is_empty() || name.is_empty() || name.contains('/') {
+            return None;
+        }
+        Some((owner.to_string(), name.to_string()))
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct PullRequest {
+        number: u64,
+        html_url: String,
+    }
+
+    struct GitHub {
+        http: Client,
+        api: String,
+        token: Option<String>,
+        owner: String,
+        name: String,
+    }
+
+    impl GitHub {
+        fn pulls_url(&self) -> String {
+            format!("{}/repos/{}/{}/pulls", self.api, self.owner, self.name)
+        }
+
+        fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+            let request = request
+                .header("Accept", "application/vnd.github+json")
+                .header("User-Agent", "git-changes-rs");
+            match &self.token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        }
+
+        async fn open_pull(&self, head: &str) -> Result<Option<PullRequest>> {
+            let response = self
+                .authorized(self.http.get(self.pulls_url()))
+                .query(&[
+                    ("head", format!("{}:{}", self.owner, head)),
+                    ("state", "open".to_string()),
+                ])
+                .send()
+                .await
+                .context("failed to reach the GitHub API")?;
+            let pulls: Vec<PullRequest> = checked(response).await?.json().await?;
+            Ok(pulls.into_iter().next())
+        }
+
+        async fn send(&self, request: RequestBuilder, payload: &Value) -> Result<PullRequest> {
+            let response = self
+                .authorized(request)
+                .json(payload)
+                .send()
+                .await
+                .context("failed to reach the GitHub API")?;
+            Ok(checked(response).await?.json().await?)
+        }
+    }
+
+    // GitHub's errors, reworded where there is something to do about them
+    async fn checked(response: reqwest::Response) -> Result<reqwest::Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let text = response.text().await.unwrap_or_default();
+        Err(match status {
+            StatusCode::UNAUTHORIZED => {
+                anyhow!("GitHub rejected GITHUB_TOKEN (401); check that it is valid")
+            }
+            StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => anyhow!(
+                "GitHub returned {}; check that GITHUB_TOKEN can access the repository and write pull requests",
+                status
+            ),
+            StatusCode::UNPROCESSABLE_ENTITY if text.contains("No commits between") => anyhow!(
+                "GitHub sees no commits between the base and this branch; push your commits first"
+            ),
+            StatusCode::UNPROCESSABLE_ENTITY if text.contains("\"field\":\"head\"") => anyhow!(
+                "GitHub doesn't know this branch; push it first (git push -u origin <branch>)"
+            ),
+            _ => anyhow!("GitHub returned {}: {}", status, text.trim()),
+        })
+    }
+
+    pub async fn publish(
+        repo: &Repository,
+        text: &PrText,
+        base: Option<&str>,
+        dry_run: bool,
+    ) -> Result<()> {
+        let remote = repo
+            .find_remote("origin")
+            .context("the repository has no 'origin' remote")?;
+        let url = remote.url().context("origin has no URL")?;
+        let (owner, name) = parse_remote(url)
+            .with_context(|| format!("origin ({}) is not a github.com repository", url))?;
+
+        let token = env::var("GITHUB_TOKEN").ok().filter(|t| !t.is_empty());
+        if token.is_none() && !dry_run {
+            return Err(anyhow!("--github needs GITHUB_TOKEN to be set"));
+        }
+        let github = GitHub {
+            http: Client::new(),
+            api: env::var("GITHUB_API_URL")
+                .unwrap_or_else(|_| DEFAULT_API_URL.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            token,
+            owner,
+            name,
+        };
+
+        let head = pr::pushed_branch(repo)?;
+        let base = pr::base_branch(repo, base)?;
+        let existing = match &github.token {
+            Some(_) => github.open_pull(&head).await?,
+            None => {
+                warn!("without GITHUB_TOKEN an existing pull request can't be looked up");
+                None
+            }
+        };
+
+        match existing {
+            Some(pull) => {
+                let url = format!("{}/{}", github.pulls_url(), pull.number);
+                let payload = json!({ "title": text.title, "body": text.body });
+                if dry_run {
+                    outln!("would PATCH {}\n{:#}", url, payload)?;
+                    return Ok(());
+                }
+                let pull = github.send(github.http.patch(url), &payload).await?;
+                outln!("updated #{}: {}", pull.number, pull.html_url)?;
+            }
+            None => {
+                let payload = json!({
+                    "title": text.title,
+                    "body": text.body,
+                    "head": head,
+                    "base": base,
+                });
+                if dry_run {
+                    outln!("would POST {}\n{:#}", github.pulls_url(), payload)?;
+                    return Ok(());
+                }
+                let pull = github
+                    .send(github.http.post(github.pulls_url()), &payload)
+                    .await?;
+                outln!("created #{}: {}", pull.number, pull.html_url)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+// src/gitlab.rs
+// pr --gitlab: creating or updating the merge request for the current branch
+
+use crate::pr::PrText;
+use anyhow::Result;
+use git2::Repository;
+#[cfg(feature = "gitlab")]
+use tracing::warn;
+
+// how the merge request is published
+#[cfg_attr(not(feature = "gitlab"), allow(dead_code))]
+pub struct GitLabOptions<'a> {
+    pub base: Option<&'a str>,
+    // --gitlab-url, for self-hosted instances (CI_SERVER_URL otherwise)
+    pub url: Option<&'a str>,
+    pub draft: bool,
+    pub dry_run: bool,
+}
+
+#[cfg(not(feature = "gitlab"))]
+pub async fn publish(
+    _repo: &Repository,
+    _text: &PrText,
+    _options: &GitLabOptions<'_>,
+) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "this build has no GitLab support (rebuild with the 'gitlab' feature)"
+    ))
+}
+
+#[cfg(feature = "gitlab")]
+pub use client::publish;
+
+#[cfg(feature = "gitlab")]
+mod client {
+    use super::*;
+    use crate::pr;
+    use anyhow::{anyhow, Context};
+    use reqwest::{Client, RequestBuilder, StatusCode};
+    use serde::Deserialize;
+    use serde_json::{json, Value};
+    use std::env;
+
+    const DRAFT_PREFIX: &str = "Draft: ";
+
+    // host and project path (subgroups included) from the ssh or https forms of a remote
+    pub fn parse_remote(url: &str) -> Option<(String, String)> {
+        let url = url.trim().trim_end_matches('/');
+        let (host, path) = if let Some(rest) = url
+            .strip_prefix("https://")
+            .or_else(|| url.strip_prefix("http://"))
+            .or_else(|| url.strip_prefix("ssh://"))
+        {
+            let (authority, path) = rest.split_once('/')?;
+            // drop credentials and an ssh port; the API is on the web host
+            let host = authority.rsplit('@').next()?;
+            let host = match url.starts_with("ssh://") {
+                true => host.split(':').next()?,
+                false => host,
+            };
+            (host.to_string(), path)
+        } else {
+            // scp-like: git@host:group/project.git
+            let (authority, path) = url.split_once(':')?;
+            (authority.rsplit('@').next()?.to_string(), path)
+        };
+        let path = path.strip_suffix(".git").unwrap_or(path);
+        if host.is_empty() || !path.contains('/') || path.split('/').any(str::is_empty) {
+            return None;
+        }
+        Some((host, path.to_string()))
+    }
+
+    // the title with exactly one draft prefix, or none
+    pub fn draft_title(title: &str, draft: bool) -> String {
+        let bare = title
+            .strip_prefix(DRAFT_PREFIX)
+            .or_else(|| title.strip_prefix("Draft:"))
+            .unwrap_or(title)
+            .trim_start();
+        match draft {
+            true => format!("{}{}", DRAFT_PREFIX, bare),
+            false => bare.to_string(),
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct MergeRequest {
+        iid: u64,
+        web_url: String,
+    }
+
+    struct GitLab {
+        http: Client,
+        // .../api/v4/projects/<url-encoded path>
+        project: String,
+        token: Option<String>,
+    }
+
+    impl GitLab {
+        fn merge_requests_url(&self) -> String {
+            format!("{}/merge_requests", self.project)
+        }
+
+        fn authorized(&self, request: RequestBuilder) -> RequestBuilder {
+            match &self.token {
+                Some(token) => request.header("PRIVATE-TOKEN", token),
+                None => request,
+            }
+        }
+
+        async fn open_merge_request(&self, source: &str) -> Result<Option<MergeRequest>> {
+            let response = self
+                .authorized(self.http.get(self.merge_requests_url()))
+                .query(&[("source_branch", source), ("state", "opened")])
+                .send()
+                .await
+                .context("failed to reach the GitLab API")?;
+            let requests: Vec<MergeRequest> = checked(response).await?.json().await?;
+            Ok(requests.into_iter().next())
+        }
+
+        async fn send(&self, request: RequestBuilder, payload: &Value) -> Result<MergeRequest> {
+            let response = self
+                .authorized(request)
+                .json(payload)
+                .send()
+                .await
+                .context("failed to reach the GitLab API")?;
+            Ok(checked(response).await?.json().await?)
+        }
+    }
+
+    // GitLab's errors, reworded where there is something to do about them
+    async fn checked(response: reqwest::Response) -> Result<reqwest::Response> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let text = response.text().await.unwrap_or_default();
+        Err(match status {
+            StatusCode::UNAUTHORIZED => {
+                anyhow!("GitLab rejected GITLAB_TOKEN (401); check that it is valid")
+            }
+            StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => anyhow!(
+                "GitLab returned {}; check the project path, --gitlab-url and that GITLAB_TOKEN has the api scope",
+                status
+            ),
+            _ if text.contains("Source branch") || text.contains("source_branch") => anyhow!(
+                "GitLab doesn't accept this branch as a source: {}; push it first (git push -u origin <branch>)",
+                text.trim()
+            ),
+            _ => anyhow!("GitLab returned {}: {}", status, text.trim()),
+        })
+    }
+
+    fn encode_path(path: &str) -> String {
+        path.replace('%', "%25").replace('/', "%2F")
+    }
+
+    pub async fn publish(
+        repo: &Repository,
+        text: &PrText,
+        options: &GitLabOptions<'_>,
+    ) -> Result<()> {
+        let remote = repo
+            .find_remote("origin")
+            .context("the repository has no 'origin' remote")?;
+        let url = remote.url().context("origin has no URL")?;
+        let (host, path) = parse_remote(url)
+            .with_context(|| format!("can't tell the GitLab project from origin ({})", url))?;
+
+        let token = env::var("GITLAB_TOKEN").ok().filter(|t| !t.is_empty());
+        if token.is_none() && !options.dry_run {
+            return Err(anyhow!("--gitlab needs GITLAB_TOKEN to be set"));
+        }
+        let server = options
+            .url
+            .map(str::to_string)
+            .or_else(|| env::var("CI_SERVER_URL").ok().filter(|u| !u.is_empty()))
+            .unwrap_or_else(|| format!("https://{}", host));
+        let gitlab = GitLab {
+            http: Client::new(),
+            project: format!(
+                "{}/api/v4/projects/{}",
+                server.trim_end_matches('/'),
+                encode_path(&path)
+            ),
+            token,
+        };
+
+        let source = pr::pushed_branch(repo)?;
+        let target = pr::base_branch(repo, options.base)?;
+        let title = draft_title(&text.title, options.draft);
+        let existing = match &gitlab.token {
+            Some(_) => gitlab.open_merge_request(&source).await?,
+            None => {
+                warn!("without GITLAB_TOKEN an existing merge request can't be looked up");
+                None
+            }
+        };
+
+        match existing {
+            Some(request) => {
+                let url = format!("{}/{}", gitlab.merge_requests_url(), request.iid);
+                let payload = json!({ "title": title, "description": text.body });
+                if options.dry_run {
+                    outln!("would PUT {}\n{:#}", url, payload)?;
+                    return Ok(());
+                }
+                let request = gitlab.send(gitlab.http.put(url), &payload).await?;
+                outln!("updated !{}: {}", request.iid, request.web_url)?;
+            }
+            None => {
+                let payload = json!({
+                    "title": title,
+                    "description": text.body,
+                    "source_branch": source,
+                    "target_branch": target,
+                });
+                if options.dry_run {
+                    outln!("would POST {}\n{:#}", gitlab.merge_requests_url(), payload)?;
+                    return Ok(());
+                }
+                let request = gitlab
+                    .send(gitlab.http.post(gitlab.merge_requests_url()), &payload)
+                    .await?;
+                outln!("created !{}: {}", request.iid, request.web_url)?;
+            }
+        }
+        Ok(())
+    }
+}
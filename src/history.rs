@@ -0,0 +1,157 @@
+// src/history.rs
+// resolving commit ranges for the subcommands that look at existing history
+
+use crate::paths;
+use anyhow::{anyhow, Context, Result};
+use git2::{Diff, DiffDelta, DiffFormat, Oid, Repository, Sort};
+
+// commits in `range` (git's `a..b`, `a..`, `..b` or a single rev), newest first.
+// without a range, the current branch's commits that aren't on its upstream.
+pub fn commits_in_range(repo: &Repository, range: Option<&str>) -> Result<Vec<Oid>> {
+    let mut walk = repo.revwalk().context("failed to create revision walker")?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+        .context("failed to set revision walk order")?;
+
+    match range {
+        Some(range) if range.contains("..") => {
+            let (from, to) = range
+                .split_once("..")
+                .context("failed to split commit range")?;
+            let to = if to.is_empty() { "HEAD" } else { to };
+            push_rev(repo, &mut walk, to)?;
+            if !from.is_empty() {
+                let from_oid = resolve(repo, from)?;
+                walk.hide(from_oid)
+                    .with_context(|| format!("failed to exclude '{}' from the walk", from))?;
+            }
+        }
+        Some(rev) => push_rev(repo, &mut walk, rev)?,
+        None => {
+            let upstream = upstream_oid(repo)?;
+            walk.push_head().context("failed to start walk at HEAD")?;
+            walk.hide(upstream)
+                .context("failed to exclude upstream from the walk")?;
+        }
+    }
+
+    walk.collect::<Result<Vec<_>, _>>()
+        .context("failed to walk commit range")
+}
+
+// the commit the current branch's upstream points at
+pub fn upstream_oid(repo: &Repository) -> Result<Oid> {
+    let head = repo.head().context("failed to get head reference")?;
+    if !head.is_branch() {
+        return Err(anyhow!(
+            "HEAD is detached; pass an explicit range such as origin/main..HEAD"
+        ));
+    }
+    let name = head.shorthand().unwrap_or("HEAD").to_string();
+    let branch = git2::Branch::wrap(head);
+    let upstream = branch.upstream().map_err(|_| {
+        anyhow!(
+            "branch '{}' has no upstream; pass an explicit range such as origin/main..HEAD",
+            name
+        )
+    })?;
+    upstream
+        .get()
+        .target()
+        .with_context(|| format!("upstream of '{}' has no target", name))
+}
+
+pub fn resolve(repo: &Repository, rev: &str) -> Result<Oid> {
+    let object = repo
+        .revparse_single(rev)
+        .with_context(|| format!("failed to resolve revision '{}'", rev))?;
+    let commit = object
+        .peel_to_commit()
+        .with_context(|| format!("'{}' does not point to a commit", rev))?;
+    Ok(commit.id())
+}
+
+fn push_rev(repo: &Repository, walk: &mut git2::Revwalk<'_>, rev: &str) -> Result<()> {
+    let oid = resolve(repo, rev)?;
+    walk.push(oid)
+        .with_context(|| format!("failed to start walk at '{}'", rev))
+}
+
+// up to `limit` commits reachable from HEAD along first parents, newest first; none on an unborn branch
+pub fn recent_commits(repo: &Repository, limit: usize) -> Result<Vec<Oid>> {
+    if repo.head().is_err() {
+        return Ok(Vec::new());
+    }
+    let mut walk = repo.revwalk().context("failed to create revision walker")?;
+    walk.simplify_first_parent()
+        .context("failed to restrict the walk to first parents")?;
+    walk.push_head().context("failed to start walk at HEAD")?;
+    walk.take(limit)
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to walk recent commits")
+}
+
+// what a commit changed relative to its first parent (or to nothing, for a root commit)
+pub fn diff_to_parent(repo: &Repository, oid: Oid) -> Result<Diff<'_>> {
+    let commit = repo
+        .find_commit(oid)
+        .with_context(|| format!("failed to look up commit {}", oid))?;
+    let tree = commit.tree().context("failed to read commit tree")?;
+    let parent_tree = match commit.parents().next() {
+        Some(parent) => Some(parent.tree().context("failed to read parent tree")?),
+        None => None,
+    };
+    repo.diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        .context("failed to diff commit against its parent")
+}
+
+// everything that changed from `from` to `to`, as one diff
+pub fn diff_between(repo: &Repository, from: Oid, to: Oid) -> Result<Diff<'_>> {
+    let from_tree = repo
+        .find_commit(from)
+        .and_then(|c| c.tree())
+        .context("failed to read the base tree")?;
+    let to_tree = repo
+        .find_commit(to)
+        .and_then(|c| c.tree())
+        .context("failed to read the head tree")?;
+    repo.diff_tree_to_tree(Some(&from_tree), Some(&to_tree), None)
+        .context("failed to diff the range")
+}
+
+// paths a commit touched, in diff order
+pub fn changed_paths(diff: &Diff<'_>) -> Vec<String> {
+    diff.deltas()
+        .filter_map(|d| d.new_file().path().or(d.old_file().path()))
+        .map(paths::display)
+        .collect()
+}
+
+// the diff as patch text, with +/- markers on changed lines
+pub fn patch_text(diff: &Diff<'_>) -> Result<String> {
+    patch_text_where(diff, |_| true)
+}
+
+// patch text of the files `include` accepts
+pub fn patch_text_where(diff: &Diff<'_>, include: impl Fn(&DiffDelta) -> bool) -> Result<String> {
+    let mut text = String::new();
+    diff.print(DiffFormat::Patch, |delta, _hunk, line| {
+        if !include(&delta) {
+            return true;
+        }
+        if matches!(line.origin(), '+' | '-' | ' ') {
+            text.push(line.origin());
+        }
+        let content = String::from_utf8_lossy(line.content());
+        match line.origin() {
+            'F' => text.push_str(&paths::unquote_header(&content)),
+            _ => text.push_str(&content),
+        }
+        true
+    })
+    .context("failed to render commit diff")?;
+    Ok(text)
+}
+
+pub fn short_id(oid: Oid) -> String {
+    oid.to_string()[..7].to_string()
+}
@@ -0,0 +1,520 @@
+// src/hook.rs
+// prepare-commit-msg support: pre-filling the message file git is about to open, and
+// installing the hook script that does it
+
+use crate::interrupt;
+use anyhow::{anyhow, Context, Result};
+use git2::Repository;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_HOOK: &str = "prepare-commit-msg";
+
+// identifies scripts we wrote, so uninstalling never removes someone else's hook
+const MARKER: &str = "# installed by git-changes-rs install-hook";
+
+// where a hook that was already there is kept when --force replaces it
+const BACKUP_SUFFIX: &str = ".pre-git-changes";
+
+// message sources for which git already has a message worth keeping
+const SKIPPED_SOURCES: &[&str] = &["merge", "commit"];
+
+// `git merge --squash`: the file holds a dump of every squashed commit, which is replaced
+pub const SQUASH_SOURCE: &str = "squash";
+
+// a merge commit; git's "Merge branch 'x'" is kept unless merge messages are wanted
+pub const MERGE_SOURCE: &str = "merge";
+
+// -m/-F, and what git's sequencer uses when committing a cherry-pick
+const MESSAGE_SOURCE: &str = "message";
+
+// which of git's own messages are replaced: merges with --merge-messages, and the copied
+// message of a cherry-pick with --cherry-pick-messages (git commits a conflicted cherry-pick
+// with the merge source, so it must be told apart from a merge)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Replaced {
+    pub merges: bool,
+    pub cherry_picks: bool,
+    pub cherry_picking: bool,
+}
+
+impl Replaced {
+    // true when git's text is replaced wholesale, keeping only its comments
+    pub fn covers(&self, args: &HookArgs) -> bool {
+        match args.source.as_deref() {
+            Some(SQUASH_SOURCE) => true,
+            Some(MERGE_SOURCE) if self.cherry_picking => self.cherry_picks,
+            Some(MERGE_SOURCE) => self.merges,
+            Some(MESSAGE_SOURCE) => self.cherry_picking && self.cherry_picks,
+            _ => false,
+        }
+    }
+}
+
+// the hook's arguments, in git's order: <msgfile> [<source> [<sha>]]
+#[derive(Debug, Clone)]
+pub struct HookArgs {
+    pub path: PathBuf,
+    pub source: Option<String>,
+    pub sha: Option<String>,
+}
+
+impl HookArgs {
+    pub fn from_values(values: &[String]) -> Option<Self> {
+        Some(HookArgs {
+            path: PathBuf::from(values.first()?),
+            source: values.get(1).cloned(),
+            sha: values.get(2).cloned(),
+        })
+    }
+
+    pub fn read(&self) -> Result<String> {
+        fs::read_to_string(&self.path)
+            .with_context(|| format!("failed to read {}", self.path.display()))
+    }
+}
+
+// true when the file has anything besides comments and blank lines
+pub fn has_content(existing: &str) -> bool {
+    existing
+        .lines()
+        .any(|line| !line.trim().is_empty() && !line.starts_with('#'))
+}
+
+// why generation should be skipped, if it should
+pub fn skip_reason(args: &HookArgs, existing: &str, replaced: Replaced) -> Option<String> {
+    if replaced.covers(args) {
+        return None;
+    }
+    if let Some(source) = args.source.as_deref() {
+        if SKIPPED_SOURCES.contains(&source) {
+            return Some(match args.sha.as_deref() {
+                Some(sha) => format!("message source is '{}' from {}", source, sha),
+                None => format!("message source is '{}'", source),
+            });
+        }
+    }
+    if has_content(existing) {
+        return Some("the message file already has content".to_string());
+    }
+    None
+}
+
+// git's comment lines, without the squashed commits' messages above them
+pub fn comments_only(existing: &str) -> String {
+    existing
+        .split_inclusive('\n')
+        .filter(|line| line.starts_with('#'))
+        .collect()
+}
+
+// the message above git's comment block, which stays as it was
+pub fn insert_message(existing: &str, message: &str) -> String {
+    let comments = existing.trim_start_matches(['\n', '\r']);
+    if comments.is_empty() {
+        format!("{}\n", message.trim_end())
+    } else {
+        format!("{}\n\n{}", message.trim_end(), comments)
+    }
+}
+
+pub fn write_message(args: &HookArgs, existing: &str, message: &str) -> Result<()> {
+    let _writing = interrupt::writing()?;
+    fs::write(&args.path, insert_message(existing, message))
+        .with_context(|| format!("failed to write {}", args.path.display()))
+}
+
+// core.hooksPath if set (relative to the working tree, like git), otherwise the hooks
+// directory of the main git dir, which linked worktrees share
+pub fn hooks_dir(repo: &Repository) -> Result<PathBuf> {
+    let configured = repo
+        .config()
+        .context("failed to read git config")?
+        .get_path("core.hooksPath")
+        .ok();
+    Ok(match configured {
+        Some(path) if path.is_relative() => repo
+            .workdir()
+            .unwrap_or_else(|| repo.commondir())
+            .join(path),
+        Some(path) => path,
+        None => repo.commondir().join("hooks"),
+    })
+}
+
+fn backup_path(hook: &Path) -> PathBuf {
+    let mut name = hook.file_name().unwrap_or_default().to_os_string();
+    name.push(BACKUP_SUFFIX);
+    hook.with_file_name(name)
+}
+
+pub fn is_ours(hook: &Path) -> bool {
+    fs::read_to_string(hook).is_ok_and(|script| script.contains(MARKER))
+}
+
+// the binary one of our hooks runs, from its `exec` line
+pub fn installed_binary(hook: &Path) -> Option<PathBuf> {
+    let script = fs::read_to_string(hook).ok()?;
+    let line = script.lines().find_map(|line| line.strip_prefix("exec "))?;
+    first_word(line).map(PathBuf::from)
+}
+
+// the first word of `line` the way sh reads the quoting hook_script writes: '...' runs and
+// backslash escapes, plus the "..." of hooks written before the path was single-quoted
+fn first_word(line: &str) -> Option<String> {
+    let mut word = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => loop {
+                match chars.next()? {
+                    end if end == c => break,
+                    c => word.push(c),
+                }
+            },
+            '\\' => word.push(chars.next()?),
+            ' ' => break,
+            c => word.push(c),
+        }
+    }
+    (!word.is_empty()).then_some(word)
+}
+
+// `text` as one sh word: inside single quotes nothing is special, and a quote of its own is
+// closed, escaped and reopened
+fn single_quoted(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "'\\''"))
+}
+
+// git runs hooks through sh on every platform (Git for Windows ships one), so the script is
+// the same everywhere; only a Windows binary path needs forward slashes
+fn hook_script(binary: &Path, chained: Option<&Path>, options: &[&str]) -> String {
+    let mut binary = binary.display().to_string();
+    if cfg!(windows) {
+        binary = binary.replace('\\', "/");
+    }
+    let mut script = format!("#!/bin/sh\n{}\n", MARKER);
+    if let Some(previous) = chained {
+        let name = previous.file_name().unwrap_or_default().to_string_lossy();
+        // like git, a hook that isn't executable is skipped
+        script.push_str(&format!(
+            "previous=\"$(dirname \"$0\")/{}\"\nif [ -x \"$previous\" ]; then\n    \"$previous\" \"$@\" || exit $?\nfi\n",
+            name
+        ));
+    }
+    let options: String = options.iter().map(|o| format!(" {}", o)).collect();
+    script.push_str(&format!(
+        "exec {}{} --hook \"$@\"\n",
+        single_quoted(&binary),
+        options
+    ));
+    script
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("failed to make {} executable", path.display()))
+}
+
+// Windows has no executable bit; git's sh decides by the shebang
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+// writes the hook; an existing foreign hook is only replaced with `force`, and is then kept
+// next to it and called first. `options` are passed before --hook on every run
+pub fn install(
+    repo: &Repository,
+    hook_type: &str,
+    force: bool,
+    options: &[&str],
+) -> Result<PathBuf> {
+    let dir = hooks_dir(repo)?;
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+    let hook = dir.join(hook_type);
+    let backup = backup_path(&hook);
+
+    if hook.exists() && !is_ours(&hook) {
+        if !force {
+            return Err(anyhow!(
+                "{} already exists; use --force to keep it as {} and call it before ours",
+                hook.display(),
+                backup.display()
+            ));
+        }
+        if backup.exists() {
+            return Err(anyhow!(
+                "{} already exists; move it away before installing",
+                backup.display()
+            ));
+        }
+        fs::rename(&hook, &backup)
+            .with_context(|| format!("failed to rename {}", hook.display()))?;
+    }
+
+    let binary = env::current_exe().context("failed to locate the git-changes-rs binary")?;
+    let chained = backup.exists().then_some(backup.as_path());
+    fs::write(&hook, hook_script(&binary, chained, options))
+        .with_context(|| format!("failed to write {}", hook.display()))?;
+    make_executable(&hook)?;
+    Ok(hook)
+}
+
+// removes our hook and puts back the one it replaced, if any; returns the restored path
+pub fn uninstall(repo: &Repository, hook_type: &str) -> Result<Option<PathBuf>> {
+    let hook = hooks_dir(repo)?.join(hook_type);
+    if !hook.exists() {
+        return Err(anyhow!("{} does not exist", hook.display()));
+    }
+    if !is_ours(&hook) {
+        return Err(anyhow!(
+            "{} was not installed by git-changes-rs; leaving it alone",
+            hook.display()
+        ));
+    }
+    fs::remove_file(&hook).with_context(|| format!("failed to remove {}", hook.display()))?;
+
+    let backup = backup_path(&hook);
+    if !backup.exists() {
+        return Ok(None);
+    }
+    fs::rename(&backup, &hook)
+        .with_context(|| format!("failed to restore {}", backup.display()))?;
+    Ok(Some(hook))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(values: &[&str]) -> HookArgs {
+        let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        HookArgs::from_values(&values).unwrap()
+    }
+
+    const TEMPLATE: &str =
+        "\n# Please enter the commit message for your changes.\n#\n# On branch main\n";
+
+    #[test]
+    fn arguments_are_in_gits_order() {
+        let full = args(&[".git/COMMIT_EDITMSG", "commit", "abc123"]);
+        assert_eq!(full.path, Path::new(".git/COMMIT_EDITMSG"));
+        assert_eq!(full.source.as_deref(), Some("commit"));
+        assert_eq!(full.sha.as_deref(), Some("abc123"));
+        assert!(args(&[".git/COMMIT_EDITMSG"]).source.is_none());
+        assert!(HookArgs::from_values(&[]).is_none());
+    }
+
+    #[test]
+    fn gits_own_messages_are_kept_unless_replaced() {
+        let none = Replaced::default();
+        assert_eq!(skip_reason(&args(&["f"]), TEMPLATE, none), None);
+        assert_eq!(skip_reason(&args(&["f", "template"]), TEMPLATE, none), None);
+        assert_eq!(
+            skip_reason(&args(&["f", "commit", "abc123"]), TEMPLATE, none).as_deref(),
+            Some("message source is 'commit' from abc123")
+        );
+        assert_eq!(
+            skip_reason(&args(&["f", "merge"]), "Merge branch 'x'\n", none).as_deref(),
+            Some("message source is 'merge'")
+        );
+        assert_eq!(
+            skip_reason(&args(&["f", "message"]), "wip\n", none).as_deref(),
+            Some("the message file already has content")
+        );
+        // a squash is always replaced, whatever the file holds
+        assert_eq!(
+            skip_reason(
+                &args(&["f", "squash"]),
+                "Squashed commit of the following:\n",
+                none
+            ),
+            None
+        );
+
+        let merges = Replaced {
+            merges: true,
+            ..Replaced::default()
+        };
+        assert_eq!(
+            skip_reason(&args(&["f", "merge"]), "Merge branch 'x'\n", merges),
+            None
+        );
+    }
+
+    #[test]
+    fn a_cherry_pick_is_told_apart_from_a_merge() {
+        let picking = Replaced {
+            merges: true,
+            cherry_picks: false,
+            cherry_picking: true,
+        };
+        assert!(!picking.covers(&args(&["f", "merge"])));
+        assert!(!picking.covers(&args(&["f", "message"])));
+        let replaced = Replaced {
+            cherry_picks: true,
+            ..picking
+        };
+        assert!(replaced.covers(&args(&["f", "merge"])));
+        assert!(replaced.covers(&args(&["f", "message"])));
+    }
+
+    #[test]
+    fn the_message_goes_above_gits_comments() {
+        assert_eq!(
+            insert_message(TEMPLATE, "feat: add notes\n\n"),
+            format!("feat: add notes\n\n{}", TEMPLATE.trim_start())
+        );
+        assert_eq!(insert_message("", "feat: add notes"), "feat: add notes\n");
+        assert_eq!(
+            comments_only("Squashed commit of the following:\n\ncommit abc\n# comment\n"),
+            "# comment\n"
+        );
+        assert!(!has_content(TEMPLATE));
+        assert!(has_content("wip\n# comment\n"));
+    }
+
+    fn repo() -> (tempfile::TempDir, Repository) {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn the_hooks_directory_follows_core_hooks_path() {
+        let (_dir, repo) = repo();
+        assert_eq!(hooks_dir(&repo).unwrap(), repo.path().join("hooks"));
+        let mut config = repo.config().unwrap();
+        config.set_str("core.hooksPath", ".githooks").unwrap();
+        assert_eq!(
+            hooks_dir(&repo).unwrap(),
+            repo.workdir().unwrap().join(".githooks")
+        );
+        if cfg!(unix) {
+            config.set_str("core.hooksPath", "/etc/hooks").unwrap();
+            assert_eq!(hooks_dir(&repo).unwrap(), Path::new("/etc/hooks"));
+        }
+    }
+
+    #[test]
+    fn installing_over_a_foreign_hook_chains_it_and_uninstalling_restores_it() {
+        let (_dir, repo) = repo();
+        let hooks = repo.path().join("hooks");
+        fs::create_dir_all(&hooks).unwrap();
+        let hook = hooks.join(DEFAULT_HOOK);
+        fs::write(&hook, "#!/bin/sh\necho theirs\n").unwrap();
+
+        let error = install(&repo, DEFAULT_HOOK, false, &[]).unwrap_err();
+        assert!(error.to_string().contains("use --force"), "{}", error);
+        assert!(uninstall(&repo, DEFAULT_HOOK)
+            .unwrap_err()
+            .to_string()
+            .contains("was not installed by git-changes-rs"));
+
+        let installed = install(&repo, DEFAULT_HOOK, true, &["--merge-messages"]).unwrap();
+        assert_eq!(installed, hook);
+        assert!(is_ours(&hook));
+        let script = fs::read_to_string(&hook).unwrap();
+        assert!(
+            script.contains("previous=\"$(dirname \"$0\")/prepare-commit-msg.pre-git-changes\""),
+            "{}",
+            script
+        );
+        assert!(
+            script.ends_with(" --merge-messages --hook \"$@\"\n"),
+            "{}",
+            script
+        );
+        assert_eq!(installed_binary(&hook), Some(env::current_exe().unwrap()));
+        assert_eq!(
+            fs::read_to_string(backup_path(&hook)).unwrap(),
+            "#!/bin/sh\necho theirs\n"
+        );
+
+        // installing again only rewrites ours, still chained
+        install(&repo, DEFAULT_HOOK, false, &[]).unwrap();
+        assert!(fs::read_to_string(&hook).unwrap().contains("previous="));
+
+        assert_eq!(uninstall(&repo, DEFAULT_HOOK).unwrap(), Some(hook.clone()));
+        assert_eq!(
+            fs::read_to_string(&hook).unwrap(),
+            "#!/bin/sh\necho theirs\n"
+        );
+        assert!(!backup_path(&hook).exists());
+    }
+
+    #[test]
+    fn uninstalling_our_only_hook_leaves_nothing() {
+        let (_dir, repo) = repo();
+        let hook = install(&repo, DEFAULT_HOOK, false, &[]).unwrap();
+        assert!(!fs::read_to_string(&hook).unwrap().contains("previous="));
+        assert_eq!(uninstall(&repo, DEFAULT_HOOK).unwrap(), None);
+        assert!(!hook.exists());
+        assert!(uninstall(&repo, DEFAULT_HOOK).is_err());
+    }
+
+    #[test]
+    fn the_binary_path_is_quoted_for_sh_and_read_back() {
+        for binary in [
+            "/usr/local/bin/git-changes-rs",
+            "/opt/it's \"odd\" $HOME `date` \\dir/git-changes-rs",
+        ] {
+            let script = hook_script(Path::new(binary), None, &["--merge-messages"]);
+            let dir = tempfile::tempdir().unwrap();
+            let hook = dir.path().join(DEFAULT_HOOK);
+            fs::write(&hook, &script).unwrap();
+            assert_eq!(
+                installed_binary(&hook),
+                Some(PathBuf::from(binary)),
+                "{}",
+                script
+            );
+        }
+        assert_eq!(single_quoted("it's"), "'it'\\''s'");
+
+        // a hook written before the path was single-quoted
+        let dir = tempfile::tempdir().unwrap();
+        let hook = dir.path().join(DEFAULT_HOOK);
+        fs::write(
+            &hook,
+            "#!/bin/sh\nexec \"/usr/bin/git-changes-rs\" --hook \"$@\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            installed_binary(&hook),
+            Some(PathBuf::from("/usr/bin/git-changes-rs"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn sh_runs_a_binary_whose_path_has_quotes_and_dollars() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("it's \"odd\" $HOME `date`");
+        fs::create_dir(&bin).unwrap();
+        let binary = bin.join("git-changes-rs");
+        let out = dir.path().join("args");
+        fs::write(
+            &binary,
+            format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\n", out.display()),
+        )
+        .unwrap();
+        make_executable(&binary).unwrap();
+        let hook = dir.path().join(DEFAULT_HOOK);
+        fs::write(&hook, hook_script(&binary, None, &["--merge-messages"])).unwrap();
+        let status = std::process::Command::new("sh")
+            .arg(&hook)
+            .arg("COMMIT_EDITMSG")
+            .status()
+            .unwrap();
+        assert!(status.success());
+        assert_eq!(
+            fs::read_to_string(&out).unwrap(),
+            "--merge-messages\n--hook\nCOMMIT_EDITMSG\n"
+        );
+    }
+}
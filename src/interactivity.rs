@@ -0,0 +1,234 @@
+// src/interactivity.rs
+// whether the tool may stop and ask the user anything, the terminal it asks on, and the
+// question asked before anything is sent to the provider
+
+use crate::interrupt;
+use anyhow::{anyhow, Context, Result};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// file names listed in the question before sending
+#[cfg_attr(not(feature = "gemini"), allow(dead_code))]
+const LISTED_FILES: usize = 10;
+
+// --confirm or privacy.confirm_send
+static CONFIRM_SENDS: AtomicBool = AtomicBool::new(false);
+// --yes counts as the answer
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+// asked once per run, not once per request
+#[cfg_attr(not(feature = "gemini"), allow(dead_code))]
+static SEND_APPROVED: AtomicBool = AtomicBool::new(false);
+
+// every prompt checks this one setting instead of probing the terminal itself.
+// non-interactive runs take the defaults: the first candidate, no editor, and no confirmations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interactivity {
+    Interactive,
+    NonInteractive,
+}
+
+impl Interactivity {
+    // --yes wins; otherwise there has to be a terminal to ask on, whatever stdin and stdout are
+    pub fn decide(assume_yes: bool, has_terminal: bool) -> Self {
+        if assume_yes || !has_terminal {
+            Interactivity::NonInteractive
+        } else {
+            Interactivity::Interactive
+        }
+    }
+
+    pub fn detect(assume_yes: bool) -> Self {
+        Self::decide(assume_yes, Terminal::open().is_some())
+    }
+
+    pub fn is_interactive(self) -> bool {
+        self == Interactivity::Interactive
+    }
+}
+
+// the controlling terminal itself rather than stdin and stdout, so questions still reach the
+// user with stdout piped into another program
+pub struct Terminal {
+    pub input: BufReader<File>,
+    pub output: File,
+    _prompting: interrupt::Prompting,
+}
+
+impl Terminal {
+    // none without a controlling terminal (CI, cron, a detached process)
+    pub fn open() -> Option<Terminal> {
+        let (input, output) = open_files().ok()?;
+        Some(Terminal {
+            _prompting: interrupt::prompting(&output),
+            input: BufReader::new(input),
+            output,
+        })
+    }
+
+    // for a child that takes over the terminal, such as the editor
+    pub fn handles(&self) -> io::Result<(File, File)> {
+        Ok((self.input.get_ref().try_clone()?, self.output.try_clone()?))
+    }
+}
+
+#[cfg(not(windows))]
+fn open_files() -> io::Result<(File, File)> {
+    let tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    Ok((tty.try_clone()?, tty))
+}
+
+#[cfg(windows)]
+fn open_files() -> io::Result<(File, File)> {
+    let input = OpenOptions::new().read(true).write(true).open("CONIN$")?;
+    let output = OpenOptions::new().read(true).write(true).open("CONOUT$")?;
+    Ok((input, output))
+}
+
+// y/N; end of input is a no
+pub fn confirm(question: &str, input: &mut impl BufRead, out: &mut impl Write) -> io::Result<bool> {
+    write!(out, "{} [y/N]: ", question)?;
+    out.flush()?;
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    Ok(matches!(
+        line.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+pub fn assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+pub fn confirm_sends() {
+    CONFIRM_SENDS.store(true, Ordering::Relaxed);
+}
+
+// --confirm or privacy.confirm_send is on
+pub fn confirms_sends() -> bool {
+    CONFIRM_SENDS.load(Ordering::Relaxed)
+}
+
+// with confirmation on, shows where the request goes and what's in it and waits for a y. Without
+// a terminal to ask on, nothing is sent: an unanswerable question is a no
+#[cfg_attr(not(feature = "gemini"), allow(dead_code))]
+pub fn approve_send(provider: &str, model: &str, system_prompt: &str, prompt: &str) -> Result<()> {
+    if !CONFIRM_SENDS.load(Ordering::Relaxed) || SEND_APPROVED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    if ASSUME_YES.load(Ordering::Relaxed) {
+        SEND_APPROVED.store(true, Ordering::Relaxed);
+        return Ok(());
+    }
+    let Some(mut terminal) = Terminal::open() else {
+        return Err(anyhow!(
+            "not sent: confirmation before sending is on and there is no terminal to ask on \
+             (pass --yes to send anyway)"
+        ));
+    };
+    let summary = send_summary(provider, model, system_prompt, prompt);
+    write!(terminal.output, "{}", summary).context("failed to write to the terminal")?;
+    let approved = confirm("send it?", &mut terminal.input, &mut terminal.output)
+        .context("failed to read the answer")?;
+    if !approved {
+        return Err(anyhow!("not sent: the request was not confirmed"));
+    }
+    SEND_APPROVED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+// the destination, the size and the files, as read from the diff headers in the prompt
+#[cfg_attr(not(feature = "gemini"), allow(dead_code))]
+fn send_summary(provider: &str, model: &str, system_prompt: &str, prompt: &str) -> String {
+    let files: Vec<&str> = prompt
+        .lines()
+        .filter_map(|line| line.strip_prefix("diff --git a/"))
+        .filter_map(|paths| paths.split_once(" b/").map(|(old, _)| old))
+        .collect();
+    let bytes = system_prompt.len() + prompt.len();
+    // about four bytes per token for code and English
+    let mut summary = format!(
+        "\nabout to send to {} ({}): {} files, {:.1} KB (about {} tokens)\n",
+        provider,
+        model,
+        files.len(),
+        bytes as f64 / 1024.0,
+        bytes / 4
+    );
+    for file in files.iter().take(LISTED_FILES) {
+        summary.push_str(&format!("    {}\n", file));
+    }
+    if files.len() > LISTED_FILES {
+        summary.push_str(&format!(
+            "    ... and {} more\n",
+            files.len() - LISTED_FILES
+        ));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn only_a_terminal_without_yes_is_interactive() {
+        assert_eq!(
+            Interactivity::decide(false, true),
+            Interactivity::Interactive
+        );
+        assert_eq!(
+            Interactivity::decide(true, true),
+            Interactivity::NonInteractive
+        );
+        assert_eq!(
+            Interactivity::decide(false, false),
+            Interactivity::NonInteractive
+        );
+        assert!(!Interactivity::decide(true, false).is_interactive());
+    }
+
+    #[test]
+    fn confirm_takes_only_yes() {
+        for (typed, answer) in [
+            ("y\n", true),
+            (" YES \n", true),
+            ("n\n", false),
+            ("\n", false),
+            ("yep\n", false),
+            ("", false),
+        ] {
+            let mut out = Vec::new();
+            let confirmed = confirm("send it?", &mut Cursor::new(typed), &mut out).unwrap();
+            assert_eq!(confirmed, answer, "{:?}", typed);
+            assert_eq!(out, b"send it? [y/N]: ");
+        }
+    }
+
+    #[test]
+    fn the_summary_names_the_destination_the_size_and_the_files() {
+        let diff = |path: &str| format!("diff --git a/{0} b/{0}\n+a line\n", path);
+        let prompt: String = (0..12).map(|i| diff(&format!("src/f{}.rs", i))).collect();
+        let summary = send_summary("gemini", "gemini-2.0-flash", "system", &prompt);
+        let bytes = "system".len() + prompt.len();
+        let mut lines = summary.lines().skip(1);
+        assert_eq!(
+            lines.next().unwrap(),
+            format!(
+                "about to send to gemini (gemini-2.0-flash): 12 files, {:.1} KB (about {} tokens)",
+                bytes as f64 / 1024.0,
+                bytes / 4
+            )
+        );
+        let listed: Vec<&str> = lines.collect();
+        assert_eq!(listed.len(), LISTED_FILES + 1);
+        assert_eq!(listed[0], "    src/f0.rs");
+        assert_eq!(listed[LISTED_FILES - 1], "    src/f9.rs");
+        assert_eq!(listed[LISTED_FILES], "    ... and 2 more");
+
+        let summary = send_summary("gemini", "m", "", &diff("old name"));
+        assert!(summary.ends_with(": 1 files, 0.0 KB (about 10 tokens)\n    old name\n"));
+    }
+}
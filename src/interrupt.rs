@@ -0,0 +1,189 @@
+// src/interrupt.rs
+// Ctrl-C: the request in flight is dropped and the run returns Interrupted, which main turns
+// into 130, so what the run holds is cleaned up on the way out. A file or commit being written
+// is finished first, so none is left half done. A run blocked on a question at the terminal
+// can't return, and neither does one pressed twice: main restores the terminal and ends it.
+// While the editor runs, Ctrl-C is the editor's, as it is under git
+
+use crate::error::GitChangesError;
+use crate::spinner;
+use std::fs::File;
+use std::future::Future;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use tokio::sync::Notify;
+
+static PRESSED: AtomicBool = AtomicBool::new(false);
+static WRITING: AtomicUsize = AtomicUsize::new(0);
+static PROMPTING: AtomicUsize = AtomicUsize::new(0);
+static EDITING: AtomicUsize = AtomicUsize::new(0);
+// woken on the first press, for what is waiting in `cancelled`
+static CANCEL: Notify = Notify::const_new();
+// woken when the run has to end without returning, for main
+static FORCE: Notify = Notify::const_new();
+// the terminal's settings from before the first open question, put back when it ends
+static SAVED_TERMINAL: Mutex<Option<String>> = Mutex::new(None);
+
+// called once, inside the runtime
+pub fn install() {
+    tokio::spawn(async {
+        let mut presses = 0;
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if EDITING.load(Ordering::SeqCst) > 0 {
+                continue;
+            }
+            presses += 1;
+            // set before looking at WRITING, which a writer raises before looking at PRESSED,
+            // so one of the two always sees the other
+            PRESSED.store(true, Ordering::SeqCst);
+            CANCEL.notify_waiters();
+            let writing = WRITING.load(Ordering::SeqCst) > 0;
+            if presses > 1 || (!writing && PROMPTING.load(Ordering::SeqCst) > 0) {
+                FORCE.notify_one();
+            }
+        }
+    });
+}
+
+pub fn pressed() -> bool {
+    PRESSED.load(Ordering::SeqCst)
+}
+
+// resolves once Ctrl-C has been pressed
+pub async fn cancelled() {
+    loop {
+        // registered before the flag is read, so a press in between still wakes it
+        let notified = CANCEL.notified();
+        if pressed() {
+            return;
+        }
+        notified.await;
+    }
+}
+
+// `future`, or Interrupted once Ctrl-C is pressed, dropping it wherever it is waiting
+pub async fn cancellable<T, E, F>(future: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: From<GitChangesError>,
+{
+    tokio::select! {
+        biased;
+        _ = cancelled() => Err(GitChangesError::Interrupted.into()),
+        result = future => result,
+    }
+}
+
+// resolves when the run has to end without returning: a second press, or one while a question
+// waits on the terminal
+pub async fn forced() {
+    FORCE.notified().await;
+}
+
+// the terminal as it was before the questions, with the spinner and the unanswered question
+// line cleared away; for main, before it ends the run
+pub fn restore_terminal() {
+    spinner::clear();
+    let saved = SAVED_TERMINAL.lock().ok().and_then(|mut saved| saved.take());
+    if let Some(settings) = saved {
+        set_terminal(&settings);
+        if let Ok(mut tty) = File::options().write(true).open("/dev/tty") {
+            let _ = writeln!(tty);
+        }
+    }
+}
+
+// held while something must not be left half written; an interruption meanwhile is noticed at
+// the next step, and one from before stops the run before the write starts
+pub struct Writing(());
+
+pub fn writing() -> Result<Writing, GitChangesError> {
+    WRITING.fetch_add(1, Ordering::SeqCst);
+    let writing = Writing(());
+    if pressed() {
+        return Err(GitChangesError::Interrupted);
+    }
+    Ok(writing)
+}
+
+impl Drop for Writing {
+    fn drop(&mut self) {
+        WRITING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// held by an open terminal, which a question blocks on; the first one saves the terminal's
+// settings, and the last one puts them back if the run was interrupted meanwhile
+pub struct Prompting(());
+
+pub fn prompting(tty: &File) -> Prompting {
+    if PROMPTING.fetch_add(1, Ordering::SeqCst) == 0 {
+        if let Some(settings) = terminal_settings(tty) {
+            if let Ok(mut saved) = SAVED_TERMINAL.lock() {
+                *saved = Some(settings);
+            }
+        }
+    }
+    Prompting(())
+}
+
+impl Drop for Prompting {
+    fn drop(&mut self) {
+        if PROMPTING.fetch_sub(1, Ordering::SeqCst) == 1 {
+            let saved = SAVED_TERMINAL.lock().ok().and_then(|mut saved| saved.take());
+            if let Some(settings) = saved.filter(|_| pressed()) {
+                set_terminal(&settings);
+            }
+        }
+    }
+}
+
+// held while the editor runs; Ctrl-C meanwhile is left to it
+pub struct Editing(());
+
+pub fn editing() -> Editing {
+    EDITING.fetch_add(1, Ordering::SeqCst);
+    Editing(())
+}
+
+impl Drop for Editing {
+    fn drop(&mut self) {
+        EDITING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(unix)]
+fn terminal_settings(tty: &File) -> Option<String> {
+    let output = std::process::Command::new("stty")
+        .arg("-g")
+        .stdin(tty.try_clone().ok()?)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    let settings = String::from_utf8(output.stdout).ok()?;
+    output
+        .status
+        .success()
+        .then(|| settings.trim().to_string())
+}
+
+#[cfg(not(unix))]
+fn terminal_settings(_tty: &File) -> Option<String> {
+    None
+}
+
+#[cfg(unix)]
+fn set_terminal(settings: &str) {
+    let Ok(tty) = File::options().read(true).write(true).open("/dev/tty") else {
+        return;
+    };
+    let _ = std::process::Command::new("stty")
+        .arg(settings)
+        .stdin(tty)
+        .stderr(std::process::Stdio::null())
+        .status();
+}
+
+#[cfg(not(unix))]
+fn set_terminal(_settings: &str) {}
@@ -0,0 +1,74 @@
+// src/invocation.rs
+// how the tool was started: as `git changes` (git runs a `git-changes` binary on PATH for that)
+// or under its own name, and the directories git hands over in GIT_PREFIX, GIT_DIR and
+// GIT_WORK_TREE
+
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// the binary name git looks for when it runs `git changes`
+const GIT_SUBCOMMAND: &str = "git-changes";
+// paths git exports that are relative to the directory it ran the tool in
+const GIT_PATH_VARIABLES: [&str; 3] = ["GIT_DIR", "GIT_WORK_TREE", "GIT_INDEX_FILE"];
+
+// true when argv[0] is `git-changes`, i.e. the tool runs as `git changes`
+pub fn as_git_subcommand() -> bool {
+    env::args_os()
+        .next()
+        .map(PathBuf::from)
+        .and_then(|path| path.file_stem().map(|stem| stem == GIT_SUBCOMMAND))
+        .unwrap_or(false)
+}
+
+// the name usage lines and --help show
+pub fn program_name() -> &'static str {
+    match as_git_subcommand() {
+        true => "git changes",
+        false => "git-changes-rs",
+    }
+}
+
+// moves to the directory the user meant before any relative path is read. A `!` alias runs at
+// the top of the working tree with the directory it was typed in as GIT_PREFIX, so that comes
+// first (except in the hook, whose message file is relative to where git started it); then -C,
+// relative to that, like git's own. GIT_DIR and friends are made absolute beforehand so they
+// keep naming the same place
+pub fn enter(chdir: Option<&str>, hook: bool) -> Result<()> {
+    let prefix = env::var("GIT_PREFIX")
+        .ok()
+        .filter(|prefix| !prefix.is_empty() && !hook);
+    let targets: Vec<&str> = prefix.as_deref().into_iter().chain(chdir).collect();
+    if targets.is_empty() {
+        return Ok(());
+    }
+    let current = env::current_dir().context("failed to read the current directory")?;
+    for variable in GIT_PATH_VARIABLES {
+        if let Some(value) = env::var_os(variable).filter(|value| !value.is_empty()) {
+            env::set_var(variable, current.join(value));
+        }
+    }
+    for target in targets {
+        env::set_current_dir(target).with_context(|| format!("cannot change to '{}'", target))?;
+    }
+    Ok(())
+}
+
+// the repository of a directory argument. With GIT_DIR set, as git sets it for
+// `git --git-dir=<path> changes`, the current directory means that repository, with
+// GIT_WORK_TREE as its working tree; any other directory is looked up as usual
+pub fn open(directory: &str) -> Result<Repository, git2::Error> {
+    if env::var_os("GIT_DIR").is_some_and(|dir| !dir.is_empty()) && is_current(directory) {
+        return Repository::open_from_env();
+    }
+    Repository::discover(directory)
+}
+
+fn is_current(directory: &str) -> bool {
+    let canonical = |path: &Path| fs::canonicalize(path).ok();
+    let current = env::current_dir().ok();
+    canonical(Path::new(directory))
+        .is_some_and(|dir| current.and_then(|c| canonical(&c)) == Some(dir))
+}
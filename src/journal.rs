@@ -0,0 +1,203 @@
+// src/journal.rs
+// the log of generated messages behind `history`: one JSON line per message in the user's state
+// directory, with where and for what it was generated and what became of it. The diff itself is
+// never written, only its hash; privacy.history = false turns the log off. The log is readable
+// by its owner only, like the files output.rs writes.
+//
+// Nothing is locked. Concurrent writers rely on each entry being a single append, and rotation
+// is a plain rename: two writers that both find the log full can both rename it, and the second
+// rename then replaces history.jsonl.1 with the few entries written in between, losing the
+// older ones. For a log of suggestions that is an accepted loss
+
+use crate::{cache, changelog_file, interrupt, paths, select};
+use anyhow::{anyhow, Context, Result};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const STATE_DIR: &str = "git-changes";
+const HISTORY_FILE: &str = "history.jsonl";
+// past this the log moves to history.jsonl.1, replacing the one before
+const MAX_BYTES: u64 = 1024 * 1024;
+// how much of an id `history` shows, like a short commit id
+pub const SHORT_ID: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    // printed or handed on, with nobody asked about it
+    Suggested,
+    // turned away at the review or candidate prompt
+    Rejected,
+    // taken at a prompt, or edited
+    Accepted,
+    Committed,
+}
+
+impl Outcome {
+    pub fn label(self) -> &'static str {
+        match self {
+            Outcome::Suggested => "suggested",
+            Outcome::Rejected => "rejected",
+            Outcome::Accepted => "accepted",
+            Outcome::Committed => "committed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    // seconds since the epoch, UTC
+    pub time: u64,
+    // the working tree, or the git dir of a bare repository
+    pub repo: String,
+    pub branch: String,
+    pub diff_hash: String,
+    pub model: String,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+    pub message: String,
+}
+
+impl Entry {
+    pub fn short_id(&self) -> &str {
+        &self.id[..self.id.len().min(SHORT_ID)]
+    }
+
+    // YYYY-MM-DD HH:MM in UTC
+    pub fn date(&self) -> String {
+        let (year, month, day) = changelog_file::civil_date((self.time / 86_400) as i64);
+        let minutes = self.time % 86_400 / 60;
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            year,
+            month,
+            day,
+            minutes / 60,
+            minutes % 60
+        )
+    }
+}
+
+// $XDG_STATE_HOME, ~/.local/state or %LOCALAPPDATA%
+fn state_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| match cfg!(windows) {
+            true => env::var_os("LOCALAPPDATA").map(PathBuf::from),
+            false => env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")),
+        })?;
+    Some(base.join(STATE_DIR))
+}
+
+pub fn history_path() -> Option<PathBuf> {
+    state_dir().map(|dir| dir.join(HISTORY_FILE))
+}
+
+fn rotated_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".1");
+    path.with_file_name(name)
+}
+
+// what `history` matches entries on
+pub fn repo_key(repo: &Repository) -> String {
+    paths::display(repo.workdir().unwrap_or_else(|| repo.path()))
+}
+
+pub fn entry(
+    repo: &Repository,
+    diff: &str,
+    model: &str,
+    message: &str,
+    outcome: Outcome,
+    commit: Option<Oid>,
+) -> Entry {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let repo_key = repo_key(repo);
+    let seed = format!("{}\n{}\n{}", now.as_nanos(), repo_key, message);
+    Entry {
+        id: format!("{:016x}", cache::fnv1a(seed.as_bytes())),
+        time: now.as_secs(),
+        repo: repo_key,
+        branch: select::branch_key(repo),
+        diff_hash: cache::diff_hash(diff),
+        model: model.to_string(),
+        outcome,
+        commit: commit.map(|oid| oid.to_string()),
+        message: message.to_string(),
+    }
+}
+
+// appends one line. Hooks of several repositories may write at once: each entry goes out as a
+// single append, which the OS doesn't interleave, and reading skips any line that doesn't parse
+pub fn append(entry: &Entry) -> Result<()> {
+    let path = history_path().ok_or_else(|| anyhow!("no state directory (HOME isn't set)"))?;
+    let _writing = interrupt::writing()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create state directory {}", dir.display()))?;
+    }
+    // whoever finds the log full moves it; the next writer then finds a small file
+    if fs::metadata(&path).is_ok_and(|meta| meta.len() > MAX_BYTES) {
+        fs::rename(&path, rotated_path(&path))
+            .with_context(|| format!("failed to rotate {}", path.display()))?;
+    }
+    let mut line = serde_json::to_string(entry).context("failed to serialize history entry")?;
+    line.push('\n');
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    file.write_all(line.as_bytes())
+        .with_context(|| format!("failed to write {}", path.display()))
+}
+
+// every entry, oldest first, from the rotated log and the current one
+pub fn load() -> Vec<Entry> {
+    let Some(path) = history_path() else {
+        return Vec::new();
+    };
+    [rotated_path(&path), path]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .flat_map(|text| {
+            text.lines()
+                .filter_map(|line| serde_json::from_str::<Entry>(line).ok())
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+// the entry whose id starts with `prefix`; an error when none or several do
+pub fn find<'a>(entries: &'a [Entry], prefix: &str) -> Result<&'a Entry> {
+    let prefix = prefix.trim().to_ascii_lowercase();
+    if prefix.is_empty() {
+        return Err(anyhow!("no history id given"));
+    }
+    let matches: Vec<&Entry> = entries
+        .iter()
+        .filter(|entry| entry.id.starts_with(&prefix))
+        .collect();
+    match matches.as_slice() {
+        [entry] => Ok(entry),
+        [] => Err(anyhow!("no history entry with id {}", prefix)),
+        several => Err(anyhow!(
+            "{} history entries start with {}; give more of the id",
+            several.len(),
+            prefix
+        )),
+    }
+}
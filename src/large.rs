@@ -0,0 +1,113 @@
+// src/large.rs
+// the guard against sending a diff far bigger than a commit usually is, such as one with
+// node_modules or a regenerated lockfile staged by accident
+
+pub const DEFAULT_MAX_FILES: usize = 200;
+pub const DEFAULT_MAX_KB: usize = 500;
+// the biggest files named in the warning
+const LISTED_FILES: usize = 10;
+
+// either limit at 0 is off
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub files: usize,
+    pub kb: usize,
+}
+
+// what was too big about the request, with the files that contributed most; none when it fits
+pub fn check(limits: Limits, files: &[(&str, usize)], prompt_bytes: usize) -> Option<String> {
+    let kb = prompt_bytes.div_ceil(1024);
+    let mut over = Vec::new();
+    if limits.files > 0 && files.len() > limits.files {
+        over.push(format!("{} files (limit {})", files.len(), limits.files));
+    }
+    if limits.kb > 0 && kb > limits.kb {
+        over.push(format!("{} KB of prompt (limit {} KB)", kb, limits.kb));
+    }
+    if over.is_empty() {
+        return None;
+    }
+
+    let mut report = format!("the diff is unusually large: {}", over.join(", "));
+    report.push_str("\nbiggest files:");
+    for (path, bytes) in biggest(files) {
+        report.push_str(&format!(
+            "\n    {:>8.1} KB  {}",
+            bytes as f64 / 1024.0,
+            path
+        ));
+    }
+    Some(report)
+}
+
+fn biggest<'a>(files: &[(&'a str, usize)]) -> Vec<(&'a str, usize)> {
+    let mut sorted = files.to_vec();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    sorted.truncate(LISTED_FILES);
+    sorted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIMITS: Limits = Limits { files: 3, kb: 4 };
+
+    #[test]
+    fn a_diff_inside_the_limits_passes() {
+        let files = [("a.rs", 100), ("b.rs", 200), ("c.rs", 300)];
+        assert_eq!(check(LIMITS, &files, 4 * 1024), None);
+        let off = Limits { files: 0, kb: 0 };
+        assert_eq!(check(off, &[("a.rs", 1 << 30); 5000], 1 << 30), None);
+    }
+
+    #[test]
+    fn each_limit_is_reported_with_the_biggest_files() {
+        let files = [("a.rs", 100), ("b.rs", 2048), ("c.rs", 300), ("d.rs", 5000)];
+        assert_eq!(
+            check(LIMITS, &files, 4 * 1024).unwrap(),
+            "the diff is unusually large: 4 files (limit 3)\n\
+             biggest files:\n\
+             \x20        4.9 KB  d.rs\n\
+             \x20        2.0 KB  b.rs\n\
+             \x20        0.3 KB  c.rs\n\
+             \x20        0.1 KB  a.rs"
+        );
+        // a byte over is a kilobyte over
+        let report = check(LIMITS, &files[..1], 4 * 1024 + 1).unwrap();
+        assert!(report.starts_with("the diff is unusually large: 5 KB of prompt (limit 4 KB)\n"));
+        let report = check(LIMITS, &files, 10 * 1024).unwrap();
+        assert!(report.starts_with(
+            "the diff is unusually large: 4 files (limit 3), 10 KB of prompt (limit 4 KB)\n"
+        ));
+    }
+
+    #[test]
+    fn an_accidental_node_modules_names_only_the_ten_biggest() {
+        let paths: Vec<String> = (0..3000)
+            .map(|i| format!("node_modules/pkg{}/index.js", i))
+            .collect();
+        let files: Vec<(&str, usize)> = paths
+            .iter()
+            .enumerate()
+            .map(|(i, path)| (path.as_str(), 1000 + i % 7 * 100))
+            .collect();
+        let limits = Limits {
+            files: DEFAULT_MAX_FILES,
+            kb: DEFAULT_MAX_KB,
+        };
+        let report = check(limits, &files, 3_300_000).unwrap();
+        assert!(report.starts_with(
+            "the diff is unusually large: 3000 files (limit 200), 3223 KB of prompt (limit 500 KB)\n"
+        ));
+        let listed: Vec<&str> = report.lines().skip(2).collect();
+        assert_eq!(listed.len(), LISTED_FILES);
+        // the biggest, ties in path order
+        assert!(listed.iter().all(|line| line.contains("1.6 KB")));
+        assert!(
+            listed[0].ends_with("node_modules/pkg1000/index.js"),
+            "{}",
+            listed[0]
+        );
+    }
+}
@@ -0,0 +1,136 @@
+// src/lib.rs
+//! Commit messages from a repository's changes, written by a language model.
+//!
+//! The binary is a thin layer over this crate. The same pipeline is here for other tools: the
+//! changes are collected with [`diff::collect_changes`], turned into a [`prompt::Prompt`] by
+//! [`prompt::build_prompt`], and sent to a [`provider::Provider`] by [`message::generate`],
+//! which reformats the answer and, with [`message::Rules`], checks it.
+//!
+//! ```no_run
+//! use git_changes_rs::diff::{self, CollectOptions, DiffMode};
+//! use git_changes_rs::message::{self, FormatOptions, Rules};
+//! use git_changes_rs::prompt::{self, PromptOptions};
+//! use git_changes_rs::provider::{Gemini, Sampling};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! let repo = git2::Repository::discover(".")?;
+//! let changes = diff::collect_changes(&repo, &CollectOptions::new(DiffMode::Staged))?;
+//! let prompt = prompt::build_prompt(&changes, &PromptOptions::default());
+//! // reads GEMINI_API_KEY
+//! let gemini = Gemini::new(Sampling::default());
+//! let rules = Rules::default();
+//! let message = message::generate(&gemini, &prompt, &FormatOptions::default(), Some(&rules)).await?;
+//! println!("{}", message);
+//! # Ok(())
+//! # }
+//! ```
+// stdout carries only the result; everything written to it goes through output::print
+#![deny(missing_docs)]
+#![deny(clippy::print_stdout)]
+
+pub use error::GitChangesError;
+
+use std::sync::atomic::AtomicBool;
+
+// progress lines of a run, on stderr so stdout carries only the result
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::QUIET.load(::std::sync::atomic::Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        }
+    };
+}
+
+// the result on stdout: the message, a report, a list. Like print! and println!, but they
+// return a Result to pass up with `?`; a reader that goes away (`| head`) ends the run quietly
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::print(&format!($($arg)*))
+    };
+}
+
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        $crate::output::print(&format!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+#[doc(hidden)]
+pub mod app;
+mod attach;
+mod branch;
+mod bullets;
+mod bump;
+mod cache;
+mod changelog;
+mod changelog_file;
+mod cherry_pick;
+#[doc(hidden)]
+pub mod cli;
+mod clipboard;
+mod code_review;
+mod commands;
+mod commit;
+pub mod config;
+mod context;
+mod conventional;
+pub mod diff;
+mod editor;
+pub mod error;
+mod exit;
+mod explain;
+pub mod filter;
+mod fixup;
+mod format;
+#[cfg(feature = "gemini")]
+mod gemini;
+mod github;
+mod gitlab;
+mod history;
+mod hook;
+mod interactivity;
+mod interrupt;
+mod invocation;
+mod journal;
+mod large;
+mod lint;
+mod logging;
+mod merge;
+pub mod message;
+#[cfg(feature = "test-util")]
+pub mod mock;
+mod notes;
+mod operation;
+mod output;
+mod pager;
+mod paths;
+mod per_file;
+mod picker;
+mod pr;
+pub mod prompt;
+pub mod provider;
+mod push;
+mod release_notes;
+mod report;
+mod revert;
+mod review;
+mod reword;
+mod rust_api;
+mod select;
+mod sign;
+mod spinner;
+mod split;
+mod squash;
+mod standup;
+mod stash;
+mod style;
+mod tag;
+mod template;
+mod timings;
+mod trailers;
+mod tree;
+mod validate;
+
+// -q drops the progress lines; diagnostics go through the logger (see logging.rs)
+pub(crate) static QUIET: AtomicBool = AtomicBool::new(false);
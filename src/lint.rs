@@ -0,0 +1,108 @@
+// src/lint.rs
+// checks existing commit messages against the same rules used for generated ones
+
+use crate::history;
+use crate::prompt;
+use crate::validate::{self, Rules, Violation};
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+
+pub struct LintFailure {
+    pub oid: Oid,
+    pub message: String,
+    pub violations: Vec<Violation>,
+}
+
+impl LintFailure {
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+}
+
+pub struct LintReport {
+    pub checked: usize,
+    pub skipped_merges: usize,
+    pub failures: Vec<LintFailure>,
+}
+
+pub fn lint_range(repo: &Repository, range: Option<&str>, rules: &Rules) -> Result<LintReport> {
+    let oids = history::commits_in_range(repo, range)?;
+
+    let mut report = LintReport {
+        checked: 0,
+        skipped_merges: 0,
+        failures: Vec::new(),
+    };
+
+    for oid in oids {
+        let commit = repo
+            .find_commit(oid)
+            .with_context(|| format!("failed to look up commit {}", oid))?;
+
+        // merge commits carry git's own generated messages
+        if commit.parent_count() > 1 {
+            report.skipped_merges += 1;
+            continue;
+        }
+
+        let message = String::from_utf8_lossy(commit.message_bytes())
+            .trim_end()
+            .to_string();
+        report.checked += 1;
+
+        let violations = validate::validate(&message, rules);
+        if !violations.is_empty() {
+            report.failures.push(LintFailure {
+                oid,
+                message,
+                violations,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+pub fn print_report(report: &LintReport) -> Result<()> {
+    for failure in &report.failures {
+        outln!("{} {}", history::short_id(failure.oid), failure.subject())?;
+        for violation in &failure.violations {
+            outln!("  {}", violation)?;
+        }
+    }
+
+    let mut summary = format!(
+        "{} of {} commits failed",
+        report.failures.len(),
+        report.checked
+    );
+    if report.skipped_merges > 0 {
+        summary.push_str(&format!(
+            " ({} merge commits skipped)",
+            report.skipped_merges
+        ));
+    }
+    outln!("{}", summary)
+}
+
+// prompt asking the model to repair one failing message, using the files it touched as context
+pub fn fix_prompt(repo: &Repository, failure: &LintFailure) -> Result<String> {
+    let diff = history::diff_to_parent(repo, failure.oid)?;
+    let files: Vec<String> = history::changed_paths(&diff)
+        .iter()
+        .map(|p| format!("- {}", p))
+        .collect();
+
+    let problems: Vec<String> = failure
+        .violations
+        .iter()
+        .map(|v| format!("- {}", v))
+        .collect();
+
+    Ok(format!(
+        "The existing commit message below does not follow the conventional commits format.\n\n{}\n\nProblems:\n{}\n\nFiles changed by the commit:\n{}\n\nReturn only a corrected commit message that keeps the original intent and fixes these problems.",
+        prompt::previous_message_section(&failure.message),
+        problems.join("\n"),
+        files.join("\n")
+    ))
+}
@@ -0,0 +1,85 @@
+// src/logging.rs
+// diagnostics on stderr through `tracing`: warnings by default, more with -v, -vv and -vvv,
+// or whatever RUST_LOG asks for. The result itself is printed directly, never logged
+
+use crate::{style, timings};
+use std::fmt;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::filter::{self, EnvFilter};
+use tracing_subscriber::fmt::format::{FmtSpan, Writer};
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+
+const CRATE_TARGET: &str = "git_changes_rs";
+
+// -v shows what a run decided (patterns, style, targets, each request and its tokens), -vv adds
+// the details and the prompts, -vvv how long each span (collect_diff, generate, request) took.
+// `timings` adds the table of phases at the end, which -vv prints too
+pub fn init(verbosity: u8, timings: bool) {
+    let level = match verbosity {
+        0 => Level::WARN,
+        1 => Level::INFO,
+        2 => Level::DEBUG,
+        _ => Level::TRACE,
+    };
+    // other crates stay at warnings unless RUST_LOG names them
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(format!("warn,{}={}", CRATE_TARGET, level)));
+    let spans = if verbosity > 2 {
+        FmtSpan::CLOSE
+    } else {
+        FmtSpan::NONE
+    };
+    let diagnostics = tracing_subscriber::fmt::layer()
+        .with_span_events(spans)
+        .with_writer(std::io::stderr)
+        .event_format(Diagnostics)
+        .with_filter(filter);
+    // the phases are this crate's spans at every level, whatever the log level lets through
+    let timings = (timings || verbosity > 1).then(|| {
+        timings::layer().with_filter(filter::filter_fn(|metadata| {
+            metadata.is_span() && metadata.target().starts_with(CRATE_TARGET)
+        }))
+    });
+    tracing_subscriber::registry()
+        .with(diagnostics)
+        .with(timings)
+        .init();
+}
+
+// `warning: ...` as before, and `>>> module: span: message` for the rest
+struct Diagnostics;
+
+impl<S, N> FormatEvent<S, N> for Diagnostics
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        let metadata = event.metadata();
+        match *metadata.level() {
+            Level::ERROR => write!(writer, "{} ", style::error("error:"))?,
+            Level::WARN => write!(writer, "{} ", style::warning("warning:"))?,
+            _ => {
+                let module = match metadata.target() {
+                    CRATE_TARGET => "main",
+                    target => target.rsplit("::").next().unwrap_or(target),
+                };
+                write!(writer, ">>> {}: ", module)?;
+                if let Some(scope) = ctx.event_scope() {
+                    for span in scope.from_root() {
+                        write!(writer, "{}: ", span.name())?;
+                    }
+                }
+            }
+        }
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
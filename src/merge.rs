@@ -0,0 +1,162 @@
+// src/merge.rs
+// merge messages: describing an in-progress merge from MERGE_HEAD, MERGE_MSG and what
+// conflicted along the way
+
+use crate::{attach, history, operation, squash};
+use anyhow::Result;
+use git2::{Oid, Repository};
+use std::fs;
+
+const MERGE_HEAD: &str = "MERGE_HEAD";
+const MERGE_MSG: &str = "MERGE_MSG";
+
+// the diff is the whole branch as it lands, which can be long
+const MAX_MERGE_DIFF_BYTES: usize = 48 * 1024;
+
+// enough subjects to see what the branch was for
+const MAX_SUBJECTS: usize = 50;
+
+pub const MERGE_SYSTEM_PROMPT: &str = "You write the message for a merge commit. This is not a conventional commit: the subject line is given to you and must be used exactly as is. After one blank line, write a short body in plain prose (wrapped at 72 columns) explaining what the merged branch brings in, drawing on its commit subjects, and, if there were conflicts, which files conflicted and how the resolution combines both sides as far as the diff shows it. Do not list every commit, do not use bullet points unless there are several unrelated changes, and do not invent details the input doesn't show. Reply with the message only, without code fences.";
+
+#[derive(Debug, Clone)]
+pub struct PendingMerge {
+    pub title: String,
+    pub heads: Vec<Oid>,
+    // the merged commits HEAD didn't have, oldest first
+    pub subjects: Vec<String>,
+    pub conflicts: Vec<String>,
+    // paths still marked as conflicted in the index; the commit will fail until they're added
+    pub unresolved: Vec<String>,
+}
+
+// the merge git is in the middle of, if any
+pub fn pending(repo: &Repository) -> Result<Option<PendingMerge>> {
+    let Ok(text) = fs::read_to_string(repo.path().join(MERGE_HEAD)) else {
+        return Ok(None);
+    };
+    let heads: Vec<Oid> = text
+        .lines()
+        .filter_map(|line| Oid::from_str(line.trim()).ok())
+        .collect();
+    if heads.is_empty() {
+        return Ok(None);
+    }
+    let merge_msg = fs::read_to_string(repo.path().join(MERGE_MSG)).unwrap_or_default();
+
+    let mut subjects = Vec::new();
+    for head in &heads {
+        subjects.extend(squash::branch_subjects(repo, &head.to_string())?);
+    }
+
+    let unresolved = operation::unresolved(repo)?;
+    let conflicts = if unresolved.is_empty() {
+        conflict_section(&merge_msg)
+    } else {
+        unresolved.clone()
+    };
+
+    Ok(Some(PendingMerge {
+        title: title(repo, &merge_msg, &heads),
+        heads,
+        subjects,
+        conflicts,
+        unresolved,
+    }))
+}
+
+// git's own subject ("Merge branch 'x'"), made to name the branch merged into as well
+fn title(repo: &Repository, merge_msg: &str, heads: &[Oid]) -> String {
+    let into = repo
+        .head()
+        .ok()
+        .filter(|head| head.is_branch())
+        .and_then(|head| head.shorthand().map(str::to_string));
+    let subject = merge_msg
+        .lines()
+        .next()
+        .map(str::trim)
+        .filter(|line| line.starts_with("Merge "))
+        .map(str::to_string)
+        .unwrap_or_else(|| {
+            let names: Vec<String> = heads
+                .iter()
+                .map(|oid| format!("'{}'", history::short_id(*oid)))
+                .collect();
+            format!("Merge commit {}", names.join(" and "))
+        });
+    match into {
+        Some(into) if !subject.contains(" into ") => format!("{} into {}", subject, into),
+        _ => subject,
+    }
+}
+
+// the "Conflicts:" list git adds to MERGE_MSG, commented out ("# Conflicts:\n#\tpath") in
+// current versions and plain in older ones
+fn conflict_section(merge_msg: &str) -> Vec<String> {
+    let lines = merge_msg
+        .lines()
+        .map(|line| line.strip_prefix('#').unwrap_or(line))
+        .skip_while(|line| line.trim() != "Conflicts:")
+        .skip(1);
+    let mut paths = Vec::new();
+    for line in lines {
+        if line.trim().is_empty() && paths.is_empty() {
+            continue;
+        }
+        match line.strip_prefix('\t') {
+            Some(path) if !path.trim().is_empty() => paths.push(path.trim().to_string()),
+            _ => break,
+        }
+    }
+    paths
+}
+
+pub fn merge_prompt(merge: &PendingMerge, diff: &str, files: &[String]) -> String {
+    let mut prompt = format!("Subject line to use: {}\n\n", merge.title);
+    if merge.subjects.is_empty() {
+        prompt.push_str("The merged branch has no commits of its own beyond merges.\n");
+    } else {
+        prompt.push_str("Commits being merged, oldest first:\n");
+        for subject in merge.subjects.iter().take(MAX_SUBJECTS) {
+            prompt.push_str(&format!("- {}\n", subject));
+        }
+        if merge.subjects.len() > MAX_SUBJECTS {
+            prompt.push_str(&format!(
+                "- ... and {} more\n",
+                merge.subjects.len() - MAX_SUBJECTS
+            ));
+        }
+    }
+    if !merge.conflicts.is_empty() {
+        prompt.push_str("\nFiles that conflicted and were resolved:\n");
+        for path in &merge.conflicts {
+            prompt.push_str(&format!("- {}\n", path));
+        }
+    }
+    let listed: Vec<String> = files.iter().map(|f| format!("- {}", f)).collect();
+    prompt.push_str(&format!(
+        "\nFiles the merge changes:\n{}\n\n```diff\n{}\n```",
+        listed.join("\n"),
+        attach::truncate_middle(diff, MAX_MERGE_DIFF_BYTES)
+    ));
+    prompt
+}
+
+// the generated message under the subject we asked for, whatever the model wrote as its own
+pub fn with_title(merge: &PendingMerge, message: &str) -> String {
+    // a long subject may have been wrapped onto the next lines, so its whole paragraph goes
+    let body = match message.trim_start().starts_with("Merge ") {
+        true => message
+            .trim_start()
+            .split_once("\n\n")
+            .map(|(_, body)| body)
+            .unwrap_or_default(),
+        false => message,
+    };
+    let body = body.trim();
+    if body.is_empty() {
+        merge.title.clone()
+    } else {
+        format!("{}\n\n{}", merge.title, body)
+    }
+}
@@ -0,0 +1,269 @@
+// src/message.rs
+//! From a [`Prompt`] to a finished message: the model's answer is reformatted (subject cap,
+//! body wrapping) and, with [`Rules`], checked and re-asked for while it breaks them. An answer
+//! with nothing in it is asked for once more before it counts as a failure.
+
+pub use crate::format::FormatOptions;
+pub use crate::validate::Rules;
+
+use crate::error::{GitChangesError, ProviderFailure};
+use crate::prompt::{Prompt, PromptOptions};
+use crate::provider::{Gemini, Provider};
+use crate::{format, interrupt, validate};
+use anyhow::{anyhow, Result};
+use tracing::{instrument, warn};
+
+// corrective re-asks after the first attempt fails validation
+const MAX_VALIDATION_RETRIES: usize = 2;
+
+// added to the prompt when asking again after an empty answer
+const EMPTY_NUDGE: &str = "Respond with only the commit message.";
+
+/// Asks `provider` for a message and formats it. With `rules`, a message that breaks them is
+/// asked for again with the problems listed, twice at most, and is a
+/// [`GitChangesError::Validation`] after that. An [empty](ProviderFailure::Empty) answer is asked
+/// for once more, with a nudge and [`Provider::generate_varied`], and stays an empty failure
+/// when that one is empty too. In the binary, Ctrl-C drops the request and returns
+/// [`GitChangesError::Interrupted`].
+///
+/// ```
+/// use git_changes_rs::message::{self, FormatOptions, Rules};
+/// use git_changes_rs::prompt::Prompt;
+/// use git_changes_rs::provider::Provider;
+/// use git_changes_rs::GitChangesError;
+///
+/// // a provider with a canned answer, as a test would use
+/// struct Canned;
+///
+/// impl Provider for Canned {
+///     async fn candidates(&self, _: &Prompt, _: u32) -> Result<Vec<String>, GitChangesError> {
+///         Ok(vec!["feat(notes): add a notes file\n\n- start the notes".to_string()])
+///     }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), GitChangesError> {
+/// let prompt = Prompt {
+///     system: "Write a conventional commit message.".to_string(),
+///     user: "diff --git a/notes.txt b/notes.txt ...".to_string(),
+///     max_output_tokens: 512,
+///     title_only: false,
+/// };
+/// let rules = Rules::default();
+/// let message = message::generate(&Canned, &prompt, &FormatOptions::default(), Some(&rules)).await?;
+/// assert!(message.starts_with("feat(notes): add a notes file"));
+/// # Ok(())
+/// # }
+/// ```
+#[instrument(name = "generate", skip_all)]
+pub async fn generate<P: Provider>(
+    provider: &P,
+    prompt: &Prompt,
+    format_options: &FormatOptions,
+    rules: Option<&Rules>,
+) -> Result<String, GitChangesError> {
+    let mut request = prompt.clone();
+    let mut attempt = 0;
+
+    loop {
+        let response = interrupt::cancellable(answer(provider, &request)).await?;
+
+        let message = shape_response(&response, prompt, format_options);
+        let Some(rules) = rules else {
+            return Ok(message);
+        };
+
+        let violations = validate::validate(&message, rules);
+        if violations.is_empty() {
+            return Ok(message);
+        }
+
+        let problems: Vec<String> = violations.iter().map(|v| format!("  {}", v)).collect();
+        if attempt == MAX_VALIDATION_RETRIES {
+            return Err(GitChangesError::Validation {
+                attempts: attempt + 1,
+                problems,
+                message,
+            });
+        }
+
+        attempt += 1;
+        warn!(
+            "generated message failed validation, retrying ({}/{}):\n{}",
+            attempt,
+            MAX_VALIDATION_RETRIES,
+            problems.join("\n")
+        );
+        request.user = format!(
+            "{}\n\n{}",
+            prompt.user,
+            validate::corrective_instruction(&message, &violations)
+        );
+    }
+}
+
+/// Up to `count` different messages from one request, formatted. Candidates that break `rules`
+/// are dropped; when none is left, one message comes from [`generate`] instead.
+#[instrument(name = "generate", skip_all)]
+pub async fn candidates<P: Provider>(
+    provider: &P,
+    prompt: &Prompt,
+    format_options: &FormatOptions,
+    rules: Option<&Rules>,
+    count: u32,
+) -> Result<Vec<String>, GitChangesError> {
+    let responses = match interrupt::cancellable(provider.candidates(prompt, count)).await {
+        Err(error) if is_empty(&error) => Vec::new(),
+        responses => responses?,
+    };
+
+    let mut candidates = Vec::new();
+    for response in responses {
+        let message = shape_response(&response, prompt, format_options);
+        let violations = rules
+            .map(|rules| validate::validate(&message, rules))
+            .unwrap_or_default();
+        if violations.is_empty() {
+            if !candidates.contains(&message) {
+                candidates.push(message);
+            }
+        } else {
+            warn!(
+                "dropping a candidate that failed validation: {}",
+                violations[0]
+            );
+        }
+    }
+
+    if candidates.is_empty() {
+        candidates.push(generate(provider, prompt, format_options, rules).await?);
+    }
+    Ok(candidates)
+}
+
+// one answer, asked for again with a nudge and a freer draw when the first has nothing in it
+async fn answer<P: Provider>(provider: &P, prompt: &Prompt) -> Result<String, GitChangesError> {
+    match provider.generate(prompt).await {
+        Err(error) if is_empty(&error) => {
+            warn!("the model returned an empty answer, asking again");
+            let mut nudged = prompt.clone();
+            nudged.user = format!("{}\n\n{}", prompt.user, EMPTY_NUDGE);
+            provider
+                .generate_varied(&nudged)
+                .await
+                .map_err(|error| match error {
+                    GitChangesError::Provider {
+                        provider,
+                        failure: ProviderFailure::Empty,
+                        status,
+                        ..
+                    } => GitChangesError::Provider {
+                        provider,
+                        failure: ProviderFailure::Empty,
+                        status,
+                        retry_after_secs: None,
+                        message: format!("{} returned no message, even when asked again", provider),
+                        source: None,
+                    },
+                    error => error,
+                })
+        }
+        response => response,
+    }
+}
+
+fn is_empty(error: &GitChangesError) -> bool {
+    matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::Empty,
+            ..
+        }
+    )
+}
+
+// the commands' way in, with the prompt texts they built themselves; a test build answers
+// from the mock the environment describes, if it does
+pub(crate) async fn generate_message(
+    client: &Gemini,
+    system_prompt: &str,
+    user_prompt: &str,
+    prompt_options: &PromptOptions,
+    format_options: &FormatOptions,
+    rules: Option<&Rules>,
+) -> Result<String> {
+    let prompt = Prompt::from_texts(system_prompt, user_prompt, prompt_options);
+    #[cfg(feature = "test-util")]
+    if let Some(mock) = crate::mock::from_env()? {
+        return generate(mock, &prompt, format_options, rules)
+            .await
+            .map_err(command_error);
+    }
+    generate(client, &prompt, format_options, rules)
+        .await
+        .map_err(command_error)
+}
+
+pub(crate) async fn generate_candidates(
+    client: &Gemini,
+    system_prompt: &str,
+    user_prompt: &str,
+    prompt_options: &PromptOptions,
+    format_options: &FormatOptions,
+    rules: Option<&Rules>,
+    count: u32,
+) -> Result<Vec<String>> {
+    let prompt = Prompt::from_texts(system_prompt, user_prompt, prompt_options);
+    #[cfg(feature = "test-util")]
+    if let Some(mock) = crate::mock::from_env()? {
+        return candidates(mock, &prompt, format_options, rules, count)
+            .await
+            .map_err(command_error);
+    }
+    candidates(client, &prompt, format_options, rules, count)
+        .await
+        .map_err(command_error)
+}
+
+// with the hint and the context the commands have always shown
+fn command_error(error: GitChangesError) -> anyhow::Error {
+    match error {
+        GitChangesError::Validation { .. } => {
+            anyhow!("{}\n(use --no-validate to accept it anyway)", error)
+        }
+        // nothing failed to fetch: the user stopped it
+        GitChangesError::Interrupted => error.into_anyhow(),
+        error => error
+            .into_anyhow()
+            .context("failed to fetch response from gemini api"),
+    }
+}
+
+#[instrument(name = "format", skip_all)]
+fn shape_response(response: &str, prompt: &Prompt, format_options: &FormatOptions) -> String {
+    if prompt.title_only {
+        title_only_message(response, format_options)
+    } else {
+        format::format_message(response, format_options)
+    }
+}
+
+// keeps only the subject line, warning about whatever had to be dropped
+fn title_only_message(response: &str, format_options: &FormatOptions) -> String {
+    let (line, dropped) = format::first_line(response);
+    if dropped {
+        warn!("model returned more than one line; keeping only the first");
+    }
+
+    let formatted = format::format_message(&line, format_options);
+    match formatted.split_once('\n') {
+        Some((subject, _)) => {
+            warn!(
+                "subject exceeded {} characters and was truncated",
+                format_options.max_title_length
+            );
+            subject.to_string()
+        }
+        None => formatted,
+    }
+}
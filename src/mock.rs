@@ -0,0 +1,198 @@
+// src/mock.rs
+//! [`MockProvider`], a scripted [`Provider`] for tests, built with the `test-util` feature.
+//!
+//! It answers from a list instead of a model, remembers every [`Prompt`] it was given, and can
+//! fail on a chosen call. The binary uses one too when it is built with the feature and
+//! `GIT_CHANGES_MOCK_ANSWERS` is set, so end-to-end tests can run it without a network:
+//!
+//! - `GIT_CHANGES_MOCK_ANSWERS`: the answers, as a JSON array of strings
+//! - `GIT_CHANGES_MOCK_FAIL_ON`: the call (from 1) that fails as a network failure would
+//! - `GIT_CHANGES_MOCK_PROMPTS`: a file each prompt is appended to, one JSON object a line
+//!
+//! ```
+//! use git_changes_rs::message::{self, FormatOptions};
+//! use git_changes_rs::mock::MockProvider;
+//! use git_changes_rs::prompt::Prompt;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), git_changes_rs::GitChangesError> {
+//! let provider = MockProvider::new(["feat(notes): add a notes file"]);
+//! let prompt = Prompt {
+//!     system: "Write a conventional commit message.".to_string(),
+//!     user: "diff --git a/notes.txt b/notes.txt ...".to_string(),
+//!     max_output_tokens: 512,
+//!     title_only: false,
+//! };
+//! let message = message::generate(&provider, &prompt, &FormatOptions::default(), None).await?;
+//! assert_eq!(message, "feat(notes): add a notes file");
+//! assert_eq!(provider.prompts(), [prompt]);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{GitChangesError, ProviderFailure};
+use crate::prompt::Prompt;
+use crate::provider::Provider;
+use anyhow::{Context, Result};
+use serde_json::json;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+// the binary's mock, read from the environment once
+static FROM_ENV: OnceLock<Option<MockProvider>> = OnceLock::new();
+
+/// A provider that answers from a script.
+///
+/// Each candidate takes the next answer; once they run out, the last one is repeated. An empty
+/// answer is left out, and a call with only empty ones fails as an
+/// [empty](ProviderFailure::Empty) answer does. Calls are counted from 1, and one set with
+/// [`MockProvider::fail_on`] fails instead of answering.
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    answers: Vec<String>,
+    failures: Vec<(usize, ProviderFailure)>,
+    // appended to as well as kept, for the binary's tests
+    record: Option<PathBuf>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    calls: usize,
+    next: usize,
+    prompts: Vec<Prompt>,
+}
+
+impl MockProvider {
+    /// A provider giving `answers` in order.
+    pub fn new<I, S>(answers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        MockProvider {
+            answers: answers.into_iter().map(Into::into).collect(),
+            ..MockProvider::default()
+        }
+    }
+
+    /// The same provider, failing the `call`th call (from 1) as `failure`. The failed call
+    /// takes no answer.
+    pub fn fail_on(mut self, call: usize, failure: ProviderFailure) -> Self {
+        self.failures.push((call, failure));
+        self
+    }
+
+    /// The prompts received so far, in order, including those of failed calls.
+    pub fn prompts(&self) -> Vec<Prompt> {
+        self.state().prompts.clone()
+    }
+
+    /// How many calls were made.
+    pub fn calls(&self) -> usize {
+        self.state().calls
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn answer(&self, prompt: &Prompt, count: u32) -> Result<Vec<String>, GitChangesError> {
+        let mut state = self.state();
+        state.calls += 1;
+        state.prompts.push(prompt.clone());
+        if let Some(path) = &self.record {
+            record(path, prompt).map_err(GitChangesError::Other)?;
+        }
+        let call = state.calls;
+        if let Some(&(_, failure)) = self.failures.iter().find(|(n, _)| *n == call) {
+            return Err(GitChangesError::Provider {
+                provider: "mock",
+                failure,
+                status: None,
+                retry_after_secs: None,
+                message: format!("mock failure on call {}", call),
+                source: None,
+            });
+        }
+        if self.answers.is_empty() {
+            return Err(GitChangesError::Provider {
+                provider: "mock",
+                failure: ProviderFailure::BadResponse,
+                status: None,
+                retry_after_secs: None,
+                message: "the mock has no answers".to_string(),
+                source: None,
+            });
+        }
+        let mut answers = Vec::new();
+        for _ in 0..count.max(1) {
+            let index = state.next.min(self.answers.len() - 1);
+            let answer = self.answers[index].trim();
+            if !answer.is_empty() {
+                answers.push(answer.to_string());
+            }
+            state.next += 1;
+        }
+        if answers.is_empty() {
+            return Err(GitChangesError::Provider {
+                provider: "mock",
+                failure: ProviderFailure::Empty,
+                status: None,
+                retry_after_secs: None,
+                message: format!("mock returned no text on call {}", call),
+                source: None,
+            });
+        }
+        Ok(answers)
+    }
+}
+
+impl Provider for MockProvider {
+    async fn candidates(
+        &self,
+        prompt: &Prompt,
+        count: u32,
+    ) -> Result<Vec<String>, GitChangesError> {
+        self.answer(prompt, count)
+    }
+}
+
+fn record(path: &Path, prompt: &Prompt) -> Result<()> {
+    let line = json!({"system": prompt.system, "user": prompt.user});
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    writeln!(file, "{}", line).with_context(|| format!("failed to write {}", path.display()))
+}
+
+// whether the run answers from the mock, whatever providers the build has
+pub(crate) fn scripted() -> bool {
+    env::var_os("GIT_CHANGES_MOCK_ANSWERS").is_some()
+}
+
+// the mock GIT_CHANGES_MOCK_ANSWERS describes, if it is set
+pub(crate) fn from_env() -> Result<Option<&'static MockProvider>> {
+    if let Some(mock) = FROM_ENV.get() {
+        return Ok(mock.as_ref());
+    }
+    let Ok(answers) = env::var("GIT_CHANGES_MOCK_ANSWERS") else {
+        return Ok(FROM_ENV.get_or_init(|| None).as_ref());
+    };
+    let answers: Vec<String> = serde_json::from_str(&answers)
+        .context("GIT_CHANGES_MOCK_ANSWERS is not a JSON array of strings")?;
+    let mut mock = MockProvider::new(answers);
+    if let Ok(call) = env::var("GIT_CHANGES_MOCK_FAIL_ON") {
+        let call = call
+            .parse()
+            .context("GIT_CHANGES_MOCK_FAIL_ON is not a number")?;
+        mock = mock.fail_on(call, ProviderFailure::Network);
+    }
+    mock.record = env::var_os("GIT_CHANGES_MOCK_PROMPTS").map(PathBuf::from);
+    Ok(FROM_ENV.get_or_init(|| Some(mock)).as_ref())
+}
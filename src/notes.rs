@@ -0,0 +1,144 @@
+// src/notes.rs
+// --note: recording how a commit's message was generated in a git note
+
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+
+// kept apart from refs/notes/commits so `git log` doesn't show them by default
+pub const NOTES_REF: &str = "refs/notes/git-changes";
+
+// what is recorded; never the diff, the prompt or anything from the environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationNote {
+    pub tool: String,
+    pub tool_version: String,
+    pub provider: String,
+    pub model: String,
+    pub prompt_version: u32,
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+    pub human_edited: bool,
+}
+
+// adds the note to `oid`, replacing an older one (an amended commit can get a note again)
+pub fn add(repo: &Repository, oid: Oid, note: &GenerationNote) -> Result<()> {
+    let signature = repo
+        .signature()
+        .context("failed to get signature (check user.name and user.email)")?;
+    let text = serde_json::to_string_pretty(note).context("failed to encode the note")?;
+    repo.note(
+        &signature,
+        &signature,
+        Some(NOTES_REF),
+        oid,
+        &format!("{}\n", text),
+        true,
+    )
+    .with_context(|| format!("failed to add a note to {}", oid))?;
+    Ok(())
+}
+
+// the note on `oid` as it is stored
+pub fn read(repo: &Repository, oid: Oid) -> Result<Option<String>> {
+    match repo.find_note(Some(NOTES_REF), oid) {
+        Ok(note) => Ok(Some(note.message().unwrap_or_default().to_string())),
+        Err(e) if e.code() == git2::ErrorCode::NotFound => Ok(None),
+        Err(e) => Err(e).context("failed to read the notes"),
+    }
+}
+
+// the note as indented JSON in field order; notes that aren't ours are shown as they are
+pub fn render(text: &str) -> String {
+    serde_json::from_str::<GenerationNote>(text)
+        .ok()
+        .and_then(|note| serde_json::to_string_pretty(&note).ok())
+        .unwrap_or_else(|| text.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(human_edited: bool) -> GenerationNote {
+        GenerationNote {
+            tool: "git-changes-rs".to_string(),
+            tool_version: "0.14.0".to_string(),
+            provider: "gemini".to_string(),
+            model: "gemini-2.0-flash".to_string(),
+            prompt_version: 3,
+            prompt_tokens: 1200,
+            output_tokens: 80,
+            human_edited,
+        }
+    }
+
+    fn commit(repo: &Repository) -> Oid {
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Test").unwrap();
+        config.set_str("user.email", "test@example.com").unwrap();
+        let signature = repo.signature().unwrap();
+        let tree = repo
+            .find_tree(repo.index().unwrap().write_tree().unwrap())
+            .unwrap();
+        repo.commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            "feat: start",
+            &tree,
+            &[],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn a_note_reads_back_as_it_was_added_and_can_be_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let oid = commit(&repo);
+        assert_eq!(read(&repo, oid).unwrap(), None);
+
+        add(&repo, oid, &note(false)).unwrap();
+        let text = read(&repo, oid).unwrap().unwrap();
+        let stored: GenerationNote = serde_json::from_str(&text).unwrap();
+        assert_eq!(stored.prompt_tokens, 1200);
+        assert!(!stored.human_edited);
+        assert!(text.ends_with("}\n"), "{}", text);
+        // not in the notes `git log` shows
+        assert!(repo.find_note(None, oid).is_err());
+
+        add(&repo, oid, &note(true)).unwrap();
+        let text = read(&repo, oid).unwrap().unwrap();
+        assert!(
+            serde_json::from_str::<GenerationNote>(&text)
+                .unwrap()
+                .human_edited
+        );
+    }
+
+    #[test]
+    fn render_keeps_the_field_order_and_shows_other_notes_as_they_are() {
+        let text = serde_json::to_string_pretty(&note(false)).unwrap();
+        let rendered = render(&text);
+        assert_eq!(rendered, text);
+        let fields: Vec<&str> = rendered
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix('"')?.split('"').next())
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "tool",
+                "tool_version",
+                "provider",
+                "model",
+                "prompt_version",
+                "prompt_tokens",
+                "output_tokens",
+                "human_edited"
+            ]
+        );
+        assert_eq!(render("reviewed by Ada\n\n"), "reviewed by Ada");
+    }
+}
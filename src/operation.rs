@@ -0,0 +1,69 @@
+// src/operation.rs
+// the git operation a repository is in the middle of, and the conflicts it left in the index;
+// a diff with unresolved conflicts is mostly conflict markers, so generating stops there
+
+use anyhow::{anyhow, Context, Result};
+use git2::{Repository, RepositoryState};
+use tracing::warn;
+
+// what `repo.state()` says is in progress, as git's own commands are named
+pub fn in_progress(repo: &Repository) -> Option<&'static str> {
+    match repo.state() {
+        RepositoryState::Clean => None,
+        RepositoryState::Merge => Some("merge"),
+        RepositoryState::Revert | RepositoryState::RevertSequence => Some("revert"),
+        RepositoryState::CherryPick | RepositoryState::CherryPickSequence => Some("cherry-pick"),
+        RepositoryState::Bisect => Some("bisect"),
+        RepositoryState::Rebase
+        | RepositoryState::RebaseInteractive
+        | RepositoryState::RebaseMerge => Some("rebase"),
+        RepositoryState::ApplyMailbox | RepositoryState::ApplyMailboxOrRebase => Some("am"),
+    }
+}
+
+// the paths still marked as conflicted in the index, each once
+pub fn unresolved(repo: &Repository) -> Result<Vec<String>> {
+    let index = repo.index().context("failed to read the index")?;
+    if !index.has_conflicts() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for conflict in index
+        .conflicts()
+        .context("failed to read the index conflicts")?
+    {
+        let conflict = conflict.context("failed to read an index conflict")?;
+        let entry = conflict.our.or(conflict.their).or(conflict.ancestor);
+        if let Some(entry) = entry {
+            let path = String::from_utf8_lossy(&entry.path).into_owned();
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+// stops while conflicts remain, naming the operation and the files; `allow` only warns
+pub fn check_conflicts(repo: &Repository, allow: bool) -> Result<()> {
+    let paths = unresolved(repo)?;
+    if paths.is_empty() {
+        return Ok(());
+    }
+    let problem = match in_progress(repo) {
+        Some(operation) => format!(
+            "{} in progress with unresolved conflicts in {}",
+            operation,
+            paths.join(", ")
+        ),
+        None => format!("unresolved conflicts in {}", paths.join(", ")),
+    };
+    if allow {
+        warn!("{}; describing the changes anyway (--allow-conflicts)", problem);
+        return Ok(());
+    }
+    Err(anyhow!(
+        "{}\nresolve them and 'git add' the files first, or pass --allow-conflicts to describe the changes anyway",
+        problem
+    ))
+}
@@ -0,0 +1,164 @@
+// src/output.rs
+// stdout, which only ever gets the result, and the final message: printed there, or written
+// to a file for other tools to pick up
+
+use crate::{exit, interrupt, style};
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions, Permissions};
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+// --output value meaning "only the message, on stdout"
+pub const STDOUT: &str = "-";
+
+// everything out! and outln! print; a closed pipe means the reader has what it wanted, so
+// the run ends with 0 and nothing more to say
+pub fn print(text: &str) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    match stdout
+        .write_all(text.as_bytes())
+        .and_then(|()| stdout.flush())
+    {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Err(exit::Status(0).into()),
+        Err(e) => Err(e).context("failed to write to stdout"),
+    }
+}
+
+// how a bare message ends: one newline, nothing (--no-trailing-newline) or a NUL (-z)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Terminator {
+    #[default]
+    Newline,
+    None,
+    Nul,
+}
+
+impl Terminator {
+    fn as_str(self) -> &'static str {
+        match self {
+            Terminator::Newline => "\n",
+            Terminator::None => "",
+            Terminator::Nul => "\0",
+        }
+    }
+}
+
+// the rules a commit message goes through on its way out, so that `git commit -F -` gets
+// exactly what was generated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Framing {
+    pub terminator: Terminator,
+    // --allow-comment-lines: leave lines starting with `#` as they are
+    pub allow_comment_lines: bool,
+}
+
+impl Framing {
+    // the message without blank lines or `---` fences around it or trailing whitespace, and
+    // with a `\` in front of any line git would strip as a comment
+    pub fn body(&self, message: &str) -> String {
+        let fence = |line: &&str| line.is_empty() || *line == "---";
+        let lines: Vec<&str> = message.lines().map(str::trim_end).collect();
+        let start = lines.iter().position(|line| !fence(line));
+        let end = lines.iter().rposition(|line| !fence(line));
+        let (Some(start), Some(end)) = (start, end) else {
+            return String::new();
+        };
+        let lines: Vec<String> = lines[start..=end]
+            .iter()
+            .map(
+                |line| match self.allow_comment_lines || !line.starts_with('#') {
+                    true => line.to_string(),
+                    false => format!("\\{}", line),
+                },
+            )
+            .collect();
+        lines.join("\n")
+    }
+
+    // the body with its terminator
+    pub fn apply(&self, message: &str) -> String {
+        format!("{}{}", self.body(message), self.terminator.as_str())
+    }
+}
+
+// the message on stdout: framed under `heading` with the subject highlighted at a terminal, or
+// bare (-q, `--output -`, a pipe) and never colored, through `framing`
+pub fn print_message(heading: &str, message: &str, bare: bool, framing: &Framing) -> Result<()> {
+    if bare || !io::stdout().is_terminal() {
+        return out!("{}", framing.apply(message));
+    }
+    let (subject, body) = match message.split_once('\n') {
+        Some((subject, body)) => (subject, format!("\n{}", body)),
+        None => (message, String::new()),
+    };
+    outln!(
+        "\n{}:\n---\n{}{}\n---",
+        heading,
+        style::subject(subject),
+        body
+    )
+}
+
+// text as it goes into the file: trimmed, with a single trailing newline
+pub fn file_text(message: &str) -> String {
+    format!("{}\n", message.trim_end())
+}
+
+// a sibling of `path`, so the final rename stays on one filesystem
+fn temp_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.{}.tmp", name, process::id()))
+}
+
+// readable by the owner only; messages can describe unreleased work
+#[cfg(unix)]
+fn create_private(path: &Path) -> std::io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+}
+
+#[cfg(not(unix))]
+fn create_private(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().write(true).create_new(true).open(path)
+}
+
+// writes to a temporary file and renames it into place, so readers never see half a message
+pub fn write_message(path: &Path, message: &str) -> Result<()> {
+    write_atomically(path, file_text(message).as_bytes(), None)
+}
+
+// a commit message for `git commit -F <path>`, through the same framing as on stdout
+pub fn write_commit_message(path: &Path, message: &str, framing: &Framing) -> Result<()> {
+    write_atomically(path, framing.apply(message).as_bytes(), None)
+}
+
+// replaces a file the user keeps, such as CHANGELOG.md: the bytes as given, with the
+// existing file's permissions instead of owner-only ones
+pub fn replace_file(path: &Path, contents: &str) -> Result<()> {
+    let permissions = fs::metadata(path).ok().map(|m| m.permissions());
+    write_atomically(path, contents.as_bytes(), permissions)
+}
+
+fn write_atomically(path: &Path, bytes: &[u8], permissions: Option<Permissions>) -> Result<()> {
+    let _writing = interrupt::writing()?;
+    let temp = temp_path(path);
+    let written = create_private(&temp)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            if let Some(permissions) = permissions {
+                file.set_permissions(permissions)?;
+            }
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written.with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,81 @@
+// src/pager.rs
+// long output through the user's pager, the way git shows diffs
+
+use anyhow::{anyhow, Context, Result};
+use git2::Repository;
+use std::env;
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+// git's order: $GIT_PAGER, core.pager, $PAGER, then less; `cat` or an empty value means none
+pub fn pager_command(repo: Option<&Repository>) -> Option<String> {
+    let configured = || repo?.config().ok()?.get_string("core.pager").ok();
+    let pager = env::var("GIT_PAGER")
+        .ok()
+        .or_else(configured)
+        .or_else(|| env::var("PAGER").ok())
+        .unwrap_or_else(|| "less".to_string());
+    let pager = pager.trim();
+    (!pager.is_empty() && pager != "cat").then(|| pager.to_string())
+}
+
+// `text` through the pager when stdout is a terminal, otherwise printed as it is
+pub fn page(repo: Option<&Repository>, text: &str) -> Result<()> {
+    let pager = pager_command(repo).filter(|_| io::stdout().is_terminal());
+    let Some(pager) = pager else {
+        out!("{}", text)?;
+        return Ok(());
+    };
+    run(&pager, text, Stdio::inherit())
+}
+
+// `text` through the pager on the terminal itself, for showing something while asking about
+// it; stdout may be a pipe then
+pub fn page_to(repo: Option<&Repository>, text: &str, terminal: &mut File) -> Result<()> {
+    let Some(pager) = pager_command(repo) else {
+        return write!(terminal, "{}", text).context("failed to write to the terminal");
+    };
+    let output = terminal
+        .try_clone()
+        .context("failed to hand the terminal to the pager")?;
+    run(&pager, text, Stdio::from(output))
+}
+
+fn run(pager: &str, text: &str, stdout: Stdio) -> Result<()> {
+    let mut command = if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.arg("/C").arg(pager);
+        command
+    } else {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(pager);
+        command
+    };
+    // what git sets: quit if it fits on one screen, keep colors, don't clear the screen
+    if env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(stdout)
+        .spawn()
+        .with_context(|| format!("failed to launch pager '{}'", pager))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // quitting the pager early closes the pipe; that's not an error
+        match stdin.write_all(text.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(e).context("failed to write to the pager")
+            }
+            _ => {}
+        }
+    }
+    let status = child.wait().context("failed to wait for the pager")?;
+    if !status.success() && status.code() == Some(127) {
+        return Err(anyhow!(
+            "pager '{}' was not found; set GIT_PAGER, core.pager or PAGER",
+            pager
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,90 @@
+// src/paths.rs
+// file names as git hands them over: bytes that are usually, but not always, UTF-8. They stay
+// `Path`s wherever git or the file system gets them back, and become text only for display and
+// the prompt
+
+use std::path::Path;
+
+// a name for people and the model; bytes that aren't UTF-8 show as U+FFFD, which also marks
+// the name as not being exactly the file's
+pub fn display(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+// `path` for a POSIX shell: single-quoted, with any bytes that aren't UTF-8 spelled out through
+// printf so the script names the real file
+pub fn shell_quote(path: &Path) -> String {
+    let quote = |text: &str| format!("'{}'", text.replace('\'', "'\\''"));
+    if let Some(text) = path.to_str() {
+        return quote(text);
+    }
+    let mut format = String::new();
+    for chunk in path.as_os_str().as_encoded_bytes().utf8_chunks() {
+        format.push_str(&chunk.valid().replace('%', "%%").replace('\\', "\\\\"));
+        for byte in chunk.invalid() {
+            format.push_str(&format!("\\{:03o}", byte));
+        }
+    }
+    format!("\"$(printf {})\"", quote(&format))
+}
+
+// the file header lines of a patch with the paths git quoted for their non-ASCII bytes
+// ("caf\303\251.txt") written out again, like `core.quotePath=false`; paths that aren't UTF-8,
+// or that need quoting for other reasons, stay as they are
+pub fn unquote_header(header: &str) -> String {
+    let mut out = String::with_capacity(header.len());
+    let mut rest = header;
+    while let Some(start) = rest.find('"') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = quoted_len(rest) else {
+            break;
+        };
+        match unquote(&rest[1..end - 1]) {
+            Some(text) => out.push_str(&text),
+            None => out.push_str(&rest[..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    out
+}
+
+// the length of the quoted string `text` starts with, both quotes included
+fn quoted_len(text: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return Some(index + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// the inside of a C-style quoted string, when it decodes to UTF-8 that git wouldn't quote
+// without core.quotePath
+fn unquote(inner: &str) -> Option<String> {
+    let bytes = inner.as_bytes();
+    let mut decoded = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'\\' => {
+                // only octal escapes come from core.quotePath; \t, \n, \" or \\ keep the quotes
+                let digits = inner.get(index + 1..index + 4)?;
+                decoded.push(u8::from_str_radix(digits, 8).ok()?);
+                index += 4;
+            }
+            byte => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    let text = String::from_utf8(decoded).ok()?;
+    let plain = !text.chars().any(|c| c.is_control() || c == '"' || c == '\\');
+    plain.then_some(text)
+}
@@ -0,0 +1,67 @@
+// src/per_file.rs
+// --per-file: committing each staged file on its own, from a snapshot of the index
+
+use crate::{commit, paths};
+use anyhow::{Context, Result};
+use git2::{IndexEntry, Repository};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+// what the index held for each staged path (none: staged as deleted) before the index was
+// reset to HEAD; paths go back into the index as they are committed, or at the end
+pub struct StagedSnapshot {
+    entries: BTreeMap<PathBuf, Option<IndexEntry>>,
+}
+
+impl StagedSnapshot {
+    // records every staged path, then unstages them all
+    pub fn take(repo: &Repository) -> Result<Self> {
+        let head_tree = commit::head_tree(repo)?;
+        let mut index = repo.index().context("failed to read the index")?;
+        let diff = repo
+            .diff_tree_to_index(head_tree.as_ref(), Some(&index), None)
+            .context("failed to get diff between head tree and index")?;
+
+        let mut entries = BTreeMap::new();
+        for delta in diff.deltas() {
+            for file in [delta.old_file(), delta.new_file()] {
+                if let Some(path) = file.path() {
+                    entries.insert(path.to_path_buf(), index.get_path(path, 0));
+                }
+            }
+        }
+
+        match &head_tree {
+            Some(tree) => index
+                .read_tree(tree)
+                .context("failed to reset the index to HEAD")?,
+            None => index.clear().context("failed to clear the index")?,
+        }
+        index.write().context("failed to write the index")?;
+        Ok(StagedSnapshot { entries })
+    }
+
+    // stages `paths` as they were in the snapshot
+    pub fn stage(&mut self, repo: &Repository, staged: &[PathBuf]) -> Result<()> {
+        let mut index = repo.index().context("failed to read the index")?;
+        for path in staged {
+            let name = || paths::display(path);
+            match self.entries.remove(path) {
+                Some(Some(entry)) => index
+                    .add(&entry)
+                    .with_context(|| format!("failed to stage {}", name()))?,
+                Some(None) => index
+                    .remove_path(path)
+                    .with_context(|| format!("failed to stage the removal of {}", name()))?,
+                None => {}
+            }
+        }
+        index.write().context("failed to write the index")
+    }
+
+    // puts everything not committed yet back into the index
+    pub fn restore(mut self, repo: &Repository) -> Result<()> {
+        let rest: Vec<PathBuf> = self.entries.keys().cloned().collect();
+        self.stage(repo, &rest)
+    }
+}
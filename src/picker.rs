@@ -0,0 +1,92 @@
+// src/picker.rs
+// choosing one of several generated candidates; i/o is injected so it works without a terminal
+
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickerAction {
+    Select(usize),
+    Edit(usize),
+    // revise a candidate with free-text feedback
+    Feedback(usize),
+    Regenerate,
+    Quit,
+}
+
+pub fn render(candidates: &[String], highlight: bool, out: &mut impl Write) -> io::Result<()> {
+    for (idx, candidate) in candidates.iter().enumerate() {
+        let mut lines = candidate.lines();
+        let subject = lines.next().unwrap_or_default();
+        if highlight {
+            writeln!(out, "\n[{}] \x1b[1m{}\x1b[0m", idx + 1, subject)?;
+        } else {
+            writeln!(out, "\n[{}] {}", idx + 1, subject)?;
+        }
+        for line in lines {
+            writeln!(out, "    {}", line)?;
+        }
+    }
+    Ok(())
+}
+
+// "2" selects, "e" / "e2" edits, "f" / "f2" gives feedback, "r" regenerates, "q" quits;
+// none when the input is not understood
+pub fn parse_action(input: &str, count: usize) -> Option<PickerAction> {
+    let input = input.trim().to_ascii_lowercase();
+    let number = |text: &str| -> Option<usize> {
+        let n: usize = text.trim().parse().ok()?;
+        (1..=count).contains(&n).then_some(n - 1)
+    };
+
+    match input.as_str() {
+        "r" => Some(PickerAction::Regenerate),
+        "q" => Some(PickerAction::Quit),
+        "e" => Some(PickerAction::Edit(0)),
+        "f" => Some(PickerAction::Feedback(0)),
+        _ => {
+            if let Some(rest) = input.strip_prefix('e') {
+                number(rest).map(PickerAction::Edit)
+            } else if let Some(rest) = input.strip_prefix('f') {
+                number(rest).map(PickerAction::Feedback)
+            } else {
+                number(&input).map(PickerAction::Select)
+            }
+        }
+    }
+}
+
+// asks until a valid action is entered; end of input counts as quitting
+pub fn prompt(
+    count: usize,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> io::Result<PickerAction> {
+    loop {
+        write!(
+            out,
+            "\nchoose [1-{}], e<n> to edit, f<n> to give feedback, r to regenerate, q to quit: ",
+            count
+        )?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(PickerAction::Quit);
+        }
+        match parse_action(&line, count) {
+            Some(action) => return Ok(action),
+            None => writeln!(out, "unrecognized choice '{}'", line.trim())?,
+        }
+    }
+}
+
+// one line of feedback for a revision; none when it is empty or input has ended
+pub fn ask_feedback(input: &mut impl BufRead, out: &mut impl Write) -> io::Result<Option<String>> {
+    write!(out, "feedback: ")?;
+    out.flush()?;
+
+    let mut line = String::new();
+    input.read_line(&mut line)?;
+    let feedback = line.trim();
+    Ok((!feedback.is_empty()).then(|| feedback.to_string()))
+}
@@ -0,0 +1,159 @@
+// src/pr.rs
+// pull request titles and descriptions for the current branch against its base
+
+use crate::attach;
+use crate::history;
+use anyhow::{anyhow, Context, Result};
+use git2::{Oid, Repository};
+#[cfg(any(feature = "github", feature = "gitlab"))]
+use tracing::warn;
+
+// the combined diff of a long branch gets its middle cut, like attachments
+const MAX_PR_DIFF_BYTES: usize = 48 * 1024;
+
+pub const PR_MAX_OUTPUT_TOKENS: u32 = 1024;
+
+pub const PR_SYSTEM_PROMPT: &str = "You write pull request descriptions for reviewers. Reply with the pull request title on the first line (plain text, no markdown, under 72 characters), a blank line, then a markdown body with exactly these sections: `## Summary` (two or three sentences on what the branch does and why), `## Changes` (bullet points of the meaningful changes) and `## Testing` (how the change was or should be verified, based only on what the diff shows). Do not invent issue numbers, links or test results.";
+
+#[derive(Debug, Clone)]
+pub struct PrText {
+    pub title: String,
+    pub body: String,
+}
+
+impl PrText {
+    // the first non-empty line is the title, the rest the body
+    pub fn parse(response: &str) -> Option<Self> {
+        let response = response.trim();
+        let (title, body) = response.split_once('\n').unwrap_or((response, ""));
+        let title = title
+            .trim()
+            .trim_start_matches('#')
+            .trim()
+            .trim_start_matches("Title:")
+            .trim()
+            .trim_matches(['*', '`'])
+            .to_string();
+        if title.is_empty() {
+            return None;
+        }
+        Some(PrText {
+            title,
+            body: body.trim().to_string(),
+        })
+    }
+
+    pub fn markdown(&self) -> String {
+        format!("# {}\n\n{}", self.title, self.body)
+    }
+}
+
+// the branch and what it is compared against
+pub struct BranchRange {
+    pub base_name: String,
+    pub merge_base: Oid,
+    pub head: Oid,
+    // newest first
+    pub commits: Vec<Oid>,
+}
+
+fn range_for(repo: &Repository, base_name: &str, base: Oid) -> Result<BranchRange> {
+    let head = history::resolve(repo, "HEAD")?;
+    let merge_base = repo
+        .merge_base(head, base)
+        .with_context(|| format!("HEAD and {} have no common history", base_name))?;
+    let commits = history::commits_in_range(repo, Some(&format!("{}..HEAD", merge_base)))?;
+    Ok(BranchRange {
+        base_name: base_name.to_string(),
+        merge_base,
+        head,
+        commits,
+    })
+}
+
+// an explicit base, or the first of the upstream and origin/HEAD that HEAD is ahead of
+pub fn branch_range(repo: &Repository, base: Option<&str>) -> Result<BranchRange> {
+    if let Some(base) = base {
+        return range_for(repo, base, history::resolve(repo, base)?);
+    }
+
+    let mut candidates = Vec::new();
+    if let Ok(upstream) = history::upstream_oid(repo) {
+        candidates.push(("the upstream".to_string(), upstream));
+    }
+    if let Ok(origin_head) = history::resolve(repo, "refs/remotes/origin/HEAD") {
+        candidates.push(("origin/HEAD".to_string(), origin_head));
+    }
+    if candidates.is_empty() {
+        return Err(anyhow!(
+            "no upstream or origin/HEAD to compare with; pass --base (e.g. --base origin/main)"
+        ));
+    }
+
+    let mut last = None;
+    for (name, oid) in candidates {
+        let range = range_for(repo, &name, oid)?;
+        if !range.commits.is_empty() {
+            return Ok(range);
+        }
+        last = Some(range);
+    }
+    last.context("no base candidates")
+}
+
+pub fn pr_prompt(subjects: &[String], diff: &str) -> String {
+    let listed: Vec<String> = subjects.iter().map(|s| format!("- {}", s)).collect();
+    format!(
+        "Write the pull request for a branch with these commits (oldest first):\n{}\n\nCombined diff against the base:\n\n```diff\n{}\n```",
+        listed.join("\n"),
+        attach::truncate_middle(diff, MAX_PR_DIFF_BYTES)
+    )
+}
+
+// the base branch name on the forge: --base without its remote prefix, or origin's default
+#[cfg(any(feature = "github", feature = "gitlab"))]
+pub fn base_branch(repo: &Repository, base: Option<&str>) -> Result<String> {
+    if let Some(base) = base {
+        return Ok(base.strip_prefix("origin/").unwrap_or(base).to_string());
+    }
+    let origin_head = repo
+        .find_reference("refs/remotes/origin/HEAD")
+        .ok()
+        .and_then(|r| r.symbolic_target().map(str::to_string))
+        .context("origin/HEAD is not set; pass --base with the target branch")?;
+    Ok(origin_head
+        .strip_prefix("refs/remotes/origin/")
+        .unwrap_or(&origin_head)
+        .to_string())
+}
+
+// the current branch, which must be on origin before a pull request can be opened from it
+#[cfg(any(feature = "github", feature = "gitlab"))]
+pub fn pushed_branch(repo: &Repository) -> Result<String> {
+    let head = repo.head().context("failed to get head reference")?;
+    if !head.is_branch() {
+        return Err(anyhow!(
+            "HEAD is detached; check out the branch to open a pull request for"
+        ));
+    }
+    let name = head.shorthand().unwrap_or_default().to_string();
+    let remote = repo
+        .find_reference(&format!("refs/remotes/origin/{}", name))
+        .ok()
+        .and_then(|r| r.target());
+    match remote {
+        None => Err(anyhow!(
+            "'{}' isn't on origin yet; push it first (git push -u origin {})",
+            name,
+            name
+        )),
+        Some(oid) if Some(oid) != head.target() => {
+            warn!(
+                "origin/{} differs from your local branch; the pull request shows what was pushed",
+                name
+            );
+            Ok(name)
+        }
+        Some(_) => Ok(name),
+    }
+}
@@ -0,0 +1,324 @@
+// src/prompt.rs
+//! The [`Prompt`] sent to the model: a system prompt with the instructions, and a user prompt
+//! with the changes and whatever context the [`PromptOptions`] add.
+
+pub use crate::attach::Attachment;
+
+use crate::attach;
+use crate::config::StyleConfig;
+use crate::diff::ChangeSet;
+use crate::error::GitChangesError;
+use std::collections::BTreeMap;
+
+const SYSTEM_PROMPT_INTRO: &str = "You are an AI coding assistant that generates precise and structured Git commit messages. Your task is to produce **only** the commit title and body, following the **conventional commits** format (e.g., `fix(main)`, `feat(cli)`), using imperative verbs such as 'fix', 'add', 'remove'.";
+
+const SYSTEM_PROMPT_OUTRO: &str = "**Do not include any additional explanatory text** like the suggestion for what to include in the message or a recap of the format. Only return the commit message.";
+
+const TITLE_ONLY_INSTRUCTION: &str = "Return only the commit title as a single line that briefly summarizes the change, with no body, bullet points, or blank lines.";
+
+const PER_FILE_BULLETS_INSTRUCTION: &str = "In the body, write one bullet per meaningful file or directory from the list of changed files, in the order they are listed. Each bullet must start with that exact path followed by a colon (e.g., `- src/diff.rs: handle renamed files in exclusion check`). Never mention paths that are not in the list.";
+
+// recorded in --note; bump it when the built-in prompts change in a way that shows in messages
+pub(crate) const PROMPT_VERSION: u32 = 1;
+
+pub(crate) const DEFAULT_STYLE: &str = "detailed";
+pub(crate) const DEFAULT_MAX_OUTPUT_TOKENS: u32 = 512;
+// a single subject line never needs more than this
+pub(crate) const TITLE_ONLY_MAX_OUTPUT_TOKENS: u32 = 64;
+
+/// What is sent to the model for one message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Prompt {
+    /// The instructions: the shape of the message, its style, language and allowed types.
+    pub system: String,
+    /// The changes, with the context the options add.
+    pub user: String,
+    /// The most the answer may take, in tokens.
+    pub max_output_tokens: u32,
+    /// Whether only a subject line was asked for; [`crate::message::generate`] keeps only the
+    /// first line of the answer then.
+    pub title_only: bool,
+}
+
+impl Prompt {
+    // prompt texts a command built itself, sized and shaped as `options` asks
+    pub(crate) fn from_texts(system: &str, user: &str, options: &PromptOptions) -> Self {
+        Prompt {
+            system: system.to_string(),
+            user: user.to_string(),
+            max_output_tokens: options.max_output_tokens(),
+            title_only: options.title_only,
+        }
+    }
+}
+
+/// The prompt for a message about `changes`.
+///
+/// It depends on nothing but its arguments, so the same changes and options always give the
+/// same prompt.
+///
+/// ```
+/// use git2::Repository;
+/// use git_changes_rs::diff::{collect_changes, CollectOptions};
+/// use git_changes_rs::prompt::{build_prompt, PromptOptions};
+/// use std::path::Path;
+///
+/// # let dir = std::env::temp_dir().join(format!("git-changes-doc-{}", std::process::id()));
+/// # let _ = std::fs::remove_dir_all(&dir);
+/// let repo = Repository::init(&dir)?;
+/// std::fs::write(dir.join("notes.txt"), "remember the milk\n")?;
+/// let mut index = repo.index()?;
+/// index.add_path(Path::new("notes.txt"))?;
+/// index.write()?;
+///
+/// let changes = collect_changes(&repo, &CollectOptions::default())?;
+/// let prompt = build_prompt(&changes, &PromptOptions::default());
+/// assert!(prompt.user.contains("remember the milk"));
+/// assert!(prompt.system.contains("conventional commits"));
+/// # std::fs::remove_dir_all(&dir)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn build_prompt(changes: &ChangeSet, options: &PromptOptions) -> Prompt {
+    Prompt {
+        system: build_system_prompt(options),
+        user: build_user_prompt(changes.diff(), changes.files(), options),
+        max_output_tokens: options.max_output_tokens(),
+        title_only: options.title_only,
+    }
+}
+
+/// A named prompt fragment describing the shape and tone of the message.
+#[derive(Debug, Clone)]
+pub struct StylePreset {
+    /// The name `--style` and `message.style` use.
+    pub name: String,
+    /// The instruction added to the system prompt.
+    pub instruction: String,
+    /// The most a message in this style may take, in tokens.
+    pub max_output_tokens: u32,
+}
+
+fn builtin_style(name: &str) -> Option<StylePreset> {
+    let (instruction, max_output_tokens) = match name {
+        "concise" => (
+            "The title should briefly summarize the change. Omit the body unless it is genuinely needed, and then use at most two short bullet points.",
+            128,
+        ),
+        "detailed" => (
+            "The title should briefly summarize the change, followed by a detailed bullet-point list explaining the meaningful changes in the body.",
+            DEFAULT_MAX_OUTPUT_TOKENS,
+        ),
+        "corporate" => (
+            "Use a formal, professional tone. The title should briefly summarize the change, followed by a short paragraph starting with \"Why:\" that explains the motivation, and then a bullet-point list of the meaningful changes.",
+            640,
+        ),
+        "casual" => (
+            "Use a relaxed, friendly tone while staying accurate. The title should briefly summarize the change, followed by a few short bullet points describing what changed.",
+            384,
+        ),
+        _ => return None,
+    };
+
+    Some(StylePreset {
+        name: name.to_string(),
+        instruction: instruction.to_string(),
+        max_output_tokens,
+    })
+}
+
+impl Default for StylePreset {
+    fn default() -> Self {
+        builtin_style(DEFAULT_STYLE).expect("default style is built in")
+    }
+}
+
+/// The names of the styles that are always there: `concise`, `detailed` (the default),
+/// `corporate` and `casual`.
+pub const BUILTIN_STYLES: &[&str] = &["concise", "detailed", "corporate", "casual"];
+
+/// The style named `name`: one of `custom` (the `[styles]` of a config), which add to or
+/// replace the built-in ones, or else a built-in style.
+/// An unknown name is a [`GitChangesError::Config`] listing the ones there are.
+pub fn resolve_style(
+    name: &str,
+    custom: &BTreeMap<String, StyleConfig>,
+) -> Result<StylePreset, GitChangesError> {
+    if let Some(style) = custom.get(name) {
+        return Ok(StylePreset {
+            name: name.to_string(),
+            instruction: style.prompt.trim().to_string(),
+            max_output_tokens: style
+                .max_output_tokens
+                .or_else(|| builtin_style(name).map(|s| s.max_output_tokens))
+                .unwrap_or(DEFAULT_MAX_OUTPUT_TOKENS),
+        });
+    }
+
+    builtin_style(name).ok_or_else(|| {
+        let mut available: Vec<&str> = BUILTIN_STYLES.to_vec();
+        available.extend(custom.keys().map(String::as_str));
+        GitChangesError::config(format!(
+            "unknown style '{}' (available: {})",
+            name,
+            available.join(", ")
+        ))
+    })
+}
+
+/// What the prompt asks for, and the context it carries besides the diff.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct PromptOptions {
+    /// Natural language for the subject and body, such as `pt-BR`; none means English.
+    pub language: Option<String>,
+    /// The shape and tone of the message.
+    pub style: StylePreset,
+    /// Ask for the subject line only.
+    pub title_only: bool,
+    /// One body bullet per changed file or area, starting with its path.
+    pub per_file_bullets: bool,
+    /// Text files (test output, logs) added as supporting material.
+    pub attachments: Vec<Attachment>,
+    /// One line per file whose public Rust API changed.
+    pub api_changes: Vec<String>,
+    /// Whether any of those changes removed or altered existing public items.
+    pub api_breaking: bool,
+    /// The commit types the message may use; empty means the conventional ones.
+    pub types: Vec<String>,
+    /// The commit type the message has to use.
+    pub commit_type: Option<String>,
+    /// The scope the message has to use.
+    pub scope: Option<String>,
+    /// The layout of the tracked files, for choosing a scope and wording.
+    pub tree: Option<String>,
+    /// A note about a recent commit these changes partly undo.
+    pub related_commit: Option<String>,
+}
+
+impl PromptOptions {
+    /// The most the answer may take, in tokens: the style's cap, or less for a subject line.
+    pub fn max_output_tokens(&self) -> u32 {
+        if self.title_only {
+            TITLE_ONLY_MAX_OUTPUT_TOKENS.min(self.style.max_output_tokens)
+        } else {
+            self.style.max_output_tokens
+        }
+    }
+}
+
+// true when the requested language is just english under another name
+pub(crate) fn is_default_language(language: &str) -> bool {
+    let lang = language.trim().to_ascii_lowercase();
+    lang.is_empty() || lang == "english" || lang == "en" || lang.starts_with("en-")
+}
+
+/// The system prompt alone: the instructions for the message `options` describes.
+pub fn build_system_prompt(options: &PromptOptions) -> String {
+    let shape = if options.title_only {
+        TITLE_ONLY_INSTRUCTION
+    } else {
+        options.style.instruction.as_str()
+    };
+    let mut prompt = format!("{} {} {}", SYSTEM_PROMPT_INTRO, shape, SYSTEM_PROMPT_OUTRO);
+
+    if !options.types.is_empty() {
+        prompt.push_str(&format!(
+            " The commit type must be one of: {}. Do not use any other type.",
+            options.types.join(", ")
+        ));
+    }
+
+    if options.per_file_bullets && !options.title_only {
+        prompt.push(' ');
+        prompt.push_str(PER_FILE_BULLETS_INSTRUCTION);
+    }
+
+    match (&options.commit_type, &options.scope) {
+        (Some(kind), Some(scope)) => prompt.push_str(&format!(
+            " Use the commit type `{}` and the scope `{}`: the title starts with `{}({}): `.",
+            kind, scope, kind, scope
+        )),
+        (Some(kind), None) => prompt.push_str(&format!(" Use the commit type `{}`.", kind)),
+        (None, Some(scope)) => prompt.push_str(&format!(" Use the scope `{}`.", scope)),
+        (None, None) => {}
+    }
+
+    if let Some(language) = options.language.as_deref() {
+        if !is_default_language(language) {
+            prompt.push_str(&format!(
+                " Write the commit title and body in {}. Keep the conventional commit type and scope tokens (e.g., `feat`, `fix(cli)`, `BREAKING CHANGE`) in English, because tooling parses them.",
+                language.trim()
+            ));
+        }
+    }
+
+    prompt
+}
+
+// the existing message of a commit being rewritten, which carries intent the diff lacks
+pub(crate) fn previous_message_section(message: &str) -> String {
+    format!("Previous message (improve upon it and keep its factual content, such as ticket numbers and the reasoning behind the change; copy any trailers like `Signed-off-by:` or `Refs:` verbatim):\n```\n{}\n```", message.trim())
+}
+
+// the commits a squash merge combines; context for the intent, not text to reuse
+pub(crate) fn squash_section(subjects: &[String]) -> String {
+    let listed: Vec<String> = subjects.iter().map(|s| format!("- {}", s)).collect();
+    format!("These staged changes squash the following commits (oldest first):\n{}\n\nUse them only to understand the intent. Write one message that summarizes the whole change; do not list, number or copy these subjects.", listed.join("\n"))
+}
+
+// the commit being cherry-picked; its substance carries over, its wording may not
+pub(crate) fn cherry_pick_section(original: &str, branch: Option<&str>) -> String {
+    let onto = branch
+        .map(|b| format!(" onto the `{}` branch", b))
+        .unwrap_or_default();
+    format!("These changes cherry-pick (backport) the following commit{}:\n```\n{}\n```\n\nKeep its substance: the type and scope, what was changed and why, and any ticket references. Adapt the wording to what the diff does here, for example when only part of it applies or it had to change to fit this branch. Do not add a \"(cherry picked from commit ...)\" line or copy its trailers; both are added afterwards.", onto, original.trim())
+}
+
+// a follow-up turn asking for a revision of an earlier suggestion; all context is resent
+pub(crate) fn feedback_section(previous: &str, feedback: &str) -> String {
+    format!("Previously suggested commit message:\n```\n{}\n```\n\nRevise that message according to this feedback from the author, keeping everything the feedback doesn't ask to change:\n{}", previous.trim(), feedback.trim())
+}
+
+/// The user prompt alone: the patch text `diffs` of `files`, with the context in `options`.
+pub fn build_user_prompt(diffs: &str, files: &[String], options: &PromptOptions) -> String {
+    let mut prompt = format!("Analyze the following Git diff carefully (excluding specified files like Cargo.lock, *.log, etc.) to understand the changes and generate a conventional commit message:\n\n```diff\n{}\n```", diffs);
+
+    if options.per_file_bullets && !options.title_only {
+        let listed: Vec<String> = files.iter().map(|f| format!("- {}", f)).collect();
+        prompt.push_str(&format!("\n\nChanged files:\n{}", listed.join("\n")));
+    }
+
+    if !options.api_changes.is_empty() {
+        let listed: Vec<String> = options
+            .api_changes
+            .iter()
+            .map(|c| format!("- {}", c))
+            .collect();
+        prompt.push_str(&format!(
+            "\n\nPublic API changes detected by static analysis of the Rust sources:\n{}",
+            listed.join("\n")
+        ));
+        if options.api_breaking {
+            prompt.push_str("\nExisting public items were removed or changed. Unless these are clearly internal, treat this as a breaking change: add `!` after the type/scope and a `BREAKING CHANGE:` footer describing what callers must change.");
+        }
+    }
+
+    if let Some(note) = &options.related_commit {
+        prompt.push_str("\n\n");
+        prompt.push_str(note);
+    }
+
+    if let Some(tree) = &options.tree {
+        prompt.push_str(&format!(
+            "\n\nRepository layout (tracked files, top two directory levels):\n```\n{}\n```",
+            tree
+        ));
+    }
+
+    if !options.attachments.is_empty() {
+        prompt.push_str("\n\n");
+        prompt.push_str(&attach::render(&options.attachments));
+    }
+
+    prompt
+}
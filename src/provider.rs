@@ -0,0 +1,591 @@
+// src/provider.rs
+//! The model behind the messages: the [`Provider`] trait and [`Gemini`], its implementation
+//! for Google's Gemini API.
+//!
+//! Each provider is a cargo feature of its name ([`PROVIDERS`] lists them); a build without one
+//! still has its types, but its requests fail with a [`GitChangesError::Config`].
+
+use crate::config;
+use crate::error::GitChangesError;
+use crate::prompt::Prompt;
+use anyhow::{anyhow, Result};
+#[cfg(feature = "gemini")]
+use reqwest::Client;
+use serde_json::json;
+use std::env;
+use std::future::Future;
+use std::sync::atomic::AtomicU64;
+use std::sync::OnceLock;
+use tracing::debug;
+
+/// The model requests go to unless `--model` or `generation.model` in the config names another.
+pub const GEMINI_MODEL: &str = "gemini-2.0-flash";
+/// Where requests go unless `GEMINI_BASE_URL` or [`Gemini::with_base_url`] names another root.
+pub const GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// The providers `--provider` and `generation.provider` can name, whether or not this build
+/// has them.
+pub const PROVIDERS: &[&str] = &["gemini"];
+
+// the ones compiled in, each behind the cargo feature of its name
+const ENABLED: &[&str] = &[
+    #[cfg(feature = "gemini")]
+    "gemini",
+];
+
+// --model, when given
+pub(crate) static MODEL: OnceLock<String> = OnceLock::new();
+// --provider, when given on the command line rather than left at its default
+pub(crate) static PROVIDER: OnceLock<String> = OnceLock::new();
+
+// the hottest temperature Gemini takes
+const MAX_TEMPERATURE: f64 = 2.0;
+
+// seed sent with --deterministic so repeated runs sample the same way
+const DETERMINISTIC_SEED: u32 = 42;
+
+// the answer types generateContent takes
+const GEMINI_MIME_TYPES: &[&str] = &["text/plain", "application/json", "text/x.enum"];
+
+// tokens used by the requests since the last --note took them
+pub(crate) static PROMPT_TOKENS: AtomicU64 = AtomicU64::new(0);
+pub(crate) static OUTPUT_TOKENS: AtomicU64 = AtomicU64::new(0);
+
+/// A model that answers prompts.
+///
+/// [`crate::message::generate`] works with any of them, so a tool can put its own model, or a
+/// canned one for tests, behind the same pipeline.
+pub trait Provider: Sync {
+    /// Up to `count` answers to `prompt`, trimmed and in the model's order. On success there is
+    /// at least one.
+    fn candidates(
+        &self,
+        prompt: &Prompt,
+        count: u32,
+    ) -> impl Future<Output = Result<Vec<String>, GitChangesError>> + Send;
+
+    /// One answer to `prompt`.
+    fn generate(
+        &self,
+        prompt: &Prompt,
+    ) -> impl Future<Output = Result<String, GitChangesError>> + Send {
+        async move {
+            self.candidates(prompt, 1)
+                .await?
+                .into_iter()
+                .next()
+                .ok_or_else(|| GitChangesError::Other(anyhow!("the model returned no answer")))
+        }
+    }
+
+    /// One answer to `prompt`, drawn a little more freely than [`Provider::generate`] draws
+    /// it; asked for once after an answer came back [empty](crate::error::ProviderFailure::Empty). It is
+    /// the same as `generate` unless a provider says otherwise.
+    fn generate_varied(
+        &self,
+        prompt: &Prompt,
+    ) -> impl Future<Output = Result<String, GitChangesError>> + Send {
+        self.generate(prompt)
+    }
+}
+
+/// The sampling parameters sent with every request.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Sampling {
+    /// Randomness of the answer, from 0 (greedy) to 2.
+    pub temperature: f64,
+    /// Nucleus sampling: the share of the probability mass tokens are drawn from.
+    pub top_p: f64,
+    /// Draw only from this many of the most likely tokens.
+    pub top_k: Option<u32>,
+    /// Sample the same way every time for the same request.
+    pub seed: Option<u32>,
+}
+
+impl Default for Sampling {
+    fn default() -> Self {
+        Sampling {
+            temperature: 0.7,
+            top_p: 1.0,
+            top_k: None,
+            seed: None,
+        }
+    }
+}
+
+impl Sampling {
+    // the defaults with whatever the config's [generation] section sets
+    pub(crate) fn configured(generation: &config::GenerationConfig) -> Self {
+        let default = Sampling::default();
+        Sampling {
+            temperature: generation.temperature.unwrap_or(default.temperature),
+            top_p: generation.top_p.unwrap_or(default.top_p),
+            top_k: generation.top_k.or(default.top_k),
+            seed: default.seed,
+        }
+    }
+
+    /// Greedy decoding with a fixed seed, for the same message on every run. Still best-effort:
+    /// the model behind an id can change.
+    pub fn deterministic() -> Self {
+        Sampling {
+            temperature: 0.0,
+            top_p: 1.0,
+            top_k: Some(1),
+            seed: Some(DETERMINISTIC_SEED),
+        }
+    }
+
+    // a different draw for "regenerate": hotter, up to the API's maximum but never colder, no
+    // greedy topK, and the next seed if one is pinned, so a deterministic run still regenerates
+    // reproducibly
+    pub(crate) fn varied(self) -> Self {
+        Sampling {
+            temperature: self
+                .temperature
+                .max((self.temperature + 0.2).min(MAX_TEMPERATURE)),
+            top_p: self.top_p,
+            top_k: None,
+            seed: self.seed.map(|seed| seed.wrapping_add(1)),
+        }
+    }
+}
+
+/// One rule of the provider's content filter: how likely content in `category` has to be
+/// harmful before the answer is blocked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafetySetting {
+    /// The harm category, e.g. `HARM_CATEGORY_CIVIC_INTEGRITY`.
+    pub category: String,
+    /// The blocking threshold, e.g. `BLOCK_NONE`.
+    pub threshold: String,
+}
+
+/// Everything a request asks of the model besides the prompt.
+///
+/// Built from the defaults with the methods below; each provider sends what its API knows of
+/// it and leaves the rest out (with a debug log). The [`Prompt`]'s cap on the answer and the
+/// number of candidates a call asks for replace [`GenerationOptions::max_output_tokens`] and
+/// [`GenerationOptions::candidate_count`] for that call.
+///
+/// ```
+/// use git_changes_rs::provider::{Gemini, GenerationOptions, Sampling};
+///
+/// let options = GenerationOptions::default()
+///     .sampling(Sampling::deterministic())
+///     .thinking_budget(0);
+/// let gemini = Gemini::new(Sampling::default()).with_options(options);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct GenerationOptions {
+    /// Temperature, nucleus share, top-k and seed.
+    pub sampling: Sampling,
+    /// The longest answer, in tokens.
+    pub max_output_tokens: u32,
+    /// How many different answers to ask for.
+    pub candidate_count: u32,
+    /// The MIME type of the answer; `text/plain` for a commit message.
+    pub response_mime_type: String,
+    /// The content filter rules sent with the request.
+    pub safety_settings: Vec<SafetySetting>,
+    /// How many tokens a model that thinks may spend on it, 0 for none; the model's own
+    /// default when unset.
+    pub thinking_budget: Option<u32>,
+}
+
+impl Default for GenerationOptions {
+    fn default() -> Self {
+        GenerationOptions {
+            sampling: Sampling::default(),
+            max_output_tokens: crate::prompt::StylePreset::default().max_output_tokens,
+            candidate_count: 1,
+            response_mime_type: "text/plain".to_string(),
+            // the rule requests have always carried
+            safety_settings: vec![SafetySetting {
+                category: "HARM_CATEGORY_CIVIC_INTEGRITY".to_string(),
+                threshold: "BLOCK_NONE".to_string(),
+            }],
+            thinking_budget: None,
+        }
+    }
+}
+
+impl GenerationOptions {
+    // the defaults with whatever the config's [generation] section sets
+    pub(crate) fn configured(generation: &config::GenerationConfig) -> Self {
+        GenerationOptions {
+            sampling: Sampling::configured(generation),
+            thinking_budget: generation.thinking_budget,
+            ..GenerationOptions::default()
+        }
+    }
+
+    /// The same options sampling with `sampling`.
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.sampling = sampling;
+        self
+    }
+
+    /// The same options with this temperature.
+    pub fn temperature(mut self, temperature: f64) -> Self {
+        self.sampling.temperature = temperature;
+        self
+    }
+
+    /// The same options with this nucleus share.
+    pub fn top_p(mut self, top_p: f64) -> Self {
+        self.sampling.top_p = top_p;
+        self
+    }
+
+    /// The same options drawing only from the `top_k` likeliest tokens.
+    pub fn top_k(mut self, top_k: u32) -> Self {
+        self.sampling.top_k = Some(top_k);
+        self
+    }
+
+    /// The same options with a fixed seed.
+    pub fn seed(mut self, seed: u32) -> Self {
+        self.sampling.seed = Some(seed);
+        self
+    }
+
+    /// The same options with another cap on the answer.
+    pub fn max_output_tokens(mut self, max_output_tokens: u32) -> Self {
+        self.max_output_tokens = max_output_tokens;
+        self
+    }
+
+    /// The same options asking for `count` answers.
+    pub fn candidate_count(mut self, count: u32) -> Self {
+        self.candidate_count = count;
+        self
+    }
+
+    /// The same options asking for answers of this MIME type.
+    pub fn response_mime_type(mut self, mime_type: impl Into<String>) -> Self {
+        self.response_mime_type = mime_type.into();
+        self
+    }
+
+    /// The same options with `threshold` for `category`, replacing any rule it had.
+    pub fn safety_setting(
+        mut self,
+        category: impl Into<String>,
+        threshold: impl Into<String>,
+    ) -> Self {
+        let setting = SafetySetting {
+            category: category.into(),
+            threshold: threshold.into(),
+        };
+        self.safety_settings
+            .retain(|existing| existing.category != setting.category);
+        self.safety_settings.push(setting);
+        self
+    }
+
+    /// The same options with no content filter rules of their own.
+    pub fn no_safety_settings(mut self) -> Self {
+        self.safety_settings.clear();
+        self
+    }
+
+    /// The same options with a thinking budget, 0 to turn thinking off.
+    pub fn thinking_budget(mut self, tokens: u32) -> Self {
+        self.thinking_budget = Some(tokens);
+        self
+    }
+
+    // what one call sends: the prompt's cap and the call's count over the stored ones
+    pub(crate) fn for_request(&self, max_output_tokens: u32, candidate_count: u32) -> Self {
+        self.clone()
+            .max_output_tokens(max_output_tokens)
+            .candidate_count(candidate_count)
+    }
+}
+
+/// The providers of [`PROVIDERS`] this build has.
+pub fn enabled_providers() -> &'static [&'static str] {
+    ENABLED
+}
+
+/// Whether `name` is a provider this build can use: one it doesn't know, or one whose feature
+/// was left out, is a [`GitChangesError::Config`] saying so.
+pub fn check_provider(name: &str) -> Result<(), GitChangesError> {
+    if !PROVIDERS.contains(&name) {
+        return Err(GitChangesError::config(format!(
+            "unknown provider '{}' (available: {})",
+            name,
+            PROVIDERS.join(", ")
+        )));
+    }
+    match ENABLED.contains(&name) {
+        true => Ok(()),
+        false => Err(not_in_build(name)),
+    }
+}
+
+// the provider requests go to: --provider, generation.provider from the config, or the first
+// of PROVIDERS
+pub(crate) fn selected(generation: &config::GenerationConfig) -> &str {
+    PROVIDER
+        .get()
+        .or(generation.provider.as_ref())
+        .map_or(PROVIDERS[0], String::as_str)
+}
+
+// the client a command sends its requests with, for the selected provider; one this build
+// doesn't have is an error before anything is sent. A test build answering from the mock has
+// no need of one
+pub(crate) fn client(generation: &config::GenerationConfig) -> Result<Gemini, GitChangesError> {
+    #[cfg(feature = "test-util")]
+    if crate::mock::scripted() {
+        return Ok(Gemini::configured(generation));
+    }
+    let name = selected(generation);
+    check_provider(name)?;
+    match name {
+        "gemini" => Ok(Gemini::configured(generation)),
+        _ => Err(not_in_build(name)),
+    }
+}
+
+fn not_in_build(name: &str) -> GitChangesError {
+    GitChangesError::config(format!(
+        "this build has no {} support (rebuild with the '{}' feature)",
+        name, name
+    ))
+}
+
+/// Google's Gemini API. The key is read from `GEMINI_API_KEY` for each request, and the model
+/// is [`GEMINI_MODEL`] unless a config read with [`crate::config::load`] names another.
+/// Requests need the `gemini` feature, which is on by default.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "gemini"), allow(dead_code))]
+pub struct Gemini {
+    #[cfg(feature = "gemini")]
+    pub(crate) http: Client,
+    pub(crate) options: GenerationOptions,
+    pub(crate) base_url: String,
+}
+
+impl Gemini {
+    /// A client sampling with `sampling`, with a 60 second timeout per request. It talks to
+    /// `GEMINI_BASE_URL` when that is set, or else to [`GEMINI_BASE_URL`].
+    pub fn new(sampling: Sampling) -> Self {
+        Gemini {
+            #[cfg(feature = "gemini")]
+            http: create_http_client(),
+            options: GenerationOptions::default().sampling(sampling),
+            base_url: base_url(),
+        }
+    }
+
+    // the client a command uses: the config's [generation] section over the defaults
+    pub(crate) fn configured(generation: &config::GenerationConfig) -> Self {
+        Gemini::new(Sampling::default()).with_options(GenerationOptions::configured(generation))
+    }
+
+    /// The same client sending `options` with every request, its sampling included.
+    pub fn with_options(self, options: GenerationOptions) -> Self {
+        Gemini { options, ..self }
+    }
+
+    /// The same client with another root for the API, such as a gateway or a local server
+    /// standing in for it in tests; `/models/...` is appended to it.
+    pub fn with_base_url(self, base_url: impl Into<String>) -> Self {
+        let base_url: String = base_url.into();
+        Gemini {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            ..self
+        }
+    }
+}
+
+impl Provider for Gemini {
+    async fn candidates(
+        &self,
+        prompt: &Prompt,
+        count: u32,
+    ) -> Result<Vec<String>, GitChangesError> {
+        send_to_gemini(
+            self,
+            &prompt.system,
+            &prompt.user,
+            prompt.max_output_tokens,
+            count,
+        )
+        .await
+        .map_err(GitChangesError::from)
+    }
+
+    // a hotter draw, the way "regenerate" asks for one
+    async fn generate_varied(&self, prompt: &Prompt) -> Result<String, GitChangesError> {
+        let mut varied = self.clone();
+        varied.options.sampling = varied.options.sampling.varied();
+        varied.generate(prompt).await
+    }
+}
+
+// the model requests go to: --model, generation.model from the config, or the default
+pub(crate) fn model_id() -> &'static str {
+    MODEL
+        .get()
+        .map(String::as_str)
+        .or_else(config::model)
+        .unwrap_or(GEMINI_MODEL)
+}
+
+// GEMINI_BASE_URL, when set, for every request of the run
+pub(crate) fn base_url() -> String {
+    env::var("GEMINI_BASE_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .map_or_else(
+            || GEMINI_BASE_URL.to_string(),
+            |url| url.trim().trim_end_matches('/').to_string(),
+        )
+}
+
+// `models` and the reachability check of `doctor`
+#[cfg(feature = "gemini")]
+pub(crate) fn models_url() -> String {
+    format!("{}/models", base_url())
+}
+
+#[cfg(feature = "gemini")]
+pub(crate) fn create_http_client() -> Client {
+    Client::builder()
+        .timeout(std::time::Duration::from_secs(60))
+        .build()
+        .expect("failed to create http client")
+}
+
+// the generationConfig object of a request with `options` to `model`; what Gemini doesn't
+// take is left out
+pub(crate) fn generation_config(options: &GenerationOptions, model: &str) -> serde_json::Value {
+    let sampling = &options.sampling;
+    let mut config = json!({
+        "temperature": sampling.temperature,
+        "topP": sampling.top_p,
+        "maxOutputTokens": options.max_output_tokens,
+    });
+    if GEMINI_MIME_TYPES.contains(&options.response_mime_type.as_str()) {
+        config["responseMimeType"] = json!(options.response_mime_type);
+    } else {
+        debug!(
+            "gemini has no {} answers; leaving responseMimeType out",
+            options.response_mime_type
+        );
+    }
+    if let Some(top_k) = sampling.top_k {
+        config["topK"] = json!(top_k);
+    }
+    if let Some(seed) = sampling.seed {
+        config["seed"] = json!(seed);
+    }
+    if options.candidate_count > 1 {
+        config["candidateCount"] = json!(options.candidate_count);
+    }
+    if let Some(budget) = options.thinking_budget {
+        if thinks(model) {
+            config["thinkingConfig"] = json!({ "thinkingBudget": budget });
+        } else {
+            debug!("{} doesn't think; leaving the thinking budget out", model);
+        }
+    }
+    config
+}
+
+// the safetySettings array of a request
+#[cfg_attr(not(feature = "gemini"), allow(dead_code))]
+pub(crate) fn safety_settings(options: &GenerationOptions) -> serde_json::Value {
+    options
+        .safety_settings
+        .iter()
+        .map(|setting| json!({"category": setting.category, "threshold": setting.threshold}))
+        .collect()
+}
+
+// the models with a thinking budget: 2.5 and later
+fn thinks(model: &str) -> bool {
+    let Some(version) = model.strip_prefix("gemini-") else {
+        return false;
+    };
+    let mut numbers = version
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|n| n.parse::<u32>().unwrap_or(0));
+    let major = numbers.next().unwrap_or(0);
+    let minor = numbers.next().unwrap_or(0);
+    (major, minor) >= (2, 5)
+}
+
+// without the gemini feature, the client is all there but every request fails
+#[cfg(not(feature = "gemini"))]
+pub(crate) async fn send_to_gemini(
+    _client: &Gemini,
+    _system_prompt: &str,
+    _user_prompt: &str,
+    _max_output_tokens: u32,
+    _candidate_count: u32,
+) -> Result<Vec<String>> {
+    Err(not_in_build("gemini").into())
+}
+
+#[cfg(not(feature = "gemini"))]
+pub(crate) async fn list_models() -> Result<Vec<String>> {
+    Err(not_in_build("gemini").into())
+}
+
+#[cfg(feature = "gemini")]
+pub(crate) use crate::gemini::{list_models, send_to_gemini};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sampling(temperature: f64) -> Sampling {
+        Sampling {
+            temperature,
+            ..Sampling::default()
+        }
+    }
+
+    #[test]
+    fn the_selected_provider_is_checked_against_the_build() {
+        let generation = config::GenerationConfig::default();
+        assert_eq!(selected(&generation), "gemini");
+        let unknown = config::GenerationConfig {
+            provider: Some("nonesuch".to_string()),
+            ..config::GenerationConfig::default()
+        };
+        assert_eq!(selected(&unknown), "nonesuch");
+        // GIT_CHANGES_MOCK_ANSWERS isn't set in unit tests, so the build decides
+        assert_eq!(client(&generation).is_ok(), cfg!(feature = "gemini"));
+        assert!(matches!(
+            client(&unknown).unwrap_err(),
+            GitChangesError::Config { .. }
+        ));
+    }
+
+    #[test]
+    fn a_varied_draw_is_hotter_up_to_the_maximum() {
+        let varied = sampling(0.7).varied();
+        assert!((varied.temperature - 0.9).abs() < 1e-9);
+        assert_eq!(varied.top_k, None);
+        assert!((sampling(1.5).varied().temperature - 1.7).abs() < 1e-9);
+        assert_eq!(sampling(1.9).varied().temperature, MAX_TEMPERATURE);
+        // already over the maximum: left as it is rather than cooled
+        assert_eq!(sampling(2.5).varied().temperature, 2.5);
+    }
+
+    #[test]
+    fn a_varied_deterministic_draw_takes_the_next_seed() {
+        let varied = Sampling::deterministic().varied();
+        assert_eq!(varied.seed, Some(DETERMINISTIC_SEED + 1));
+        assert_eq!(varied.top_k, None);
+        assert!(varied.temperature > 0.0);
+    }
+}
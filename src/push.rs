@@ -0,0 +1,219 @@
+// src/push.rs
+// --push: sending the current branch to its upstream once the commit is made
+
+use anyhow::{anyhow, Context, Result};
+use git2::{
+    BranchType, Cred, CredentialType, Direction, ErrorCode, Oid, PushOptions, RemoteCallbacks,
+    Repository,
+};
+use std::cell::RefCell;
+use std::process::Command;
+
+// where a branch without an upstream goes, like `git push --set-upstream origin <branch>`
+const DEFAULT_REMOTE: &str = "origin";
+
+// where the current branch is pushed: its upstream or origin/<branch>, which is then set up
+#[derive(Debug, Clone)]
+pub struct Target {
+    pub branch: String,
+    pub remote: String,
+    pub remote_branch: String,
+    pub set_upstream: bool,
+}
+
+impl Target {
+    pub fn of_head(repo: &Repository) -> Result<Self> {
+        let head = repo.head().context("failed to get head reference")?;
+        if !head.is_branch() {
+            return Err(anyhow!("HEAD is detached; there is no branch to push"));
+        }
+        let branch = head
+            .shorthand()
+            .context("the branch name isn't valid UTF-8")?
+            .to_string();
+        let refname = head.name().context("the branch name isn't valid UTF-8")?;
+
+        let remote = repo
+            .branch_upstream_remote(refname)
+            .ok()
+            .and_then(|remote| remote.as_str().map(str::to_string));
+        let merge = repo
+            .branch_upstream_merge(refname)
+            .ok()
+            .and_then(|merge| merge.as_str().map(str::to_string));
+        match (remote, merge) {
+            (Some(remote), Some(merge)) => Ok(Target {
+                branch,
+                remote,
+                remote_branch: merge
+                    .strip_prefix("refs/heads/")
+                    .unwrap_or(&merge)
+                    .to_string(),
+                set_upstream: false,
+            }),
+            _ => {
+                repo.find_remote(DEFAULT_REMOTE).map_err(|_| {
+                    anyhow!(
+                        "{} has no upstream and there is no '{}' remote to create one on",
+                        branch,
+                        DEFAULT_REMOTE
+                    )
+                })?;
+                Ok(Target {
+                    remote_branch: branch.clone(),
+                    branch,
+                    remote: DEFAULT_REMOTE.to_string(),
+                    set_upstream: true,
+                })
+            }
+        }
+    }
+
+    fn refspec(&self, force: bool) -> String {
+        format!(
+            "{}refs/heads/{}:refs/heads/{}",
+            if force { "+" } else { "" },
+            self.branch,
+            self.remote_branch
+        )
+    }
+
+    // the remote-tracking ref, i.e. where we last saw the remote branch
+    fn tracking_ref(&self) -> String {
+        format!("refs/remotes/{}/{}", self.remote, self.remote_branch)
+    }
+}
+
+// ssh-agent for ssh remotes, then the configured credential helpers, then whatever libgit2
+// can do by default; each is tried once, since libgit2 keeps asking while they fail
+fn callbacks(repo: &Repository) -> Result<RemoteCallbacks<'static>> {
+    let config = repo.config().context("failed to read the git config")?;
+    let mut tried = CredentialType::empty();
+    let mut callbacks = RemoteCallbacks::new();
+    callbacks.credentials(move |url, username, allowed| {
+        if allowed.contains(CredentialType::SSH_KEY) && !tried.contains(CredentialType::SSH_KEY) {
+            tried.insert(CredentialType::SSH_KEY);
+            return Cred::ssh_key_from_agent(username.unwrap_or("git"));
+        }
+        if allowed.contains(CredentialType::USER_PASS_PLAINTEXT)
+            && !tried.contains(CredentialType::USER_PASS_PLAINTEXT)
+        {
+            tried.insert(CredentialType::USER_PASS_PLAINTEXT);
+            return Cred::credential_helper(&config, url, username);
+        }
+        if allowed.contains(CredentialType::DEFAULT) && !tried.contains(CredentialType::DEFAULT) {
+            tried.insert(CredentialType::DEFAULT);
+            return Cred::default();
+        }
+        Err(git2::Error::from_str(
+            "no credentials were accepted (tried ssh-agent and the credential helpers)",
+        ))
+    });
+    Ok(callbacks)
+}
+
+// pushes with libgit2; `lease` makes it a forced push that only goes ahead while the remote
+// branch is still where the remote-tracking ref says, like `git push --force-with-lease`
+pub fn push(repo: &Repository, target: &Target, lease: bool) -> Result<()> {
+    let mut remote = repo
+        .find_remote(&target.remote)
+        .with_context(|| format!("failed to find the remote '{}'", target.remote))?;
+
+    if lease {
+        let expected = repo.refname_to_id(&target.tracking_ref()).ok();
+        let connection = remote
+            .connect_auth(Direction::Push, Some(callbacks(repo)?), None)
+            .with_context(|| format!("failed to connect to '{}'", target.remote))?;
+        let wanted = format!("refs/heads/{}", target.remote_branch);
+        let actual: Option<Oid> = connection
+            .list()
+            .context("failed to list the remote's branches")?
+            .iter()
+            .find(|head| head.name() == wanted)
+            .map(|head| head.oid());
+        drop(connection);
+        if actual.is_some() && actual != expected {
+            return Err(anyhow!(
+                "{}/{} has moved since it was last fetched; refusing to overwrite it (fetch and look first)",
+                target.remote,
+                target.remote_branch
+            ));
+        }
+    }
+
+    // the server can refuse a ref without the push itself failing
+    let rejected = RefCell::new(None);
+    {
+        let mut callbacks = callbacks(repo)?;
+        callbacks.push_update_reference(|refname, status| {
+            if let Some(status) = status {
+                *rejected.borrow_mut() = Some(format!("{} was rejected: {}", refname, status));
+            }
+            Ok(())
+        });
+        let mut options = PushOptions::new();
+        options.remote_callbacks(callbacks);
+        remote
+            .push(&[target.refspec(lease)], Some(&mut options))
+            .map_err(|e| match e.code() {
+                ErrorCode::Auth => anyhow!(
+                    "{} (--use-git-binary pushes with git's own credentials)",
+                    e.message()
+                ),
+                _ => anyhow!("{}", e.message()),
+            })?;
+    }
+    if let Some(reason) = rejected.into_inner() {
+        return Err(anyhow!(reason));
+    }
+
+    if target.set_upstream {
+        let mut branch = repo
+            .find_branch(&target.branch, BranchType::Local)
+            .context("failed to find the pushed branch")?;
+        branch
+            .set_upstream(Some(&format!("{}/{}", target.remote, target.remote_branch)))
+            .context("pushed, but failed to set the upstream")?;
+    }
+    Ok(())
+}
+
+// `git push` for --use-git-binary, so git's credential setup and pre-push hooks apply; the
+// lease names the remote branch and where it was last fetched, as `push` checks it, rather
+// than leaving git to work out which remote-tracking ref applies
+pub fn push_with_git(repo: &Repository, target: &Target, lease: bool) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .context("--use-git-binary needs a repository with a working tree")?;
+    let mut command = Command::new("git");
+    command.arg("push").arg("--quiet");
+    if lease {
+        command.arg(lease_arg(repo, target));
+    }
+    if target.set_upstream {
+        command.arg("--set-upstream");
+    }
+    let status = command
+        .arg(&target.remote)
+        .arg(target.refspec(false))
+        .current_dir(workdir)
+        .status()
+        .context("failed to run git")?;
+    if !status.success() {
+        return Err(anyhow!("git push exited with {}", status));
+    }
+    Ok(())
+}
+
+// `--force-with-lease=<ref>:<expect>`; with no remote-tracking ref the expectation is empty,
+// which has git insist the remote branch doesn't exist yet
+fn lease_arg(repo: &Repository, target: &Target) -> String {
+    let expected = repo
+        .refname_to_id(&target.tracking_ref())
+        .map(|oid| oid.to_string())
+        .unwrap_or_default();
+    format!(
+        "--force-with-lease=refs/heads/{}:{}",
+        target.remote_branch, expected
+    )
+}
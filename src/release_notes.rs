@@ -0,0 +1,104 @@
+// src/release_notes.rs
+// release notes: the changelog's commits, minus internal work, written for an audience
+
+use crate::changelog::{Entry, Section};
+
+pub const RELEASE_NOTES_MAX_OUTPUT_TOKENS: u32 = 2048;
+
+const USERS_SYSTEM_PROMPT: &str = "You write release notes for the people who use the software, not for its developers. Describe what changed from the user's point of view: what they can now do, what works better, what no longer goes wrong. Avoid internal names, file paths, function names and commit jargon. Write markdown with up to three `###` sections, in this order and only when they have content: `### Highlights` (the one to three changes users will care about most, each in a sentence or two), `### Improvements` and `### Fixes` (one short bullet per change). Merge commits that describe the same change. Do not invent changes, and do not add a title or any text outside the sections.";
+
+const DEVELOPERS_SYSTEM_PROMPT: &str = "You write release notes for developers who build on or integrate with the software. Describe what changed in terms they act on: new APIs, options and behavior, changed defaults, deprecations and anything that needs a migration step, naming the affected commands, flags or items. Write markdown with up to three `###` sections, in this order and only when they have content: `### Highlights` (the one to three most important changes, each in a sentence or two), `### Improvements` and `### Fixes` (one short bullet per change). Merge commits that describe the same change. Do not invent changes, and do not add a title or any text outside the sections.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Audience {
+    Users,
+    Developers,
+}
+
+impl Audience {
+    pub const NAMES: [&'static str; 2] = ["users", "developers"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "users" => Some(Audience::Users),
+            "developers" => Some(Audience::Developers),
+            _ => None,
+        }
+    }
+
+    pub fn system_prompt(self) -> &'static str {
+        match self {
+            Audience::Users => USERS_SYSTEM_PROMPT,
+            Audience::Developers => DEVELOPERS_SYSTEM_PROMPT,
+        }
+    }
+}
+
+// commit types that never change what users see
+const INTERNAL_KINDS: [&str; 6] = ["refactor", "test", "ci", "style", "build", "chore"];
+
+// directories whose files only matter to the project's developers
+const INTERNAL_DIRS: [&str; 8] = [
+    "tests",
+    "test",
+    "benches",
+    "fixtures",
+    "testdata",
+    "__tests__",
+    ".github",
+    ".circleci",
+];
+
+// tests, benchmarks, CI configuration and fixtures
+pub fn is_internal_path(path: &str) -> bool {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let stem = name.split('.').next().unwrap_or(name);
+    let in_internal_dir = path
+        .split('/')
+        .rev()
+        .skip(1)
+        .any(|dir| INTERNAL_DIRS.contains(&dir));
+    in_internal_dir
+        || stem.starts_with("test_")
+        || stem.ends_with("_test")
+        || stem.ends_with("_tests")
+        || name.contains(".test.")
+        || name.contains(".spec.")
+        || name == ".gitlab-ci.yml"
+}
+
+// refactors, and commits whose files are all tests or CI, have nothing to tell users;
+// breaking changes are always kept
+pub fn is_internal(entry: &Entry) -> bool {
+    if entry.section == Section::Breaking {
+        return false;
+    }
+    let internal_kind = entry
+        .kind
+        .as_deref()
+        .is_some_and(|kind| INTERNAL_KINDS.contains(&kind));
+    let internal_paths = !entry.paths.is_empty() && entry.paths.iter().all(|p| is_internal_path(p));
+    internal_kind || internal_paths
+}
+
+pub fn release_notes_prompt(from: &str, to: &str, entries: &[&Entry]) -> String {
+    let mut prompt = format!(
+        "Write the release notes for the changes from {} to {}. Each commit is listed with its conventional-commit section:\n\n",
+        from, to
+    );
+    for entry in entries {
+        prompt.push_str(&format!(
+            "- [{}] {}\n",
+            entry.section.title(),
+            entry.subject
+        ));
+    }
+    prompt
+}
+
+// the --heading template with {version} and {date} filled in
+pub fn heading(template: &str, version: &str, date: &str) -> String {
+    template
+        .replace("{version}", version)
+        .replace("{date}", date)
+}
@@ -0,0 +1,271 @@
+// src/report.rs
+// the result of a generation run for other tools and places: --json (or --format yaml) for
+// scripts, --format markdown for pull requests, wikis and chat
+
+use crate::conventional::ConventionalMessage;
+use crate::exit::{self, Class};
+use crate::trailers::Trailer;
+use serde::Serialize;
+use tracing::warn;
+
+// bumped when a field is renamed or removed; new fields don't change it
+pub const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub schema_version: u32,
+    pub message: String,
+    pub conventional: Option<Conventional>,
+    pub files: Files,
+    pub model: String,
+    pub usage: Usage,
+    pub elapsed_ms: u128,
+    // the message is the one the last run generated for the same request
+    pub cached: bool,
+    // set with --commit or --amend
+    pub commit: Option<String>,
+}
+
+// the parts of a conventional-commit message; none when the subject doesn't have that shape
+#[derive(Debug, Serialize)]
+pub struct Conventional {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub scope: Option<String>,
+    pub breaking: bool,
+    pub subject: String,
+    pub body: String,
+    pub trailers: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Files {
+    pub included: Vec<String>,
+    pub excluded: Vec<String>,
+}
+
+// a changed file for the markdown summary table
+#[derive(Debug, Clone)]
+pub struct FileStat {
+    pub path: String,
+    pub status: &'static str,
+    pub added: usize,
+    pub removed: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Usage {
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub schema_version: u32,
+    pub error: ErrorDetail,
+    // the exit status as a word: "no_changes", "provider_error" and so on
+    pub code: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorDetail {
+    pub kind: exit::Kind,
+    pub message: String,
+    pub retry_after_secs: Option<u64>,
+    pub provider: Option<&'static str>,
+}
+
+impl ErrorReport {
+    // `message` is the whole chain, not only the error the class was read from
+    pub fn new(class: &Class, message: String) -> Self {
+        ErrorReport {
+            schema_version: SCHEMA_VERSION,
+            error: ErrorDetail {
+                kind: class.kind,
+                message,
+                retry_after_secs: class.retry_after_secs,
+                provider: class.provider,
+            },
+            code: exit::label(class.code()),
+        }
+    }
+}
+
+pub fn conventional(parsed: &ConventionalMessage) -> Option<Conventional> {
+    let prefix = parsed.prefix.as_ref()?;
+    Some(Conventional {
+        kind: prefix.kind.clone(),
+        scope: prefix.scope.clone(),
+        breaking: parsed.is_breaking(),
+        subject: parsed.subject.clone(),
+        body: parsed.body_text(),
+        trailers: parsed.trailers.iter().map(Trailer::render).collect(),
+    })
+}
+
+// --format markdown: the subject as a heading, the body as written (its bullets are already a
+// list), the files in a collapsed table and the model and tokens in a footnote
+pub fn markdown(
+    parsed: &ConventionalMessage,
+    stats: &[FileStat],
+    model: &str,
+    usage: &Usage,
+) -> String {
+    let mut out = format!("### {}[^generated]\n", escape(&parsed.title()));
+    if !parsed.body.is_empty() {
+        out.push_str(&format!("\n{}\n", parsed.body_text()));
+    }
+    if !parsed.trailers.is_empty() {
+        let trailers: Vec<String> = parsed.trailers.iter().map(Trailer::render).collect();
+        // two trailing spaces keep them on separate lines
+        out.push_str(&format!("\n{}\n", trailers.join("  \n")));
+    }
+
+    let added: usize = stats.iter().map(|stat| stat.added).sum();
+    let removed: usize = stats.iter().map(|stat| stat.removed).sum();
+    out.push_str(&format!(
+        "\n<details>\n<summary>{} {} changed (+{}/-{})</summary>\n\n",
+        stats.len(),
+        if stats.len() == 1 { "file" } else { "files" },
+        added,
+        removed
+    ));
+    out.push_str("| File | Status | +/- |\n| --- | --- | --- |\n");
+    for stat in stats {
+        out.push_str(&format!(
+            "| `{}` | {} | +{}/-{} |\n",
+            stat.path.replace('|', "\\|"),
+            stat.status,
+            stat.added,
+            stat.removed
+        ));
+    }
+    out.push_str("\n</details>\n");
+
+    out.push_str(&format!(
+        "\n[^generated]: Written by git-changes-rs with {} ({} prompt tokens, {} output tokens).\n",
+        model, usage.prompt_tokens, usage.output_tokens
+    ));
+    out
+}
+
+// `*`, `_` or `<` in a subject shouldn't turn into other markdown
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('*', "\\*")
+        .replace('_', "\\_")
+        .replace('<', "&lt;")
+}
+
+// the two ways the same documents are written; both go through the structs above, so the
+// schemas can't drift apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    // multi-line strings such as the message come out as block scalars
+    Yaml,
+}
+
+// pretty-printed, like the notes; stdout carries nothing else in this mode
+pub fn print<T: Serialize>(value: &T, encoding: Encoding) -> anyhow::Result<()> {
+    let text = match encoding {
+        Encoding::Json => serde_json::to_string_pretty(value).map_err(|e| e.to_string()),
+        // a document of its own, with the `---` that starts one
+        Encoding::Yaml => serde_yaml::to_string(value)
+            .map(|text| format!("---\n{}", text.trim_end()))
+            .map_err(|e| e.to_string()),
+    };
+    match text {
+        Ok(text) => outln!("{}", text),
+        Err(e) => {
+            warn!("failed to encode the {:?} output: {}", encoding, e);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn the_conventional_parts_come_from_the_parsed_message() {
+        let parsed = ConventionalMessage::parse(
+            "feat(api)!: drop the v1 routes\n\n- remove the old handlers\n\nRefs: #12",
+        );
+        let parts = serde_json::to_value(conventional(&parsed).unwrap()).unwrap();
+        assert_eq!(
+            parts,
+            json!({
+                "type": "feat",
+                "scope": "api",
+                "breaking": true,
+                "subject": "drop the v1 routes",
+                "body": "- remove the old handlers",
+                "trailers": ["Refs: #12"],
+            })
+        );
+        assert!(conventional(&ConventionalMessage::parse("Update the notes")).is_none());
+    }
+
+    #[test]
+    fn a_report_keeps_its_field_names() {
+        let report = Report {
+            schema_version: SCHEMA_VERSION,
+            message: "fix: a typo".to_string(),
+            conventional: None,
+            files: Files {
+                included: vec!["src/lib.rs".to_string()],
+                excluded: vec!["Cargo.lock".to_string()],
+            },
+            model: "gemini-2.0-flash".to_string(),
+            usage: Usage {
+                prompt_tokens: 120,
+                output_tokens: 8,
+            },
+            elapsed_ms: 5,
+            cached: false,
+            commit: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            json!({
+                "schema_version": 2,
+                "message": "fix: a typo",
+                "conventional": null,
+                "files": {"included": ["src/lib.rs"], "excluded": ["Cargo.lock"]},
+                "model": "gemini-2.0-flash",
+                "usage": {"prompt_tokens": 120, "output_tokens": 8},
+                "elapsed_ms": 5,
+                "cached": false,
+                "commit": null,
+            })
+        );
+    }
+
+    #[test]
+    fn an_error_report_carries_the_class_and_the_status_word() {
+        let class = Class {
+            kind: exit::Kind::RateLimited,
+            provider: Some("gemini"),
+            retry_after_secs: Some(21),
+        };
+        let report = ErrorReport::new(&class, "rate limited: slow down".to_string());
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            json!({
+                "schema_version": 2,
+                "error": {
+                    "kind": "rate_limited",
+                    "message": "rate limited: slow down",
+                    "retry_after_secs": 21,
+                    "provider": "gemini",
+                },
+                "code": "provider_error",
+            })
+        );
+        let report = ErrorReport::new(&Class::new(exit::Kind::NoChanges), String::new());
+        assert_eq!(report.code, "no_changes");
+    }
+}
@@ -0,0 +1,126 @@
+// src/revert.rs
+// recognizing pending changes that undo a recent commit
+
+use crate::cache;
+use crate::history;
+use anyhow::{Context, Result};
+use git2::{DiffDelta, DiffFormat, DiffLine, Oid, Repository};
+use std::collections::HashMap;
+
+// how many commits back the detection looks
+pub const DEFAULT_REVERT_DEPTH: usize = 20;
+// at or above this similarity the message is written locally as a revert
+pub const FULL_REVERT_SIMILARITY: f64 = 0.9;
+// at or above this the related commit is mentioned in the prompt
+pub const PARTIAL_REVERT_SIMILARITY: f64 = 0.3;
+
+#[derive(Debug, Clone)]
+pub struct RevertMatch {
+    pub oid: Oid,
+    pub subject: String,
+    pub similarity: f64,
+}
+
+impl RevertMatch {
+    pub fn is_full(&self) -> bool {
+        self.similarity >= FULL_REVERT_SIMILARITY
+    }
+
+    // the same shape `git revert` produces, with the conventional-commit type in front
+    pub fn message(&self) -> String {
+        format!(
+            "revert: {}\n\nThis reverts commit {}.",
+            self.subject, self.oid
+        )
+    }
+
+    pub fn prompt_note(&self) -> String {
+        format!(
+            "Part of this change undoes commit {} (\"{}\"). Mention that commit in the body.",
+            history::short_id(self.oid),
+            self.subject
+        )
+    }
+}
+
+// hash of one added or removed line, normalized for whitespace. `invert` swaps + and -,
+// so a commit's inverted lines hash the same as the lines of a change that undoes it.
+pub fn line_hash(delta: &DiffDelta, line: &DiffLine, invert: bool) -> Option<u64> {
+    let origin = match (line.origin(), invert) {
+        ('+', false) | ('-', true) => b'+',
+        ('-', false) | ('+', true) => b'-',
+        _ => return None,
+    };
+    let path = delta
+        .new_file()
+        .path()
+        .or_else(|| delta.old_file().path())?;
+    let content = String::from_utf8_lossy(line.content());
+
+    let mut key = path.to_string_lossy().into_owned().into_bytes();
+    key.push(0);
+    key.push(origin);
+    key.push(0);
+    key.extend_from_slice(content.trim().as_bytes());
+    Some(cache::fnv1a(&key))
+}
+
+// share of lines the two sides have in common (as multisets), relative to the larger side
+pub fn similarity(current: &[u64], inverted: &[u64]) -> f64 {
+    if current.is_empty() || inverted.is_empty() {
+        return 0.0;
+    }
+    let mut counts: HashMap<u64, usize> = HashMap::new();
+    for hash in inverted {
+        *counts.entry(*hash).or_default() += 1;
+    }
+    let mut shared = 0;
+    for hash in current {
+        if let Some(count) = counts.get_mut(hash).filter(|c| **c > 0) {
+            *count -= 1;
+            shared += 1;
+        }
+    }
+    shared as f64 / current.len().max(inverted.len()) as f64
+}
+
+// the recent commit whose inverted diff best matches `current`, if any reaches the partial
+// threshold. `include` applies the same file filter as the pending diff.
+pub fn find_revert(
+    repo: &Repository,
+    current: &[u64],
+    depth: usize,
+    include: impl Fn(&DiffDelta) -> bool,
+) -> Result<Option<RevertMatch>> {
+    let mut best: Option<RevertMatch> = None;
+
+    for oid in history::recent_commits(repo, depth)? {
+        let commit = repo
+            .find_commit(oid)
+            .with_context(|| format!("failed to look up commit {}", oid))?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        let diff = history::diff_to_parent(repo, oid)?;
+
+        let mut inverted = Vec::new();
+        diff.print(DiffFormat::Patch, |delta, _hunk, line| {
+            if include(&delta) {
+                inverted.extend(line_hash(&delta, &line, true));
+            }
+            true
+        })
+        .context("failed to read commit diff")?;
+
+        let score = similarity(current, &inverted);
+        if score >= PARTIAL_REVERT_SIMILARITY && best.as_ref().is_none_or(|b| score > b.similarity)
+        {
+            best = Some(RevertMatch {
+                oid,
+                subject: commit.summary().unwrap_or_default().to_string(),
+                similarity: score,
+            });
+        }
+    }
+    Ok(best)
+}
@@ -0,0 +1,89 @@
+// src/review.rs
+// the accept / edit / regenerate / feedback / quit prompt shown after a suggestion
+
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReviewAction {
+    Accept,
+    Edit,
+    Regenerate,
+    Feedback,
+    Quit,
+}
+
+// single letters or the full words; none when the input is not understood
+pub fn parse_action(input: &str) -> Option<ReviewAction> {
+    match input.trim().to_ascii_lowercase().as_str() {
+        "a" | "accept" | "y" | "yes" => Some(ReviewAction::Accept),
+        "e" | "edit" => Some(ReviewAction::Edit),
+        "r" | "regenerate" => Some(ReviewAction::Regenerate),
+        "f" | "feedback" => Some(ReviewAction::Feedback),
+        "q" | "quit" => Some(ReviewAction::Quit),
+        _ => None,
+    }
+}
+
+pub fn render(message: &str, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "\nsuggested commit message:\n---\n{}\n---", message)
+}
+
+// asks until a valid action is entered; end of input counts as quitting
+pub fn prompt(input: &mut impl BufRead, out: &mut impl Write) -> io::Result<ReviewAction> {
+    loop {
+        write!(out, "[a]ccept, [e]dit, [r]egenerate, [f]eedback, [q]uit: ")?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(ReviewAction::Quit);
+        }
+        match parse_action(&line) {
+            Some(action) => return Ok(action),
+            None => writeln!(out, "unrecognized choice '{}'", line.trim())?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn actions_are_letters_or_words_in_any_case() {
+        for (input, action) in [
+            ("a", ReviewAction::Accept),
+            (" Yes\n", ReviewAction::Accept),
+            ("E", ReviewAction::Edit),
+            ("regenerate", ReviewAction::Regenerate),
+            ("f", ReviewAction::Feedback),
+            ("QUIT", ReviewAction::Quit),
+        ] {
+            assert_eq!(parse_action(input), Some(action), "{:?}", input);
+        }
+        assert_eq!(parse_action(""), None);
+        assert_eq!(parse_action("accepted"), None);
+    }
+
+    #[test]
+    fn the_prompt_asks_until_it_understands() {
+        let mut out = Vec::new();
+        let action = prompt(&mut Cursor::new("maybe\n\nr\na\n"), &mut out).unwrap();
+        assert_eq!(action, ReviewAction::Regenerate);
+        let question = "[a]ccept, [e]dit, [r]egenerate, [f]eedback, [q]uit: ";
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "{q}unrecognized choice 'maybe'\n{q}unrecognized choice ''\n{q}",
+                q = question
+            )
+        );
+    }
+
+    #[test]
+    fn end_of_input_quits() {
+        let action = prompt(&mut Cursor::new("what\n"), &mut Vec::new()).unwrap();
+        assert_eq!(action, ReviewAction::Quit);
+    }
+}
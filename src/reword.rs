@@ -0,0 +1,155 @@
+// src/reword.rs
+// rewording low-quality commits on the current branch, as a rebase todo or in place
+
+use crate::cache;
+use crate::history;
+use anyhow::{anyhow, Context, Result};
+use git2::{Commit, Oid, Repository};
+use std::fs;
+
+pub struct RewordEntry {
+    pub oid: Oid,
+    pub original: String,
+    // none keeps the original message
+    pub message: Option<String>,
+}
+
+impl RewordEntry {
+    pub fn subject(&self) -> &str {
+        self.original.lines().next().unwrap_or_default()
+    }
+}
+
+// commits to consider, oldest first: `onto..HEAD`, or the branch's commits not on its upstream
+pub fn branch_commits(repo: &Repository, onto: Option<&str>) -> Result<Vec<Oid>> {
+    let range = onto.map(|base| format!("{}..HEAD", base));
+    let mut oids = history::commits_in_range(repo, range.as_deref())?;
+    oids.reverse();
+    Ok(oids)
+}
+
+fn first_parent<'r>(commit: &Commit<'r>) -> Option<Commit<'r>> {
+    commit.parents().next()
+}
+
+// the base to hand `git rebase -i`: the parent of the oldest commit, or --root
+fn rebase_base(repo: &Repository, entries: &[RewordEntry]) -> Result<String> {
+    let Some(oldest) = entries.first() else {
+        return Ok("HEAD".to_string());
+    };
+    let commit = repo.find_commit(oldest.oid)?;
+    Ok(match first_parent(&commit) {
+        Some(parent) => history::short_id(parent.id()),
+        None => "--root".to_string(),
+    })
+}
+
+// a todo for `git rebase -i` that amends each reworded commit from a message file under .git
+pub fn todo_list(repo: &Repository, entries: &[RewordEntry]) -> Result<String> {
+    let dir = cache::data_dir(repo).join("reword");
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let mut todo = format!(
+        "# git rebase -i {}, then replace the todo with:\n",
+        rebase_base(repo, entries)?
+    );
+    for entry in entries {
+        todo.push_str(&format!(
+            "pick {} {}\n",
+            history::short_id(entry.oid),
+            entry.subject()
+        ));
+        if let Some(message) = &entry.message {
+            let path = dir.join(format!("{}.txt", entry.oid));
+            fs::write(&path, format!("{}\n", message))
+                .with_context(|| format!("failed to write {}", path.display()))?;
+            todo.push_str(&format!(
+                "exec git commit --amend --only --quiet -F \"{}\"\n",
+                path.display()
+            ));
+        }
+    }
+    Ok(todo)
+}
+
+// --apply rewrites history, so only allow it for unpublished, linear, ahead-only branches
+pub fn check_apply(repo: &Repository, entries: &[RewordEntry]) -> Result<()> {
+    let head = repo.head().context("failed to get head reference")?;
+    if !head.is_branch() {
+        return Err(anyhow!(
+            "HEAD is detached; --apply needs a branch to update"
+        ));
+    }
+    let head_oid = head.target().context("HEAD has no target")?;
+    if entries.last().map(|e| e.oid) != Some(head_oid) {
+        return Err(anyhow!("the commits to reword must end at HEAD"));
+    }
+
+    for entry in entries {
+        if repo.find_commit(entry.oid)?.parent_count() > 1 {
+            return Err(anyhow!(
+                "{} is a merge commit; --apply only rewrites linear history (use the printed rebase todo instead)",
+                history::short_id(entry.oid)
+            ));
+        }
+    }
+
+    if let Ok(upstream) = history::upstream_oid(repo) {
+        let (_, behind) = repo
+            .graph_ahead_behind(head_oid, upstream)
+            .context("failed to compare the branch with its upstream")?;
+        if behind > 0 {
+            return Err(anyhow!(
+                "the branch is {} commits behind its upstream; refusing to rewrite it",
+                behind
+            ));
+        }
+        for entry in entries {
+            let published = entry.oid == upstream
+                || repo
+                    .graph_descendant_of(upstream, entry.oid)
+                    .context("failed to check whether a commit was pushed")?;
+            if published {
+                return Err(anyhow!(
+                    "{} is already on the upstream; refusing to rewrite pushed commits",
+                    history::short_id(entry.oid)
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// rebuilds the commits with their new messages (same trees, authors and committers) and
+// moves the branch to the new tip
+pub fn apply(repo: &Repository, entries: &[RewordEntry]) -> Result<Oid> {
+    let oldest = entries.first().context("nothing to reword")?;
+    let mut parent = first_parent(&repo.find_commit(oldest.oid)?);
+
+    for entry in entries {
+        let commit = repo.find_commit(entry.oid)?;
+        let message = match &entry.message {
+            Some(message) => format!("{}\n", message.trim_end()),
+            None => entry.original.clone(),
+        };
+        let tree = commit.tree().context("failed to read commit tree")?;
+        let parents: Vec<&Commit> = parent.iter().collect();
+        let new_oid = repo
+            .commit(
+                None,
+                &commit.author(),
+                &commit.committer(),
+                &message,
+                &tree,
+                &parents,
+            )
+            .with_context(|| format!("failed to rewrite {}", history::short_id(entry.oid)))?;
+        parent = Some(repo.find_commit(new_oid)?);
+    }
+
+    let tip = parent.context("no commits were rewritten")?.id();
+    let mut head = repo.head().context("failed to get head reference")?;
+    head.set_target(tip, "git-changes reword")
+        .context("failed to move the branch to the reworded commits")?;
+    Ok(tip)
+}
@@ -0,0 +1,313 @@
+// src/rust_api.rs
+// compares the `pub` items of a rust file before and after a change
+
+use quote::ToTokens;
+use std::collections::BTreeMap;
+use syn::{Fields, ImplItem, Item, TraitItem, Visibility};
+
+// public item name (e.g. "fn fetch_diffs", "Config::load") -> rendered signature
+pub type ApiItems = BTreeMap<String, String>;
+
+#[derive(Debug, Default)]
+pub struct ApiDiff {
+    pub removed: Vec<String>,
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl ApiDiff {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.changed.is_empty()
+    }
+
+    // removals and signature changes can break downstream code
+    pub fn is_breaking(&self) -> bool {
+        !self.removed.is_empty() || !self.changed.is_empty()
+    }
+}
+
+// none when the source doesn't parse, so callers can skip the file quietly
+pub fn public_items(source: &str) -> Option<ApiItems> {
+    let file = syn::parse_file(source).ok()?;
+    let mut items = ApiItems::new();
+    collect_items(&file.items, "", &mut items);
+    Some(items)
+}
+
+pub fn diff_items(old: &ApiItems, new: &ApiItems) -> ApiDiff {
+    let mut diff = ApiDiff::default();
+    for (name, signature) in old {
+        match new.get(name) {
+            None => diff.removed.push(signature.clone()),
+            Some(new_signature) if new_signature != signature => diff
+                .changed
+                .push(format!("{} (was: {})", new_signature, signature)),
+            Some(_) => {}
+        }
+    }
+    for (name, signature) in new {
+        if !old.contains_key(name) {
+            diff.added.push(signature.clone());
+        }
+    }
+    diff
+}
+
+// one summary line per file, e.g. "src/lib.rs: removed pub fn a(), added pub struct B"
+pub fn summarize(path: &str, diff: &ApiDiff) -> String {
+    let mut parts = Vec::new();
+    parts.extend(diff.removed.iter().map(|s| format!("removed {}", s)));
+    parts.extend(
+        diff.changed
+            .iter()
+            .map(|s| format!("changed signature of {}", s)),
+    );
+    parts.extend(diff.added.iter().map(|s| format!("added {}", s)));
+    format!("{}: {}", path, parts.join(", "))
+}
+
+fn is_public(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+fn render<T: ToTokens>(node: &T) -> String {
+    compact(&node.to_token_stream().to_string())
+}
+
+// token streams print with spaces between every token; tighten the common cases
+fn compact(tokens: &str) -> String {
+    let mut out = tokens.to_string();
+    for (from, to) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" ::", "::"),
+        ("& ", "&"),
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        ("( ", "("),
+        (" )", ")"),
+        (" (", "("),
+        (" :", ":"),
+        ("' ", "'"),
+    ] {
+        out = out.replace(from, to);
+    }
+    out.replace("->", " -> ").replace("  ", " ")
+}
+
+// "fn bar(&self)" rendered as "fn Foo::bar(&self)"
+fn method_signature(owner: &str, sig: &syn::Signature) -> String {
+    let ident = sig.ident.to_string();
+    render(sig).replacen(
+        &format!("fn {}", ident),
+        &format!("fn {}::{}", owner, ident),
+        1,
+    )
+}
+
+fn qualified(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}::{}", prefix, name)
+    }
+}
+
+fn collect_items(items: &[Item], prefix: &str, out: &mut ApiItems) {
+    for item in items {
+        match item {
+            Item::Fn(f) if is_public(&f.vis) => {
+                let name = qualified(prefix, &f.sig.ident.to_string());
+                out.insert(format!("fn {}", name), format!("pub {}", render(&f.sig)));
+            }
+            Item::Struct(s) if is_public(&s.vis) => {
+                let name = qualified(prefix, &s.ident.to_string());
+                let fields: Vec<String> = match &s.fields {
+                    Fields::Named(named) => named
+                        .named
+                        .iter()
+                        .filter(|f| is_public(&f.vis))
+                        .map(|f| {
+                            format!(
+                                "{}: {}",
+                                f.ident.as_ref().map(|i| i.to_string()).unwrap_or_default(),
+                                render(&f.ty)
+                            )
+                        })
+                        .collect(),
+                    Fields::Unnamed(unnamed) => unnamed
+                        .unnamed
+                        .iter()
+                        .filter(|f| is_public(&f.vis))
+                        .map(|f| render(&f.ty))
+                        .collect(),
+                    Fields::Unit => Vec::new(),
+                };
+                let generics = render(&s.generics);
+                let signature = if fields.is_empty() {
+                    format!("pub struct {}{}", name, generics)
+                } else {
+                    format!(
+                        "pub struct {}{} {{ {} }}",
+                        name,
+                        generics,
+                        fields.join(", ")
+                    )
+                };
+                out.insert(format!("struct {}", name), signature);
+            }
+            Item::Enum(e) if is_public(&e.vis) => {
+                let name = qualified(prefix, &e.ident.to_string());
+                let variants: Vec<String> = e.variants.iter().map(render).collect();
+                out.insert(
+                    format!("enum {}", name),
+                    format!(
+                        "pub enum {}{} {{ {} }}",
+                        name,
+                        render(&e.generics),
+                        variants.join(", ")
+                    ),
+                );
+            }
+            Item::Trait(t) if is_public(&t.vis) => {
+                let name = qualified(prefix, &t.ident.to_string());
+                out.insert(
+                    format!("trait {}", name),
+                    format!("pub trait {}{}", name, render(&t.generics)),
+                );
+                for trait_item in &t.items {
+                    if let TraitItem::Fn(method) = trait_item {
+                        out.insert(
+                            format!("fn {}::{}", name, method.sig.ident),
+                            method_signature(&name, &method.sig),
+                        );
+                    }
+                }
+            }
+            Item::Type(t) if is_public(&t.vis) => {
+                let name = qualified(prefix, &t.ident.to_string());
+                out.insert(
+                    format!("type {}", name),
+                    format!("pub type {} = {}", name, render(&t.ty)),
+                );
+            }
+            Item::Const(c) if is_public(&c.vis) => {
+                let name = qualified(prefix, &c.ident.to_string());
+                out.insert(
+                    format!("const {}", name),
+                    format!("pub const {}: {}", name, render(&c.ty)),
+                );
+            }
+            Item::Static(s) if is_public(&s.vis) => {
+                let name = qualified(prefix, &s.ident.to_string());
+                out.insert(
+                    format!("static {}", name),
+                    format!("pub static {}: {}", name, render(&s.ty)),
+                );
+            }
+            Item::Mod(m) if is_public(&m.vis) => {
+                let name = qualified(prefix, &m.ident.to_string());
+                out.insert(format!("mod {}", name), format!("pub mod {}", name));
+                if let Some((_, inner)) = &m.content {
+                    collect_items(inner, &name, out);
+                }
+            }
+            Item::Impl(imp) => {
+                let self_ty = render(&imp.self_ty);
+                let self_name = qualified(prefix, &self_ty);
+                if let Some((_, trait_path, _)) = &imp.trait_ {
+                    let trait_name = render(trait_path);
+                    out.insert(
+                        format!("impl {} for {}", trait_name, self_name),
+                        format!("impl {} for {}", trait_name, self_name),
+                    );
+                    continue;
+                }
+                for impl_item in &imp.items {
+                    if let ImplItem::Fn(method) = impl_item {
+                        if is_public(&method.vis) {
+                            out.insert(
+                                format!("fn {}::{}", self_name, method.sig.ident),
+                                format!("pub {}", method_signature(&self_name, &method.sig)),
+                            );
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(source: &str) -> ApiItems {
+        public_items(source).expect("the source parses")
+    }
+
+    #[test]
+    fn only_public_items_are_collected() {
+        let items = items(
+            "pub fn fetch(path: &str) -> Result<Vec<u8>, Error> { todo!() }
+             fn helper() {}
+             pub struct Config { pub name: String, secret: String }
+             pub(crate) struct Internal;
+             pub mod net { pub const PORT: u16 = 80; fn hidden() {} }",
+        );
+        assert_eq!(
+            items.keys().collect::<Vec<_>>(),
+            ["const net::PORT", "fn fetch", "mod net", "struct Config"]
+        );
+        assert_eq!(
+            items["fn fetch"],
+            "pub fn fetch(path: &str) -> Result<Vec<u8>, Error>"
+        );
+        assert_eq!(items["struct Config"], "pub struct Config { name: String }");
+    }
+
+    #[test]
+    fn methods_are_named_after_their_type_and_trait_impls_once() {
+        let items = items(
+            "pub struct Repo;
+             impl Repo { pub fn open(&self) -> bool { true } fn private(&self) {} }
+             impl Default for Repo { fn default() -> Self { Repo } }",
+        );
+        assert_eq!(items["fn Repo::open"], "pub fn Repo::open(&self) -> bool");
+        assert!(!items.contains_key("fn Repo::private"));
+        assert_eq!(items["impl Default for Repo"], "impl Default for Repo");
+        assert!(!items.keys().any(|name| name.contains("default")));
+    }
+
+    #[test]
+    fn source_that_doesnt_parse_has_no_items() {
+        assert_eq!(public_items("pub fn broken("), None);
+    }
+
+    #[test]
+    fn removals_and_signature_changes_are_breaking_additions_arent() {
+        let old = items("pub fn a() {} pub fn b(x: u8) {}");
+        let new = items("pub fn b(x: u16) {} pub fn c() {}");
+        let diff = diff_items(&old, &new);
+        assert_eq!(diff.removed, ["pub fn a()"]);
+        assert_eq!(diff.changed, ["pub fn b(x: u16) (was: pub fn b(x: u8))"]);
+        assert_eq!(diff.added, ["pub fn c()"]);
+        assert!(diff.is_breaking());
+        assert_eq!(
+            summarize("src/lib.rs", &diff),
+            "src/lib.rs: removed pub fn a(), changed signature of pub fn b(x: u16) (was: pub fn b(x: u8)), added pub fn c()"
+        );
+
+        let added = diff_items(
+            &old,
+            &items("pub fn a() {} pub fn b(x: u8) {} pub fn d() {}"),
+        );
+        assert!(!added.is_breaking());
+        assert!(!added.is_empty());
+        // a body that changes behind the same signature isn't an API change
+        assert!(diff_items(&old, &items("pub fn a() { 1; } pub fn b(x: u8) {}")).is_empty());
+    }
+}
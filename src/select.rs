@@ -0,0 +1,283 @@
+// src/select.rs
+// --select: a checklist of the changed files, the excluded ones unchecked, so the message can
+// be about some of them without writing patterns; i/o is injected like the picker's
+
+use crate::{cache, interrupt};
+use crate::report::FileStat;
+use anyhow::{Context, Result};
+use git2::Repository;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+const SELECTIONS_FILE: &str = "selections";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelectAction {
+    Toggle(Vec<usize>),
+    All,
+    None,
+    Done,
+    Quit,
+}
+
+pub fn render(files: &[FileStat], checked: &[bool], out: &mut impl Write) -> io::Result<()> {
+    let width = files
+        .iter()
+        .map(|file| file.status.len())
+        .max()
+        .unwrap_or(0);
+    writeln!(out)?;
+    for (idx, (file, checked)) in files.iter().zip(checked).enumerate() {
+        writeln!(
+            out,
+            "[{}] {:>3}  {:<width$}  {}  +{} -{}",
+            if *checked { 'x' } else { ' ' },
+            idx + 1,
+            file.status,
+            file.path,
+            file.added,
+            file.removed,
+            width = width
+        )?;
+    }
+    Ok(())
+}
+
+// "3", "1 4" or "2-5" toggle, "a" checks all, "n" none, an empty line is done, "q" quits;
+// none when the input is not understood
+pub fn parse_action(input: &str, count: usize) -> Option<SelectAction> {
+    let input = input.trim().to_ascii_lowercase();
+    let number = |text: &str| -> Option<usize> {
+        let n: usize = text.trim().parse().ok()?;
+        (1..=count).contains(&n).then_some(n - 1)
+    };
+
+    match input.as_str() {
+        "" => Some(SelectAction::Done),
+        "a" => Some(SelectAction::All),
+        "n" => Some(SelectAction::None),
+        "q" => Some(SelectAction::Quit),
+        _ => {
+            let mut picked = Vec::new();
+            for part in input.split(|c: char| c == ',' || c.is_whitespace()) {
+                if part.is_empty() {
+                    continue;
+                }
+                match part.split_once('-') {
+                    Some((from, to)) => {
+                        let (from, to) = (number(from)?, number(to)?);
+                        picked.extend(from.min(to)..=from.max(to));
+                    }
+                    None => picked.push(number(part)?),
+                }
+            }
+            Some(SelectAction::Toggle(picked))
+        }
+    }
+}
+
+// shows the list and toggles until the user is done; none when they quit or input ends
+pub fn choose(
+    files: &[FileStat],
+    mut checked: Vec<bool>,
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> io::Result<Option<Vec<bool>>> {
+    loop {
+        render(files, &checked, out)?;
+        write!(
+            out,
+            "\ntoggle [1-{}] (e.g. 2 4-6), a for all, n for none, enter to go on, q to quit: ",
+            files.len()
+        )?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match parse_action(&line, files.len()) {
+            Some(SelectAction::Done) => return Ok(Some(checked)),
+            Some(SelectAction::Quit) => return Ok(None),
+            Some(SelectAction::All) => checked.iter_mut().for_each(|c| *c = true),
+            Some(SelectAction::None) => checked.iter_mut().for_each(|c| *c = false),
+            Some(SelectAction::Toggle(picked)) => {
+                for idx in picked {
+                    checked[idx] = !checked[idx];
+                }
+            }
+            None => writeln!(out, "unrecognized choice '{}'", line.trim())?,
+        }
+    }
+}
+
+// selections are remembered per branch; a detached HEAD shares one entry
+pub fn branch_key(repo: &Repository) -> String {
+    repo.head()
+        .ok()
+        .filter(|head| head.is_branch())
+        .and_then(|head| head.shorthand().map(str::to_string))
+        .unwrap_or_else(|| "HEAD".to_string())
+}
+
+fn selections_path(repo: &Repository) -> PathBuf {
+    cache::data_dir(repo).join(SELECTIONS_FILE)
+}
+
+// one `branch<TAB>path` line per selected file; ref names can't hold a tab, so the first one
+// splits them
+fn read_all(repo: &Repository) -> Vec<(String, String)> {
+    fs::read_to_string(selections_path(repo))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| line.split_once('\t'))
+        .map(|(branch, path)| (branch.to_string(), path.to_string()))
+        .collect()
+}
+
+// the paths selected last time on `branch`; none if nothing was remembered for it
+pub fn load(repo: &Repository, branch: &str) -> Option<Vec<String>> {
+    let paths: Vec<String> = read_all(repo)
+        .into_iter()
+        .filter(|(b, _)| b == branch)
+        .map(|(_, path)| path)
+        .collect();
+    (!paths.is_empty()).then_some(paths)
+}
+
+// replaces the entry for `branch`, leaving the other branches' alone
+pub fn store(repo: &Repository, branch: &str, paths: &[&str]) -> Result<()> {
+    let _writing = interrupt::writing()?;
+    let mut contents = String::new();
+    for (b, path) in read_all(repo).iter().filter(|(b, _)| b != branch) {
+        contents.push_str(&format!("{}\t{}\n", b, path));
+    }
+    for path in paths {
+        contents.push_str(&format!("{}\t{}\n", branch, path));
+    }
+    let path = selections_path(repo);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create cache directory {}", dir.display()))?;
+    }
+    fs::write(&path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
+
+// hunks this long show their first lines only, with `v` for the rest
+const SHOWN_LINES: usize = 40;
+
+// one hunk as --select-hunks asks about it
+pub struct HunkView {
+    pub path: String,
+    // the header and lines with their +/- markers
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HunkAction {
+    Keep,
+    Drop,
+    // keep or drop this hunk and the rest of the file's
+    KeepFile,
+    DropFile,
+    // drop this hunk and every one after it
+    Quit,
+    View,
+    Help,
+}
+
+const HUNK_HELP: &str = "y - use this hunk
+n - leave this hunk out
+a - use this hunk and the rest of the file's
+d - leave this hunk and the rest of the file's out
+q - leave this hunk and all later ones out
+v - show the whole hunk in the pager
+? - print this help
+";
+
+pub fn parse_hunk_action(input: &str) -> Option<HunkAction> {
+    match input.trim().to_ascii_lowercase().as_str() {
+        "y" => Some(HunkAction::Keep),
+        "n" => Some(HunkAction::Drop),
+        "a" => Some(HunkAction::KeepFile),
+        "d" => Some(HunkAction::DropFile),
+        "q" => Some(HunkAction::Quit),
+        "v" => Some(HunkAction::View),
+        "?" => Some(HunkAction::Help),
+        _ => None,
+    }
+}
+
+fn render_hunk(hunk: &HunkView, out: &mut impl Write) -> io::Result<()> {
+    writeln!(out, "\n--- {}", hunk.path)?;
+    let lines: Vec<&str> = hunk.text.lines().collect();
+    for line in lines.iter().take(SHOWN_LINES) {
+        writeln!(out, "{}", line)?;
+    }
+    if lines.len() > SHOWN_LINES {
+        writeln!(
+            out,
+            "... {} more lines (v to see all)",
+            lines.len() - SHOWN_LINES
+        )?;
+    }
+    Ok(())
+}
+
+// asks about each hunk in turn, like `git add -p`; `view` shows a whole hunk. End of input
+// leaves the remaining hunks out, as `q` does
+pub fn choose_hunks(
+    hunks: &[HunkView],
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+    mut view: impl FnMut(&str) -> io::Result<()>,
+) -> io::Result<Vec<bool>> {
+    let mut keep = vec![false; hunks.len()];
+    let mut index = 0;
+    while index < hunks.len() {
+        let hunk = &hunks[index];
+        render_hunk(hunk, out)?;
+        write!(
+            out,
+            "({}/{}) use this hunk [y,n,a,d,q,v,?]? ",
+            index + 1,
+            hunks.len()
+        )?;
+        out.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(out)?;
+            break;
+        }
+        let rest_of_file = hunks[index..]
+            .iter()
+            .take_while(|other| other.path == hunk.path)
+            .count();
+        match parse_hunk_action(&line) {
+            Some(HunkAction::Keep) => keep[index] = true,
+            Some(HunkAction::Drop) => {}
+            Some(HunkAction::KeepFile) => {
+                keep[index..index + rest_of_file].fill(true);
+                index += rest_of_file - 1;
+            }
+            Some(HunkAction::DropFile) => index += rest_of_file - 1,
+            Some(HunkAction::Quit) => break,
+            Some(HunkAction::View) => {
+                view(&hunk.text)?;
+                continue;
+            }
+            Some(HunkAction::Help) => {
+                write!(out, "{}", HUNK_HELP)?;
+                continue;
+            }
+            None => {
+                writeln!(out, "unrecognized choice '{}'", line.trim())?;
+                continue;
+            }
+        }
+        index += 1;
+    }
+    Ok(keep)
+}
@@ -0,0 +1,149 @@
+// src/sign.rs
+// signing commit buffers the way `git commit -S` does, from git's gpg.* and user.signingKey
+
+use anyhow::{anyhow, Context, Result};
+use git2::{Config, Repository};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningFormat {
+    OpenPgp,
+    X509,
+    Ssh,
+}
+
+impl SigningFormat {
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "openpgp" => Ok(SigningFormat::OpenPgp),
+            "x509" => Ok(SigningFormat::X509),
+            "ssh" => Ok(SigningFormat::Ssh),
+            other => Err(anyhow!("unsupported gpg.format '{}'", other)),
+        }
+    }
+
+    fn default_program(self) -> &'static str {
+        match self {
+            SigningFormat::OpenPgp => "gpg",
+            SigningFormat::X509 => "gpgsm",
+            SigningFormat::Ssh => "ssh-keygen",
+        }
+    }
+
+    fn config_name(self) -> &'static str {
+        match self {
+            SigningFormat::OpenPgp => "openpgp",
+            SigningFormat::X509 => "x509",
+            SigningFormat::Ssh => "ssh",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Signer {
+    pub format: SigningFormat,
+    program: String,
+    key: String,
+}
+
+fn config_string(config: &Config, name: &str) -> Option<String> {
+    config
+        .get_string(name)
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+impl Signer {
+    // a signer when `force` (--sign) or commit.gpgsign asks for one
+    pub fn from_config(repo: &Repository, force: bool) -> Result<Option<Self>> {
+        let config = repo.config().context("failed to read git config")?;
+        if !force && !config.get_bool("commit.gpgsign").unwrap_or(false) {
+            return Ok(None);
+        }
+
+        let format = SigningFormat::parse(
+            config_string(&config, "gpg.format")
+                .as_deref()
+                .unwrap_or("openpgp"),
+        )?;
+        // gpg.program predates gpg.<format>.program and only ever meant OpenPGP
+        let program = config_string(&config, &format!("gpg.{}.program", format.config_name()))
+            .or_else(|| {
+                (format == SigningFormat::OpenPgp)
+                    .then(|| config_string(&config, "gpg.program"))
+                    .flatten()
+            })
+            .unwrap_or_else(|| format.default_program().to_string());
+
+        let key = match format {
+            SigningFormat::Ssh => {
+                let key = config
+                    .get_path("user.signingKey")
+                    .map_err(|_| anyhow!("SSH signing needs user.signingKey to be set"))?;
+                let key = key.display().to_string();
+                if key.starts_with("key::") || key.starts_with("ssh-") {
+                    return Err(anyhow!(
+                        "user.signingKey holds a literal SSH key; point it at the key file instead"
+                    ));
+                }
+                key
+            }
+            // git falls back to the committer identity to pick the key
+            _ => match config_string(&config, "user.signingKey") {
+                Some(key) => key,
+                None => {
+                    let signature = repo.signature().map_err(|_| {
+                        anyhow!("signing needs user.signingKey, or user.name and user.email")
+                    })?;
+                    signature.to_string()
+                }
+            },
+        };
+        Ok(Some(Signer {
+            format,
+            program,
+            key,
+        }))
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        match self.format {
+            SigningFormat::Ssh => command.args(["-Y", "sign", "-n", "git", "-f", &self.key]),
+            _ => command.args(["--status-fd=2", "-bsau", &self.key]),
+        };
+        command
+    }
+
+    // the detached, armored signature of `buffer`; the signer's stderr is the error on failure
+    pub fn sign(&self, buffer: &str) -> Result<String> {
+        let mut child = self
+            .command()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run {}", self.program))?;
+        child
+            .stdin
+            .take()
+            .context("failed to open the signer's stdin")?
+            .write_all(buffer.as_bytes())
+            .context("failed to pass the commit to the signer")?;
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("failed to wait for {}", self.program))?;
+
+        let signature = String::from_utf8_lossy(&output.stdout).to_string();
+        if !output.status.success() || signature.trim().is_empty() {
+            return Err(anyhow!(
+                "{} failed to sign the commit ({}):\n{}",
+                self.program,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+        Ok(signature)
+    }
+}
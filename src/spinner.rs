@@ -0,0 +1,89 @@
+// src/spinner.rs
+// a spinner with the elapsed time on stderr while a slow step runs, so a wait on the API
+// doesn't look like a hang
+
+use crate::style;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const TICK: Duration = Duration::from_millis(100);
+// quick steps finish before anything is drawn
+const DELAY: Duration = Duration::from_millis(300);
+
+// off for --json and for the hook, where the terminal belongs to git
+static ENABLED: AtomicBool = AtomicBool::new(true);
+// a line is on screen, for clearing it when the process exits under a spinner
+static DRAWN: AtomicBool = AtomicBool::new(false);
+
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+// drawn from its own thread until dropped, which clears the line again
+pub struct Spinner {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Spinner {
+    // nothing is drawn with -q, when disabled, or when stderr isn't a terminal
+    pub fn start(label: &str) -> Spinner {
+        let stop = Arc::new(AtomicBool::new(false));
+        let shown = ENABLED.load(Ordering::Relaxed)
+            && !crate::QUIET.load(Ordering::Relaxed)
+            && io::stderr().is_terminal();
+        let thread = shown.then(|| {
+            let stop = Arc::clone(&stop);
+            let label = label.to_string();
+            thread::spawn(move || spin(&label, &stop))
+        });
+        Spinner { stop, thread }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn spin(label: &str, stop: &AtomicBool) {
+    let started = Instant::now();
+    let frames = style::glyphs().spinner;
+    let mut frame = 0;
+    while !stop.load(Ordering::Relaxed) {
+        if frames.is_empty() {
+            // --ascii: one line that stays, with nothing drawn over
+            if frame == 0 && started.elapsed() >= DELAY {
+                eprintln!("{}...", label);
+                frame = 1;
+            }
+        } else if started.elapsed() >= DELAY {
+            eprint!(
+                "\r\x1b[2K{} {}... {}s",
+                frames[frame % frames.len()],
+                label,
+                started.elapsed().as_secs()
+            );
+            let _ = io::stderr().flush();
+            DRAWN.store(true, Ordering::Relaxed);
+            frame += 1;
+        }
+        thread::sleep(TICK);
+    }
+    clear();
+}
+
+// wipes the spinner's line, if one is shown
+pub fn clear() {
+    if DRAWN.swap(false, Ordering::Relaxed) {
+        eprint!("\r\x1b[2K");
+        let _ = io::stderr().flush();
+    }
+}
@@ -0,0 +1,139 @@
+// src/split.rs
+// proposing file-level commit groupings for a large change, and making those commits
+
+use crate::attach;
+use crate::cache;
+use crate::commit;
+use crate::paths;
+use anyhow::{anyhow, Context, Result};
+use git2::Repository;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::PathBuf;
+
+// how much of each file's patch the grouping request sees
+pub const MAX_FILE_EXCERPT: usize = 1500;
+
+pub const GROUPING_SYSTEM_PROMPT: &str = "You group the changes of a working tree into a small number of coherent, independently reviewable git commits. Files that implement the same concern belong together; unrelated concerns go into separate commits. Reply with one line per commit in the form `N: path, path` (e.g., `1: src/cli.rs, README.md`), using only the paths you were given, each exactly once. Do not add any other text.";
+
+pub struct SplitGroup {
+    pub files: Vec<String>,
+    pub message: String,
+}
+
+impl SplitGroup {
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or_default()
+    }
+}
+
+// each changed file with the start of its patch
+pub fn grouping_prompt(files: &[(&str, &str)]) -> String {
+    let mut prompt = String::from("Group these changed files into commits:\n");
+    for (path, patch) in files {
+        prompt.push_str(&format!(
+            "\n=== {} ===\n{}\n",
+            path,
+            attach::truncate_middle(patch.trim_end(), MAX_FILE_EXCERPT)
+        ));
+    }
+    prompt
+}
+
+// the groups in the model's reply, restricted to real paths; every path lands in exactly
+// one group, and the ones the model left out form a last group. the second list holds
+// paths the model made up.
+pub fn parse_groups(response: &str, files: &[String]) -> (Vec<Vec<String>>, Vec<String>) {
+    let known: BTreeSet<&str> = files.iter().map(String::as_str).collect();
+    let mut assigned = BTreeSet::new();
+    let mut groups = Vec::new();
+    let mut unknown = Vec::new();
+
+    for line in response.lines() {
+        let Some((label, paths)) = line.split_once(':') else {
+            continue;
+        };
+        let label = label.trim().trim_start_matches(['-', '*', ' ']);
+        if label.is_empty() || !label.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+
+        let mut group = Vec::new();
+        for path in paths.split(',') {
+            let path = path.trim().trim_matches('`');
+            if path.is_empty() {
+                continue;
+            }
+            if !known.contains(path) {
+                unknown.push(path.to_string());
+            } else if assigned.insert(path.to_string()) {
+                group.push(path.to_string());
+            }
+        }
+        if !group.is_empty() {
+            groups.push(group);
+        }
+    }
+
+    let rest: Vec<String> = files
+        .iter()
+        .filter(|f| !assigned.contains(f.as_str()))
+        .cloned()
+        .collect();
+    if !rest.is_empty() {
+        groups.push(rest);
+    }
+    (groups, unknown)
+}
+
+// shell commands that make the commits, with each message in a file under .git
+pub fn commands(repo: &Repository, groups: &[(SplitGroup, Vec<PathBuf>)]) -> Result<String> {
+    let dir = cache::data_dir(repo).join("split");
+    fs::create_dir_all(&dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    let mut script = String::new();
+    for (number, (group, files)) in groups.iter().enumerate() {
+        let path = dir.join(format!("{}.txt", number + 1));
+        fs::write(&path, format!("{}\n", group.message.trim_end()))
+            .with_context(|| format!("failed to write {}", path.display()))?;
+
+        let quoted: Vec<String> = files.iter().map(|p| paths::shell_quote(p)).collect();
+        script.push_str(&format!(
+            "git add -A -- {0} && git commit -F {1} -- {0}\n",
+            quoted.join(" "),
+            paths::shell_quote(&path)
+        ));
+    }
+    Ok(script)
+}
+
+// stages exactly `paths` as they are in the working tree, deletions included
+pub fn stage_paths(repo: &Repository, staged: &[PathBuf]) -> Result<()> {
+    let workdir = repo
+        .workdir()
+        .context("split --apply needs a repository with a working tree")?;
+    let mut index = repo.index().context("failed to read the index")?;
+    for path in staged {
+        let name = || paths::display(path);
+        if workdir.join(path).exists() {
+            index
+                .add_path(path)
+                .with_context(|| format!("failed to stage {}", name()))?;
+        } else {
+            index
+                .remove_path(path)
+                .with_context(|| format!("failed to stage the removal of {}", name()))?;
+        }
+    }
+    index.write().context("failed to write the index")
+}
+
+// with something already staged, the first commit would swallow it
+pub fn check_apply(repo: &Repository) -> Result<()> {
+    if commit::has_staged_changes(repo)? {
+        return Err(anyhow!(
+            "split --apply works on unstaged changes; commit or unstage what is staged first"
+        ));
+    }
+    Ok(())
+}
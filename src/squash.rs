@@ -0,0 +1,63 @@
+// src/squash.rs
+// squash merges: the commits being squashed, from SQUASH_MSG or a named branch
+
+use crate::history;
+use anyhow::{Context, Result};
+use git2::{Oid, Repository};
+use std::fs;
+
+// what `git merge --squash` leaves in the git dir until the commit is made
+const SQUASH_MSG: &str = "SQUASH_MSG";
+
+// the subjects listed in SQUASH_MSG ("commit <sha>" blocks in `git log` format), oldest
+// first; none when no squash is in progress
+pub fn pending_subjects(repo: &Repository) -> Result<Option<Vec<String>>> {
+    let path = repo.path().join(SQUASH_MSG);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return Ok(None),
+    };
+
+    let mut subjects = Vec::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        let Some(sha) = line.strip_prefix("commit ") else {
+            continue;
+        };
+        let resolved = Oid::from_str(sha.trim())
+            .ok()
+            .and_then(|oid| repo.find_commit(oid).ok());
+        let subject = match resolved {
+            Some(commit) => commit.summary().unwrap_or_default().to_string(),
+            // the first indented line after the headers is the subject
+            None => lines
+                .by_ref()
+                .find(|l| l.starts_with("    "))
+                .unwrap_or_default()
+                .trim()
+                .to_string(),
+        };
+        if !subject.is_empty() {
+            subjects.push(subject);
+        }
+    }
+    // git lists the newest commit first
+    subjects.reverse();
+    Ok(Some(subjects))
+}
+
+// --squash-of: the subjects of the branch's commits that HEAD doesn't have, oldest first
+pub fn branch_subjects(repo: &Repository, branch: &str) -> Result<Vec<String>> {
+    let oids = history::commits_in_range(repo, Some(&format!("HEAD..{}", branch)))?;
+    let mut subjects = Vec::new();
+    for oid in oids.iter().rev() {
+        let commit = repo
+            .find_commit(*oid)
+            .with_context(|| format!("failed to look up commit {}", oid))?;
+        if commit.parent_count() > 1 {
+            continue;
+        }
+        subjects.push(commit.summary().unwrap_or_default().to_string());
+    }
+    Ok(subjects)
+}
@@ -0,0 +1,513 @@
+// src/standup.rs
+// standup: your recent commits, by day, for a summary to paste into chat
+
+use crate::changelog_file::civil_date;
+use anyhow::{anyhow, Context, Result};
+use git2::{Repository, Sort};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const STANDUP_MAX_OUTPUT_TOKENS: u32 = 1024;
+
+pub const STANDUP_SYSTEM_PROMPT: &str = "You turn someone's commits into a short standup update they can paste into a chat message. For each day you are given, write the day as a bold heading followed by one to three short bullet points in plain, first-person language (\"Fixed ...\", \"Worked on ...\"), merging related commits into one point. Do not use conventional-commit prefixes, commit hashes or file paths unless a name is the clearest way to say it. When commits come from several repositories, mention the project where it helps. Reply with the update only.";
+
+// whole days are what a standup is about; more than this is a report, not a standup
+const MAX_COMMITS: usize = 200;
+
+// 1970-01-01 was a Thursday
+const WEEKDAYS: [&str; 7] = [
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+];
+
+// directories not worth descending into when looking for repositories
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor"];
+
+#[derive(Debug, Clone)]
+pub struct StandupCommit {
+    pub repo: String,
+    pub subject: String,
+    // the commit time like `git log --since` uses, with its UTC offset, which decides the day
+    pub time: i64,
+    pub offset_minutes: i32,
+}
+
+// days since 1970-01-01 for a proleptic Gregorian date; the inverse of `civil_date`
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (i64::from(month) + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// a date that exists: one that comes back the same from days_from_civil and civil_date, so
+// 2024-02-31 isn't quietly March 2nd
+fn parse_date(text: &str) -> Option<(i64, u32, u32)> {
+    let mut parts = text.splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    let date = (year, month, day);
+    ((1..=12).contains(&month) && civil_date(days_from_civil(year, month, day)) == date)
+        .then_some(date)
+}
+
+fn parse_clock(text: &str) -> Option<i64> {
+    let mut parts = text.split(':');
+    let hours: i64 = parts.next()?.parse().ok()?;
+    let minutes: i64 = parts.next()?.parse().ok()?;
+    let seconds: i64 = match parts.next() {
+        Some(seconds) => seconds.parse().ok()?,
+        None => 0,
+    };
+    (hours < 24 && minutes < 60 && seconds < 60).then_some(hours * 3600 + minutes * 60 + seconds)
+}
+
+// --since: `today`, `yesterday`, `N days ago` (also hours and weeks), or an ISO date or
+// date-time, all in local time (`offset_minutes` east of UTC). Days count from midnight, so
+// `yesterday` at 9am still includes yesterday morning
+pub fn parse_since(text: &str, now: i64, offset_minutes: i32) -> Result<i64> {
+    let offset = i64::from(offset_minutes) * 60;
+    let local_midnight =
+        |days_back: i64| ((now + offset).div_euclid(86_400) - days_back) * 86_400 - offset;
+    let text = text.trim().to_ascii_lowercase();
+
+    match text.as_str() {
+        "today" => return Ok(local_midnight(0)),
+        "yesterday" => return Ok(local_midnight(1)),
+        _ => {}
+    }
+
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if let [count, unit, rest @ ..] = words.as_slice() {
+        if rest.is_empty() || rest == ["ago"] {
+            if let Ok(count) = count.parse::<i64>() {
+                match unit.trim_end_matches('s') {
+                    "hour" => return Ok(now - count * 3600),
+                    "day" => return Ok(local_midnight(count)),
+                    "week" => return Ok(local_midnight(count * 7)),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    let (date, clock) = match text.split_once(['t', ' ']) {
+        Some((date, clock)) => (date, Some(clock)),
+        None => (text.as_str(), None),
+    };
+    if let Some((year, month, day)) = parse_date(date) {
+        let seconds = match clock {
+            Some(clock) => parse_clock(clock),
+            None => Some(0),
+        };
+        if let Some(seconds) = seconds {
+            return Ok(days_from_civil(year, month, day) * 86_400 + seconds - offset);
+        }
+    }
+
+    Err(anyhow!(
+        "can't read --since '{}'; use 'yesterday', '3 days ago' or a date like 2024-05-01",
+        text
+    ))
+}
+
+// the day a commit was made on, in the time zone it was made in
+fn local_day(commit: &StandupCommit) -> i64 {
+    (commit.time + i64::from(commit.offset_minutes) * 60).div_euclid(86_400)
+}
+
+fn day_label(days: i64) -> String {
+    let (year, month, day) = civil_date(days);
+    format!(
+        "{} {:04}-{:02}-{:02}",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        year,
+        month,
+        day
+    )
+}
+
+// the author's commits since `since` on any local branch, merges left out, oldest first
+pub fn collect(
+    repo: &Repository,
+    label: &str,
+    author: &str,
+    since: i64,
+) -> Result<Vec<StandupCommit>> {
+    let mut walk = repo.revwalk().context("failed to walk the history")?;
+    walk.set_sorting(Sort::TIME)
+        .context("failed to sort the history")?;
+    walk.push_glob("refs/heads/*")
+        .context("failed to read the local branches")?;
+
+    let mut commits = Vec::new();
+    for oid in walk {
+        let oid = oid.context("failed to walk the history")?;
+        let commit = repo
+            .find_commit(oid)
+            .with_context(|| format!("failed to look up commit {}", oid))?;
+        let when = commit.time();
+        // sorted newest first; a commit with a skewed date can still hide a newer one, but
+        // scanning the whole history for that isn't worth it
+        if when.seconds() < since {
+            break;
+        }
+        let mine = commit
+            .author()
+            .email()
+            .is_some_and(|email| email.eq_ignore_ascii_case(author));
+        if !mine || commit.parent_count() > 1 {
+            continue;
+        }
+        commits.push(StandupCommit {
+            repo: label.to_string(),
+            subject: commit.summary().unwrap_or_default().to_string(),
+            time: when.seconds(),
+            offset_minutes: when.offset_minutes(),
+        });
+    }
+    commits.reverse();
+    Ok(commits)
+}
+
+// the git repositories under `root` (`--recursive`), in path order; a repository's own
+// subdirectories aren't searched further
+pub fn discover_repos(root: &Path, max_depth: usize) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, depth)) = pending.pop() {
+        if dir.join(".git").exists() {
+            found.push(dir);
+            continue;
+        }
+        if depth == max_depth {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut children: Vec<PathBuf> = entries
+            .flatten()
+            .filter(|entry| entry.file_type().is_ok_and(|t| t.is_dir()))
+            .filter(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_str())
+            })
+            .map(|entry| entry.path())
+            .collect();
+        children.sort();
+        pending.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+    }
+    found
+}
+
+// commits per day (days since the epoch), oldest day first
+pub fn group_by_day(commits: &[StandupCommit]) -> BTreeMap<i64, Vec<&StandupCommit>> {
+    let mut days: BTreeMap<i64, Vec<&StandupCommit>> = BTreeMap::new();
+    for commit in commits {
+        days.entry(local_day(commit)).or_default().push(commit);
+    }
+    days
+}
+
+pub fn standup_prompt(commits: &[StandupCommit], several_repos: bool) -> String {
+    let skipped = commits.len().saturating_sub(MAX_COMMITS);
+    let commits = &commits[skipped..];
+    let mut prompt = String::from("Summarize these commits for a standup update.\n");
+    for (day, commits) in group_by_day(commits) {
+        prompt.push_str(&format!("\n{}:\n", day_label(day)));
+        for commit in commits {
+            match several_repos {
+                true => prompt.push_str(&format!("- [{}] {}\n", commit.repo, commit.subject)),
+                false => prompt.push_str(&format!("- {}\n", commit.subject)),
+            }
+        }
+    }
+    if skipped > 0 {
+        prompt.push_str(&format!("\n({} older commits were left out.)\n", skipped));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::{Oid, Signature, Time};
+
+    // 2024-05-01T00:00:00Z
+    const MAY_FIRST: i64 = 1_714_521_600;
+
+    fn commit(repo: &str, subject: &str, time: i64, offset_minutes: i32) -> StandupCommit {
+        StandupCommit {
+            repo: repo.to_string(),
+            subject: subject.to_string(),
+            time,
+            offset_minutes,
+        }
+    }
+
+    // commits on `branch` by `email` at `time`, on top of `parents`
+    fn commit_at(
+        repo: &Repository,
+        branch: &str,
+        email: &str,
+        subject: &str,
+        time: i64,
+        parents: &[Oid],
+    ) -> Oid {
+        let signature = Signature::new("Test", email, &Time::new(time, 0)).unwrap();
+        let tree = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree).unwrap();
+        let parents: Vec<_> = parents
+            .iter()
+            .map(|oid| repo.find_commit(*oid).unwrap())
+            .collect();
+        let parents: Vec<_> = parents.iter().collect();
+        repo.commit(
+            Some(branch),
+            &signature,
+            &signature,
+            subject,
+            &tree,
+            &parents,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn days_from_civil_undoes_civil_date() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2024, 5, 1), MAY_FIRST / 86_400);
+        for days in [-800_000, -1, 0, 59, 60, 11_016, 19_844, 2_932_896] {
+            let (year, month, day) = civil_date(days);
+            assert_eq!(days_from_civil(year, month, day), days);
+        }
+    }
+
+    #[test]
+    fn today_and_yesterday_start_at_local_midnight() {
+        // 01:00 UTC on May 2nd is 03:00 there in UTC+2, and still 20:00 on May 1st in UTC-5
+        let now = MAY_FIRST + 86_400 + 3600;
+        assert_eq!(
+            parse_since("today", now, 120).unwrap(),
+            MAY_FIRST + 22 * 3600
+        );
+        assert_eq!(
+            parse_since("Yesterday", now, 120).unwrap(),
+            MAY_FIRST - 2 * 3600
+        );
+        assert_eq!(
+            parse_since("today", now, -300).unwrap(),
+            MAY_FIRST + 5 * 3600
+        );
+        assert_eq!(parse_since(" today ", now, 0).unwrap(), MAY_FIRST + 86_400);
+    }
+
+    #[test]
+    fn counted_days_and_weeks_go_back_to_midnight_and_hours_dont() {
+        let now = MAY_FIRST + 86_400 + 9 * 3600;
+        assert_eq!(
+            parse_since("3 days ago", now, 0).unwrap(),
+            MAY_FIRST - 2 * 86_400
+        );
+        assert_eq!(parse_since("1 day", now, 0).unwrap(), MAY_FIRST);
+        assert_eq!(parse_since("2 hours", now, 0).unwrap(), now - 7200);
+        assert_eq!(parse_since("1 hour ago", now, 60).unwrap(), now - 3600);
+        assert_eq!(
+            parse_since("1 week ago", now, 0).unwrap(),
+            MAY_FIRST - 6 * 86_400
+        );
+    }
+
+    #[test]
+    fn dates_are_read_in_local_time() {
+        let now = MAY_FIRST + 30 * 86_400;
+        assert_eq!(parse_since("2024-05-01", now, 0).unwrap(), MAY_FIRST);
+        assert_eq!(
+            parse_since("2024-05-01", now, 120).unwrap(),
+            MAY_FIRST - 7200
+        );
+        let half_past_nine = MAY_FIRST + 9 * 3600 + 30 * 60;
+        assert_eq!(
+            parse_since("2024-05-01T09:30", now, 0).unwrap(),
+            half_past_nine
+        );
+        assert_eq!(
+            parse_since("2024-05-01 09:30:15", now, 0).unwrap(),
+            half_past_nine + 15
+        );
+        // a leap day is a real date
+        assert_eq!(
+            parse_since("2024-02-29", now, 0).unwrap(),
+            MAY_FIRST - 62 * 86_400
+        );
+    }
+
+    #[test]
+    fn anything_else_is_an_error_that_says_what_works() {
+        for text in [
+            "last tuesday",
+            "3 fortnights ago",
+            "2024-13-01",
+            "2024-02-31",
+            "2023-02-29",
+            "2024-04-31",
+            "2024-05-00",
+            "2024-05-01T25:00",
+            "",
+        ] {
+            let error = parse_since(text, MAY_FIRST, 0).unwrap_err().to_string();
+            assert!(error.starts_with("can't read --since"), "{}", error);
+            assert!(error.contains("'3 days ago'"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn collect_keeps_the_authors_commits_inside_the_window() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let since = MAY_FIRST;
+        let main = "refs/heads/main";
+        let me = "me@example.com";
+
+        let old = commit_at(&repo, main, me, "chore: before the window", since - 60, &[]);
+        let first = commit_at(&repo, main, me, "feat: the first one", since + 10, &[old]);
+        let theirs = commit_at(
+            &repo,
+            main,
+            "them@example.com",
+            "fix: someone else's",
+            since + 20,
+            &[first],
+        );
+        let merge = commit_at(
+            &repo,
+            main,
+            me,
+            "Merge branch 'side'",
+            since + 30,
+            &[theirs, old],
+        );
+        commit_at(
+            &repo,
+            main,
+            "ME@example.com",
+            "fix: the last one",
+            since + 40,
+            &[merge],
+        );
+        commit_at(
+            &repo,
+            "refs/heads/topic",
+            me,
+            "feat: on another branch",
+            since + 50,
+            &[old],
+        );
+
+        let commits = collect(&repo, "project", me, since).unwrap();
+        let subjects: Vec<&str> = commits.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(
+            subjects,
+            [
+                "feat: the first one",
+                "fix: the last one",
+                "feat: on another branch"
+            ]
+        );
+        assert!(commits.iter().all(|c| c.repo == "project"));
+        assert_eq!(commits[0].time, since + 10);
+    }
+
+    #[test]
+    fn commits_fall_on_the_day_of_their_own_time_zone() {
+        // 23:30 UTC on May 1st is already May 2nd in UTC+1
+        let late = MAY_FIRST + 23 * 3600 + 30 * 60;
+        let commits = [
+            commit("a", "one", late, 0),
+            commit("a", "two", late, 60),
+            commit("a", "three", MAY_FIRST + 3600, -120),
+        ];
+        let days = group_by_day(&commits);
+        let day = MAY_FIRST / 86_400;
+        let subjects =
+            |day: i64| -> Vec<&str> { days[&day].iter().map(|c| c.subject.as_str()).collect() };
+        assert_eq!(
+            days.keys().copied().collect::<Vec<_>>(),
+            [day - 1, day, day + 1]
+        );
+        assert_eq!(subjects(day - 1), ["three"]);
+        assert_eq!(subjects(day), ["one"]);
+        assert_eq!(subjects(day + 1), ["two"]);
+    }
+
+    #[test]
+    fn the_prompt_lists_commits_under_their_day() {
+        let commits = [
+            commit("api", "feat: add the endpoint", MAY_FIRST + 3600, 0),
+            commit("web", "fix: the form", MAY_FIRST + 7200, 0),
+            commit("api", "docs: the endpoint", MAY_FIRST + 86_400, 0),
+        ];
+        assert_eq!(
+            standup_prompt(&commits, false),
+            "Summarize these commits for a standup update.\n\
+             \nWednesday 2024-05-01:\n- feat: add the endpoint\n- fix: the form\n\
+             \nThursday 2024-05-02:\n- docs: the endpoint\n"
+        );
+        let several = standup_prompt(&commits, true);
+        assert!(several.contains("- [api] feat: add the endpoint\n- [web] fix: the form\n"));
+        assert_eq!(day_label(0), "Thursday 1970-01-01");
+    }
+
+    #[test]
+    fn the_prompt_keeps_the_newest_commits() {
+        let commits: Vec<_> = (0..MAX_COMMITS as i64 + 3)
+            .map(|i| commit("a", &format!("commit {}", i), MAY_FIRST + i, 0))
+            .collect();
+        let prompt = standup_prompt(&commits, false);
+        assert!(!prompt.contains("- commit 2\n"));
+        assert!(prompt.contains("- commit 3\n"));
+        assert!(prompt.ends_with("\n(3 older commits were left out.)\n"));
+    }
+
+    #[test]
+    fn discover_repos_skips_dot_and_build_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        for path in [
+            "b",
+            "a",
+            "a/nested",
+            ".hidden",
+            "node_modules/dep",
+            "deep/er",
+        ] {
+            Repository::init(root.join(path)).unwrap();
+        }
+        Repository::init(root.join("too/deep/for/three")).unwrap();
+
+        let found: Vec<PathBuf> = discover_repos(root, 3)
+            .into_iter()
+            .map(|path| path.strip_prefix(root).unwrap().to_path_buf())
+            .collect();
+        assert_eq!(
+            found,
+            [
+                PathBuf::from("a"),
+                PathBuf::from("b"),
+                PathBuf::from("deep/er")
+            ]
+        );
+        assert_eq!(discover_repos(&root.join("a"), 3), [root.join("a")]);
+    }
+}
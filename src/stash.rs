@@ -0,0 +1,58 @@
+// src/stash.rs
+// stash: stashing the dirty working tree under a generated description
+
+use anyhow::{anyhow, Context, Result};
+use git2::{Diff, DiffOptions, ErrorCode, Oid, Repository, StashFlags, Status, StatusOptions};
+
+// what there is to stash: tracked changes (staged or not), and untracked files
+pub fn dirty_state(repo: &Repository) -> Result<(bool, bool)> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(true).include_ignored(false);
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .context("failed to read the status")?;
+    let mut tracked = false;
+    let mut untracked = false;
+    for entry in statuses.iter() {
+        if entry.status().contains(Status::WT_NEW) {
+            untracked = true;
+        } else if !entry.status().is_empty() && !entry.status().contains(Status::IGNORED) {
+            tracked = true;
+        }
+    }
+    Ok((tracked, untracked))
+}
+
+// HEAD against the working tree as it would be stashed, index changes included
+pub fn dirty_diff(repo: &Repository, include_untracked: bool) -> Result<Diff<'_>> {
+    let head = repo
+        .head()
+        .and_then(|head| head.peel_to_tree())
+        .map_err(|_| anyhow!("there is no commit to stash against yet"))?;
+    let mut options = DiffOptions::new();
+    options.ignore_whitespace(true);
+    if include_untracked {
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .show_untracked_content(true);
+    }
+    repo.diff_tree_to_workdir_with_index(Some(&head), Some(&mut options))
+        .context("failed to diff the working tree against HEAD")
+}
+
+// `git stash push -m <message>`; git2 stores it as "On <branch>: <message>" like git does
+pub fn save(repo: &mut Repository, message: &str, include_untracked: bool) -> Result<Oid> {
+    let signature = repo
+        .signature()
+        .context("failed to get signature (check user.name and user.email)")?;
+    let flags = match include_untracked {
+        true => StashFlags::INCLUDE_UNTRACKED,
+        false => StashFlags::DEFAULT,
+    };
+    repo.stash_save(&signature, message, Some(flags))
+        .map_err(|e| match e.code() {
+            ErrorCode::NotFound => anyhow!("nothing to stash"),
+            _ => anyhow!("git refused to stash: {}", e.message()),
+        })
+}
@@ -0,0 +1,106 @@
+// src/style.rs
+// color for a person at a terminal: the subject, the +/- counts and warnings. Nothing is colored
+// with --no-color, NO_COLOR or a TERM without colors, or on a stream that isn't a terminal.
+// Also the few characters the tool draws itself, which --ascii keeps to plain ASCII
+
+use anstyle::{AnsiColor, Style};
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STDOUT: AtomicBool = AtomicBool::new(false);
+static STDERR: AtomicBool = AtomicBool::new(false);
+static ASCII: AtomicBool = AtomicBool::new(false);
+
+const SUBJECT: Style = Style::new().bold();
+const ADDED: Style = AnsiColor::Green.on_default();
+const REMOVED: Style = AnsiColor::Red.on_default();
+const WARNING: Style = AnsiColor::Yellow.on_default().bold();
+const ERROR: Style = AnsiColor::Red.on_default().bold();
+
+// every non-ASCII character of the tool's own output, so --ascii is one switch; what comes
+// from the model or the repository is printed as it is
+pub struct Glyphs {
+    // frames of the spinner; none means a plain "label..." line instead
+    pub spinner: &'static [char],
+    // doctor's marks for a check that passed, failed, or found something worth knowing
+    pub pass: &'static str,
+    pub fail: &'static str,
+    pub note: &'static str,
+}
+
+const UNICODE_GLYPHS: Glyphs = Glyphs {
+    spinner: &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'],
+    pass: "✓",
+    fail: "✗",
+    note: "!",
+};
+const ASCII_GLYPHS: Glyphs = Glyphs {
+    spinner: &[],
+    pass: "ok",
+    fail: "xx",
+    note: "!!",
+};
+
+pub fn glyphs() -> &'static Glyphs {
+    match ASCII.load(Ordering::Relaxed) {
+        true => &ASCII_GLYPHS,
+        false => &UNICODE_GLYPHS,
+    }
+}
+
+// TERM=dumb, or a locale that isn't UTF-8 (LC_ALL, then LC_CTYPE, then LANG, where an unset one
+// means C). The Windows console doesn't say through either, so it's never limited
+fn limited_terminal() -> bool {
+    if std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+        return true;
+    }
+    if cfg!(windows) {
+        return false;
+    }
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .into_iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    !(locale.contains("utf-8") || locale.contains("utf8"))
+}
+
+pub fn init(no_color: bool, ascii: bool) {
+    ASCII.store(ascii || limited_terminal(), Ordering::Relaxed);
+    let wanted = !no_color && !anstyle_query::no_color() && anstyle_query::term_supports_color();
+    // the Windows console only reads escape codes once it's told to
+    #[cfg(windows)]
+    let wanted = wanted && anstyle_query::windows::enable_ansi_colors().unwrap_or(false);
+    STDOUT.store(wanted && io::stdout().is_terminal(), Ordering::Relaxed);
+    STDERR.store(wanted && io::stderr().is_terminal(), Ordering::Relaxed);
+}
+
+fn paint(enabled: &AtomicBool, style: Style, text: &str) -> String {
+    match enabled.load(Ordering::Relaxed) {
+        true => format!("{}{}{:#}", style, text, style),
+        false => text.to_string(),
+    }
+}
+
+// on stdout
+pub fn subject(text: &str) -> String {
+    paint(&STDOUT, SUBJECT, text)
+}
+
+// on stderr
+pub fn added(count: usize) -> String {
+    paint(&STDERR, ADDED, &format!("+{}", count))
+}
+
+pub fn removed(count: usize) -> String {
+    paint(&STDERR, REMOVED, &format!("-{}", count))
+}
+
+pub fn warning(label: &str) -> String {
+    paint(&STDERR, WARNING, label)
+}
+
+pub fn error(label: &str) -> String {
+    paint(&STDERR, ERROR, label)
+}
@@ -0,0 +1,146 @@
+// src/tag.rs
+// annotated release tags: finding the previous tag, prompting, and creating the tag
+
+use crate::changelog::{self, Entry};
+use anyhow::{anyhow, Context, Result};
+use git2::{Oid, Reference, Repository, Sort};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+pub const TAG_MAX_OUTPUT_TOKENS: u32 = 512;
+
+pub const TAG_SYSTEM_PROMPT: &str = "You write annotated git tag messages for releases. Reply with the message only: a one-line summary of the release (plain text, under 72 characters), a blank line, then a few short bullet points with the most important changes, breaking changes first. Stay close to the commits you are given, do not invent changes, and do not use markdown headings.";
+
+pub fn is_valid_name(name: &str) -> bool {
+    Reference::is_valid_name(&format!("refs/tags/{}", name))
+}
+
+pub fn exists(repo: &Repository, name: &str) -> bool {
+    repo.find_reference(&format!("refs/tags/{}", name)).is_ok()
+}
+
+// like `git describe --tags --abbrev=0`: the first tag met walking back from HEAD, either
+// kind; `skip` is left out so re-tagging with --force still finds the one before
+pub fn previous_tag(repo: &Repository, skip: Option<&str>) -> Result<Option<String>> {
+    let mut tagged: HashMap<Oid, Vec<String>> = HashMap::new();
+    for reference in repo
+        .references_glob("refs/tags/*")
+        .context("failed to list tags")?
+    {
+        let reference = reference.context("failed to read a tag")?;
+        let Some(tag) = reference.shorthand().map(str::to_string) else {
+            continue;
+        };
+        if Some(tag.as_str()) == skip {
+            continue;
+        }
+        if let Ok(commit) = reference.peel_to_commit() {
+            tagged.entry(commit.id()).or_default().push(tag);
+        }
+    }
+    if tagged.is_empty() {
+        return Ok(None);
+    }
+
+    let mut walk = repo.revwalk().context("failed to create revision walker")?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+        .context("failed to set revision walk order")?;
+    walk.push_head().context("failed to start walk at HEAD")?;
+    for oid in walk {
+        let oid = oid.context("failed to walk history")?;
+        if let Some(tags) = tagged.get_mut(&oid) {
+            // several tags on one commit: the highest sorting name, as in v1.0.0 and v1.0.0-rc1
+            tags.sort();
+            return Ok(tags.pop());
+        }
+    }
+    Ok(None)
+}
+
+pub fn tag_prompt(name: &str, from: Option<&str>, entries: &[Entry]) -> String {
+    let since = match from {
+        Some(from) => format!("since {}", from),
+        None => "(the first release)".to_string(),
+    };
+    let mut prompt = format!(
+        "Write the annotation for tag {} {}, from these commits grouped by section:\n",
+        name, since
+    );
+    for (section, entries) in changelog::group(entries) {
+        prompt.push_str(&format!("\n{}:\n", section.title()));
+        for entry in entries {
+            prompt.push_str(&format!("- {}\n", entry.subject));
+        }
+    }
+    prompt
+}
+
+// the reply without code fences or surrounding blank lines
+pub fn clean_message(response: &str) -> String {
+    let lines: Vec<&str> = response
+        .trim()
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect();
+    lines.join("\n").trim().to_string()
+}
+
+// signing tags goes through git itself, like --use-git-binary for commits
+pub fn wants_signature(repo: &Repository, sign: bool) -> bool {
+    sign || repo
+        .config()
+        .and_then(|config| config.get_bool("tag.gpgSign"))
+        .unwrap_or(false)
+}
+
+// an annotated tag on HEAD with the configured identity as tagger
+pub fn create(repo: &Repository, name: &str, message: &str, force: bool) -> Result<Oid> {
+    let head = repo
+        .head()
+        .and_then(|head| head.peel(git2::ObjectType::Commit))
+        .map_err(|_| anyhow!("tagging needs an existing HEAD commit"))?;
+    let tagger = repo
+        .signature()
+        .context("failed to get signature (check user.name and user.email)")?;
+    repo.tag(
+        name,
+        &head,
+        &tagger,
+        &format!("{}\n", message.trim_end()),
+        force,
+    )
+    .with_context(|| format!("failed to create tag '{}'", name))
+}
+
+// `git tag -s`, which reads gpg.format and user.signingKey itself
+pub fn create_signed_with_git(
+    repo: &Repository,
+    name: &str,
+    message: &str,
+    force: bool,
+) -> Result<()> {
+    let directory = repo.workdir().unwrap_or(repo.path());
+    let mut command = Command::new("git");
+    command.args(["tag", "-s", "--cleanup=whitespace", "-F", "-"]);
+    if force {
+        command.arg("--force");
+    }
+    let mut child = command
+        .arg(name)
+        .current_dir(directory)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("failed to run git")?;
+    child
+        .stdin
+        .take()
+        .context("failed to open git's stdin")?
+        .write_all(format!("{}\n", message.trim_end()).as_bytes())
+        .context("failed to pass the message to git")?;
+    let status = child.wait().context("failed to wait for git")?;
+    if !status.success() {
+        return Err(anyhow!("git tag exited with {}", status));
+    }
+    Ok(())
+}
@@ -0,0 +1,268 @@
+// src/template.rs
+// --format-string: the result printed through a template of `{placeholder}`s filled from the
+// parsed message and the run's metadata. `{{` and `}}` are literal braces, and `\n`, `\t` and
+// `\\` are escapes, so a template can be written inside single quotes
+
+use crate::conventional::ConventionalMessage;
+use crate::report::Usage;
+use crate::trailers::Trailer;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Type,
+    Scope,
+    Subject,
+    Title,
+    Body,
+    Trailers,
+    Message,
+    Breaking,
+    Files,
+    Model,
+    TokensIn,
+    TokensOut,
+}
+
+// in the order the error lists them
+const FIELDS: [(&str, Field); 12] = [
+    ("type", Field::Type),
+    ("scope", Field::Scope),
+    ("subject", Field::Subject),
+    ("title", Field::Title),
+    ("body", Field::Body),
+    ("trailers", Field::Trailers),
+    ("message", Field::Message),
+    ("breaking", Field::Breaking),
+    ("files", Field::Files),
+    ("model", Field::Model),
+    ("tokens_in", Field::TokensIn),
+    ("tokens_out", Field::TokensOut),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError(String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+// what a template is filled from
+pub struct Values<'a> {
+    pub message: &'a ConventionalMessage,
+    pub files: &'a [String],
+    pub model: &'a str,
+    pub usage: &'a Usage,
+}
+
+impl Template {
+    // every placeholder is checked here, so a typo fails before anything is generated
+    pub fn parse(text: &str) -> Result<Template, TemplateError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => {
+                    return Err(TemplateError(
+                        "unmatched '}' in the template; write '}}' for a literal brace".to_string(),
+                    ))
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(TemplateError(
+                                    "unclosed '{' in the template; write '{{' for a literal brace"
+                                        .to_string(),
+                                ))
+                            }
+                        }
+                    }
+                    let field = FIELDS
+                        .iter()
+                        .find(|(known, _)| *known == name.trim())
+                        .map(|&(_, field)| field)
+                        .ok_or_else(|| {
+                            let known: Vec<String> = FIELDS
+                                .iter()
+                                .map(|(name, _)| format!("{{{}}}", name))
+                                .collect();
+                            TemplateError(format!(
+                                "unknown placeholder {{{}}} (available: {})",
+                                name,
+                                known.join(", ")
+                            ))
+                        })?;
+                    if !literal.is_empty() {
+                        segments.push(Segment::Text(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Field(field));
+                }
+                '\\' => match chars.peek() {
+                    Some('n') => {
+                        chars.next();
+                        literal.push('\n');
+                    }
+                    Some('t') => {
+                        chars.next();
+                        literal.push('\t');
+                    }
+                    Some('\\') => {
+                        chars.next();
+                        literal.push('\\');
+                    }
+                    _ => literal.push('\\'),
+                },
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Text(literal));
+        }
+        Ok(Template { segments })
+    }
+
+    // a message without a `type(scope):` prefix has an empty {type} and {scope}, and its whole
+    // first line as {subject}
+    pub fn render(&self, values: &Values) -> String {
+        let message = values.message;
+        let prefix = message.prefix.as_ref();
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Field(field) => out.push_str(&match field {
+                    Field::Type => prefix.map(|p| p.kind.clone()).unwrap_or_default(),
+                    Field::Scope => prefix.and_then(|p| p.scope.clone()).unwrap_or_default(),
+                    Field::Subject => message.subject.clone(),
+                    Field::Title => message.title(),
+                    Field::Body => message.body_text(),
+                    Field::Trailers => {
+                        let trailers: Vec<String> =
+                            message.trailers.iter().map(Trailer::render).collect();
+                        trailers.join("\n")
+                    }
+                    Field::Message => message.to_string(),
+                    Field::Breaking => message.is_breaking().to_string(),
+                    Field::Files => values.files.join(", "),
+                    Field::Model => values.model.to_string(),
+                    Field::TokensIn => values.usage.prompt_tokens.to_string(),
+                    Field::TokensOut => values.usage.output_tokens.to_string(),
+                }),
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, message: &str) -> String {
+        let message = ConventionalMessage::parse(message);
+        let files = ["src/api.rs".to_string(), "src/lib.rs".to_string()];
+        let usage = Usage {
+            prompt_tokens: 420,
+            output_tokens: 17,
+        };
+        let values = Values {
+            message: &message,
+            files: &files,
+            model: "gemini-2.0-flash",
+            usage: &usage,
+        };
+        Template::parse(template).unwrap().render(&values)
+    }
+
+    const MESSAGE: &str =
+        "feat(api)!: drop the v1 routes\n\n- remove the old handlers\n\nRefs: #12";
+
+    #[test]
+    fn every_placeholder_is_filled() {
+        assert_eq!(
+            render("{type}|{scope}|{subject}|{breaking}", MESSAGE),
+            "feat|api|drop the v1 routes|true"
+        );
+        assert_eq!(
+            render("{title}\n{body}\n{trailers}", MESSAGE),
+            "feat(api)!: drop the v1 routes\n- remove the old handlers\nRefs: #12"
+        );
+        assert_eq!(render("{message}", MESSAGE), MESSAGE);
+        assert_eq!(
+            render(
+                "{files} by {model}: {tokens_in} in, {tokens_out} out",
+                MESSAGE
+            ),
+            "src/api.rs, src/lib.rs by gemini-2.0-flash: 420 in, 17 out"
+        );
+        assert_eq!(render("{ type }", MESSAGE), "feat");
+    }
+
+    #[test]
+    fn a_message_without_a_prefix_has_an_empty_type_and_scope() {
+        assert_eq!(
+            render("[{type}][{scope}] {subject} {breaking}", "Update the notes"),
+            "[][] Update the notes false"
+        );
+    }
+
+    #[test]
+    fn braces_double_and_backslashes_escape() {
+        assert_eq!(
+            render("{{{type}}}\\t{{}}\\n\\\\n \\x", MESSAGE),
+            "{feat}\t{}\n\\n \\x"
+        );
+        assert_eq!(render("plain text", MESSAGE), "plain text");
+        assert_eq!(render("", MESSAGE), "");
+    }
+
+    #[test]
+    fn mistakes_are_caught_when_parsing() {
+        let error = Template::parse("{type}: {subjcet}")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("unknown placeholder {subjcet} (available: {type}, {scope}, "),
+            "{}",
+            error
+        );
+        assert!(error.ends_with("{tokens_in}, {tokens_out})"), "{}", error);
+        assert!(Template::parse("{type")
+            .unwrap_err()
+            .to_string()
+            .starts_with("unclosed '{'"));
+        assert!(Template::parse("type}")
+            .unwrap_err()
+            .to_string()
+            .starts_with("unmatched '}'"));
+        assert!(Template::parse("{}").is_err());
+    }
+}
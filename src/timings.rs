@@ -0,0 +1,196 @@
+// src/timings.rs
+// --timings: a tracing layer that adds up how long each span was open, and the table printed
+// from that at the end. The phases are the spans the code already has, so -vvv and the table
+// report the same ones
+
+use std::fmt::{self, Write as _};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+// one row per span name, in the order the spans were first opened
+static PHASES: Mutex<Vec<Phase>> = Mutex::new(Vec::new());
+static STARTED: Mutex<Option<Instant>> = Mutex::new(None);
+
+// a span name with everything recorded under it
+#[derive(Debug, Clone)]
+pub struct Phase {
+    pub name: &'static str,
+    // how many of the other phases it runs inside
+    pub depth: usize,
+    pub count: u32,
+    pub elapsed: Duration,
+    // the `bytes` and `tokens` fields of its spans, added up
+    pub bytes: Option<u64>,
+    pub tokens: Option<u64>,
+}
+
+// the layer for logging::init; the table is printed only when this was called
+pub fn layer() -> Timings {
+    ENABLED.store(true, Ordering::Relaxed);
+    *STARTED.lock().unwrap() = Some(Instant::now());
+    Timings
+}
+
+pub struct Timings;
+
+// kept in a span's extensions while it is open
+struct Open {
+    row: usize,
+    opened: Instant,
+    fields: Fields,
+}
+
+#[derive(Default)]
+struct Fields {
+    bytes: Option<u64>,
+    tokens: Option<u64>,
+}
+
+impl Visit for Fields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "bytes" => self.bytes = Some(value),
+            "tokens" => self.tokens = Some(value),
+            _ => {}
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if let Ok(value) = u64::try_from(value) {
+            self.record_u64(field, value);
+        }
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+}
+
+impl<S> Layer<S> for Timings
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let name = span.name();
+        let depth = span.scope().skip(1).count();
+        let mut phases = PHASES.lock().unwrap();
+        let row = match phases.iter().position(|phase| phase.name == name) {
+            Some(row) => row,
+            None => {
+                phases.push(Phase {
+                    name,
+                    depth,
+                    count: 0,
+                    elapsed: Duration::ZERO,
+                    bytes: None,
+                    tokens: None,
+                });
+                phases.len() - 1
+            }
+        };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(Open {
+            row,
+            opened: Instant::now(),
+            fields,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(open) = span.extensions_mut().get_mut::<Open>() {
+                values.record(&mut open.fields);
+            }
+        }
+    }
+
+    // wall time from opening to closing: a request spends most of it waiting, which is the point
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(open) = ctx
+            .span(&id)
+            .and_then(|span| span.extensions_mut().remove::<Open>())
+        else {
+            return;
+        };
+        let mut phases = PHASES.lock().unwrap();
+        let phase = &mut phases[open.row];
+        phase.count += 1;
+        phase.elapsed += open.opened.elapsed();
+        let add = |total: Option<u64>, value: Option<u64>| match (total, value) {
+            (total, None) => total,
+            (total, Some(value)) => Some(total.unwrap_or(0) + value),
+        };
+        phase.bytes = add(phase.bytes, open.fields.bytes);
+        phase.tokens = add(phase.tokens, open.fields.tokens);
+    }
+}
+
+// the phases as a table; nested ones are indented under the phase they ran in
+pub fn render(phases: &[Phase], total: Duration) -> String {
+    let label = |phase: &Phase| match phase.count {
+        1 => format!("{}{}", "  ".repeat(phase.depth), phase.name),
+        n => format!("{}{} ({}x)", "  ".repeat(phase.depth), phase.name, n),
+    };
+    let width = phases
+        .iter()
+        .map(|phase| label(phase).len())
+        .chain(["total".len()])
+        .max()
+        .unwrap_or(0);
+    let mut table = String::from("timings:\n");
+    for phase in phases {
+        let mut extra = Vec::new();
+        if let Some(bytes) = phase.bytes {
+            extra.push(format!("{} bytes", bytes));
+        }
+        if let Some(tokens) = phase.tokens {
+            extra.push(format!("{} tokens", tokens));
+        }
+        let row = format!(
+            "  {:<width$}  {:>10}  {}",
+            label(phase),
+            millis(phase.elapsed),
+            extra.join(", "),
+            width = width
+        );
+        let _ = writeln!(table, "{}", row.trim_end());
+    }
+    let _ = writeln!(
+        table,
+        "  {:<width$}  {:>10}",
+        "total",
+        millis(total),
+        width = width
+    );
+    table
+}
+
+fn millis(elapsed: Duration) -> String {
+    format!("{:.1} ms", elapsed.as_secs_f64() * 1000.0)
+}
+
+// prints the table to stderr, out of the way of anything piped; nothing without --timings or
+// when no phase ran
+pub fn print() {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let phases = PHASES.lock().unwrap().clone();
+    if phases.is_empty() {
+        return;
+    }
+    let total = STARTED
+        .lock()
+        .unwrap()
+        .map_or(Duration::ZERO, |started| started.elapsed());
+    eprint!("{}", render(&phases, total));
+}
@@ -0,0 +1,246 @@
+// src/trailers.rs
+// git trailers (Signed-off-by, Co-authored-by, ...) appended after the message body
+
+use crate::conventional::ConventionalMessage;
+use anyhow::{anyhow, Context, Result};
+use git2::Repository;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trailer {
+    pub key: String,
+    // ": " for `Key: value`, " #" for `Refs #123`-style footers
+    pub separator: String,
+    pub value: String,
+}
+
+impl Trailer {
+    pub fn new(key: &str, value: &str) -> Self {
+        Trailer {
+            key: key.to_string(),
+            separator: ": ".to_string(),
+            value: value.trim().to_string(),
+        }
+    }
+
+    pub fn parse(line: &str) -> Option<Self> {
+        if !crate::validate::is_footer_line(line) {
+            return None;
+        }
+        let line = line.trim();
+        let (key, separator, value) = match line.split_once(": ") {
+            Some((key, value)) if !key.contains(' ') || key.starts_with("BREAKING") => {
+                (key, ": ", value)
+            }
+            _ => {
+                let (key, value) = line.split_once(" #")?;
+                (key, " #", value)
+            }
+        };
+        Some(Trailer {
+            key: key.to_string(),
+            separator: separator.to_string(),
+            value: value.to_string(),
+        })
+    }
+
+    pub fn render(&self) -> String {
+        format!("{}{}{}", self.key, self.separator, self.value)
+    }
+}
+
+// Signed-off-by for the identity configured in the repository
+pub fn signoff(repo: &Repository) -> Result<Trailer> {
+    let config = repo.config().context("failed to read git config")?;
+    let name = config.get_string("user.name").ok();
+    let email = config.get_string("user.email").ok();
+    match (name, email) {
+        (Some(name), Some(email)) => Ok(Trailer::new(
+            "Signed-off-by",
+            &format!("{} <{}>", name, email),
+        )),
+        _ => Err(anyhow!(
+            "--signoff needs user.name and user.email to be set in git config"
+        )),
+    }
+}
+
+// trailer disclosing that the message was generated, from the configured key and template
+pub fn attribution(key: &str, template: &str, model: &str) -> Trailer {
+    let value = template
+        .replace("{model}", model)
+        .replace("{version}", env!("CARGO_PKG_VERSION"));
+    Trailer::new(key, &value)
+}
+
+// clap value parser for `Name <email>` identities
+pub fn parse_identity(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    let invalid = || format!("'{}' must have the form 'Name <email>'", value);
+
+    let (name, rest) = value.split_once('<').ok_or_else(invalid)?;
+    let email = rest.strip_suffix('>').ok_or_else(invalid)?;
+    let valid_email = email.contains('@')
+        && !email.starts_with('@')
+        && !email.ends_with('@')
+        && !email.contains(char::is_whitespace)
+        && !email.contains(['<', '>']);
+    if name.trim().is_empty() || !valid_email {
+        return Err(invalid());
+    }
+    Ok(format!("{} <{}>", name.trim(), email))
+}
+
+// the trailers at the end of a message, excluding BREAKING CHANGE notes (which are content, not metadata)
+pub fn extract(message: &str) -> Vec<Trailer> {
+    ConventionalMessage::parse(message)
+        .trailers
+        .into_iter()
+        .filter(|t| !t.key.starts_with("BREAKING"))
+        .collect()
+}
+
+// re-attaches trailers from the original message that the model dropped from its rewrite
+pub fn preserve(original: &str, generated: &str) -> String {
+    append(generated, &extract(original))
+}
+
+// adds a paragraph at the end of the body, above the trailers if there are any
+pub fn insert_paragraph(message: &str, paragraph: &str) -> String {
+    let mut parsed = ConventionalMessage::parse(message);
+    parsed.push_paragraph(paragraph);
+    parsed.to_string()
+}
+
+// appends trailers after the body (and after any BREAKING CHANGE footer), skipping duplicates
+pub fn append(message: &str, trailers: &[Trailer]) -> String {
+    let mut parsed = ConventionalMessage::parse(message);
+    parsed.add_trailers(trailers);
+    parsed.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailers_parse_with_either_separator_and_render_back() {
+        for line in [
+            "Signed-off-by: Ada <ada@example.com>",
+            "Refs #123",
+            "BREAKING CHANGE: the config moved",
+        ] {
+            let trailer = Trailer::parse(line).unwrap();
+            assert_eq!(trailer.render(), line);
+        }
+        let refs = Trailer::parse("Refs #123").unwrap();
+        assert_eq!((refs.key.as_str(), refs.separator.as_str()), ("Refs", " #"));
+        assert_eq!(Trailer::parse("not a trailer at all"), None);
+        assert_eq!(Trailer::parse("Two words: value"), None);
+    }
+
+    #[test]
+    fn identities_need_a_name_and_an_email() {
+        assert_eq!(
+            parse_identity("  Ada Lovelace   <ada@example.com> ").as_deref(),
+            Ok("Ada Lovelace <ada@example.com>")
+        );
+        for invalid in [
+            "Ada",
+            "<ada@example.com>",
+            "Ada <ada.example.com>",
+            "Ada <@example.com>",
+            "Ada <ada@>",
+            "Ada <ada @example.com>",
+            "Ada <ada@example.com",
+        ] {
+            assert!(parse_identity(invalid).is_err(), "{} was accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn signoff_is_the_configured_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let mut config = repo.config().unwrap();
+        config.set_str("user.name", "Ada").unwrap();
+        config.set_str("user.email", "ada@example.com").unwrap();
+        assert_eq!(
+            signoff(&repo).unwrap().render(),
+            "Signed-off-by: Ada <ada@example.com>"
+        );
+    }
+
+    #[test]
+    fn appended_trailers_go_last_and_once() {
+        let signed = Trailer::new("Signed-off-by", "Ada <ada@example.com>");
+        let coauthor = Trailer::new("Co-authored-by", "Bob <bob@example.com>");
+        assert_eq!(
+            append("feat: add notes", std::slice::from_ref(&signed)),
+            "feat: add notes\n\nSigned-off-by: Ada <ada@example.com>"
+        );
+        assert_eq!(
+            append(
+                "feat!: move the notes\n\n- into docs/\n\nBREAKING CHANGE: notes.txt is gone\nSigned-off-by: Ada <ada@example.com>",
+                &[signed, coauthor],
+            ),
+            "feat!: move the notes\n\n- into docs/\n\nBREAKING CHANGE: notes.txt is gone\nSigned-off-by: Ada <ada@example.com>\nCo-authored-by: Bob <bob@example.com>"
+        );
+    }
+
+    #[test]
+    fn attribution_fills_in_the_model_and_version() {
+        let trailer = attribution(
+            "Generated-by",
+            "{model} via git-changes-rs {version}",
+            "gemini-2.5-pro",
+        );
+        assert_eq!(
+            trailer.render(),
+            format!(
+                "Generated-by: gemini-2.5-pro via git-changes-rs {}",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+        assert_eq!(
+            attribution("Generated-by", "  a person  ", "m").value,
+            "a person"
+        );
+    }
+
+    #[test]
+    fn extract_leaves_out_breaking_change_notes() {
+        let message = "feat!: move the notes\n\n- into docs/\n\nBREAKING CHANGE: notes.txt is gone\nRefs #12\nSigned-off-by: Ada <ada@example.com>";
+        assert_eq!(
+            extract(message)
+                .iter()
+                .map(Trailer::render)
+                .collect::<Vec<_>>(),
+            ["Refs #12", "Signed-off-by: Ada <ada@example.com>"]
+        );
+        assert!(extract("feat: add notes\n\n- a body: with a colon").is_empty());
+    }
+
+    #[test]
+    fn preserve_puts_back_only_the_trailers_the_rewrite_dropped() {
+        let original = "fix: stuff\n\nRefs #12\nSigned-off-by: Ada <ada@example.com>";
+        assert_eq!(
+            preserve(original, "fix(notes): keep the notes\n\nRefs #12"),
+            "fix(notes): keep the notes\n\nRefs #12\nSigned-off-by: Ada <ada@example.com>"
+        );
+        assert_eq!(
+            preserve("fix: stuff", "fix(notes): keep the notes"),
+            "fix(notes): keep the notes"
+        );
+    }
+
+    #[test]
+    fn insert_paragraph_goes_above_the_trailers() {
+        assert_eq!(
+            insert_paragraph(
+                "fix: keep the notes\n\nSigned-off-by: Ada <ada@example.com>",
+                "(cherry picked from commit abc)"
+            ),
+            "fix: keep the notes\n\n(cherry picked from commit abc)\n\nSigned-off-by: Ada <ada@example.com>"
+        );
+    }
+}
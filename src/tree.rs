@@ -0,0 +1,159 @@
+// src/tree.rs
+// compact two-level view of the tracked files, included in the prompt with --tree-context
+
+use std::collections::BTreeMap;
+
+// limits for the rendered tree; beyond them directories are summarized by file count
+pub const MAX_TREE_ENTRIES: usize = 300;
+pub const MAX_TREE_BYTES: usize = 2048;
+// children listed under one top-level directory before the rest collapse into "(+N more files)"
+const MAX_DIR_ENTRIES: usize = 20;
+
+#[derive(Default)]
+struct Node {
+    dirs: BTreeMap<String, Node>,
+    files: Vec<String>,
+}
+
+impl Node {
+    fn insert(&mut self, path: &str) {
+        match path.split_once('/') {
+            Some((dir, rest)) => self.dirs.entry(dir.to_string()).or_default().insert(rest),
+            None => self.files.push(path.to_string()),
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.files.len() + self.dirs.values().map(Node::count).sum::<usize>()
+    }
+
+    // (line text, files the line accounts for); directories first, then files
+    fn entries(&self) -> Vec<(String, usize)> {
+        let mut entries: Vec<(String, usize)> = self
+            .dirs
+            .iter()
+            .map(|(name, node)| {
+                (
+                    format!("{}/ ({})", name, files_label(node.count())),
+                    node.count(),
+                )
+            })
+            .collect();
+        entries.extend(self.files.iter().map(|name| (name.clone(), 1)));
+        entries
+    }
+}
+
+fn files_label(count: usize) -> String {
+    if count == 1 {
+        "1 file".to_string()
+    } else {
+        format!("{} files", count)
+    }
+}
+
+fn more_line(indent: &str, count: usize) -> String {
+    format!(
+        "{}(+{} more {})",
+        indent,
+        count,
+        if count == 1 { "file" } else { "files" }
+    )
+}
+
+// renders repository-relative paths ('/'-separated) as the top two directory levels.
+// pure, so the same index always yields the same text.
+pub fn render_tree(paths: &[String], max_entries: usize, max_bytes: usize) -> String {
+    let mut root = Node::default();
+    for path in paths {
+        root.insert(path.trim_start_matches('/'));
+    }
+    let total = root.count();
+
+    // every candidate line in order, paired with the files it newly accounts for
+    let mut lines: Vec<(String, usize)> = Vec::new();
+    for (name, node) in &root.dirs {
+        lines.push((format!("{}/ ({})", name, files_label(node.count())), 0));
+        let children = node.entries();
+        let shown = children.len().min(MAX_DIR_ENTRIES);
+        lines.extend(
+            children[..shown]
+                .iter()
+                .map(|(text, covers)| (format!("  {}", text), *covers)),
+        );
+        let hidden: usize = children[shown..].iter().map(|(_, covers)| covers).sum();
+        if hidden > 0 {
+            lines.push((more_line("  ", hidden), hidden));
+        }
+    }
+    lines.extend(root.files.iter().map(|name| (name.clone(), 1)));
+
+    let mut out = String::new();
+    let mut covered = 0;
+    for (idx, (text, covers)) in lines.iter().enumerate() {
+        // leave room for the closing summary line
+        if idx >= max_entries || out.len() + text.len() + 32 > max_bytes {
+            if total > covered {
+                out.push_str(&more_line("", total - covered));
+            }
+            return out.trim_end().to_string();
+        }
+        out.push_str(text);
+        out.push('\n');
+        covered += covers;
+    }
+    out.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(paths: &[&str]) -> Vec<String> {
+        paths.iter().map(|path| path.to_string()).collect()
+    }
+
+    #[test]
+    fn two_levels_with_directories_first() {
+        let rendered = render_tree(
+            &paths(&[
+                "README.md",
+                "src/main.rs",
+                "src/commands/generate.rs",
+                "src/commands/lint.rs",
+                "tests/e2e.rs",
+                "Cargo.toml",
+            ]),
+            MAX_TREE_ENTRIES,
+            MAX_TREE_BYTES,
+        );
+        assert_eq!(
+            rendered,
+            "src/ (3 files)\n  commands/ (2 files)\n  main.rs\ntests/ (1 file)\n  e2e.rs\nREADME.md\nCargo.toml"
+        );
+    }
+
+    #[test]
+    fn a_big_directory_collapses_past_its_first_entries() {
+        let many: Vec<String> = (0..25).map(|i| format!("src/file{:02}.rs", i)).collect();
+        let rendered = render_tree(&many, MAX_TREE_ENTRIES, MAX_TREE_BYTES);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(lines[0], "src/ (25 files)");
+        assert_eq!(lines.len(), 1 + MAX_DIR_ENTRIES + 1);
+        assert_eq!(lines.last(), Some(&"  (+5 more files)"));
+    }
+
+    #[test]
+    fn the_limits_end_it_with_what_is_left() {
+        let files = paths(&["a.rs", "b.rs", "c.rs", "d.rs"]);
+        assert_eq!(
+            render_tree(&files, 2, MAX_TREE_BYTES),
+            "a.rs\nb.rs\n(+2 more files)"
+        );
+        let rendered = render_tree(&files, MAX_TREE_ENTRIES, 40);
+        assert!(rendered.len() <= 40, "{}", rendered);
+        assert!(rendered.ends_with("more files)"), "{}", rendered);
+        // the same paths always render the same
+        assert_eq!(render_tree(&files, 2, 40), render_tree(&files, 2, 40));
+    }
+}
@@ -0,0 +1,443 @@
+// src/validate.rs
+// conventional-commit rules shared by generation and linting
+
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+pub const DEFAULT_TYPES: &[&str] = &[
+    "feat", "fix", "docs", "style", "refactor", "perf", "test", "build", "ci", "chore", "revert",
+];
+
+// the allowed type list from the flag or config (in that order), defaulting to the standard set
+pub fn resolve_types(configured: Option<Vec<String>>) -> Result<Vec<String>> {
+    let Some(types) = configured else {
+        return Ok(DEFAULT_TYPES.iter().map(|t| t.to_string()).collect());
+    };
+
+    let mut resolved: Vec<String> = Vec::new();
+    for kind in types {
+        let kind = kind.trim().to_string();
+        if kind.is_empty() {
+            continue;
+        }
+        let valid = kind
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if !valid {
+            return Err(anyhow!(
+                "invalid commit type '{}': use lowercase letters, digits and '-'",
+                kind
+            ));
+        }
+        if !resolved.contains(&kind) {
+            resolved.push(kind);
+        }
+    }
+
+    if resolved.is_empty() {
+        return Err(anyhow!(
+            "the list of allowed commit types is empty; set at least one type or remove the setting"
+        ));
+    }
+    Ok(resolved)
+}
+
+pub fn is_default_types(types: &[String]) -> bool {
+    types.len() == DEFAULT_TYPES.len() && types.iter().zip(DEFAULT_TYPES).all(|(a, b)| a == b)
+}
+
+/// The conventional-commit rules a generated message has to follow.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Rules {
+    /// The allowed types; by default the standard ones, from `feat` to `revert`.
+    pub types: Vec<String>,
+    /// The longest subject line, in characters.
+    pub max_title_length: usize,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            types: DEFAULT_TYPES.iter().map(|t| t.to_string()).collect(),
+            max_title_length: crate::format::DEFAULT_MAX_TITLE_LENGTH,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    // 1-based line within the message
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header<'a> {
+    pub kind: &'a str,
+    pub scope: Option<&'a str>,
+    pub breaking: bool,
+    pub subject: &'a str,
+}
+
+// parses `type(scope)!: subject`; none when the line doesn't have that shape at all
+pub fn parse_header(line: &str) -> Option<Header<'_>> {
+    let kind_len = line
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '-')
+        .unwrap_or(line.len());
+    if kind_len == 0 {
+        return None;
+    }
+    let (kind, mut rest) = line.split_at(kind_len);
+
+    let mut scope = None;
+    if let Some(after_paren) = rest.strip_prefix('(') {
+        let close = after_paren.find(')')?;
+        let inner = &after_paren[..close];
+        if inner.trim().is_empty() || inner.contains('(') {
+            return None;
+        }
+        scope = Some(inner);
+        rest = &after_paren[close + 1..];
+    }
+
+    let breaking = rest.starts_with('!');
+    if breaking {
+        rest = &rest[1..];
+    }
+
+    let subject = rest.strip_prefix(':')?;
+    if !subject.is_empty() && !subject.starts_with(' ') {
+        return None;
+    }
+
+    Some(Header {
+        kind,
+        scope,
+        breaking,
+        subject: subject.trim(),
+    })
+}
+
+// clap value parser for --scope: what fits between the parentheses of `type(scope):`
+pub fn parse_scope(value: &str) -> Result<String, String> {
+    let scope = value.trim();
+    if scope.is_empty() || scope.contains(['(', ')', '\n', '\r']) {
+        return Err(format!(
+            "'{}' can't be a scope: it must not be empty or hold parentheses",
+            value
+        ));
+    }
+    Ok(scope.to_string())
+}
+
+pub fn validate(message: &str, rules: &Rules) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    let lines: Vec<&str> = message.lines().collect();
+
+    let Some(first) = lines.first().filter(|l| !l.trim().is_empty()) else {
+        violations.push(Violation {
+            line: 1,
+            message: "message is empty or starts with a blank line".to_string(),
+        });
+        return violations;
+    };
+
+    match parse_header(first) {
+        None => violations.push(Violation {
+            line: 1,
+            message: format!(
+                "subject must have the form `type(scope): subject`, got '{}'",
+                first
+            ),
+        }),
+        Some(header) => {
+            if !rules.types.iter().any(|t| t == header.kind) {
+                violations.push(Violation {
+                    line: 1,
+                    message: format!(
+                        "unknown type '{}' (allowed: {})",
+                        header.kind,
+                        rules.types.join(", ")
+                    ),
+                });
+            }
+            if header.subject.is_empty() {
+                violations.push(Violation {
+                    line: 1,
+                    message: "subject is empty".to_string(),
+                });
+            } else if header.subject.ends_with('.') {
+                violations.push(Violation {
+                    line: 1,
+                    message: "subject must not end with a period".to_string(),
+                });
+            }
+        }
+    }
+
+    let subject_len = crate::format::char_len(first);
+    if rules.max_title_length > 0 && subject_len > rules.max_title_length {
+        violations.push(Violation {
+            line: 1,
+            message: format!(
+                "subject is {} characters (max {})",
+                subject_len, rules.max_title_length
+            ),
+        });
+    }
+
+    if lines.len() > 1 && !lines[1].trim().is_empty() {
+        violations.push(Violation {
+            line: 2,
+            message: "subject must be followed by a blank line before the body".to_string(),
+        });
+    }
+
+    if let Some(start) = footer_start(&lines) {
+        for (idx, line) in lines.iter().enumerate().skip(start) {
+            // blank lines and indented continuation lines belong to the previous footer
+            if line.trim().is_empty() || line.starts_with([' ', '\t']) {
+                continue;
+            }
+            if !is_footer_line(line) {
+                violations.push(Violation {
+                    line: idx + 1,
+                    message: format!(
+                        "footer line must be `Token: value` or `Token #value` (use '-' instead of spaces in tokens), got '{}'",
+                        line
+                    ),
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+// the last paragraph of the body, when its first line looks like a footer
+fn footer_start(lines: &[&str]) -> Option<usize> {
+    let end = lines.iter().rposition(|l| !l.trim().is_empty())?;
+    let start = lines[..=end].iter().rposition(|l| l.trim().is_empty())? + 1;
+    if start < 2 {
+        return None;
+    }
+    is_footer_line(lines[start]).then_some(start)
+}
+
+pub fn is_footer_line(line: &str) -> bool {
+    if crate::format::is_trailer_line(line) {
+        return true;
+    }
+    match line.split_once(" #") {
+        Some((token, value)) => {
+            !value.trim().is_empty()
+                && token
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_alphabetic())
+                && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        }
+        None => false,
+    }
+}
+
+// the follow-up instruction sent when a generated message is rejected
+pub fn corrective_instruction(message: &str, violations: &[Violation]) -> String {
+    let problems: Vec<String> = violations.iter().map(|v| format!("- {}", v)).collect();
+    format!(
+        "Your previous answer was rejected because it is not a valid conventional commit message:\n{}\n\nPrevious answer:\n```\n{}\n```\n\nReturn only a corrected commit message that fixes these problems.",
+        problems.join("\n"),
+        message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header<'a>(
+        kind: &'a str,
+        scope: Option<&'a str>,
+        breaking: bool,
+        subject: &'a str,
+    ) -> Option<Header<'a>> {
+        Some(Header {
+            kind,
+            scope,
+            breaking,
+            subject,
+        })
+    }
+
+    #[test]
+    fn headers() {
+        let cases = [
+            ("feat: add it", header("feat", None, false, "add it")),
+            (
+                "fix(cli): read the flag",
+                header("fix", Some("cli"), false, "read the flag"),
+            ),
+            ("feat!: drop v1", header("feat", None, true, "drop v1")),
+            (
+                "feat(api)!: drop v1",
+                header("feat", Some("api"), true, "drop v1"),
+            ),
+            (
+                "feat(my-scope)!:  spaced ",
+                header("feat", Some("my-scope"), true, "spaced"),
+            ),
+            (
+                "build-system: bump",
+                header("build-system", None, false, "bump"),
+            ),
+            ("feat:", header("feat", None, false, "")),
+            ("feat: ", header("feat", None, false, "")),
+            (
+                "Feat: capitalised",
+                header("Feat", None, false, "capitalised"),
+            ),
+            ("feat:no space", None),
+            ("feat(cli):no space", None),
+            ("feat add it", None),
+            ("feat(): empty scope", None),
+            ("feat( ): blank scope", None),
+            ("feat(a(b)): nested scope", None),
+            ("feat(cli: unclosed", None),
+            ("feat !: space before the bang", None),
+            (": no type", None),
+            ("", None),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(parse_header(line), expected, "{:?}", line);
+        }
+    }
+
+    fn problems(message: &str, rules: &Rules) -> Vec<String> {
+        validate(message, rules)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn valid_messages() {
+        let rules = Rules::default();
+        for message in [
+            "feat: add it",
+            "fix(cli)!: read the flag",
+            "chore: tidy\n\nA body.\n\nRefs: #12\nSigned-off-by: A <a@example.com>",
+            "feat!: drop v1\n\nBREAKING CHANGE: v1 is gone\n  and so is its config",
+            "fix: close the file\n\nCloses #40",
+        ] {
+            assert_eq!(
+                problems(message, &rules),
+                Vec::<String>::new(),
+                "{:?}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_messages() {
+        let rules = Rules::default();
+        let cases: &[(&str, &[&str])] = &[
+            ("", &["line 1: message is empty or starts with a blank line"]),
+            ("\nfeat: late", &["line 1: message is empty or starts with a blank line"]),
+            (
+                "feat:no space",
+                &["line 1: subject must have the form `type(scope): subject`, got 'feat:no space'"],
+            ),
+            ("feat: ", &["line 1: subject is empty"]),
+            ("fix(cli):", &["line 1: subject is empty"]),
+            ("fix: end with a period.", &["line 1: subject must not end with a period"]),
+            (
+                "feature: add it",
+                &["line 1: unknown type 'feature' (allowed: feat, fix, docs, style, refactor, perf, test, build, ci, chore, revert)"],
+            ),
+            (
+                "feat: add it\nno blank line",
+                &["line 2: subject must be followed by a blank line before the body"],
+            ),
+            (
+                "feat: add it\n\nA body.\n\nRefs: #12\nnot a footer",
+                &["line 6: footer line must be `Token: value` or `Token #value` (use '-' instead of spaces in tokens), got 'not a footer'"],
+            ),
+        ];
+        for (message, expected) in cases {
+            assert_eq!(problems(message, &rules), *expected, "{:?}", message);
+        }
+
+        let long = format!("feat: {}", "x".repeat(70));
+        assert_eq!(
+            problems(&long, &rules),
+            ["line 1: subject is 76 characters (max 72)"]
+        );
+    }
+
+    #[test]
+    fn a_custom_type_list() {
+        let rules = Rules {
+            types: resolve_types(Some(vec!["feature".into(), "bugfix".into()])).unwrap(),
+            ..Rules::default()
+        };
+        assert!(validate("feature: add it", &rules).is_empty());
+        assert_eq!(
+            problems("feat: add it", &rules),
+            ["line 1: unknown type 'feat' (allowed: feature, bugfix)"]
+        );
+    }
+
+    #[test]
+    fn scopes_fit_between_the_parentheses() {
+        assert_eq!(parse_scope(" cli ").unwrap(), "cli");
+        assert_eq!(parse_scope("my-scope/sub").unwrap(), "my-scope/sub");
+        assert!(parse_scope(" ").is_err());
+        assert!(parse_scope("a(b)").is_err());
+        assert!(parse_scope("cli\nfix").is_err());
+    }
+
+    #[test]
+    fn resolving_the_type_list() {
+        assert!(is_default_types(&resolve_types(None).unwrap()));
+        assert_eq!(
+            resolve_types(Some(vec![
+                " feat ".into(),
+                "".into(),
+                "feat".into(),
+                "wip-2".into()
+            ]))
+            .unwrap(),
+            ["feat", "wip-2"]
+        );
+        assert!(!is_default_types(
+            &resolve_types(Some(vec!["feat".into()])).unwrap()
+        ));
+
+        let empty = resolve_types(Some(vec![])).unwrap_err();
+        assert!(empty.to_string().contains("is empty"), "{}", empty);
+        assert!(resolve_types(Some(vec![" ".into()])).is_err());
+        let invalid = resolve_types(Some(vec!["Feat".into()])).unwrap_err();
+        assert_eq!(
+            invalid.to_string(),
+            "invalid commit type 'Feat': use lowercase letters, digits and '-'"
+        );
+    }
+
+    #[test]
+    fn the_corrective_instruction_quotes_the_answer_and_its_problems() {
+        let message = "feature: add it.";
+        let instruction = corrective_instruction(message, &validate(message, &Rules::default()));
+        assert!(instruction.contains("- line 1: unknown type 'feature'"));
+        assert!(instruction.contains("- line 1: subject must not end with a period"));
+        assert!(instruction.contains("```\nfeature: add it.\n```"));
+        assert!(instruction.ends_with("fixes these problems."));
+    }
+}
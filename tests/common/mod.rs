@@ -0,0 +1,108 @@
+// tests/common/mod.rs
+// throwaway repositories for the integration tests: a fresh `git init` in a temporary directory
+// that goes away with the value, and the few steps a test needs to put changes in it
+
+// every test crate compiles this module, and none of them uses all of it
+#![allow(dead_code)]
+
+use git2::{IndexAddOption, Oid, Repository, Signature};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+pub struct TestRepo {
+    // kept for its Drop, which removes the directory
+    dir: TempDir,
+    pub repo: Repository,
+}
+
+impl TestRepo {
+    // an empty repository: no commits, HEAD unborn
+    pub fn new() -> Self {
+        let dir = tempfile::tempdir().expect("failed to create a temporary directory");
+        let repo = Repository::init(dir.path()).expect("failed to init a repository");
+        TestRepo { dir, repo }
+    }
+
+    // a repository whose HEAD commits `files`, with nothing staged or changed after it
+    pub fn with_commit(files: &[(&str, &str)]) -> Self {
+        let test = TestRepo::new();
+        for (path, contents) in files {
+            test.write(path, contents);
+        }
+        test.stage_all();
+        test.commit("initial commit");
+        test
+    }
+
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    // writes a file in the working tree, creating its directories
+    pub fn write(&self, path: &str, contents: impl AsRef<[u8]>) {
+        let path = self.path().join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("failed to create a directory");
+        }
+        fs::write(path, contents).expect("failed to write a file");
+    }
+
+    pub fn remove(&self, path: &str) {
+        fs::remove_file(self.path().join(path)).expect("failed to remove a file");
+    }
+
+    pub fn rename(&self, from: &str, to: &str) {
+        fs::rename(self.path().join(from), self.path().join(to)).expect("failed to rename a file");
+    }
+
+    // `git add <path>`, or `git rm --cached` for a file that's gone from the working tree
+    pub fn stage(&self, path: &str) {
+        let mut index = self.repo.index().expect("failed to read the index");
+        match self.path().join(path).exists() {
+            true => index.add_path(Path::new(path)),
+            false => index.remove_path(Path::new(path)),
+        }
+        .expect("failed to stage a file");
+        index.write().expect("failed to write the index");
+    }
+
+    // `git add -A`
+    pub fn stage_all(&self) {
+        let mut index = self.repo.index().expect("failed to read the index");
+        index
+            .add_all(["*"], IndexAddOption::DEFAULT, None)
+            .expect("failed to stage the working tree");
+        index
+            .update_all(["*"], None)
+            .expect("failed to stage removed files");
+        index.write().expect("failed to write the index");
+    }
+
+    // commits the index on top of HEAD, or as the first commit
+    pub fn commit(&self, message: &str) -> Oid {
+        let signature = Signature::now("Test", "test@example.com").expect("bad signature");
+        let mut index = self.repo.index().expect("failed to read the index");
+        let tree_id = index.write_tree().expect("failed to write a tree");
+        let tree = self
+            .repo
+            .find_tree(tree_id)
+            .expect("failed to find the tree");
+        let parent = self
+            .repo
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        self.repo
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                message,
+                &tree,
+                &parents,
+            )
+            .expect("failed to commit")
+    }
+}
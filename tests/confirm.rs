@@ -0,0 +1,177 @@
+// tests/confirm.rs
+// --confirm and privacy.confirm_send against a local wiremock server: without a terminal to ask
+// on nothing is sent, and on one only a y sends. util-linux `setsid` and `script` give the run no
+// terminal or one of its own; a test is skipped where they aren't installed
+#![cfg(all(unix, feature = "gemini"))]
+
+mod common;
+
+use common::TestRepo;
+use git_changes_rs::provider::GEMINI_MODEL;
+use serde_json::json;
+use std::process::{Output, Stdio};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const MESSAGE: &str = "feat(notes): add the project notes";
+
+async fn server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/models/{}:generateContent", GEMINI_MODEL)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [{"content": {"parts": [{"text": MESSAGE}], "role": "model"}}]
+        })))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn repo() -> TestRepo {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    test.write("notes.txt", "the project notes\n");
+    test.stage("notes.txt");
+    test
+}
+
+fn installed(tool: &str) -> bool {
+    let found = std::process::Command::new(tool)
+        .arg("--version")
+        .output()
+        .is_ok();
+    if !found {
+        eprintln!("skipped: util-linux {} isn't installed", tool);
+    }
+    found
+}
+
+// `program` with the binary's command line for generate in `test`, isolated, against `server`
+fn command(program: &str, server: &MockServer, home: &std::path::Path) -> Command {
+    let mut command = Command::new(program);
+    command
+        .env_clear()
+        .env("HOME", home)
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GEMINI_API_KEY", "test-key")
+        .env("GEMINI_BASE_URL", server.uri())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    command
+}
+
+// generate in a session of its own, so there is no terminal to open whatever runs the tests
+async fn without_terminal(test: &TestRepo, server: &MockServer, args: &[&str]) -> Output {
+    let home = tempfile::tempdir().unwrap();
+    command("setsid", server, home.path())
+        .args(["-w", env!("CARGO_BIN_EXE_git-changes-rs")])
+        .arg(test.path())
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .expect("failed to run setsid")
+}
+
+// generate on a terminal of its own with `typed` typed into it: the status and what it showed
+async fn on_terminal(
+    test: &TestRepo,
+    server: &MockServer,
+    args: &[&str],
+    typed: &str,
+) -> (Option<i32>, String) {
+    let quote = |arg: &str| format!("'{}'", arg.replace('\'', "'\\''"));
+    let mut line = vec![
+        quote(env!("CARGO_BIN_EXE_git-changes-rs")),
+        quote(test.path().to_str().unwrap()),
+    ];
+    line.extend(args.iter().map(|arg| quote(arg)));
+
+    let home = tempfile::tempdir().unwrap();
+    let mut child = command("script", server, home.path())
+        .args(["-qec", &line.join(" "), "/dev/null"])
+        .spawn()
+        .expect("failed to run script");
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(typed.as_bytes()).await.unwrap();
+    drop(stdin);
+    let output = child.wait_with_output().await.unwrap();
+    let shown = String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n");
+    (output.status.code(), shown)
+}
+
+async fn requests(server: &MockServer) -> usize {
+    server.received_requests().await.unwrap_or_default().len()
+}
+
+#[tokio::test]
+async fn confirm_without_a_terminal_sends_nothing() {
+    if !installed("setsid") {
+        return;
+    }
+    let test = repo();
+    let server = server().await;
+
+    let output = without_terminal(&test, &server, &["--confirm"]).await;
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "not sent: confirmation before sending is on and there is no terminal to ask on \
+         (pass --yes to send anyway)"
+    ));
+    assert_eq!(requests(&server).await, 0);
+
+    // --yes is the answer
+    let output = without_terminal(&test, &server, &["--confirm", "--yes"]).await;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), MESSAGE);
+    assert_eq!(requests(&server).await, 1);
+}
+
+#[tokio::test]
+async fn confirm_send_in_the_config_is_the_same_as_confirm() {
+    if !installed("setsid") {
+        return;
+    }
+    let test = repo();
+    test.write(".git-changes.toml", "[privacy]\nconfirm_send = true\n");
+    let server = server().await;
+
+    let output = without_terminal(&test, &server, &[]).await;
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no terminal to ask on"));
+    assert_eq!(requests(&server).await, 0);
+}
+
+#[tokio::test]
+async fn the_question_shows_what_is_sent_and_only_yes_sends_it() {
+    if !installed("script") {
+        return;
+    }
+    let test = repo();
+    let server = server().await;
+
+    for typed in ["n\n", "\n", "\x04"] {
+        let (code, shown) = on_terminal(&test, &server, &["--confirm"], typed).await;
+        assert_eq!(code, Some(1), "{:?}: {}", typed, shown);
+        assert!(
+            shown.contains("not sent: the request was not confirmed"),
+            "{}",
+            shown
+        );
+    }
+    assert_eq!(requests(&server).await, 0);
+
+    // y sends, and a accepts the suggestion in the review that follows
+    let (code, shown) = on_terminal(&test, &server, &["--confirm"], "y\na\n").await;
+    assert_eq!(code, Some(0), "{}", shown);
+    let about = format!("about to send to gemini ({}): 1 files, ", GEMINI_MODEL);
+    assert!(shown.contains(&about), "{}", shown);
+    assert!(shown.contains("\n    notes.txt\n"), "{}", shown);
+    assert!(shown.contains("send it? [y/N]: "), "{}", shown);
+    assert!(shown.contains(MESSAGE), "{}", shown);
+    assert_eq!(requests(&server).await, 1);
+}
@@ -0,0 +1,285 @@
+// tests/diff.rs
+// which changes collect_changes reads: unstaged before staged, untracked files, excludes, an
+// unborn HEAD, binary files, renames, and the churn lines --ignore-lines leaves out
+
+mod common;
+
+use common::TestRepo;
+use git_changes_rs::diff::{collect_changes, ChangeSet, CollectOptions, DiffMode, Untracked};
+use git_changes_rs::GitChangesError;
+
+fn collect(test: &TestRepo, mode: DiffMode) -> ChangeSet {
+    collect_changes(&test.repo, &CollectOptions::new(mode)).expect("failed to collect changes")
+}
+
+fn collect_excluding(test: &TestRepo, patterns: &[&str]) -> ChangeSet {
+    let options = CollectOptions::new(DiffMode::Auto).exclude(patterns.iter().copied());
+    collect_changes(&test.repo, &options).expect("failed to collect changes")
+}
+
+#[test]
+fn unstaged_changes_only() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+    test.write("notes.txt", "first\nsecond\n");
+
+    let changes = collect(&test, DiffMode::Auto);
+    assert_eq!(changes.files(), ["notes.txt"]);
+    assert!(!changes.is_staged());
+    assert!(changes.diff().contains("second"));
+}
+
+#[test]
+fn staged_changes_only() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+    test.write("notes.txt", "first\nsecond\n");
+    test.stage("notes.txt");
+
+    let changes = collect(&test, DiffMode::Auto);
+    assert_eq!(changes.files(), ["notes.txt"]);
+    assert!(changes.is_staged());
+    assert!(changes.diff().contains("second"));
+}
+
+#[test]
+fn unstaged_changes_come_before_staged_ones() {
+    let test = TestRepo::with_commit(&[("staged.txt", "a\n"), ("unstaged.txt", "b\n")]);
+    test.write("staged.txt", "a\nstaged line\n");
+    test.stage("staged.txt");
+    test.write("unstaged.txt", "b\nunstaged line\n");
+
+    let auto = collect(&test, DiffMode::Auto);
+    assert_eq!(auto.files(), ["unstaged.txt"]);
+    assert!(!auto.is_staged());
+    assert!(!auto.diff().contains("a/staged.txt"));
+
+    let staged = collect(&test, DiffMode::Staged);
+    assert_eq!(staged.files(), ["staged.txt"]);
+    assert!(staged.is_staged());
+    assert!(!staged.diff().contains("unstaged line"));
+}
+
+fn collect_untracked(test: &TestRepo, untracked: Untracked) -> ChangeSet {
+    let options = CollectOptions::new(DiffMode::Auto).untracked(untracked);
+    collect_changes(&test.repo, &options).expect("failed to collect changes")
+}
+
+#[test]
+fn untracked_files_are_read_only_when_asked_for() {
+    let test = TestRepo::with_commit(&[(".gitignore", "*.log\n")]);
+    test.write("new/deep/notes.txt", "the project notes\n");
+    test.write("debug.log", "noise\n");
+
+    assert!(collect(&test, DiffMode::Auto).is_empty());
+
+    let changes = collect_untracked(&test, Untracked::NotIgnored);
+    assert_eq!(changes.files(), ["new/deep/notes.txt"]);
+    assert!(!changes.is_staged());
+    assert!(changes.diff().contains("+the project notes"));
+    assert!(!changes.diff().contains("noise"));
+
+    let changes = collect_untracked(&test, Untracked::All);
+    assert_eq!(changes.files(), ["debug.log", "new/deep/notes.txt"]);
+    assert!(changes.diff().contains("+noise"));
+}
+
+#[test]
+fn untracked_files_come_after_the_unstaged_changes_and_respect_excludes() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+    test.write("notes.txt", "first\nsecond\n");
+    test.write("Cargo.lock", "# generated\n");
+    test.write("todo.txt", "write the notes\n");
+
+    let options = CollectOptions::new(DiffMode::Auto)
+        .untracked(Untracked::NotIgnored)
+        .exclude(["Cargo.lock"]);
+    let changes = collect_changes(&test.repo, &options).unwrap();
+    assert_eq!(changes.files(), ["notes.txt", "todo.txt"]);
+    assert_eq!(changes.excluded(), ["Cargo.lock"]);
+}
+
+#[test]
+fn untracked_files_are_not_part_of_the_staged_changes() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+    test.write("notes.txt", "first\nsecond\n");
+    test.stage("notes.txt");
+    test.write("todo.txt", "write the notes\n");
+
+    let options = CollectOptions::new(DiffMode::Staged).untracked(Untracked::NotIgnored);
+    let changes = collect_changes(&test.repo, &options).unwrap();
+    assert_eq!(changes.files(), ["notes.txt"]);
+    assert!(!changes.diff().contains("todo"));
+}
+
+#[test]
+fn amend_reads_the_index_against_the_parent_of_head() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+    test.write("notes.txt", "first\nsecond\n");
+    test.stage("notes.txt");
+    test.commit("add a line");
+
+    assert!(collect(&test, DiffMode::Staged).is_empty());
+    let amend = collect(&test, DiffMode::Amend);
+    assert_eq!(amend.files(), ["notes.txt"]);
+    assert!(amend.diff().contains("second"));
+}
+
+#[test]
+fn a_clean_working_tree_has_no_changes() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+
+    let changes = collect(&test, DiffMode::Auto);
+    assert!(changes.is_empty());
+    assert!(changes.excluded().is_empty());
+    assert!(changes.diff().trim().is_empty());
+}
+
+#[test]
+fn excluded_files_are_left_out_and_listed() {
+    let test = TestRepo::with_commit(&[("Cargo.lock", "# lock\n"), ("src/lib.rs", "// lib\n")]);
+    test.write("Cargo.lock", "# lock\nversion = 4\n");
+    test.write("src/lib.rs", "// lib\npub fn f() {}\n");
+
+    let changes = collect_excluding(&test, &["Cargo.lock"]);
+    assert_eq!(changes.files(), ["src/lib.rs"]);
+    assert_eq!(changes.excluded(), ["Cargo.lock"]);
+    assert!(!changes.diff().contains("version = 4"));
+}
+
+#[test]
+fn excluding_every_changed_file_leaves_nothing() {
+    let test = TestRepo::with_commit(&[("build.log", "one\n"), ("logs/run.log", "two\n")]);
+    test.write("build.log", "one\nmore\n");
+    test.write("logs/run.log", "two\nmore\n");
+
+    let changes = collect_excluding(&test, &["*.log", "logs/**"]);
+    assert!(changes.is_empty());
+    assert!(changes.diff().trim().is_empty());
+    assert_eq!(changes.excluded(), ["build.log", "logs/run.log"]);
+}
+
+#[test]
+fn an_empty_repository_has_no_changes() {
+    let test = TestRepo::new();
+
+    let changes = collect(&test, DiffMode::Auto);
+    assert!(changes.is_empty());
+    assert!(changes.excluded().is_empty());
+}
+
+#[test]
+fn staged_files_before_the_first_commit() {
+    let test = TestRepo::new();
+    test.write("README.md", "# project\n");
+    test.stage("README.md");
+
+    let changes = collect(&test, DiffMode::Auto);
+    assert_eq!(changes.files(), ["README.md"]);
+    assert!(changes.is_staged());
+    assert!(changes.diff().contains("# project"));
+}
+
+#[test]
+fn binary_files_are_listed_without_their_contents() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+    test.write("logo.png", b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01");
+    test.stage("logo.png");
+
+    let changes = collect(&test, DiffMode::Auto);
+    assert_eq!(changes.files(), ["logo.png"]);
+    assert!(changes.diff().contains("Binary files"));
+    assert!(!changes.diff().contains("IHDR"));
+}
+
+#[test]
+fn a_staged_rename_shows_both_paths() {
+    let test = TestRepo::with_commit(&[("old.txt", "contents\n")]);
+    test.rename("old.txt", "new.txt");
+    test.stage("old.txt");
+    test.stage("new.txt");
+
+    let changes = collect(&test, DiffMode::Auto);
+    let mut files = changes.files().to_vec();
+    files.sort();
+    assert_eq!(files, ["new.txt", "old.txt"]);
+}
+
+#[test]
+fn a_rename_out_of_an_excluded_directory_is_excluded() {
+    let test = TestRepo::with_commit(&[("vendor/lib.c", "int x;\n")]);
+    test.rename("vendor/lib.c", "lib.c");
+    test.stage("vendor/lib.c");
+    test.stage("lib.c");
+
+    let changes = collect_excluding(&test, &["vendor/**"]);
+    assert_eq!(changes.files(), ["lib.c"]);
+    assert_eq!(changes.excluded(), ["vendor/lib.c"]);
+}
+
+fn collect_ignoring(test: &TestRepo, patterns: &[&str]) -> ChangeSet {
+    let options = CollectOptions::new(DiffMode::Auto).ignore_lines(patterns.iter().copied());
+    collect_changes(&test.repo, &options).expect("failed to collect changes")
+}
+
+const MANIFEST: &str = "[package]\nname = \"app\"\nversion = \"1.0.0\"\nedition = \"2018\"\n\n[dependencies]\nserde = \"1\"\n";
+
+#[test]
+fn a_hunk_of_nothing_but_churn_is_dropped_and_noted() {
+    let test = TestRepo::with_commit(&[
+        (
+            "src/build_info.rs",
+            "// generated at: 2024-01-01T10:00:00Z\npub const BUILD: u32 = 7;\n",
+        ),
+        ("notes.txt", "first\n"),
+    ]);
+    test.write(
+        "src/build_info.rs",
+        "// generated at: 2024-02-02T11:30:00Z\npub const BUILD: u32 = 7;\n",
+    );
+    test.write("notes.txt", "first\nsecond\n");
+
+    let changes = collect_ignoring(&test, &["^// generated at: "]);
+    assert_eq!(changes.files(), ["notes.txt", "src/build_info.rs"]);
+    let diff = changes.diff();
+    let build_info = &diff[diff.find("diff --git a/src/build_info.rs").unwrap()..];
+    assert!(!build_info.contains("@@"), "{}", build_info);
+    assert!(!build_info.contains("generated at"), "{}", build_info);
+    assert!(build_info.ends_with("+++ b/src/build_info.rs\n# 2 churn lines ignored\n"));
+    assert!(diff.contains("+second\n"));
+}
+
+#[test]
+fn a_hunk_with_churn_and_real_changes_only_shrinks() {
+    let test = TestRepo::with_commit(&[("app.toml", MANIFEST)]);
+    test.write(
+        "app.toml",
+        MANIFEST.replace("1.0.0", "1.1.0").replace("2018", "2021"),
+    );
+
+    let changes = collect_ignoring(&test, &["^version = \""]);
+    let diff = changes.diff();
+    let hunk = &diff[diff.find("@@").unwrap()..];
+    assert_eq!(
+        hunk,
+        "@@ -1,6 +1,6 @@\n [package]\n name = \"app\"\n-edition = \"2018\"\n+edition = \"2021\"\n \n [dependencies]\n serde = \"1\"\n# 2 churn lines ignored\n"
+    );
+}
+
+#[test]
+fn a_match_without_a_partner_is_a_real_change() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+    test.write("app.toml", MANIFEST);
+    test.stage("app.toml");
+
+    let changes = collect_ignoring(&test, &["^version = \""]);
+    assert!(changes.diff().contains("+version = \"1.0.0\"\n"));
+    assert!(!changes.diff().contains("churn"));
+}
+
+#[test]
+fn an_invalid_line_pattern_is_a_config_error() {
+    let test = TestRepo::with_commit(&[("notes.txt", "first\n")]);
+    let options = CollectOptions::new(DiffMode::Auto).ignore_lines(["version = (unclosed"]);
+    let error = collect_changes(&test.repo, &options).expect_err("the pattern doesn't compile");
+    assert!(matches!(error, GitChangesError::Config { .. }));
+    assert!(error.to_string().contains("version = (unclosed"));
+}
@@ -0,0 +1,2253 @@
+// tests/e2e.rs
+// the whole run against the mock provider: the binary reads a temporary repository, builds the
+// prompt, takes the scripted answer, formats it, and prints or commits it
+
+mod common;
+
+use common::TestRepo;
+use git_changes_rs::error::ProviderFailure;
+use git_changes_rs::message::{self, FormatOptions, Rules};
+use git_changes_rs::mock::MockProvider;
+use git_changes_rs::prompt::Prompt;
+use git_changes_rs::GitChangesError;
+use serde_json::Value;
+use std::ffi::OsStr;
+use std::fs;
+use std::process::{Command, Output};
+
+const ANSWER: &str = "  feat(notes): add a notes file with the first few lines of the project notes\n\n- start the notes with a line about what the project is for, which is long enough to be wrapped\n";
+
+// a repository with one commit and a staged change on top of it
+fn repo() -> TestRepo {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    let mut config = test.repo.config().unwrap();
+    config.set_str("user.name", "Test").unwrap();
+    config.set_str("user.email", "test@example.com").unwrap();
+    test.write("notes.txt", "the project notes\n");
+    test.stage("notes.txt");
+    test
+}
+
+// `command` with nothing from the environment running the tests (keys, config,
+// GIT_CHANGES_* settings) but `extra`, and `home` as HOME
+fn isolate(command: &mut Command, home: &std::path::Path, extra: &[(&str, &str)]) {
+    command
+        .env_clear()
+        .env("HOME", home)
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("GIT_CONFIG_NOSYSTEM", "1");
+    for (name, value) in extra {
+        command.env(name, value);
+    }
+}
+
+// the binary with `args`
+fn run_bare(args: &[&OsStr], extra: &[(&str, &str)]) -> Output {
+    let home = tempfile::tempdir().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_git-changes-rs"));
+    isolate(&mut command, home.path(), extra);
+    command
+        .args(args)
+        .output()
+        .expect("failed to run git-changes-rs")
+}
+
+// the binary with `args`, answered by a mock
+fn run_with(args: &[&OsStr], answers: &[&str], extra: &[(&str, &str)]) -> Output {
+    let answers = serde_json::to_string(answers).unwrap();
+    let mut all = vec![("GIT_CHANGES_MOCK_ANSWERS", answers.as_str())];
+    all.extend(extra);
+    run_bare(args, &all)
+}
+
+// generate in `test`
+fn run(test: &TestRepo, answers: &[&str], args: &[&str], extra: &[(&str, &str)]) -> Output {
+    let mut all = vec![test.path().as_os_str()];
+    all.extend(args.iter().map(OsStr::new));
+    run_with(&all, answers, extra)
+}
+
+// generate in `test` on a terminal of its own, with `typed` typed into it: util-linux `script`
+// runs the binary on a pty. The status and what the terminal showed, or none where `script`
+// isn't there
+#[cfg(unix)]
+fn run_on_terminal(
+    test: &TestRepo,
+    answers: &[&str],
+    args: &[&str],
+    extra: &[(&str, &str)],
+    typed: &str,
+) -> Option<(Option<i32>, String)> {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    if Command::new("script").arg("--version").output().is_err() {
+        eprintln!("skipped: util-linux script isn't installed");
+        return None;
+    }
+    let quote = |arg: &str| format!("'{}'", arg.replace('\'', "'\\''"));
+    let mut line = vec![
+        quote(env!("CARGO_BIN_EXE_git-changes-rs")),
+        quote(test.path().to_str().unwrap()),
+    ];
+    line.extend(args.iter().map(|arg| quote(arg)));
+
+    let home = tempfile::tempdir().unwrap();
+    let answers = serde_json::to_string(answers).unwrap();
+    let mut all = vec![("GIT_CHANGES_MOCK_ANSWERS", answers.as_str())];
+    all.extend(extra);
+    let mut command = Command::new("script");
+    isolate(&mut command, home.path(), &all);
+    let mut child = command
+        .args(["-qec", &line.join(" "), "/dev/null"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run script");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(typed.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    let shown = String::from_utf8_lossy(&output.stdout).replace("\r\n", "\n");
+    Some((output.status.code(), shown))
+}
+
+// another subcommand, given `--repo` for `test`
+fn run_subcommand(test: &TestRepo, name: &str, args: &[&str]) -> Output {
+    let mut all = vec![
+        OsStr::new(name),
+        OsStr::new("--repo"),
+        test.path().as_os_str(),
+    ];
+    all.extend(args.iter().map(OsStr::new));
+    run_with(&all, &[], &[])
+}
+
+// the message the library makes of `answer`, which the binary should end up with too
+async fn formatted(answer: &str) -> String {
+    let prompt = Prompt {
+        system: String::new(),
+        user: String::new(),
+        max_output_tokens: 512,
+        title_only: false,
+    };
+    message::generate(
+        &MockProvider::new([answer]),
+        &prompt,
+        &FormatOptions::default(),
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+fn head_message(test: &TestRepo) -> String {
+    let head = test.repo.head().unwrap().peel_to_commit().unwrap();
+    head.message().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn the_formatted_answer_is_printed() {
+    let test = repo();
+    let output = run(&test, &[ANSWER], &[], &[]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let expected = formatted(ANSWER).await;
+    assert_ne!(expected, ANSWER.trim(), "the answer should need formatting");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap().trim_end(),
+        expected
+    );
+}
+
+// the exact bytes a pipe gets: no heading, no `---` lines, one trailing newline
+#[test]
+fn stdout_that_isnt_a_terminal_gets_the_bare_message() {
+    let test = repo();
+    let output = run(&test, &[ANSWER], &[], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    insta::assert_snapshot!(format!("{:?}", String::from_utf8(output.stdout).unwrap()));
+
+    let output = run(&test, &[ANSWER], &["--null"], &[]);
+    assert!(output.stdout.ends_with(b"wrapped\0"), "{:?}", output);
+}
+
+// the --json document a script reads, with the one field that changes from run to run zeroed;
+// stdout holds that document and nothing else
+#[test]
+fn the_json_report_keeps_its_shape() {
+    let test = repo();
+    test.write("Cargo.lock", "# generated\n");
+    test.stage("Cargo.lock");
+    let output = run(&test, &[ANSWER], &["--json"], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let mut report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(report["elapsed_ms"].is_u64());
+    report["elapsed_ms"] = 0.into();
+    insta::assert_snapshot!(serde_json::to_string_pretty(&report).unwrap());
+
+    // the same request again is answered from the cache
+    let output = run(&test, &[], &["--json"], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let again: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(again["cached"], true);
+    assert_eq!(again["message"], report["message"]);
+}
+
+// the same document as YAML: the message as a block scalar, and the fields the JSON one has
+#[test]
+fn the_yaml_report_is_the_json_one_in_yaml() {
+    let test = repo();
+    let output = run(&test, &[ANSWER], &["--format", "yaml"], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let text = String::from_utf8(output.stdout).unwrap();
+    let elapsed = text
+        .lines()
+        .find(|line| line.starts_with("elapsed_ms: "))
+        .unwrap()
+        .to_string();
+    insta::assert_snapshot!(text.replace(&elapsed, "elapsed_ms: 0"));
+
+    let yaml: Value = serde_yaml::from_str(&text).unwrap();
+    let output = run(&test, &[], &["--json"], &[]);
+    let mut json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    json["elapsed_ms"] = yaml["elapsed_ms"].clone();
+    json["cached"] = false.into();
+    assert_eq!(yaml, json);
+}
+
+#[test]
+fn a_yaml_run_reports_errors_in_yaml() {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    let output = run(&test, &[ANSWER], &["--format", "yaml"], &[]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.starts_with("---\n"), "{}", text);
+    let report: Value = serde_yaml::from_str(&text).unwrap();
+    assert_eq!(report["error"]["kind"], "no_changes");
+    assert_eq!(report["code"], "no_changes");
+}
+
+#[tokio::test]
+async fn commit_creates_a_commit_with_the_formatted_answer() {
+    let test = repo();
+    let output = run(&test, &[ANSWER], &["--commit", "--yes"], &[]);
+    assert!(output.status.success(), "{:?}", output);
+
+    assert_eq!(
+        head_message(&test),
+        format!("{}\n", formatted(ANSWER).await)
+    );
+    let head = test.repo.head().unwrap().peel_to_commit().unwrap();
+    assert_eq!(head.parent_count(), 1);
+    assert!(head.tree().unwrap().get_name("notes.txt").is_some());
+}
+
+#[test]
+fn the_prompt_carries_the_staged_diff() {
+    let test = repo();
+    let prompts = test.path().join(".git").join("prompts.jsonl");
+    let output = run(
+        &test,
+        &["feat(notes): add a notes file"],
+        &[],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let recorded = fs::read_to_string(prompts).unwrap();
+    let lines: Vec<&str> = recorded.lines().collect();
+    assert_eq!(lines.len(), 1);
+    let prompt: Value = serde_json::from_str(lines[0]).unwrap();
+    let user = prompt["user"].as_str().unwrap();
+    assert!(user.contains("notes.txt"));
+    assert!(user.contains("the project notes"));
+    assert!(!user.contains("README.md"));
+    assert!(!prompt["system"].as_str().unwrap().is_empty());
+}
+
+// a new file next to new files each kind of ignore rule matches: .gitignore,
+// .git/info/exclude and core.excludesFile
+fn repo_with_ignored_files() -> TestRepo {
+    let test = repo();
+    let git_dir = test.path().join(".git");
+    test.write(".gitignore", "target/\n");
+    test.stage(".gitignore");
+    fs::write(git_dir.join("info").join("exclude"), "*.swp\n").unwrap();
+    let excludes_file = git_dir.join("global-ignore");
+    fs::write(&excludes_file, "node_modules/\n").unwrap();
+    let mut config = test.repo.config().unwrap();
+    config
+        .set_str("core.excludesFile", excludes_file.to_str().unwrap())
+        .unwrap();
+
+    test.write("src/kept.rs", "pub fn kept() {}\n");
+    test.write("target/debug/app.d", "build output\n");
+    test.write(".notes.txt.swp", "editor swap file\n");
+    test.write("node_modules/left-pad/index.js", "module.exports = 1;\n");
+    test
+}
+
+// the files of HEAD's tree, and the one prompt of a --commit run with `args`
+fn committed_and_prompted(test: &TestRepo, args: &[&str]) -> (Vec<String>, String) {
+    let prompts = test.path().join(".git").join("prompts.jsonl");
+    let output = run(
+        test,
+        &["feat: add the kept module"],
+        args,
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let tree = test.repo.head().unwrap().peel_to_tree().unwrap();
+    let mut files = Vec::new();
+    tree.walk(git2::TreeWalkMode::PreOrder, |dir, entry| {
+        if entry.kind() == Some(git2::ObjectType::Blob) {
+            files.push(format!("{}{}", dir, entry.name().unwrap()));
+        }
+        git2::TreeWalkResult::Ok
+    })
+    .unwrap();
+    let recorded = fs::read_to_string(prompts).unwrap();
+    let prompt: Value = serde_json::from_str(recorded.lines().next().unwrap()).unwrap();
+    (files, prompt["user"].as_str().unwrap().to_string())
+}
+
+#[test]
+fn ignored_untracked_files_are_neither_staged_nor_described() {
+    let test = repo_with_ignored_files();
+    let (files, user) = committed_and_prompted(
+        &test,
+        &["--commit", "--yes", "--add-all", "--include-untracked"],
+    );
+
+    assert!(files.contains(&"src/kept.rs".to_string()), "{:?}", files);
+    assert!(user.contains("src/kept.rs"));
+    for ignored in [
+        "target/debug/app.d",
+        ".notes.txt.swp",
+        "node_modules/left-pad/index.js",
+    ] {
+        assert!(
+            !files.iter().any(|file| file.starts_with(ignored)),
+            "{:?}",
+            files
+        );
+        assert!(!user.contains(ignored), "{} is in the prompt", ignored);
+    }
+}
+
+#[test]
+fn no_gitignore_stages_and_describes_ignored_files_too() {
+    let test = repo_with_ignored_files();
+    let (files, user) = committed_and_prompted(
+        &test,
+        &[
+            "--commit",
+            "--yes",
+            "--add-all",
+            "--include-untracked",
+            "--no-gitignore",
+        ],
+    );
+
+    for path in [
+        "src/kept.rs",
+        "target/debug/app.d",
+        ".notes.txt.swp",
+        "node_modules/left-pad/index.js",
+    ] {
+        assert!(files.contains(&path.to_string()), "{:?}", files);
+        assert!(user.contains(path), "{} is not in the prompt", path);
+    }
+}
+
+// the prompt of a run that prints the message, with `args`
+fn prompted(test: &TestRepo, args: &[&str]) -> String {
+    let prompts = test.path().join(".git").join("prompts.jsonl");
+    let output = run(
+        test,
+        &["feat: add the kept module"],
+        args,
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let recorded = fs::read_to_string(prompts).unwrap();
+    let prompt: Value = serde_json::from_str(recorded.lines().last().unwrap()).unwrap();
+    prompt["user"].as_str().unwrap().to_string()
+}
+
+#[test]
+fn include_untracked_describes_new_files_without_staging_them() {
+    let test = repo_with_ignored_files();
+    // something unstaged, so the run reads the working tree
+    test.write("README.md", "# project\n\nmore\n");
+
+    let user = prompted(&test, &["--include-untracked"]);
+    assert!(user.contains("src/kept.rs"));
+    assert!(user.contains("+pub fn kept() {}"));
+    assert!(!user.contains("target/debug/app.d"));
+    assert!(test
+        .repo
+        .index()
+        .unwrap()
+        .get_path("src/kept.rs".as_ref(), 0)
+        .is_none());
+
+    let user = prompted(&test, &["--include-untracked", "--no-gitignore"]);
+    assert!(user.contains("+build output"));
+    assert!(user.contains("node_modules/left-pad/index.js"));
+    assert!(!user.contains("prompts.jsonl"));
+
+    let user = prompted(&test, &[]);
+    assert!(!user.contains("src/kept.rs"));
+}
+
+// a repository whose branch and `feature` both changed config.txt since they split; returns
+// the commit on `feature`
+#[test]
+fn a_removed_pub_fn_is_named_in_the_prompt_as_breaking() {
+    let test = TestRepo::with_commit(&[
+        ("Cargo.toml", "[package]\nname = \"greeter\"\n"),
+        ("src/lib.rs", "pub fn greet() {}\npub fn part(x: u8) {}\n"),
+    ]);
+    test.write("src/lib.rs", "pub fn part(x: u16) {}\npub fn added() {}\n");
+    test.stage("src/lib.rs");
+
+    let prompt = prompted(&test, &["--no-cache"]);
+    assert!(
+        prompt.contains("- src/lib.rs: removed pub fn greet(), changed signature of pub fn part(x: u16) (was: pub fn part(x: u8)), added pub fn added()"),
+        "{}",
+        prompt
+    );
+    assert!(prompt.contains("`BREAKING CHANGE:` footer"), "{}", prompt);
+
+    let prompt = prompted(&test, &["--no-cache", "--no-rust-api"]);
+    assert!(!prompt.contains("Public API changes"), "{}", prompt);
+}
+
+#[test]
+fn signoff_and_co_authors_are_appended_to_the_commit() {
+    let test = repo();
+    let output = run(
+        &test,
+        &["feat(notes): add a notes file"],
+        &[
+            "--commit",
+            "--yes",
+            "--signoff",
+            "--co-author",
+            "Ada <ada@example.com>",
+            "--co-author",
+            "Bob <bob@example.com>",
+        ],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        head_message(&test),
+        "feat(notes): add a notes file\n\nSigned-off-by: Test <test@example.com>\nCo-authored-by: Ada <ada@example.com>\nCo-authored-by: Bob <bob@example.com>\n"
+    );
+
+    let output = run(
+        &test,
+        &["feat: never asked for"],
+        &["--co-author", "Ada"],
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+}
+
+#[test]
+fn attribution_comes_from_the_flag_or_the_config() {
+    let test = repo();
+    let output = run(
+        &test,
+        &["feat(notes): add a notes file"],
+        &["--attribution", "--no-cache"],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "feat(notes): add a notes file\n\nCommit-message-generated-by: git-changes-rs (gemini-2.0-flash)\n"
+    );
+
+    let output = run(
+        &test,
+        &["feat(notes): add a notes file"],
+        &["--no-cache"],
+        &[
+            ("GIT_CHANGES_ATTRIBUTION", "true"),
+            ("GIT_CHANGES_ATTRIBUTION_KEY", "Assisted-by"),
+            ("GIT_CHANGES_ATTRIBUTION_TEMPLATE", "{model}"),
+        ],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "feat(notes): add a notes file\n\nAssisted-by: gemini-2.0-flash\n"
+    );
+}
+
+#[test]
+fn an_amend_sends_the_old_message_and_keeps_its_trailers() {
+    let test = repo();
+    test.commit("wip\n\nRefs #12\nSigned-off-by: Ada <ada@example.com>");
+    test.write("notes.txt", "the project notes, longer\n");
+    test.stage("notes.txt");
+
+    let prompts = prompts_file(&test);
+    let output = run(
+        &test,
+        &["feat(notes): add a notes file"],
+        &["--amend", "--yes"],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let recorded = fs::read_to_string(prompts).unwrap();
+    let prompt: Value = serde_json::from_str(recorded.lines().last().unwrap()).unwrap();
+    let user = prompt["user"].as_str().unwrap();
+    assert!(
+        user.contains("Previous message (improve upon it")
+            && user.contains("wip\n\nRefs #12\nSigned-off-by: Ada <ada@example.com>\n```"),
+        "{}",
+        user
+    );
+    assert_eq!(
+        head_message(&test),
+        "feat(notes): add a notes file\n\nRefs #12\nSigned-off-by: Ada <ada@example.com>\n"
+    );
+}
+
+#[test]
+fn smart_context_names_the_function_each_hunk_is_in() {
+    let body: String = (0..12)
+        .map(|i| format!("    let x{} = {};\n", i, i))
+        .collect();
+    let source = format!("fn first() {{\n{}}}\n\nfn second() {{\n{}}}\n", body, body);
+    let test = TestRepo::with_commit(&[("src/main.rs", source.as_str())]);
+    test.write(
+        "src/main.rs",
+        source.replacen("let x11 = 11;", "let x11 = 110;", 2),
+    );
+    test.stage("src/main.rs");
+
+    let prompt = prompted(&test, &["--no-cache", "--smart-context"]);
+    assert_eq!(
+        prompt.matches(" // [git-changes context: in fn ").count(),
+        2,
+        "{}",
+        prompt
+    );
+    assert!(
+        prompt.contains("[git-changes context: in fn first]"),
+        "{}",
+        prompt
+    );
+    assert!(
+        prompt.contains("[git-changes context: in fn second]"),
+        "{}",
+        prompt
+    );
+
+    let prompt = prompted(&test, &["--no-cache"]);
+    assert!(!prompt.contains("git-changes context"), "{}", prompt);
+}
+
+#[test]
+fn tree_context_lists_the_tracked_files() {
+    let test = repo();
+    test.write("src/lib.rs", "pub fn greet() {}\n");
+    test.write("scratch.txt", "not tracked\n");
+
+    let prompt = prompted(&test, &["--no-cache", "--tree-context"]);
+    assert!(prompt.contains("README.md\nnotes.txt"), "{}", prompt);
+    assert!(!prompt.contains("src/ ("), "{}", prompt);
+    assert!(!prompt.contains("scratch.txt"), "{}", prompt);
+
+    test.stage("src/lib.rs");
+    let prompt = prompted(&test, &["--no-cache", "--tree-context"]);
+    assert!(
+        prompt.contains("src/ (1 file)\n  lib.rs\nREADME.md\nnotes.txt"),
+        "{}",
+        prompt
+    );
+}
+
+#[test]
+fn fixup_detect_names_the_commit_the_change_follows_up_on() {
+    let test = repo();
+    test.commit("feat(notes): add a notes file");
+    test.write("src/lib.rs", "pub fn greet() {}\n");
+    test.stage("src/lib.rs");
+    test.commit("feat: add greet");
+
+    // notes.txt again: one commit back, full overlap
+    test.write("notes.txt", "the project notes, fixed\n");
+    test.stage("notes.txt");
+    let prompts = prompts_file(&test);
+    let output = run(
+        &test,
+        &["feat: never asked for"],
+        &["--fixup-detect", "--commit", "--yes"],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        head_message(&test),
+        "fixup! feat(notes): add a notes file\n"
+    );
+    assert!(!prompts.exists());
+
+    // a file no recent commit touched gets a message of its own
+    test.write("other.txt", "unrelated\n");
+    test.stage("other.txt");
+    let output = run(
+        &test,
+        &["feat: add other"],
+        &["--fixup-detect", "--commit", "--yes"],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(head_message(&test), "feat: add other\n");
+
+    let output = run(
+        &test,
+        &["feat: never asked for"],
+        &["--fixup", "HEAD~1", "--fixup-detect"],
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+}
+
+// the files in HEAD's tree
+fn head_files(test: &TestRepo) -> Vec<String> {
+    let tree = test.repo.head().unwrap().peel_to_tree().unwrap();
+    tree.iter()
+        .map(|entry| entry.name().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn add_all_stages_tracked_changes_before_describing_and_committing_them() {
+    let test = repo();
+    test.commit("feat(notes): add a notes file");
+    test.write("README.md", "# project, renamed\n");
+    test.remove("notes.txt");
+    test.write("draft.txt", "not tracked\n");
+
+    let prompt = prompted(&test, &["--add-all", "--commit", "--yes"]);
+    assert!(
+        prompt.contains("diff --git a/README.md b/README.md"),
+        "{}",
+        prompt
+    );
+    assert!(prompt.contains("deleted file mode"), "{}", prompt);
+    assert!(!prompt.contains("draft.txt"), "{}", prompt);
+    assert_eq!(head_files(&test), ["README.md"]);
+    assert_eq!(head_message(&test), "feat: add the kept module\n");
+
+    // an excluded file is left out of the prompt but still committed
+    test.write("README.md", "# project, again\n");
+    test.write("draft.txt", "still not tracked\n");
+    let output = run(
+        &test,
+        &["docs: touch the readme"],
+        &[
+            "-a",
+            "--commit",
+            "--yes",
+            "--include-untracked",
+            "--exclude",
+            "draft.txt",
+        ],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(head_files(&test), ["README.md", "draft.txt"]);
+
+    let output = run(&test, &["feat: never asked for"], &["--add-all"], &[]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+}
+
+// the prompts a run recorded in `test`
+fn recorded_prompts(test: &TestRepo) -> Vec<String> {
+    fs::read_to_string(prompts_file(test))
+        .unwrap_or_default()
+        .lines()
+        .map(|line| {
+            let prompt: Value = serde_json::from_str(line).unwrap();
+            prompt["user"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn the_review_loop_asks_again_regenerates_and_accepts() {
+    let test = repo();
+    let prompts = prompts_file(&test);
+    let Some((code, shown)) = run_on_terminal(
+        &test,
+        &["feat: first", "feat: second"],
+        &["--commit"],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+        "x\nr\na\n",
+    ) else {
+        return;
+    };
+    assert_eq!(code, Some(0), "{}", shown);
+    assert!(
+        shown.contains("suggested commit message:\n---\nfeat: first\n---"),
+        "{}",
+        shown
+    );
+    assert!(shown.contains("unrecognized choice 'x'"), "{}", shown);
+    assert!(shown.contains("regenerating..."), "{}", shown);
+    assert_eq!(head_message(&test), "feat: second\n");
+    assert_eq!(recorded_prompts(&test).len(), 2);
+}
+
+#[cfg(unix)]
+#[test]
+fn feedback_in_the_review_loop_goes_into_the_next_request() {
+    let test = repo();
+    let prompts = prompts_file(&test);
+    let Some((code, shown)) = run_on_terminal(
+        &test,
+        &["feat: first", "feat(notes): add the notes"],
+        &["--commit"],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+        "f\nname the notes in the scope\na\n",
+    ) else {
+        return;
+    };
+    assert_eq!(code, Some(0), "{}", shown);
+    assert!(shown.contains("feedback: "), "{}", shown);
+    let prompts = recorded_prompts(&test);
+    assert_eq!(prompts.len(), 2);
+    assert!(prompts[1].contains("feat: first"), "{}", prompts[1]);
+    assert!(
+        prompts[1].contains("name the notes in the scope"),
+        "{}",
+        prompts[1]
+    );
+    assert_eq!(head_message(&test), "feat(notes): add the notes\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn edit_in_the_review_loop_commits_what_the_editor_saved() {
+    let test = repo();
+    let Some((code, shown)) = run_on_terminal(
+        &test,
+        &["feat: first"],
+        &["--commit"],
+        &[("GIT_EDITOR", "sed -i s/first/edited/")],
+        "e\n",
+    ) else {
+        return;
+    };
+    assert_eq!(code, Some(0), "{}", shown);
+    assert_eq!(head_message(&test), "feat: edited\n");
+}
+
+// the cache keeps what the model said, so the next run doesn't reuse the edit as its answer
+#[cfg(unix)]
+#[test]
+fn an_edited_suggestion_is_not_what_the_cache_reuses() {
+    let test = repo();
+    let saved = test.path().join(".git").join("message.txt");
+    let Some((code, shown)) = run_on_terminal(
+        &test,
+        &["feat: first"],
+        &["--edit", "--output", saved.to_str().unwrap()],
+        &[("GIT_EDITOR", "sed -i s/first/edited/")],
+        "a\n",
+    ) else {
+        return;
+    };
+    assert_eq!(code, Some(0), "{}", shown);
+    assert_eq!(fs::read_to_string(&saved).unwrap(), "feat: edited\n");
+
+    let (report, sent) = reported(&test, "feat: never asked for", &[]);
+    assert_eq!((report["cached"].as_bool(), sent), (Some(true), 0));
+    assert_eq!(report["message"], "feat: first");
+}
+
+#[cfg(unix)]
+#[test]
+fn quitting_the_review_loop_or_closing_its_input_commits_nothing() {
+    let test = repo();
+    let head = test.repo.head().unwrap().target();
+    for typed in ["q\n", "\x04"] {
+        let Some((code, shown)) = run_on_terminal(
+            &test,
+            &["feat: first"],
+            &["--commit", "--no-cache"],
+            &[],
+            typed,
+        ) else {
+            return;
+        };
+        assert_eq!(code, Some(1), "{}", shown);
+        assert!(
+            shown.contains("aborted: the suggestion was not accepted"),
+            "{}",
+            shown
+        );
+        assert_eq!(test.repo.head().unwrap().target(), head);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn select_lists_the_excluded_files_unchecked_and_q_sends_nothing() {
+    let test = repo();
+    test.write("Cargo.lock", "# generated\n");
+    test.stage("Cargo.lock");
+    let prompts = prompts_file(&test);
+    let extra = [("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())];
+    let Some((code, shown)) = run_on_terminal(&test, &[ANSWER], &["--select"], &extra, "q\n")
+    else {
+        return;
+    };
+    assert_eq!(code, Some(0), "{}", shown);
+    assert!(shown.contains("[ ]   1  added  Cargo.lock"), "{}", shown);
+    assert!(shown.contains("[x]   2  added  notes.txt"), "{}", shown);
+    assert!(shown.contains("quit; nothing was sent"), "{}", shown);
+    assert!(!prompts.exists());
+
+    // checking the excluded file takes it back in
+    let Some((code, shown)) = run_on_terminal(&test, &[ANSWER], &["--select"], &extra, "1\n\na\n")
+    else {
+        return;
+    };
+    assert_eq!(code, Some(0), "{}", shown);
+    let prompts = recorded_prompts(&test);
+    assert_eq!(prompts.len(), 1, "{}", shown);
+    assert!(prompts[0].contains("Cargo.lock"), "{}", prompts[0]);
+    assert!(prompts[0].contains("notes.txt"), "{}", prompts[0]);
+}
+
+#[cfg(unix)]
+#[test]
+fn yes_takes_the_defaults_even_on_a_terminal() {
+    let test = repo();
+    let Some((code, shown)) = run_on_terminal(
+        &test,
+        &["feat: first", "feat: second"],
+        &["--commit", "--yes", "--candidates", "2", "--edit"],
+        &[("GIT_EDITOR", "false")],
+        "",
+    ) else {
+        return;
+    };
+    assert_eq!(code, Some(0), "{}", shown);
+    assert!(!shown.contains("[a]ccept"), "{}", shown);
+    assert!(
+        shown.contains("not opening the editor in non-interactive mode"),
+        "{}",
+        shown
+    );
+    assert_eq!(head_message(&test), "feat: first\n");
+}
+
+#[test]
+fn the_installed_hook_fills_in_the_message_git_commit_opens() {
+    let test = repo();
+    let hooks = test.path().join(".git").join("hooks");
+    fs::create_dir_all(&hooks).unwrap();
+    let theirs = hooks.join("prepare-commit-msg");
+    fs::write(
+        &theirs,
+        "#!/bin/sh\necho \"$2\" >> \"$(dirname \"$0\")/ran\"\n",
+    )
+    .unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&theirs, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let output = run_subcommand(&test, "install-hook", &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let output = run_subcommand(&test, "install-hook", &["--force"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    // git runs the hook, which calls the one it replaced first; the editor keeps what it finds
+    let answers = serde_json::to_string(&["feat(notes): add a notes file"]).unwrap();
+    let extra = [
+        ("GIT_CHANGES_MOCK_ANSWERS", answers.as_str()),
+        ("GIT_EDITOR", "true"),
+    ];
+    let output = git_with(&test, &["commit", "--quiet"], &extra);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(head_message(&test), "feat(notes): add a notes file\n");
+    assert_eq!(fs::read_to_string(hooks.join("ran")).unwrap(), "\n");
+
+    // a message given with -m is kept
+    test.write("notes.txt", "more notes\n");
+    test.stage("notes.txt");
+    let output = git_with(&test, &["commit", "--quiet", "-m", "docs: by hand"], &extra);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(head_message(&test), "docs: by hand\n");
+    assert_eq!(
+        fs::read_to_string(hooks.join("ran")).unwrap(),
+        "\nmessage\n"
+    );
+
+    let output = run_subcommand(&test, "uninstall-hook", &[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        fs::read_to_string(&theirs).unwrap(),
+        "#!/bin/sh\necho \"$2\" >> \"$(dirname \"$0\")/ran\"\n"
+    );
+    let output = run_subcommand(&test, "uninstall-hook", &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+}
+
+// `notes show` for `rev` in `test`
+fn notes_show(test: &TestRepo, rev: &str) -> Output {
+    let args = [
+        OsStr::new("notes"),
+        OsStr::new("show"),
+        OsStr::new("--repo"),
+        test.path().as_os_str(),
+        OsStr::new(rev),
+    ];
+    run_with(&args, &[], &[])
+}
+
+#[test]
+fn note_records_the_generation_and_notes_show_reads_it_back() {
+    let test = repo();
+    let output = run(
+        &test,
+        &["feat(notes): add a notes file"],
+        &["--commit", "--yes", "--note"],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let output = notes_show(&test, "HEAD");
+    assert!(output.status.success(), "{:?}", output);
+    let note: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(note["tool"], "git-changes-rs");
+    assert_eq!(note["tool_version"], env!("CARGO_PKG_VERSION"));
+    assert_eq!(note["model"], "gemini-2.0-flash");
+    assert_eq!(note["human_edited"], false);
+    // nothing from the diff, the prompt or the environment
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("notes.txt"));
+
+    let output = notes_show(&test, "HEAD~1");
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("has no note in refs/notes/git-changes"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn generate_as_a_subcommand_does_what_the_bare_command_does() {
+    let bare = repo();
+    let output = run(&bare, &[ANSWER], &["--commit", "--quiet"], &[]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let sub = repo();
+    let args = [
+        OsStr::new("--quiet"),
+        OsStr::new("generate"),
+        sub.path().as_os_str(),
+        OsStr::new("--commit"),
+    ];
+    let output = run_with(&args, &[ANSWER], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(head_message(&sub), head_message(&bare));
+}
+
+#[test]
+fn a_generate_flag_with_another_subcommand_is_a_usage_error() {
+    let test = repo();
+    let head = test.repo.head().unwrap().target().unwrap();
+    let args = [
+        OsStr::new("--commit"),
+        OsStr::new("lint"),
+        OsStr::new("--repo"),
+        test.path().as_os_str(),
+    ];
+    let output = run_with(&args, &[ANSWER], &[]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("--commit belongs to generate and can't be used with 'lint'"));
+    assert_eq!(test.repo.head().unwrap().target().unwrap(), head);
+
+    // the global ones are fine on either side
+    let mut args = vec![OsStr::new("-q"), OsStr::new("lint"), OsStr::new("--repo")];
+    args.extend([test.path().as_os_str(), OsStr::new("--no-color")]);
+    let output = run_with(&args, &[], &[]);
+    assert_ne!(output.status.code(), Some(5), "{:?}", output);
+}
+// `completions <shell>` started in `dir`, a home of its own with no key or cache unless `extra`
+// sets them
+fn completions(shell: &str, dir: &std::path::Path, extra: &[(&str, &str)]) -> Output {
+    let home = tempfile::tempdir().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_git-changes-rs"));
+    isolate(&mut command, home.path(), extra);
+    command
+        .current_dir(dir)
+        .args(["completions", shell])
+        .output()
+        .expect("failed to run git-changes-rs")
+}
+
+#[test]
+fn every_shell_gets_a_script_without_a_repository_or_key() {
+    let dir = tempfile::tempdir().unwrap();
+    for shell in ["bash", "zsh", "fish", "powershell", "elvish"] {
+        let output = completions(shell, dir.path(), &[]);
+        assert!(output.status.success(), "{}: {:?}", shell, output);
+        let script = String::from_utf8(output.stdout).unwrap();
+        assert!(script.contains("git-changes-rs"), "{}", shell);
+        assert!(script.contains("standup"), "{}", shell);
+        assert!(script.contains("provider"), "{}", shell);
+    }
+    let output = completions("tcsh", dir.path(), &[]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+}
+
+#[test]
+fn completions_offer_the_cached_models_and_the_configured_types() {
+    let test = repo();
+    test.write(
+        ".git-changes.toml",
+        "[commit]\ntypes = [\"feat\", \"hotfix\"]\n",
+    );
+    let cache = tempfile::tempdir().unwrap();
+    let models = cache.path().join("git-changes-rs");
+    fs::create_dir_all(&models).unwrap();
+    fs::write(models.join("models"), "gemini-test-pro\ngemini-test-lite\n").unwrap();
+
+    let cache_home = cache.path().to_str().unwrap();
+    let output = completions("bash", test.path(), &[("XDG_CACHE_HOME", cache_home)]);
+    assert!(output.status.success(), "{:?}", output);
+    let script = String::from_utf8(output.stdout).unwrap();
+    assert!(script.contains("gemini-test-pro gemini-test-lite"));
+    assert!(script.contains("hotfix"));
+
+    // without them --model takes anything and --types offers the standard set
+    let dir = tempfile::tempdir().unwrap();
+    let script = String::from_utf8(completions("bash", dir.path(), &[]).stdout).unwrap();
+    assert!(!script.contains("gemini-test-pro"));
+    assert!(!script.contains("hotfix"));
+    assert!(script.contains("refactor"));
+}
+// a user config.toml under a config home of its own, which `extra` can point XDG_CONFIG_HOME at
+fn user_config(text: &str) -> tempfile::TempDir {
+    let home = tempfile::tempdir().unwrap();
+    let dir = home.path().join("git-changes");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config.toml"), text).unwrap();
+    home
+}
+
+// --print-config in `test`: what it printed, and the lines of it that set `keys`
+fn printed_config(test: &TestRepo, args: &[&str], extra: &[(&str, &str)], keys: &[&str]) -> String {
+    let mut all = vec!["--print-config"];
+    all.extend(args);
+    let output = run(test, &[], &all, extra);
+    assert!(output.status.success(), "{:?}", output);
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .filter(|line| {
+            keys.iter()
+                .any(|key| line.starts_with(&format!("{} = ", key)))
+        })
+        .map(|line| line.replace(test.path().to_str().unwrap(), "<repo>"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn the_repository_config_beats_the_users_key_by_key() {
+    let test = repo();
+    test.write(
+        ".git-changes.toml",
+        "[message]\nmax_title_length = 60\n\n[diff]\nexclude = [\"*.log\"]\n",
+    );
+    let home = user_config("[message]\nstyle = \"detailed\"\nmax_title_length = 50\n");
+    let xdg = home.path().to_str().unwrap();
+    let user = home.path().join("git-changes/config.toml");
+
+    let printed = printed_config(
+        &test,
+        &["--exclude", "*.tmp"],
+        &[("XDG_CONFIG_HOME", xdg)],
+        &["style", "max_title_length", "exclude", "warn_title_length"],
+    );
+    assert_eq!(
+        printed,
+        format!(
+            "style = \"detailed\"  # global {}\n\
+             max_title_length = 60  # repo <repo>/.git-changes.toml\n\
+             warn_title_length = 50  # default\n\
+             exclude = [\"*.log\", \"*.tmp\"]  # repo <repo>/.git-changes.toml and --exclude",
+            user.display()
+        )
+    );
+
+    // the flag beats both files
+    let printed = printed_config(
+        &test,
+        &["--max-title-length", "72"],
+        &[("XDG_CONFIG_HOME", xdg)],
+        &["max_title_length"],
+    );
+    assert_eq!(printed, "max_title_length = 72  # --max-title-length");
+}
+
+#[test]
+fn config_replaces_both_files() {
+    let test = repo();
+    test.write(".git-changes.toml", "[message]\nmax_title_length = 60\n");
+    let home = user_config("[message]\nstyle = \"detailed\"\n");
+    let other = tempfile::tempdir().unwrap();
+    let path = other.path().join("ci.toml");
+    fs::write(&path, "[message]\nstyle = \"casual\"\n").unwrap();
+
+    let printed = printed_config(
+        &test,
+        &["--config", path.to_str().unwrap()],
+        &[("XDG_CONFIG_HOME", home.path().to_str().unwrap())],
+        &["style", "max_title_length"],
+    );
+    assert_eq!(
+        printed,
+        format!(
+            "style = \"casual\"  # --config {}\nmax_title_length = 72  # default",
+            path.display()
+        )
+    );
+}
+
+#[test]
+fn config_mistakes_name_the_file() {
+    let test = repo();
+    let home = user_config("[message]\nbogus = 1\n");
+    let xdg = home.path().to_str().unwrap();
+    let output = run(&test, &[], &["--print-config"], &[("XDG_CONFIG_HOME", xdg)]);
+    assert!(output.status.success(), "{:?}", output);
+    let user = home.path().join("git-changes/config.toml");
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "unknown key 'message.bogus' in {}",
+        user.display()
+    )));
+
+    test.write(".git-changes.toml", "[message]\nstyle = \n");
+    let output = run(&test, &[ANSWER], &["--json"], &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "invalid_config");
+    let message = report["error"]["message"].as_str().unwrap();
+    assert!(message.contains(".git-changes.toml"), "{}", message);
+    assert!(message.contains("line 2, column 9"), "{}", message);
+}
+#[test]
+fn variables_beat_the_config_files_and_flags_beat_variables() {
+    let test = repo();
+    test.write(
+        ".git-changes.toml",
+        "[message]\nmax_title_length = 60\n\n[diff]\nexclude = [\"*.log\"]\nmax_files = 5\n",
+    );
+    let home = user_config("[message]\nstyle = \"detailed\"\n");
+    let env = [
+        ("XDG_CONFIG_HOME", home.path().to_str().unwrap()),
+        ("GIT_CHANGES_MAX_TITLE_LENGTH", "70"),
+        ("GIT_CHANGES_STYLE", "casual"),
+        ("GIT_CHANGES_EXCLUDE", "*.a, dist/**"),
+        // empty counts as unset
+        ("GIT_CHANGES_MAX_FILES", ""),
+    ];
+    let keys = ["style", "max_title_length", "exclude", "max_files"];
+    assert_eq!(
+        printed_config(&test, &[], &env, &keys),
+        "style = \"casual\"  # GIT_CHANGES_STYLE\n\
+         max_title_length = 70  # GIT_CHANGES_MAX_TITLE_LENGTH\n\
+         exclude = [\"*.a\", \"dist/**\"]  # GIT_CHANGES_EXCLUDE\n\
+         max_files = 5  # repo <repo>/.git-changes.toml"
+    );
+    assert_eq!(
+        printed_config(
+            &test,
+            &["--max-title-length", "80"],
+            &env,
+            &["max_title_length"]
+        ),
+        "max_title_length = 80  # --max-title-length"
+    );
+
+    // and what they set is what the run uses
+    let output = run(&test, &[ANSWER], &["--json"], &env[..4]);
+    assert!(output.status.success(), "{:?}", output);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let subject = report["message"]
+        .as_str()
+        .unwrap()
+        .lines()
+        .next()
+        .unwrap()
+        .to_string();
+    assert!(subject.len() > 60 && subject.len() <= 70, "{}", subject);
+}
+
+#[test]
+fn a_variable_that_doesnt_fit_its_setting_is_an_error() {
+    let test = repo();
+    let output = run(
+        &test,
+        &[ANSWER],
+        &["--json"],
+        &[("GIT_CHANGES_MAX_FILES", "lots")],
+    );
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "invalid_config");
+    let message = report["error"]["message"].as_str().unwrap();
+    assert!(
+        message.contains("GIT_CHANGES_MAX_FILES=lots is not a valid value for diff.max_files"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn git_changes_config_names_the_file_like_config() {
+    let test = repo();
+    test.write(".git-changes.toml", "[message]\nstyle = \"detailed\"\n");
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ci.toml");
+    fs::write(&path, "[message]\nstyle = \"casual\"\n").unwrap();
+    let env = [("GIT_CHANGES_CONFIG", path.to_str().unwrap())];
+    assert_eq!(
+        printed_config(&test, &[], &env, &["style"]),
+        format!("style = \"casual\"  # --config {}", path.display())
+    );
+}
+#[test]
+fn format_string_prints_the_message_through_the_template() {
+    let test = repo();
+    let output = run(
+        &test,
+        &["feat(notes): add a notes file\n\n- start the notes"],
+        &[
+            "--format-string",
+            "{type}/{scope}: {subject}\\n{files} {{{model}}}",
+        ],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "feat/notes: add a notes file\nnotes.txt {gemini-2.0-flash}\n"
+    );
+}
+
+#[test]
+fn a_bad_format_string_fails_before_anything_is_sent() {
+    let test = repo();
+    let prompts = prompts_file(&test);
+    let extra = [("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())];
+    let output = run(
+        &test,
+        &["feat: never asked for"],
+        &["--format-string", "{kind}: {subject}"],
+        &extra,
+    );
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("unknown placeholder {kind} (available: {type}, "));
+
+    let output = run(
+        &test,
+        &["feat: never asked for"],
+        &["--format-string", "{subject}", "--json"],
+        &extra,
+    );
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    assert!(!prompts.exists());
+}
+
+fn diverged() -> (TestRepo, git2::Oid) {
+    let test = TestRepo::with_commit(&[("config.txt", "mode = base\n")]);
+    let mut config = test.repo.config().unwrap();
+    config.set_str("user.name", "Test").unwrap();
+    config.set_str("user.email", "test@example.com").unwrap();
+    let base = test.repo.head().unwrap().target().unwrap();
+
+    test.write("config.txt", "mode = theirs\n");
+    test.stage("config.txt");
+    let theirs = test.commit("feat: switch to their mode");
+    test.repo
+        .branch("feature", &test.repo.find_commit(theirs).unwrap(), false)
+        .unwrap();
+    let base = test.repo.find_object(base, None).unwrap();
+    test.repo.reset(&base, git2::ResetType::Hard, None).unwrap();
+    drop(base);
+    test.write("config.txt", "mode = ours\n");
+    test.stage("config.txt");
+    test.commit("feat: switch to our mode");
+    (test, theirs)
+}
+
+// `git merge feature`, stopped at the conflict in config.txt
+fn conflicted_merge() -> TestRepo {
+    let (test, _) = diverged();
+    let feature = test
+        .repo
+        .find_reference("refs/heads/feature")
+        .and_then(|reference| test.repo.reference_to_annotated_commit(&reference))
+        .unwrap();
+    test.repo.merge(&[&feature], None, None).unwrap();
+    drop(feature);
+    assert!(test.repo.index().unwrap().has_conflicts());
+    test
+}
+
+fn prompts_file(test: &TestRepo) -> std::path::PathBuf {
+    test.path().join(".git").join("prompts.jsonl")
+}
+
+#[test]
+fn unresolved_conflicts_stop_the_run_before_any_request() {
+    let test = conflicted_merge();
+    let prompts = prompts_file(&test);
+    let output = run(
+        &test,
+        &["feat: resolve the merge"],
+        &[],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("merge in progress with unresolved conflicts in config.txt"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("--allow-conflicts"), "{}", stderr);
+    assert!(!prompts.exists());
+}
+
+#[test]
+fn a_conflicted_cherry_pick_is_named_as_one() {
+    let (test, theirs) = diverged();
+    let commit = test.repo.find_commit(theirs).unwrap();
+    test.repo.cherrypick(&commit, None).unwrap();
+
+    let output = run(&test, &["feat: switch modes"], &[], &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("cherry-pick in progress with unresolved conflicts in config.txt"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn allow_conflicts_describes_the_changes_despite_the_conflicts() {
+    let test = conflicted_merge();
+    let prompts = prompts_file(&test);
+    let output = run(
+        &test,
+        &["Merge branch 'feature'"],
+        &["--allow-conflicts"],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(prompts).unwrap().lines().count(), 1);
+}
+
+#[test]
+fn force_doesnt_get_past_conflicts_and_allow_conflicts_doesnt_amend() {
+    let test = conflicted_merge();
+    let prompts = prompts_file(&test);
+    let env = [("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())];
+
+    // --force only means something with --amend
+    let output = run(&test, &["feat: resolve"], &["--force"], &env);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+
+    let output = run(
+        &test,
+        &["feat: resolve"],
+        &["--amend", "--force", "--yes"],
+        &env,
+    );
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("unresolved conflicts in config.txt"),
+        "{}",
+        stderr
+    );
+    assert!(!prompts.exists());
+}
+
+// a repository whose HEAD is already on origin/main, with a change staged on top
+fn pushed() -> TestRepo {
+    let test = repo();
+    let head = test.repo.head().unwrap().target().unwrap();
+    test.repo
+        .reference("refs/remotes/origin/main", head, true, "test")
+        .unwrap();
+    test
+}
+
+#[test]
+fn amending_a_pushed_head_needs_force_and_allow_conflicts_isnt_it() {
+    let test = pushed();
+    let head = test.repo.head().unwrap().target().unwrap();
+
+    for args in [
+        &["--amend", "--yes"][..],
+        &["--amend", "--yes", "--allow-conflicts"],
+    ] {
+        let output = run(&test, &["feat: add notes"], args, &[]);
+        assert_eq!(output.status.code(), Some(1), "{:?}", output);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("refusing to amend a pushed commit (use --force"),
+            "{}",
+            stderr
+        );
+        assert_eq!(test.repo.head().unwrap().target().unwrap(), head);
+    }
+
+    let output = run(
+        &test,
+        &["feat: add notes"],
+        &["--amend", "--yes", "--force"],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_ne!(test.repo.head().unwrap().target().unwrap(), head);
+    assert_eq!(head_message(&test), "feat: add notes\n");
+}
+
+// `git` in `test`, with only `extra` from the environment
+fn git_with(test: &TestRepo, args: &[&str], extra: &[(&str, &str)]) -> Output {
+    let home = tempfile::tempdir().unwrap();
+    let mut command = Command::new("git");
+    isolate(&mut command, home.path(), extra);
+    command
+        .args(args)
+        .current_dir(test.path())
+        .output()
+        .expect("failed to run git")
+}
+
+// `git` in `test`, which has to succeed
+fn git(test: &TestRepo, args: &[&str]) {
+    let output = git_with(test, args, &[]);
+    assert!(output.status.success(), "git {:?}: {:?}", args, output);
+}
+
+// `git <args>` run in `dir` with the binary on PATH as `git-changes`, the way it is installed
+// for `git changes`, and answered by a mock
+#[cfg(unix)]
+fn as_git_changes(dir: &std::path::Path, args: &[&str], answers: &[&str]) -> Output {
+    let bin = tempfile::tempdir().unwrap();
+    std::os::unix::fs::symlink(
+        env!("CARGO_BIN_EXE_git-changes-rs"),
+        bin.path().join("git-changes"),
+    )
+    .unwrap();
+    let mut path = std::ffi::OsString::from(bin.path());
+    path.push(":");
+    path.push(std::env::var_os("PATH").unwrap_or_default());
+
+    let home = tempfile::tempdir().unwrap();
+    let answers = serde_json::to_string(answers).unwrap();
+    let mut command = Command::new("git");
+    isolate(
+        &mut command,
+        home.path(),
+        &[("GIT_CHANGES_MOCK_ANSWERS", answers.as_str())],
+    );
+    command
+        .env("PATH", path)
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .expect("failed to run git")
+}
+
+#[cfg(unix)]
+#[test]
+fn git_changes_says_so_in_its_usage_and_works_where_it_is_run() {
+    let test = repo();
+    // -h, since git answers `git changes --help` with the man page
+    let output = as_git_changes(test.path(), &["changes", "-h"], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let help = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        help.contains("Usage: git changes [OPTIONS] [directory]"),
+        "{}",
+        help
+    );
+
+    let output = as_git_changes(test.path(), &["changes", "--no-such-flag"], &[]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Usage: git changes"));
+
+    // no directory argument: the one git was run in, here a subdirectory of the working tree
+    fs::create_dir(test.path().join("docs")).unwrap();
+    let output = as_git_changes(
+        &test.path().join("docs"),
+        &["changes", "--commit", "--quiet"],
+        &["feat(notes): add the notes"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(head_message(&test), "feat(notes): add the notes\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn git_dir_and_work_tree_from_git_name_the_repository() {
+    let test = repo();
+    let elsewhere = tempfile::tempdir().unwrap();
+    let git_dir = format!("--git-dir={}", test.path().join(".git").display());
+    let work_tree = format!("--work-tree={}", test.path().display());
+    let output = as_git_changes(
+        elsewhere.path(),
+        &[&git_dir, &work_tree, "changes", "--commit", "--quiet"],
+        &["feat(notes): add the notes"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(head_message(&test), "feat(notes): add the notes\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn an_alias_reads_paths_from_where_it_was_typed() {
+    let test = repo();
+    fs::create_dir(test.path().join("docs")).unwrap();
+    // `.` is where the alias was typed, not the top of the working tree it runs in
+    let alias = format!(
+        "!{} . --output message.txt",
+        env!("CARGO_BIN_EXE_git-changes-rs")
+    );
+    git(&test, &["config", "alias.msg", &alias]);
+
+    let output = as_git_changes(
+        &test.path().join("docs"),
+        &["msg"],
+        &["feat(notes): add the notes"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let written = fs::read_to_string(test.path().join("docs/message.txt")).unwrap();
+    assert_eq!(written.trim_end(), "feat(notes): add the notes");
+    assert!(!test.path().join("message.txt").exists());
+}
+
+#[test]
+fn dash_c_stands_in_for_the_directory_and_follows_the_prefix() {
+    let test = repo();
+    fs::create_dir(test.path().join("docs")).unwrap();
+    let docs = test.path().join("docs");
+    let args = [
+        OsStr::new("-C"),
+        docs.as_os_str(),
+        OsStr::new("--output"),
+        OsStr::new("message.txt"),
+    ];
+    let output = run_with(&args, &["feat(notes): add the notes"], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(test.path().join("docs/message.txt").exists());
+
+    // -C is relative to GIT_PREFIX, which is relative to where the run starts
+    let args = [
+        OsStr::new("-C"),
+        OsStr::new(".."),
+        OsStr::new("--output"),
+        OsStr::new("top.txt"),
+    ];
+    let output = run_with(
+        &args,
+        &["feat(notes): add the notes"],
+        &[("GIT_PREFIX", test.path().join("docs").to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(test.path().join("top.txt").exists());
+
+    let output = run_with(&[OsStr::new("-C"), OsStr::new("/no/such/dir")], &[], &[]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot change to '/no/such/dir'"));
+}
+
+// `repo()` with its branch pushed to a bare `origin`, as its upstream; the bare repository is
+// returned with it so it lives as long as the test
+fn with_origin() -> (TestRepo, tempfile::TempDir) {
+    let test = repo();
+    let origin = tempfile::tempdir().unwrap();
+    git2::Repository::init_bare(origin.path()).unwrap();
+    test.repo
+        .remote("origin", origin.path().to_str().unwrap())
+        .unwrap();
+    git(
+        &test,
+        &["push", "--quiet", "--set-upstream", "origin", "HEAD"],
+    );
+    (test, origin)
+}
+
+// where `origin` has the test repository's branch
+fn on_origin(test: &TestRepo, origin: &tempfile::TempDir) -> git2::Oid {
+    let head = test.repo.head().unwrap();
+    git2::Repository::open_bare(origin.path())
+        .unwrap()
+        .refname_to_id(head.name().unwrap())
+        .unwrap()
+}
+
+#[test]
+fn use_git_binary_pushes_an_amend_with_an_explicit_lease() {
+    let (test, origin) = with_origin();
+    let args = [
+        "--amend",
+        "--force",
+        "--push",
+        "--force-with-lease",
+        "--use-git-binary",
+        "--yes",
+    ];
+    let output = run(&test, &["feat: add notes"], &args, &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let head = test.repo.head().unwrap().target().unwrap();
+    assert_eq!(head_message(&test), "feat: add notes\n");
+    assert_eq!(on_origin(&test, &origin), head);
+
+    // someone else pushes over it, and this clone never fetches that
+    let tracking = format!(
+        "refs/remotes/origin/{}",
+        test.repo.head().unwrap().shorthand().unwrap()
+    );
+    let seen = test.repo.refname_to_id(&tracking).unwrap();
+    git(
+        &test,
+        &["commit", "--quiet", "--amend", "-m", "feat: theirs"],
+    );
+    git(&test, &["push", "--quiet", "--force", "origin", "HEAD"]);
+    let theirs = on_origin(&test, &origin);
+    test.repo.reference(&tracking, seen, true, "test").unwrap();
+
+    test.write("notes.txt", "the project notes, again\n");
+    test.stage("notes.txt");
+    let output = run(&test, &["feat: add notes again"], &args, &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("the commit succeeded; only the push failed")
+            && stderr.contains("stale info"),
+        "{}",
+        stderr
+    );
+    assert_eq!(on_origin(&test, &origin), theirs);
+}
+
+#[test]
+fn use_git_binary_needs_something_for_git_to_do() {
+    let test = repo();
+    let output = run(
+        &test,
+        &["feat: add notes"],
+        &["--amend", "--use-git-binary", "--yes"],
+        &[],
+    );
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+}
+
+#[test]
+fn a_resolved_merge_gets_a_merge_message() {
+    let test = conflicted_merge();
+    test.write("config.txt", "mode = both\n");
+    test.stage("config.txt");
+    let prompts = prompts_file(&test);
+    let output = run(
+        &test,
+        &["Merge branch 'feature'\n\nBrings in their mode and keeps ours alongside it."],
+        &[],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    let recorded = fs::read_to_string(prompts).unwrap();
+    let prompt: Value = serde_json::from_str(recorded.lines().next().unwrap()).unwrap();
+    let system = prompt["system"].as_str().unwrap();
+    assert!(system.starts_with("You write the message for a merge commit"));
+    let user = prompt["user"].as_str().unwrap();
+    assert!(user.contains("Subject line to use: Merge branch 'feature' into "));
+    assert!(user.contains("- feat: switch to their mode"));
+    assert!(user.contains("Files that conflicted and were resolved:\n- config.txt"));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("Merge branch 'feature' into "),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("keeps ours alongside it"));
+}
+
+#[test]
+fn a_provider_failure_is_reported_and_nothing_is_committed() {
+    let test = repo();
+    let before = test.repo.head().unwrap().target();
+    let output = run(
+        &test,
+        &["feat(notes): add a notes file"],
+        &["--commit", "--yes", "--json"],
+        &[("GIT_CHANGES_MOCK_FAIL_ON", "1")],
+    );
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "network");
+    assert_eq!(report["error"]["provider"], "mock");
+    assert_eq!(test.repo.head().unwrap().target(), before);
+}
+
+#[test]
+fn an_answer_that_stays_empty_exits_with_its_own_status() {
+    let test = repo();
+    let before = test.repo.head().unwrap().target();
+    let output = run(&test, &[""], &["--commit", "--yes", "--json"], &[]);
+    assert_eq!(output.status.code(), Some(7), "{:?}", output);
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "empty_response");
+    assert_eq!(report["code"], "empty_response");
+    let message = report["error"]["message"].as_str().unwrap();
+    assert!(message.contains("mock returned no message, even when asked again"));
+    assert!(!message.contains("parse"));
+    assert_eq!(test.repo.head().unwrap().target(), before);
+}
+
+#[tokio::test]
+async fn an_empty_answer_is_asked_for_once_more_then_fails() {
+    let prompt = Prompt {
+        system: "Write a conventional commit message.".to_string(),
+        user: "diff --git a/notes.txt b/notes.txt\n+the project notes\n".to_string(),
+        max_output_tokens: 512,
+        title_only: false,
+    };
+
+    let provider = MockProvider::new(["", "feat(notes): add a notes file"]);
+    let message = message::generate(&provider, &prompt, &FormatOptions::default(), None)
+        .await
+        .unwrap();
+    assert_eq!(message, "feat(notes): add a notes file");
+    let prompts = provider.prompts();
+    assert_eq!(prompts.len(), 2);
+    assert_eq!(
+        prompts[1].user,
+        format!("{}\n\nRespond with only the commit message.", prompt.user)
+    );
+
+    let provider = MockProvider::new(["", " ", "feat(notes): never asked for"]);
+    let error = message::generate(&provider, &prompt, &FormatOptions::default(), None)
+        .await
+        .expect_err("both answers are empty");
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            provider: "mock",
+            failure: ProviderFailure::Empty,
+            ..
+        }
+    ));
+    assert_eq!(provider.calls(), 2);
+}
+
+#[tokio::test]
+async fn the_mock_records_prompts_and_follows_its_script() {
+    let prompt = Prompt {
+        system: "Write a conventional commit message.".to_string(),
+        user: "diff --git a/notes.txt b/notes.txt\n+the project notes\n".to_string(),
+        max_output_tokens: 512,
+        title_only: false,
+    };
+    let provider = MockProvider::new(["updated some files", "feat(notes): add a notes file"])
+        .fail_on(3, ProviderFailure::RateLimited);
+
+    let message = message::generate(
+        &provider,
+        &prompt,
+        &FormatOptions::default(),
+        Some(&Rules::default()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(message, "feat(notes): add a notes file");
+    assert_eq!(provider.calls(), 2);
+    let prompts = provider.prompts();
+    assert_eq!(prompts[0], prompt);
+    assert!(prompts[1].user.contains("updated some files"));
+
+    let error = message::generate(&provider, &prompt, &FormatOptions::default(), None)
+        .await
+        .expect_err("the third call fails");
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            provider: "mock",
+            failure: ProviderFailure::RateLimited,
+            ..
+        }
+    ));
+    assert!(error.is_retryable());
+
+    // past the end of the script, the last answer repeats
+    let again = message::generate(&provider, &prompt, &FormatOptions::default(), None)
+        .await
+        .unwrap();
+    assert_eq!(again, "feat(notes): add a notes file");
+    assert_eq!(provider.calls(), 4);
+}
+
+#[test]
+fn lint_failures_exit_1_after_the_report_with_no_error_of_their_own() {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    test.write("notes.txt", "notes\n");
+    test.stage("notes.txt");
+    test.commit("feat: add notes");
+
+    let output = run_subcommand(&test, "lint", &["HEAD~1"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("initial commit"), "{}", stdout);
+    assert!(stdout.ends_with("1 of 1 commits failed\n"), "{}", stdout);
+    assert!(!String::from_utf8(output.stderr).unwrap().contains("Error"));
+
+    let output = run_subcommand(&test, "lint", &["HEAD~1..HEAD"]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+}
+
+#[test]
+fn a_json_error_is_printed_once() {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    let output = run(&test, &["feat: nothing"], &["--json"], &[]);
+    assert_eq!(output.status.code(), Some(2), "{:?}", output);
+
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "no_changes");
+    assert_eq!(report["code"], "no_changes");
+    assert!(!String::from_utf8(output.stderr).unwrap().contains("Error"));
+}
+
+#[test]
+fn help_and_version_exit_0_and_bad_usage_5() {
+    let output = run_with(&[OsStr::new("--help")], &[], &[]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert!(String::from_utf8(output.stdout)
+        .unwrap()
+        .contains("Exit status:"));
+
+    let output = run_with(&[OsStr::new("--version")], &[], &[]);
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("git-changes-rs {}\n", env!("CARGO_PKG_VERSION"))
+    );
+
+    let output = run_with(&[OsStr::new("--no-such-flag")], &[], &[]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+}
+
+#[test]
+fn usage_the_run_itself_rejects_exits_5() {
+    // clap asks for the directory before the run would
+    let output = run_with(&[], &[ANSWER], &[]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("<directory>"), "{}", stderr);
+
+    // no terminal to ask on
+    let test = repo();
+    for flag in ["--select", "--select-hunks"] {
+        let output = run(&test, &[ANSWER], &[flag, "--json"], &[]);
+        assert_eq!(output.status.code(), Some(5), "{:?}", output);
+        let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+        assert_eq!(report["error"]["kind"], "usage");
+        assert_eq!(report["code"], "usage");
+        assert!(
+            report["error"]["message"]
+                .as_str()
+                .unwrap()
+                .contains("needs a terminal"),
+            "{}",
+            report
+        );
+    }
+
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    test.write("README.md", "# project, unstaged\n");
+    let output = run(&test, &[ANSWER], &["--commit"], &[]);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("nothing is staged to commit"), "{}", stderr);
+}
+
+#[test]
+fn type_takes_only_the_configured_types_and_goes_into_the_prompt() {
+    let test = repo();
+    test.write(
+        ".git-changes.toml",
+        "[commit]\ntypes = [\"feat\", \"infra\"]\n",
+    );
+    let prompts = prompts_file(&test);
+    let extra = [("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())];
+    let output = run(&test, &["fix: never asked for"], &["--type", "fix"], &extra);
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("invalid value 'fix' for '--type': the allowed types are feat, infra"),
+        "{}",
+        stderr
+    );
+    assert!(!prompts.exists());
+
+    let output = run(
+        &test,
+        &["feat: add the notes"],
+        &["--type", "infra", "--scope", "docs", "-q"],
+        &extra,
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "infra(docs): add the notes\n"
+    );
+    let sent = fs::read_to_string(&prompts).unwrap();
+    let sent: Value = serde_json::from_str(sent.lines().next().unwrap()).unwrap();
+    let system = sent["system"].as_str().unwrap();
+    assert!(
+        system.contains("Use the commit type `infra` and the scope `docs`"),
+        "{}",
+        system
+    );
+}
+
+#[test]
+fn title_only_with_type_and_scope_commits_one_prefixed_line() {
+    let test = repo();
+    let output = run(
+        &test,
+        &["feat: add the notes\n\n- notes.txt: the project notes"],
+        &[
+            "--title-only",
+            "--type",
+            "fix",
+            "--scope",
+            "cli",
+            "--commit",
+            "-q",
+        ],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "fix(cli): add the notes\n"
+    );
+    assert_eq!(head_message(&test), "fix(cli): add the notes\n");
+}
+
+// the statuses not covered above, one test each; 130 is in tests/interrupt.rs
+
+#[test]
+fn a_directory_outside_any_repository_exits_1() {
+    let dir = tempfile::tempdir().unwrap();
+    let output = run_with(&[dir.path().as_os_str(), OsStr::new("--json")], &[], &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "not_a_repository");
+    assert_eq!(report["code"], "error");
+}
+
+#[test]
+fn changes_that_are_all_excluded_exit_3() {
+    let test = repo();
+    let prompts = prompts_file(&test);
+    let output = run(
+        &test,
+        &["feat: never asked for"],
+        &["--json", "--exclude", "*.txt"],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "all_excluded");
+    assert_eq!(report["code"], "all_excluded");
+    assert!(!prompts.exists());
+}
+
+// a build without gemini stops at the provider before it looks for the key
+#[cfg(feature = "gemini")]
+#[test]
+fn a_missing_key_is_a_provider_error_and_exits_4() {
+    let test = repo();
+    let output = run_bare(
+        &[test.path().as_os_str(), OsStr::new("--json")],
+        &[("GIT_CHANGES_PROVIDER", "gemini")],
+    );
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "missing_key");
+    assert_eq!(report["error"]["provider"], "gemini");
+    assert_eq!(report["code"], "provider_error");
+
+    let output = run_bare(&[test.path().as_os_str()], &[]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("GEMINI_API_KEY"), "{}", stderr);
+}
+
+#[test]
+fn a_diff_over_the_limits_exits_6_without_a_terminal() {
+    let test = repo();
+    test.write("more.txt", "more notes\n");
+    test.stage("more.txt");
+    let prompts = prompts_file(&test);
+    let output = run(
+        &test,
+        &["feat: never asked for"],
+        &["--json"],
+        &[
+            ("GIT_CHANGES_MAX_FILES", "1"),
+            ("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap()),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(6), "{:?}", output);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "too_large");
+    assert_eq!(report["code"], "too_large");
+    assert!(!prompts.exists());
+
+    let output = run(
+        &test,
+        &["feat(notes): add more notes"],
+        &["--force-large"],
+        &[("GIT_CHANGES_MAX_FILES", "1")],
+    );
+    assert_eq!(output.status.code(), Some(0), "{:?}", output);
+}
+
+#[test]
+fn yes_does_not_send_a_diff_over_the_limits() {
+    let test = repo();
+    test.write("big.txt", "a line of a generated file\n".repeat(200));
+    test.stage("big.txt");
+    let prompts = prompts_file(&test);
+    let output = run(
+        &test,
+        &["feat: never asked for"],
+        &["--yes"],
+        &[
+            ("GIT_CHANGES_MAX_KB", "2"),
+            ("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap()),
+        ],
+    );
+    assert_eq!(output.status.code(), Some(6), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("the diff is unusually large: "),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("KB of prompt (limit 2 KB)"), "{}", stderr);
+    let big = stderr.find("KB  big.txt").unwrap();
+    let notes = stderr.find("KB  notes.txt").unwrap();
+    assert!(big < notes, "{}", stderr);
+    assert!(
+        stderr.contains("use --force-large to send it"),
+        "{}",
+        stderr
+    );
+    assert!(!prompts.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn a_diff_over_the_limits_is_sent_only_when_the_terminal_says_so() {
+    let test = repo();
+    test.write("more.txt", "more notes\n");
+    test.stage("more.txt");
+    let prompts = prompts_file(&test);
+    let extra = [
+        ("GIT_CHANGES_MAX_FILES", "1"),
+        ("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap()),
+    ];
+    for typed in ["n\n", "\x04"] {
+        let Some((code, shown)) = run_on_terminal(&test, &["feat: no"], &[], &extra, typed) else {
+            return;
+        };
+        assert_eq!(code, Some(6), "{:?}: {}", typed, shown);
+        assert!(shown.contains("send it anyway? [y/N]: "), "{}", shown);
+    }
+    assert!(!prompts.exists());
+
+    // y sends, and a accepts the suggestion in the review that follows
+    let Some((code, shown)) = run_on_terminal(
+        &test,
+        &["feat(notes): add more notes"],
+        &[],
+        &extra,
+        "y\na\n",
+    ) else {
+        return;
+    };
+    assert_eq!(code, Some(0), "{}", shown);
+    assert!(shown.contains("feat(notes): add more notes"), "{}", shown);
+    assert_eq!(recorded_prompts(&test).len(), 1);
+}
+
+// the report of a --json run with `args` in `test`, and the prompts it sent
+fn reported(test: &TestRepo, answer: &str, args: &[&str]) -> (Value, usize) {
+    let prompts = test.path().join(".git").join("prompts.jsonl");
+    let before = fs::read_to_string(&prompts)
+        .unwrap_or_default()
+        .lines()
+        .count();
+    let mut all = vec!["--json"];
+    all.extend(args);
+    let output = run(
+        test,
+        &[answer],
+        &all,
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let after = fs::read_to_string(&prompts)
+        .unwrap_or_default()
+        .lines()
+        .count();
+    (
+        serde_json::from_slice(&output.stdout).unwrap(),
+        after - before,
+    )
+}
+
+#[test]
+fn the_same_request_reuses_the_last_message() {
+    let test = repo();
+    let (report, sent) = reported(&test, "feat: add notes", &[]);
+    assert_eq!((report["cached"].as_bool(), sent), (Some(false), 1));
+
+    let (report, sent) = reported(&test, "feat: something else", &[]);
+    assert_eq!((report["cached"].as_bool(), sent), (Some(true), 0));
+    assert_eq!(report["message"], "feat: add notes");
+
+    let (report, sent) = reported(&test, "feat: asked again", &["--no-cache"]);
+    assert_eq!((report["cached"].as_bool(), sent), (Some(false), 1));
+    assert_eq!(report["message"], "feat: asked again");
+}
+
+#[test]
+fn whatever_shapes_the_prompt_is_part_of_the_cache_key() {
+    let test = repo();
+    fs::write(
+        test.path().join(".git").join("failure.log"),
+        "test failed\n",
+    )
+    .unwrap();
+    let attachment = test.path().join(".git").join("failure.log");
+    let attachment = attachment.to_str().unwrap();
+    reported(&test, "feat: add notes", &[]);
+
+    for args in [
+        &["--style", "concise"][..],
+        &["--title-only"],
+        &["--lang", "de"],
+        &["--model", "gemini-2.5-pro"],
+        &["--attach", attachment],
+        &["--signoff"],
+    ] {
+        let (report, sent) = reported(&test, "feat: asked again", args);
+        assert_eq!(report["cached"], false, "{:?}", args);
+        assert_eq!(sent, 1, "{:?}", args);
+    }
+
+    // another attachment with the same name
+    fs::write(attachment, "another failure\n").unwrap();
+    let (report, _) = reported(&test, "feat: asked again", &["--attach", attachment]);
+    assert_eq!(report["cached"], false);
+}
+
+#[test]
+fn show_diff_stops_before_the_request() {
+    let test = repo();
+    let prompts = test.path().join(".git").join("prompts.jsonl");
+    let output = run(
+        &test,
+        &["feat: add notes"],
+        &["--show-diff", "--exclude", "README.md"],
+        &[("GIT_CHANGES_MOCK_PROMPTS", prompts.to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(
+        stdout.starts_with("diff --git a/notes.txt b/notes.txt\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("+the project notes\n"));
+    assert!(!prompts.exists());
+}
+
+#[test]
+fn show_diff_with_confirm_goes_on_to_generate() {
+    let test = repo();
+    let output = run(
+        &test,
+        &["feat: add notes"],
+        &["--show-diff", "--confirm", "--yes"],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "feat: add notes\n"
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("+the project notes\n"), "{}", stderr);
+}
+
+#[cfg(unix)]
+#[test]
+fn the_history_log_is_readable_by_its_owner_only() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let test = repo();
+    let state = tempfile::tempdir().unwrap();
+    let output = run(
+        &test,
+        &[ANSWER],
+        &[],
+        &[("XDG_STATE_HOME", state.path().to_str().unwrap())],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let log = state.path().join("git-changes").join("history.jsonl");
+    let mode = fs::metadata(&log).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+}
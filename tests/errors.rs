@@ -0,0 +1,151 @@
+// tests/errors.rs
+// the error document --json prints for each kind of failure, snapshotted: the provider's against
+// a local wiremock server, and the ones that never get as far as a request
+#![cfg(feature = "gemini")]
+
+mod common;
+
+use common::TestRepo;
+use git_changes_rs::provider::GEMINI_MODEL;
+use serde_json::{json, Value};
+use std::process::Output;
+use tokio::process::Command;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn repo() -> TestRepo {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    test.write("notes.txt", "the project notes\n");
+    test.stage("notes.txt");
+    test
+}
+
+async fn server(response: ResponseTemplate) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/models/{}:generateContent", GEMINI_MODEL)))
+        .respond_with(response)
+        .mount(&server)
+        .await;
+    server
+}
+
+// generate --json in `test` with only `env` set
+async fn generate(test: &TestRepo, env: &[(&str, &str)]) -> Output {
+    let home = tempfile::tempdir().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_git-changes-rs"))
+        .env_clear()
+        .env("HOME", home.path())
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .envs(env.iter().copied())
+        .arg(test.path())
+        .arg("--json")
+        .output()
+        .await
+        .expect("failed to run git-changes-rs")
+}
+
+async fn against(test: &TestRepo, server: &MockServer) -> Output {
+    let uri = server.uri();
+    generate(
+        test,
+        &[("GEMINI_API_KEY", "test-key"), ("GEMINI_BASE_URL", &uri)],
+    )
+    .await
+}
+
+// the document, with `exit` checked and the paths and addresses that change between runs
+// replaced; stderr stays free of the error in this mode
+fn document(test: &TestRepo, output: &Output, exit: i32, server: Option<&MockServer>) -> String {
+    assert_eq!(output.status.code(), Some(exit), "{:?}", output);
+    assert!(
+        !String::from_utf8_lossy(&output.stderr).contains("Error:"),
+        "{:?}",
+        output
+    );
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let mut text = serde_json::to_string_pretty(&report).unwrap();
+    text = text.replace(test.path().to_str().unwrap(), "<repo>");
+    if let Some(server) = server {
+        text = text.replace(&server.uri(), "<server>");
+    }
+    text
+}
+
+#[tokio::test]
+async fn rate_limited() {
+    let test = repo();
+    let server = server(ResponseTemplate::new(429).insert_header("Retry-After", "21").set_body_json(
+        json!({"error": {"code": 429, "message": "Resource has been exhausted", "status": "RESOURCE_EXHAUSTED"}}),
+    ))
+    .await;
+    let output = against(&test, &server).await;
+    insta::assert_snapshot!(document(&test, &output, 4, Some(&server)));
+}
+
+#[tokio::test]
+async fn api_error() {
+    let test = repo();
+    let server = server(ResponseTemplate::new(400).set_body_json(json!({
+        "error": {"code": 400, "message": "API key not valid.", "status": "INVALID_ARGUMENT"}
+    })))
+    .await;
+    let output = against(&test, &server).await;
+    insta::assert_snapshot!(document(&test, &output, 4, Some(&server)));
+}
+
+#[tokio::test]
+async fn bad_response() {
+    let test = repo();
+    let server = server(ResponseTemplate::new(200).set_body_string("{\"candidates\": [")).await;
+    let output = against(&test, &server).await;
+    insta::assert_snapshot!(document(&test, &output, 4, Some(&server)));
+}
+
+#[tokio::test]
+async fn network() {
+    let test = repo();
+    // a port nothing listens on once the listener is gone
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let base = format!("http://127.0.0.1:{}", port);
+    let output = generate(
+        &test,
+        &[("GEMINI_API_KEY", "test-key"), ("GEMINI_BASE_URL", &base)],
+    )
+    .await;
+    let text = document(&test, &output, 4, None);
+    // the key is never part of the message, and what the OS says about the refusal varies
+    assert!(!text.contains("test-key"), "{}", text);
+    let cut = text
+        .find(": client error")
+        .expect("no client error in the message");
+    let end = text[cut..].find("\",\n").unwrap();
+    insta::assert_snapshot!(format!("{}{}", &text[..cut], &text[cut + end..]));
+}
+
+#[tokio::test]
+async fn missing_key() {
+    let test = repo();
+    let output = generate(&test, &[]).await;
+    insta::assert_snapshot!(document(&test, &output, 4, None));
+}
+
+#[tokio::test]
+async fn no_changes() {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    let output = generate(&test, &[("GEMINI_API_KEY", "test-key")]).await;
+    insta::assert_snapshot!(document(&test, &output, 2, None));
+}
+
+#[tokio::test]
+async fn invalid_config() {
+    let test = repo();
+    test.write(".git-changes.toml", "[message]\nstyle = 3\n");
+    let output = generate(&test, &[("GEMINI_API_KEY", "test-key")]).await;
+    insta::assert_snapshot!(document(&test, &output, 1, None));
+}
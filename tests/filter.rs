@@ -0,0 +1,199 @@
+// tests/filter.rs
+// properties of the exclude patterns over generated paths and patterns: which spelling of a
+// path or pattern is used doesn't change what is excluded, a directory's `/**` covers
+// everything under it, and names that aren't UTF-8 are matched by their bytes.
+// Counterexamples proptest has found are kept as plain tests at the end
+
+mod common;
+
+use git_changes_rs::filter::{is_excluded_file, is_excluded_path};
+use proptest::prelude::*;
+
+// short names from a small alphabet, so generated paths and patterns often meet
+fn component() -> impl Strategy<Value = String> {
+    "[abc]{1,3}(\\.rs)?"
+}
+
+fn path() -> impl Strategy<Value = Vec<String>> {
+    prop::collection::vec(component(), 1..5)
+}
+
+// a component as a pattern: itself, or with a wildcard in it
+fn pattern_component() -> impl Strategy<Value = String> {
+    prop_oneof![
+        component(),
+        Just("*".to_string()),
+        Just("**".to_string()),
+        Just("*.rs".to_string()),
+        "[abc]{1,2}\\*",
+        "[abc]\\?",
+    ]
+}
+
+fn pattern() -> impl Strategy<Value = Vec<String>> {
+    prop::collection::vec(pattern_component(), 1..5)
+}
+
+fn excluded(path: &str, pattern: &str) -> bool {
+    is_excluded_path(path, &[pattern.to_string()])
+}
+
+// `./` put in front of the segment at `at`
+fn with_dot(segments: &[String], at: usize) -> String {
+    let mut segments = segments.to_vec();
+    let at = at % segments.len();
+    segments.insert(at, ".".to_string());
+    segments.join("/")
+}
+
+proptest! {
+    #[test]
+    fn backslashes_and_slashes_agree(path in path(), pattern in pattern()) {
+        let unix = excluded(&path.join("/"), &pattern.join("/"));
+        prop_assert_eq!(unix, excluded(&path.join("\\"), &pattern.join("/")));
+        prop_assert_eq!(unix, excluded(&path.join("/"), &pattern.join("\\")));
+    }
+
+    #[test]
+    fn a_dot_segment_changes_nothing(
+        path in path(),
+        pattern in pattern(),
+        at_path in any::<usize>(),
+        at_pattern in any::<usize>(),
+    ) {
+        let plain = excluded(&path.join("/"), &pattern.join("/"));
+        prop_assert_eq!(plain, excluded(&with_dot(&path, at_path), &pattern.join("/")));
+        prop_assert_eq!(plain, excluded(&path.join("/"), &with_dot(&pattern, at_pattern)));
+    }
+
+    #[test]
+    fn a_directory_glob_covers_everything_under_it(dir in path(), rest in path()) {
+        let pattern = format!("{}/**", dir.join("/"));
+        let path = format!("{}/{}", dir.join("/"), rest.join("/"));
+        prop_assert!(excluded(&path, &pattern));
+    }
+
+    #[test]
+    fn a_path_is_its_own_pattern(path in path()) {
+        prop_assert!(excluded(&path.join("/"), &path.join("/")));
+    }
+
+    #[test]
+    fn more_patterns_never_exclude_less(
+        path in path(),
+        patterns in prop::collection::vec(pattern(), 0..4),
+        extra in pattern(),
+    ) {
+        let path = path.join("/");
+        let mut patterns: Vec<String> = patterns.iter().map(|p| p.join("/")).collect();
+        let before = is_excluded_path(&path, &patterns);
+        patterns.push(extra.join("/"));
+        prop_assert!(!before || is_excluded_path(&path, &patterns));
+    }
+}
+
+#[cfg(unix)]
+mod bytes {
+    use super::*;
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    fn file(name: &[u8]) -> &Path {
+        Path::new(OsStr::from_bytes(name))
+    }
+
+    fn excluded_file(name: &[u8], pattern: &str) -> bool {
+        is_excluded_file(file(name), &[pattern.to_string()])
+    }
+
+    proptest! {
+        #[test]
+        fn a_utf8_name_matches_as_its_text(path in path(), pattern in pattern()) {
+            let (path, pattern) = (path.join("/"), pattern.join("/"));
+            prop_assert_eq!(
+                excluded_file(path.as_bytes(), &pattern),
+                excluded(&path, &pattern)
+            );
+        }
+
+        #[test]
+        fn wildcards_match_any_bytes(
+            dir in "[abc]{1,3}",
+            name in prop::collection::vec(any::<u8>().prop_filter("no separator", |b| *b != b'/' && *b != b'\\'), 1..8),
+        ) {
+            let mut path = format!("{}/", dir).into_bytes();
+            path.extend(&name);
+            path.extend(b".log");
+            prop_assert!(excluded_file(&path, "*.log"));
+            let under_dir = format!("{}/**", dir);
+            prop_assert!(excluded_file(&path, &under_dir));
+            prop_assert!(!excluded_file(&path, "*.txt"));
+        }
+    }
+
+    #[test]
+    fn stray_bytes_are_not_the_replacement_character() {
+        assert!(!excluded_file(b"caf\xe9.txt", "caf\u{fffd}.txt"));
+        assert!(excluded_file(b"caf\xe9.txt", "caf?.txt"));
+        assert!(excluded_file(b"caf\xe9.txt", "caf*"));
+        assert!(!excluded_file(b"caf\xe9.txt", "cafe.txt"));
+        // the UTF-8 spelling of é is another name
+        assert!(!excluded_file(b"caf\xe9.txt", "caf\u{e9}.txt"));
+        assert!(excluded_file("caf\u{e9}.txt".as_bytes(), "caf\u{e9}.txt"));
+    }
+
+    // two names lossy decoding would make the same
+    #[test]
+    fn names_that_differ_in_stray_bytes_are_told_apart() {
+        let lossy_a = String::from_utf8_lossy(b"a\xff.rs").into_owned();
+        let lossy_b = String::from_utf8_lossy(b"a\xfe.rs").into_owned();
+        assert_eq!(lossy_a, lossy_b);
+        assert!(!excluded_file(b"a\xff.rs", &lossy_a));
+        assert!(!excluded_file(b"a\xfe.rs", &lossy_b));
+        assert!(excluded_file(b"a\xff.rs", "a?.rs"));
+    }
+
+    #[test]
+    fn a_non_utf8_file_in_a_repository_is_excluded_by_its_pattern() {
+        use git_changes_rs::diff::{collect_changes, CollectOptions, DiffMode};
+
+        let test = common::TestRepo::with_commit(&[("README.md", "# project\n")]);
+        let name = OsStr::from_bytes(b"caf\xe9.log");
+        std::fs::write(test.path().join(name), "noise\n").unwrap();
+        std::fs::write(test.path().join("notes.txt"), "notes\n").unwrap();
+        let mut index = test.repo.index().unwrap();
+        index.add_path(file(b"caf\xe9.log")).unwrap();
+        index.add_path(Path::new("notes.txt")).unwrap();
+        index.write().unwrap();
+
+        let options = CollectOptions::new(DiffMode::Staged).exclude(["*.log"]);
+        let changes = collect_changes(&test.repo, &options).unwrap();
+        assert_eq!(changes.files(), ["notes.txt"]);
+        assert_eq!(changes.excluded().len(), 1);
+
+        let options = CollectOptions::new(DiffMode::Staged).exclude(["caf\u{fffd}.log"]);
+        let changes = collect_changes(&test.repo, &options).unwrap();
+        assert_eq!(changes.files().len(), 2);
+        assert!(changes.excluded().is_empty());
+    }
+}
+
+fn patterns(patterns: &[&str]) -> Vec<String> {
+    patterns.iter().map(|p| p.to_string()).collect()
+}
+
+#[test]
+fn a_leading_dot_segment_in_a_path_is_dropped() {
+    assert!(is_excluded_path("./a.rs", &patterns(&["*"])));
+}
+
+#[test]
+fn a_leading_dot_segment_in_a_pattern_is_dropped() {
+    assert!(is_excluded_path("a.rs", &patterns(&["./*"])));
+    assert!(is_excluded_path(
+        "target/debug/app",
+        &patterns(&["./target/**"])
+    ));
+    assert!(is_excluded_path("src/a.rs", &patterns(&["src/./*.rs"])));
+}
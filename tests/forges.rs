@@ -0,0 +1,362 @@
+// tests/forges.rs
+// pr --github and pr --gitlab against a local wiremock server that stands in for both the model
+// and the forge: the request each makes, creating against updating, and the errors they reword
+#![cfg(all(feature = "gemini", feature = "github", feature = "gitlab"))]
+
+mod common;
+
+use common::TestRepo;
+use git_changes_rs::provider::GEMINI_MODEL;
+use serde_json::{json, Value};
+use std::process::Output;
+use tokio::process::Command;
+use wiremock::matchers::{body_json, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const DESCRIPTION: &str = "Add a notes file\n\n## Summary\n\nStarts the project notes.";
+
+// a branch `feature`, pushed, one commit ahead of origin/main, with origin at `url`
+fn branch(url: &str) -> TestRepo {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    let base = test.repo.head().unwrap().target().unwrap();
+    let repo = &test.repo;
+    repo.reference("refs/remotes/origin/main", base, true, "test")
+        .unwrap();
+    repo.reference_symbolic(
+        "refs/remotes/origin/HEAD",
+        "refs/remotes/origin/main",
+        true,
+        "test",
+    )
+    .unwrap();
+    repo.remote("origin", url).unwrap();
+    repo.branch("feature", &repo.find_commit(base).unwrap(), true)
+        .unwrap();
+    repo.set_head("refs/heads/feature").unwrap();
+    test.write("notes.txt", "the project notes\n");
+    test.stage("notes.txt");
+    let head = test.commit("feat: add a notes file");
+    repo.reference("refs/remotes/origin/feature", head, true, "test")
+        .unwrap();
+    test
+}
+
+// a server writing DESCRIPTION for the pull request; the forge's routes are mounted on it too
+async fn server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/models/{}:generateContent", GEMINI_MODEL)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [{"content": {"parts": [{"text": DESCRIPTION}], "role": "model"}}]
+        })))
+        .mount(&server)
+        .await;
+    server
+}
+
+// `pr` in `test` with `args`; the tokio Command keeps the runtime, and so the server, running
+async fn pr(test: &TestRepo, server: &MockServer, args: &[&str], env: &[(&str, &str)]) -> Output {
+    let home = tempfile::tempdir().unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_git-changes-rs"));
+    command
+        .env_clear()
+        .env("HOME", home.path())
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GEMINI_API_KEY", "test-key")
+        .env("GEMINI_BASE_URL", server.uri())
+        .env("GITHUB_API_URL", server.uri())
+        .args(["pr", "--repo"])
+        .arg(test.path())
+        .args(args)
+        .envs(env.iter().copied());
+    command
+        .output()
+        .await
+        .expect("failed to run git-changes-rs")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+async fn github_pulls(server: &MockServer, open: Value) {
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/project/pulls"))
+        .and(query_param("head", "owner:feature"))
+        .and(query_param("state", "open"))
+        .and(header("authorization", "Bearer gh-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(open))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+const GITHUB: &str = "git@github.com:owner/project.git";
+const GITHUB_TOKEN: (&str, &str) = ("GITHUB_TOKEN", "gh-token");
+
+#[tokio::test]
+async fn github_creates_a_pull_request() {
+    let test = branch(GITHUB);
+    let server = server().await;
+    github_pulls(&server, json!([])).await;
+    Mock::given(method("POST"))
+        .and(path("/repos/owner/project/pulls"))
+        .and(header("authorization", "Bearer gh-token"))
+        .and(header("accept", "application/vnd.github+json"))
+        .and(body_json(json!({
+            "title": "Add a notes file",
+            "body": "## Summary\n\nStarts the project notes.",
+            "head": "feature",
+            "base": "main",
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "number": 7,
+            "html_url": "https://github.com/owner/project/pull/7",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let output = pr(&test, &server, &["--github"], &[GITHUB_TOKEN]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "created #7: https://github.com/owner/project/pull/7\n"
+    );
+}
+
+#[tokio::test]
+async fn github_updates_the_open_pull_request() {
+    let test = branch(GITHUB);
+    let server = server().await;
+    github_pulls(
+        &server,
+        json!([{"number": 3, "html_url": "https://github.com/owner/project/pull/3"}]),
+    )
+    .await;
+    Mock::given(method("PATCH"))
+        .and(path("/repos/owner/project/pulls/3"))
+        .and(header("authorization", "Bearer gh-token"))
+        .and(body_json(json!({
+            "title": "Add a notes file",
+            "body": "## Summary\n\nStarts the project notes.",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "number": 3,
+            "html_url": "https://github.com/owner/project/pull/3",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let output = pr(&test, &server, &["--github"], &[GITHUB_TOKEN]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "updated #3: https://github.com/owner/project/pull/3\n"
+    );
+}
+
+#[tokio::test]
+async fn github_401_says_the_token_was_rejected() {
+    let test = branch(GITHUB);
+    let server = server().await;
+    Mock::given(method("GET"))
+        .and(path("/repos/owner/project/pulls"))
+        .respond_with(
+            ResponseTemplate::new(401).set_body_string("{\"message\":\"Bad credentials\"}"),
+        )
+        .mount(&server)
+        .await;
+
+    let output = pr(&test, &server, &["--github"], &[GITHUB_TOKEN]).await;
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("GitHub rejected GITHUB_TOKEN (401); check that it is valid"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test]
+async fn github_422_says_what_to_push() {
+    let cases = [
+        (
+            r#"{"message":"Validation Failed","errors":[{"message":"No commits between main and feature"}]}"#,
+            "GitHub sees no commits between the base and this branch; push your commits first",
+        ),
+        (
+            r#"{"message":"Validation Failed","errors":[{"resource":"PullRequest","field":"head","code":"invalid"}]}"#,
+            "GitHub doesn't know this branch; push it first (git push -u origin <branch>)",
+        ),
+    ];
+    for (body, expected) in cases {
+        let test = branch(GITHUB);
+        let server = server().await;
+        github_pulls(&server, json!([])).await;
+        Mock::given(method("POST"))
+            .and(path("/repos/owner/project/pulls"))
+            .respond_with(ResponseTemplate::new(422).set_body_string(body))
+            .mount(&server)
+            .await;
+
+        let output = pr(&test, &server, &["--github"], &[GITHUB_TOKEN]).await;
+        assert!(!output.status.success());
+        assert!(stderr(&output).contains(expected), "{}", stderr(&output));
+    }
+}
+
+#[tokio::test]
+async fn github_needs_a_token_unless_it_is_a_dry_run() {
+    let test = branch(GITHUB);
+    let server = server().await;
+
+    let output = pr(&test, &server, &["--github"], &[]).await;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("--github needs GITHUB_TOKEN to be set"));
+
+    let output = pr(&test, &server, &["--github", "--dry-run"], &[]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with(&format!(
+        "would POST {}/repos/owner/project/pulls\n",
+        server.uri()
+    )));
+}
+
+const GITLAB: &str = "git@gitlab.example.com:group/sub/project.git";
+const GITLAB_TOKEN: (&str, &str) = ("GITLAB_TOKEN", "gl-token");
+const MERGE_REQUESTS: &str = "/api/v4/projects/group%2Fsub%2Fproject/merge_requests";
+
+async fn gitlab_open(server: &MockServer, open: Value) {
+    Mock::given(method("GET"))
+        .and(path(MERGE_REQUESTS))
+        .and(query_param("source_branch", "feature"))
+        .and(query_param("state", "opened"))
+        .and(header("private-token", "gl-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(open))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+async fn gitlab(test: &TestRepo, server: &MockServer, args: &[&str]) -> Output {
+    let uri = server.uri();
+    let args: Vec<&str> = ["--gitlab", "--gitlab-url", uri.as_str()]
+        .into_iter()
+        .chain(args.iter().copied())
+        .collect();
+    pr(test, server, &args, &[GITLAB_TOKEN]).await
+}
+
+#[tokio::test]
+async fn gitlab_creates_a_draft_merge_request() {
+    let test = branch(GITLAB);
+    let server = server().await;
+    gitlab_open(&server, json!([])).await;
+    Mock::given(method("POST"))
+        .and(path(MERGE_REQUESTS))
+        .and(header("private-token", "gl-token"))
+        .and(body_json(json!({
+            "title": "Draft: Add a notes file",
+            "description": "## Summary\n\nStarts the project notes.",
+            "source_branch": "feature",
+            "target_branch": "main",
+        })))
+        .respond_with(ResponseTemplate::new(201).set_body_json(json!({
+            "iid": 12,
+            "web_url": "https://gitlab.example.com/group/sub/project/-/merge_requests/12",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let output = gitlab(&test, &server, &["--draft"]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "created !12: https://gitlab.example.com/group/sub/project/-/merge_requests/12\n"
+    );
+}
+
+#[tokio::test]
+async fn gitlab_updates_the_open_merge_request() {
+    let test = branch(GITLAB);
+    let server = server().await;
+    gitlab_open(
+        &server,
+        json!([{"iid": 4, "web_url": "https://gitlab.example.com/mr/4"}]),
+    )
+    .await;
+    Mock::given(method("PUT"))
+        .and(path(format!("{}/4", MERGE_REQUESTS)))
+        .and(header("private-token", "gl-token"))
+        .and(body_json(json!({
+            "title": "Add a notes file",
+            "description": "## Summary\n\nStarts the project notes.",
+        })))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "iid": 4,
+            "web_url": "https://gitlab.example.com/mr/4",
+        })))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let output = gitlab(&test, &server, &[]).await;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "updated !4: https://gitlab.example.com/mr/4\n"
+    );
+}
+
+#[tokio::test]
+async fn gitlab_401_says_the_token_was_rejected() {
+    let test = branch(GITLAB);
+    let server = server().await;
+    Mock::given(method("GET"))
+        .and(path(MERGE_REQUESTS))
+        .respond_with(
+            ResponseTemplate::new(401).set_body_string("{\"message\":\"401 Unauthorized\"}"),
+        )
+        .mount(&server)
+        .await;
+
+    let output = gitlab(&test, &server, &[]).await;
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output).contains("GitLab rejected GITLAB_TOKEN (401); check that it is valid"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[tokio::test]
+async fn gitlab_422_about_the_source_branch_says_to_push_it() {
+    let test = branch(GITLAB);
+    let server = server().await;
+    gitlab_open(&server, json!([])).await;
+    Mock::given(method("POST"))
+        .and(path(MERGE_REQUESTS))
+        .respond_with(
+            ResponseTemplate::new(422)
+                .set_body_string(r#"{"message":["Source branch does not exist"]}"#),
+        )
+        .mount(&server)
+        .await;
+
+    let output = gitlab(&test, &server, &[]).await;
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("GitLab doesn't accept this branch as a source")
+            && stderr.contains("push it first (git push -u origin <branch>)"),
+        "{}",
+        stderr
+    );
+}
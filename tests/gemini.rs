@@ -0,0 +1,568 @@
+// tests/gemini.rs
+// the Gemini client against a local wiremock server: what a request carries, how each kind of
+// answer is read, and how failures are classified
+#![cfg(feature = "gemini")]
+
+use git_changes_rs::error::ProviderFailure;
+use git_changes_rs::message::{self, FormatOptions, Rules};
+use git_changes_rs::prompt::{build_system_prompt, Prompt, PromptOptions};
+use git_changes_rs::provider::{Gemini, GenerationOptions, Provider, Sampling, GEMINI_MODEL};
+use git_changes_rs::GitChangesError;
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::sync::Once;
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+const DIFF: &str = "diff --git a/notes.txt b/notes.txt\n+a new line\n";
+
+// set once, before any request reads it
+fn api_key() {
+    static KEY: Once = Once::new();
+    KEY.call_once(|| std::env::set_var("GEMINI_API_KEY", "test-key"));
+}
+
+fn prompt() -> Prompt {
+    Prompt {
+        system: "Write a conventional commit message.".to_string(),
+        user: format!("```diff\n{}```", DIFF),
+        max_output_tokens: 256,
+        title_only: false,
+    }
+}
+
+fn answer(texts: &[&str]) -> Value {
+    let candidates: Vec<Value> = texts
+        .iter()
+        .map(|text| json!({"content": {"parts": [{"text": text}], "role": "model"}}))
+        .collect();
+    json!({
+        "candidates": candidates,
+        "usageMetadata": {"promptTokenCount": 40, "candidatesTokenCount": 12}
+    })
+}
+
+// a server answering every generateContent request with `response`
+async fn server(response: ResponseTemplate) -> MockServer {
+    api_key();
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/models/{}:generateContent", GEMINI_MODEL)))
+        .and(query_param("key", "test-key"))
+        .respond_with(response)
+        .mount(&server)
+        .await;
+    server
+}
+
+fn gemini(server: &MockServer) -> Gemini {
+    Gemini::new(Sampling::default()).with_base_url(server.uri())
+}
+
+async fn failure(response: ResponseTemplate) -> GitChangesError {
+    let server = server(response).await;
+    gemini(&server)
+        .candidates(&prompt(), 1)
+        .await
+        .expect_err("the request should have failed")
+}
+
+fn body(request: &Request) -> Value {
+    serde_json::from_slice(&request.body).expect("the request body is json")
+}
+
+#[tokio::test]
+async fn a_candidate_is_read_and_trimmed() {
+    let server = server(ResponseTemplate::new(200).set_body_json(answer(&[
+        "  feat(notes): add a line\n\n- add a line to the notes\n",
+    ])))
+    .await;
+
+    let answers = gemini(&server).candidates(&prompt(), 1).await.unwrap();
+    assert_eq!(
+        answers,
+        ["feat(notes): add a line\n\n- add a line to the notes"]
+    );
+}
+
+#[tokio::test]
+async fn fields_and_values_the_client_doesnt_know_are_ignored() {
+    let server = server(ResponseTemplate::new(200).set_body_json(json!({
+        "candidates": [{
+            "content": {
+                "parts": [
+                    {"text": "feat(notes): add a line", "thoughtSignature": "c2ln"},
+                    {"inlineData": {"mimeType": "text/plain", "data": "eA=="}}
+                ],
+                "role": "model"
+            },
+            "finishReason": "A_REASON_FROM_THE_FUTURE",
+            "index": 0,
+            "avgLogprobs": -0.25,
+            "citationMetadata": {"citationSources": []},
+            "safetyRatings": [
+                {"category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE", "probabilityScore": 0.01}
+            ]
+        }],
+        "promptFeedback": {"safetyRatings": [], "tokenCount": 40},
+        "usageMetadata": {
+            "promptTokenCount": 40,
+            "candidatesTokenCount": 12,
+            "totalTokenCount": 52,
+            "promptTokensDetails": [{"modality": "TEXT", "tokenCount": 40}]
+        },
+        "modelVersion": "gemini-2.5-flash",
+        "responseId": "abc123"
+    })))
+    .await;
+
+    let answers = gemini(&server).candidates(&prompt(), 1).await.unwrap();
+    assert_eq!(answers, ["feat(notes): add a line"]);
+}
+
+#[tokio::test]
+async fn the_request_carries_the_prompts_and_the_generation_config() {
+    let server =
+        server(ResponseTemplate::new(200).set_body_json(answer(&["feat: a", "fix: b"]))).await;
+    let sampling = Sampling::deterministic();
+
+    let answers = Gemini::new(sampling)
+        .with_base_url(server.uri())
+        .candidates(&prompt(), 2)
+        .await
+        .unwrap();
+    assert_eq!(answers, ["feat: a", "fix: b"]);
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    let body = body(&requests[0]);
+    assert_eq!(
+        body["systemInstruction"]["parts"][0]["text"],
+        "Write a conventional commit message."
+    );
+    assert_eq!(body["contents"][0]["role"], "user");
+    let user = body["contents"][0]["parts"][0]["text"].as_str().unwrap();
+    assert!(user.contains(DIFF));
+
+    let config = &body["generationConfig"];
+    assert_eq!(config["temperature"], json!(sampling.temperature));
+    assert_eq!(config["topP"], json!(sampling.top_p));
+    assert_eq!(config["maxOutputTokens"], 256);
+    assert_eq!(config["candidateCount"], 2);
+    assert!(config["seed"].is_u64());
+}
+
+#[tokio::test]
+async fn the_language_instruction_reaches_the_system_instruction() {
+    let server = server(ResponseTemplate::new(200).set_body_json(answer(&["feat: a"]))).await;
+    let system = |language: &str| {
+        let mut options = PromptOptions::default();
+        options.language = Some(language.to_string());
+        build_system_prompt(&options)
+    };
+
+    for language in ["pt-BR", "en"] {
+        let prompt = Prompt {
+            system: system(language),
+            ..prompt()
+        };
+        gemini(&server).candidates(&prompt, 1).await.unwrap();
+    }
+
+    let requests = server.received_requests().await.unwrap();
+    let sent = |n: usize| {
+        body(&requests[n])["systemInstruction"]["parts"][0]["text"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(sent(0), system("pt-BR"));
+    assert!(sent(0).contains("Write the commit title and body in pt-BR."));
+    assert!(sent(0).contains("type and scope tokens"));
+    assert!(!sent(1).contains("Write the commit title and body in"));
+    assert_eq!(sent(1), build_system_prompt(&PromptOptions::default()));
+}
+
+#[tokio::test]
+async fn the_default_options_send_the_payload_they_always_have() {
+    let server = server(ResponseTemplate::new(200).set_body_json(answer(&["feat: a"]))).await;
+
+    gemini(&server).candidates(&prompt(), 1).await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(
+        body(&requests[0]),
+        json!({
+            "contents": [{"role": "user", "parts": [{"text": format!("```diff\n{}```", DIFF)}]}],
+            "systemInstruction": {"parts": [{"text": "Write a conventional commit message."}]},
+            "generationConfig": {
+                "temperature": 0.7,
+                "topP": 1.0,
+                "maxOutputTokens": 256,
+                "responseMimeType": "text/plain"
+            },
+            "safetySettings": [
+                {"category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "BLOCK_NONE"}
+            ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn generation_options_are_translated_and_unsupported_ones_left_out() {
+    let server = server(ResponseTemplate::new(200).set_body_json(answer(&["feat: a"]))).await;
+    let options = GenerationOptions::default()
+        .temperature(0.2)
+        .top_k(20)
+        .seed(7)
+        .max_output_tokens(1000)
+        .response_mime_type("text/markdown")
+        .safety_setting("HARM_CATEGORY_CIVIC_INTEGRITY", "BLOCK_ONLY_HIGH")
+        .safety_setting("HARM_CATEGORY_HARASSMENT", "BLOCK_NONE")
+        .thinking_budget(0);
+
+    Gemini::new(Sampling::default())
+        .with_options(options)
+        .with_base_url(server.uri())
+        .candidates(&prompt(), 1)
+        .await
+        .unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    let body = body(&requests[0]);
+    // the prompt's cap wins over the options' own
+    assert_eq!(
+        body["generationConfig"],
+        json!({"temperature": 0.2, "topP": 1.0, "topK": 20, "seed": 7, "maxOutputTokens": 256})
+    );
+    assert_eq!(
+        body["safetySettings"],
+        json!([
+            {"category": "HARM_CATEGORY_CIVIC_INTEGRITY", "threshold": "BLOCK_ONLY_HIGH"},
+            {"category": "HARM_CATEGORY_HARASSMENT", "threshold": "BLOCK_NONE"}
+        ])
+    );
+}
+
+// the shapes of an answer the model put nothing in
+fn empty_answers() -> [Value; 6] {
+    [
+        json!({"candidates": []}),
+        json!({"usageMetadata": {"promptTokenCount": 40}}),
+        json!({"candidates": [{"finishReason": "STOP"}]}),
+        json!({"candidates": [{"content": {"role": "model"}, "finishReason": "STOP"}]}),
+        json!({"candidates": [{"content": {"parts": [], "role": "model"}}]}),
+        json!({"candidates": [{"content": {"parts": [{"text": " \n"}], "role": "model"}}]}),
+    ]
+}
+
+#[tokio::test]
+async fn an_answer_with_no_text_is_empty_not_unparsable() {
+    for answer in empty_answers() {
+        let error = failure(ResponseTemplate::new(200).set_body_json(&answer)).await;
+        assert!(
+            matches!(
+                error,
+                GitChangesError::Provider {
+                    failure: ProviderFailure::Empty,
+                    provider: "gemini",
+                    ..
+                }
+            ),
+            "{}: {:?}",
+            answer,
+            error
+        );
+        assert!(error.to_string().contains("no text"), "{}", error);
+        assert!(!error.is_retryable());
+    }
+}
+
+#[tokio::test]
+async fn an_empty_answer_is_asked_for_again_with_a_nudge_and_a_hotter_draw() {
+    api_key();
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({"candidates": []})))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(answer(&["feat(notes): add a line"])),
+        )
+        .with_priority(2)
+        .mount(&server)
+        .await;
+
+    let message = message::generate(&gemini(&server), &prompt(), &FormatOptions::default(), None)
+        .await
+        .unwrap();
+    assert_eq!(message, "feat(notes): add a line");
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let (first, retry) = (body(&requests[0]), body(&requests[1]));
+    let user = retry["contents"][0]["parts"][0]["text"].as_str().unwrap();
+    assert!(user.contains(DIFF));
+    assert!(user.ends_with("Respond with only the commit message."));
+    assert_eq!(first["generationConfig"]["temperature"], 0.7);
+    let hotter = retry["generationConfig"]["temperature"].as_f64().unwrap();
+    assert!((hotter - 0.9).abs() < 1e-9, "{}", hotter);
+}
+
+#[tokio::test]
+async fn an_answer_that_stays_empty_says_the_model_returned_nothing() {
+    let server = server(ResponseTemplate::new(200).set_body_json(json!({
+        "candidates": [{"content": {"role": "model"}, "finishReason": "STOP"}]
+    })))
+    .await;
+
+    let error = message::generate(&gemini(&server), &prompt(), &FormatOptions::default(), None)
+        .await
+        .expect_err("every answer is empty");
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::Empty,
+            ..
+        }
+    ));
+    assert_eq!(
+        error.to_string(),
+        "gemini returned no message, even when asked again"
+    );
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn a_safety_block_is_a_bad_response_and_not_asked_for_again() {
+    let blocked = json!({
+        "candidates": [{"finishReason": "SAFETY", "safetyRatings": [
+            {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true}
+        ]}],
+        "promptFeedback": {"blockReason": "SAFETY"}
+    });
+    let server = server(ResponseTemplate::new(200).set_body_json(blocked)).await;
+    let error = message::generate(&gemini(&server), &prompt(), &FormatOptions::default(), None)
+        .await
+        .expect_err("the answer was blocked");
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::BadResponse,
+            ..
+        }
+    ));
+    assert!(error.to_string().contains("blocked the answer (SAFETY)"));
+    assert_eq!(server.received_requests().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn a_blocked_candidate_names_the_reason_and_the_blocked_categories() {
+    let error = failure(ResponseTemplate::new(200).set_body_json(json!({
+        "candidates": [{"finishReason": "PROHIBITED_CONTENT", "safetyRatings": [
+            {"category": "HARM_CATEGORY_HARASSMENT", "probability": "LOW"},
+            {"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "HIGH", "blocked": true}
+        ]}]
+    })))
+    .await;
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::BadResponse,
+            ..
+        }
+    ));
+    assert_eq!(
+        error.to_string(),
+        "gemini blocked the answer (PROHIBITED_CONTENT: HARM_CATEGORY_DANGEROUS_CONTENT)"
+    );
+}
+
+#[tokio::test]
+async fn running_out_of_tokens_before_any_text_is_an_empty_answer_that_says_so() {
+    let error = failure(ResponseTemplate::new(200).set_body_json(json!({
+        "candidates": [{"content": {"role": "model"}, "finishReason": "MAX_TOKENS"}],
+        "usageMetadata": {"promptTokenCount": 40, "thoughtsTokenCount": 256}
+    })))
+    .await;
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::Empty,
+            ..
+        }
+    ));
+    assert!(
+        error.to_string().contains("output token limit"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn status_429_is_rate_limiting_with_the_wait_from_retry_after() {
+    let error = failure(
+        ResponseTemplate::new(429)
+            .insert_header("Retry-After", "7")
+            .set_body_json(json!({"error": {
+                "code": 429,
+                "message": "Resource has been exhausted",
+                "status": "RESOURCE_EXHAUSTED"
+            }})),
+    )
+    .await;
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::RateLimited,
+            status: Some(429),
+            ..
+        }
+    ));
+    assert_eq!(error.retry_after_secs(), Some(7));
+    assert!(error.is_retryable());
+    assert!(error.to_string().contains("Resource has been exhausted"));
+}
+
+#[tokio::test]
+async fn the_wait_can_come_from_the_error_envelope() {
+    let error = failure(ResponseTemplate::new(429).set_body_json(json!({"error": {
+        "code": 429,
+        "message": "quota exceeded",
+        "status": "RESOURCE_EXHAUSTED",
+        "details": [{"@type": "type.googleapis.com/google.rpc.RetryInfo", "retryDelay": "20.5s"}]
+    }})))
+    .await;
+    assert_eq!(error.retry_after_secs(), Some(21));
+}
+
+#[tokio::test]
+async fn status_500_is_a_retryable_api_error() {
+    let error = failure(ResponseTemplate::new(500).set_body_string("internal error")).await;
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::Api,
+            status: Some(500),
+            retry_after_secs: None,
+            ..
+        }
+    ));
+    assert!(error.is_retryable());
+}
+
+#[tokio::test]
+async fn status_400_is_an_api_error_to_not_retry() {
+    let error = failure(ResponseTemplate::new(400).set_body_json(json!({"error": {
+        "code": 400,
+        "message": "API key not valid",
+        "status": "INVALID_ARGUMENT"
+    }})))
+    .await;
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::Api,
+            status: Some(400),
+            ..
+        }
+    ));
+    assert!(!error.is_retryable());
+}
+
+#[tokio::test]
+async fn malformed_json_is_a_bad_response() {
+    let error = failure(ResponseTemplate::new(200).set_body_string("{\"candidates\": [")).await;
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::BadResponse,
+            ..
+        }
+    ));
+    assert!(error.to_string().contains("failed to parse json response"));
+}
+
+#[tokio::test]
+async fn no_server_is_a_retryable_network_failure() {
+    api_key();
+    // a port that was free a moment ago; wiremock's own servers go back to a pool when dropped
+    let port = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .unwrap()
+        .port();
+
+    let error = Gemini::new(Sampling::default())
+        .with_base_url(format!("http://127.0.0.1:{}", port))
+        .candidates(&prompt(), 1)
+        .await
+        .expect_err("nothing is listening");
+    assert!(matches!(
+        error,
+        GitChangesError::Provider {
+            failure: ProviderFailure::Network,
+            ..
+        }
+    ));
+    assert!(error.is_retryable());
+}
+
+#[tokio::test]
+async fn a_message_that_breaks_the_rules_is_asked_for_again() {
+    api_key();
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(answer(&["updated some files"])))
+        .up_to_n_times(1)
+        .with_priority(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(
+            ResponseTemplate::new(200).set_body_json(answer(&["feat(notes): add a line"])),
+        )
+        .with_priority(2)
+        .mount(&server)
+        .await;
+
+    let message = message::generate(
+        &gemini(&server),
+        &prompt(),
+        &FormatOptions::default(),
+        Some(&Rules::default()),
+    )
+    .await
+    .unwrap();
+    assert_eq!(message, "feat(notes): add a line");
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 2);
+    let retry = body(&requests[1]);
+    let user = retry["contents"][0]["parts"][0]["text"].as_str().unwrap();
+    assert!(user.contains(DIFF));
+    assert!(user.contains("Your previous answer was rejected"));
+    assert!(user.contains("updated some files"));
+}
+
+#[tokio::test]
+async fn a_message_that_keeps_breaking_the_rules_fails_validation() {
+    let server =
+        server(ResponseTemplate::new(200).set_body_json(answer(&["updated some files"]))).await;
+
+    let error = message::generate(
+        &gemini(&server),
+        &prompt(),
+        &FormatOptions::default(),
+        Some(&Rules::default()),
+    )
+    .await
+    .expect_err("every answer breaks the rules");
+    assert!(matches!(
+        error,
+        GitChangesError::Validation { attempts: 3, .. }
+    ));
+    assert_eq!(server.received_requests().await.unwrap().len(), 3);
+}
@@ -0,0 +1,104 @@
+// tests/interrupt.rs
+// Ctrl-C during a request that never gets an answer: the binary drops it and ends with 130
+// instead of waiting, and nothing is committed
+#![cfg(all(unix, feature = "gemini"))]
+
+mod common;
+
+use common::TestRepo;
+use git_changes_rs::provider::GEMINI_MODEL;
+use serde_json::Value;
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tokio::process::Command;
+use tokio::time::{sleep, timeout};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+// a server that takes the request and never answers it, as far as a test can tell
+async fn server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/models/{}:generateContent", GEMINI_MODEL)))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(600)))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn repo() -> TestRepo {
+    let test = TestRepo::with_commit(&[("README.md", "# project\n")]);
+    let mut config = test.repo.config().unwrap();
+    config.set_str("user.name", "Test").unwrap();
+    config.set_str("user.email", "test@example.com").unwrap();
+    test.write("notes.txt", "the project notes\n");
+    test.stage("notes.txt");
+    test
+}
+
+// generate in `test` with `args`, sent SIGINT once the request has reached `server`
+async fn interrupted(test: &TestRepo, server: &MockServer, args: &[&str]) -> Output {
+    let home = tempfile::tempdir().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_git-changes-rs"))
+        .env_clear()
+        .env("HOME", home.path())
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GEMINI_API_KEY", "test-key")
+        .env("GEMINI_BASE_URL", server.uri())
+        .arg(test.path())
+        .arg("--yes")
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run git-changes-rs");
+
+    timeout(Duration::from_secs(30), async {
+        while server.received_requests().await.unwrap_or_default().is_empty() {
+            assert!(child.try_wait().unwrap().is_none(), "the run ended first");
+            sleep(Duration::from_millis(20)).await;
+        }
+    })
+    .await
+    .expect("the request never reached the server");
+    let status = std::process::Command::new("kill")
+        .args(["-INT", &child.id().unwrap().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    timeout(Duration::from_secs(30), child.wait_with_output())
+        .await
+        .expect("the run didn't end after Ctrl-C")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn ctrl_c_drops_the_request_and_ends_with_130() {
+    let test = repo();
+    let head = test.repo.head().unwrap().target().unwrap();
+    let server = server().await;
+
+    let output = interrupted(&test, &server, &["--commit"]).await;
+    assert_eq!(output.status.code(), Some(130), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).lines().last(),
+        Some("Error: interrupted")
+    );
+    assert!(output.stdout.is_empty());
+    assert_eq!(test.repo.head().unwrap().target().unwrap(), head);
+}
+
+#[tokio::test]
+async fn ctrl_c_with_json_reports_interrupted() {
+    let test = repo();
+    let server = server().await;
+
+    let output = interrupted(&test, &server, &["--json"]).await;
+    assert_eq!(output.status.code(), Some(130), "{:?}", output);
+    let report: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["error"]["kind"], "interrupted");
+    assert_eq!(report["code"], "interrupted");
+}
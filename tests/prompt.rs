@@ -0,0 +1,102 @@
+// tests/prompt.rs
+// snapshots of the prompts built for one fixed set of changes, so that any change to what the
+// model is sent (lost +/- markers, a section moved, different truncation) shows up as a
+// snapshot diff to review; `cargo insta review` accepts the intended ones
+
+mod common;
+
+use common::TestRepo;
+use git_changes_rs::diff::{collect_changes, ChangeSet, CollectOptions, DiffMode};
+use git_changes_rs::prompt::{build_prompt, Attachment, Prompt, PromptOptions};
+
+// a modified file, a rename, a new binary and an excluded lockfile, all staged
+fn changes() -> ChangeSet {
+    let test = TestRepo::with_commit(&[
+        (
+            "src/lib.rs",
+            "pub fn greet() -> &'static str {\n    \"hello\"\n}\n",
+        ),
+        ("docs/usage.txt", "run it with no arguments\n"),
+        ("Cargo.lock", "version = 3\n"),
+    ]);
+    test.write(
+        "src/lib.rs",
+        "pub fn greet(name: &str) -> String {\n    format!(\"hello, {}\", name)\n}\n",
+    );
+    test.rename("docs/usage.txt", "docs/running.txt");
+    test.write(
+        "assets/logo.png",
+        [0x89, b'P', b'N', b'G', 0, 0, 0, 13, 0, 1],
+    );
+    test.write("Cargo.lock", "version = 4\n");
+    test.stage_all();
+
+    let options = CollectOptions::new(DiffMode::Staged).exclude(["Cargo.lock"]);
+    collect_changes(&test.repo, &options).unwrap()
+}
+
+fn render(prompt: &Prompt) -> String {
+    format!(
+        "max_output_tokens: {}\ntitle_only: {}\n--- system\n{}\n--- user\n{}\n",
+        prompt.max_output_tokens, prompt.title_only, prompt.system, prompt.user
+    )
+}
+
+#[test]
+fn the_default_prompt() {
+    let changes = changes();
+    assert_eq!(changes.excluded(), ["Cargo.lock"]);
+    insta::assert_snapshot!(render(&build_prompt(&changes, &PromptOptions::default())));
+}
+
+#[test]
+fn a_subject_line_only_prompt() {
+    let mut options = PromptOptions::default();
+    options.title_only = true;
+    insta::assert_snapshot!(render(&build_prompt(&changes(), &options)));
+}
+
+#[test]
+fn a_prompt_with_every_kind_of_context() {
+    let mut options = PromptOptions::default();
+    options.language = Some("pt-BR".to_string());
+    options.per_file_bullets = true;
+    options.types = vec!["feat".to_string(), "fix".to_string()];
+    options.commit_type = Some("fix".to_string());
+    options.scope = Some("greet".to_string());
+    options.api_changes = vec!["src/lib.rs: changed `fn greet`".to_string()];
+    options.api_breaking = true;
+    options.tree = Some("assets/\ndocs/\nsrc/\nCargo.lock".to_string());
+    options.related_commit =
+        Some("These changes partly undo commit 1234567 (feat: greet).".to_string());
+    options.attachments = vec![Attachment::from_text(
+        "test-output.txt",
+        "running 1 test\ntest greets ... ok\n",
+        1024,
+    )];
+    insta::assert_snapshot!(render(&build_prompt(&changes(), &options)));
+}
+
+#[test]
+fn a_long_attachment_is_truncated_in_the_middle() {
+    let log: String = (1..=40)
+        .map(|n| format!("line {:02} of the build log\n", n))
+        .collect();
+    let attachment = Attachment::from_text("build.log", &log, 200);
+    assert!(attachment.truncated());
+
+    let mut options = PromptOptions::default();
+    options.attachments = vec![attachment];
+    let prompt = build_prompt(&changes(), &options);
+    let material = &prompt.user[prompt.user.find("Supporting material").unwrap()..];
+    insta::assert_snapshot!(material);
+}
+
+#[test]
+fn the_same_inputs_give_the_same_prompt() {
+    let options = PromptOptions::default();
+    assert_eq!(
+        build_prompt(&changes(), &options),
+        build_prompt(&changes(), &options)
+    );
+}
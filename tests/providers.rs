@@ -0,0 +1,77 @@
+// tests/providers.rs
+// the provider registry: which providers a build knows and which it was compiled with
+
+mod common;
+
+use git_changes_rs::provider;
+use git_changes_rs::GitChangesError;
+
+#[test]
+fn an_unknown_provider_is_a_config_error() {
+    let error = provider::check_provider("nonesuch").expect_err("there is no such provider");
+    assert!(matches!(error, GitChangesError::Config { .. }));
+    assert!(error.to_string().contains("available: gemini"));
+}
+
+#[test]
+fn the_enabled_providers_follow_the_features() {
+    assert_eq!(
+        provider::enabled_providers().contains(&"gemini"),
+        cfg!(feature = "gemini")
+    );
+    for name in provider::PROVIDERS {
+        let enabled = provider::enabled_providers().contains(name);
+        assert_eq!(provider::check_provider(name).is_ok(), enabled, "{}", name);
+    }
+}
+
+#[cfg(not(feature = "gemini"))]
+#[tokio::test]
+async fn a_provider_left_out_of_the_build_says_so() {
+    use git_changes_rs::provider::{Gemini, GenerationOptions, Provider, Sampling};
+
+    let prompt = git_changes_rs::prompt::Prompt {
+        system: String::new(),
+        user: "diff --git a/notes.txt b/notes.txt\n".to_string(),
+        max_output_tokens: 256,
+        title_only: false,
+    };
+    let gemini = Gemini::new(Sampling::default()).with_options(GenerationOptions::default());
+    let error = gemini
+        .candidates(&prompt, 1)
+        .await
+        .expect_err("the build has no gemini");
+    assert!(matches!(error, GitChangesError::Config { .. }));
+    assert!(error
+        .to_string()
+        .contains("rebuild with the 'gemini' feature"));
+}
+
+// every command picks its provider the same way, so explain stands in for the others
+#[cfg(not(feature = "gemini"))]
+#[test]
+fn a_command_with_a_provider_left_out_fails_before_sending() {
+    let test = common::TestRepo::with_commit(&[("README.md", "# project\n")]);
+    test.write("notes.txt", "the project notes\n");
+    test.stage("notes.txt");
+    let home = tempfile::tempdir().unwrap();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_git-changes-rs"))
+        .env_clear()
+        .env("HOME", home.path())
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GEMINI_API_KEY", "test-key")
+        // nothing listens here; a request would fail with a network error instead
+        .env("GEMINI_BASE_URL", "http://127.0.0.1:9")
+        .args(["explain", "--yes", "--repo"])
+        .arg(test.path())
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("this build has no gemini support (rebuild with the 'gemini' feature)"),
+        "{}",
+        stderr
+    );
+}
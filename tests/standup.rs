@@ -0,0 +1,190 @@
+// tests/standup.rs
+// standup against a local wiremock server standing in for the model: only your commits inside
+// --since reach it, and a window with none of them sends nothing at all
+#![cfg(feature = "gemini")]
+
+mod common;
+
+use common::TestRepo;
+use git2::{Signature, Time};
+use git_changes_rs::provider::GEMINI_MODEL;
+use serde_json::{json, Value};
+use std::process::Output;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const UPDATE: &str = "**Today**\n- Worked on the notes";
+
+async fn server() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(format!("/models/{}:generateContent", GEMINI_MODEL)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "candidates": [{"content": {"parts": [{"text": UPDATE}], "role": "model"}}]
+        })))
+        .mount(&server)
+        .await;
+    server
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+// commits the index on HEAD as `email`, `age` seconds ago
+fn commit_ago(test: &TestRepo, email: &str, subject: &str, age: i64) {
+    let signature = Signature::new("Test", email, &Time::new(now() - age, 0)).unwrap();
+    let tree = test.repo.index().unwrap().write_tree().unwrap();
+    let tree = test.repo.find_tree(tree).unwrap();
+    let parent = test
+        .repo
+        .head()
+        .ok()
+        .map(|head| head.peel_to_commit().unwrap());
+    let parents: Vec<_> = parent.iter().collect();
+    test.repo
+        .commit(
+            Some("HEAD"),
+            &signature,
+            &signature,
+            subject,
+            &tree,
+            &parents,
+        )
+        .unwrap();
+}
+
+// ten days of history: yours from ten days and from one hour ago, and someone else's in between
+fn repo() -> TestRepo {
+    let test = TestRepo::new();
+    let mut config = test.repo.config().unwrap();
+    config.set_str("user.email", "me@example.com").unwrap();
+    test.write("README.md", "# project\n");
+    test.stage_all();
+    commit_ago(
+        &test,
+        "me@example.com",
+        "chore: start the project",
+        10 * 86_400,
+    );
+    test.write("notes.txt", "the project notes\n");
+    test.stage_all();
+    commit_ago(&test, "them@example.com", "fix: their typo", 2 * 3600);
+    test.write("notes.txt", "the project notes, longer\n");
+    test.stage_all();
+    commit_ago(&test, "me@example.com", "feat: extend the notes", 3600);
+    test
+}
+
+async fn standup(test: &TestRepo, server: &MockServer, args: &[&str]) -> Output {
+    let home = tempfile::tempdir().unwrap();
+    Command::new(env!("CARGO_BIN_EXE_git-changes-rs"))
+        .env_clear()
+        .env("HOME", home.path())
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("GEMINI_API_KEY", "test-key")
+        .env("GEMINI_BASE_URL", server.uri())
+        .args(["standup", "--repo"])
+        .arg(test.path())
+        .args(args)
+        .output()
+        .await
+        .expect("failed to run git-changes-rs")
+}
+
+// the user prompts the server was sent
+async fn prompts(server: &MockServer) -> Vec<String> {
+    server
+        .received_requests()
+        .await
+        .unwrap_or_default()
+        .iter()
+        .map(|request| {
+            let body: Value = serde_json::from_slice(&request.body).unwrap();
+            body["contents"][0]["parts"][0]["text"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn only_your_commits_inside_the_window_are_sent() {
+    let test = repo();
+    let server = server().await;
+
+    let output = standup(&test, &server, &["--since", "3 hours ago"]).await;
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim_end(), UPDATE);
+    let prompts = prompts(&server).await;
+    assert_eq!(prompts.len(), 1);
+    assert!(
+        prompts[0].contains("- feat: extend the notes\n"),
+        "{}",
+        prompts[0]
+    );
+    assert!(!prompts[0].contains("their typo"), "{}", prompts[0]);
+    assert!(!prompts[0].contains("start the project"), "{}", prompts[0]);
+}
+
+#[tokio::test]
+async fn a_wider_window_and_another_author_change_what_is_sent() {
+    let test = repo();
+    let server = server().await;
+
+    let output = standup(&test, &server, &["--since", "2 weeks ago"]).await;
+    assert!(output.status.success(), "{:?}", output);
+    let output = standup(
+        &test,
+        &server,
+        &["--since", "2 weeks ago", "--author", "them@example.com"],
+    )
+    .await;
+    assert!(output.status.success(), "{:?}", output);
+
+    let prompts = prompts(&server).await;
+    assert_eq!(prompts.len(), 2);
+    let start = prompts[0].find("start the project").unwrap();
+    let extend = prompts[0].find("extend the notes").unwrap();
+    assert!(start < extend, "{}", prompts[0]);
+    assert!(!prompts[0].contains("their typo"), "{}", prompts[0]);
+    assert!(prompts[1].contains("- fix: their typo\n"), "{}", prompts[1]);
+    assert!(!prompts[1].contains("notes"), "{}", prompts[1]);
+}
+
+#[tokio::test]
+async fn a_window_without_your_commits_sends_nothing() {
+    let test = repo();
+    let server = server().await;
+
+    let output = standup(
+        &test,
+        &server,
+        &["--since", "1 week ago", "--author", "nobody@example.com"],
+    )
+    .await;
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("no commits of yours since 1 week ago")
+    );
+    assert!(prompts(&server).await.is_empty());
+}
+
+#[tokio::test]
+async fn an_unreadable_since_fails_before_sending() {
+    let test = repo();
+    let server = server().await;
+
+    let output = standup(&test, &server, &["--since", "last tuesday"]).await;
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can't read --since 'last tuesday'"));
+    assert!(prompts(&server).await.is_empty());
+}